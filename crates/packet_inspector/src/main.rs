@@ -1,9 +1,12 @@
 use std::error::Error;
 use std::fmt::Write;
+use std::fs::File;
 use std::io;
-use std::io::ErrorKind;
+use std::io::{BufWriter, ErrorKind};
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::bail;
 use clap::Parser;
@@ -15,6 +18,7 @@ use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::Semaphore;
 use tokio::task::JoinHandle;
 use tracing_subscriber::filter::LevelFilter;
+use valence_protocol::capture::{CaptureWriter, PacketDirection, PacketState};
 use valence_protocol::packets::c2s::handshake::Handshake;
 use valence_protocol::packets::c2s::login::{EncryptionResponse, LoginStart};
 use valence_protocol::packets::c2s::play::C2sPlayPacket;
@@ -50,8 +54,15 @@ struct Cli {
     /// there is no limit.
     #[clap(short, long)]
     max_connections: Option<usize>,
+    /// An optional directory to record the packets of every connection to.
+    /// Each connection is written to its own capture file, which can be
+    /// replayed with Valence's test harness.
+    #[clap(short, long)]
+    capture_dir: Option<PathBuf>,
 }
 
+type Capture = Arc<Mutex<CaptureWriter<BufWriter<File>>>>;
+
 struct State {
     cli: Arc<Cli>,
     enc: PacketEncoder,
@@ -60,6 +71,9 @@ struct State {
     write: OwnedWriteHalf,
     buf: String,
     style: owo_colors::Style,
+    capture: Option<Capture>,
+    direction: PacketDirection,
+    state: PacketState,
 }
 
 impl State {
@@ -80,6 +94,13 @@ impl State {
 
         let pkt: P = self.dec.try_next_packet()?.unwrap();
 
+        if let Some(capture) = &self.capture {
+            capture
+                .lock()
+                .unwrap()
+                .write_packet(self.direction, self.state, &pkt)?;
+        }
+
         self.enc.append_packet(&pkt)?;
 
         let bytes = self.enc.take();
@@ -135,7 +156,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
         let cli = cli.clone();
         tokio::spawn(async move {
-            if let Err(e) = handle_connection(client, cli).await {
+            if let Err(e) = handle_connection(client, remote_client_addr, cli).await {
                 eprintln!("Connection to {remote_client_addr} ended with: {e:#}");
             } else {
                 eprintln!("Connection to {remote_client_addr} ended.");
@@ -147,9 +168,27 @@ async fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-async fn handle_connection(client: TcpStream, cli: Arc<Cli>) -> anyhow::Result<()> {
+async fn handle_connection(
+    client: TcpStream,
+    remote_client_addr: SocketAddr,
+    cli: Arc<Cli>,
+) -> anyhow::Result<()> {
     eprintln!("Connecting to {}", cli.server_addr);
 
+    let capture = match &cli.capture_dir {
+        Some(dir) => {
+            let millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+            let addr = remote_client_addr.to_string().replace([':', '[', ']'], "_");
+            let path = dir.join(format!("{millis}_{addr}.vcap"));
+
+            eprintln!("Capturing packets to {}", path.display());
+
+            let writer = CaptureWriter::new(BufWriter::new(File::create(path)?))?;
+            Some(Arc::new(Mutex::new(writer)))
+        }
+        None => None,
+    };
+
     let server = TcpStream::connect(cli.server_addr).await?;
 
     if let Err(e) = server.set_nodelay(true) {
@@ -167,6 +206,9 @@ async fn handle_connection(client: TcpStream, cli: Arc<Cli>) -> anyhow::Result<(
         write: client_write,
         buf: String::new(),
         style: owo_colors::Style::new().purple(),
+        capture: capture.clone(),
+        direction: PacketDirection::Clientbound,
+        state: PacketState::Handshaking,
     };
 
     let mut c2s = State {
@@ -177,12 +219,18 @@ async fn handle_connection(client: TcpStream, cli: Arc<Cli>) -> anyhow::Result<(
         write: server_write,
         buf: String::new(),
         style: owo_colors::Style::new().green(),
+        capture,
+        direction: PacketDirection::Serverbound,
+        state: PacketState::Handshaking,
     };
 
     let handshake: Handshake = c2s.rw_packet().await?;
 
     match handshake.next_state {
         HandshakeNextState::Status => {
+            c2s.state = PacketState::Status;
+            s2c.state = PacketState::Status;
+
             c2s.rw_packet::<StatusRequest>().await?;
             s2c.rw_packet::<StatusResponse>().await?;
             c2s.rw_packet::<PingRequest>().await?;
@@ -191,6 +239,9 @@ async fn handle_connection(client: TcpStream, cli: Arc<Cli>) -> anyhow::Result<(
             Ok(())
         }
         HandshakeNextState::Login => {
+            c2s.state = PacketState::Login;
+            s2c.state = PacketState::Login;
+
             c2s.rw_packet::<LoginStart>().await?;
 
             match s2c.rw_packet::<S2cLoginPacket>().await? {
@@ -224,6 +275,9 @@ async fn handle_connection(client: TcpStream, cli: Arc<Cli>) -> anyhow::Result<(
                 }
             }
 
+            c2s.state = PacketState::Play;
+            s2c.state = PacketState::Play;
+
             let c2s_fut: JoinHandle<anyhow::Result<()>> = tokio::spawn(async move {
                 loop {
                     c2s.rw_packet::<C2sPlayPacket>().await?;
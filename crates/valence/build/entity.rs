@@ -120,7 +120,7 @@ impl Value {
             Value::String(_) => quote!(Box<str>),
             Value::TextComponent(_) => quote!(Text),
             Value::OptionalTextComponent(_) => quote!(Option<Text>),
-            Value::ItemStack(_) => quote!(()), // TODO
+            Value::ItemStack(_) => quote!(Option<ItemStack>),
             Value::Boolean(_) => quote!(bool),
             Value::Rotation { .. } => quote!(EulerAngle),
             Value::BlockPos(_) => quote!(BlockPos),
@@ -145,6 +145,7 @@ impl Value {
             Value::String(_) => quote!(&str),
             Value::TextComponent(_) => quote!(&Text),
             Value::OptionalTextComponent(_) => quote!(Option<&Text>),
+            Value::ItemStack(_) => quote!(Option<&ItemStack>),
             Value::NbtCompound(_) => quote!(&valence_nbt::Compound),
             _ => self.field_type(),
         }
@@ -155,7 +156,9 @@ impl Value {
             Value::String(_) | Value::TextComponent(_) | Value::NbtCompound(_) => {
                 quote!(&self.#field_name)
             }
-            Value::OptionalTextComponent(_) => quote!(self.#field_name.as_ref()),
+            Value::OptionalTextComponent(_) | Value::ItemStack(_) => {
+                quote!(self.#field_name.as_ref())
+            }
             _ => quote!(self.#field_name),
         }
     }
@@ -172,7 +175,10 @@ impl Value {
                 assert!(t.is_none());
                 quote!(None)
             }
-            Value::ItemStack(_) => quote!(()), // TODO
+            Value::ItemStack(s) => {
+                assert_eq!(s, "1 air");
+                quote!(None)
+            }
             Value::Boolean(b) => quote!(#b),
             Value::Rotation { pitch, yaw, roll } => quote! {
                 EulerAngle {
@@ -232,6 +238,10 @@ impl Value {
     pub fn encodable_expr(&self, self_lvalue: TokenStream) -> TokenStream {
         match self {
             Value::Integer(_) => quote!(VarInt(#self_lvalue)),
+            // Optional ints are sent as a VarInt of the value plus one, or zero if absent.
+            Value::OptionalInt(_) => {
+                quote!(VarInt(#self_lvalue.get().map_or(0, |n| n as i32 + 1)))
+            }
             _ => self_lvalue,
         }
     }
@@ -355,15 +365,18 @@ pub fn build() -> anyhow::Result<TokenStream> {
             }
         });
 
-        let initial_tracked_data_stmts = fields.iter().map(|&field| {
+        let field_encoders = fields.iter().map(|&field| {
             let field_name = ident(&field.name);
+            let field_type = field.default_value.field_type();
             let field_index = field.index;
-            let default_expr = field.default_value.default_expr();
             let type_id = field.default_value.type_id();
-            let encodable = field.default_value.encodable_expr(quote!(self.#field_name));
+            let encoder_name = ident(format!("encode_{}", &field.name));
+            let encodable = field.default_value.encodable_expr(quote!((*#field_name)));
 
             quote! {
-                if self.#field_name != (#default_expr) {
+                /// Appends the tracked data entry of this field with the given
+                /// value to `data`.
+                pub(crate) fn #encoder_name(#field_name: &#field_type, data: &mut Vec<u8>) {
                     data.push(#field_index);
                     VarInt(#type_id).encode(&mut *data).unwrap();
                     #encodable.encode(&mut *data).unwrap();
@@ -371,17 +384,26 @@ pub fn build() -> anyhow::Result<TokenStream> {
             }
         });
 
+        let initial_tracked_data_stmts = fields.iter().map(|&field| {
+            let field_name = ident(&field.name);
+            let default_expr = field.default_value.default_expr();
+            let encoder_name = ident(format!("encode_{}", &field.name));
+
+            quote! {
+                if self.#field_name != (#default_expr) {
+                    Self::#encoder_name(&self.#field_name, data);
+                }
+            }
+        });
+
         let updated_tracked_data_stmts = fields.iter().map(|&field| {
             let field_name = ident(&field.name);
             let field_index = field.index;
-            let type_id = field.default_value.type_id();
-            let encodable = field.default_value.encodable_expr(quote!(self.#field_name));
+            let encoder_name = ident(format!("encode_{}", &field.name));
 
             quote! {
                 if (self.__modified_flags >> #field_index as #modified_flags_type) & 1 == 1 {
-                    data.push(#field_index);
-                    VarInt(#type_id).encode(&mut *data).unwrap();
-                    #encodable.encode(&mut *data).unwrap();
+                    Self::#encoder_name(&self.#field_name, data);
                 }
             }
         });
@@ -415,11 +437,81 @@ pub fn build() -> anyhow::Result<TokenStream> {
                     self.__modified_flags = 0;
                 }
 
+                /// Returns the flags common to every kind of entity as they are
+                /// encoded.
+                pub(crate) fn flags_byte(&self) -> u8 {
+                    self.flags
+                }
+
+                #(#field_encoders)*
+
                 #(#getter_setters)*
             }
         }
     });
 
+    // The bits of the `flags` field common to every kind of entity.
+    let common_flag_accessors = entities["Entity"]
+        .fields
+        .iter()
+        .filter(|f| f.name == "flags")
+        .flat_map(|f| &f.bits)
+        .map(|bit| {
+            let bit_name = ident(&bit.name);
+            let getter_name = ident(format!("get_{}", &bit.name));
+            let setter_name = ident(format!("set_{}", &bit.name));
+            let getter_doc = format!(
+                "Gets the `{}` bit of the flags which are common to every kind of entity.",
+                bit.name
+            );
+            let setter_doc = format!(
+                "Sets the `{}` bit of the flags which are common to every kind of entity.",
+                bit.name
+            );
+
+            quote! {
+                #[doc = #getter_doc]
+                pub fn #bit_name(&self) -> bool {
+                    match self {
+                        #(Self::#concrete_entity_names(e) => e.#getter_name(),)*
+                    }
+                }
+
+                #[doc = #setter_doc]
+                pub fn #setter_name(&mut self, #bit_name: bool) {
+                    match self {
+                        #(Self::#concrete_entity_names(e) => e.#setter_name(#bit_name),)*
+                    }
+                }
+            }
+        })
+        .collect::<TokenStream>();
+
+    // The encoders of the fields common to every kind of entity. Every concrete
+    // entity has the same encoders for these, so the first one is used.
+    let first_concrete_entity = &concrete_entity_names[0];
+    let common_field_encoders = entities["Entity"]
+        .fields
+        .iter()
+        .map(|field| {
+            let field_name = ident(&field.name);
+            let field_type = field.default_value.field_type();
+            let encoder_name = ident(format!("encode_{}", &field.name));
+            let doc = format!(
+                "Appends the tracked data entry of the `{}` field, which is common to every \
+                 kind of entity, with the given value to `data`.",
+                field.name
+            );
+
+            quote! {
+                #[doc = #doc]
+                pub(crate) fn #encoder_name(#field_name: &#field_type, data: &mut Vec<u8>) {
+                    #first_concrete_entity::#encoder_name(#field_name, data)
+                }
+            }
+        })
+        .collect::<TokenStream>();
+
     let translation_key_arms = concrete_entities.iter().map(|(k, v)| {
         let name = ident(k);
         let key = v
@@ -432,6 +524,24 @@ pub fn build() -> anyhow::Result<TokenStream> {
         }
     });
 
+    let from_ident_arms = concrete_entities.iter().map(|(k, v)| {
+        let name = ident(k);
+        let path = v.typ.as_ref().unwrap();
+
+        quote! {
+            #path => Some(Self::#name),
+        }
+    });
+
+    let to_ident_arms = concrete_entities.iter().map(|(k, v)| {
+        let name = ident(k);
+        let path = v.typ.as_ref().unwrap();
+
+        quote! {
+            Self::#name => ::valence_protocol::Ident::new_unchecked(#path, 0),
+        }
+    });
+
     Ok(quote! {
         /// Contains a variant for each concrete entity type.
         #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -445,6 +555,26 @@ pub fn build() -> anyhow::Result<TokenStream> {
                     #(#translation_key_arms)*
                 }
             }
+
+            /// Gets the entity kind from its resource identifier, such as
+            /// `minecraft:zombie`.
+            pub fn from_ident(ident: ::valence_protocol::Ident<&str>) -> Option<Self> {
+                if ident.namespace() != "minecraft" {
+                    return None;
+                }
+
+                match ident.path() {
+                    #(#from_ident_arms)*
+                    _ => None,
+                }
+            }
+
+            /// Gets the resource identifier of this entity kind.
+            pub fn ident(self) -> ::valence_protocol::Ident<&'static str> {
+                match self {
+                    #(#to_ident_arms)*
+                }
+            }
         }
 
         pub enum TrackedData {
@@ -493,6 +623,42 @@ pub fn build() -> anyhow::Result<TokenStream> {
                     #(Self::#concrete_entity_names(e) => e.clear_modifications(),)*
                 }
             }
+
+            /// Gets the value of the `name_visible` field which is common to
+            /// every kind of entity.
+            pub fn name_visible(&self) -> bool {
+                match self {
+                    #(Self::#concrete_entity_names(e) => e.get_name_visible(),)*
+                }
+            }
+
+            /// Gets the value of the `pose` field which is common to every kind
+            /// of entity.
+            pub fn pose(&self) -> Pose {
+                match self {
+                    #(Self::#concrete_entity_names(e) => e.get_pose(),)*
+                }
+            }
+
+            /// Sets the value of the `pose` field which is common to every kind
+            /// of entity.
+            pub fn set_pose(&mut self, pose: Pose) {
+                match self {
+                    #(Self::#concrete_entity_names(e) => e.set_pose(pose),)*
+                }
+            }
+
+            /// Returns the flags common to every kind of entity as they are
+            /// encoded.
+            pub(crate) fn flags_byte(&self) -> u8 {
+                match self {
+                    #(Self::#concrete_entity_names(e) => e.flags_byte(),)*
+                }
+            }
+
+            #common_flag_accessors
+
+            #common_field_encoders
         }
 
         #(#concrete_entity_structs)*
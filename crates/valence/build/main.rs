@@ -10,6 +10,9 @@ mod entity_event;
 
 pub fn main() -> anyhow::Result<()> {
     println!("cargo:rerun-if-changed=../../extracted/");
+    // Set with `RUSTFLAGS="--cfg tokio_unstable"` to use the `tokio-console`
+    // feature.
+    println!("cargo:rustc-check-cfg=cfg(tokio_unstable)");
 
     let generators = [
         (entity::build as fn() -> _, "entity.rs"),
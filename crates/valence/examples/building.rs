@@ -1,3 +1,4 @@
+use valence::block_placement::compute_placement;
 use valence::client::despawn_disconnected_clients;
 use valence::client::event::{
     default_event_handler, FinishDigging, StartDigging, StartSneaking, UseItemOnBlock,
@@ -134,6 +135,11 @@ fn place_blocks(
             continue;
         };
 
+        let Some(placement) = compute_placement(&instance, client, event, block_kind) else {
+            // the block can't be placed here
+            continue;
+        };
+
         if client.game_mode() == GameMode::Survival {
             // check if the player has the item in their inventory and remove
             // it.
@@ -146,7 +152,6 @@ fn place_blocks(
             };
             inventory.replace_slot(slot_id, slot);
         }
-        let real_pos = event.position.get_in_direction(event.face);
-        instance.set_block(real_pos, block_kind.to_state());
+        placement.apply(&mut instance);
     }
 }
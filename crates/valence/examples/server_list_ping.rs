@@ -1,5 +1,3 @@
-use std::net::SocketAddr;
-
 use valence::prelude::*;
 
 pub fn main() {
@@ -15,8 +13,7 @@ impl AsyncCallbacks for MyCallbacks {
     async fn server_list_ping(
         &self,
         _shared: &SharedServer,
-        remote_addr: SocketAddr,
-        _protocol_version: i32,
+        ctx: &ConnectionContext,
     ) -> ServerListPing {
         ServerListPing::Respond {
             online_players: 42,
@@ -26,12 +23,17 @@ impl AsyncCallbacks for MyCallbacks {
                 id: Uuid::from_u128(12345),
             }],
             description: "Your IP address is ".into_text()
-                + remote_addr.to_string().color(Color::GOLD),
+                + ctx.remote_addr.to_string().color(Color::GOLD),
             favicon_png: include_bytes!("../../../assets/logo-64x64.png"),
         }
     }
 
-    async fn login(&self, _shared: &SharedServer, _info: &NewClientInfo) -> Result<(), Text> {
+    async fn login(
+        &self,
+        _shared: &SharedServer,
+        _ctx: &ConnectionContext,
+        _info: &mut NewClientInfo,
+    ) -> Result<(), Text> {
         Err("You are not meant to join this example".color(Color::RED))
     }
 }
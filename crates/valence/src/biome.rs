@@ -3,6 +3,7 @@
 use std::collections::HashSet;
 
 use anyhow::ensure;
+use serde::Serialize;
 use tracing::warn;
 use valence_nbt::{compound, Compound};
 use valence_protocol::ident;
@@ -25,7 +26,7 @@ pub struct BiomeId(pub(crate) u16);
 /// [`ServerPlugin::with_biomes`]
 ///
 /// [`ServerPlugin::with_biomes`]: crate::config::ServerPlugin::with_biomes
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Biome {
     /// The unique name for this biome. The name can be
     /// seen in the F3 debug menu.
@@ -187,7 +188,8 @@ impl Default for Biome {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BiomePrecipitation {
     #[default]
     Rain,
@@ -197,7 +199,8 @@ pub enum BiomePrecipitation {
 
 /// Minecraft handles grass colors for swamps and dark oak forests in a special
 /// way.
-#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BiomeGrassColorModifier {
     Swamp,
     DarkForest,
@@ -205,7 +208,7 @@ pub enum BiomeGrassColorModifier {
     None,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct BiomeMusic {
     pub replace_current_music: bool,
     pub sound: Ident<String>,
@@ -213,13 +216,13 @@ pub struct BiomeMusic {
     pub max_delay: i32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct BiomeAdditionsSound {
     pub sound: Ident<String>,
     pub tick_chance: f64,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct BiomeMoodSound {
     pub sound: Ident<String>,
     pub tick_delay: i32,
@@ -227,7 +230,7 @@ pub struct BiomeMoodSound {
     pub block_search_extent: i32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct BiomeParticle {
     pub probability: f32,
     pub kind: Ident<String>,
@@ -0,0 +1,546 @@
+//! Vanilla-like block breaking.
+//!
+//! The [`BlockBreakingPlugin`] turns the raw digging events sent by clients
+//! into a single [`BlockBroken`] event per block. Break times are computed
+//! from the hardness of the block and the tool held by the client, other
+//! viewers are shown the block cracking as it is mined, and clients claiming
+//! to finish breaking a block too early are ignored.
+//!
+//! Valence does not remove the block for you. Handle [`BlockBroken`] events to
+//! decide what happens to the block.
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashMap;
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::packets::s2c::play::SetBlockDestroyStage;
+use valence_protocol::types::GameMode;
+use valence_protocol::{BlockKind, BlockPos, BlockState, ItemKind, ItemStack, VarInt};
+
+use crate::client::event::{CancelDigging, FinishDigging, StartDigging};
+use crate::client::Client;
+use crate::config::DEFAULT_TPS;
+use crate::entity::McEntity;
+use crate::instance::Instance;
+use crate::inventory::Inventory;
+use crate::server::Server;
+use crate::view::ChunkPos;
+
+/// Clients may finish breaking a block this fraction of the expected time
+/// early. This accounts for latency and matches the leniency of the vanilla
+/// server.
+const FINISH_TOLERANCE: f32 = 0.7;
+
+/// Adds the [`BlockBroken`] event and the systems for tracking the digging
+/// progress of clients.
+pub struct BlockBreakingPlugin;
+
+impl Plugin for BlockBreakingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BlockBroken>()
+            .add_system_to_stage(CoreStage::Update, update_block_breaking);
+    }
+}
+
+/// Sent when a client has broken a block.
+///
+/// In creative mode, blocks are broken as soon as the client starts digging.
+/// Otherwise, the client must have spent enough time digging the block with
+/// its held item.
+#[derive(Clone, Debug)]
+pub struct BlockBroken {
+    pub client: Entity,
+    pub position: BlockPos,
+    /// The state of the block at the time it was broken.
+    pub state: BlockState,
+}
+
+/// The kinds of tools which mine some blocks faster than others.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ToolKind {
+    Pickaxe,
+    Axe,
+    Shovel,
+    Hoe,
+    Sword,
+    Shears,
+}
+
+impl ToolKind {
+    /// Returns the kind of tool `item` is along with its mining speed, or
+    /// `None` if `item` is not a tool.
+    pub fn from_item(item: ItemKind) -> Option<(Self, f32)> {
+        let name = item.to_str();
+
+        if name == "shears" {
+            return Some((Self::Shears, 2.0));
+        }
+
+        let (material, kind) = name.rsplit_once('_')?;
+
+        let kind = match kind {
+            "pickaxe" => Self::Pickaxe,
+            "axe" => Self::Axe,
+            "shovel" => Self::Shovel,
+            "hoe" => Self::Hoe,
+            "sword" => Self::Sword,
+            _ => return None,
+        };
+
+        let speed = match material {
+            "wooden" => 2.0,
+            "stone" => 4.0,
+            "iron" => 6.0,
+            "diamond" => 8.0,
+            "netherite" => 9.0,
+            "golden" => 12.0,
+            _ => return None,
+        };
+
+        Some((kind, speed))
+    }
+}
+
+/// Returns the vanilla hardness of a block, or `None` if the block cannot be
+/// broken outside of creative mode.
+pub fn block_hardness(kind: BlockKind) -> Option<f32> {
+    let hardness = kind.hardness();
+    (hardness >= 0.0).then_some(hardness)
+}
+
+/// Returns the vanilla blast resistance of a block, which determines how much
+/// of an explosion the block absorbs. Waterlogged blocks are as resistant as
+/// the water inside them.
+pub fn blast_resistance(state: BlockState) -> f32 {
+    let resistance = state.to_kind().blast_resistance();
+
+    if state.get(PropName::Waterlogged) == Some(PropValue::True) {
+        resistance.max(BlockKind::Water.blast_resistance())
+    } else {
+        resistance
+    }
+}
+
+/// Returns the kind of tool which mines `kind` the fastest, if any.
+pub fn preferred_tool(kind: BlockKind) -> Option<ToolKind> {
+    let name = kind.to_str();
+
+    if name == "cobweb" {
+        Some(ToolKind::Sword)
+    } else if name.ends_with("_leaves") || name.ends_with("_wool") {
+        Some(ToolKind::Shears)
+    } else if name.ends_with("_log")
+        || name.ends_with("_wood")
+        || name.ends_with("_stem")
+        || name.ends_with("_hyphae")
+        || name.ends_with("_planks")
+        || name.ends_with("_fence")
+        || name.ends_with("_fence_gate")
+        || name.ends_with("_sign")
+        || matches!(name, "chest" | "trapped_chest" | "barrel" | "crafting_table" | "bookshelf")
+    {
+        Some(ToolKind::Axe)
+    } else if matches!(
+        name,
+        "dirt"
+            | "grass_block"
+            | "farmland"
+            | "dirt_path"
+            | "podzol"
+            | "mycelium"
+            | "clay"
+            | "gravel"
+            | "coarse_dirt"
+            | "rooted_dirt"
+            | "sand"
+            | "red_sand"
+            | "soul_sand"
+            | "soul_soil"
+            | "snow"
+            | "snow_block"
+            | "powder_snow"
+    ) || name.ends_with("_concrete_powder")
+    {
+        Some(ToolKind::Shovel)
+    } else if matches!(name, "sponge" | "wet_sponge" | "hay_block" | "target") {
+        Some(ToolKind::Hoe)
+    } else if requires_pickaxe(name) {
+        Some(ToolKind::Pickaxe)
+    } else {
+        None
+    }
+}
+
+/// Blocks which drop nothing unless mined with a pickaxe. These also take
+/// much longer to break without one.
+fn requires_pickaxe(name: &str) -> bool {
+    matches!(
+        name,
+        "obsidian"
+            | "crying_obsidian"
+            | "respawn_anchor"
+            | "netherite_block"
+            | "ancient_debris"
+            | "ender_chest"
+            | "anvil"
+            | "chipped_anvil"
+            | "damaged_anvil"
+            | "iron_block"
+            | "diamond_block"
+            | "emerald_block"
+            | "gold_block"
+            | "lapis_block"
+            | "iron_door"
+            | "iron_trapdoor"
+            | "iron_bars"
+            | "spawner"
+            | "enchanting_table"
+            | "hopper"
+            | "furnace"
+            | "blast_furnace"
+            | "smoker"
+            | "dispenser"
+            | "dropper"
+            | "end_stone"
+            | "cobblestone"
+            | "mossy_cobblestone"
+            | "bricks"
+            | "nether_bricks"
+            | "cauldron"
+            | "stone"
+            | "andesite"
+            | "diorite"
+            | "granite"
+            | "blackstone"
+            | "netherrack"
+            | "terracotta"
+            | "magma_block"
+    ) || name.ends_with("_ore")
+        || name.starts_with("deepslate")
+        || name.starts_with("polished_")
+        || name.ends_with("_bricks")
+        || name.ends_with("_concrete")
+        || name.ends_with("_terracotta")
+        || name.ends_with("stone")
+        || name.contains("copper")
+        || ((name.ends_with("_stairs") || name.ends_with("_slab") || name.ends_with("_wall"))
+            && !name.contains("oak")
+            && !name.contains("spruce")
+            && !name.contains("birch")
+            && !name.contains("jungle")
+            && !name.contains("acacia")
+            && !name.contains("mangrove")
+            && !name.contains("crimson")
+            && !name.contains("warped")
+            && !name.contains("bamboo"))
+}
+
+/// Computes the number of ticks needed to break a block in survival mode with
+/// the held item `tool`, using the [`block_hardness`] of the block.
+///
+/// Returns `None` if the block is unbreakable. `Some(0)` indicates the block
+/// is broken instantly.
+pub fn break_ticks(state: BlockState, tool: Option<&ItemStack>, on_ground: bool) -> Option<u32> {
+    let hardness = block_hardness(state.to_kind())?;
+    Some(break_ticks_with_hardness(
+        state.to_kind(),
+        hardness,
+        tool,
+        on_ground,
+    ))
+}
+
+/// Like [`break_ticks`], but allows specifying the hardness of the block.
+pub fn break_ticks_with_hardness(
+    kind: BlockKind,
+    hardness: f32,
+    tool: Option<&ItemStack>,
+    on_ground: bool,
+) -> u32 {
+    if hardness <= 0.0 {
+        return 0;
+    }
+
+    let preferred = preferred_tool(kind);
+
+    let tool = tool.and_then(|stack| ToolKind::from_item(stack.item));
+
+    let (speed, correct_tool) = match (tool, preferred) {
+        (Some((ToolKind::Sword, _)), Some(ToolKind::Sword)) => (15.0, true),
+        (Some((ToolKind::Shears, _)), Some(ToolKind::Shears)) => (15.0, true),
+        (Some((tool, speed)), Some(preferred)) if tool == preferred => (speed, true),
+        (Some((ToolKind::Sword, _)), _) => (1.5, false),
+        _ => (1.0, false),
+    };
+
+    let can_harvest = correct_tool || !requires_pickaxe(kind.to_str());
+
+    let mut damage = speed / hardness / if can_harvest { 30.0 } else { 100.0 };
+
+    if !on_ground {
+        damage /= 5.0;
+    }
+
+    if damage > 1.0 {
+        0
+    } else {
+        (1.0 / damage).ceil() as u32
+    }
+}
+
+/// Converts a number of server ticks to the number of ticks clients run in the
+/// same time. Break times are counted in client ticks, and clients always run
+/// at the default tick rate whatever the tick rate of the server is.
+fn to_client_ticks(server_ticks: i64, tps: i64) -> f32 {
+    server_ticks.max(0) as f32 * DEFAULT_TPS as f32 / tps as f32
+}
+
+/// The state of a client that is in the process of breaking a block.
+struct Digging {
+    position: BlockPos,
+    start_tick: i64,
+    ticks: u32,
+    /// The last destroy stage sent to other clients.
+    stage: u8,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_block_breaking(
+    server: Res<Server>,
+    mut clients: Query<(Entity, &mut Client, &Inventory, Option<&McEntity>)>,
+    instances: Query<&Instance>,
+    mut start_digging: EventReader<StartDigging>,
+    mut cancel_digging: EventReader<CancelDigging>,
+    mut finish_digging: EventReader<FinishDigging>,
+    mut block_broken: EventWriter<BlockBroken>,
+    mut digging: Local<FxHashMap<Entity, Digging>>,
+) {
+    let current_tick = server.current_tick();
+    let tps = server.tps();
+    // Destroy stages to show to the clients in view of the block, other than
+    // the client breaking it, which shows the cracks on its own.
+    let mut stages = vec![];
+
+    for event in start_digging.iter() {
+        let Ok((_, client, inventory, _)) = clients.get(event.client) else {
+            continue
+        };
+
+        let Ok(instance) = instances.get(client.instance()) else {
+            continue
+        };
+
+        let Some(state) = instance.block(event.position).map(|b| b.state()) else {
+            continue
+        };
+
+        let ticks = match client.game_mode() {
+            GameMode::Creative => Some(0),
+            GameMode::Survival | GameMode::Adventure => break_ticks(
+                state,
+                inventory.slot(client.held_item_slot()),
+                client.on_ground(),
+            ),
+            GameMode::Spectator => None,
+        };
+
+        match ticks {
+            Some(0) => {
+                digging.remove(&event.client);
+                block_broken.send(BlockBroken {
+                    client: event.client,
+                    position: event.position,
+                    state,
+                });
+            }
+            Some(ticks) => {
+                digging.insert(
+                    event.client,
+                    Digging {
+                        position: event.position,
+                        start_tick: current_tick,
+                        ticks,
+                        stage: 0,
+                    },
+                );
+            }
+            None => {
+                digging.remove(&event.client);
+            }
+        }
+    }
+
+    for event in cancel_digging.iter() {
+        if let Some(dig) = digging.remove(&event.client) {
+            if let Ok((_, client, _, Some(entity))) = clients.get(event.client) {
+                stages.push(destroy_stage(
+                    event.client,
+                    client,
+                    entity.protocol_id(),
+                    dig.position,
+                    10,
+                ));
+            }
+        }
+    }
+
+    for event in finish_digging.iter() {
+        let Some(dig) = digging.remove(&event.client) else {
+            continue
+        };
+
+        let Ok((_, client, _, entity)) = clients.get(event.client) else {
+            continue
+        };
+
+        if let Some(entity) = entity {
+            stages.push(destroy_stage(
+                event.client,
+                client,
+                entity.protocol_id(),
+                dig.position,
+                10,
+            ));
+        }
+
+        let elapsed = to_client_ticks(current_tick - dig.start_tick, tps);
+
+        // If the client finished too early, nothing happens. The client will
+        // restore the block on its own once the block change is acknowledged.
+        if dig.position != event.position || elapsed < dig.ticks as f32 * FINISH_TOLERANCE {
+            continue;
+        }
+
+        let Ok(instance) = instances.get(client.instance()) else {
+            continue
+        };
+
+        if let Some(block) = instance.block(event.position) {
+            block_broken.send(BlockBroken {
+                client: event.client,
+                position: event.position,
+                state: block.state(),
+            });
+        }
+    }
+
+    // Show the block cracking to other clients.
+    digging.retain(|&client_id, dig| {
+        let Ok((_, client, _, entity)) = clients.get(client_id) else {
+            // Client no longer exists.
+            return false;
+        };
+
+        let Some(entity) = entity else {
+            return true;
+        };
+
+        let elapsed = to_client_ticks(current_tick - dig.start_tick, tps);
+        let stage = (elapsed * 10.0 / dig.ticks as f32).min(9.0) as u8;
+
+        if stage != dig.stage {
+            dig.stage = stage;
+
+            stages.push(destroy_stage(
+                client_id,
+                client,
+                entity.protocol_id(),
+                dig.position,
+                stage,
+            ));
+        }
+
+        true
+    });
+
+    if stages.is_empty() {
+        return;
+    }
+
+    for (client_id, mut client, _, _) in &mut clients {
+        for (breaker, instance, pkt) in &stages {
+            if client_id != *breaker
+                && client.instance() == *instance
+                && client
+                    .view()
+                    .contains(ChunkPos::from_block_pos(pkt.position))
+            {
+                client.write_packet(pkt);
+            }
+        }
+    }
+}
+
+/// Returns the destroy stage packet of the block `client_id` is breaking
+/// along with the breaker and its instance. A stage of 10 clears the cracks.
+fn destroy_stage(
+    client_id: Entity,
+    client: &Client,
+    entity_id: i32,
+    position: BlockPos,
+    destroy_stage: u8,
+) -> (Entity, Entity, SetBlockDestroyStage) {
+    (
+        client_id,
+        client.instance(),
+        SetBlockDestroyStage {
+            entity_id: VarInt(entity_id),
+            position,
+            destroy_stage,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn break_times() {
+        let pickaxe = ItemStack::new(ItemKind::DiamondPickaxe, 1, None);
+        let shovel = ItemStack::new(ItemKind::WoodenShovel, 1, None);
+
+        // Stone takes 7.5 seconds by hand and 0.3 seconds with a diamond pickaxe.
+        assert_eq!(break_ticks(BlockState::STONE, None, true), Some(150));
+        assert_eq!(break_ticks(BlockState::STONE, Some(&pickaxe), true), Some(6));
+
+        // Dirt takes 0.75 seconds by hand and 0.4 seconds with a wooden shovel.
+        assert_eq!(break_ticks(BlockState::DIRT, None, true), Some(15));
+        assert_eq!(break_ticks(BlockState::DIRT, Some(&shovel), true), Some(8));
+
+        // Mining in the air is five times slower.
+        assert_eq!(break_ticks(BlockState::DIRT, None, false), Some(75));
+
+        // Obsidian takes 9.4 seconds with a diamond pickaxe.
+        assert_eq!(
+            break_ticks(BlockState::OBSIDIAN, Some(&pickaxe), true),
+            Some(188)
+        );
+
+        assert_eq!(break_ticks(BlockState::BEDROCK, Some(&pickaxe), true), None);
+        assert_eq!(break_ticks(BlockState::TORCH, None, true), Some(0));
+    }
+
+    #[test]
+    fn client_ticks_at_other_tick_rates() {
+        assert_eq!(to_client_ticks(20, DEFAULT_TPS), 20.0);
+        assert_eq!(to_client_ticks(10, 10), 20.0);
+        assert_eq!(to_client_ticks(40, 40), 20.0);
+        assert_eq!(to_client_ticks(-5, DEFAULT_TPS), 0.0);
+    }
+
+    #[test]
+    fn vanilla_blast_resistance() {
+        assert_eq!(blast_resistance(BlockState::AIR), 0.0);
+        assert_eq!(blast_resistance(BlockState::WATER), 100.0);
+        assert_eq!(blast_resistance(BlockState::STONE), 6.0);
+        assert_eq!(blast_resistance(BlockState::OAK_PLANKS), 3.0);
+        assert_eq!(blast_resistance(BlockState::END_STONE), 9.0);
+        assert_eq!(blast_resistance(BlockState::OBSIDIAN), 1200.0);
+        assert_eq!(blast_resistance(BlockState::BEDROCK), 3_600_000.0);
+
+        let waterlogged = BlockState::OAK_SLAB.set(PropName::Waterlogged, PropValue::True);
+        assert_eq!(blast_resistance(BlockState::OAK_SLAB), 3.0);
+        assert_eq!(blast_resistance(waterlogged), 100.0);
+    }
+}
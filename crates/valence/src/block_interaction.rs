@@ -0,0 +1,379 @@
+//! Vanilla-like interaction with doors, trapdoors, fence gates, buttons and
+//! levers.
+//!
+//! The [`BlockInteractionPlugin`] toggles these blocks when clients right
+//! click them, updating the block state and playing the same sounds as the
+//! vanilla server. Buttons are released automatically after a delay. A
+//! [`BlockInteracted`] event is sent for every block changed this way so that
+//! other systems can react to it.
+//!
+//! Iron doors and iron trapdoors can only be opened with redstone and are left
+//! unchanged.
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::types::{GameMode, Hand, SoundCategory};
+use valence_protocol::{BlockFace, BlockPos, BlockState, Sound};
+
+use crate::client::event::UseItemOnBlock;
+use crate::client::Client;
+use crate::entity::{McEntity, TrackedData};
+use crate::instance::Instance;
+use crate::inventory::Inventory;
+use crate::server::Server;
+
+/// Adds the [`BlockInteracted`] event and the systems for toggling blocks
+/// clicked by clients.
+pub struct BlockInteractionPlugin;
+
+impl Plugin for BlockInteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BlockInteracted>()
+            .init_resource::<PressedButtons>()
+            .add_system_to_stage(CoreStage::Update, interact_with_blocks)
+            .add_system_to_stage(
+                CoreStage::Update,
+                release_buttons.after(interact_with_blocks),
+            );
+    }
+}
+
+/// Sent when the state of a block was changed by the
+/// [`BlockInteractionPlugin`].
+///
+/// For doors, only the half which was clicked is reported.
+#[derive(Clone, Debug)]
+pub struct BlockInteracted {
+    /// The client who interacted with the block, or `None` if a button was
+    /// released.
+    pub client: Option<Entity>,
+    /// The instance containing the block.
+    pub instance: Entity,
+    pub position: BlockPos,
+    /// The state of the block before the interaction.
+    pub old_state: BlockState,
+    /// The state of the block after the interaction.
+    pub new_state: BlockState,
+}
+
+/// Buttons which are waiting to be released.
+#[derive(Resource, Default)]
+struct PressedButtons(Vec<PressedButton>);
+
+struct PressedButton {
+    instance: Entity,
+    position: BlockPos,
+    release_tick: i64,
+}
+
+/// Returns the number of ticks a button stays pressed for, or `None` if `state`
+/// is not a button.
+fn button_press_ticks(state: BlockState) -> Option<i64> {
+    let name = state.to_kind().to_str();
+
+    if !name.ends_with("_button") {
+        None
+    } else if name.starts_with("stone") || name.starts_with("polished_blackstone") {
+        Some(20)
+    } else {
+        Some(30)
+    }
+}
+
+/// Returns the sound played when opening (`open == true`) or closing the
+/// given block.
+fn toggle_sound(state: BlockState, open: bool) -> Option<Sound> {
+    let name = state.to_kind().to_str();
+
+    let nether = name.starts_with("crimson") || name.starts_with("warped");
+    let bamboo = name.starts_with("bamboo");
+
+    let sound = if name.ends_with("_trapdoor") {
+        match (nether, bamboo, open) {
+            (true, _, true) => Sound::BlockNetherWoodTrapdoorOpen,
+            (true, _, false) => Sound::BlockNetherWoodTrapdoorClose,
+            (_, true, true) => Sound::BlockBambooWoodTrapdoorOpen,
+            (_, true, false) => Sound::BlockBambooWoodTrapdoorClose,
+            (_, _, true) => Sound::BlockWoodenTrapdoorOpen,
+            (_, _, false) => Sound::BlockWoodenTrapdoorClose,
+        }
+    } else if name.ends_with("_door") {
+        match (nether, bamboo, open) {
+            (true, _, true) => Sound::BlockNetherWoodDoorOpen,
+            (true, _, false) => Sound::BlockNetherWoodDoorClose,
+            (_, true, true) => Sound::BlockBambooWoodDoorOpen,
+            (_, true, false) => Sound::BlockBambooWoodDoorClose,
+            (_, _, true) => Sound::BlockWoodenDoorOpen,
+            (_, _, false) => Sound::BlockWoodenDoorClose,
+        }
+    } else if name.ends_with("_fence_gate") {
+        match (nether, bamboo, open) {
+            (true, _, true) => Sound::BlockNetherWoodFenceGateOpen,
+            (true, _, false) => Sound::BlockNetherWoodFenceGateClose,
+            (_, true, true) => Sound::BlockBambooWoodFenceGateOpen,
+            (_, true, false) => Sound::BlockBambooWoodFenceGateClose,
+            (_, _, true) => Sound::BlockFenceGateOpen,
+            (_, _, false) => Sound::BlockFenceGateClose,
+        }
+    } else if name.ends_with("_button") {
+        let stone = name.starts_with("stone") || name.starts_with("polished_blackstone");
+
+        match (nether, bamboo, stone, open) {
+            (true, _, _, true) => Sound::BlockNetherWoodButtonClickOn,
+            (true, _, _, false) => Sound::BlockNetherWoodButtonClickOff,
+            (_, true, _, true) => Sound::BlockBambooWoodButtonClickOn,
+            (_, true, _, false) => Sound::BlockBambooWoodButtonClickOff,
+            (_, _, true, true) => Sound::BlockStoneButtonClickOn,
+            (_, _, true, false) => Sound::BlockStoneButtonClickOff,
+            (_, _, _, true) => Sound::BlockWoodenButtonClickOn,
+            (_, _, _, false) => Sound::BlockWoodenButtonClickOff,
+        }
+    } else if name == "lever" {
+        Sound::BlockLeverClick
+    } else {
+        return None;
+    };
+
+    Some(sound)
+}
+
+fn play_toggle_sound(instance: &mut Instance, pos: BlockPos, state: BlockState, open: bool) {
+    let Some(sound) = toggle_sound(state, open) else {
+        return
+    };
+
+    let name = state.to_kind().to_str();
+
+    let (volume, pitch) = if name == "lever" || name.ends_with("_button") {
+        (0.3, if open { 0.6 } else { 0.5 })
+    } else {
+        (1.0, rand::random::<f32>() * 0.1 + 0.9)
+    };
+
+    let center = [pos.x as f64 + 0.5, pos.y as f64 + 0.5, pos.z as f64 + 0.5];
+
+    instance.play_sound(sound, SoundCategory::Block, center, volume, pitch);
+}
+
+/// Computes the state of a block after being clicked, or `None` if the block
+/// is not interactable.
+///
+/// `player_facing` is the horizontal direction the player is facing, which
+/// determines the direction fence gates open in.
+fn interacted_state(state: BlockState, player_facing: PropValue) -> Option<BlockState> {
+    let name = state.to_kind().to_str();
+
+    if name == "iron_door" || name == "iron_trapdoor" {
+        return None;
+    }
+
+    if name.ends_with("_door") || name.ends_with("_trapdoor") {
+        let open = state.get(PropName::Open)?.to_bool()?;
+        return Some(state.set(PropName::Open, PropValue::from_bool(!open)));
+    }
+
+    if name.ends_with("_fence_gate") {
+        let open = state.get(PropName::Open)?.to_bool()?;
+        let mut new_state = state.set(PropName::Open, PropValue::from_bool(!open));
+
+        // Fence gates always open away from the player.
+        if !open && state.get(PropName::Facing) == Some(opposite(player_facing)) {
+            new_state = new_state.set(PropName::Facing, player_facing);
+        }
+
+        return Some(new_state);
+    }
+
+    if name.ends_with("_button") {
+        // Pressing an already pressed button does nothing.
+        if state.get(PropName::Powered)?.to_bool()? {
+            return None;
+        }
+
+        return Some(state.set(PropName::Powered, PropValue::True));
+    }
+
+    if name == "lever" {
+        let powered = state.get(PropName::Powered)?.to_bool()?;
+        return Some(state.set(PropName::Powered, PropValue::from_bool(!powered)));
+    }
+
+    None
+}
+
+fn opposite(facing: PropValue) -> PropValue {
+    match facing {
+        PropValue::North => PropValue::South,
+        PropValue::South => PropValue::North,
+        PropValue::West => PropValue::East,
+        PropValue::East => PropValue::West,
+        other => other,
+    }
+}
+
+fn horizontal_facing(yaw: f32) -> PropValue {
+    match ((yaw / 90.0 + 0.5).floor() as i32).rem_euclid(4) {
+        0 => PropValue::South,
+        1 => PropValue::West,
+        2 => PropValue::North,
+        _ => PropValue::East,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn interact_with_blocks(
+    server: Res<Server>,
+    clients: Query<(&Client, &Inventory, Option<&McEntity>)>,
+    mut instances: Query<&mut Instance>,
+    mut events: EventReader<UseItemOnBlock>,
+    mut interacted: EventWriter<BlockInteracted>,
+    mut pressed: ResMut<PressedButtons>,
+) {
+    for event in events.iter() {
+        if event.hand != Hand::Main {
+            continue;
+        }
+
+        let Ok((client, inventory, entity)) = clients.get(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator {
+            continue;
+        }
+
+        // Sneaking players place the held item instead of interacting.
+        let sneaking = matches!(
+            entity.map(|e| e.data()),
+            Some(TrackedData::Player(player)) if player.get_sneaking()
+        );
+
+        if sneaking && inventory.slot(client.held_item_slot()).is_some() {
+            continue;
+        }
+
+        let Ok(mut instance) = instances.get_mut(client.instance()) else {
+            continue
+        };
+
+        let Some(old_state) = instance.block(event.position).map(|b| b.state()) else {
+            continue
+        };
+
+        let Some(new_state) = interacted_state(old_state, horizontal_facing(client.yaw())) else {
+            continue
+        };
+
+        instance.set_block(event.position, new_state);
+
+        // Keep both halves of doors in sync.
+        if let Some(half) = old_state.get(PropName::Half) {
+            if old_state.to_kind().to_str().ends_with("_door") {
+                let other_pos = match half {
+                    PropValue::Lower => event.position.get_in_direction(BlockFace::Top),
+                    _ => event.position.get_in_direction(BlockFace::Bottom),
+                };
+
+                if let Some(other) = instance.block(other_pos).map(|b| b.state()) {
+                    if other.to_kind() == old_state.to_kind() {
+                        let open = new_state.get(PropName::Open).unwrap_or(PropValue::False);
+                        instance.set_block(other_pos, other.set(PropName::Open, open));
+                    }
+                }
+            }
+        }
+
+        let open = new_state
+            .get(PropName::Open)
+            .or_else(|| new_state.get(PropName::Powered))
+            .and_then(|v| v.to_bool())
+            .unwrap_or(false);
+
+        play_toggle_sound(&mut instance, event.position, new_state, open);
+
+        if let Some(ticks) = button_press_ticks(new_state) {
+            pressed.0.push(PressedButton {
+                instance: client.instance(),
+                position: event.position,
+                release_tick: server.current_tick() + ticks,
+            });
+        }
+
+        interacted.send(BlockInteracted {
+            client: Some(event.client),
+            instance: client.instance(),
+            position: event.position,
+            old_state,
+            new_state,
+        });
+    }
+}
+
+fn release_buttons(
+    server: Res<Server>,
+    mut instances: Query<&mut Instance>,
+    mut interacted: EventWriter<BlockInteracted>,
+    mut pressed: ResMut<PressedButtons>,
+) {
+    let current_tick = server.current_tick();
+
+    pressed.0.retain(|button| {
+        if button.release_tick > current_tick {
+            return true;
+        }
+
+        let Ok(mut instance) = instances.get_mut(button.instance) else {
+            return false
+        };
+
+        let Some(old_state) = instance.block(button.position).map(|b| b.state()) else {
+            return false
+        };
+
+        // The button may have been removed in the meantime.
+        if button_press_ticks(old_state).is_none()
+            || old_state.get(PropName::Powered) != Some(PropValue::True)
+        {
+            return false;
+        }
+
+        let new_state = old_state.set(PropName::Powered, PropValue::False);
+        instance.set_block(button.position, new_state);
+        play_toggle_sound(&mut instance, button.position, new_state, false);
+
+        interacted.send(BlockInteracted {
+            client: None,
+            instance: button.instance,
+            position: button.position,
+            old_state,
+            new_state,
+        });
+
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggling() {
+        let door = BlockState::OAK_DOOR;
+        let opened = interacted_state(door, PropValue::North).unwrap();
+        assert_eq!(opened.get(PropName::Open), Some(PropValue::True));
+        assert_eq!(interacted_state(opened, PropValue::North), Some(door));
+
+        assert_eq!(interacted_state(BlockState::IRON_DOOR, PropValue::North), None);
+
+        let button = BlockState::STONE_BUTTON;
+        let pressed = interacted_state(button, PropValue::North).unwrap();
+        assert_eq!(pressed.get(PropName::Powered), Some(PropValue::True));
+        assert_eq!(interacted_state(pressed, PropValue::North), None);
+
+        let gate = BlockState::OAK_FENCE_GATE.set(PropName::Facing, PropValue::North);
+        let opened = interacted_state(gate, PropValue::South).unwrap();
+        assert_eq!(opened.get(PropName::Facing), Some(PropValue::South));
+    }
+}
@@ -0,0 +1,443 @@
+//! Vanilla-like block placement.
+//!
+//! When a client places a block, it only tells the server which face of which
+//! block was clicked. [`compute_placement`] works out where the new block
+//! should go and which [`BlockState`] it should have: stairs face away from
+//! the player, logs are aligned with the clicked face, slabs are placed on the
+//! clicked half, torches attach to walls, blocks placed in water are
+//! waterlogged, and so on.
+//!
+//! ```
+//! # use valence::prelude::*;
+//! # use valence::client::event::UseItemOnBlock;
+//! use valence::block_placement::compute_placement;
+//!
+//! fn place_blocks(
+//!     clients: Query<(&Client, &Inventory)>,
+//!     mut instances: Query<&mut Instance>,
+//!     mut events: EventReader<UseItemOnBlock>,
+//! ) {
+//!     for event in events.iter() {
+//!         let Ok((client, inventory)) = clients.get(event.client) else {
+//!             continue;
+//!         };
+//!
+//!         let Some(kind) = inventory
+//!             .slot(client.held_item_slot())
+//!             .and_then(|stack| stack.item.to_block_kind())
+//!         else {
+//!             continue;
+//!         };
+//!
+//!         let Ok(mut instance) = instances.get_mut(client.instance()) else {
+//!             continue;
+//!         };
+//!
+//!         if let Some(placement) = compute_placement(&instance, client, event, kind) {
+//!             placement.apply(&mut instance);
+//!         }
+//!     }
+//! }
+//! ```
+
+use glam::Vec3;
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::{BlockFace, BlockKind, BlockPos, BlockState};
+
+use crate::client::event::UseItemOnBlock;
+use crate::client::Client;
+use crate::instance::Instance;
+
+/// The result of a successful [`compute_placement`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Placement {
+    /// Where the block is placed.
+    pub position: BlockPos,
+    /// The state of the placed block.
+    pub state: BlockState,
+    /// The other half of blocks which occupy two positions, such as doors and
+    /// beds.
+    pub other_half: Option<(BlockPos, BlockState)>,
+}
+
+impl Placement {
+    /// Sets the placed block (and its other half, if any) in `instance`.
+    pub fn apply(&self, instance: &mut Instance) {
+        instance.set_block(self.position, self.state);
+
+        if let Some((pos, state)) = self.other_half {
+            instance.set_block(pos, state);
+        }
+    }
+}
+
+/// Computes the placement of a block of the given kind resulting from `event`.
+///
+/// Returns `None` if the block cannot be placed, e.g. because the target
+/// position is occupied by a block that is not replaceable or is outside of
+/// any loaded chunk.
+///
+/// This does not check if the placed block would intersect any entities, nor
+/// does it consume the item used to place the block.
+pub fn compute_placement(
+    instance: &Instance,
+    client: &Client,
+    event: &UseItemOnBlock,
+    kind: BlockKind,
+) -> Option<Placement> {
+    let clicked = instance.block(event.position)?.state();
+
+    // Clicking the face of a single slab with a slab of the same kind merges
+    // them into a double slab.
+    if clicked.to_kind() == kind && clicked.get(PropName::Type).is_some() {
+        let merges = match clicked.get(PropName::Type) {
+            Some(PropValue::Bottom) => event.face == BlockFace::Top,
+            Some(PropValue::Top) => event.face == BlockFace::Bottom,
+            _ => false,
+        };
+
+        if merges {
+            return Some(Placement {
+                position: event.position,
+                state: clicked
+                    .set(PropName::Type, PropValue::Double)
+                    .set(PropName::Waterlogged, PropValue::False),
+                other_half: None,
+            });
+        }
+    }
+
+    // Replaceable blocks such as grass and snow layers are replaced directly
+    // instead of placing against them.
+    let position = if clicked.is_replaceable() && clicked.to_kind() != kind {
+        event.position
+    } else {
+        event.position.get_in_direction(event.face)
+    };
+
+    let replaced = instance.block(position)?.state();
+
+    let ctx = PlacementContext {
+        face: event.face,
+        cursor_pos: event.cursor_pos,
+        yaw: client.yaw(),
+        pitch: client.pitch(),
+    };
+
+    if !replaced.is_replaceable() {
+        // Slabs can also be merged by clicking the block adjacent to them.
+        if replaced.to_kind() == kind && replaced.get(PropName::Type).is_some() {
+            let top = ctx.places_on_top_half();
+            let merges = match replaced.get(PropName::Type) {
+                Some(PropValue::Bottom) => top,
+                Some(PropValue::Top) => !top,
+                _ => false,
+            };
+
+            if merges {
+                return Some(Placement {
+                    position,
+                    state: replaced
+                        .set(PropName::Type, PropValue::Double)
+                        .set(PropName::Waterlogged, PropValue::False),
+                    other_half: None,
+                });
+            }
+        }
+
+        return None;
+    }
+
+    let mut state = oriented_state(kind, &ctx)?;
+
+    // Blocks placed in water sources become waterlogged.
+    let in_water = replaced.to_kind() == BlockKind::Water
+        && replaced.get(PropName::Level) == Some(PropValue::_0);
+    state = state.set(PropName::Waterlogged, PropValue::from_bool(in_water));
+
+    let mut other_half = None;
+
+    if state.get(PropName::Half) == Some(PropValue::Lower) {
+        // Doors and other tall blocks.
+        let upper_pos = position.get_in_direction(BlockFace::Top);
+        if !instance.block(upper_pos)?.state().is_replaceable() {
+            return None;
+        }
+
+        other_half = Some((upper_pos, state.set(PropName::Half, PropValue::Upper)));
+    } else if state.get(PropName::Part) == Some(PropValue::Foot) {
+        // Beds.
+        let head_pos = position.get_in_direction(ctx.horizontal_facing());
+        if !instance.block(head_pos)?.state().is_replaceable() {
+            return None;
+        }
+
+        other_half = Some((head_pos, state.set(PropName::Part, PropValue::Head)));
+    }
+
+    Some(Placement {
+        position,
+        state,
+        other_half,
+    })
+}
+
+/// The information about the placement used to orient the block.
+struct PlacementContext {
+    face: BlockFace,
+    cursor_pos: Vec3,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl PlacementContext {
+    /// The horizontal direction the player is facing.
+    fn horizontal_facing(&self) -> BlockFace {
+        match ((self.yaw / 90.0 + 0.5).floor() as i32).rem_euclid(4) {
+            0 => BlockFace::South,
+            1 => BlockFace::West,
+            2 => BlockFace::North,
+            _ => BlockFace::East,
+        }
+    }
+
+    /// The direction the player is looking, including up and down.
+    fn look_direction(&self) -> BlockFace {
+        if self.pitch < -45.0 {
+            BlockFace::Top
+        } else if self.pitch > 45.0 {
+            BlockFace::Bottom
+        } else {
+            self.horizontal_facing()
+        }
+    }
+
+    /// Whether or not blocks with a top and bottom half (slabs, stairs,
+    /// trapdoors) should be placed in the top half.
+    fn places_on_top_half(&self) -> bool {
+        match self.face {
+            BlockFace::Bottom => true,
+            BlockFace::Top => false,
+            _ => self.cursor_pos.y > 0.5,
+        }
+    }
+}
+
+/// Computes the oriented state of a block before waterlogging and the other
+/// half are taken into account.
+fn oriented_state(kind: BlockKind, ctx: &PlacementContext) -> Option<BlockState> {
+    let name = kind.to_str();
+    let is_horizontal_face = !matches!(ctx.face, BlockFace::Top | BlockFace::Bottom);
+
+    // Torches, signs, banners and heads have separate wall-mounted variants.
+    if let Some(wall_kind) = wall_variant(name) {
+        return match ctx.face {
+            BlockFace::Bottom => None,
+            BlockFace::Top if name.ends_with("torch") => Some(kind.to_state()),
+            BlockFace::Top => Some(
+                kind.to_state()
+                    .set(PropName::Rotation, rotation_prop(ctx.yaw + 180.0)),
+            ),
+            face => Some(
+                wall_kind
+                    .to_state()
+                    .set(PropName::Facing, face_prop(face)),
+            ),
+        };
+    }
+
+    let mut state = kind.to_state();
+
+    if state.get(PropName::Axis).is_some() {
+        // Logs, pillars, chains, etc. Blocks that can't be vertical, such as
+        // nether portals, keep their default axis since `set` leaves them
+        // unchanged.
+        let axis = match ctx.face {
+            BlockFace::Top | BlockFace::Bottom => PropValue::Y,
+            BlockFace::North | BlockFace::South => PropValue::Z,
+            BlockFace::West | BlockFace::East => PropValue::X,
+        };
+
+        state = state.set(PropName::Axis, axis);
+    }
+
+    if state.get(PropName::Face).is_some() {
+        // Buttons, levers and grindstones.
+        let (attach, facing) = match ctx.face {
+            BlockFace::Top => (PropValue::Floor, ctx.horizontal_facing()),
+            BlockFace::Bottom => (PropValue::Ceiling, ctx.horizontal_facing()),
+            face => (PropValue::Wall, face),
+        };
+
+        return Some(
+            state
+                .set(PropName::Face, attach)
+                .set(PropName::Facing, face_prop(facing)),
+        );
+    }
+
+    if name.ends_with("_slab") {
+        let ty = if ctx.places_on_top_half() {
+            PropValue::Top
+        } else {
+            PropValue::Bottom
+        };
+
+        return Some(state.set(PropName::Type, ty));
+    }
+
+    if name.ends_with("_trapdoor") {
+        let facing = if is_horizontal_face {
+            ctx.face
+        } else {
+            ctx.horizontal_facing().opposite()
+        };
+
+        let half = if ctx.places_on_top_half() {
+            PropValue::Top
+        } else {
+            PropValue::Bottom
+        };
+
+        return Some(
+            state
+                .set(PropName::Facing, face_prop(facing))
+                .set(PropName::Half, half),
+        );
+    }
+
+    if name.ends_with("_stairs") {
+        let half = if ctx.places_on_top_half() {
+            PropValue::Top
+        } else {
+            PropValue::Bottom
+        };
+
+        return Some(
+            state
+                .set(PropName::Facing, face_prop(ctx.horizontal_facing()))
+                .set(PropName::Half, half),
+        );
+    }
+
+    if name.ends_with("_door") || name.ends_with("_fence_gate") || name.ends_with("_bed") {
+        return Some(state.set(PropName::Facing, face_prop(ctx.horizontal_facing())));
+    }
+
+    if state.get(PropName::Facing).is_some() {
+        let can_face_up = state.set(PropName::Facing, PropValue::Up) != state
+            || state.get(PropName::Facing) == Some(PropValue::Up);
+        let can_face_down = state.set(PropName::Facing, PropValue::Down) != state
+            || state.get(PropName::Facing) == Some(PropValue::Down);
+
+        let facing = if can_face_up && can_face_down {
+            match name {
+                // Observers watch the block in front of the player.
+                "observer" => ctx.look_direction(),
+                // These face the player.
+                "piston" | "sticky_piston" | "dispenser" | "dropper" | "command_block"
+                | "chain_command_block" | "repeating_command_block" => {
+                    ctx.look_direction().opposite()
+                }
+                // End rods, lightning rods, shulker boxes, etc. point away from the
+                // clicked block.
+                _ => ctx.face,
+            }
+        } else if can_face_down {
+            // Hoppers point into the clicked block.
+            if is_horizontal_face {
+                ctx.face.opposite()
+            } else {
+                BlockFace::Bottom
+            }
+        } else if name == "ladder" || name.ends_with("_wall_fan") {
+            if !is_horizontal_face {
+                return None;
+            }
+
+            ctx.face
+        } else {
+            // Chests, furnaces, etc. face the player.
+            ctx.horizontal_facing().opposite()
+        };
+
+        state = state.set(PropName::Facing, face_prop(facing));
+    }
+
+    Some(state)
+}
+
+/// Returns the wall-mounted variant of a block, if any.
+fn wall_variant(name: &str) -> Option<BlockKind> {
+    let wall_name = match name.rsplit_once('_') {
+        Some((prefix, last)) => format!("{prefix}_wall_{last}"),
+        None => format!("wall_{name}"),
+    };
+
+    BlockKind::from_str(&wall_name)
+}
+
+fn face_prop(face: BlockFace) -> PropValue {
+    match face {
+        BlockFace::Bottom => PropValue::Down,
+        BlockFace::Top => PropValue::Up,
+        BlockFace::North => PropValue::North,
+        BlockFace::South => PropValue::South,
+        BlockFace::West => PropValue::West,
+        BlockFace::East => PropValue::East,
+    }
+}
+
+/// Converts a yaw in degrees to one of the 16 rotations used by standing
+/// signs, banners and heads.
+fn rotation_prop(yaw: f32) -> PropValue {
+    let rotation = ((yaw * 16.0 / 360.0 + 0.5).floor() as i32).rem_euclid(16);
+    PropValue::from_u16(rotation as u16).expect("rotation should be in 0..16")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(face: BlockFace, cursor_y: f32, yaw: f32) -> PlacementContext {
+        PlacementContext {
+            face,
+            cursor_pos: Vec3::new(0.5, cursor_y, 0.5),
+            yaw,
+            pitch: 0.0,
+        }
+    }
+
+    #[test]
+    fn orientation() {
+        // Facing north.
+        let state = oriented_state(BlockKind::OakStairs, &ctx(BlockFace::Top, 1.0, 180.0));
+        assert_eq!(
+            state.and_then(|s| s.get(PropName::Facing)),
+            Some(PropValue::North)
+        );
+
+        let state = oriented_state(BlockKind::OakSlab, &ctx(BlockFace::East, 0.75, 0.0));
+        assert_eq!(
+            state.and_then(|s| s.get(PropName::Type)),
+            Some(PropValue::Top)
+        );
+
+        let state = oriented_state(BlockKind::OakLog, &ctx(BlockFace::West, 0.5, 0.0));
+        assert_eq!(
+            state.and_then(|s| s.get(PropName::Axis)),
+            Some(PropValue::X)
+        );
+
+        // Furnaces face the player.
+        let state = oriented_state(BlockKind::Furnace, &ctx(BlockFace::Top, 1.0, 0.0));
+        assert_eq!(
+            state.and_then(|s| s.get(PropName::Facing)),
+            Some(PropValue::North)
+        );
+
+        let state = oriented_state(BlockKind::Torch, &ctx(BlockFace::South, 0.5, 0.0));
+        assert_eq!(state.map(|s| s.to_kind()), Some(BlockKind::WallTorch));
+        assert_eq!(oriented_state(BlockKind::Torch, &ctx(BlockFace::Bottom, 0.0, 0.0)), None);
+    }
+}
@@ -0,0 +1,258 @@
+//! Scheduled and random block ticks.
+//!
+//! Many vanilla blocks change over time: crops grow, fire spreads, leaves
+//! decay, and redstone components switch after a delay. Adding a
+//! [`BlockTicks`] component to an instance entity and the [`BlockTickPlugin`]
+//! to the app provides the two kinds of updates these behaviors are built on:
+//!
+//! - Scheduled ticks are requested for a position with
+//!   [`BlockTicks::schedule`] and arrive as a [`ScheduledBlockTick`] event
+//!   after the delay, if the block is still of the same kind.
+//! - Random ticks pick random blocks in every non-empty chunk section within
+//!   the [simulation distance] of a client each tick and arrive as
+//!   [`RandomBlockTick`] events.
+//!
+//! Events are sent in [`CoreStage::PreUpdate`], so they can be handled in
+//! [`CoreStage::Update`] like client events.
+//!
+//! [simulation distance]: Client::simulation_distance
+
+use std::collections::BTreeMap;
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use rand::Rng;
+use rustc_hash::FxHashSet;
+use valence_protocol::{BlockKind, BlockPos, BlockState};
+
+use crate::client::Client;
+use crate::instance::Instance;
+use crate::pause::gameplay_systems;
+use crate::server::Server;
+use crate::view::{ChunkPos, ChunkView};
+
+/// Adds the [`ScheduledBlockTick`] and [`RandomBlockTick`] events and the
+/// system sending them for instances with a [`BlockTicks`] component.
+pub struct BlockTickPlugin;
+
+impl Plugin for BlockTickPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ScheduledBlockTick>()
+            .add_event::<RandomBlockTick>()
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                gameplay_systems().with_system(tick_blocks),
+            );
+    }
+}
+
+/// The default number of random ticks per chunk section per tick, which
+/// matches the vanilla `randomTickSpeed` game rule.
+pub const DEFAULT_RANDOM_TICK_SPEED: u32 = 3;
+
+/// A component for instance entities which keeps track of the scheduled block
+/// ticks in the instance and configures random ticking.
+#[derive(Component, Clone, Debug)]
+pub struct BlockTicks {
+    /// The number of blocks chosen at random in every chunk section within
+    /// the simulation distance of a client each tick. Zero disables random
+    /// ticks.
+    pub random_tick_speed: u32,
+    /// The current tick as of the last update.
+    current_tick: i64,
+    /// Scheduled ticks keyed by the tick they are due.
+    pending: BTreeMap<i64, Vec<(BlockPos, BlockKind)>>,
+    scheduled: FxHashSet<(BlockPos, BlockKind)>,
+}
+
+impl BlockTicks {
+    pub fn new() -> Self {
+        Self {
+            random_tick_speed: DEFAULT_RANDOM_TICK_SPEED,
+            current_tick: 0,
+            pending: BTreeMap::new(),
+            scheduled: FxHashSet::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_random_tick_speed(mut self, random_tick_speed: u32) -> Self {
+        self.random_tick_speed = random_tick_speed;
+        self
+    }
+
+    /// Schedules a tick for the block of kind `kind` at `pos` in `delay` ticks.
+    /// A delay of zero or less results in a tick during the next update.
+    ///
+    /// Like in vanilla, a block can only have one tick scheduled at a time.
+    /// Returns `false` if a tick was already scheduled, in which case the
+    /// existing tick is kept.
+    pub fn schedule(&mut self, pos: impl Into<BlockPos>, kind: BlockKind, delay: i64) -> bool {
+        let pos = pos.into();
+
+        if !self.scheduled.insert((pos, kind)) {
+            return false;
+        }
+
+        self.pending
+            .entry(self.current_tick + delay.max(1))
+            .or_default()
+            .push((pos, kind));
+
+        true
+    }
+
+    /// Returns whether or not a tick is scheduled for the block of kind `kind`
+    /// at `pos`.
+    pub fn is_scheduled(&self, pos: impl Into<BlockPos>, kind: BlockKind) -> bool {
+        self.scheduled.contains(&(pos.into(), kind))
+    }
+
+    /// Cancels all scheduled ticks.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.scheduled.clear();
+    }
+
+    /// Returns the number of scheduled ticks.
+    pub fn scheduled_count(&self) -> usize {
+        self.scheduled.len()
+    }
+}
+
+impl Default for BlockTicks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sent when a tick scheduled with [`BlockTicks::schedule`] is due. Not sent
+/// if the block changed to a different kind in the meantime.
+#[derive(Clone, Debug)]
+pub struct ScheduledBlockTick {
+    pub instance: Entity,
+    pub position: BlockPos,
+    pub state: BlockState,
+}
+
+/// Sent for blocks chosen at random in instances with a nonzero
+/// [`BlockTicks::random_tick_speed`]. Not sent for air.
+#[derive(Clone, Debug)]
+pub struct RandomBlockTick {
+    pub instance: Entity,
+    pub position: BlockPos,
+    pub state: BlockState,
+}
+
+fn tick_blocks(
+    server: Res<Server>,
+    mut instances: Query<(Entity, &Instance, &mut BlockTicks)>,
+    clients: Query<&Client>,
+    mut scheduled_events: EventWriter<ScheduledBlockTick>,
+    mut random_events: EventWriter<RandomBlockTick>,
+) {
+    let current_tick = server.current_tick();
+    let mut rng = rand::thread_rng();
+    let mut simulated = FxHashSet::<ChunkPos>::default();
+
+    for (instance_id, instance, mut ticks) in &mut instances {
+        let ticks = &mut *ticks;
+        ticks.current_tick = current_tick;
+
+        while let Some(&tick) = ticks.pending.keys().next() {
+            if tick > current_tick {
+                break;
+            }
+
+            for (pos, kind) in ticks.pending.remove(&tick).unwrap_or_default() {
+                ticks.scheduled.remove(&(pos, kind));
+
+                let Some(state) = instance.block(pos).map(|b| b.state()) else {
+                    continue
+                };
+
+                if state.to_kind() == kind {
+                    scheduled_events.send(ScheduledBlockTick {
+                        instance: instance_id,
+                        position: pos,
+                        state,
+                    });
+                }
+            }
+        }
+
+        if ticks.random_tick_speed == 0 {
+            continue;
+        }
+
+        let min_y = instance.min_y();
+
+        simulated.clear();
+
+        for client in &clients {
+            if client.instance() == instance_id {
+                ChunkView::new(
+                    ChunkPos::from_dvec3(client.position()),
+                    client.simulation_distance(),
+                )
+                .for_each(|pos| {
+                    simulated.insert(pos);
+                });
+            }
+        }
+
+        for &chunk_pos in &simulated {
+            let Some(chunk) = instance.chunk(chunk_pos) else {
+                continue
+            };
+
+            for sect_y in 0..chunk.section_count() {
+                if chunk.section_is_empty(sect_y) {
+                    continue;
+                }
+
+                for _ in 0..ticks.random_tick_speed {
+                    let x = rng.gen_range(0..16);
+                    let y = sect_y * 16 + rng.gen_range(0..16);
+                    let z = rng.gen_range(0..16);
+
+                    let state = chunk.block_state(x, y, z);
+
+                    if state.is_air() {
+                        continue;
+                    }
+
+                    random_events.send(RandomBlockTick {
+                        instance: instance_id,
+                        position: BlockPos::new(
+                            chunk_pos.x * 16 + x as i32,
+                            min_y + y as i32,
+                            chunk_pos.z * 16 + z as i32,
+                        ),
+                        state,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_scheduled_tick_per_block() {
+        let mut ticks = BlockTicks::new();
+
+        assert!(ticks.schedule([0, 0, 0], BlockKind::Repeater, 2));
+        assert!(!ticks.schedule([0, 0, 0], BlockKind::Repeater, 4));
+        assert!(ticks.schedule([0, 0, 0], BlockKind::Comparator, 2));
+
+        assert!(ticks.is_scheduled([0, 0, 0], BlockKind::Repeater));
+        assert_eq!(ticks.scheduled_count(), 2);
+
+        ticks.clear();
+        assert_eq!(ticks.scheduled_count(), 0);
+    }
+}
@@ -1,53 +1,111 @@
 use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::net::IpAddr;
 use std::num::Wrapping;
+use std::ops::Range;
 use std::time::Instant;
 
-use anyhow::{bail, Context};
+use anyhow::bail;
 use bevy_ecs::prelude::*;
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use glam::{DVec3, Vec3};
-use tracing::warn;
+use rustc_hash::FxHashSet;
+use tracing::{trace_span, warn};
 use uuid::Uuid;
 use valence_protocol::packets::s2c::particle::Particle;
 use valence_protocol::packets::s2c::play::{
-    AcknowledgeBlockChange, CombatDeath, DisconnectPlay, EntityEvent, GameEvent, KeepAliveS2c,
-    LoginPlay, ParticleS2c, PluginMessageS2c, RemoveEntitiesEncode, ResourcePackS2c, Respawn,
-    SetActionBarText, SetCenterChunk, SetDefaultSpawnPosition, SetEntityMetadata,
-    SetEntityVelocity, SetRenderDistance, SetSubtitleText, SetTitleAnimationTimes, SetTitleText,
-    SoundEffect, SynchronizePlayerPosition, SystemChatMessage, UnloadChunk,
+    AcknowledgeBlockChange, CombatDeath, DisconnectPlay, EntityEffect, EntityEvent, Explosion,
+    GameEvent, KeepAliveS2c, LoginPlay, OpenSignEditor, ParticleS2c, PlayerAbilitiesS2c,
+    PluginMessageS2c, RemoveEntitiesEncode, ResourcePackS2c, Respawn, SetActionBarText, SetCamera,
+    SetCenterChunk, SetCooldown, SetDefaultSpawnPosition, SetEntityMetadata, SetEntityVelocity,
+    SetRenderDistance, SetSimulationDistance, SetSubtitleText, SetTitleAnimationTimes,
+    SetTitleText, SoundEffect, SynchronizePlayerPosition, SystemChatMessage, UnloadChunk,
+    UpdateTime, WorldEvent,
 };
 use valence_protocol::types::{
-    GameEventKind, GameMode, GlobalPos, Property, SoundCategory, SyncPlayerPosLookFlags,
+    ChatMode, DisplayedSkinParts, EntityEffectFlags, GameEventKind, GameMode, GlobalPos, MainHand,
+    PlayerAbilitiesFlags, Property, SoundCategory, SyncPlayerPosLookFlags,
 };
 use valence_protocol::{
-    BlockPos, EncodePacket, Ident, ItemStack, PacketDecoder, PacketEncoder, RawBytes, Sound, Text,
-    Username, VarInt,
+    BlockPos, EncodePacket, Ident, ItemKind, ItemStack, PacketDecoder, PacketEncoder, RawBytes,
+    Sound, Text, Username, VarInt,
 };
 
+use crate::client::event::UpdateSettings;
+use crate::config::{ConnectionContext, OutgoingOverflowPolicy, DEFAULT_TPS};
 use crate::dimension::DimensionId;
 use crate::entity::data::Player;
-use crate::entity::{velocity_to_packet_units, EntityStatus, McEntity};
-use crate::instance::Instance;
+use crate::entity::{velocity_to_packet_units, EntityKind, EntityStatus, McEntity, TrackedData};
+use crate::instance::{Instance, PartitionCell};
+use crate::localization::{LocalizedMessage, DEFAULT_FALLBACK_LOCALE};
+use crate::math::{from_yaw_and_pitch, Aabb};
 use crate::packet::WritePacket;
-use crate::server::{NewClientInfo, Server};
+use crate::server::{NewClientInfo, Server, SharedServer};
+use crate::timings::TickTimings;
 use crate::view::{ChunkPos, ChunkView};
 use crate::{Despawned, NULL_ENTITY};
 
 pub mod event;
 
+/// The height of a standing player's eyes above its feet.
+pub const PLAYER_EYE_HEIGHT: f64 = 1.62;
+
+/// The maximum distance in blocks between a client's eyes and a hitbox the
+/// client can interact with. See [`Client::can_reach`].
+pub const MAX_REACH_DISTANCE: f64 = 6.0;
+
+/// The simulation distance of clients in chunks unless it is changed with
+/// [`Client::set_simulation_distance`].
+pub const DEFAULT_SIMULATION_DISTANCE: u8 = 16;
+
+/// The flying speed of clients unless it is changed with
+/// [`Client::set_flying_speed`].
+pub const DEFAULT_FLYING_SPEED: f32 = 0.05;
+
+/// The field of view modifier of clients unless it is changed with
+/// [`Client::set_fov_modifier`].
+pub const DEFAULT_FOV_MODIFIER: f32 = 0.1;
+
+/// The number of ticks after which a teleport the client has not confirmed
+/// causes a [`TeleportTimedOut`] event. Vanilla servers resend teleports after
+/// this long.
+pub const TELEPORT_TIMEOUT_TICKS: i64 = 20;
+
+/// How far below a client the explosion sent by [`Client::add_velocity`] is
+/// placed, which is out of the client's hearing range.
+const KNOCKBACK_EXPLOSION_DEPTH: f64 = 256.0;
+
+/// How long the nausea of [`TeleportEffect::NetherPortal`] lasts, in ticks.
+/// This is the time survival players have to stand in a nether portal.
+const NETHER_PORTAL_EFFECT_TICKS: i32 = 80;
+
+/// The world event of the sound played after travelling through a nether
+/// portal.
+const PORTAL_TRAVEL_WORLD_EVENT: i32 = 1032;
+
+/// The status effect IDs of nausea and blindness.
+const NAUSEA_EFFECT_ID: i32 = 9;
+const BLINDNESS_EFFECT_ID: i32 = 15;
+
 /// Represents a client connected to the server. Used to send and receive
 /// packets from the client.
 #[derive(Component)]
 pub struct Client {
     conn: Box<dyn ClientConnection>,
     enc: PacketEncoder,
+    /// The number of bytes in `enc` written by packets which are sent even if
+    /// the outgoing buffer of the connection is full.
+    urgent_len: usize,
     dec: PacketDecoder,
+    /// The compression threshold sent to the client during login.
+    compression_threshold: Option<u32>,
+    compression_enabled: bool,
     scratch: Vec<u8>,
     is_disconnected: bool,
     username: Username<String>,
     uuid: Uuid,
     ip: IpAddr,
+    connection_context: ConnectionContext,
     properties: Vec<Property>,
     instance: Entity,
     old_instance: Entity,
@@ -59,29 +117,109 @@ pub struct Client {
     pitch: f32,
     pitch_modified: bool,
     on_ground: bool,
+    /// The velocity to send with [`SetEntityVelocity`] at the end of the tick.
+    velocity_to_set: Option<Vec3>,
+    /// The velocity to add with the knockback of an [`Explosion`] at the end of
+    /// the tick.
+    velocity_to_add: Vec3,
     game_mode: GameMode,
+    /// The game mode before the last change, which the client shows in the
+    /// game mode switcher.
+    previous_game_mode: Option<GameMode>,
+    /// The game mode at the end of the last tick, or the game mode the client
+    /// joined with, to send [`GameModeChanged`] events.
+    old_game_mode: GameMode,
+    /// Allows flying in survival and adventure mode.
+    allow_flying: bool,
+    flying: bool,
+    flying_speed: f32,
+    fov_modifier: f32,
+    /// Makes the client invulnerable in survival and adventure mode.
+    invulnerable: bool,
+    /// If the abilities need to be sent at the end of the tick.
+    abilities_modified: bool,
+    /// The entity the client views the world from, if not itself.
+    camera: Option<Entity>,
+    /// If the camera needs to be sent at the end of the tick.
+    camera_modified: bool,
+    /// The sign the client was allowed to edit with
+    /// [`Client::open_sign_editor`].
+    editing_sign: Option<BlockPos>,
+    /// If the sign editor needs to be opened at the end of the tick.
+    open_sign_editor: bool,
     op_level: u8,
     block_change_sequence: i32,
     // TODO: make this a component and default to the self-entity's player data?
     player_data: Player,
+    /// If the flags of the client's own player data need to be sent at the
+    /// end of the tick even if they are unchanged.
+    resend_player_flags: bool,
+    /// The view distance set with [`Client::set_view_distance`].
     view_distance: u8,
+    max_view_distance: u8,
+    simulation_distance: u8,
+    /// The limits of the client's instance, copied when the client is
+    /// updated.
+    instance_max_view_distance: u8,
+    instance_max_simulation_distance: u8,
+    /// The effective view and simulation distances sent to the client.
     old_view_distance: u8,
+    old_simulation_distance: u8,
+    /// The language from the client's settings, e.g. `en_us`.
+    locale: Box<str>,
+    /// Messages queued with [`msg!`](crate::msg) which have not been
+    /// translated yet.
+    localized_messages: Vec<LocalizedMessage>,
+    /// The time of day shown to this client regardless of its instance.
+    time_override: Option<i64>,
+    old_time_override: Option<i64>,
+    /// The weather shown to this client regardless of its instance.
+    weather_override: Option<Weather>,
+    old_weather_override: Option<Weather>,
     death_location: Option<(DimensionId, BlockPos)>,
     entities_to_despawn: Vec<VarInt>,
+    /// Entities in view whose custom names are hidden from this client because
+    /// they are outside of the entity's name visibility range.
+    hidden_name_tags: FxHashSet<Entity>,
+    /// Entities outlined for this client only.
+    glowing_entities: FxHashSet<Entity>,
+    /// The entities the client was told are glowing, although they might not
+    /// be.
+    glowing_overrides: FxHashSet<Entity>,
+    /// The protocol ID of the entity the client was told it is riding.
+    vehicle_override: Option<i32>,
+    /// Chunks in view which have not been sent yet, nearest first. Only used
+    /// if the number of chunks sent per tick is limited.
+    pending_chunks: VecDeque<ChunkPos>,
+    /// Chunks to send again to this client only. See [`Client::resend_chunk`].
+    chunks_to_resend: Vec<ChunkPos>,
     got_keepalive: bool,
     last_keepalive_id: u64,
     keepalive_sent_time: Instant,
     ping: i32,
+    /// Traffic counters since the client joined, in the play state.
+    packets_sent: u64,
+    packets_received: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    /// Set when the client is disconnected for not responding to a keepalive,
+    /// until the [`ClientTimedOut`] event is sent.
+    timed_out: bool,
     /// Counts up as teleports are made.
     teleport_id_counter: u32,
-    /// The number of pending client teleports that have yet to receive a
-    /// confirmation. Inbound client position packets should be ignored while
-    /// this is nonzero.
-    pending_teleports: u32,
+    /// The client teleports that have yet to receive a confirmation, oldest
+    /// first. Inbound client position packets should be ignored while this is
+    /// not empty.
+    pending_teleports: VecDeque<PendingTeleport>,
+    /// The effect to show after the next teleport is sent.
+    teleport_effect: Option<TeleportEffect>,
     /// If the client needs initialization.
     is_new: bool,
     /// If the client needs to be sent the respawn packet for the current world.
     needs_respawn: bool,
+    /// If the client was moved to another instance with
+    /// [`Client::transfer_to_instance`].
+    needs_transfer: bool,
     is_hardcore: bool,
     is_flat: bool,
     has_respawn_screen: bool,
@@ -98,27 +236,88 @@ pub struct Client {
     pub(crate) held_item_slot: u16,
 }
 
+/// The rain and thunder levels of the weather shown to a client.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Weather {
+    /// The rain level in `0.0..=1.0`. Zero means no rain.
+    pub rain: f32,
+    /// The thunder level in `0.0..=1.0`. Thunder only darkens the sky while it
+    /// is raining.
+    pub thunder: f32,
+}
+
+impl Weather {
+    pub const CLEAR: Self = Self {
+        rain: 0.0,
+        thunder: 0.0,
+    };
+
+    pub const RAIN: Self = Self {
+        rain: 1.0,
+        thunder: 0.0,
+    };
+
+    pub const THUNDER: Self = Self {
+        rain: 1.0,
+        thunder: 1.0,
+    };
+}
+
 pub trait ClientConnection: Send + Sync + 'static {
     fn try_send(&mut self, bytes: BytesMut) -> anyhow::Result<()>;
     fn try_recv(&mut self) -> anyhow::Result<BytesMut>;
+
+    /// Sends a sequence of byte buffers in order. Connections which support
+    /// vectored writes can send the buffers without copying them. The default
+    /// implementation concatenates the buffers and calls [`Self::try_send`].
+    fn try_send_vectored(&mut self, segments: Vec<Bytes>) -> anyhow::Result<()> {
+        let mut bytes = BytesMut::with_capacity(segments.iter().map(|s| s.len()).sum());
+
+        for segment in segments {
+            bytes.extend_from_slice(&segment);
+        }
+
+        self.try_send(bytes)
+    }
+
+    /// Like [`Self::try_send_vectored`], but the buffers are sent even if
+    /// the outgoing buffer of the connection is full. This is only used for
+    /// the few packets which must reach the client regardless, such as
+    /// keepalives and the reason it was disconnected for. The default
+    /// implementation calls [`Self::try_send_vectored`].
+    fn send_urgent_vectored(&mut self, segments: Vec<Bytes>) -> anyhow::Result<()> {
+        self.try_send_vectored(segments)
+    }
+
+    /// Returns the number of bytes sent through this connection which have
+    /// not been written to the client yet. The default implementation returns
+    /// zero.
+    fn queued_len(&self) -> usize {
+        0
+    }
 }
 
 impl Client {
     pub(crate) fn new(
         info: NewClientInfo,
+        connection_context: ConnectionContext,
         conn: Box<dyn ClientConnection>,
         enc: PacketEncoder,
         dec: PacketDecoder,
     ) -> Self {
         Self {
             conn,
+            compression_threshold: enc.compression_threshold(),
+            compression_enabled: true,
             enc,
+            urgent_len: 0,
             dec,
             scratch: vec![],
             is_disconnected: false,
             username: info.username,
             uuid: info.uuid,
             ip: info.ip,
+            connection_context,
             properties: info.properties,
             instance: NULL_ENTITY,
             old_instance: NULL_ENTITY,
@@ -130,16 +329,49 @@ impl Client {
             pitch: 0.0,
             pitch_modified: true,
             on_ground: false,
+            velocity_to_set: None,
+            velocity_to_add: Vec3::ZERO,
             game_mode: GameMode::default(),
+            previous_game_mode: None,
+            old_game_mode: GameMode::default(),
+            allow_flying: false,
+            flying: false,
+            flying_speed: DEFAULT_FLYING_SPEED,
+            fov_modifier: DEFAULT_FOV_MODIFIER,
+            invulnerable: false,
+            abilities_modified: false,
+            camera: None,
+            camera_modified: false,
+            editing_sign: None,
+            open_sign_editor: false,
             op_level: 0,
             block_change_sequence: 0,
             player_data: Player::new(),
+            resend_player_flags: false,
             view_distance: 2,
+            max_view_distance: 32,
+            simulation_distance: DEFAULT_SIMULATION_DISTANCE,
+            instance_max_view_distance: 32,
+            instance_max_simulation_distance: 32,
             old_view_distance: 2,
+            old_simulation_distance: DEFAULT_SIMULATION_DISTANCE,
+            locale: DEFAULT_FALLBACK_LOCALE.into(),
+            localized_messages: vec![],
+            time_override: None,
+            old_time_override: None,
+            weather_override: None,
+            old_weather_override: None,
             death_location: None,
             entities_to_despawn: vec![],
+            hidden_name_tags: FxHashSet::default(),
+            glowing_entities: FxHashSet::default(),
+            glowing_overrides: FxHashSet::default(),
+            vehicle_override: None,
+            pending_chunks: VecDeque::new(),
+            chunks_to_resend: vec![],
             is_new: true,
             needs_respawn: false,
+            needs_transfer: false,
             is_hardcore: false,
             is_flat: false,
             has_respawn_screen: false,
@@ -147,8 +379,14 @@ impl Client {
             last_keepalive_id: 0,
             keepalive_sent_time: Instant::now(),
             ping: -1,
+            packets_sent: 0,
+            packets_received: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            timed_out: false,
             teleport_id_counter: 0,
-            pending_teleports: 0,
+            pending_teleports: VecDeque::new(),
+            teleport_effect: None,
             cursor_item: None,
             cursor_item_modified: false,
             window_id: 0,
@@ -162,6 +400,11 @@ impl Client {
         self.is_new
     }
 
+    /// If the client is sent a respawn packet at the end of this tick.
+    pub(crate) fn needs_respawn(&self) -> bool {
+        self.needs_respawn
+    }
+
     /// Attempts to write a play packet into this client's packet buffer. The
     /// packet will be sent at the end of the tick.
     ///
@@ -174,6 +417,19 @@ impl Client {
         self.enc.write_packet(pkt);
     }
 
+    /// Writes a packet which is sent even if the outgoing buffer of the
+    /// connection is full, such as keepalives and teleports. Clients which
+    /// can't keep up would otherwise time out or be unable to confirm
+    /// teleports while the buffer drains.
+    fn write_urgent_packet<P>(&mut self, pkt: &P)
+    where
+        P: EncodePacket + ?Sized,
+    {
+        let len_before = self.enc.len();
+        self.enc.write_packet(pkt);
+        self.urgent_len += self.enc.len() - len_before;
+    }
+
     /// Writes arbitrary bytes to this client's packet buffer. The packet data
     /// must be properly compressed for the current compression threshold but
     /// never encrypted.
@@ -202,6 +458,18 @@ impl Client {
         self.ip
     }
 
+    /// Gets the context of this client's connection, including the data
+    /// attached in [`AsyncCallbacks::connect`].
+    ///
+    /// [`AsyncCallbacks::connect`]: crate::config::AsyncCallbacks::connect
+    pub fn connection_context(&self) -> &ConnectionContext {
+        &self.connection_context
+    }
+
+    pub fn connection_context_mut(&mut self) -> &mut ConnectionContext {
+        &mut self.connection_context
+    }
+
     /// Gets the properties from this client's game profile.
     pub fn properties(&self) -> &[Property] {
         &self.properties
@@ -232,6 +500,26 @@ impl Client {
         self.needs_respawn = true;
     }
 
+    /// Moves the client to the [`Instance`] entity `instance` at `pos`.
+    ///
+    /// Unlike [`Client::set_instance`], the client is only respawned if the
+    /// dimension of the new instance differs from the dimension of the old
+    /// one, in which case the client discards the old chunks and entities by
+    /// itself. Otherwise, the old chunks are unloaded and the old entities
+    /// despawned without showing the loading screen, so the transfer looks
+    /// like a teleport.
+    ///
+    /// The given [`Entity`] must exist and have the [`Instance`] component.
+    /// Otherwise, the client is disconnected at the end of the tick.
+    pub fn transfer_to_instance(&mut self, instance: Entity, pos: impl Into<DVec3>) {
+        if self.instance != instance {
+            self.instance = instance;
+            self.needs_transfer = true;
+        }
+
+        self.set_position(pos);
+    }
+
     /// Gets the absolute position of this client in the instance it is located
     /// in.
     pub fn position(&self) -> DVec3 {
@@ -243,6 +531,75 @@ impl Client {
         self.position_modified = true;
     }
 
+    /// Sets the position of this client without sending it to the client.
+    /// This is for positions the client moves to on its own, such as the seat
+    /// of the vehicle it is riding.
+    pub(crate) fn set_position_unsynced(&mut self, pos: impl Into<DVec3>) {
+        self.position = pos.into();
+    }
+
+    /// Moves the client to `pos` in the [`Instance`] entity `instance` like
+    /// [`Client::transfer_to_instance`], and shows the given effect once the
+    /// client has been moved.
+    ///
+    /// The effect is sent after the teleport and the respawn of the client, if
+    /// any, since respawning clears the status effects of the client. Use
+    /// [`Client::is_teleporting`] to find out when the client has arrived.
+    pub fn teleport_with_effects(
+        &mut self,
+        instance: Entity,
+        pos: impl Into<DVec3>,
+        effect: TeleportEffect,
+    ) {
+        self.transfer_to_instance(instance, pos);
+        self.teleport_effect = Some(effect);
+    }
+
+    /// Whether or not the client has yet to confirm a teleport. Movement
+    /// packets of the client are ignored until all teleports are confirmed, as
+    /// they were sent from the position the client had before.
+    pub fn is_teleporting(&self) -> bool {
+        !self.pending_teleports.is_empty()
+    }
+
+    /// Gets the number of teleports the client has yet to confirm.
+    pub fn pending_teleports(&self) -> usize {
+        self.pending_teleports.len()
+    }
+
+    /// Gets the ID of the last teleport sent to the client, if any. This is the
+    /// ID found in [`TeleportConfirmed`] and [`TeleportTimedOut`] events.
+    ///
+    /// Teleports are sent at the end of the tick, so the ID of a teleport made
+    /// with [`Client::set_position`] is only known in the next tick.
+    ///
+    /// [`TeleportConfirmed`]: crate::client::event::TeleportConfirmed
+    pub fn last_teleport_id(&self) -> Option<u32> {
+        (self.teleport_id_counter != 0).then(|| self.teleport_id_counter.wrapping_sub(1))
+    }
+
+    /// Gets the position of this client's eyes, which is where the client
+    /// looks from and interacts from.
+    pub fn eye_position(&self) -> DVec3 {
+        self.position + DVec3::new(0.0, PLAYER_EYE_HEIGHT, 0.0)
+    }
+
+    /// Gets the unit vector in the direction this client is looking, computed
+    /// from its yaw and pitch.
+    pub fn look_direction(&self) -> Vec3 {
+        from_yaw_and_pitch(self.yaw, self.pitch)
+    }
+
+    /// Whether or not the hitbox is close enough to this client's eyes for
+    /// the client to interact with it, using the same limit as vanilla
+    /// servers. Used to validate interactions like
+    /// [`InteractWithEntity`](event::InteractWithEntity), since modified
+    /// clients can claim to interact with anything.
+    pub fn can_reach(&self, hitbox: Aabb) -> bool {
+        hitbox.distance_squared_to_point(self.eye_position())
+            < MAX_REACH_DISTANCE * MAX_REACH_DISTANCE
+    }
+
     /// Returns the position this client was in at the end of the previous tick.
     pub fn old_position(&self) -> DVec3 {
         self.old_position
@@ -250,7 +607,7 @@ impl Client {
 
     /// Gets a [`ChunkView`] representing the chunks this client can see.
     pub fn view(&self) -> ChunkView {
-        ChunkView::new(ChunkPos::from_dvec3(self.position), self.view_distance)
+        ChunkView::new(ChunkPos::from_dvec3(self.position), self.view_distance())
     }
 
     pub fn old_view(&self) -> ChunkView {
@@ -260,11 +617,27 @@ impl Client {
         )
     }
 
+    /// Sets the velocity of this client in meters per second, replacing the
+    /// velocity the client is moving with.
+    ///
+    /// The velocity is sent at the end of the tick, after the client is
+    /// teleported by [`Client::set_position`], because teleports reset the
+    /// velocity of the client. Velocity added with [`Client::add_velocity`]
+    /// earlier in the tick is discarded.
     pub fn set_velocity(&mut self, velocity: impl Into<Vec3>) {
-        self.enc.write_packet(&SetEntityVelocity {
-            entity_id: VarInt(0),
-            velocity: velocity_to_packet_units(velocity.into()),
-        });
+        self.velocity_to_set = Some(velocity.into());
+        self.velocity_to_add = Vec3::ZERO;
+    }
+
+    /// Adds to the velocity of this client in meters per second, like the
+    /// knockback of an explosion or a hit.
+    ///
+    /// Unlike [`Client::set_velocity`], the movement of the client is kept, so
+    /// knockback from several sources in the same tick stacks. The sum is sent
+    /// at the end of the tick, after the velocity set with
+    /// [`Client::set_velocity`] and after the client is teleported.
+    pub fn add_velocity(&mut self, velocity: impl Into<Vec3>) {
+        self.velocity_to_add += velocity.into();
     }
 
     /// Gets this client's yaw (in degrees).
@@ -312,6 +685,46 @@ impl Client {
         });
     }
 
+    /// Plays the sound of travelling through a nether portal and makes the
+    /// client's screen swirl with nausea for `ticks` ticks.
+    pub fn show_nether_portal_effect(&mut self, ticks: i32) {
+        self.write_packet(&WorldEvent {
+            event: PORTAL_TRAVEL_WORLD_EVENT,
+            location: BlockPos::at(self.position),
+            data: 0,
+            disable_relative_volume: false,
+        });
+
+        self.write_status_effect(NAUSEA_EFFECT_ID, ticks);
+    }
+
+    /// Blinds the client for `ticks` ticks. The client's view fades back in
+    /// during the last second.
+    pub fn fade_screen(&mut self, ticks: i32) {
+        self.write_status_effect(BLINDNESS_EFFECT_ID, ticks);
+    }
+
+    fn write_status_effect(&mut self, effect_id: i32, ticks: i32) {
+        self.write_packet(&EntityEffect {
+            entity_id: VarInt(0),
+            effect_id: VarInt(effect_id),
+            amplifier: 0,
+            duration: VarInt(ticks),
+            flags: EntityEffectFlags::new(),
+            factor_codec: None,
+        });
+    }
+
+    fn show_teleport_effect(&mut self, effect: TeleportEffect) {
+        match effect {
+            TeleportEffect::NetherPortal => {
+                self.show_nether_portal_effect(NETHER_PORTAL_EFFECT_TICKS)
+            }
+            TeleportEffect::Credits => self.win_game(true),
+            TeleportEffect::Fade { ticks } => self.fade_screen(ticks),
+        }
+    }
+
     pub fn has_respawn_screen(&self) -> bool {
         self.has_respawn_screen
     }
@@ -347,19 +760,100 @@ impl Client {
     /// The current view distance of this client measured in chunks. The client
     /// will not be able to see chunks and entities past this distance.
     ///
+    /// This is the distance set with [`Self::set_view_distance`], limited by
+    /// [`Self::max_view_distance`] and the [maximum of the client's
+    /// instance](Instance::set_max_view_distance).
+    ///
     /// The result is in `2..=32`.
     pub fn view_distance(&self) -> u8 {
         self.view_distance
+            .min(self.max_view_distance)
+            .min(self.instance_max_view_distance)
     }
 
     /// Sets the view distance. The client will not be able to see chunks and
-    /// entities past this distance.
+    /// entities past this distance. This is set to the render distance in the
+    /// client's settings whenever they change.
     ///
     /// The new view distance is measured in chunks and is clamped to `2..=32`.
     pub fn set_view_distance(&mut self, dist: u8) {
         self.view_distance = dist.clamp(2, 32);
     }
 
+    /// Gets the maximum view distance of this client set by
+    /// [`Self::set_max_view_distance`].
+    pub fn max_view_distance(&self) -> u8 {
+        self.max_view_distance
+    }
+
+    /// Limits the view distance of this client independently of the render
+    /// distance in its settings. Chunks and entities past this distance are
+    /// not sent to the client.
+    ///
+    /// The distance is measured in chunks and is clamped to `2..=32`. The
+    /// default is `32`.
+    pub fn set_max_view_distance(&mut self, dist: u8) {
+        self.max_view_distance = dist.clamp(2, 32);
+    }
+
+    /// The current simulation distance of this client measured in chunks.
+    /// The client only ticks entities, such as itself, within this distance.
+    ///
+    /// This is the distance set with [`Self::set_simulation_distance`],
+    /// limited by the [view distance](Self::view_distance) and the [maximum of
+    /// the client's instance](Instance::set_max_simulation_distance).
+    ///
+    /// The result is in `2..=32`.
+    pub fn simulation_distance(&self) -> u8 {
+        self.simulation_distance
+            .min(self.view_distance())
+            .min(self.instance_max_simulation_distance)
+    }
+
+    /// Sets the simulation distance of this client.
+    ///
+    /// The distance is measured in chunks and is clamped to `2..=32`. The
+    /// default is [`DEFAULT_SIMULATION_DISTANCE`].
+    pub fn set_simulation_distance(&mut self, dist: u8) {
+        self.simulation_distance = dist.clamp(2, 32);
+    }
+
+    /// Gets the time of day shown to this client set by
+    /// [`Self::set_time_override`].
+    pub fn time_override(&self) -> Option<i64> {
+        self.time_override
+    }
+
+    /// Fixes the time of day shown to this client, without affecting other
+    /// clients. The time is in ticks, where 6000 is noon and 18000 is
+    /// midnight.
+    ///
+    /// The time is sent again every second, so it doesn't drift even if the
+    /// client tries to advance its clock. When the override is removed with
+    /// `None`, the client's clock starts running again from the overridden
+    /// time.
+    pub fn set_time_override(&mut self, time_of_day: Option<i64>) {
+        self.time_override = time_of_day.map(|t| t.rem_euclid(24000));
+    }
+
+    /// Gets the weather shown to this client set by
+    /// [`Self::set_weather_override`].
+    pub fn weather_override(&self) -> Option<Weather> {
+        self.weather_override
+    }
+
+    /// Sets the weather shown to this client, without affecting other clients.
+    /// The levels are clamped to `0.0..=1.0`.
+    ///
+    /// Like the time override, the weather is sent again every second. When
+    /// the override is removed with `None`, the weather is cleared.
+    pub fn set_weather_override(&mut self, weather: Option<Weather>) {
+        self.weather_override = weather.map(|w| Weather {
+            rain: w.rain.clamp(0.0, 1.0),
+            thunder: w.thunder.clamp(0.0, 1.0),
+        });
+    }
+
     /// Gets the last death location of this client. The client will see
     /// `minecraft:recovery_compass` items point at the returned position.
     ///
@@ -375,11 +869,37 @@ impl Client {
         self.game_mode
     }
 
+    /// Gets the game mode the client had before the last call to
+    /// [`Self::set_game_mode`], if any.
+    pub fn previous_game_mode(&self) -> Option<GameMode> {
+        self.previous_game_mode
+    }
+
     /// Sets the client's game mode.
+    ///
+    /// Like in vanilla, the client's abilities are updated to match the new
+    /// game mode: flight is allowed in creative and spectator mode, and the
+    /// client stops flying when switching to survival or adventure mode.
+    /// Leaving spectator mode also resets the client's camera.
+    ///
+    /// A [`GameModeChanged`] event is sent at the end of the tick.
     pub fn set_game_mode(&mut self, game_mode: GameMode) {
         if self.game_mode != game_mode {
+            self.previous_game_mode = Some(self.game_mode);
             self.game_mode = game_mode;
 
+            match game_mode {
+                GameMode::Survival | GameMode::Adventure => self.flying = false,
+                GameMode::Creative => {}
+                GameMode::Spectator => self.flying = true,
+            }
+
+            if game_mode != GameMode::Spectator {
+                self.set_camera(None);
+            }
+
+            self.abilities_modified = true;
+
             if !self.is_new {
                 self.write_packet(&GameEvent {
                     kind: GameEventKind::ChangeGameMode,
@@ -389,6 +909,153 @@ impl Client {
         }
     }
 
+    /// Whether or not the client may fly in survival and adventure mode.
+    /// Clients in creative and spectator mode can always fly.
+    pub fn allow_flying(&self) -> bool {
+        self.allow_flying
+    }
+
+    /// Sets whether or not the client may fly in survival and adventure mode.
+    /// Clients start flying by double tapping the jump key, which sends a
+    /// [`StartFlying`](event::StartFlying) event. Disallowing flying stops a
+    /// flying client.
+    ///
+    /// The abilities are sent again even if they did not change, which can be
+    /// used to stop a client from flying while it is still allowed to.
+    pub fn set_allow_flying(&mut self, allow: bool) {
+        self.allow_flying = allow;
+        self.abilities_modified = true;
+
+        if !allow && !self.may_fly() {
+            self.flying = false;
+        }
+    }
+
+    /// Whether or not the client is allowed to fly, either by its game mode or
+    /// by [`Client::set_allow_flying`].
+    fn may_fly(&self) -> bool {
+        self.allow_flying || matches!(self.game_mode, GameMode::Creative | GameMode::Spectator)
+    }
+
+    /// Whether or not the client is flying. This is updated when the client
+    /// starts or stops flying on its own.
+    pub fn is_flying(&self) -> bool {
+        self.flying
+    }
+
+    /// Starts or stops the flight of the client.
+    pub fn set_flying(&mut self, flying: bool) {
+        if self.flying != flying {
+            self.flying = flying;
+            self.abilities_modified = true;
+        }
+    }
+
+    /// Gets the flying speed of the client.
+    pub fn flying_speed(&self) -> f32 {
+        self.flying_speed
+    }
+
+    /// Sets the flying speed of the client. The default is
+    /// [`DEFAULT_FLYING_SPEED`].
+    pub fn set_flying_speed(&mut self, speed: f32) {
+        if self.flying_speed != speed {
+            self.flying_speed = speed;
+            self.abilities_modified = true;
+        }
+    }
+
+    /// Gets the field of view modifier of the client.
+    pub fn fov_modifier(&self) -> f32 {
+        self.fov_modifier
+    }
+
+    /// Sets the field of view modifier of the client. The default is
+    /// [`DEFAULT_FOV_MODIFIER`].
+    ///
+    /// This is the walking speed of the vanilla abilities, which the client
+    /// only uses to widen or narrow its field of view. The speed the client
+    /// actually walks with is not changed.
+    pub fn set_fov_modifier(&mut self, modifier: f32) {
+        if self.fov_modifier != modifier {
+            self.fov_modifier = modifier;
+            self.abilities_modified = true;
+        }
+    }
+
+    /// Whether or not the client is invulnerable. Clients in creative and
+    /// spectator mode are always invulnerable.
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable || matches!(self.game_mode, GameMode::Creative | GameMode::Spectator)
+    }
+
+    /// Makes the client invulnerable in survival and adventure mode. Attacks
+    /// by the combat plugin are ignored, and the client does not show damage
+    /// it would take on its own, like fall damage.
+    pub fn set_invulnerable(&mut self, invulnerable: bool) {
+        if self.invulnerable != invulnerable {
+            self.invulnerable = invulnerable;
+            self.abilities_modified = true;
+        }
+    }
+
+    /// Gets the entity the client is viewing the world from, or `None` if the
+    /// client is viewing the world from its own eyes.
+    pub fn camera(&self) -> Option<Entity> {
+        self.camera
+    }
+
+    /// Makes the client view the world from the eyes of the given entity, like
+    /// spectators do in vanilla when they click on an entity. `None` returns
+    /// the camera to the client itself.
+    ///
+    /// The entity must have the [`McEntity`] component and be loaded by the
+    /// client, or the client ignores the camera. The camera is reset when the
+    /// entity is despawned and when the client leaves spectator mode. The
+    /// [`default_event_handler`] also resets it when the client starts
+    /// sneaking.
+    ///
+    /// [`default_event_handler`]: crate::client::event::default_event_handler
+    pub fn set_camera(&mut self, camera: Option<Entity>) {
+        if self.camera != camera {
+            self.camera = camera;
+            self.camera_modified = true;
+        }
+    }
+
+    /// Opens the editor for the sign at `pos` at the end of the tick, like after
+    /// placing a sign in vanilla. The text is sent as an [`UpdateSign`] event
+    /// when the client closes the editor, which the [`SignPlugin`] writes to
+    /// the sign.
+    ///
+    /// [`UpdateSign`]: crate::client::event::UpdateSign
+    /// [`SignPlugin`]: crate::sign::SignPlugin
+    pub fn open_sign_editor(&mut self, pos: impl Into<BlockPos>) {
+        self.editing_sign = Some(pos.into());
+        self.open_sign_editor = true;
+    }
+
+    /// Gets the position of the sign the client was allowed to edit with
+    /// [`Client::open_sign_editor`], until the client is done editing it.
+    pub fn editing_sign(&self) -> Option<BlockPos> {
+        self.editing_sign
+    }
+
+    pub(crate) fn stop_editing_sign(&mut self) {
+        self.editing_sign = None;
+    }
+
+    /// Shows the cooldown animation over all items of the given kind in the
+    /// client's inventory for `ticks` ticks. A cooldown of zero ticks removes
+    /// the animation. This is only visual and does not stop the client from
+    /// using the items.
+    pub fn set_item_cooldown(&mut self, item: ItemKind, ticks: i32) {
+        self.write_packet(&SetCooldown {
+            item_id: VarInt(item.to_raw() as i32),
+            cooldown_ticks: VarInt(ticks),
+        });
+    }
+
     /// Sets the client's OP level.
     pub fn set_op_level(&mut self, op_level: u8) {
         self.op_level = op_level;
@@ -408,6 +1075,48 @@ impl Client {
         self.op_level
     }
 
+    /// Sets whether or not packets encoded for this client individually are
+    /// compressed. Packet data shared between clients, such as chunks, is
+    /// compressed according to the server's configuration regardless.
+    ///
+    /// Disabling compression saves CPU time for clients on fast connections,
+    /// such as those connected through a proxy on the same machine. Has no
+    /// effect if compression is disabled on the server.
+    pub fn set_compression_enabled(&mut self, enabled: bool) {
+        self.compression_enabled = enabled;
+
+        // The client expects the compressed packet format once compression is
+        // set up, so packets are sent uncompressed in that format instead.
+        self.enc.set_compression(if enabled {
+            self.compression_threshold
+        } else {
+            self.compression_threshold.map(|_| u32::MAX)
+        });
+    }
+
+    /// Returns whether or not packets encoded for this client individually are
+    /// compressed. See [`Self::set_compression_enabled`].
+    pub fn is_compression_enabled(&self) -> bool {
+        self.compression_enabled && self.compression_threshold.is_some()
+    }
+
+    /// Sets the zlib compression level from 0 (fastest) to 9 (smallest) for
+    /// packets encoded for this client individually. Values above 9 are
+    /// clamped.
+    ///
+    /// The initial level is [`ServerPlugin::compression_level`].
+    ///
+    /// [`ServerPlugin::compression_level`]: crate::config::ServerPlugin::compression_level
+    pub fn set_compression_level(&mut self, level: u32) {
+        self.enc.set_compression_level(level);
+    }
+
+    /// Gets the zlib compression level of packets encoded for this client
+    /// individually.
+    pub fn compression_level(&self) -> u32 {
+        self.enc.compression_level()
+    }
+
     /// Sets the last death location. The client will see
     /// `minecraft:recovery_compass` items point at the provided position.
     /// If the client's current dimension differs from the provided
@@ -427,10 +1136,40 @@ impl Client {
         });
     }
 
+    /// Returns the round-trip time of the client's connection in
+    /// milliseconds, or `-1` if it hasn't been measured yet.
+    ///
+    /// The ping is measured with the keepalives sent every
+    /// [`ServerPlugin::keepalive_interval`] and smoothed over several
+    /// measurements like vanilla.
+    ///
+    /// [`ServerPlugin::keepalive_interval`]: crate::config::ServerPlugin::keepalive_interval
     pub fn ping(&self) -> i32 {
         self.ping
     }
 
+    /// Returns the number of packets sent to the client since it joined.
+    pub fn packets_sent(&self) -> u64 {
+        self.packets_sent
+    }
+
+    /// Returns the number of packets received from the client since it
+    /// joined.
+    pub fn packets_received(&self) -> u64 {
+        self.packets_received
+    }
+
+    /// Returns the number of bytes sent to the client since it joined,
+    /// after compression and encryption.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Returns the number of bytes received from the client since it joined.
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
     /// The item that the client thinks it's holding under the mouse
     /// cursor. Only relevant when the client has an open inventory.
     pub fn cursor_item(&self) -> Option<&ItemStack> {
@@ -454,6 +1193,13 @@ impl Client {
         &mut self.player_data
     }
 
+    /// Sends the flags of the client's own player data at the end of the tick
+    /// even if they are unchanged. The client sets some flags on its own, such
+    /// as `fall_flying`, which the server has to overwrite.
+    pub(crate) fn resend_player_flags(&mut self) {
+        self.resend_player_flags = true;
+    }
+
     /// Sends a system message to the player which is visible in the chat. The
     /// message is only visible to this client.
     pub fn send_message(&mut self, msg: impl Into<Text>) {
@@ -463,6 +1209,36 @@ impl Client {
         });
     }
 
+    /// Queues a message which is translated into the client's language. The
+    /// [`msg!`](crate::msg) macro is a more convenient way to call this.
+    ///
+    /// Requires the [`LocalizationPlugin`]. Messages are discarded without it.
+    ///
+    /// [`LocalizationPlugin`]: crate::localization::LocalizationPlugin
+    pub fn send_localized_message(&mut self, key: impl Into<Cow<'static, str>>, args: Vec<Text>) {
+        self.localized_messages.push((key.into(), args));
+    }
+
+    pub(crate) fn has_localized_messages(&self) -> bool {
+        !self.localized_messages.is_empty()
+    }
+
+    pub(crate) fn take_localized_messages(&mut self) -> Vec<LocalizedMessage> {
+        std::mem::take(&mut self.localized_messages)
+    }
+
+    /// Gets the client's language as sent in its settings, e.g. `en_us`. This
+    /// is [`DEFAULT_FALLBACK_LOCALE`] until the settings are received.
+    ///
+    /// [`DEFAULT_FALLBACK_LOCALE`]: crate::localization::DEFAULT_FALLBACK_LOCALE
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    pub fn set_locale(&mut self, locale: impl Into<Box<str>>) {
+        self.locale = locale.into();
+    }
+
     pub fn send_plugin_message(&mut self, channel: Ident<&str>, data: &[u8]) {
         self.write_packet(&PluginMessageS2c {
             channel,
@@ -470,6 +1246,64 @@ impl Client {
         });
     }
 
+    /// Sets whether or not the [`McEntity`] `entity` is outlined for this client
+    /// only, regardless of [`McEntity::is_glowing`]. The color of the outline
+    /// is the color of the entity's team (see [`Team`]). The entity is no longer
+    /// outlined once it is despawned.
+    ///
+    /// [`Team`]: crate::team::Team
+    pub fn set_entity_glowing(&mut self, entity: Entity, glowing: bool) {
+        if glowing {
+            self.glowing_entities.insert(entity);
+        } else {
+            self.glowing_entities.remove(&entity);
+        }
+    }
+
+    /// Returns the number of chunks in view which are waiting to be sent to
+    /// the client. See [`ServerPlugin::max_chunk_sends_per_tick`].
+    ///
+    /// [`ServerPlugin::max_chunk_sends_per_tick`]: crate::config::ServerPlugin::max_chunk_sends_per_tick
+    pub fn pending_chunk_count(&self) -> usize {
+        self.pending_chunks.len()
+    }
+
+    /// Sends the chunk at `pos` again to this client at the end of the tick.
+    /// This discards any changes the client was sent separately, such as
+    /// blocks overridden for this client, without reloading the rest of the
+    /// view.
+    ///
+    /// Nothing is sent if the chunk is not loaded or not in view of the client,
+    /// or if the client was not sent the chunk yet.
+    ///
+    /// To resend a chunk to all clients in view of it, see
+    /// [`Instance::resend_chunk`].
+    pub fn resend_chunk(&mut self, pos: impl Into<ChunkPos>) {
+        self.chunks_to_resend.push(pos.into());
+    }
+
+    /// Whether or not the entity is outlined for this client with
+    /// [`Client::set_entity_glowing`].
+    pub fn is_entity_glowing(&self, entity: Entity) -> bool {
+        self.glowing_entities.contains(&entity)
+    }
+
+    /// Hands the client off to the server named `server` of the proxy the
+    /// client is connected through.
+    ///
+    /// This sends the `Connect` message on the BungeeCord plugin channel,
+    /// which is understood by BungeeCord, Waterfall and Velocity. The message
+    /// has no effect if the server is not behind a proxy (see
+    /// [`ConnectionMode`]). The native transfer packet was only added in
+    /// Minecraft 1.20.5 and is not available in the protocol version
+    /// supported by Valence.
+    ///
+    /// [`ConnectionMode`]: crate::config::ConnectionMode
+    pub fn connect_to_server(&mut self, server: &str) {
+        let data = bungeecord_message(&["Connect", server]);
+        self.send_plugin_message(Ident::new(BUNGEECORD_CHANNEL).unwrap(), &data);
+    }
+
     /// Get the slot id in the player's inventory that the client says it's
     /// holding.
     pub fn held_item_slot(&self) -> u16 {
@@ -610,6 +1444,69 @@ impl WritePacket for Client {
     fn write_packet_bytes(&mut self, bytes: &[u8]) {
         self.enc.write_packet_bytes(bytes)
     }
+
+    fn write_shared_packet_bytes(&mut self, bytes: &Bytes) {
+        self.enc.write_shared_packet_bytes(bytes)
+    }
+}
+
+/// Sent when a client is disconnected because it did not respond to a
+/// keepalive in time. Clients which time out are disconnected like any other,
+/// but this event is only sent for timeouts.
+///
+/// The identity of the client is included because the client entity is
+/// usually despawned by the time the event is read.
+#[derive(Clone, Debug)]
+pub struct ClientTimedOut {
+    pub client: Entity,
+    pub username: Username<String>,
+    pub uuid: Uuid,
+    pub ip: IpAddr,
+}
+
+/// Sent at the end of the tick when the game mode of a client was changed with
+/// [`Client::set_game_mode`]. Requests of clients to change their own game
+/// mode are sent as [`RequestGameMode`] events.
+///
+/// [`RequestGameMode`]: crate::client::event::RequestGameMode
+#[derive(Clone, Debug)]
+pub struct GameModeChanged {
+    pub client: Entity,
+    pub old: GameMode,
+    pub new: GameMode,
+}
+
+/// Sent when a client did not confirm a teleport within
+/// [`TELEPORT_TIMEOUT_TICKS`]. Movement of the client is still ignored until
+/// it confirms the teleport, so the teleport can be made again or the client
+/// disconnected.
+#[derive(Clone, Debug)]
+pub struct TeleportTimedOut {
+    pub client: Entity,
+    pub teleport_id: u32,
+}
+
+/// A teleport the client has yet to confirm.
+struct PendingTeleport {
+    id: u32,
+    sent_tick: i64,
+    /// If the [`TeleportTimedOut`] event was sent.
+    timed_out: bool,
+}
+
+/// The effect shown by [`Client::teleport_with_effects`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TeleportEffect {
+    /// The sound and screen swirl of a nether portal.
+    NetherPortal,
+    /// The end credits, like after leaving the end. The client asks to be
+    /// respawned once the credits are closed, which is sent as a
+    /// [`PerformRespawn`] event.
+    ///
+    /// [`PerformRespawn`]: crate::client::event::PerformRespawn
+    Credits,
+    /// Blinds the client for the given number of ticks.
+    Fade { ticks: i32 },
 }
 
 /// A system for adding [`Despawned`] components to disconnected clients.
@@ -621,21 +1518,148 @@ pub fn despawn_disconnected_clients(mut commands: Commands, clients: Query<(Enti
     }
 }
 
-pub(crate) fn update_clients(
-    server: Res<Server>,
-    mut clients: Query<(Entity, &mut Client, Option<&McEntity>)>,
-    instances: Query<&Instance>,
-    entities: Query<&McEntity>,
+/// The settings most recently sent by a client. The component is added to
+/// client entities when the first [`UpdateSettings`] event is received and
+/// is only changed when the settings differ, so `Changed<ClientSettings>`
+/// can be used to react to changes.
+///
+/// To send messages in the language of the client, see the
+/// [`localization`](crate::localization) module.
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct ClientSettings {
+    /// e.g. `en_us`
+    pub locale: Box<str>,
+    /// The client side render distance, in chunks.
+    ///
+    /// The value is always in `2..=32`.
+    pub view_distance: u8,
+    pub chat_mode: ChatMode,
+    /// `true` if the client has chat colors enabled, `false` otherwise.
+    pub chat_colors: bool,
+    pub displayed_skin_parts: DisplayedSkinParts,
+    pub main_hand: MainHand,
+    pub enable_text_filtering: bool,
+    pub allow_server_listings: bool,
+}
+
+impl From<&UpdateSettings> for ClientSettings {
+    fn from(event: &UpdateSettings) -> Self {
+        Self {
+            locale: event.locale.clone(),
+            view_distance: event.view_distance,
+            chat_mode: event.chat_mode,
+            chat_colors: event.chat_colors,
+            displayed_skin_parts: event.displayed_skin_parts,
+            main_hand: event.main_hand,
+            enable_text_filtering: event.enable_text_filtering,
+            allow_server_listings: event.allow_server_listings,
+        }
+    }
+}
+
+pub(crate) fn update_client_settings(
+    mut commands: Commands,
+    mut events: EventReader<UpdateSettings>,
+    mut clients: Query<Option<&mut ClientSettings>, With<Client>>,
 ) {
-    // TODO: what batch size to use?
-    clients.par_for_each_mut(16, |(entity_id, mut client, self_entity)| {
-        if !client.is_disconnected() {
+    for event in events.iter() {
+        let Ok(settings) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        let new_settings = ClientSettings::from(event);
+
+        match settings {
+            Some(mut settings) => {
+                if *settings != new_settings {
+                    *settings = new_settings;
+                }
+            }
+            None => {
+                commands.entity(event.client).insert(new_settings);
+            }
+        }
+    }
+}
+
+/// Knocks back the clients near the explosions created this tick with
+/// [`Instance::explode`].
+pub(crate) fn apply_explosion_knockback(
+    mut clients: Query<&mut Client>,
+    instances: Query<&Instance>,
+) {
+    for mut client in &mut clients {
+        if matches!(client.game_mode, GameMode::Creative | GameMode::Spectator) {
+            continue;
+        }
+
+        let Ok(instance) = instances.get(client.instance) else {
+            continue
+        };
+
+        let hitbox = Aabb::from_bottom_size(client.position, [0.6, 1.8, 0.6]);
+
+        for explosion in instance.explosions() {
+            if !explosion.options.knockback {
+                continue;
+            }
+
+            if let Some(impact) = explosion.impact(instance, client.position, hitbox) {
+                client.add_velocity(impact.knockback);
+            }
+        }
+    }
+}
+
+pub(crate) fn update_clients(
+    server: Res<Server>,
+    mut clients: Query<(Entity, &mut Client, Option<&McEntity>)>,
+    instances: Query<&Instance>,
+    entities: Query<&McEntity>,
+    all_entities: Query<(Entity, &McEntity)>,
+    mut first_pending_client: Local<usize>,
+    mut timed_out: EventWriter<ClientTimedOut>,
+    mut game_mode_changed: EventWriter<GameModeChanged>,
+    mut teleport_timed_out: EventWriter<TeleportTimedOut>,
+    timings: Res<TickTimings>,
+) {
+    let update_timer = timings.time("client updates");
+
+    // Few entities have a name visibility range, passengers, a new leash
+    // holder or a newly hooked entity, so they are collected once here instead
+    // of every client checking all the entities in view for them.
+    let mut overrides = EntityOverrides::default();
+
+    for (id, entity) in &all_entities {
+        if entity.name_visibility_range().is_some() && entity.kind() != EntityKind::Player {
+            overrides.name_tags.push(id);
+        }
+
+        if !entity.passengers().is_empty() {
+            overrides.vehicles.push(id);
+        }
+
+        if entity.leash_holder().is_some() && entity.leash_holder_modified() {
+            overrides.leashes.push(id);
+        }
+
+        if entity.fishing_bobber_hooked().is_some() && entity.fishing_bobber_hooked_modified() {
+            overrides.hooks.push(id);
+        }
+    }
+
+    // TODO: what batch size to use?
+    clients.par_for_each_mut(16, |(entity_id, mut client, self_entity)| {
+        if !client.is_disconnected() {
+            let _span = trace_span!("update_client", username = %client.username).entered();
+
             if let Err(e) = update_one_client(
                 &mut client,
                 self_entity,
                 entity_id,
                 &instances,
                 &entities,
+                &overrides,
                 &server,
             ) {
                 client.write_packet(&DisconnectPlay {
@@ -651,26 +1675,207 @@ pub(crate) fn update_clients(
             }
         }
 
+        if client.is_new {
+            // The game mode the client joins with is not a change.
+            client.old_game_mode = client.game_mode;
+        }
+
         client.is_new = false;
     });
+
+    for (entity, mut client, _) in &mut clients {
+        if client.timed_out {
+            client.timed_out = false;
+
+            timed_out.send(ClientTimedOut {
+                client: entity,
+                username: client.username.clone(),
+                uuid: client.uuid,
+                ip: client.ip,
+            });
+        }
+
+        if client.game_mode != client.old_game_mode {
+            game_mode_changed.send(GameModeChanged {
+                client: entity,
+                old: client.old_game_mode,
+                new: client.game_mode,
+            });
+
+            client.old_game_mode = client.game_mode;
+        }
+
+        for teleport in &mut client.pending_teleports {
+            if !teleport.timed_out
+                && server.current_tick() - teleport.sent_tick >= TELEPORT_TIMEOUT_TICKS
+            {
+                teleport.timed_out = true;
+
+                teleport_timed_out.send(TeleportTimedOut {
+                    client: entity,
+                    teleport_id: teleport.id,
+                });
+            }
+        }
+    }
+
+    let chunk_limits = ChunkSendLimits::new(&server);
+
+    if chunk_limits.is_limited() {
+        send_pending_chunks(
+            clients.iter_mut().map(|(_, client, _)| client.into_inner()),
+            &instances,
+            &chunk_limits,
+            &mut first_pending_client,
+        );
+    }
+
+    drop(update_timer);
+
+    let _flush_timer = timings.time("packet flush");
+
+    clients.par_for_each_mut(16, |(_, mut client, _)| {
+        let _span = trace_span!("flush_client", username = %client.username).entered();
+
+        // Check that the packets fit before they are taken from the encoder,
+        // since taking them encrypts them. Urgent packets don't count towards
+        // the capacity.
+        let urgent_len = std::mem::take(&mut client.urgent_len);
+        let normal_len = client.enc.len() - urgent_len;
+
+        if !client.is_disconnected()
+            && normal_len > 0
+            && client.conn.queued_len() + normal_len > server.outgoing_capacity()
+        {
+            warn!(
+                username = %client.username,
+                uuid = %client.uuid,
+                ip = %client.ip,
+                "outgoing packet buffer is full"
+            );
+
+            client.enc.clear();
+            client.kick("Your connection can't keep up with the server");
+        }
+
+        client.packets_sent += client.enc.packet_count() as u64;
+        let segments = client.enc.take_vectored();
+        client.bytes_sent += segments.iter().map(|s| s.len() as u64).sum::<u64>();
+
+        // Packets written to disconnected clients, such as the reason they were
+        // kicked for, are still sent.
+        if client.is_disconnected() {
+            if !segments.is_empty() {
+                let _ = client.conn.send_urgent_vectored(segments);
+            }
+
+            return;
+        }
+
+        // The other packets fit, so the buffer only overflows by the urgent
+        // packets. They are sent in order with the rest so that encryption and
+        // the order of packets are preserved.
+        let res = if urgent_len > 0 {
+            client.conn.send_urgent_vectored(segments)
+        } else {
+            client.conn.try_send_vectored(segments)
+        };
+
+        if let Err(e) = res {
+            client.is_disconnected = true;
+            warn!(
+                username = %client.username,
+                uuid = %client.uuid,
+                ip = %client.ip,
+                "failed to flush packet queue: {e:#}"
+            );
+        }
+    });
+}
+
+/// The plugin channel used to send messages to a BungeeCord compatible proxy.
+const BUNGEECORD_CHANNEL: &str = "bungeecord:main";
+
+/// Encodes the arguments of a BungeeCord plugin message as Java's
+/// `DataOutput::writeUTF` does: a big-endian `u16` length followed by the
+/// string.
+fn bungeecord_message(args: &[&str]) -> Vec<u8> {
+    let mut data = vec![];
+
+    for arg in args {
+        let len = arg.len().min(u16::MAX as usize);
+        data.extend_from_slice(&(len as u16).to_be_bytes());
+        data.extend_from_slice(&arg.as_bytes()[..len]);
+    }
+
+    data
+}
+
+/// Returns the packet setting the abilities of the client, which are the
+/// abilities vanilla gives players in its game mode combined with the ones set
+/// on the client.
+fn abilities_packet(client: &Client) -> PlayerAbilitiesS2c {
+    PlayerAbilitiesS2c {
+        flags: PlayerAbilitiesFlags::new()
+            .with_invulnerable(client.is_invulnerable())
+            .with_flying(client.flying)
+            .with_allow_flying(client.may_fly())
+            .with_instant_break(client.game_mode == GameMode::Creative),
+        flying_speed: client.flying_speed,
+        fov_modifier: client.fov_modifier,
+    }
+}
+
+/// The entities which might need per-client metadata, passenger or leash
+/// overrides in [`update_one_client`].
+#[derive(Default)]
+struct EntityOverrides {
+    /// Entities with a name visibility range.
+    name_tags: Vec<Entity>,
+    /// Entities with passengers.
+    vehicles: Vec<Entity>,
+    /// Entities whose leash holder was changed this tick.
+    leashes: Vec<Entity>,
+    /// Fishing bobbers whose hooked entity was changed this tick.
+    hooks: Vec<Entity>,
 }
 
 #[inline]
 fn update_one_client(
     client: &mut Client,
-    _self_entity: Option<&McEntity>,
-    _self_id: Entity,
+    self_entity: Option<&McEntity>,
+    self_id: Entity,
     instances: &Query<&Instance>,
     entities: &Query<&McEntity>,
+    overrides: &EntityOverrides,
     server: &Server,
 ) -> anyhow::Result<()> {
     let Ok(instance) = instances.get(client.instance) else {
         bail!("client is in a nonexistent instance");
     };
 
+    let self_protocol_id = self_entity.map(|e| e.protocol_id());
+
+    client.instance_max_view_distance = instance.max_view_distance().unwrap_or(32);
+    client.instance_max_simulation_distance = instance.max_simulation_distance().unwrap_or(32);
+
     // Send the login (play) packet and other initial packets. We defer this until
     // now so that the user can set the client's initial location, game
     // mode, etc.
+    let dimension_changed = instances
+        .get(client.old_instance)
+        .map_or(true, |old| old.dimension() != instance.dimension());
+
+    // Transfers to an instance of another dimension need a respawn. Transfers
+    // within the same dimension only reload the chunks and entities below.
+    if client.needs_transfer {
+        client.needs_transfer = false;
+        client.needs_respawn |= dimension_changed;
+    }
+
+    // The client forgets the weather when it joins or respawns.
+    let respawned = client.is_new || client.needs_respawn;
+
     if client.is_new {
         client.needs_respawn = false;
 
@@ -693,7 +1898,7 @@ fn update_one_client(
             entity_id: 0, // ID 0 is reserved for clients.
             is_hardcore: client.is_hardcore,
             game_mode: client.game_mode,
-            previous_game_mode: -1,
+            previous_game_mode: client.previous_game_mode.map_or(-1, |m| m as i8),
             dimension_names,
             registry_codec: Cow::Borrowed(server.registry_codec()),
             dimension_type_name: dimension_name,
@@ -701,7 +1906,7 @@ fn update_one_client(
             hashed_seed: 42,
             max_players: VarInt(0), // Unused
             view_distance: VarInt(client.view_distance() as i32),
-            simulation_distance: VarInt(16),
+            simulation_distance: VarInt(client.simulation_distance() as i32),
             reduced_debug_info: false,
             enable_respawn_screen: client.has_respawn_screen,
             is_debug: false,
@@ -709,6 +1914,10 @@ fn update_one_client(
             last_death_location,
         })?;
 
+        let abilities = abilities_packet(client);
+        client.enc.append_packet(&abilities)?;
+        client.abilities_modified = false;
+
         /*
         // TODO: enable all the features?
         send.append_packet(&FeatureFlags {
@@ -716,10 +1925,16 @@ fn update_one_client(
         })?;
         */
     } else {
-        if client.view_distance != client.old_view_distance {
+        if client.view_distance() != client.old_view_distance {
             // Change the render distance fog.
             client.enc.append_packet(&SetRenderDistance {
-                view_distance: VarInt(client.view_distance.into()),
+                view_distance: VarInt(client.view_distance().into()),
+            })?;
+        }
+
+        if client.simulation_distance() != client.old_simulation_distance {
+            client.enc.append_packet(&SetSimulationDistance {
+                simulation_distance: VarInt(client.simulation_distance().into()),
             })?;
         }
 
@@ -738,37 +1953,92 @@ fn update_one_client(
                 dimension_name,
                 hashed_seed: 0,
                 game_mode: client.game_mode,
-                previous_game_mode: -1,
+                previous_game_mode: client.previous_game_mode.map_or(-1, |m| m as i8),
                 is_debug: false,
                 is_flat: client.is_flat,
                 copy_metadata: true,
                 last_death_location,
             })?;
+
+            // The client resets its abilities to the ones of its game mode.
+            client.abilities_modified = true;
         }
     }
 
-    // Check if it's time to send another keepalive.
-    if server.current_tick() % (server.tps() * 10) == 0 {
-        if client.got_keepalive {
-            let id = rand::random();
-            client.enc.write_packet(&KeepAliveS2c { id });
+    // The client's clock and weather drift over time, so the overrides are sent
+    // periodically.
+    let resend_environment = respawned || server.current_tick() % server.tps() == 0;
 
-            client.got_keepalive = false;
-            client.last_keepalive_id = id;
-            client.keepalive_sent_time = Instant::now();
-        } else {
-            bail!("timed out (no keepalive response)");
+    if resend_environment || client.time_override != client.old_time_override {
+        if let Some(time) = client.time_override {
+            // A negative time of day stops the client's clock.
+            client.enc.write_packet(&UpdateTime {
+                world_age: server.current_tick(),
+                time_of_day: if time == 0 { -1 } else { -time },
+            });
+        } else if let Some(time) = client.old_time_override {
+            client.enc.write_packet(&UpdateTime {
+                world_age: server.current_tick(),
+                time_of_day: time,
+            });
+        }
+    }
+
+    if resend_environment || client.weather_override != client.old_weather_override {
+        if let Some(weather) = client.weather_override {
+            client.enc.write_packet(&GameEvent {
+                kind: if weather.rain > 0.0 {
+                    GameEventKind::BeginRaining
+                } else {
+                    GameEventKind::EndRaining
+                },
+                value: 0.0,
+            });
+
+            client.enc.write_packet(&GameEvent {
+                kind: GameEventKind::RainLevelChange,
+                value: weather.rain,
+            });
+
+            client.enc.write_packet(&GameEvent {
+                kind: GameEventKind::ThunderLevelChange,
+                value: weather.thunder,
+            });
+        } else if client.old_weather_override.is_some() {
+            client.enc.write_packet(&GameEvent {
+                kind: GameEventKind::EndRaining,
+                value: 0.0,
+            });
         }
     }
 
+    if !client.got_keepalive && client.keepalive_sent_time.elapsed() >= server.client_timeout() {
+        client.timed_out = true;
+        bail!("timed out (no keepalive response)");
+    }
+
+    // Check if it's time to send another keepalive.
+    let keepalive_interval_ticks =
+        (server.keepalive_interval().as_secs_f64() * server.tps() as f64).max(1.0) as i64;
+
+    if client.got_keepalive && server.current_tick() % keepalive_interval_ticks == 0 {
+        let id = rand::random();
+        client.write_urgent_packet(&KeepAliveS2c { id });
+
+        client.got_keepalive = false;
+        client.last_keepalive_id = id;
+        client.keepalive_sent_time = Instant::now();
+    }
+
     // Send instance-wide packet data.
-    client.enc.append_bytes(&instance.packet_buf);
+    client.enc.append_shared(instance.packet_bytes.clone());
 
     let old_view = client.old_view();
     let view = client.view();
 
-    // Make sure the center chunk is set before loading chunks!
-    if old_view.pos != view.pos {
+    // Make sure the center chunk is set before loading chunks! Respawning
+    // resets the center chunk.
+    if old_view.pos != view.pos || respawned {
         // TODO: does the client initialize the center chunk to (0, 0)?
         client.enc.write_packet(&SetCenterChunk {
             chunk_x: VarInt(view.pos.x),
@@ -794,15 +2064,20 @@ fn update_one_client(
 
                 // Send entity spawn packets for entities entering the client's view.
                 for &(id, src_pos) in &cell.incoming {
-                    if src_pos.map_or(true, |p| !old_view.contains(p)) {
-                        // The incoming entity originated from outside the view distance, so it
-                        // must be spawned.
-                        if let Ok(entity) = entities.get(id) {
+                    if let Ok(entity) = entities.get(id) {
+                        let entity_view = old_instance.entity_view(entity.kind(), old_view);
+
+                        // The incoming entity originated from outside the tracking range, so
+                        // it must be spawned.
+                        if entity_view.contains(pos)
+                            && src_pos.map_or(true, |p| !entity_view.contains(p))
+                        {
                             // Spawn the entity at the old position so that later relative entity
                             // movement packets will not set the entity to the wrong position.
                             entity.write_init_packets(
                                 &mut client.enc,
                                 entity.old_position(),
+                                self_protocol_id,
                                 &mut client.scratch,
                             );
                         }
@@ -811,10 +2086,14 @@ fn update_one_client(
 
                 // Send entity despawn packets for entities exiting the client's view.
                 for &(id, dest_pos) in &cell.outgoing {
-                    if dest_pos.map_or(true, |p| !old_view.contains(p)) {
-                        // The outgoing entity moved outside the view distance, so it must be
+                    if let Ok(entity) = entities.get(id) {
+                        let entity_view = old_instance.entity_view(entity.kind(), old_view);
+
+                        // The outgoing entity moved outside the tracking range, so it must be
                         // despawned.
-                        if let Ok(entity) = entities.get(id) {
+                        if entity_view.contains(pos)
+                            && dest_pos.map_or(true, |p| !entity_view.contains(p))
+                        {
                             client
                                 .entities_to_despawn
                                 .push(VarInt(entity.protocol_id()));
@@ -825,34 +2104,63 @@ fn update_one_client(
                 // Send all data in the chunk's packet buffer to this client. This will update
                 // entities in the cell, spawn or update the chunk in the cell, or send any
                 // other packet data that was added here by users.
-                client.enc.append_bytes(&cell.packet_buf);
+                if old_instance.has_entity_tracking_ranges() {
+                    append_tracked_cell_packets(
+                        &mut client.enc,
+                        cell,
+                        pos,
+                        old_view,
+                        old_instance,
+                        entities,
+                    );
+                } else {
+                    client.enc.append_shared(cell.packet_bytes.clone());
+                }
             }
         });
     }
 
+    // Chunks entering the view are queued and sent by `send_pending_chunks` if
+    // the chunks sent per tick are limited or chunk data can be held back for
+    // slow clients.
+    let defer_chunks = ChunkSendLimits::new(server).is_limited();
+
     // Was the client's instance changed?
     if client.old_instance != client.instance {
-        if let Ok(old_instance) = instances.get(client.old_instance) {
-            // TODO: only send unload packets when old dimension == new dimension, since the
-            //       client will do the unloading for us in that case?
-
+        // The client unloads everything by itself when it respawns in another
+        // dimension.
+        let client_unloaded = respawned && dimension_changed;
+
+        // Chunks of the old instance which were never sent.
+        let old_pending: FxHashSet<ChunkPos> = client.pending_chunks.drain(..).collect();
+
+        if let Some(old_instance) = instances
+            .get(client.old_instance)
+            .ok()
+            .filter(|_| !client_unloaded)
+        {
             // Unload all chunks and entities in the old view.
             old_view.for_each(|pos| {
                 if let Some(cell) = old_instance.partition.get(&pos) {
                     // Unload the chunk at this cell if it was loaded.
-                    if cell.chunk.is_some() {
+                    if cell.chunk.is_some() && !old_pending.contains(&pos) {
                         client.enc.write_packet(&UnloadChunk {
                             chunk_x: pos.x,
                             chunk_z: pos.z,
                         });
                     }
 
-                    // Unload all the entities in the cell.
+                    // Unload all the tracked entities in the cell.
                     for &id in &cell.entities {
                         if let Ok(entity) = entities.get(id) {
-                            client
-                                .entities_to_despawn
-                                .push(VarInt(entity.protocol_id()));
+                            if old_instance
+                                .entity_view(entity.kind(), old_view)
+                                .contains(pos)
+                            {
+                                client
+                                    .entities_to_despawn
+                                    .push(VarInt(entity.protocol_id()));
+                            }
                         }
                     }
                 }
@@ -864,24 +2172,31 @@ fn update_one_client(
             if let Some(cell) = instance.partition.get(&pos) {
                 // Load the chunk at this cell if there is one.
                 if let Some(chunk) = &cell.chunk {
-                    chunk.write_init_packets(
-                        &instance.info,
-                        pos,
-                        &mut client.enc,
-                        &mut client.scratch,
-                    );
+                    if defer_chunks {
+                        client.pending_chunks.push_back(pos);
+                    } else {
+                        chunk.write_init_packets(
+                            &instance.info,
+                            pos,
+                            &mut client.enc,
+                            &mut client.scratch,
+                        );
 
-                    chunk.mark_viewed();
+                        chunk.mark_viewed();
+                    }
                 }
 
-                // Load all the entities in this cell.
+                // Load all the tracked entities in this cell.
                 for &id in &cell.entities {
                     if let Ok(entity) = entities.get(id) {
-                        entity.write_init_packets(
-                            &mut client.enc,
-                            entity.position(),
-                            &mut client.scratch,
-                        );
+                        if instance.entity_view(entity.kind(), view).contains(pos) {
+                            entity.write_init_packets(
+                                &mut client.enc,
+                                entity.position(),
+                                self_protocol_id,
+                                &mut client.scratch,
+                            );
+                        }
                     }
                 }
             }
@@ -892,22 +2207,37 @@ fn update_one_client(
         // Unload chunks and entities in the old view and load chunks and entities in
         // the new view. We don't need to do any work where the old and new view
         // overlap.
+
+        // Chunks which left the view before they were sent.
+        let old_pending: FxHashSet<ChunkPos> = client
+            .pending_chunks
+            .iter()
+            .copied()
+            .filter(|&pos| !view.contains(pos))
+            .collect();
+
+        if !old_pending.is_empty() {
+            client.pending_chunks.retain(|&pos| view.contains(pos));
+        }
+
         old_view.diff_for_each(view, |pos| {
             if let Some(cell) = instance.partition.get(&pos) {
                 // Unload the chunk at this cell if it was loaded.
-                if cell.chunk.is_some() {
+                if cell.chunk.is_some() && !old_pending.contains(&pos) {
                     client.enc.write_packet(&UnloadChunk {
                         chunk_x: pos.x,
                         chunk_z: pos.z,
                     });
                 }
 
-                // Unload all the entities in the cell.
+                // Unload all the tracked entities in the cell.
                 for &id in &cell.entities {
                     if let Ok(entity) = entities.get(id) {
-                        client
-                            .entities_to_despawn
-                            .push(VarInt(entity.protocol_id()));
+                        if instance.entity_view(entity.kind(), old_view).contains(pos) {
+                            client
+                                .entities_to_despawn
+                                .push(VarInt(entity.protocol_id()));
+                        }
                     }
                 }
             }
@@ -917,28 +2247,91 @@ fn update_one_client(
             if let Some(cell) = instance.partition.get(&pos) {
                 // Load the chunk at this cell if there is one.
                 if let Some(chunk) = &cell.chunk {
-                    chunk.write_init_packets(
-                        &instance.info,
-                        pos,
-                        &mut client.enc,
-                        &mut client.scratch,
-                    );
+                    if defer_chunks {
+                        client.pending_chunks.push_back(pos);
+                    } else {
+                        chunk.write_init_packets(
+                            &instance.info,
+                            pos,
+                            &mut client.enc,
+                            &mut client.scratch,
+                        );
 
-                    chunk.mark_viewed();
+                        chunk.mark_viewed();
+                    }
                 }
 
-                // Load all the entities in this cell.
+                // Load all the tracked entities in this cell.
                 for &id in &cell.entities {
                     if let Ok(entity) = entities.get(id) {
-                        entity.write_init_packets(
-                            &mut client.enc,
-                            entity.position(),
-                            &mut client.scratch,
-                        );
+                        if instance.entity_view(entity.kind(), view).contains(pos) {
+                            entity.write_init_packets(
+                                &mut client.enc,
+                                entity.position(),
+                                self_protocol_id,
+                                &mut client.scratch,
+                            );
+                        }
                     }
                 }
             }
         });
+
+        // Entities with a tracking range can enter or leave the range of the
+        // client where the old and new view overlap.
+        if instance.has_entity_tracking_ranges() {
+            view.for_each(|pos| {
+                if !old_view.contains(pos) {
+                    return;
+                }
+
+                if let Some(cell) = instance.partition.get(&pos) {
+                    for &id in &cell.entities {
+                        if let Ok(entity) = entities.get(id) {
+                            let was_tracked =
+                                instance.entity_view(entity.kind(), old_view).contains(pos);
+                            let is_tracked =
+                                instance.entity_view(entity.kind(), view).contains(pos);
+
+                            if was_tracked && !is_tracked {
+                                client
+                                    .entities_to_despawn
+                                    .push(VarInt(entity.protocol_id()));
+                            } else if !was_tracked && is_tracked {
+                                entity.write_init_packets(
+                                    &mut client.enc,
+                                    entity.position(),
+                                    self_protocol_id,
+                                    &mut client.scratch,
+                                );
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    // Resend the chunks invalidated for this client. Chunks which the client is
+    // loading this tick or has not been sent yet are up to date anyway.
+    for pos in std::mem::take(&mut client.chunks_to_resend) {
+        if client.old_instance == client.instance
+            && old_view.contains(pos)
+            && view.contains(pos)
+            && !client.pending_chunks.contains(&pos)
+        {
+            if let Some(chunk) = instance.chunk(pos) {
+                chunk.write_init_packets(&instance.info, pos, &mut client.enc, &mut client.scratch);
+            }
+        }
+    }
+
+    // Send the nearest chunks first.
+    if defer_chunks {
+        client
+            .pending_chunks
+            .make_contiguous()
+            .sort_by_key(|&pos| pos.distance_squared(view.pos));
     }
 
     // Despawn all the entities that are queued to be despawned.
@@ -950,6 +2343,169 @@ fn update_one_client(
         client.entities_to_despawn.clear();
     }
 
+    // Hide the custom names of entities that are too far away from the client and
+    // restore the names of entities that have come back in range. Outline the
+    // entities glowing for this client only. Show the client riding the vehicles
+    // its player entity is a passenger of and holding the leashes it holds. This
+    // is done after the entity init and update packets are sent so that our
+    // overrides take precedence.
+    let old_hidden_name_tags = std::mem::take(&mut client.hidden_name_tags);
+    let old_glowing_overrides = std::mem::take(&mut client.glowing_overrides);
+    let old_vehicle_override = client.vehicle_override.take();
+
+    let client_instance = client.instance;
+
+    // Returns the entity if it is in view of the client.
+    let get_in_view = |id: Entity| {
+        entities.get(id).ok().filter(|entity| {
+            entity.instance() == client_instance
+                && instance
+                    .entity_view(entity.kind(), view)
+                    .contains(ChunkPos::from_dvec3(entity.position()))
+        })
+    };
+
+    for &id in &overrides.name_tags {
+        let Some(entity) = get_in_view(id) else {
+            continue
+        };
+
+        let name_visible = entity.data().name_visible();
+        let was_hidden = old_hidden_name_tags.contains(&id);
+
+        let should_hide = name_visible
+            && entity.name_visibility_range().map_or(false, |range| {
+                entity.position().distance_squared(client.position) > range * range
+            });
+
+        if should_hide {
+            client.hidden_name_tags.insert(id);
+        }
+
+        // The entity's own update packets might have reset the flag, so we send it
+        // again if any were written this tick.
+        if should_hide != was_hidden || (should_hide && !entity.self_update_range.is_empty()) {
+            write_tracked_data(
+                &mut client.enc,
+                &mut client.scratch,
+                entity.protocol_id(),
+                |data| TrackedData::encode_name_visible(&(!should_hide && name_visible), data),
+            );
+        }
+    }
+
+    // Restore the names of entities which no longer have a name visibility range.
+    for &id in &old_hidden_name_tags {
+        let Some(entity) = get_in_view(id) else {
+            continue
+        };
+
+        if entity.name_visibility_range().is_none() {
+            write_tracked_data(
+                &mut client.enc,
+                &mut client.scratch,
+                entity.protocol_id(),
+                |data| TrackedData::encode_name_visible(&entity.data().name_visible(), data),
+            );
+        }
+    }
+
+    // Forget the entities which were despawned.
+    client.glowing_entities.retain(|&id| entities.contains(id));
+
+    for &id in &client.glowing_entities {
+        let Some(entity) = get_in_view(id) else {
+            continue
+        };
+
+        if entity.is_glowing() {
+            continue;
+        }
+
+        client.glowing_overrides.insert(id);
+
+        if !old_glowing_overrides.contains(&id) || !entity.self_update_range.is_empty() {
+            write_tracked_data(
+                &mut client.enc,
+                &mut client.scratch,
+                entity.protocol_id(),
+                // Bit 6 of the flags is `glowing`.
+                |data| TrackedData::encode_flags(&(entity.flags_byte() | 1 << 6), data),
+            );
+        }
+    }
+
+    for &id in &old_glowing_overrides {
+        if client.glowing_overrides.contains(&id) {
+            continue;
+        }
+
+        if let Some(entity) = get_in_view(id) {
+            write_tracked_data(
+                &mut client.enc,
+                &mut client.scratch,
+                entity.protocol_id(),
+                |data| TrackedData::encode_flags(&entity.flags_byte(), data),
+            );
+        }
+    }
+
+    if let Some(self_protocol_id) = self_protocol_id {
+        for &id in &overrides.vehicles {
+            let Some(entity) = get_in_view(id) else {
+                continue
+            };
+
+            if !entity.passengers().contains(&self_protocol_id) {
+                continue;
+            }
+
+            let protocol_id = entity.protocol_id();
+            client.vehicle_override = Some(protocol_id);
+
+            // The client knows itself by the protocol ID 0 instead of the ID of its
+            // player entity.
+            if old_vehicle_override != Some(protocol_id) || entity.passengers_modified() {
+                let mut pkt = entity.passengers_packet();
+
+                for id in &mut pkt.passengers {
+                    if id.0 == self_protocol_id {
+                        id.0 = 0;
+                    }
+                }
+
+                client.enc.write_packet(&pkt);
+            }
+        }
+
+        // The update packets refer to the holder by the ID of its player entity,
+        // so the client holding the leash needs its own packet.
+        for &id in &overrides.leashes {
+            let Some(entity) = get_in_view(id) else {
+                continue
+            };
+
+            if entity.leash_holder() == Some(self_protocol_id) {
+                client
+                    .enc
+                    .write_packet(&entity.leash_packet(Some(self_protocol_id)));
+            }
+        }
+
+        // Likewise for the client hooked by a fishing bobber.
+        for &id in &overrides.hooks {
+            let Some(entity) = get_in_view(id) else {
+                continue
+            };
+
+            if entity.fishing_bobber_hooked() == Some(self_protocol_id) {
+                let pkt =
+                    entity.fishing_bobber_hooked_packet(Some(self_protocol_id), &mut client.scratch);
+                client.enc.write_packet(&pkt);
+            }
+        }
+    }
+
     // Teleport the client. Do this after chunk packets are sent so the client does
     // not accidentally pass through blocks.
     if client.position_modified || client.yaw_modified || client.pitch_modified {
@@ -960,7 +2516,7 @@ fn update_one_client(
             .with_y_rot(!client.yaw_modified)
             .with_x_rot(!client.pitch_modified);
 
-        client.enc.write_packet(&SynchronizePlayerPosition {
+        client.write_urgent_packet(&SynchronizePlayerPosition {
             position: if client.position_modified {
                 client.position.to_array()
             } else {
@@ -977,7 +2533,11 @@ fn update_one_client(
             dismount_vehicle: false,
         });
 
-        client.pending_teleports = client.pending_teleports.wrapping_add(1);
+        client.pending_teleports.push_back(PendingTeleport {
+            id: client.teleport_id_counter,
+            sent_tick: server.current_tick(),
+            timed_out: false,
+        });
         client.teleport_id_counter = client.teleport_id_counter.wrapping_add(1);
 
         client.position_modified = false;
@@ -985,15 +2545,72 @@ fn update_one_client(
         client.pitch_modified = false;
     }
 
-    // This closes the "downloading terrain" screen.
-    // Send this after the initial chunks are loaded.
-    if client.is_new {
-        client.enc.write_packet(&SetDefaultSpawnPosition {
+    if let Some(effect) = client.teleport_effect.take() {
+        client.show_teleport_effect(effect);
+    }
+
+    // Send velocity after the teleport, which would reset it.
+    if let Some(velocity) = client.velocity_to_set.take() {
+        client.enc.write_packet(&SetEntityVelocity {
+            entity_id: VarInt(0),
+            velocity: velocity_to_packet_units(velocity),
+        });
+    }
+
+    if client.velocity_to_add != Vec3::ZERO {
+        // The client plays the sound and particles of the explosion, so it is
+        // placed far enough below the client for them to go unnoticed.
+        client.enc.write_packet(&Explosion {
+            position: (client.position - DVec3::new(0.0, KNOCKBACK_EXPLOSION_DEPTH, 0.0))
+                .to_array(),
+            strength: 0.0,
+            records: vec![],
+            player_motion: (client.velocity_to_add / DEFAULT_TPS as f32).to_array(),
+        });
+
+        client.velocity_to_add = Vec3::ZERO;
+    }
+
+    // This closes the "downloading terrain" screen.
+    // Send this after the initial chunks are loaded.
+    if client.is_new {
+        client.enc.write_packet(&SetDefaultSpawnPosition {
             position: BlockPos::at(client.position),
             angle: client.yaw,
         });
     }
 
+    if client.abilities_modified {
+        client.abilities_modified = false;
+
+        let abilities = abilities_packet(client);
+        client.enc.write_packet(&abilities);
+    }
+
+    if let Some(camera) = client.camera {
+        if entities.get(camera).is_err() {
+            client.camera = None;
+            client.camera_modified = true;
+        }
+    }
+
+    // The client resets its camera when it respawns.
+    if client.camera_modified || (respawned && client.camera.is_some()) {
+        client.camera_modified = false;
+
+        // Clients know themselves by the protocol ID 0.
+        let entity_id = match client.camera {
+            Some(camera) if camera != self_id => {
+                entities.get(camera).map_or(0, |e| e.protocol_id())
+            }
+            _ => 0,
+        };
+
+        client.enc.write_packet(&SetCamera {
+            entity_id: VarInt(entity_id),
+        });
+    }
+
     // Update the client's own player metadata.
     client.scratch.clear();
     client.player_data.updated_tracked_data(&mut client.scratch);
@@ -1008,6 +2625,16 @@ fn update_one_client(
         });
     }
 
+    if client.resend_player_flags {
+        client.resend_player_flags = false;
+
+        let flags = client.player_data.flags_byte();
+
+        write_tracked_data(&mut client.enc, &mut client.scratch, 0, |data| {
+            TrackedData::encode_flags(&flags, data)
+        });
+    }
+
     // Acknowledge broken/placed blocks.
     if client.block_change_sequence != 0 {
         client.enc.write_packet(&AcknowledgeBlockChange {
@@ -1017,29 +2644,202 @@ fn update_one_client(
         client.block_change_sequence = 0;
     }
 
+    // Open the sign editor after the sign was sent.
+    if client.open_sign_editor {
+        client.open_sign_editor = false;
+
+        if let Some(location) = client.editing_sign {
+            client.enc.write_packet(&OpenSignEditor { location });
+        }
+    }
+
     client.old_instance = client.instance;
     client.old_position = client.position;
-    client.old_view_distance = client.view_distance;
+    client.old_view_distance = client.view_distance();
+    client.old_simulation_distance = client.simulation_distance();
+    client.old_time_override = client.time_override;
+    client.old_weather_override = client.weather_override;
 
-    client
-        .conn
-        .try_send(client.enc.take())
-        .context("failed to flush packet queue")?;
+    // Discard messages left over if the localization plugin is missing.
+    client.localized_messages.clear();
 
     Ok(())
 }
 
+/// Writes a [`SetEntityMetadata`] packet for the entity with the protocol ID
+/// `entity_id` containing the tracked data entries appended by `encode`.
+fn write_tracked_data(
+    enc: &mut PacketEncoder,
+    scratch: &mut Vec<u8>,
+    entity_id: i32,
+    encode: impl FnOnce(&mut Vec<u8>),
+) {
+    scratch.clear();
+    encode(scratch);
+    scratch.push(0xff);
+
+    enc.write_packet(&SetEntityMetadata {
+        entity_id: VarInt(entity_id),
+        metadata: RawBytes(scratch),
+    });
+}
+
+/// Appends the packet data of a cell to the encoder of a client, leaving out
+/// the update packets of entities outside their tracking range of the client.
+fn append_tracked_cell_packets(
+    enc: &mut PacketEncoder,
+    cell: &PartitionCell,
+    pos: ChunkPos,
+    view: ChunkView,
+    instance: &Instance,
+    entities: &Query<&McEntity>,
+) {
+    let mut untracked: Vec<Range<usize>> = cell
+        .entities
+        .iter()
+        .filter_map(|&id| entities.get(id).ok())
+        .filter(|entity| {
+            !entity.self_update_range.is_empty()
+                && !instance.entity_view(entity.kind(), view).contains(pos)
+        })
+        .map(|entity| entity.self_update_range.clone())
+        .collect();
+
+    untracked.sort_unstable_by_key(|range| range.start);
+
+    let mut start = 0;
+
+    for range in untracked {
+        enc.append_shared(cell.packet_bytes.slice(start..range.start));
+        start = range.end;
+    }
+
+    enc.append_shared(cell.packet_bytes.slice(start..));
+}
+
+/// The limits on the chunks sent by [`send_pending_chunks`] in a tick.
+struct ChunkSendLimits {
+    /// The maximum number of chunks sent to all clients.
+    chunks: Option<usize>,
+    /// The maximum number of bytes of chunk data sent to all clients.
+    bytes: Option<usize>,
+    /// The maximum number of chunks sent to each client.
+    client_chunks: Option<usize>,
+    /// The maximum number of bytes of chunk data sent to each client.
+    client_bytes: Option<usize>,
+    /// Clients with more than this many bytes of outgoing packets are skipped.
+    queue_len: Option<usize>,
+}
+
+impl ChunkSendLimits {
+    fn new(server: &SharedServer) -> Self {
+        Self {
+            chunks: server.max_chunk_sends_per_tick(),
+            bytes: server.max_chunk_bytes_per_tick(),
+            client_chunks: server.max_client_chunk_sends_per_tick(),
+            client_bytes: server.max_client_chunk_bytes_per_tick(),
+            queue_len: (server.outgoing_overflow_policy() == OutgoingOverflowPolicy::DropChunks)
+                .then_some(server.outgoing_capacity() / 2),
+        }
+    }
+
+    /// If chunks entering the view of clients must be queued to respect the
+    /// limits.
+    fn is_limited(&self) -> bool {
+        self.chunks.is_some()
+            || self.bytes.is_some()
+            || self.client_chunks.is_some()
+            || self.client_bytes.is_some()
+            || self.queue_len.is_some()
+    }
+}
+
+/// Sends the queued chunks of the clients, one chunk per client in turn, until
+/// the limits are reached. The client served first changes every tick so that
+/// no client is always last.
+fn send_pending_chunks<'a>(
+    clients: impl Iterator<Item = &'a mut Client>,
+    instances: &Query<&Instance>,
+    limits: &ChunkSendLimits,
+    first: &mut usize,
+) {
+    // The clients along with the number of chunks and bytes sent to them.
+    let mut clients: Vec<_> = clients
+        .filter(|client| !client.is_disconnected && !client.pending_chunks.is_empty())
+        .map(|client| (client, 0, 0))
+        .collect();
+
+    if clients.is_empty() {
+        return;
+    }
+
+    let len = clients.len();
+    clients.rotate_left(*first % len);
+    *first = first.wrapping_add(1);
+
+    let mut chunks_left = limits.chunks.unwrap_or(usize::MAX);
+    let mut bytes_left = limits.bytes.unwrap_or(usize::MAX);
+
+    while chunks_left > 0 && bytes_left > 0 && !clients.is_empty() {
+        clients.retain_mut(|(client, chunks_sent, bytes_sent)| {
+            if chunks_left == 0 || bytes_left == 0 {
+                return true;
+            }
+
+            let Ok(instance) = instances.get(client.instance) else {
+                return false
+            };
+
+            if limits.queue_len.map_or(false, |limit| {
+                client.conn.queued_len() + client.enc.len() > limit
+            }) {
+                return false;
+            }
+
+            while let Some(pos) = client.pending_chunks.pop_front() {
+                if let Some(chunk) = instance.partition.get(&pos).and_then(|c| c.chunk.as_ref()) {
+                    let len_before = client.enc.len();
+
+                    chunk.write_init_packets(
+                        &instance.info,
+                        pos,
+                        &mut client.enc,
+                        &mut client.scratch,
+                    );
+
+                    chunk.mark_viewed();
+
+                    let len = client.enc.len() - len_before;
+
+                    chunks_left -= 1;
+                    bytes_left = bytes_left.saturating_sub(len);
+                    *chunks_sent += 1;
+                    *bytes_sent += len;
+                    break;
+                }
+            }
+
+            !client.pending_chunks.is_empty()
+                && limits.client_chunks.map_or(true, |max| *chunks_sent < max)
+                && limits.client_bytes.map_or(true, |max| *bytes_sent < max)
+        });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeSet;
+    use std::time::Duration;
 
     use bevy_app::App;
     use valence_protocol::packets::s2c::play::ChunkDataAndUpdateLight;
     use valence_protocol::packets::S2cPlayPacket;
 
     use super::*;
+    use crate::config::{ConnectionMode, ServerPlugin};
     use crate::instance::Chunk;
-    use crate::unit_test::util::scenario_single_client;
+    use crate::unit_test::util::{create_mock_client, gen_client_info, scenario_single_client};
+    use crate::{assert_packet_count, assert_packet_order};
 
     #[test]
     fn client_chunk_view_change() {
@@ -1122,4 +2922,834 @@ mod tests {
             assert!(loaded_chunks.contains(&pos), "{pos:?}");
         }
     }
+
+    #[test]
+    fn bungeecord_connect_message() {
+        assert_eq!(
+            bungeecord_message(&["Connect", "lobby"]),
+            b"\0\x07Connect\0\x05lobby"
+        );
+    }
+
+    #[test]
+    fn client_transfer_same_dimension() {
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut instance = app
+            .world
+            .query::<&mut Instance>()
+            .single_mut(&mut app.world);
+
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        let server = app.world.resource::<Server>();
+        let mut other = server.new_instance(DimensionId::default());
+        other.insert_chunk([0, 0], Chunk::default());
+        let other_ent = app.world.spawn(other).id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.transfer_to_instance(other_ent, [8.0, 0.0, 8.0]);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::Respawn(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::UnloadChunk(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::UnloadChunk(_),
+            S2cPlayPacket::ChunkDataAndUpdateLight(_)
+        );
+    }
+
+    #[test]
+    fn resend_chunks() {
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut instance = app
+            .world
+            .query::<&mut Instance>()
+            .single_mut(&mut app.world);
+
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.insert_chunk([100, 100], Chunk::default());
+
+        app.update();
+        client_helper.clear_sent();
+
+        // Resend to all clients in view.
+        let mut instance = app
+            .world
+            .query::<&mut Instance>()
+            .single_mut(&mut app.world);
+
+        assert!(instance.resend_chunk([0, 0]));
+        assert!(instance.resend_chunk([100, 100]));
+        assert!(!instance.resend_chunk([1, 1]));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+
+        // Resend to a single client.
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.resend_chunk([0, 0]);
+        client.resend_chunk([100, 100]);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+
+        // Nothing is resent on the next tick.
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+    }
+
+    #[test]
+    fn chunk_send_budget_shared_between_clients() {
+        let mut app = App::new();
+        app.add_plugin(
+            ServerPlugin::new(())
+                .with_compression_threshold(None)
+                .with_connection_mode(ConnectionMode::Offline)
+                .with_listen(false)
+                .with_max_chunk_sends_per_tick(Some(2)),
+        );
+
+        let server = app.world.resource::<Server>();
+        let mut instance = server.new_instance(DimensionId::default());
+
+        for z in -1..=1 {
+            for x in -1..=1 {
+                instance.insert_chunk([x, z], Chunk::default());
+            }
+        }
+
+        let instance_ent = app.world.spawn(instance).id();
+
+        let mut helpers = vec![];
+
+        for name in ["first", "second"] {
+            let (mut client, helper) = create_mock_client(gen_client_info(name));
+            client.set_instance(instance_ent);
+            client.set_position([8.0, 0.0, 8.0]);
+            app.world.spawn(client);
+            helpers.push(helper);
+        }
+
+        app.update();
+
+        for helper in &mut helpers {
+            let sent_packets = helper.collect_sent().unwrap();
+
+            assert_packet_count!(sent_packets, 1, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+
+            // The nearest chunk is sent first.
+            assert!(sent_packets.iter().any(|p| matches!(
+                p,
+                S2cPlayPacket::ChunkDataAndUpdateLight(ChunkDataAndUpdateLight {
+                    chunk_x: 0,
+                    chunk_z: 0,
+                    ..
+                })
+            )));
+        }
+
+        for _ in 0..8 {
+            app.update();
+        }
+
+        for helper in &mut helpers {
+            let sent_packets = helper.collect_sent().unwrap();
+            assert_packet_count!(sent_packets, 8, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+        }
+
+        for client in app.world.query::<&Client>().iter(&app.world) {
+            assert_eq!(client.pending_chunk_count(), 0);
+        }
+    }
+
+    #[test]
+    fn view_and_simulation_distance_limits() {
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut instance = app
+            .world
+            .query::<&mut Instance>()
+            .single_mut(&mut app.world);
+
+        for z in -10..10 {
+            for x in -10..10 {
+                instance.insert_chunk([x, z], Chunk::default());
+            }
+        }
+
+        instance.set_max_view_distance(Some(4));
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([8.0, 0.0, 8.0]);
+        client.set_view_distance(8);
+        client.set_simulation_distance(6);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        assert_packet_count!(
+            sent_packets,
+            1,
+            S2cPlayPacket::LoginPlay(LoginPlay {
+                view_distance: VarInt(4),
+                simulation_distance: VarInt(4),
+                ..
+            })
+        );
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.view_distance(), 4);
+
+        let chunk_count = sent_packets
+            .iter()
+            .filter(|p| matches!(p, S2cPlayPacket::ChunkDataAndUpdateLight(_)))
+            .count();
+        assert_eq!(chunk_count, client.view().iter().count());
+
+        // Lifting the instance limit raises the distances up to the client's
+        // own limits.
+        app.world
+            .query::<&mut Instance>()
+            .single_mut(&mut app.world)
+            .set_max_view_distance(None);
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_max_view_distance(5);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        assert_packet_count!(
+            sent_packets,
+            1,
+            S2cPlayPacket::SetRenderDistance(SetRenderDistance {
+                view_distance: VarInt(5)
+            })
+        );
+        assert_packet_count!(
+            sent_packets,
+            1,
+            S2cPlayPacket::SetSimulationDistance(SetSimulationDistance {
+                simulation_distance: VarInt(5)
+            })
+        );
+    }
+
+    #[test]
+    fn chunk_byte_and_client_budgets() {
+        // Returns the number of chunks sent to a client in each of the first
+        // ticks after it joins an instance of 3x3 chunks.
+        fn chunks_sent_per_tick(plugin: ServerPlugin<()>) -> Vec<usize> {
+            let mut app = App::new();
+            app.add_plugin(
+                plugin
+                    .with_compression_threshold(None)
+                    .with_connection_mode(ConnectionMode::Offline)
+                    .with_listen(false),
+            );
+
+            let server = app.world.resource::<Server>();
+            let mut instance = server.new_instance(DimensionId::default());
+
+            for z in -1..=1 {
+                for x in -1..=1 {
+                    instance.insert_chunk([x, z], Chunk::default());
+                }
+            }
+
+            let instance_ent = app.world.spawn(instance).id();
+
+            let (mut client, mut client_helper) = create_mock_client(gen_client_info("test"));
+            client.set_instance(instance_ent);
+            client.set_position([8.0, 0.0, 8.0]);
+            app.world.spawn(client);
+
+            (0..4)
+                .map(|_| {
+                    app.update();
+
+                    let sent_packets = client_helper.collect_sent().unwrap();
+                    sent_packets
+                        .iter()
+                        .filter(|p| matches!(p, S2cPlayPacket::ChunkDataAndUpdateLight(_)))
+                        .count()
+                })
+                .collect()
+        }
+
+        assert_eq!(
+            chunks_sent_per_tick(
+                ServerPlugin::new(()).with_max_client_chunk_sends_per_tick(Some(4))
+            ),
+            [4, 4, 1, 0]
+        );
+
+        // The chunk which reaches a byte budget is still sent.
+        assert_eq!(
+            chunks_sent_per_tick(ServerPlugin::new(()).with_max_chunk_bytes_per_tick(Some(1))),
+            [1, 1, 1, 1]
+        );
+        assert_eq!(
+            chunks_sent_per_tick(
+                ServerPlugin::new(()).with_max_client_chunk_bytes_per_tick(Some(1))
+            ),
+            [1, 1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn glowing_entity_forgotten_after_despawn() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let zombie = app
+            .world
+            .spawn(McEntity::new(EntityKind::Zombie, instance_ent))
+            .id();
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_entity_glowing(zombie, true);
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(client.is_entity_glowing(zombie));
+
+        app.world.entity_mut(zombie).insert(Despawned);
+        app.update();
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(!client.is_entity_glowing(zombie));
+    }
+
+    #[test]
+    fn slow_client_drops_chunks_then_kicked() {
+        const CAPACITY: usize = 1 << 20;
+
+        let mut app = App::new();
+        app.add_plugin(
+            ServerPlugin::new(())
+                .with_compression_threshold(None)
+                .with_connection_mode(ConnectionMode::Offline)
+                .with_listen(false)
+                .with_outgoing_capacity(CAPACITY)
+                .with_outgoing_overflow_policy(OutgoingOverflowPolicy::DropChunks),
+        );
+
+        let server = app.world.resource::<Server>();
+        let mut instance = server.new_instance(DimensionId::default());
+
+        for z in -1..=1 {
+            for x in -1..=1 {
+                instance.insert_chunk([x, z], Chunk::default());
+            }
+        }
+
+        let instance_ent = app.world.spawn(instance).id();
+
+        let (mut client, mut client_helper) = create_mock_client(gen_client_info("test"));
+        client.set_instance(instance_ent);
+        let client_ent = app.world.spawn(client).id();
+
+        // Chunks are held back while more than half of the buffer is used.
+        client_helper.set_queued_len(CAPACITY / 2 + 1);
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::LoginPlay(_));
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.pending_chunk_count(), 9);
+
+        client_helper.set_queued_len(0);
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 9, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+
+        // The game state doesn't fit, so the client is kicked.
+        client_helper.set_queued_len(CAPACITY);
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .send_message("hello");
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SystemChatMessage(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::DisconnectPlay(_));
+    }
+
+    #[test]
+    fn urgent_packets_sent_with_full_buffer() {
+        const CAPACITY: usize = 1 << 20;
+
+        let mut app = App::new();
+        app.add_plugin(
+            ServerPlugin::new(())
+                .with_compression_threshold(None)
+                .with_connection_mode(ConnectionMode::Offline)
+                .with_listen(false)
+                .with_outgoing_capacity(CAPACITY)
+                .with_keepalive_interval(Duration::ZERO),
+        );
+
+        let server = app.world.resource::<Server>();
+        let instance = server.new_instance(DimensionId::default());
+        let instance_ent = app.world.spawn(instance).id();
+
+        let (mut client, mut client_helper) = create_mock_client(gen_client_info("test"));
+        client.set_instance(instance_ent);
+        let client_ent = app.world.spawn(client).id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        // Keepalives and teleports are sent even though the buffer is full.
+        client_helper.set_queued_len(CAPACITY);
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.got_keepalive = true;
+        client.set_position([0.0, 100.0, 0.0]);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::KeepAliveS2c(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SynchronizePlayerPosition(_));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::DisconnectPlay(_));
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(!client.is_disconnected());
+
+        // Other packets still don't fit.
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .send_message("hello");
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SystemChatMessage(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::DisconnectPlay(_));
+    }
+
+    #[test]
+    fn client_settings_component() {
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut send_settings = |view_distance| {
+            client_helper.send(&valence_protocol::packets::c2s::play::ClientInformation {
+                locale: "en_us",
+                view_distance,
+                chat_mode: ChatMode::Enabled,
+                chat_colors: true,
+                displayed_skin_parts: DisplayedSkinParts::new(),
+                main_hand: MainHand::Right,
+                enable_text_filtering: false,
+                allow_server_listings: true,
+            });
+        };
+
+        send_settings(8);
+        app.update();
+
+        let settings = app.world.get::<ClientSettings>(client_ent).unwrap();
+        assert_eq!(&*settings.locale, "en_us");
+        assert_eq!(settings.view_distance, 8);
+
+        send_settings(12);
+        app.update();
+
+        let settings = app.world.get::<ClientSettings>(client_ent).unwrap();
+        assert_eq!(settings.view_distance, 12);
+    }
+
+    #[test]
+    fn keepalive_ping_smoothing() {
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut keepalive = |app: &mut App, id, rtt| {
+            let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+            client.got_keepalive = false;
+            client.last_keepalive_id = id;
+            client.keepalive_sent_time = Instant::now() - Duration::from_millis(rtt);
+
+            client_helper.send(&valence_protocol::packets::c2s::play::KeepAliveC2s { id });
+            app.update();
+
+            app.world.get::<Client>(client_ent).unwrap().ping()
+        };
+
+        let first = keepalive(&mut app, 1, 100);
+        assert!(first >= 100);
+
+        let second = keepalive(&mut app, 2, 300);
+        assert!(second > first && second < 300);
+    }
+
+    #[test]
+    fn velocity_sent_after_teleport() {
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.add_velocity([1.0, 0.0, 0.0]);
+        client.set_velocity([0.0, 20.0, 0.0]);
+        client.add_velocity([2.0, 0.0, 0.0]);
+        client.add_velocity([4.0, 0.0, 0.0]);
+        client.set_position([0.0, 100.0, 0.0]);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetEntityVelocity(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::Explosion(_));
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::SynchronizePlayerPosition(_),
+            S2cPlayPacket::SetEntityVelocity(_),
+            S2cPlayPacket::Explosion(_)
+        );
+
+        for pkt in &sent_packets {
+            if let S2cPlayPacket::Explosion(pkt) = pkt {
+                assert_eq!(pkt.player_motion, [0.3, 0.0, 0.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn entity_tracking_ranges() {
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_entity_tracking_range(EntityKind::Zombie, Some(1));
+        assert_eq!(instance.entity_tracking_range(EntityKind::Zombie), Some(1));
+        assert_eq!(instance.entity_tracking_range(EntityKind::Item), None);
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([8.0, 0.0, 8.0]);
+        client.set_view_distance(8);
+
+        let mut spawn = |kind| {
+            let mut entity = McEntity::new(kind, instance_ent);
+            entity.set_position([88.0, 0.0, 8.0]);
+            app.world.spawn(entity).id()
+        };
+
+        let zombie = spawn(EntityKind::Zombie);
+        spawn(EntityKind::Item);
+
+        app.update();
+
+        // Only the item is in range.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+
+        app.world
+            .get_mut::<McEntity>(zombie)
+            .unwrap()
+            .set_position([24.0, 0.0, 8.0]);
+
+        app.update();
+
+        // The zombie moved into its tracking range.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([120.0, 0.0, 8.0]);
+
+        app.update();
+
+        // Both entities are still within the view distance, but the zombie is out
+        // of its tracking range.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SpawnEntity(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::RemoveEntities(_));
+    }
+
+    #[test]
+    fn client_timed_out_event() {
+        let mut app = App::new();
+
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.got_keepalive = false;
+        client.keepalive_sent_time = Instant::now() - Duration::from_secs(60);
+
+        app.update();
+
+        let events = app.world.resource::<Events<ClientTimedOut>>();
+        let timed_out: Vec<_> = events.get_reader().iter(events).cloned().collect();
+        assert_eq!(timed_out.len(), 1);
+        assert_eq!(timed_out[0].client, client_ent);
+    }
+
+    #[test]
+    fn abilities_reconciled_with_client() {
+        use valence_protocol::packets::c2s::play::PlayerAbilitiesC2s;
+
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        // Survival clients may not fly, so they are told to stop.
+        client_helper.send(&PlayerAbilitiesC2s::StartFlying);
+        app.update();
+
+        assert!(!app.world.get::<Client>(client_ent).unwrap().is_flying());
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::PlayerAbilitiesS2c(_));
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_allow_flying(true);
+        client.set_flying_speed(0.1);
+        client.set_fov_modifier(0.2);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::PlayerAbilitiesS2c(_));
+
+        for pkt in &sent_packets {
+            if let S2cPlayPacket::PlayerAbilitiesS2c(pkt) = pkt {
+                assert!(pkt.flags.allow_flying());
+                assert!(!pkt.flags.flying());
+                assert_eq!(pkt.flying_speed, 0.1);
+                assert_eq!(pkt.fov_modifier, 0.2);
+            }
+        }
+
+        // Now the client is allowed to start flying on its own.
+        client_helper.send(&PlayerAbilitiesC2s::StartFlying);
+        app.update();
+
+        assert!(app.world.get::<Client>(client_ent).unwrap().is_flying());
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::PlayerAbilitiesS2c(_));
+    }
+
+    #[test]
+    fn spectator_camera() {
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let zombie = app
+            .world
+            .spawn(McEntity::new(EntityKind::Zombie, instance_ent))
+            .id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_game_mode(GameMode::Spectator);
+        client.set_camera(Some(zombie));
+
+        app.update();
+
+        let events = app.world.resource::<Events<GameModeChanged>>();
+        let changed: Vec<_> = events.get_reader().iter(events).cloned().collect();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].client, client_ent);
+        assert_eq!(changed[0].old, GameMode::Survival);
+        assert_eq!(changed[0].new, GameMode::Spectator);
+
+        let zombie_id = app.world.get::<McEntity>(zombie).unwrap().protocol_id();
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetCamera(_));
+
+        for pkt in &sent_packets {
+            if let S2cPlayPacket::SetCamera(pkt) = pkt {
+                assert_eq!(pkt.entity_id.0, zombie_id);
+            }
+        }
+
+        // The camera returns to the client when the entity is gone.
+        app.world.despawn(zombie);
+        app.update();
+
+        assert_eq!(app.world.get::<Client>(client_ent).unwrap().camera(), None);
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetCamera(_));
+
+        for pkt in &sent_packets {
+            if let S2cPlayPacket::SetCamera(pkt) = pkt {
+                assert_eq!(pkt.entity_id.0, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn teleport_with_effects() {
+        use valence_protocol::packets::c2s::play::ConfirmTeleport;
+
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+
+        // Confirm the teleport sent when joining.
+        client_helper.send(&ConfirmTeleport {
+            teleport_id: VarInt(0),
+        });
+        app.update();
+        client_helper.clear_sent();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(!client.is_teleporting());
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        let instance_ent = client.instance();
+        client.teleport_with_effects(
+            instance_ent,
+            [1000.0, 64.0, 1000.0],
+            TeleportEffect::NetherPortal,
+        );
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(client.is_teleporting());
+
+        // The effect is shown after the teleport.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::EntityEffect(_));
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::SynchronizePlayerPosition(_),
+            S2cPlayPacket::WorldEvent(_),
+            S2cPlayPacket::EntityEffect(_)
+        );
+
+        client_helper.send(&ConfirmTeleport {
+            teleport_id: VarInt(1),
+        });
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(!client.is_teleporting());
+    }
+
+    #[test]
+    fn pending_teleports() {
+        use valence_protocol::packets::c2s::play::ConfirmTeleport;
+
+        use crate::client::event::TeleportConfirmed;
+
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+
+        let mut timed_out_reader = app
+            .world
+            .resource::<Events<TeleportTimedOut>>()
+            .get_reader();
+        let mut confirmed_reader = app
+            .world
+            .resource::<Events<TeleportConfirmed>>()
+            .get_reader();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        assert_eq!(client.last_teleport_id(), Some(0));
+        client.set_position([8.0, 64.0, 8.0]);
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.pending_teleports(), 2);
+        assert_eq!(client.last_teleport_id(), Some(1));
+
+        // Confirmations are matched by ID. Confirming a teleport drops the ones
+        // sent before it, which the client skipped.
+        client_helper.send(&ConfirmTeleport {
+            teleport_id: VarInt(1),
+        });
+
+        // This teleport is never confirmed.
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([16.0, 64.0, 16.0]);
+
+        let mut confirmed = vec![];
+        let mut timed_out = vec![];
+
+        for _ in 0..TELEPORT_TIMEOUT_TICKS * 2 {
+            app.update();
+
+            let events = app.world.resource::<Events<TeleportConfirmed>>();
+            confirmed.extend(confirmed_reader.iter(events).map(|e| e.teleport_id));
+
+            let events = app.world.resource::<Events<TeleportTimedOut>>();
+            timed_out.extend(timed_out_reader.iter(events).map(|e| e.teleport_id));
+        }
+
+        assert_eq!(confirmed, [1]);
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(!client.is_disconnected());
+        assert_eq!(client.pending_teleports(), 1);
+        assert_eq!(timed_out, [2]);
+    }
 }
@@ -6,7 +6,7 @@ use bevy_ecs::schedule::ShouldRun;
 use bevy_ecs::system::SystemParam;
 use glam::{DVec3, Vec3};
 use paste::paste;
-use tracing::warn;
+use tracing::{trace_span, warn};
 use uuid::Uuid;
 use valence_protocol::entity_meta::Pose;
 use valence_protocol::packets::c2s::play::{
@@ -15,14 +15,17 @@ use valence_protocol::packets::c2s::play::{
 use valence_protocol::packets::C2sPlayPacket;
 use valence_protocol::types::{
     Action, ChatMode, ClickContainerMode, CommandBlockMode, Difficulty, DiggingStatus,
-    DisplayedSkinParts, EntityInteraction, Hand, MainHand, RecipeBookId, StructureBlockAction,
-    StructureBlockFlags, StructureBlockMirror, StructureBlockMode, StructureBlockRotation,
+    DisplayedSkinParts, EntityInteraction, GameMode, Hand, MainHand, RecipeBookId,
+    StructureBlockAction, StructureBlockFlags, StructureBlockMirror, StructureBlockMode,
+    StructureBlockRotation,
 };
 use valence_protocol::{BlockFace, BlockPos, Ident, ItemStack};
 
 use crate::client::Client;
 use crate::entity::{EntityAnimation, EntityKind, McEntity, TrackedData};
 use crate::inventory::Inventory;
+use crate::server::Server;
+use crate::timings::TickTimings;
 
 #[derive(Clone, Debug)]
 pub struct QueryBlockEntity {
@@ -97,6 +100,10 @@ pub struct CommandSuggestionsRequest {
     pub text: Box<str>,
 }
 
+/// Sent when a client clicks a button of an inventory, like the options of
+/// enchanting tables. See [`Inventory::set_enchantment_option`].
+///
+/// [`Inventory::set_enchantment_option`]: crate::inventory::Inventory::set_enchantment_option
 #[derive(Clone, Debug)]
 pub struct ClickContainerButton {
     pub client: Entity,
@@ -387,6 +394,10 @@ pub struct SetSeenRecipe {
     pub recipe_id: Ident<Box<str>>,
 }
 
+/// Sent when a client changes the text in the name field of an anvil. See
+/// [`Inventory::set_anvil_repair_cost`].
+///
+/// [`Inventory::set_anvil_repair_cost`]: crate::inventory::Inventory::set_anvil_repair_cost
 #[derive(Clone, Debug)]
 pub struct RenameItem {
     pub client: Entity,
@@ -439,6 +450,10 @@ pub struct SelectTrade {
     pub slot: i32,
 }
 
+/// Sent when a client confirms the effects selected in a beacon. See
+/// [`Inventory::set_beacon_effects`].
+///
+/// [`Inventory::set_beacon_effects`]: crate::inventory::Inventory::set_beacon_effects
 #[derive(Clone, Debug)]
 pub struct SetBeaconEffect {
     pub client: Entity,
@@ -519,6 +534,12 @@ pub struct SwingArm {
     pub hand: Hand,
 }
 
+/// Sent when a client in spectator mode asks to be teleported to the entity
+/// with the given UUID, from the spectator menu.
+///
+/// The [`default_event_handler`] teleports spectators to the entity, even if it
+/// is in another instance. The entity is looked up by its UUID on the
+/// [`Server`], so entities spawned in the current tick can't be teleported to.
 #[derive(Clone, Debug)]
 pub struct TeleportToEntity {
     pub client: Entity,
@@ -549,6 +570,38 @@ pub struct UseItem {
     pub sequence: i32,
 }
 
+/// Sent when a client asks to change its own game mode with
+/// `/gamemode <mode>`. This is also what the client sends for the F3+F4 game
+/// mode switcher and the F3+N spectator shortcut, which are available to
+/// clients with an [op level] of at least 2.
+///
+/// These commands are not sent as [`ChatCommand`] events. The game mode does
+/// not change unless the request is granted by calling
+/// [`Client::set_game_mode`], so requests can be denied by ignoring them.
+///
+/// Only the self-targeted form is a request. Commands changing the game mode
+/// of other players, such as `/gamemode creative Steve`, are sent as
+/// [`ChatCommand`] events like any other command.
+///
+/// [op level]: Client::set_op_level
+#[derive(Clone, Debug)]
+pub struct RequestGameMode {
+    pub client: Entity,
+    pub game_mode: GameMode,
+}
+
+/// Sent when a client confirms a teleport, which is made with
+/// [`Client::set_position`] and sent at the end of the tick. Movement of the
+/// client is ignored until it has confirmed all teleports.
+///
+/// [`Client::last_teleport_id`] gets the ID of the last teleport sent to a
+/// client.
+#[derive(Clone, Debug)]
+pub struct TeleportConfirmed {
+    pub client: Entity,
+    pub teleport_id: u32,
+}
+
 macro_rules! events {
     (
         $(
@@ -674,6 +727,8 @@ events! {
         TeleportToEntity
         UseItemOnBlock
         UseItem
+        RequestGameMode
+        TeleportConfirmed
     }
 }
 
@@ -681,7 +736,10 @@ pub(crate) fn event_loop_run_criteria(
     mut clients: Query<(Entity, &mut Client, &mut Inventory)>,
     mut clients_to_check: Local<Vec<Entity>>,
     mut events: ClientEvents,
+    timings: Res<TickTimings>,
 ) -> ShouldRun {
+    let _timer = timings.time("packet handling");
+
     if clients_to_check.is_empty() {
         // First run of the criteria. Prepare packets.
 
@@ -702,6 +760,7 @@ pub(crate) fn event_loop_run_criteria(
                 continue;
             }
 
+            client.bytes_received += bytes.len() as u64;
             client.dec.queue_bytes(bytes);
 
             match handle_one_packet(client, inventory, entity, &mut events) {
@@ -757,33 +816,50 @@ pub(crate) fn event_loop_run_criteria(
     }
 }
 
+/// Parses the commands sent by the client's game mode shortcuts. Commands with
+/// a target after the game mode are not parsed.
+fn parse_game_mode_command(command: &str) -> Option<GameMode> {
+    match command.strip_prefix("gamemode ")? {
+        "survival" => Some(GameMode::Survival),
+        "creative" => Some(GameMode::Creative),
+        "adventure" => Some(GameMode::Adventure),
+        "spectator" => Some(GameMode::Spectator),
+        _ => None,
+    }
+}
+
 fn handle_one_packet(
     client: &mut Client,
     inventory: &mut Inventory,
     entity: Entity,
     events: &mut ClientEvents,
 ) -> anyhow::Result<bool> {
+    let _span = trace_span!("handle_packet", username = %client.username).entered();
+
     let Some(pkt) = client.dec.try_next_packet::<C2sPlayPacket>()? else {
         // No packets to decode.
         return Ok(false);
     };
 
+    client.packets_received += 1;
+
     match pkt {
         C2sPlayPacket::ConfirmTeleport(p) => {
-            if client.pending_teleports == 0 {
-                bail!("unexpected teleport confirmation");
-            }
-
             let got = p.teleport_id.0 as u32;
-            let expected = client
-                .teleport_id_counter
-                .wrapping_sub(client.pending_teleports);
 
-            if got == expected {
-                client.pending_teleports -= 1;
-            } else {
-                bail!("unexpected teleport ID (expected {expected}, got {got}");
-            }
+            // Clients confirm teleports in the order they are sent, so the
+            // teleports sent before the confirmed one will never be confirmed
+            // and are dropped as well.
+            let Some(idx) = client.pending_teleports.iter().position(|t| t.id == got) else {
+                bail!("unexpected teleport confirmation (got ID {got})");
+            };
+
+            client.pending_teleports.drain(..=idx);
+
+            events.4.teleport_confirmed.send(TeleportConfirmed {
+                client: entity,
+                teleport_id: got,
+            });
         }
         C2sPlayPacket::QueryBlockEntityTag(p) => {
             events.0.query_block_entity.send(QueryBlockEntity {
@@ -805,11 +881,18 @@ fn handle_one_packet(
             });
         }
         C2sPlayPacket::ChatCommand(p) => {
-            events.0.chat_command.send(ChatCommand {
-                client: entity,
-                command: p.command.into(),
-                timestamp: p.timestamp,
-            });
+            if let Some(game_mode) = parse_game_mode_command(p.command) {
+                events.4.request_game_mode.send(RequestGameMode {
+                    client: entity,
+                    game_mode,
+                });
+            } else {
+                events.0.chat_command.send(ChatCommand {
+                    client: entity,
+                    command: p.command.into(),
+                    timestamp: p.timestamp,
+                });
+            }
         }
         C2sPlayPacket::ChatMessage(p) => {
             events.0.chat_message.send(ChatMessage {
@@ -954,7 +1037,16 @@ fn handle_one_packet(
                 );
             } else {
                 client.got_keepalive = true;
-                client.ping = client.keepalive_sent_time.elapsed().as_millis() as i32;
+
+                let rtt = client.keepalive_sent_time.elapsed().as_millis() as i32;
+
+                // Smooth the ping like vanilla so that the ping bars don't jump
+                // around.
+                client.ping = if client.ping < 0 {
+                    rtt
+                } else {
+                    (client.ping * 3 + rtt) / 4
+                };
             }
         }
         C2sPlayPacket::LockDifficulty(p) => {
@@ -964,7 +1056,7 @@ fn handle_one_packet(
             });
         }
         C2sPlayPacket::SetPlayerPosition(p) => {
-            if client.pending_teleports != 0 {
+            if client.is_teleporting() {
                 return Ok(false);
             }
 
@@ -990,7 +1082,7 @@ fn handle_one_packet(
             client.on_ground = p.on_ground;
         }
         C2sPlayPacket::SetPlayerPositionAndRotation(p) => {
-            if client.pending_teleports != 0 {
+            if client.is_teleporting() {
                 return Ok(false);
             }
 
@@ -1023,7 +1115,7 @@ fn handle_one_packet(
             client.on_ground = p.on_ground;
         }
         C2sPlayPacket::SetPlayerRotation(p) => {
-            if client.pending_teleports != 0 {
+            if client.is_teleporting() {
                 return Ok(false);
             }
 
@@ -1051,7 +1143,7 @@ fn handle_one_packet(
             client.on_ground = p.on_ground;
         }
         C2sPlayPacket::SetPlayerOnGround(p) => {
-            if client.pending_teleports != 0 {
+            if client.is_teleporting() {
                 return Ok(false);
             }
 
@@ -1075,7 +1167,7 @@ fn handle_one_packet(
             client.on_ground = p.on_ground;
         }
         C2sPlayPacket::MoveVehicleC2s(p) => {
-            if client.pending_teleports != 0 {
+            if client.is_teleporting() {
                 return Ok(false);
             }
 
@@ -1157,10 +1249,18 @@ fn handle_one_packet(
         }
         C2sPlayPacket::PlayerAbilitiesC2s(p) => match p {
             PlayerAbilitiesC2s::StopFlying => {
-                events.2.stop_flying.send(StopFlying { client: entity })
+                client.flying = false;
+                events.2.stop_flying.send(StopFlying { client: entity });
             }
             PlayerAbilitiesC2s::StartFlying => {
-                events.2.start_flying.send(StartFlying { client: entity })
+                if client.may_fly() {
+                    client.flying = true;
+                } else {
+                    // Tell the client to stop flying, which it isn't allowed to.
+                    client.abilities_modified = true;
+                }
+
+                events.2.start_flying.send(StartFlying { client: entity });
             }
         },
         C2sPlayPacket::PlayerAction(p) => {
@@ -1411,8 +1511,8 @@ fn handle_one_packet(
                 position: p.position,
                 face: p.face,
                 cursor_pos: p.cursor_pos.into(),
-                head_inside_block: false,
-                sequence: 0,
+                head_inside_block: p.head_inside_block,
+                sequence: p.sequence.0,
             })
         }
         C2sPlayPacket::UseItem(p) => {
@@ -1448,6 +1548,7 @@ fn handle_one_packet(
 /// function correctly.
 #[allow(clippy::too_many_arguments)]
 pub fn default_event_handler(
+    server: Res<Server>,
     mut clients: Query<(&mut Client, Option<&mut McEntity>)>,
     mut update_settings: EventReader<UpdateSettings>,
     mut move_player: EventReader<MovePlayer>,
@@ -1456,9 +1557,13 @@ pub fn default_event_handler(
     mut start_sprinting: EventReader<StartSprinting>,
     mut stop_sprinting: EventReader<StopSprinting>,
     mut swing_arm: EventReader<SwingArm>,
+    mut request_game_mode: EventReader<RequestGameMode>,
+    mut teleport_to_entity: EventReader<TeleportToEntity>,
+    other_entities: Query<&McEntity, Without<Client>>,
 ) {
     for UpdateSettings {
         client,
+        locale,
         view_distance,
         displayed_skin_parts,
         main_hand,
@@ -1470,6 +1575,7 @@ pub fn default_event_handler(
         };
 
         client.set_view_distance(*view_distance);
+        client.set_locale(locale.clone());
 
         let player = client.player_mut();
 
@@ -1517,12 +1623,21 @@ pub fn default_event_handler(
     }
 
     for StartSneaking { client } in start_sneaking.iter() {
-        let Ok((_, Some(mut entity))) = clients.get_mut(*client) else {
+        let Ok((mut client, entity)) = clients.get_mut(*client) else {
+            continue
+        };
+
+        // Like in vanilla, spectators leave the camera of an entity by sneaking.
+        client.set_camera(None);
+
+        let Some(mut entity) = entity else {
             continue
         };
 
         if let TrackedData::Player(player) = entity.data_mut() {
             player.set_pose(Pose::Sneaking);
+            // Fades the name tag for other players and hides it behind walls.
+            player.set_sneaking(true);
         }
     }
 
@@ -1533,6 +1648,7 @@ pub fn default_event_handler(
 
         if let TrackedData::Player(player) = entity.data_mut() {
             player.set_pose(Pose::Standing);
+            player.set_sneaking(false);
         }
     }
 
@@ -1568,4 +1684,39 @@ pub fn default_event_handler(
             });
         }
     }
+
+    for RequestGameMode { client, game_mode } in request_game_mode.iter() {
+        let Ok((mut client, _)) = clients.get_mut(*client) else {
+            continue
+        };
+
+        // Vanilla requires this op level for `/gamemode`.
+        if client.op_level() >= 2 {
+            client.set_game_mode(*game_mode);
+        }
+    }
+
+    for TeleportToEntity { client, target } in teleport_to_entity.iter() {
+        let player_target = server
+            .client_by_uuid(*target)
+            .and_then(|target| clients.get(target).ok())
+            .map(|(client, _)| (client.instance(), client.position()));
+
+        let Some((instance, position)) = player_target.or_else(|| {
+            server
+                .entity_by_uuid(*target)
+                .and_then(|target| other_entities.get(target).ok())
+                .map(|entity| (entity.instance(), entity.position()))
+        }) else {
+            continue
+        };
+
+        let Ok((mut client, _)) = clients.get_mut(*client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator {
+            client.transfer_to_instance(instance, position);
+        }
+    }
 }
@@ -0,0 +1,718 @@
+//! Server-side melee combat.
+//!
+//! The [`CombatPlugin`] handles clients attacking entities with a [`Health`]
+//! component like vanilla does:
+//!
+//! - Attacks are only accepted if the hitbox of the target is within reach of
+//!   the eyes of the attacker.
+//! - The damage of the attacker is dealt to the target, with critical hits
+//!   while the attacker is airborne.
+//! - After a hit, targets are invulnerable for a few ticks. Stronger hits in
+//!   that time only deal the damage above the previous hit.
+//! - Targets are knocked back away from the attacker, farther if the attacker
+//!   is sprinting.
+//!
+//! Every hit sends an [`EntityDamaged`] event, and an [`EntityKilled`] event
+//! when the health of the target reaches zero. Killed clients are shown the
+//! death screen and other entities are removed with
+//! [`RemovalReason::Killed`]. The [`Health`] of clients is sent to them
+//! whenever it changes, along with their [`Food`]. Killed clients are healed
+//! to their maximum health when they respawn.
+//!
+//! Entities with [`Health`] are also damaged by explosions created with
+//! [`Instance::explode`] before [`CoreStage::PostUpdate`]. The attacker of
+//! these hits is the [source] of the explosion, or the instance if there is
+//! none.
+//!
+//! Valence does not simulate physics, so the knockback of entities which are
+//! not clients is only reported in [`EntityDamaged::knockback`] for the game
+//! to apply.
+//!
+//! [source]: crate::instance::ExplosionOptions::source
+//!
+//! ```
+//! use valence::combat::{CombatPlugin, EntityKilled, Health};
+//! use valence::prelude::*;
+//!
+//! let mut app = App::new();
+//! app.add_plugin(CombatPlugin);
+//!
+//! fn spawn_zombie(mut commands: Commands, instance: Entity) {
+//!     let zombie = McEntity::new(EntityKind::Zombie, instance);
+//!     commands.spawn((zombie, Health::new(20.0)));
+//! }
+//!
+//! fn announce_kills(mut events: EventReader<EntityKilled>) {
+//!     for event in events.iter() {
+//!         println!("{:?} killed {:?}", event.killer, event.victim);
+//!     }
+//! }
+//! ```
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::{DVec3, Vec3};
+use valence_protocol::packets::s2c::play::SetHealth;
+use valence_protocol::types::{EntityInteraction, GameMode};
+use valence_protocol::{Text, VarInt};
+
+use crate::client::event::{InteractWithEntity, PerformRespawn};
+use crate::client::{Client, PLAYER_EYE_HEIGHT};
+use crate::config::DEFAULT_TPS;
+use crate::entity::{DamageSource, McEntity, McEntityManager, RemovalReason};
+use crate::instance::Instance;
+use crate::math::from_yaw_and_pitch;
+use crate::pause::gameplay_systems;
+
+/// Adds the [`CombatSettings`] resource, the combat events, and the systems
+/// handling attacks and sending the health of clients.
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CombatSettings>()
+            .add_event::<EntityDamaged>()
+            .add_event::<EntityKilled>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                respawn_clients.before("valence_core"),
+            )
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                gameplay_systems()
+                    .with_system(tick_invulnerability.before("valence_core"))
+                    .with_system(
+                        handle_attacks
+                            .after(tick_invulnerability)
+                            .after(respawn_clients)
+                            .before("valence_core"),
+                    )
+                    .with_system(
+                        damage_from_explosions
+                            .after(handle_attacks)
+                            .before("valence_core"),
+                    ),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                send_health
+                    .after(damage_from_explosions)
+                    .after(respawn_clients)
+                    .before("valence_core"),
+            );
+    }
+}
+
+/// Configures the combat of the [`CombatPlugin`]. The defaults match vanilla.
+#[derive(Resource, Clone, Debug)]
+pub struct CombatSettings {
+    /// The maximum distance in blocks from the eyes of an attacker to the
+    /// hitbox of its target.
+    pub reach: f64,
+    /// The reach of attackers in creative mode.
+    pub creative_reach: f64,
+    /// The damage dealt by attackers without an [`AttackDamage`] component.
+    pub base_damage: f32,
+    /// The damage multiplier of critical hits.
+    pub critical_multiplier: f32,
+    /// The number of ticks a target is invulnerable for after a hit.
+    pub invulnerability_ticks: u32,
+    /// The strength of the knockback of a hit, in blocks per tick.
+    pub knockback: f32,
+    /// The knockback strength added when the attacker is sprinting.
+    pub sprint_knockback: f32,
+}
+
+impl Default for CombatSettings {
+    fn default() -> Self {
+        Self {
+            reach: 3.0,
+            creative_reach: 5.0,
+            base_damage: 1.0,
+            critical_multiplier: 1.5,
+            invulnerability_ticks: 10,
+            knockback: 0.4,
+            sprint_knockback: 0.5,
+        }
+    }
+}
+
+/// The health of an entity which can be attacked.
+#[derive(Component, Copy, Clone, PartialEq, Debug)]
+pub struct Health {
+    /// The current health, from zero to `max`.
+    pub current: f32,
+    pub max: f32,
+    /// The ticks left until the entity can take full damage again.
+    invulnerable_ticks: u32,
+    /// The damage of the hit which made the entity invulnerable.
+    last_damage: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            invulnerable_ticks: 0,
+            last_damage: 0.0,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    /// Returns if the entity was hit in the last few ticks. Hits in this time
+    /// only deal the damage above the previous hit.
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable_ticks > 0
+    }
+
+    /// Heals the entity by `amount`, up to its maximum health.
+    pub fn heal(&mut self, amount: f32) {
+        self.current = (self.current + amount.max(0.0)).min(self.max);
+    }
+
+    /// Deals `amount` damage, making the entity invulnerable for
+    /// `invulnerability_ticks`. Returns the damage actually dealt, or `None` if
+    /// the entity is invulnerable to the hit.
+    pub fn hurt(&mut self, amount: f32, invulnerability_ticks: u32) -> Option<f32> {
+        if self.is_dead() || amount <= 0.0 {
+            return None;
+        }
+
+        let dealt = if self.is_invulnerable() {
+            if amount <= self.last_damage {
+                return None;
+            }
+
+            amount - self.last_damage
+        } else {
+            self.invulnerable_ticks = invulnerability_ticks;
+            amount
+        };
+
+        self.last_damage = amount;
+        self.current = (self.current - dealt).max(0.0);
+
+        Some(dealt)
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new(20.0)
+    }
+}
+
+/// The food bar of a client, which is sent along with its [`Health`]. Clients
+/// without this component are shown a full food bar.
+#[derive(Component, Copy, Clone, PartialEq, Debug)]
+pub struct Food {
+    /// The food level, from 0 to 20.
+    pub level: i32,
+    /// The saturation, from zero to `level`.
+    pub saturation: f32,
+}
+
+impl Default for Food {
+    fn default() -> Self {
+        Self {
+            level: 20,
+            saturation: 5.0,
+        }
+    }
+}
+
+/// The damage dealt by an attacking client, replacing
+/// [`CombatSettings::base_damage`].
+#[derive(Component, Copy, Clone, PartialEq, Debug)]
+pub struct AttackDamage(pub f32);
+
+/// Sent when an entity was damaged by an attack or an explosion.
+#[derive(Clone, Debug)]
+pub struct EntityDamaged {
+    pub attacker: Entity,
+    pub target: Entity,
+    /// The damage dealt to the target.
+    pub amount: f32,
+    pub critical: bool,
+    /// The knockback velocity of the target in meters per second.
+    pub knockback: Vec3,
+}
+
+/// Sent when the health of an entity reached zero from an attack or an
+/// explosion.
+#[derive(Clone, Debug)]
+pub struct EntityKilled {
+    pub killer: Entity,
+    pub victim: Entity,
+}
+
+fn tick_invulnerability(mut healths: Query<&mut Health>) {
+    for mut health in &mut healths {
+        // The health itself is not changed, so it is not sent again to clients.
+        if health.invulnerable_ticks > 0 {
+            health.bypass_change_detection().invulnerable_ticks -= 1;
+        }
+    }
+}
+
+fn respawn_clients(mut healths: Query<&mut Health>, mut events: EventReader<PerformRespawn>) {
+    for event in events.iter() {
+        // Clients also respawn after the end credits without having died.
+        let Ok(mut health) = healths.get_mut(event.client) else {
+            continue
+        };
+
+        if health.is_dead() {
+            *health = Health::new(health.max);
+        }
+    }
+}
+
+fn handle_attacks(
+    mut commands: Commands,
+    settings: Res<CombatSettings>,
+    manager: Res<McEntityManager>,
+    mut combatants: Query<(
+        Option<&mut Client>,
+        Option<&mut McEntity>,
+        Option<&mut Health>,
+        Option<&AttackDamage>,
+    )>,
+    mut attacks: EventReader<InteractWithEntity>,
+    mut damaged: EventWriter<EntityDamaged>,
+    mut killed: EventWriter<EntityKilled>,
+) {
+    for event in attacks.iter() {
+        if !matches!(event.interact, EntityInteraction::Attack) {
+            continue;
+        }
+
+        let Some(target) = manager.get_with_protocol_id(event.entity_id) else {
+            continue
+        };
+
+        let Ok([attacker, victim]) = combatants.get_many_mut([event.client, target]) else {
+            continue
+        };
+
+        let (attacker_client, attacker_entity, attacker_health, attack_damage) = attacker;
+
+        let Some(attacker_client) = attacker_client else {
+            continue
+        };
+
+        let (mut victim_client, victim_entity, health, _) = victim;
+
+        let (Some(mut victim_entity), Some(mut health)) = (victim_entity, health) else {
+            continue
+        };
+
+        if attacker_client.game_mode() == GameMode::Spectator
+            || attacker_health.map_or(false, |h| h.is_dead())
+            || attacker_client.instance() != victim_entity.instance()
+        {
+            continue;
+        }
+
+        if victim_client
+            .as_ref()
+            .map_or(false, |c| c.is_invulnerable())
+        {
+            continue;
+        }
+
+        let reach = if attacker_client.game_mode() == GameMode::Creative {
+            settings.creative_reach
+        } else {
+            settings.reach
+        };
+
+        let eyes = attacker_client.position() + DVec3::new(0.0, PLAYER_EYE_HEIGHT, 0.0);
+
+        if victim_entity.hitbox().distance_squared_to_point(eyes) > reach * reach {
+            continue;
+        }
+
+        let critical = !attacker_client.on_ground();
+
+        let mut amount = attack_damage.map_or(settings.base_damage, |d| d.0);
+
+        if critical {
+            amount *= settings.critical_multiplier;
+        }
+
+        let Some(amount) = health.hurt(amount, settings.invulnerability_ticks) else {
+            continue
+        };
+
+        // Knock the target back away from the attacker, or in the direction
+        // the attacker is looking if they are in the same spot.
+        let offset = (victim_entity.position() - attacker_client.position()).as_vec3();
+        let mut direction = Vec3::new(offset.x, 0.0, offset.z).normalize_or_zero();
+
+        if direction == Vec3::ZERO {
+            direction = from_yaw_and_pitch(attacker_client.yaw(), 0.0);
+        }
+
+        let sprinting = attacker_entity
+            .as_ref()
+            .map_or(false, |e| e.data().sprinting());
+
+        let strength = settings.knockback
+            + if sprinting {
+                settings.sprint_knockback
+            } else {
+                0.0
+            };
+
+        let on_ground = victim_client
+            .as_ref()
+            .map_or(victim_entity.on_ground(), |c| c.on_ground());
+
+        let knockback = Vec3::new(
+            direction.x * strength,
+            if on_ground { settings.knockback } else { 0.0 },
+            direction.z * strength,
+        ) * DEFAULT_TPS as f32;
+
+        if critical {
+            victim_entity.play_crit_particles(false);
+        }
+
+        victim_entity.play_damage_effect(DamageSource::Generic);
+
+        if let Some(client) = &mut victim_client {
+            client.add_velocity(knockback);
+        }
+
+        damaged.send(EntityDamaged {
+            attacker: event.client,
+            target,
+            amount,
+            critical,
+            knockback,
+        });
+
+        if !health.is_dead() {
+            continue;
+        }
+
+        match &mut victim_client {
+            Some(client) => {
+                let message = Text::translate(
+                    "death.attack.player",
+                    [
+                        Text::from(client.username().to_string()),
+                        Text::from(attacker_client.username().to_string()),
+                    ],
+                );
+
+                client.kill(attacker_entity.as_deref(), message);
+                victim_entity.play_death_animation();
+            }
+            None => {
+                commands.entity(target).insert(RemovalReason::Killed);
+            }
+        }
+
+        killed.send(EntityKilled {
+            killer: event.client,
+            victim: target,
+        });
+    }
+}
+
+fn damage_from_explosions(
+    mut commands: Commands,
+    settings: Res<CombatSettings>,
+    instances: Query<(Entity, &Instance)>,
+    mut victims: Query<(Entity, &mut McEntity, &mut Health, Option<&mut Client>)>,
+    mut damaged: EventWriter<EntityDamaged>,
+    mut killed: EventWriter<EntityKilled>,
+) {
+    for (instance_ent, instance) in &instances {
+        for explosion in instance.explosions() {
+            if !explosion.options.damage_entities {
+                continue;
+            }
+
+            let attacker = explosion.options.source.unwrap_or(instance_ent);
+
+            for (target, mut entity, mut health, mut client) in &mut victims {
+                if entity.instance() != instance_ent || health.is_dead() {
+                    continue;
+                }
+
+                if client.as_ref().map_or(false, |c| c.is_invulnerable()) {
+                    continue;
+                }
+
+                let hitbox = entity.hitbox();
+
+                let Some(impact) = explosion.impact(instance, entity.position(), hitbox) else {
+                    continue
+                };
+
+                let ticks = settings.invulnerability_ticks;
+
+                let Some(amount) = health.hurt(impact.damage, ticks) else {
+                    continue
+                };
+
+                entity.play_damage_effect(DamageSource::Generic);
+
+                damaged.send(EntityDamaged {
+                    attacker,
+                    target,
+                    amount,
+                    critical: false,
+                    knockback: impact.knockback,
+                });
+
+                if !health.is_dead() {
+                    continue;
+                }
+
+                match &mut client {
+                    Some(client) => {
+                        let message = Text::translate(
+                            "death.attack.explosion",
+                            [Text::from(client.username().to_string())],
+                        );
+
+                        client.kill(None, message);
+                        entity.play_death_animation();
+                    }
+                    None => {
+                        commands.entity(target).insert(RemovalReason::Killed);
+                    }
+                }
+
+                killed.send(EntityKilled {
+                    killer: attacker,
+                    victim: target,
+                });
+            }
+        }
+    }
+}
+
+fn send_health(
+    mut clients: Query<(&mut Client, &Health, Option<&Food>), Or<(Changed<Health>, Changed<Food>)>>,
+) {
+    for (mut client, health, food) in &mut clients {
+        let food = food.copied().unwrap_or_default();
+
+        client.write_packet(&SetHealth {
+            health: health.current,
+            food: VarInt(food.level),
+            food_saturation: food.saturation,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::entity::EntityKind;
+    use crate::instance::ExplosionOptions;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn health_invulnerability() {
+        let mut health = Health::new(20.0);
+
+        assert_eq!(health.hurt(4.0, 10), Some(4.0));
+        assert!(health.is_invulnerable());
+        assert_eq!(health.hurt(3.0, 10), None);
+        assert_eq!(health.hurt(6.0, 10), Some(2.0));
+        assert_eq!(health.current, 14.0);
+
+        health.heal(100.0);
+        assert_eq!(health.current, 20.0);
+    }
+
+    #[test]
+    fn attack_and_kill() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(CombatPlugin);
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([0.0, 64.0, 0.0]);
+        let instance = client.instance();
+
+        let spawn = |app: &mut App, x| {
+            let mut zombie = McEntity::new(EntityKind::Zombie, instance);
+            zombie.set_position([x, 64.0, 0.0]);
+            app.world.spawn((zombie, Health::new(2.0))).id()
+        };
+
+        let near = spawn(&mut app, 1.5);
+        let far = spawn(&mut app, 10.0);
+
+        app.update();
+
+        for target in [far, near] {
+            let entity_id = app.world.get::<McEntity>(target).unwrap().protocol_id();
+
+            app.world.send_event(InteractWithEntity {
+                client: client_ent,
+                entity_id,
+                sneaking: false,
+                interact: EntityInteraction::Attack,
+            });
+        }
+
+        app.update();
+
+        // The far zombie is out of reach. The client is not on the ground, so the
+        // hit is critical.
+        assert_eq!(app.world.get::<Health>(far).unwrap().current, 2.0);
+        assert_eq!(app.world.get::<Health>(near).unwrap().current, 0.5);
+
+        let events = app.world.resource::<Events<EntityDamaged>>();
+        let hits: Vec<_> = events.get_reader().iter(events).cloned().collect();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].target, near);
+        assert!(hits[0].critical);
+        assert!(hits[0].knockback.x > 0.0);
+
+        // Skip the invulnerability of the zombie.
+        app.world
+            .get_mut::<Health>(near)
+            .unwrap()
+            .invulnerable_ticks = 0;
+
+        let entity_id = app.world.get::<McEntity>(near).unwrap().protocol_id();
+        app.world.send_event(InteractWithEntity {
+            client: client_ent,
+            entity_id,
+            sneaking: false,
+            interact: EntityInteraction::Attack,
+        });
+
+        app.update();
+
+        assert!(app.world.get::<Health>(near).unwrap().is_dead());
+        assert_eq!(
+            app.world.get::<RemovalReason>(near),
+            Some(&RemovalReason::Killed)
+        );
+
+        let events = app.world.resource::<Events<EntityKilled>>();
+        let kills: Vec<_> = events.get_reader().iter(events).cloned().collect();
+        assert_eq!(kills.len(), 1);
+        assert_eq!(kills[0].victim, near);
+    }
+
+    #[test]
+    fn respawn_restores_health() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(CombatPlugin);
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([0.0, 64.0, 0.0]);
+        let instance = client.instance();
+
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance);
+        zombie.set_position([1.5, 64.0, 0.0]);
+        let zombie = app.world.spawn((zombie, Health::new(20.0))).id();
+
+        let mut health = Health::new(20.0);
+        health.hurt(20.0, 0);
+        app.world.entity_mut(client_ent).insert((
+            health,
+            Food {
+                level: 6,
+                saturation: 0.0,
+            },
+        ));
+
+        app.update();
+
+        let entity_id = app.world.get::<McEntity>(zombie).unwrap().protocol_id();
+        let attack = InteractWithEntity {
+            client: client_ent,
+            entity_id,
+            sneaking: false,
+            interact: EntityInteraction::Attack,
+        };
+
+        // Dead clients can't attack.
+        app.world.send_event(attack.clone());
+        app.update();
+
+        assert_eq!(app.world.get::<Health>(zombie).unwrap().current, 20.0);
+
+        app.world.send_event(PerformRespawn { client: client_ent });
+        client_helper.clear_sent();
+        app.update();
+
+        assert_eq!(app.world.get::<Health>(client_ent).unwrap().current, 20.0);
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        let set_health = sent_packets.iter().find_map(|p| match p {
+            S2cPlayPacket::SetHealth(p) => Some(p),
+            _ => None,
+        });
+        assert_eq!(set_health.map(|p| (p.health, p.food.0)), Some((20.0, 6)));
+
+        app.world.send_event(attack);
+        app.update();
+
+        assert!(app.world.get::<Health>(zombie).unwrap().current < 20.0);
+    }
+
+    #[test]
+    fn explosion_damage() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(CombatPlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let spawn = |app: &mut App, x| {
+            let mut zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+            zombie.set_position([x, 64.0, 0.0]);
+            app.world.spawn((zombie, Health::new(100.0))).id()
+        };
+
+        let near = spawn(&mut app, 2.0);
+        let far = spawn(&mut app, 20.0);
+
+        app.update();
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .explode([0.0, 64.0, 0.0], 4.0, ExplosionOptions::default());
+
+        app.update();
+
+        assert!(app.world.get::<Health>(near).unwrap().current < 100.0);
+        assert_eq!(app.world.get::<Health>(far).unwrap().current, 100.0);
+
+        let events = app.world.resource::<Events<EntityDamaged>>();
+        let hits: Vec<_> = events.get_reader().iter(events).cloned().collect();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].target, near);
+        assert_eq!(hits[0].attacker, instance_ent);
+        assert!(hits[0].knockback.x > 0.0);
+
+        // Explosions only last for the tick they were created in.
+        assert!(app
+            .world
+            .get::<Instance>(instance_ent)
+            .unwrap()
+            .explosions()
+            .is_empty());
+    }
+}
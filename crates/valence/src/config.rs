@@ -1,16 +1,23 @@
+use std::any::{Any, TypeId};
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+#[cfg(unix)]
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use bevy_app::{App, Plugin};
+use rustc_hash::FxHashMap;
 use serde::Serialize;
 use tokio::runtime::Handle;
 use tracing::error;
 use uuid::Uuid;
-use valence_protocol::{Text, Username};
+use valence_protocol::{Text, Username, DEFAULT_COMPRESSION_LEVEL, PROTOCOL_VERSION};
 
 use crate::biome::Biome;
 use crate::dimension::Dimension;
+use crate::entity::EntityKind;
 use crate::server::{NewClientInfo, SharedServer};
 
 #[derive(Clone)]
@@ -35,12 +42,31 @@ pub struct ServerPlugin<A> {
     ///
     /// `1024`. This may change in a future version.
     pub max_connections: usize,
-    /// The socket address the server will be bound to.
+    /// Whether or not the server listens for connections on
+    /// [`Self::listeners`].
+    ///
+    /// When `false`, Valence runs in embedded mode: no network listener is
+    /// opened and the server does not take over the app's runner. Each call to
+    /// [`App::update`] simulates a single tick. Clients can still join through
+    /// [`SharedServer::connect_local_client`]. This is useful for tests and
+    /// tools which only need Valence's world model.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub listen: bool,
+    /// The [`Listener`]s the server accepts connections on. Each listener is
+    /// bound to its own TCP address or Unix socket and can have its own
+    /// settings.
+    ///
+    /// IPv6 listeners only accept IPv6 connections, so a server listening on
+    /// both IPv4 and IPv6 needs a listener for each.
     ///
     /// # Default Value
     ///
-    /// `0.0.0.0:25565`, which will listen on every available network interface.
-    pub address: SocketAddr,
+    /// A single listener on `0.0.0.0:25565`, which will listen on every
+    /// available IPv4 network interface.
+    pub listeners: Arc<[Listener]>,
     /// The ticks per second of the server. This is the number of game updates
     /// that should occur in one second.
     ///
@@ -57,6 +83,19 @@ pub struct ServerPlugin<A> {
     ///
     /// [`DEFAULT_TPS`]
     pub tps: i64,
+    /// What the server does when it falls behind its tick schedule because
+    /// ticks took longer than `1 / tps` seconds. A [`ServerLagging`] event is
+    /// sent when this happens.
+    ///
+    /// This has no effect in embedded mode, where the ticks are driven by the
+    /// user.
+    ///
+    /// # Default Value
+    ///
+    /// [`TickLagPolicy::Skip`]
+    ///
+    /// [`ServerLagging`]: crate::server::ServerLagging
+    pub tick_lag_policy: TickLagPolicy,
     /// The connection mode. This determines if client authentication and
     /// encryption should take place and if the server should get the player
     /// data from a proxy.
@@ -65,6 +104,20 @@ pub struct ServerPlugin<A> {
     ///
     /// [`ConnectionMode::Online`]
     pub connection_mode: ConnectionMode,
+    /// The base URL of the session server used to authenticate players in
+    /// [online mode]. This can point to a mirror of Mojang's session server
+    /// or to a compatible authentication server, such as one used with
+    /// authlib-injector.
+    ///
+    /// This is used by the default implementation of
+    /// [`AsyncCallbacks::session_server`].
+    ///
+    /// # Default Value
+    ///
+    /// [`DEFAULT_SESSION_SERVER_URL`]
+    ///
+    /// [online mode]: ConnectionMode::Online
+    pub session_server_url: Arc<str>,
     /// The compression threshold to use for compressing packets. For a
     /// compression threshold of `Some(N)`, packets with encoded lengths >= `N`
     /// are compressed while all others are not. `None` disables compression
@@ -77,6 +130,143 @@ pub struct ServerPlugin<A> {
     ///
     /// Compression is enabled with an unspecified threshold.
     pub compression_threshold: Option<u32>,
+    /// The zlib compression level for packets above the
+    /// [compression threshold], from 0 (fastest) to 9 (smallest). Higher
+    /// levels save bandwidth at the cost of CPU time.
+    ///
+    /// The level of individual clients can be changed with
+    /// [`Client::set_compression_level`].
+    ///
+    /// # Default Value
+    ///
+    /// [`DEFAULT_COMPRESSION_LEVEL`]
+    ///
+    /// [compression threshold]: Self::compression_threshold
+    /// [`Client::set_compression_level`]: crate::client::Client::set_compression_level
+    pub compression_level: u32,
+    /// Whether or not packets sent to clients connected from the loopback
+    /// address (e.g. through a proxy on the same machine) are compressed.
+    /// Compressing them only costs CPU time.
+    ///
+    /// When `false`, the packets encoded for these clients individually are
+    /// not compressed. Packet data shared between clients, such as chunks,
+    /// is compressed once for all clients and is still sent compressed. This
+    /// has no effect if compression is disabled with
+    /// [`Self::compression_threshold`].
+    ///
+    /// See also [`Client::set_compression_enabled`].
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    ///
+    /// [`Client::set_compression_enabled`]: crate::client::Client::set_compression_enabled
+    pub compress_loopback: bool,
+    /// How long the responses to server list pings are reused. Responses are
+    /// cached separately for each protocol version sent by clients. A
+    /// duration of zero disables the cache.
+    ///
+    /// While a response is cached, [`AsyncCallbacks::server_list_ping`] is
+    /// not called, so responses specific to the pinging client should not be
+    /// cached.
+    ///
+    /// # Default Value
+    ///
+    /// `Duration::ZERO`
+    pub ping_cache_ttl: Duration,
+    /// The maximum number of server list pings accepted from a single IP
+    /// address per minute. Further pings in the same minute are ignored.
+    /// `None` disables the limit.
+    ///
+    /// Behind a proxy, all pings come from the address of the proxy, so the
+    /// limit should be disabled there.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub ping_rate_limit: Option<u32>,
+    /// The maximum number of chunks sent to all clients per tick. `None`
+    /// sends every chunk as soon as it enters a client's view.
+    ///
+    /// With a limit, the chunks entering the view of each client are queued
+    /// nearest first and the clients take turns receiving one chunk at a
+    /// time, so that a client loading many chunks at once (e.g. when joining
+    /// or teleporting) cannot starve the others.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub max_chunk_sends_per_tick: Option<usize>,
+    /// The maximum number of bytes of chunk data sent to all clients per tick.
+    /// `None` disables the limit. Like [`Self::max_chunk_sends_per_tick`],
+    /// this queues chunks and sends them nearest first.
+    ///
+    /// The chunk which reaches the limit is still sent, so at least one chunk
+    /// is sent every tick.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub max_chunk_bytes_per_tick: Option<usize>,
+    /// The maximum number of chunks sent to a single client per tick. `None`
+    /// disables the limit. This spreads out the chunks loaded by clients with
+    /// large view distances over several ticks.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub max_client_chunk_sends_per_tick: Option<usize>,
+    /// The maximum number of bytes of chunk data sent to a single client per
+    /// tick. `None` disables the limit. The chunk which reaches the limit is
+    /// still sent.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub max_client_chunk_bytes_per_tick: Option<usize>,
+    /// The distance (in blocks) an entity must move in a single tick on any
+    /// axis to be teleported instead of moved with a relative move packet.
+    ///
+    /// Relative moves are smaller, but each one is rounded to 1/4096 of a
+    /// block. Lowering the threshold resynchronizes the exact position of fast
+    /// moving entities more often. Values above
+    /// [`MAX_ENTITY_TELEPORT_THRESHOLD`] have no effect, because relative moves
+    /// can't encode larger distances.
+    ///
+    /// # Default Value
+    ///
+    /// [`MAX_ENTITY_TELEPORT_THRESHOLD`]
+    pub entity_teleport_threshold: f64,
+    /// The tracking range (in chunks) of entities by [`EntityKind`]. Clients
+    /// only see entities of a kind within this many chunks of them, or within
+    /// their view distance if that is smaller. Kinds without an entry are
+    /// tracked up to the view distance of the client.
+    ///
+    /// Instances can override these ranges with
+    /// [`Instance::set_entity_tracking_range`].
+    ///
+    /// [`Instance::set_entity_tracking_range`]: crate::instance::Instance::set_entity_tracking_range
+    ///
+    /// # Default Value
+    ///
+    /// Empty
+    pub entity_tracking_ranges: FxHashMap<EntityKind, u8>,
+    /// The time between keepalive packets sent to each client. The ping of
+    /// clients is measured with these packets.
+    ///
+    /// # Default Value
+    ///
+    /// `Duration::from_secs(10)`
+    pub keepalive_interval: Duration,
+    /// The time clients have to respond to a keepalive packet before they are
+    /// disconnected and a [`ClientTimedOut`] event is sent.
+    ///
+    /// # Default Value
+    ///
+    /// `Duration::from_secs(10)`
+    ///
+    /// [`ClientTimedOut`]: crate::client::ClientTimedOut
+    pub client_timeout: Duration,
     /// The maximum capacity (in bytes) of the buffer used to hold incoming
     /// packet data.
     ///
@@ -101,6 +291,14 @@ pub struct ServerPlugin<A> {
     /// An unspecified value is used that should be adequate for most
     /// situations. This default may change in future versions.
     pub outgoing_capacity: usize,
+    /// What the server does with the packets of a client which can't keep up
+    /// with them and whose outgoing buffer is full. See
+    /// [`OutgoingOverflowPolicy`].
+    ///
+    /// # Default Value
+    ///
+    /// [`OutgoingOverflowPolicy::Kick`]
+    pub outgoing_overflow_policy: OutgoingOverflowPolicy,
     /// The list of [`Dimension`]s usable on the server.
     ///
     /// The dimensions returned by [`ServerPlugin::dimensions`] will be in the
@@ -139,15 +337,36 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
             callbacks: callbacks.into(),
             tokio_handle: None,
             max_connections: 1024,
-            address: SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 25565).into(),
+            listen: true,
+            listeners: [Listener::new(SocketAddr::from(SocketAddrV4::new(
+                Ipv4Addr::new(0, 0, 0, 0),
+                25565,
+            )))]
+            .as_slice()
+            .into(),
             tps: DEFAULT_TPS,
+            tick_lag_policy: TickLagPolicy::default(),
             connection_mode: ConnectionMode::Online {
                 // Note: Some people have problems using valence when this is enabled by default.
                 prevent_proxy_connections: false,
             },
+            session_server_url: DEFAULT_SESSION_SERVER_URL.into(),
             compression_threshold: Some(256),
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            compress_loopback: true,
+            ping_cache_ttl: Duration::ZERO,
+            ping_rate_limit: None,
+            max_chunk_sends_per_tick: None,
+            max_chunk_bytes_per_tick: None,
+            max_client_chunk_sends_per_tick: None,
+            max_client_chunk_bytes_per_tick: None,
+            entity_teleport_threshold: MAX_ENTITY_TELEPORT_THRESHOLD,
+            entity_tracking_ranges: FxHashMap::default(),
+            keepalive_interval: Duration::from_secs(10),
+            client_timeout: Duration::from_secs(10),
             incoming_capacity: 2097152, // 2 MiB
             outgoing_capacity: 8388608, // 8 MiB
+            outgoing_overflow_policy: OutgoingOverflowPolicy::default(),
             dimensions: [Dimension::default()].as_slice().into(),
             biomes: [Biome::default()].as_slice().into(),
         }
@@ -167,10 +386,35 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
         self
     }
 
-    /// See [`Self::address`].
+    /// See [`Self::listen`].
+    #[must_use]
+    pub fn with_listen(mut self, listen: bool) -> Self {
+        self.listen = listen;
+        self
+    }
+
+    /// Replaces the [listeners](Self::listeners) with a single listener on
+    /// `address` using the default settings.
     #[must_use]
     pub fn with_address(mut self, address: SocketAddr) -> Self {
-        self.address = address;
+        self.listeners = [Listener::new(address)].as_slice().into();
+        self
+    }
+
+    /// Gets the address of the first TCP [listener](Self::listeners).
+    ///
+    /// # Panics
+    ///
+    /// Panics if no listener is bound to a TCP address.
+    #[deprecated = "use `listeners` instead"]
+    pub fn address(&self) -> SocketAddr {
+        first_tcp_address(&self.listeners).expect("no TCP listener is configured")
+    }
+
+    /// See [`Self::listeners`].
+    #[must_use]
+    pub fn with_listeners(mut self, listeners: impl Into<Arc<[Listener]>>) -> Self {
+        self.listeners = listeners.into();
         self
     }
 
@@ -181,6 +425,13 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
         self
     }
 
+    /// See [`Self::tick_lag_policy`].
+    #[must_use]
+    pub fn with_tick_lag_policy(mut self, tick_lag_policy: TickLagPolicy) -> Self {
+        self.tick_lag_policy = tick_lag_policy;
+        self
+    }
+
     /// See [`Self::connection_mode`].
     #[must_use]
     pub fn with_connection_mode(mut self, connection_mode: ConnectionMode) -> Self {
@@ -188,6 +439,13 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
         self
     }
 
+    /// See [`Self::session_server_url`].
+    #[must_use]
+    pub fn with_session_server_url(mut self, session_server_url: impl Into<Arc<str>>) -> Self {
+        self.session_server_url = session_server_url.into();
+        self
+    }
+
     /// See [`Self::compression_threshold`].
     #[must_use]
     pub fn with_compression_threshold(mut self, compression_threshold: Option<u32>) -> Self {
@@ -195,6 +453,103 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
         self
     }
 
+    /// See [`Self::compression_level`].
+    #[must_use]
+    pub fn with_compression_level(mut self, compression_level: u32) -> Self {
+        self.compression_level = compression_level;
+        self
+    }
+
+    /// See [`Self::compress_loopback`].
+    #[must_use]
+    pub fn with_compress_loopback(mut self, compress_loopback: bool) -> Self {
+        self.compress_loopback = compress_loopback;
+        self
+    }
+
+    /// See [`Self::ping_cache_ttl`].
+    #[must_use]
+    pub fn with_ping_cache_ttl(mut self, ping_cache_ttl: Duration) -> Self {
+        self.ping_cache_ttl = ping_cache_ttl;
+        self
+    }
+
+    /// See [`Self::ping_rate_limit`].
+    #[must_use]
+    pub fn with_ping_rate_limit(mut self, ping_rate_limit: Option<u32>) -> Self {
+        self.ping_rate_limit = ping_rate_limit;
+        self
+    }
+
+    /// See [`Self::max_chunk_sends_per_tick`].
+    #[must_use]
+    pub fn with_max_chunk_sends_per_tick(
+        mut self,
+        max_chunk_sends_per_tick: Option<usize>,
+    ) -> Self {
+        self.max_chunk_sends_per_tick = max_chunk_sends_per_tick;
+        self
+    }
+
+    /// See [`Self::max_chunk_bytes_per_tick`].
+    #[must_use]
+    pub fn with_max_chunk_bytes_per_tick(
+        mut self,
+        max_chunk_bytes_per_tick: Option<usize>,
+    ) -> Self {
+        self.max_chunk_bytes_per_tick = max_chunk_bytes_per_tick;
+        self
+    }
+
+    /// See [`Self::max_client_chunk_sends_per_tick`].
+    #[must_use]
+    pub fn with_max_client_chunk_sends_per_tick(
+        mut self,
+        max_client_chunk_sends_per_tick: Option<usize>,
+    ) -> Self {
+        self.max_client_chunk_sends_per_tick = max_client_chunk_sends_per_tick;
+        self
+    }
+
+    /// See [`Self::max_client_chunk_bytes_per_tick`].
+    #[must_use]
+    pub fn with_max_client_chunk_bytes_per_tick(
+        mut self,
+        max_client_chunk_bytes_per_tick: Option<usize>,
+    ) -> Self {
+        self.max_client_chunk_bytes_per_tick = max_client_chunk_bytes_per_tick;
+        self
+    }
+
+    /// See [`Self::entity_teleport_threshold`].
+    #[must_use]
+    pub fn with_entity_teleport_threshold(mut self, entity_teleport_threshold: f64) -> Self {
+        self.entity_teleport_threshold = entity_teleport_threshold;
+        self
+    }
+
+    /// Sets the tracking range of entities of the given kind. See
+    /// [`Self::entity_tracking_ranges`].
+    #[must_use]
+    pub fn with_entity_tracking_range(mut self, kind: EntityKind, range: u8) -> Self {
+        self.entity_tracking_ranges.insert(kind, range);
+        self
+    }
+
+    /// See [`Self::keepalive_interval`].
+    #[must_use]
+    pub fn with_keepalive_interval(mut self, keepalive_interval: Duration) -> Self {
+        self.keepalive_interval = keepalive_interval;
+        self
+    }
+
+    /// See [`Self::client_timeout`].
+    #[must_use]
+    pub fn with_client_timeout(mut self, client_timeout: Duration) -> Self {
+        self.client_timeout = client_timeout;
+        self
+    }
+
     /// See [`Self::incoming_capacity`].
     #[must_use]
     pub fn with_incoming_capacity(mut self, incoming_capacity: usize) -> Self {
@@ -209,6 +564,16 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
         self
     }
 
+    /// See [`Self::outgoing_overflow_policy`].
+    #[must_use]
+    pub fn with_outgoing_overflow_policy(
+        mut self,
+        outgoing_overflow_policy: OutgoingOverflowPolicy,
+    ) -> Self {
+        self.outgoing_overflow_policy = outgoing_overflow_policy;
+        self
+    }
+
     /// See [`Self::dimensions`].
     #[must_use]
     pub fn with_dimensions(mut self, dimensions: impl Into<Arc<[Dimension]>>) -> Self {
@@ -240,6 +605,27 @@ impl<A: AsyncCallbacks> Plugin for ServerPlugin<A> {
 
 #[async_trait]
 pub trait AsyncCallbacks: Send + Sync + 'static {
+    /// Called for every new connection after the handshake, before
+    /// [`Self::server_list_ping`] or the login process. Pings over the
+    /// [`ServerPlugin::ping_rate_limit`] are dropped without calling this.
+    ///
+    /// This method is the appropriate place to look up information about the
+    /// connection which is needed by the other callbacks or later by the game,
+    /// such as the location of the IP address. The results can be stored in
+    /// the [`ConnectionContext`], which is passed to the other callbacks and
+    /// remains available with [`Client::connection_context`].
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// Does nothing.
+    ///
+    /// [`Client::connection_context`]: crate::client::Client::connection_context
+    async fn connect(&self, shared: &SharedServer, ctx: &mut ConnectionContext) {
+        #![allow(unused_variables)]
+    }
+
     /// Called when the server receives a Server List Ping query.
     /// Data for the response can be provided or the query can be ignored.
     ///
@@ -251,8 +637,7 @@ pub trait AsyncCallbacks: Send + Sync + 'static {
     async fn server_list_ping(
         &self,
         shared: &SharedServer,
-        remote_addr: SocketAddr,
-        protocol_version: i32,
+        ctx: &ConnectionContext,
     ) -> ServerListPing {
         #![allow(unused_variables)]
         ServerListPing::Respond {
@@ -270,6 +655,11 @@ pub trait AsyncCallbacks: Send + Sync + 'static {
     /// returns with `Err(reason)`, then the client is immediately
     /// disconnected with `reason` as the displayed message.
     ///
+    /// The player data retrieved from the session server or proxy can be
+    /// changed through `info` before the client joins, for example to apply
+    /// a nickname or skin stored in a database. The login success and join
+    /// game packets are sent with the modified data.
+    ///
     /// This method is the appropriate place to perform asynchronous
     /// operations such as database queries which may take some time to
     /// complete.
@@ -281,7 +671,12 @@ pub trait AsyncCallbacks: Send + Sync + 'static {
     /// The client is allowed to join unconditionally.
     ///
     /// [`Client`]: crate::client::Client
-    async fn login(&self, shared: &SharedServer, info: &NewClientInfo) -> Result<(), Text> {
+    async fn login(
+        &self,
+        shared: &SharedServer,
+        ctx: &ConnectionContext,
+        info: &mut NewClientInfo,
+    ) -> Result<(), Text> {
         #![allow(unused_variables)]
         Ok(())
     }
@@ -299,10 +694,11 @@ pub trait AsyncCallbacks: Send + Sync + 'static {
     ///
     /// # Default Implementation
     ///
-    /// Uses the official Minecraft session server. This is formatted as
-    /// `https://sessionserver.mojang.com/session/minecraft/hasJoined?username=<username>&serverId=<auth-digest>&ip=<player-ip>`.
+    /// Uses the [configured session server URL]. This is formatted as
+    /// `<session-server-url>/session/minecraft/hasJoined?username=<username>&serverId=<auth-digest>&ip=<player-ip>`.
     ///
     /// [online mode]: crate::config::ConnectionMode::Online
+    /// [configured session server URL]: ServerPlugin::session_server_url
     async fn session_server(
         &self,
         shared: &SharedServer,
@@ -310,14 +706,16 @@ pub trait AsyncCallbacks: Send + Sync + 'static {
         auth_digest: &str,
         player_ip: &IpAddr,
     ) -> String {
+        let url = shared.session_server_url().trim_end_matches('/');
+
         if shared.connection_mode()
             == (&ConnectionMode::Online {
                 prevent_proxy_connections: true,
             })
         {
-            format!("https://sessionserver.mojang.com/session/minecraft/hasJoined?username={username}&serverId={auth_digest}&ip={player_ip}")
+            format!("{url}/session/minecraft/hasJoined?username={username}&serverId={auth_digest}&ip={player_ip}")
         } else {
-            format!("https://sessionserver.mojang.com/session/minecraft/hasJoined?username={username}&serverId={auth_digest}")
+            format!("{url}/session/minecraft/hasJoined?username={username}&serverId={auth_digest}")
         }
     }
 }
@@ -371,12 +769,27 @@ pub enum ConnectionMode {
     /// [`ConnectionMode::Offline`].
     ///
     /// To protect against this, a firewall can be used. However,
-    /// [`ConnectionMode::Velocity`] is recommended as a secure alternative.
+    /// [`ConnectionMode::BungeeGuard`] or [`ConnectionMode::Velocity`] are
+    /// recommended as secure alternatives.
     ///
     /// [BungeeCord]: https://www.spigotmc.org/wiki/bungeecord/
     /// [Waterfall]: https://github.com/PaperMC/Waterfall
     /// [Velocity]: https://velocitypowered.com/
     BungeeCord,
+    /// Like [`ConnectionMode::BungeeCord`], but the proxy must also forward
+    /// one of the given tokens with the player data, as done by the
+    /// [BungeeGuard] plugin. Connections without a valid token are blocked,
+    /// so only the proxy can connect players.
+    ///
+    /// Encryption is left to the proxy, as in all modes where player data is
+    /// forwarded.
+    ///
+    /// [BungeeGuard]: https://github.com/lucko/BungeeGuard
+    BungeeGuard {
+        /// The tokens accepted from the proxy. Tokens are compared in
+        /// constant time.
+        tokens: Arc<[Arc<str>]>,
+    },
     /// This mode is used when the server is behind a [Velocity] proxy
     /// configured with the forwarding mode `modern`.
     ///
@@ -391,9 +804,220 @@ pub enum ConnectionMode {
     },
 }
 
+/// An address the server accepts connections on, along with the settings of
+/// connections made through it. See [`ServerPlugin::listeners`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct Listener {
+    /// The address the listener is bound to.
+    pub address: ListenAddress,
+    /// Whether or not connections start with a [PROXY protocol] header (v1
+    /// or v2), as sent by proxies and load balancers such as HAProxy. The
+    /// address from the header is then used as the remote address of the
+    /// client instead of the address of the proxy.
+    ///
+    /// Connections without a valid header are closed, so this should only be
+    /// enabled on listeners which are exclusively reached through such a
+    /// proxy.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    ///
+    /// [PROXY protocol]: https://www.haproxy.org/download/2.7/doc/proxy-protocol.txt
+    pub proxy_protocol: bool,
+}
+
+impl Listener {
+    pub fn new(address: impl Into<ListenAddress>) -> Self {
+        Self {
+            address: address.into(),
+            proxy_protocol: false,
+        }
+    }
+
+    /// See [`Self::proxy_protocol`].
+    #[must_use]
+    pub fn with_proxy_protocol(mut self, proxy_protocol: bool) -> Self {
+        self.proxy_protocol = proxy_protocol;
+        self
+    }
+}
+
+/// Returns the address of the first listener bound to a TCP address.
+pub(crate) fn first_tcp_address(listeners: &[Listener]) -> Option<SocketAddr> {
+    listeners
+        .iter()
+        .find_map(|listener| match &listener.address {
+            ListenAddress::Tcp(addr) => Some(*addr),
+            #[cfg(unix)]
+            ListenAddress::Unix(_) => None,
+        })
+}
+
+/// The address of a [`Listener`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ListenAddress {
+    /// A TCP socket address.
+    Tcp(SocketAddr),
+    /// The path of a Unix domain socket, which is useful for proxies running
+    /// on the same machine or in the same container group. An existing socket
+    /// file at the path is replaced.
+    ///
+    /// Unix sockets have no IP address, so clients connecting through them
+    /// are given the remote address `127.0.0.1:0` unless the address is
+    /// forwarded with the [PROXY protocol](Listener::proxy_protocol) or by the
+    /// [connection mode](ConnectionMode).
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl From<SocketAddr> for ListenAddress {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Tcp(addr)
+    }
+}
+
+#[cfg(unix)]
+impl From<PathBuf> for ListenAddress {
+    fn from(path: PathBuf) -> Self {
+        Self::Unix(path)
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => addr.fmt(f),
+            #[cfg(unix)]
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// What the server does when the packets sent to a client don't fit in its
+/// outgoing buffer. See [`ServerPlugin::outgoing_overflow_policy`].
+///
+/// The packets sent to clients have three priority classes. Disconnect,
+/// keepalive and teleport packets are always sent, even when the buffer is
+/// full, so that kicked clients see the reason and slow clients neither time
+/// out nor miss teleports while their buffer drains. Game state packets must
+/// be sent in order and can't be dropped, so a client whose game state doesn't
+/// fit is kicked. Chunk data has the lowest priority if the policy allows
+/// dropping it.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum OutgoingOverflowPolicy {
+    /// All packets are queued in the order they are written, and clients
+    /// whose buffer overflows are kicked.
+    #[default]
+    Kick,
+    /// Chunks entering the view of a client are only sent while less than
+    /// half of its outgoing buffer is used. Otherwise, they are held back
+    /// until the client catches up, which leaves room for the game state.
+    /// Clients whose buffer still overflows are kicked.
+    DropChunks,
+}
+
 /// Minecraft's standard ticks per second (TPS).
 pub const DEFAULT_TPS: i64 = 20;
 
+/// The largest distance (in blocks) on any axis that an entity can move with a
+/// relative move packet. See [`ServerPlugin::entity_teleport_threshold`].
+pub const MAX_ENTITY_TELEPORT_THRESHOLD: f64 = 8.0;
+
+/// The base URL of Mojang's session server.
+pub const DEFAULT_SESSION_SERVER_URL: &str = "https://sessionserver.mojang.com";
+
+/// What the server does with the ticks it missed while it was behind its tick
+/// schedule. See [`ServerPlugin::tick_lag_policy`].
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum TickLagPolicy {
+    /// The missed ticks are skipped and the schedule continues from the
+    /// current time. Game time slows down while the server is lagging.
+    #[default]
+    Skip,
+    /// The missed ticks are run back to back until the server is on schedule
+    /// again, so game time keeps up with real time. If more than `max_ticks`
+    /// ticks were missed, the rest are skipped to avoid falling further
+    /// behind while catching up.
+    CatchUp { max_ticks: u32 },
+}
+
+/// Information about a connection, available to the [`AsyncCallbacks`] and
+/// afterwards with [`Client::connection_context`].
+///
+/// Besides the information from the handshake, arbitrary data can be attached
+/// to the connection in [`AsyncCallbacks::connect`]. Data is keyed by its
+/// type, so each type can be stored once:
+///
+/// ```
+/// use valence::config::ConnectionContext;
+///
+/// struct Region(&'static str);
+///
+/// let mut ctx = ConnectionContext::new(([127, 0, 0, 1], 12345).into());
+/// ctx.insert(Region("eu-west"));
+///
+/// assert_eq!(ctx.get::<Region>().unwrap().0, "eu-west");
+/// ```
+///
+/// [`Client::connection_context`]: crate::client::Client::connection_context
+#[derive(Debug)]
+pub struct ConnectionContext {
+    /// The remote address of the connection. For clients behind a proxy, this
+    /// is the address of the proxy.
+    pub remote_addr: SocketAddr,
+    /// The protocol version of the client, which is not necessarily supported
+    /// by the server.
+    pub protocol_version: i32,
+    /// The server address the client connected to, as sent in the handshake.
+    /// This is the domain name if the client used one.
+    pub server_address: String,
+    /// The server port the client connected to, as sent in the handshake.
+    pub server_port: u16,
+    data: FxHashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl ConnectionContext {
+    pub fn new(remote_addr: SocketAddr) -> Self {
+        Self {
+            remote_addr,
+            protocol_version: PROTOCOL_VERSION,
+            server_address: String::new(),
+            server_port: 0,
+            data: FxHashMap::default(),
+        }
+    }
+
+    /// Attaches `value` to the connection, returning the previous value of
+    /// the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.data
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|v| v.downcast().ok())
+            .map(|v| *v)
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.data.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.data.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.data
+            .remove(&TypeId::of::<T>())
+            .and_then(|v| v.downcast().ok())
+            .map(|v| *v)
+    }
+
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.data.contains_key(&TypeId::of::<T>())
+    }
+}
+
 /// The result of the Server List Ping [callback].
 ///
 /// [callback]: crate::config::AsyncCallbacks
@@ -0,0 +1,245 @@
+//! Configuration files which are reloaded while the server is running.
+//!
+//! The [`ConfigFilePlugin`] loads a file into a resource of any type
+//! implementing [`Deserialize`] when the app is built, then checks the
+//! modification time of the file every second. When the file changed, the
+//! resource is replaced and a [`ConfigReloaded`] event is sent, so settings like gameplay constants can
+//! be changed without restarting the server. Systems can also react to the
+//! new configuration with `Res::is_changed`.
+//!
+//! Files are parsed as JSON, or as TOML if the path ends with `.toml`, which
+//! requires the `toml` feature. Files which fail to load are reported with an
+//! error and the previous configuration is kept. If the file can't be loaded
+//! at startup, the default configuration is used.
+//!
+//! ```no_run
+//! use serde::Deserialize;
+//! use valence::config_file::{ConfigFilePlugin, ConfigReloaded};
+//! use valence::prelude::*;
+//!
+//! #[derive(Resource, Deserialize, Default)]
+//! struct GameConfig {
+//!     max_players: usize,
+//!     round_seconds: u32,
+//! }
+//!
+//! let mut app = App::new();
+//! app.add_plugin(ConfigFilePlugin::<GameConfig>::new("game.json"));
+//!
+//! fn announce_reload(
+//!     config: Res<GameConfig>,
+//!     mut events: EventReader<ConfigReloaded<GameConfig>>,
+//!     mut clients: Query<&mut Client>,
+//! ) {
+//!     if events.iter().next().is_some() {
+//!         for mut client in &mut clients {
+//!             client.send_message(format!("Rounds now last {}s", config.round_seconds));
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! [`Deserialize`]: serde::Deserialize
+
+use std::fs;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context;
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use serde::de::DeserializeOwned;
+use tracing::{error, info};
+
+use crate::server::Server;
+
+/// Loads the file at a path into a resource of type `T` and reloads it when
+/// the file changes.
+pub struct ConfigFilePlugin<T> {
+    path: PathBuf,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> ConfigFilePlugin<T> {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Plugin for ConfigFilePlugin<T>
+where
+    T: Resource + DeserializeOwned + Default,
+{
+    fn build(&self, app: &mut App) {
+        let modified = modified_time(&self.path);
+
+        let (config, contents) = match load::<T>(&self.path) {
+            Ok((config, contents)) => (config, Some(contents)),
+            Err(e) => {
+                error!("failed to load config file: {e:#}");
+                (T::default(), None)
+            }
+        };
+
+        app.insert_resource(config)
+            .insert_resource(ConfigFileState::<T> {
+                path: self.path.clone(),
+                modified,
+                contents,
+                _marker: PhantomData,
+            })
+            .add_event::<ConfigReloaded<T>>()
+            .add_system_to_stage(CoreStage::PreUpdate, reload_config_file::<T>);
+    }
+}
+
+/// Sent when the resource of type `T` was reloaded from its file.
+#[derive(Debug)]
+pub struct ConfigReloaded<T> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+#[derive(Resource)]
+struct ConfigFileState<T> {
+    path: PathBuf,
+    /// The modification time of the file before it was last read, or `None`
+    /// if it is unknown.
+    modified: Option<SystemTime>,
+    /// The contents of the file when it was last loaded, or `None` if it
+    /// couldn't be read.
+    contents: Option<String>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+fn load<T: DeserializeOwned>(path: &Path) -> anyhow::Result<(T, String)> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read \"{}\"", path.display()))?;
+
+    let config = parse(path, &contents)
+        .with_context(|| format!("failed to parse \"{}\"", path.display()))?;
+
+    Ok((config, contents))
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn parse<T: DeserializeOwned>(path: &Path, contents: &str) -> anyhow::Result<T> {
+    if path.extension().map_or(false, |ext| ext == "toml") {
+        #[cfg(feature = "toml")]
+        return Ok(toml::from_str(contents)?);
+
+        #[cfg(not(feature = "toml"))]
+        anyhow::bail!("loading TOML files requires the `toml` feature");
+    }
+
+    Ok(serde_json::from_str(contents)?)
+}
+
+fn reload_config_file<T>(
+    server: Res<Server>,
+    mut state: ResMut<ConfigFileState<T>>,
+    mut config: ResMut<T>,
+    mut events: EventWriter<ConfigReloaded<T>>,
+) where
+    T: Resource + DeserializeOwned,
+{
+    if server.current_tick() % server.tps() != 0 {
+        return;
+    }
+
+    // Only read the file again if it was modified since it was last read. If
+    // the modification time is unknown, the file is always read.
+    let modified = modified_time(&state.path);
+
+    if modified.is_some() && modified == state.modified {
+        return;
+    }
+
+    state.modified = modified;
+
+    let Ok(contents) = fs::read_to_string(&state.path) else {
+        return
+    };
+
+    if state.contents.as_deref() == Some(contents.as_str()) {
+        return;
+    }
+
+    match parse(&state.path, &contents) {
+        Ok(new_config) => {
+            *config = new_config;
+            events.send(ConfigReloaded {
+                _marker: PhantomData,
+            });
+            info!("reloaded config file \"{}\"", state.path.display());
+        }
+        Err(e) => error!(
+            "failed to parse config file \"{}\": {e:#}",
+            state.path.display()
+        ),
+    }
+
+    // Invalid files are only reported once.
+    state.contents = Some(contents);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::config::ServerPlugin;
+
+    #[derive(Resource, Deserialize, Default, PartialEq, Debug)]
+    struct TestConfig {
+        max_players: usize,
+    }
+
+    #[test]
+    fn config_file_reload() {
+        let path = std::env::temp_dir().join(format!(
+            "valence_config_file_test_{}.json",
+            std::process::id()
+        ));
+        fs::write(&path, r#"{ "max_players": 10 }"#).unwrap();
+
+        let mut app = App::new();
+        app.add_plugin(ServerPlugin::new(()).with_listen(false))
+            .add_plugin(ConfigFilePlugin::<TestConfig>::new(&path));
+
+        assert_eq!(app.world.resource::<TestConfig>().max_players, 10);
+
+        // Files are checked once per second, starting with the first tick. The
+        // modification time of the file only changes every few milliseconds.
+        thread::sleep(Duration::from_millis(50));
+        fs::write(&path, r#"{ "max_players": 20 }"#).unwrap();
+        app.update();
+
+        assert_eq!(app.world.resource::<TestConfig>().max_players, 20);
+        assert!(!app
+            .world
+            .resource::<Events<ConfigReloaded<TestConfig>>>()
+            .is_empty());
+
+        // Invalid files keep the previous configuration.
+        thread::sleep(Duration::from_millis(50));
+        fs::write(&path, "{").unwrap();
+
+        for _ in 0..app.world.resource::<Server>().tps() {
+            app.update();
+        }
+
+        assert_eq!(app.world.resource::<TestConfig>().max_players, 20);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
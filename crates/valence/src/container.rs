@@ -0,0 +1,559 @@
+//! Vanilla-like containers which are blocks, such as chests, barrels and
+//! shulker boxes.
+//!
+//! The [`ContainerPlugin`] opens the inventory of these blocks when clients
+//! right click them. The contents are loaded from the `Items` of the block
+//! entity into an entity with the [`Inventory`] and [`ContainerBlock`]
+//! components, which is shared by all clients viewing the block. Chests and
+//! shulker boxes open their lids and barrels open while they are viewed,
+//! playing the same sounds as the vanilla server. Double chests are shown as
+//! one large inventory.
+//!
+//! When the last client closes the inventory, its contents are written back
+//! to the block entity and the inventory entity is despawned. The inventory is
+//! also despawned if the block is removed, without dropping its contents.
+//!
+//! Furnaces, hoppers, dispensers and droppers only store items. Smelting and
+//! moving items is left to other systems.
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use valence_nbt::{Compound, List, Value};
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::packets::s2c::play::BlockAction;
+use valence_protocol::translation_key::{
+    CONTAINER_BARREL, CONTAINER_BLAST_FURNACE, CONTAINER_CHEST, CONTAINER_CHEST_DOUBLE,
+    CONTAINER_DISPENSER, CONTAINER_DROPPER, CONTAINER_FURNACE, CONTAINER_HOPPER,
+    CONTAINER_SHULKER_BOX, CONTAINER_SMOKER,
+};
+use valence_protocol::types::{Hand, SoundCategory};
+use valence_protocol::{BlockFace, BlockPos, BlockState, ItemStack, Sound, Text, VarInt};
+
+use crate::client::event::UseItemOnBlock;
+use crate::client::Client;
+use crate::entity::{McEntity, TrackedData};
+use crate::instance::{Block, Instance};
+use crate::inventory::{Inventory, InventoryKind, OpenInventory};
+use crate::view::ChunkPos;
+use crate::Despawned;
+
+/// Adds the systems for opening container blocks and keeping their contents.
+pub struct ContainerPlugin;
+
+impl Plugin for ContainerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::Update, open_containers)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_containers.before("valence_core"),
+            );
+    }
+}
+
+/// A component for the inventory of a container block, spawned by the
+/// [`ContainerPlugin`] while clients are viewing the block.
+#[derive(Component, Clone, Debug)]
+pub struct ContainerBlock {
+    instance: Entity,
+    position: BlockPos,
+    other_half: Option<BlockPos>,
+    viewers: usize,
+}
+
+impl ContainerBlock {
+    /// Gets the instance containing the block.
+    pub fn instance(&self) -> Entity {
+        self.instance
+    }
+
+    /// Gets the position of the block. For double chests, this is the half
+    /// holding the first 27 slots.
+    pub fn position(&self) -> BlockPos {
+        self.position
+    }
+
+    /// Gets the position of the second half of double chests.
+    pub fn other_half(&self) -> Option<BlockPos> {
+        self.other_half
+    }
+
+    /// Gets the number of clients viewing the inventory.
+    pub fn viewers(&self) -> usize {
+        self.viewers
+    }
+}
+
+/// The number of slots in each half of a double chest.
+const CHEST_SLOTS: u16 = 27;
+
+/// The block action which sets the number of viewers of chests and shulker
+/// boxes, opening their lids if it is nonzero.
+const OPEN_LID_ACTION: u8 = 1;
+
+/// Returns the inventory kind and the title of the container block `state`, or
+/// `None` if it is not a container.
+fn container_kind(state: BlockState) -> Option<(InventoryKind, &'static str)> {
+    let name = state.to_kind().to_str();
+
+    let kind = match name {
+        "chest" | "trapped_chest" => (InventoryKind::Generic9x3, CONTAINER_CHEST),
+        "barrel" => (InventoryKind::Generic9x3, CONTAINER_BARREL),
+        "furnace" => (InventoryKind::Furnace, CONTAINER_FURNACE),
+        "blast_furnace" => (InventoryKind::BlastFurnace, CONTAINER_BLAST_FURNACE),
+        "smoker" => (InventoryKind::Smoker, CONTAINER_SMOKER),
+        "hopper" => (InventoryKind::Hopper, CONTAINER_HOPPER),
+        "dispenser" => (InventoryKind::Generic3x3, CONTAINER_DISPENSER),
+        "dropper" => (InventoryKind::Generic3x3, CONTAINER_DROPPER),
+        _ if name.ends_with("shulker_box") => (InventoryKind::ShulkerBox, CONTAINER_SHULKER_BOX),
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+fn is_chest(state: BlockState) -> bool {
+    matches!(state.to_kind().to_str(), "chest" | "trapped_chest")
+}
+
+/// Returns the position of the other half of a double chest, checking that
+/// both halves belong together.
+fn chest_other_half(instance: &Instance, pos: BlockPos, state: BlockState) -> Option<BlockPos> {
+    let facing = state.get(PropName::Facing)?;
+
+    // The other half is to the right of left halves when looking at the front.
+    let direction = match (state.get(PropName::Type)?, facing) {
+        (PropValue::Left, PropValue::North) | (PropValue::Right, PropValue::South) => {
+            BlockFace::East
+        }
+        (PropValue::Left, PropValue::East) | (PropValue::Right, PropValue::West) => {
+            BlockFace::South
+        }
+        (PropValue::Left, PropValue::South) | (PropValue::Right, PropValue::North) => {
+            BlockFace::West
+        }
+        (PropValue::Left, PropValue::West) | (PropValue::Right, PropValue::East) => {
+            BlockFace::North
+        }
+        _ => return None,
+    };
+
+    let other_pos = pos.get_in_direction(direction);
+    let other = instance.block(other_pos)?.state();
+
+    let other_type = match state.get(PropName::Type)? {
+        PropValue::Left => PropValue::Right,
+        _ => PropValue::Left,
+    };
+
+    (other.to_kind() == state.to_kind()
+        && other.get(PropName::Facing) == Some(facing)
+        && other.get(PropName::Type) == Some(other_type))
+    .then_some(other_pos)
+}
+
+/// Chests cannot be opened with a solid block on top of them.
+fn is_chest_blocked(instance: &Instance, pos: BlockPos) -> bool {
+    instance
+        .block(pos.get_in_direction(BlockFace::Top))
+        .map_or(false, |b| b.state().is_opaque())
+}
+
+/// Reads the `Items` of a block entity into `inventory`, starting at slot
+/// `offset`.
+fn load_items(inventory: &mut Inventory, nbt: &Compound, offset: u16) {
+    let Some(Value::List(List::Compound(items))) = nbt.get("Items") else {
+        return
+    };
+
+    for item in items {
+        let Some(Value::Byte(slot)) = item.get("Slot") else {
+            continue
+        };
+
+        let idx = offset + *slot as u8 as u16;
+
+        if idx < inventory.slot_count() {
+            if let Ok(stack) = ItemStack::from_nbt(item) {
+                inventory.replace_slot(idx, stack);
+            }
+        }
+    }
+}
+
+/// Writes `len` slots of `inventory`, starting at slot `offset`, to the `Items`
+/// of a block entity.
+fn save_items(inventory: &Inventory, nbt: &mut Compound, offset: u16, len: u16) {
+    let items = inventory
+        .slots()
+        .skip(offset as usize)
+        .take(len as usize)
+        .enumerate()
+        .filter_map(|(slot, stack)| {
+            let mut item = stack?.to_nbt();
+            item.insert("Slot", slot as i8);
+            Some(item)
+        })
+        .collect();
+
+    nbt.insert("Items", List::Compound(items));
+}
+
+fn open_containers(
+    mut commands: Commands,
+    clients: Query<(&Client, &Inventory, Option<&McEntity>)>,
+    instances: Query<&Instance>,
+    containers: Query<(Entity, &ContainerBlock), Without<Despawned>>,
+    mut events: EventReader<UseItemOnBlock>,
+) {
+    // Containers spawned by this system, which are not in the query yet.
+    let mut spawned: Vec<(Entity, ContainerBlock)> = vec![];
+
+    for event in events.iter() {
+        if event.hand != Hand::Main {
+            continue;
+        }
+
+        let Ok((client, inventory, entity)) = clients.get(event.client) else {
+            continue
+        };
+
+        // Sneaking players place the held item instead of opening containers.
+        let sneaking = matches!(
+            entity.map(|e| e.data()),
+            Some(TrackedData::Player(player)) if player.get_sneaking()
+        );
+
+        if sneaking && inventory.slot(client.held_item_slot()).is_some() {
+            continue;
+        }
+
+        let Ok(instance) = instances.get(client.instance()) else {
+            continue
+        };
+
+        let Some(state) = instance.block(event.position).map(|b| b.state()) else {
+            continue
+        };
+
+        let Some((kind, title)) = container_kind(state) else {
+            continue
+        };
+
+        let mut position = event.position;
+        let mut other_half = None;
+
+        if is_chest(state) {
+            other_half = chest_other_half(instance, position, state);
+
+            if is_chest_blocked(instance, position)
+                || other_half.map_or(false, |pos| is_chest_blocked(instance, pos))
+            {
+                continue;
+            }
+
+            // Right halves hold the first slots of double chests.
+            if let Some(other) = other_half {
+                if state.get(PropName::Type) == Some(PropValue::Left) {
+                    other_half = Some(position);
+                    position = other;
+                }
+            }
+        }
+
+        let existing = containers
+            .iter()
+            .chain(
+                spawned
+                    .iter()
+                    .map(|(entity, container)| (*entity, container)),
+            )
+            .find(|(_, c)| c.instance == client.instance() && c.position == position)
+            .map(|(entity, _)| entity);
+
+        let container_entity = match existing {
+            Some(entity) => entity,
+            None => {
+                let mut inventory = if other_half.is_some() {
+                    Inventory::with_title(
+                        InventoryKind::Generic9x6,
+                        Text::translate(CONTAINER_CHEST_DOUBLE, []),
+                    )
+                } else {
+                    Inventory::with_title(kind, Text::translate(title, []))
+                };
+
+                if let Some(nbt) = instance.block(position).and_then(|b| b.nbt()) {
+                    load_items(&mut inventory, nbt, 0);
+                }
+
+                if let Some(nbt) = other_half
+                    .and_then(|pos| instance.block(pos))
+                    .and_then(|b| b.nbt())
+                {
+                    load_items(&mut inventory, nbt, CHEST_SLOTS);
+                }
+
+                let container = ContainerBlock {
+                    instance: client.instance(),
+                    position,
+                    other_half,
+                    viewers: 0,
+                };
+
+                let entity = commands.spawn((inventory, container.clone())).id();
+                spawned.push((entity, container));
+                entity
+            }
+        };
+
+        commands
+            .entity(event.client)
+            .insert(OpenInventory::new(container_entity));
+    }
+}
+
+/// Plays the sound of a container opening (`open == true`) or closing.
+fn play_container_sound(
+    instance: &mut Instance,
+    container: &ContainerBlock,
+    state: BlockState,
+    open: bool,
+) {
+    let name = state.to_kind().to_str();
+
+    let sound = match (name, open) {
+        ("barrel", true) => Sound::BlockBarrelOpen,
+        ("barrel", false) => Sound::BlockBarrelClose,
+        (_, true) if is_chest(state) => Sound::BlockChestOpen,
+        (_, false) if is_chest(state) => Sound::BlockChestClose,
+        (_, true) if name.ends_with("shulker_box") => Sound::BlockShulkerBoxOpen,
+        (_, false) if name.ends_with("shulker_box") => Sound::BlockShulkerBoxClose,
+        _ => return,
+    };
+
+    let pos = container.position;
+    let mut center = [pos.x as f64 + 0.5, pos.y as f64 + 0.5, pos.z as f64 + 0.5];
+
+    // The sound of double chests comes from between the halves.
+    if let Some(other) = container.other_half {
+        center[0] += (other.x - pos.x) as f64 / 2.0;
+        center[2] += (other.z - pos.z) as f64 / 2.0;
+    }
+
+    let pitch = rand::random::<f32>() * 0.1 + 0.9;
+
+    instance.play_sound(sound, SoundCategory::Block, center, 0.5, pitch);
+}
+
+/// Shows the number of viewers of a container: the lids of chests and shulker
+/// boxes are opened with a block action and barrels are opened with their
+/// block state.
+fn show_viewers(
+    instance: &mut Instance,
+    container: &ContainerBlock,
+    state: BlockState,
+    open: bool,
+) {
+    let name = state.to_kind().to_str();
+
+    if name == "barrel" {
+        // Keep the block entity, which would be replaced by an empty one.
+        let nbt = instance
+            .block(container.position)
+            .and_then(|b| b.nbt().cloned())
+            .unwrap_or_default();
+
+        instance.set_block(
+            container.position,
+            Block::with_nbt(state.set(PropName::Open, PropValue::from_bool(open)), nbt),
+        );
+    } else if is_chest(state) || name.ends_with("shulker_box") {
+        for pos in [Some(container.position), container.other_half]
+            .into_iter()
+            .flatten()
+        {
+            instance.write_packet_at(
+                &BlockAction {
+                    position: pos,
+                    action_id: OPEN_LID_ACTION,
+                    action_parameter: container.viewers.min(u8::MAX as usize) as u8,
+                    block_type: VarInt(state.to_kind().to_raw() as i32),
+                },
+                ChunkPos::from_block_pos(pos),
+            );
+        }
+    }
+}
+
+fn update_containers(
+    mut commands: Commands,
+    viewers: Query<&OpenInventory, With<Client>>,
+    mut containers: Query<(Entity, &mut ContainerBlock, &Inventory), Without<Despawned>>,
+    mut instances: Query<&mut Instance>,
+) {
+    for (entity, mut container, inventory) in &mut containers {
+        let Ok(mut instance) = instances.get_mut(container.instance) else {
+            commands.entity(entity).insert(Despawned);
+            continue
+        };
+
+        // Despawn the inventory if the block was removed, which closes it for
+        // its viewers.
+        let Some(state) = instance
+            .block(container.position)
+            .map(|b| b.state())
+            .filter(|&state| container_kind(state).is_some())
+        else {
+            commands.entity(entity).insert(Despawned);
+            continue
+        };
+
+        let count = viewers
+            .iter()
+            .filter(|open| open.entity() == entity)
+            .count();
+
+        if count != container.viewers {
+            let was_open = container.viewers > 0;
+            container.viewers = count;
+
+            show_viewers(&mut instance, &container, state, count > 0);
+
+            if was_open != (count > 0) {
+                play_container_sound(&mut instance, &container, state, count > 0);
+            }
+        }
+
+        if count == 0 {
+            let halves = [Some(container.position), container.other_half];
+
+            for (i, pos) in halves.into_iter().flatten().enumerate() {
+                let len = if container.other_half.is_some() {
+                    CHEST_SLOTS
+                } else {
+                    inventory.slot_count()
+                };
+
+                let Some(mut block) = instance.block_mut(pos) else {
+                    continue
+                };
+
+                if let Some(nbt) = block.nbt_mut() {
+                    save_items(inventory, nbt, i as u16 * CHEST_SLOTS, len);
+                }
+            }
+
+            commands.entity(entity).insert(Despawned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::ItemKind;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn double_chest_other_half() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        let chest = BlockState::CHEST.set(PropName::Facing, PropValue::North);
+        let left = BlockPos::new(1, 0, 1);
+        let right = BlockPos::new(2, 0, 1);
+
+        instance.set_block(left, chest.set(PropName::Type, PropValue::Left));
+        instance.set_block(right, chest.set(PropName::Type, PropValue::Right));
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        let left_state = instance.block(left).unwrap().state();
+        let right_state = instance.block(right).unwrap().state();
+
+        assert_eq!(chest_other_half(instance, left, left_state), Some(right));
+        assert_eq!(chest_other_half(instance, right, right_state), Some(left));
+        assert_eq!(chest_other_half(instance, left, chest), None);
+    }
+
+    #[test]
+    fn open_and_save_chest() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(ContainerPlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        let pos = BlockPos::new(1, 0, 1);
+        instance.set_block(pos, BlockState::CHEST);
+
+        let mut nbt = Compound::new();
+        let mut item = ItemStack::new(ItemKind::Diamond, 3, None).to_nbt();
+        item.insert("Slot", 4_i8);
+        nbt.insert("Items", List::Compound(vec![item]));
+        instance
+            .block_mut(pos)
+            .unwrap()
+            .nbt_mut()
+            .unwrap()
+            .clone_from(&nbt);
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world.send_event(UseItemOnBlock {
+            client: client_ent,
+            hand: Hand::Main,
+            position: pos,
+            face: BlockFace::Top,
+            cursor_pos: Vec3::ZERO,
+            head_inside_block: false,
+            sequence: 0,
+        });
+
+        app.update();
+        app.update();
+
+        let open = app.world.get::<OpenInventory>(client_ent).unwrap().entity();
+        let inventory = app.world.get::<Inventory>(open).unwrap();
+        assert_eq!(inventory.kind(), InventoryKind::Generic9x3);
+        assert_eq!(
+            inventory.slot(4),
+            Some(&ItemStack::new(ItemKind::Diamond, 3, None))
+        );
+        assert_eq!(app.world.get::<ContainerBlock>(open).unwrap().viewers(), 1);
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::OpenScreen(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::BlockAction(_));
+
+        app.world
+            .get_mut::<Inventory>(open)
+            .unwrap()
+            .replace_slot(0, ItemStack::new(ItemKind::Stone, 1, None));
+        app.world.entity_mut(client_ent).remove::<OpenInventory>();
+
+        app.update();
+        app.update();
+
+        // The contents are written back and the inventory is despawned.
+        assert!(app.world.get_entity(open).is_none());
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        let nbt = instance.block(pos).unwrap().nbt().unwrap();
+        let Some(Value::List(List::Compound(items))) = nbt.get("Items") else {
+            panic!("missing items");
+        };
+        assert_eq!(items.len(), 2);
+    }
+}
@@ -0,0 +1,565 @@
+//! Elytra flight and firework rockets.
+//!
+//! With the [`ElytraPlugin`], clients wearing an elytra start gliding when
+//! they send a [`StartFlyingWithElytra`] event like vanilla. The request is
+//! checked on the server: clients on the ground, in water or without a usable
+//! elytra are told to stop gliding instead. The player entity of the client
+//! is shown gliding to other clients while the flight lasts.
+//!
+//! The movement of gliding clients is checked against the physics of gliding.
+//! Moves faster than [`ElytraSettings::max_speed`] and moves gaining more
+//! height and speed than falling could have given are rejected, which puts the
+//! client back where it was.
+//!
+//! Gliding clients using a firework rocket are boosted in the direction they
+//! look until the rocket explodes. The rocket entity is shown attached to the
+//! player entity of the client.
+//!
+//! [`GlidingStarted`], [`GlidingStopped`] and [`FireworkBoosted`] events are
+//! sent for every flight and boost.
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::{DVec3, Vec3};
+use rand::Rng;
+use valence_nbt::Value;
+use valence_protocol::types::{GameMode, Hand};
+use valence_protocol::{BlockPos, ItemKind, ItemStack};
+
+use crate::client::event::{MovePlayer, StartFlyingWithElytra, UseItem};
+use crate::client::Client;
+use crate::config::DEFAULT_TPS;
+use crate::entity::{EntityKind, EntityStatus, McEntity, TrackedData};
+use crate::instance::Instance;
+use crate::inventory::Inventory;
+use crate::Despawned;
+
+/// The slot of the chest armor in the inventory of players.
+const CHEST_SLOT: u16 = 6;
+
+/// The acceleration of players towards the ground in blocks per second
+/// squared.
+const GRAVITY: f64 = 32.0;
+
+/// The number of ticks after a boost in which the movement of the client isn't
+/// checked, because the client may still be speeding up.
+const BOOST_GRACE_TICKS: i64 = 20;
+
+/// Adds the [`ElytraSettings`] resource, the elytra events, and the systems
+/// for gliding and firework boosts.
+pub struct ElytraPlugin;
+
+impl Plugin for ElytraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ElytraSettings>()
+            .add_event::<GlidingStarted>()
+            .add_event::<GlidingStopped>()
+            .add_event::<FireworkBoosted>()
+            .add_system_to_stage(CoreStage::PostUpdate, start_gliding.before("valence_core"))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                check_glide_movement
+                    .after(start_gliding)
+                    .before("valence_core"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                use_fireworks
+                    .after(check_glide_movement)
+                    .before("valence_core"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_gliding.after(use_fireworks).before("valence_core"),
+            );
+    }
+}
+
+/// Configures the elytra flight of the [`ElytraPlugin`].
+#[derive(Resource, Clone, Debug)]
+pub struct ElytraSettings {
+    /// The maximum speed of gliding clients in blocks per second. Faster
+    /// moves are rejected.
+    ///
+    /// # Default Value
+    ///
+    /// `100.0`
+    pub max_speed: f64,
+    /// How much a move may add to the height and speed of a client beyond
+    /// what falling gives, in blocks squared per second squared. Moves
+    /// gaining more are rejected, unless the client was boosted recently.
+    ///
+    /// # Default Value
+    ///
+    /// `64.0`
+    pub energy_tolerance: f64,
+    /// If rockets are used up when boosting in game modes other than
+    /// creative.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub consume_rockets: bool,
+}
+
+impl Default for ElytraSettings {
+    fn default() -> Self {
+        Self {
+            max_speed: 100.0,
+            energy_tolerance: 64.0,
+            consume_rockets: true,
+        }
+    }
+}
+
+/// A component the [`ElytraPlugin`] adds to clients while they are gliding.
+#[derive(Component, Clone, Debug)]
+pub struct Gliding {
+    /// The speed of the last move in blocks per second. Unknown until the
+    /// first move after the client started gliding.
+    speed: Option<f64>,
+    /// The velocity of the last move in blocks per second.
+    velocity: Vec3,
+    boost: Option<Boost>,
+    /// The ticks left until the movement of the client is checked again.
+    grace_ticks: i64,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Boost {
+    rocket: Entity,
+    remaining_ticks: i64,
+}
+
+impl Gliding {
+    /// Returns the velocity of the last move of the client in blocks per
+    /// second.
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    /// Returns the firework rocket boosting the client, if any.
+    pub fn rocket(&self) -> Option<Entity> {
+        self.boost.map(|b| b.rocket)
+    }
+}
+
+/// Sent when a client started gliding.
+#[derive(Clone, Debug)]
+pub struct GlidingStarted {
+    pub client: Entity,
+}
+
+/// Sent when a client stopped gliding.
+#[derive(Clone, Debug)]
+pub struct GlidingStopped {
+    pub client: Entity,
+}
+
+/// Sent when a gliding client was boosted by a firework rocket.
+#[derive(Clone, Debug)]
+pub struct FireworkBoosted {
+    pub client: Entity,
+    pub rocket: Entity,
+    /// The number of ticks the boost lasts.
+    pub duration: i64,
+}
+
+/// Whether `stack` is an elytra which isn't broken.
+fn is_usable_elytra(stack: &ItemStack) -> bool {
+    if stack.item != ItemKind::Elytra {
+        return false;
+    }
+
+    let damage = match stack.nbt.as_ref().and_then(|nbt| nbt.get("Damage")) {
+        Some(Value::Int(damage)) => *damage,
+        _ => 0,
+    };
+
+    // Elytras stop working one point before they are used up.
+    damage < ItemKind::Elytra.max_durability() as i32 - 1
+}
+
+fn can_glide(client: &Client, inventory: &Inventory, instance: Option<&Instance>) -> bool {
+    let in_liquid = instance
+        .and_then(|i| i.block(BlockPos::at(client.position())))
+        .map_or(false, |b| b.state().is_liquid());
+
+    !client.on_ground()
+        && !in_liquid
+        && client.game_mode() != GameMode::Spectator
+        && inventory.slot(CHEST_SLOT).map_or(false, is_usable_elytra)
+}
+
+/// Sets the gliding flag of the client and its player entity.
+fn set_gliding(client: &mut Client, player: Option<Mut<McEntity>>, gliding: bool) {
+    // The client starts gliding on its own before asking, so it is told the
+    // flag even if the server's copy didn't change.
+    client.player_mut().set_fall_flying(gliding);
+    client.resend_player_flags();
+
+    if let Some(mut player) = player {
+        player.set_fall_flying(gliding);
+    }
+}
+
+/// Returns the number of ticks a boost from `rocket` lasts.
+fn boost_duration(rocket: &ItemStack) -> i64 {
+    let flight = rocket
+        .nbt
+        .as_ref()
+        .and_then(|nbt| match nbt.get("Fireworks") {
+            Some(Value::Compound(fireworks)) => match fireworks.get("Flight") {
+                Some(Value::Byte(flight)) => Some(*flight as i64),
+                _ => None,
+            },
+            _ => None,
+        })
+        .unwrap_or(1);
+
+    let mut rng = rand::thread_rng();
+
+    10 * (flight + 1) + rng.gen_range(0..6) + rng.gen_range(0..7)
+}
+
+fn start_gliding(
+    mut commands: Commands,
+    mut clients: Query<(&mut Client, &Inventory, Option<&mut McEntity>), Without<Gliding>>,
+    instances: Query<&Instance>,
+    mut events: EventReader<StartFlyingWithElytra>,
+    mut started: EventWriter<GlidingStarted>,
+) {
+    for event in events.iter() {
+        let Ok((mut client, inventory, player)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        let instance = instances.get(client.instance()).ok();
+
+        if !can_glide(&client, inventory, instance) {
+            set_gliding(&mut client, player, false);
+            continue;
+        }
+
+        set_gliding(&mut client, player, true);
+
+        commands.entity(event.client).insert(Gliding {
+            speed: None,
+            velocity: Vec3::ZERO,
+            boost: None,
+            grace_ticks: 0,
+        });
+
+        started.send(GlidingStarted {
+            client: event.client,
+        });
+    }
+}
+
+fn check_glide_movement(
+    settings: Res<ElytraSettings>,
+    mut clients: Query<(&mut Client, &mut Gliding, Option<&mut McEntity>)>,
+    mut events: EventReader<MovePlayer>,
+) {
+    for event in events.iter() {
+        let Ok((mut client, mut gliding, player)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        let motion = event.position - event.old_position;
+
+        // Rotations are sent without moving.
+        if motion == DVec3::ZERO {
+            continue;
+        }
+
+        // Clients move once per tick at the default tick rate.
+        let velocity = motion * DEFAULT_TPS as f64;
+        let speed = velocity.length();
+
+        // The sum of the height and the speed of the client can only grow by
+        // boosting, because gliding turns falling into speed with some loss.
+        let gains_energy = gliding.speed.map_or(false, |old_speed| {
+            let energy_before = GRAVITY * event.old_position.y + 0.5 * old_speed * old_speed;
+            let energy_after = GRAVITY * event.position.y + 0.5 * speed * speed;
+
+            energy_after > energy_before + settings.energy_tolerance
+        });
+
+        let boosted = gliding.boost.is_some() || gliding.grace_ticks > 0;
+
+        if speed <= settings.max_speed && (boosted || !gains_energy) {
+            gliding.speed = Some(speed);
+            gliding.velocity = velocity.as_vec3();
+            continue;
+        }
+
+        client.set_position(event.old_position);
+        gliding.speed = Some(0.0);
+        gliding.velocity = Vec3::ZERO;
+
+        if let Some(mut player) = player {
+            player.set_position(event.old_position);
+        }
+    }
+}
+
+fn use_fireworks(
+    mut commands: Commands,
+    settings: Res<ElytraSettings>,
+    mut clients: Query<(&Client, &mut Inventory, &mut Gliding, Option<&McEntity>)>,
+    mut events: EventReader<UseItem>,
+    mut boosted: EventWriter<FireworkBoosted>,
+) {
+    for event in events.iter() {
+        let Ok((client, mut inventory, mut gliding, player)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        let slot = match event.hand {
+            Hand::Main => client.held_item_slot(),
+            Hand::Off => 45,
+        };
+
+        let Some(rocket) = inventory.slot(slot).cloned() else {
+            continue
+        };
+
+        if rocket.item != ItemKind::FireworkRocket {
+            continue;
+        }
+
+        if settings.consume_rockets && client.game_mode() != GameMode::Creative {
+            let mut rest = rocket.clone();
+            let count = rest.count() - 1;
+            rest.set_count(count);
+            inventory.replace_slot(slot, (count > 0).then_some(rest));
+        }
+
+        // A new rocket replaces the one boosting the client.
+        if let Some(boost) = gliding.boost.take() {
+            commands.entity(boost.rocket).insert(Despawned);
+        }
+
+        let duration = boost_duration(&rocket);
+
+        let mut mc_entity = McEntity::new(EntityKind::FireworkRocket, client.instance());
+        mc_entity.set_position(client.position());
+
+        if let TrackedData::FireworkRocket(data) = mc_entity.data_mut() {
+            let mut item = rocket.clone();
+            item.set_count(1);
+            data.set_item(Some(item));
+        }
+
+        if let Some(player) = player {
+            mc_entity.set_firework_shooter(Some(player.protocol_id()));
+        }
+
+        let rocket = commands.spawn(mc_entity).id();
+
+        gliding.boost = Some(Boost {
+            rocket,
+            remaining_ticks: duration,
+        });
+
+        boosted.send(FireworkBoosted {
+            client: event.client,
+            rocket,
+            duration,
+        });
+    }
+}
+
+fn update_gliding(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, &Inventory, &mut Gliding)>,
+    mut entities: Query<&mut McEntity>,
+    instances: Query<&Instance>,
+    mut stopped: EventWriter<GlidingStopped>,
+) {
+    for (entity, mut client, inventory, mut gliding) in &mut clients {
+        let instance = instances.get(client.instance()).ok();
+
+        if !can_glide(&client, inventory, instance) {
+            set_gliding(&mut client, entities.get_mut(entity).ok(), false);
+
+            if let Some(boost) = gliding.boost {
+                if let Ok(mut rocket) = entities.get_mut(boost.rocket) {
+                    rocket.trigger_status(EntityStatus::ExplodeFireworkClient);
+                }
+
+                commands.entity(boost.rocket).insert(Despawned);
+            }
+
+            commands.entity(entity).remove::<Gliding>();
+            stopped.send(GlidingStopped { client: entity });
+            continue;
+        }
+
+        gliding.grace_ticks = (gliding.grace_ticks - 1).max(0);
+
+        let Some(mut boost) = gliding.boost else {
+            continue
+        };
+
+        let Ok(mut rocket) = entities.get_mut(boost.rocket) else {
+            gliding.boost = None;
+            continue
+        };
+
+        if boost.remaining_ticks <= 0 {
+            rocket.trigger_status(EntityStatus::ExplodeFireworkClient);
+            commands.entity(boost.rocket).insert(Despawned);
+
+            gliding.boost = None;
+            gliding.grace_ticks = BOOST_GRACE_TICKS;
+            continue;
+        }
+
+        boost.remaining_ticks -= 1;
+        gliding.boost = Some(boost);
+
+        rocket.set_position(client.position());
+
+        // Vanilla moves the velocity halfway towards 1.5 blocks per tick in
+        // the look direction and adds a little on top, every tick.
+        let look = client.look_direction();
+        let tps = DEFAULT_TPS as f32;
+        let velocity =
+            gliding.velocity + look * 0.1 * tps + (look * 1.5 * tps - gliding.velocity) * 0.5;
+
+        client.set_velocity(velocity);
+        gliding.velocity = velocity;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn elytra_durability() {
+        assert!(is_usable_elytra(&ItemStack::new(ItemKind::Elytra, 1, None)));
+        assert!(!is_usable_elytra(&ItemStack::new(
+            ItemKind::Elytra,
+            1,
+            Some(compound! { "Damage" => 431 })
+        )));
+        assert!(!is_usable_elytra(&ItemStack::new(
+            ItemKind::Diamond,
+            1,
+            None
+        )));
+    }
+
+    #[test]
+    fn rejected_glide_resends_flags() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(ElytraPlugin);
+
+        app.update();
+        client_helper.clear_sent();
+
+        // The client is already gliding on its screen, but the server's copy of the
+        // flag is unchanged, so the flags have to be sent regardless.
+        app.world
+            .send_event(StartFlyingWithElytra { client: client_ent });
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetEntityMetadata(pkt)
+                if pkt.entity_id.0 == 0 && *pkt.metadata.0 == [0, 0, 0, 0xff]
+        )));
+
+        Ok(())
+    }
+
+    #[test]
+    fn glide_and_reject_climbing() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(ElytraPlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .insert_chunk([0, 0], Chunk::default());
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([4.0, 100.0, 4.0]);
+
+        app.update();
+
+        // Without an elytra, the client isn't allowed to glide.
+        app.world
+            .send_event(StartFlyingWithElytra { client: client_ent });
+        app.update();
+
+        assert!(app.world.get::<Gliding>(client_ent).is_none());
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(CHEST_SLOT, ItemStack::new(ItemKind::Elytra, 1, None));
+
+        app.world
+            .send_event(StartFlyingWithElytra { client: client_ent });
+        app.update();
+
+        assert!(app.world.get::<Gliding>(client_ent).is_some());
+        assert!(app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .player()
+            .get_fall_flying());
+
+        let move_to = |app: &mut App, old_position: DVec3, position: DVec3| {
+            app.world
+                .get_mut::<Client>(client_ent)
+                .unwrap()
+                .set_position(position);
+
+            app.world.send_event(MovePlayer {
+                client: client_ent,
+                old_position,
+                position,
+                old_yaw: 0.0,
+                yaw: 0.0,
+                old_pitch: 0.0,
+                pitch: 0.0,
+                old_on_ground: false,
+                on_ground: false,
+            });
+            app.update();
+            app.world.get::<Client>(client_ent).unwrap().position()
+        };
+
+        // Gliding forward and down is fine.
+        let old = DVec3::new(4.0, 100.0, 4.0);
+        let new = DVec3::new(4.0, 99.9, 5.0);
+        assert_eq!(move_to(&mut app, old, new), new);
+
+        // Climbing a block while speeding up is not.
+        let old = new;
+        let new = DVec3::new(4.0, 100.9, 6.0);
+        assert_eq!(move_to(&mut app, old, new), old);
+
+        // Neither is moving faster than the maximum speed.
+        let new = DVec3::new(4.0, 99.0, 15.0);
+        assert_eq!(move_to(&mut app, old, new), old);
+    }
+}
@@ -4,28 +4,38 @@ use std::fmt::Formatter;
 use std::ops::Range;
 
 use bevy_ecs::prelude::*;
+use data::{FireworkRocket, FishingBobber};
 pub use data::{EntityKind, TrackedData};
 use glam::{DVec3, UVec3, Vec3};
 use rustc_hash::FxHashMap;
 use tracing::warn;
 use uuid::Uuid;
-use valence_protocol::entity_meta::{Facing, PaintingKind, Pose};
+use valence_protocol::block::BlockState;
+use valence_protocol::entity_meta::{EulerAngle, Facing, OptionalInt, PaintingKind, Pose};
 use valence_protocol::packets::s2c::play::{
-    EntityAnimationS2c, EntityEvent as EntityEventS2c, SetEntityMetadata, SetEntityVelocity,
-    SetHeadRotation, SpawnEntity, SpawnExperienceOrb, SpawnPlayer, TeleportEntity,
-    UpdateEntityPosition, UpdateEntityPositionAndRotation, UpdateEntityRotation,
+    EntityAnimationS2c, EntityEvent as EntityEventS2c, LinkEntities, SetEntityMetadata,
+    SetEntityVelocity, SetEquipment, SetHeadRotation, SetPassengers, SpawnEntity,
+    SpawnExperienceOrb, SpawnPlayer, TeleportEntity, UpdateEntityPosition,
+    UpdateEntityPositionAndRotation, UpdateEntityRotation,
 };
-use valence_protocol::{ByteAngle, RawBytes, VarInt};
+use valence_protocol::packets::s2c::set_equipment::EquipmentEntry;
+use valence_protocol::types::Hand;
+use valence_protocol::{ByteAngle, ItemStack, RawBytes, VarInt};
 
 use crate::config::DEFAULT_TPS;
 use crate::math::Aabb;
 use crate::packet::WritePacket;
+use crate::server::Server;
 use crate::{Despawned, NULL_ENTITY};
 
 pub mod data;
 
 include!(concat!(env!("OUT_DIR"), "/entity_event.rs"));
 
+/// A protocol ID which no entity is given. Sent in place of a missing entity,
+/// since clients know their own player by the ID 0.
+pub(crate) const NO_ENTITY_PROTOCOL_ID: i32 = -1;
+
 /// A [`Resource`] which maintains information about all the [`McEntity`]
 /// components on the server.
 #[derive(Resource)]
@@ -54,9 +64,10 @@ pub(crate) fn init_entities(
     mut manager: ResMut<McEntityManager>,
 ) {
     for (entity, mut mc_entity) in &mut entities {
-        if manager.next_protocol_id == 0 {
+        if manager.next_protocol_id <= 0 {
             warn!("entity protocol ID overflow");
-            // ID 0 is reserved for clients so we skip over it.
+            // ID 0 is reserved for clients and negative IDs for referring to
+            // no entity, so we skip over them.
             manager.next_protocol_id = 1;
         }
 
@@ -86,9 +97,20 @@ pub(crate) fn update_entities(mut entities: Query<&mut McEntity, Changed<McEntit
         entity.old_instance = entity.instance;
         entity.statuses = 0;
         entity.animations = 0;
-        entity.yaw_or_pitch_modified = false;
-        entity.head_yaw_modified = false;
+        if entity.yaw_or_pitch_modified {
+            entity.yaw_or_pitch_modified = false;
+            entity.old_yaw = entity.yaw;
+            entity.old_pitch = entity.pitch;
+        }
+        if entity.head_yaw_modified {
+            entity.head_yaw_modified = false;
+            entity.old_head_yaw = entity.head_yaw;
+        }
         entity.velocity_modified = false;
+        entity.equipment_modified = 0;
+        entity.passengers_modified = false;
+        entity.leash_holder_modified = false;
+        entity.fishing_bobber_hooked_modified = false;
     }
 }
 
@@ -102,12 +124,79 @@ pub(crate) fn check_entity_invariants(removed: RemovedComponents<McEntity>) {
     }
 }
 
+/// The number of client ticks a killed entity stays visible for its death
+/// animation to play. Clients always run at [`DEFAULT_TPS`], so this is scaled
+/// to the server's tick rate. See [`RemovalReason::Killed`].
+pub const DEATH_ANIMATION_TICKS: u32 = 20;
+
+/// A component for despawning an entity with an effect shown to its viewers.
+///
+/// Inserting this component instead of [`Despawned`] plays the effect for the
+/// reason and marks the entity as [`Despawned`] once the effect is finished.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum RemovalReason {
+    /// The entity disappears immediately, like entities in unloaded chunks.
+    #[default]
+    Unloaded,
+    /// The entity plays the death sound and falls over, then disappears in a
+    /// cloud of smoke after [`DEATH_ANIMATION_TICKS`].
+    Killed,
+    /// The entity disappears in a burst of portal particles, like an enderman
+    /// teleporting.
+    TeleportedAway,
+}
+
+/// The remaining ticks of the death animation of a killed entity.
+#[derive(Component)]
+struct Dying {
+    remaining_ticks: u32,
+}
+
+/// Plays the effects of entities with a [`RemovalReason`] and marks them as
+/// [`Despawned`]. Statuses are not sent for despawned entities, so entities
+/// are despawned the tick after their last effect.
+pub(crate) fn remove_entities(
+    mut commands: Commands,
+    mut entities: Query<
+        (Entity, &mut McEntity, &RemovalReason, Option<&mut Dying>),
+        Without<Despawned>,
+    >,
+    server: Res<Server>,
+) {
+    for (id, mut entity, reason, dying) in &mut entities {
+        match (reason, dying) {
+            (RemovalReason::Unloaded, _) => {
+                commands.entity(id).insert(Despawned);
+            }
+            (RemovalReason::Killed, None) => {
+                entity.trigger_status(EntityStatus::PlayDeathSoundOrAddProjectileHitParticles);
+                commands.entity(id).insert(Dying {
+                    remaining_ticks: (DEATH_ANIMATION_TICKS as i64 * server.tps() / DEFAULT_TPS)
+                        .max(1) as u32,
+                });
+            }
+            (RemovalReason::Killed, Some(mut dying)) => {
+                dying.remaining_ticks = dying.remaining_ticks.saturating_sub(1);
+
+                if dying.remaining_ticks == 0 {
+                    entity.trigger_status(EntityStatus::AddDeathParticles);
+                    commands.entity(id).insert(Despawned);
+                }
+            }
+            (RemovalReason::TeleportedAway, _) => {
+                entity.trigger_status(EntityStatus::AddPortalParticles);
+                commands.entity(id).insert(Despawned);
+            }
+        }
+    }
+}
+
 /// A component for Minecraft entities. For Valence to recognize a
 /// Minecraft entity, it must have this component attached.
 ///
 /// ECS entities with this component are not allowed to be removed from the
 /// [`World`] directly. Instead, you must mark these entities with [`Despawned`]
-/// to allow deinitialization to occur.
+/// or [`RemovalReason`] to allow deinitialization to occur.
 ///
 /// Every entity has common state which is accessible directly from this struct.
 /// This includes position, rotation, velocity, and UUID. To access data that is
@@ -130,12 +219,44 @@ pub struct McEntity {
     old_position: DVec3,
     yaw: f32,
     pitch: f32,
+    /// The yaw and pitch last sent to clients.
+    old_yaw: f32,
+    old_pitch: f32,
+    /// If the yaw or pitch differs from the ones last sent to clients once
+    /// converted to [`ByteAngle`]s.
     yaw_or_pitch_modified: bool,
     head_yaw: f32,
+    /// The head yaw last sent to clients.
+    old_head_yaw: f32,
     head_yaw_modified: bool,
     velocity: Vec3,
     velocity_modified: bool,
     on_ground: bool,
+    name_visibility_range: Option<f64>,
+    /// The items in each [`EquipmentSlot`].
+    equipment: [Option<ItemStack>; EquipmentSlot::COUNT],
+    /// Contains a set bit for every equipment slot modified this tick.
+    equipment_modified: u8,
+    /// The direction item frames face, which is sent when they are spawned.
+    item_frame_facing: Facing,
+    /// The block of falling block entities, which is sent when they are
+    /// spawned.
+    falling_block_state: BlockState,
+    /// The protocol IDs of the entities riding this entity.
+    passengers: Vec<i32>,
+    passengers_modified: bool,
+    /// The protocol ID of the entity holding the fishing rod of fishing
+    /// bobbers, which is sent when they are spawned.
+    fishing_bobber_owner: Option<i32>,
+    /// The protocol ID of the entity hooked by fishing bobbers.
+    fishing_bobber_hooked: Option<i32>,
+    fishing_bobber_hooked_modified: bool,
+    /// The protocol ID of the entity boosted by firework rockets, which is
+    /// sent when they are spawned.
+    firework_shooter: Option<i32>,
+    /// The protocol ID of the entity holding the leash of this entity.
+    leash_holder: Option<i32>,
+    leash_holder_modified: bool,
 }
 
 impl McEntity {
@@ -163,14 +284,30 @@ impl McEntity {
             old_position: DVec3::ZERO,
             yaw: 0.0,
             pitch: 0.0,
+            old_yaw: 0.0,
+            old_pitch: 0.0,
             yaw_or_pitch_modified: false,
             head_yaw: 0.0,
+            old_head_yaw: 0.0,
             head_yaw_modified: false,
             velocity: Vec3::ZERO,
             velocity_modified: false,
             protocol_id: 0,
             uuid,
             on_ground: false,
+            name_visibility_range: None,
+            equipment: Default::default(),
+            equipment_modified: 0,
+            item_frame_facing: Facing::Down,
+            falling_block_state: BlockState::SAND,
+            passengers: vec![],
+            passengers_modified: false,
+            fishing_bobber_owner: None,
+            fishing_bobber_hooked: None,
+            fishing_bobber_hooked_modified: false,
+            firework_shooter: None,
+            leash_holder: None,
+            leash_holder_modified: false,
         }
     }
 
@@ -246,10 +383,14 @@ impl McEntity {
     }
 
     /// Sets the yaw of this entity in degrees.
+    ///
+    /// Clients only see the rotation in steps of 1/256 of a turn, so it is
+    /// sent once it changes by at least one step.
     pub fn set_yaw(&mut self, yaw: f32) {
         if self.yaw != yaw {
             self.yaw = yaw;
-            self.yaw_or_pitch_modified = true;
+            self.yaw_or_pitch_modified =
+                angle_changed(self.old_yaw, yaw) || angle_changed(self.old_pitch, self.pitch);
         }
     }
 
@@ -258,11 +399,13 @@ impl McEntity {
         self.pitch
     }
 
-    /// Sets the pitch of this entity in degrees.
+    /// Sets the pitch of this entity in degrees. Like with [`Self::set_yaw`],
+    /// the rotation is sent once it changes by at least one step.
     pub fn set_pitch(&mut self, pitch: f32) {
         if self.pitch != pitch {
             self.pitch = pitch;
-            self.yaw_or_pitch_modified = true;
+            self.yaw_or_pitch_modified =
+                angle_changed(self.old_yaw, self.yaw) || angle_changed(self.old_pitch, pitch);
         }
     }
 
@@ -271,11 +414,13 @@ impl McEntity {
         self.head_yaw
     }
 
-    /// Sets the head yaw of this entity in degrees.
+    /// Sets the head yaw of this entity in degrees. Like with
+    /// [`Self::set_yaw`], the head yaw is sent once it changes by at least one
+    /// step.
     pub fn set_head_yaw(&mut self, head_yaw: f32) {
         if self.head_yaw != head_yaw {
             self.head_yaw = head_yaw;
-            self.head_yaw_modified = true;
+            self.head_yaw_modified = angle_changed(self.old_head_yaw, head_yaw);
         }
     }
 
@@ -285,6 +430,13 @@ impl McEntity {
     }
 
     /// Sets the velocity of this entity in meters per second.
+    ///
+    /// Valence does not simulate physics, so the velocity is only sent to
+    /// clients, which use it to predict the movement of the entity between
+    /// position updates. The velocity stays the same until it is changed and is
+    /// also sent to clients which start viewing the entity later. When the
+    /// entity is moved with [`McEntity::set_position`] in the same tick, the
+    /// velocity is sent after the new position.
     pub fn set_velocity(&mut self, velocity: impl Into<Vec3>) {
         let new_vel = velocity.into();
 
@@ -294,6 +446,360 @@ impl McEntity {
         }
     }
 
+    /// Adds to the velocity of this entity in meters per second, like the
+    /// knockback of an explosion or a hit. Calling this several times in the
+    /// same tick stacks the velocities. See [`McEntity::set_velocity`].
+    pub fn add_velocity(&mut self, velocity: impl Into<Vec3>) {
+        self.set_velocity(self.velocity + velocity.into());
+    }
+
+    /// Whether or not this entity is rendered as burning.
+    pub fn is_on_fire(&self) -> bool {
+        self.data.on_fire()
+    }
+
+    pub fn set_on_fire(&mut self, on_fire: bool) {
+        self.data.set_on_fire(on_fire);
+    }
+
+    /// Whether or not this entity is outlined for all viewers. The color of the
+    /// outline is the color of the entity's team (see [`Team`]).
+    ///
+    /// To outline the entity for individual clients only, see
+    /// [`Client::set_entity_glowing`].
+    ///
+    /// [`Team`]: crate::team::Team
+    /// [`Client::set_entity_glowing`]: crate::client::Client::set_entity_glowing
+    pub fn is_glowing(&self) -> bool {
+        self.data.glowing()
+    }
+
+    pub fn set_glowing(&mut self, glowing: bool) {
+        self.data.set_glowing(glowing);
+    }
+
+    pub fn is_invisible(&self) -> bool {
+        self.data.invisible()
+    }
+
+    pub fn set_invisible(&mut self, invisible: bool) {
+        self.data.set_invisible(invisible);
+    }
+
+    pub fn is_sneaking(&self) -> bool {
+        self.data.sneaking()
+    }
+
+    /// Sets the sneaking flag and the matching pose of this entity.
+    pub fn set_sneaking(&mut self, sneaking: bool) {
+        self.data.set_sneaking(sneaking);
+        self.set_pose_if(sneaking, Pose::Sneaking);
+    }
+
+    /// Whether or not this entity is flying with an elytra.
+    pub fn is_fall_flying(&self) -> bool {
+        self.data.fall_flying()
+    }
+
+    /// Sets the elytra flying flag and the matching pose of this entity.
+    pub fn set_fall_flying(&mut self, fall_flying: bool) {
+        self.data.set_fall_flying(fall_flying);
+        self.set_pose_if(fall_flying, Pose::FallFlying);
+    }
+
+    /// Sets the pose to `pose` if `enabled`, or back to standing if the pose is
+    /// `pose` and not `enabled`.
+    fn set_pose_if(&mut self, enabled: bool, pose: Pose) {
+        if enabled {
+            self.data.set_pose(pose);
+        } else if self.data.pose() == pose {
+            self.data.set_pose(Pose::Standing);
+        }
+    }
+
+    /// Returns the flags common to every kind of entity as they are encoded in
+    /// the entity's tracked data.
+    pub(crate) fn flags_byte(&self) -> u8 {
+        self.data.flags_byte()
+    }
+
+    /// Gets the item in an equipment slot of this entity.
+    pub fn equipment(&self, slot: EquipmentSlot) -> Option<&ItemStack> {
+        self.equipment[slot as usize].as_ref()
+    }
+
+    /// Sets the item in an equipment slot of this entity, which is shown to
+    /// clients in view. Returns the previous item.
+    ///
+    /// Only living entities such as mobs, players, and armor stands display
+    /// their equipment.
+    pub fn set_equipment(
+        &mut self,
+        slot: EquipmentSlot,
+        item: Option<ItemStack>,
+    ) -> Option<ItemStack> {
+        let old = std::mem::replace(&mut self.equipment[slot as usize], item);
+
+        if old != self.equipment[slot as usize] {
+            self.equipment_modified |= 1 << slot as u8;
+        }
+
+        old
+    }
+
+    fn armor_stand(&self) -> Option<&data::ArmorStand> {
+        match &self.data {
+            TrackedData::ArmorStand(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    fn armor_stand_mut(&mut self) -> Option<&mut data::ArmorStand> {
+        match &mut self.data {
+            TrackedData::ArmorStand(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Gets the rotation of a part of this armor stand. Returns `None` if this
+    /// entity is not an armor stand.
+    pub fn armor_stand_pose(&self, part: ArmorStandPart) -> Option<EulerAngle> {
+        let e = self.armor_stand()?;
+
+        Some(match part {
+            ArmorStandPart::Head => e.get_tracker_head_rotation(),
+            ArmorStandPart::Body => e.get_tracker_body_rotation(),
+            ArmorStandPart::LeftArm => e.get_tracker_left_arm_rotation(),
+            ArmorStandPart::RightArm => e.get_tracker_right_arm_rotation(),
+            ArmorStandPart::LeftLeg => e.get_tracker_left_leg_rotation(),
+            ArmorStandPart::RightLeg => e.get_tracker_right_leg_rotation(),
+        })
+    }
+
+    /// Sets the rotation of a part of this armor stand in degrees. Does nothing
+    /// if this entity is not an armor stand.
+    pub fn set_armor_stand_pose(&mut self, part: ArmorStandPart, rotation: EulerAngle) {
+        let Some(e) = self.armor_stand_mut() else {
+            return
+        };
+
+        match part {
+            ArmorStandPart::Head => e.set_tracker_head_rotation(rotation),
+            ArmorStandPart::Body => e.set_tracker_body_rotation(rotation),
+            ArmorStandPart::LeftArm => e.set_tracker_left_arm_rotation(rotation),
+            ArmorStandPart::RightArm => e.set_tracker_right_arm_rotation(rotation),
+            ArmorStandPart::LeftLeg => e.set_tracker_left_leg_rotation(rotation),
+            ArmorStandPart::RightLeg => e.set_tracker_right_leg_rotation(rotation),
+        }
+    }
+
+    /// Whether or not this entity is a small armor stand.
+    pub fn is_armor_stand_small(&self) -> bool {
+        self.armor_stand().map_or(false, |e| e.get_small())
+    }
+
+    /// Does nothing if this entity is not an armor stand.
+    pub fn set_armor_stand_small(&mut self, small: bool) {
+        if let Some(e) = self.armor_stand_mut() {
+            e.set_small(small);
+        }
+    }
+
+    /// Whether or not this entity is an armor stand with a marker hitbox. Marker
+    /// armor stands have no hitbox and cannot be interacted with.
+    pub fn is_armor_stand_marker(&self) -> bool {
+        self.armor_stand().map_or(false, |e| e.get_marker())
+    }
+
+    /// Does nothing if this entity is not an armor stand.
+    pub fn set_armor_stand_marker(&mut self, marker: bool) {
+        if let Some(e) = self.armor_stand_mut() {
+            e.set_marker(marker);
+        }
+    }
+
+    /// Whether or not this entity is an armor stand showing its arms.
+    pub fn armor_stand_shows_arms(&self) -> bool {
+        self.armor_stand().map_or(false, |e| e.get_show_arms())
+    }
+
+    /// Does nothing if this entity is not an armor stand.
+    pub fn set_armor_stand_shows_arms(&mut self, show_arms: bool) {
+        if let Some(e) = self.armor_stand_mut() {
+            e.set_show_arms(show_arms);
+        }
+    }
+
+    /// Whether or not this entity is an armor stand without a base plate.
+    pub fn is_armor_stand_base_plate_hidden(&self) -> bool {
+        self.armor_stand()
+            .map_or(false, |e| e.get_hide_base_plate())
+    }
+
+    /// Does nothing if this entity is not an armor stand.
+    pub fn set_armor_stand_base_plate_hidden(&mut self, hidden: bool) {
+        if let Some(e) = self.armor_stand_mut() {
+            e.set_hide_base_plate(hidden);
+        }
+    }
+
+    /// Gets the item displayed in this item frame. Returns `None` if the frame
+    /// is empty or this entity is not an item frame.
+    pub fn item_frame_item(&self) -> Option<&ItemStack> {
+        match &self.data {
+            TrackedData::ItemFrame(e) => e.get_item_stack(),
+            TrackedData::GlowItemFrame(e) => e.get_item_stack(),
+            _ => None,
+        }
+    }
+
+    /// Sets the item displayed in this item frame. Does nothing if this entity
+    /// is not an item frame.
+    pub fn set_item_frame_item(&mut self, item: Option<ItemStack>) {
+        match &mut self.data {
+            TrackedData::ItemFrame(e) => e.set_item_stack(item),
+            TrackedData::GlowItemFrame(e) => e.set_item_stack(item),
+            _ => {}
+        }
+    }
+
+    /// Gets the rotation of the item in this item frame in steps of 45
+    /// degrees, from 0 to 7. Returns `None` if this entity is not an item
+    /// frame.
+    pub fn item_frame_rotation(&self) -> Option<i32> {
+        match &self.data {
+            TrackedData::ItemFrame(e) => Some(e.get_rotation()),
+            TrackedData::GlowItemFrame(e) => Some(e.get_rotation()),
+            _ => None,
+        }
+    }
+
+    /// Sets the rotation of the item in this item frame in steps of 45 degrees.
+    /// The rotation wraps around after 7. Does nothing if this entity is not an
+    /// item frame.
+    pub fn set_item_frame_rotation(&mut self, rotation: i32) {
+        let rotation = rotation.rem_euclid(8);
+
+        match &mut self.data {
+            TrackedData::ItemFrame(e) => e.set_rotation(rotation),
+            TrackedData::GlowItemFrame(e) => e.set_rotation(rotation),
+            _ => {}
+        }
+    }
+
+    /// Gets the direction this entity faces if it is an item frame.
+    pub fn item_frame_facing(&self) -> Facing {
+        self.item_frame_facing
+    }
+
+    /// Sets the direction this entity faces if it is an item frame. The
+    /// direction is sent when the entity is spawned, so changing it has no
+    /// effect on clients which already see the frame.
+    pub fn set_item_frame_facing(&mut self, facing: Facing) {
+        self.item_frame_facing = facing;
+    }
+
+    /// Gets the block shown by this entity if it is a falling block.
+    pub fn falling_block_state(&self) -> BlockState {
+        self.falling_block_state
+    }
+
+    /// Sets the block shown by this entity if it is a falling block. The block
+    /// is sent when the entity is spawned, so changing it has no effect on
+    /// clients which already see the entity.
+    pub fn set_falling_block_state(&mut self, state: BlockState) {
+        self.falling_block_state = state;
+    }
+
+    /// Gets the protocol IDs of the entities riding this entity.
+    pub fn passengers(&self) -> &[i32] {
+        &self.passengers
+    }
+
+    /// Sets the entities riding this entity by their protocol IDs, see
+    /// [`Self::protocol_id`]. Clients riding this entity with their own player
+    /// entity are shown to be riding it too.
+    pub fn set_passengers(&mut self, passengers: impl Into<Vec<i32>>) {
+        let passengers = passengers.into();
+
+        if self.passengers != passengers {
+            self.passengers = passengers;
+            self.passengers_modified = true;
+        }
+    }
+
+    pub(crate) fn passengers_modified(&self) -> bool {
+        self.passengers_modified
+    }
+
+    /// Gets the protocol ID of the entity holding the fishing rod if this
+    /// entity is a fishing bobber.
+    pub fn fishing_bobber_owner(&self) -> Option<i32> {
+        self.fishing_bobber_owner
+    }
+
+    /// Sets the entity holding the fishing rod by its protocol ID if this
+    /// entity is a fishing bobber. Clients draw the fishing line from the
+    /// owner to the bobber and discard bobbers without a known owner. The
+    /// owner is sent when the entity is spawned, so changing it has no effect
+    /// on clients which already see the bobber.
+    pub fn set_fishing_bobber_owner(&mut self, owner: Option<i32>) {
+        self.fishing_bobber_owner = owner;
+    }
+
+    /// Gets the protocol ID of the entity hooked by this entity if it is a
+    /// fishing bobber.
+    pub fn fishing_bobber_hooked(&self) -> Option<i32> {
+        self.fishing_bobber_hooked
+    }
+
+    pub(crate) fn fishing_bobber_hooked_modified(&self) -> bool {
+        self.fishing_bobber_hooked_modified
+    }
+
+    /// Sets the entity hooked by this entity by its protocol ID if it is a
+    /// fishing bobber, or unhooks it with `None`. Clients show the bobber
+    /// attached to the hooked entity.
+    pub fn set_fishing_bobber_hooked(&mut self, hooked: Option<i32>) {
+        if self.fishing_bobber_hooked != hooked {
+            self.fishing_bobber_hooked = hooked;
+            self.fishing_bobber_hooked_modified = true;
+        }
+    }
+
+    /// Gets the protocol ID of the entity boosted by this entity if it is a
+    /// firework rocket.
+    pub fn firework_shooter(&self) -> Option<i32> {
+        self.firework_shooter
+    }
+
+    /// Sets the entity boosted by this entity by its protocol ID if it is a
+    /// firework rocket. Clients show the rocket attached to the shooter while
+    /// the shooter is gliding. The shooter is sent when the entity is spawned,
+    /// so changing it has no effect on clients which already see the rocket.
+    pub fn set_firework_shooter(&mut self, shooter: Option<i32>) {
+        self.firework_shooter = shooter;
+    }
+
+    /// Gets the protocol ID of the entity holding the leash of this entity.
+    pub fn leash_holder(&self) -> Option<i32> {
+        self.leash_holder
+    }
+
+    pub(crate) fn leash_holder_modified(&self) -> bool {
+        self.leash_holder_modified
+    }
+
+    /// Sets the entity holding the leash of this entity by its protocol ID,
+    /// or removes the leash with `None`. Clients only show leashes attached to
+    /// mobs.
+    pub fn set_leash_holder(&mut self, holder: Option<i32>) {
+        if self.leash_holder != holder {
+            self.leash_holder = holder;
+            self.leash_holder_modified = true;
+        }
+    }
+
     /// Gets the value of the "on ground" flag.
     pub fn on_ground(&self) -> bool {
         self.on_ground
@@ -305,6 +811,29 @@ impl McEntity {
         // TODO: on ground modified flag?
     }
 
+    /// Gets the maximum distance (in meters) from which clients are able to see
+    /// this entity's custom name. `None` indicates there is no limit.
+    pub fn name_visibility_range(&self) -> Option<f64> {
+        self.name_visibility_range
+    }
+
+    /// Sets the maximum distance (in meters) from which clients are able to see
+    /// this entity's custom name.
+    ///
+    /// Clients farther away than `range` will see the entity as if its
+    /// `name_visible` flag were unset. This has no effect on entities that do
+    /// not have their name visible to begin with. `None` removes the limit.
+    ///
+    /// This has no effect on players either, since clients show the name tags
+    /// of players regardless of the `name_visible` flag. Their name tags are
+    /// controlled with the name tag visibility of their [`Team`] instead, and
+    /// fade while they are sneaking.
+    ///
+    /// [`Team`]: crate::team::Team
+    pub fn set_name_visibility_range(&mut self, range: Option<f64>) {
+        self.name_visibility_range = range.map(|r| r.max(0.0));
+    }
+
     pub fn trigger_status(&mut self, status: EntityStatus) {
         self.statuses |= 1 << status as u64;
     }
@@ -313,6 +842,55 @@ impl McEntity {
         self.animations |= 1 << animation as u8;
     }
 
+    /// Returns whether or not `status` was triggered during this tick.
+    pub fn has_triggered_status(&self, status: EntityStatus) -> bool {
+        (self.statuses >> status as u64) & 1 == 1
+    }
+
+    /// Returns whether or not `animation` was triggered during this tick.
+    pub fn has_triggered_animation(&self, animation: EntityAnimation) -> bool {
+        (self.animations >> animation as u8) & 1 == 1
+    }
+
+    /// Swings the arm of this entity holding `hand`.
+    pub fn swing_arm(&mut self, hand: Hand) {
+        self.trigger_animation(match hand {
+            Hand::Main => EntityAnimation::SwingMainHand,
+            Hand::Off => EntityAnimation::SwingOffHand,
+        });
+    }
+
+    /// Makes this entity flash red. The hurt sound of the entity is played by
+    /// the [`EntitySoundPlugin`] if it is added.
+    ///
+    /// [`EntitySoundPlugin`]: crate::entity_sound::EntitySoundPlugin
+    pub fn play_hurt_animation(&mut self) {
+        self.trigger_animation(EntityAnimation::Damage);
+    }
+
+    /// Plays the effects of this entity taking damage from `source`. The
+    /// entity flashes red and clients play the hurt sound for the source.
+    pub fn play_damage_effect(&mut self, source: DamageSource) {
+        self.trigger_status(source.status());
+    }
+
+    /// Shows critical hit particles around this entity. `enchanted` shows the
+    /// particles of a hit with an enchanted weapon instead.
+    pub fn play_crit_particles(&mut self, enchanted: bool) {
+        self.trigger_animation(if enchanted {
+            EntityAnimation::EnchantedHit
+        } else {
+            EntityAnimation::Crit
+        });
+    }
+
+    /// Plays the death sound of this entity and makes it fall over. The entity
+    /// stays in the world, so this is only the effect. Insert
+    /// [`RemovalReason::Killed`] to despawn the entity after the animation.
+    pub fn play_death_animation(&mut self) {
+        self.trigger_status(EntityStatus::PlayDeathSoundOrAddProjectileHitParticles);
+    }
+
     /// Returns the hitbox of this entity.
     ///
     /// The hitbox describes the space that an entity occupies. Clients interact
@@ -411,7 +989,9 @@ impl McEntity {
             TrackedData::Fox(e) => baby(e.get_child(), [0.6, 0.7, 0.6]),
             TrackedData::Ghast(_) => [4.0, 4.0, 4.0],
             TrackedData::Giant(_) => [3.6, 12.0, 3.6],
-            TrackedData::GlowItemFrame(e) => return item_frame(self.position, e.get_rotation()),
+            TrackedData::GlowItemFrame(_) => {
+                return item_frame(self.position, self.item_frame_facing as i32)
+            }
             TrackedData::GlowSquid(_) => [0.8, 0.8, 0.8],
             TrackedData::Goat(e) => {
                 if e.get_pose() == Pose::LongJumping {
@@ -427,7 +1007,9 @@ impl McEntity {
             TrackedData::Illusioner(_) => [0.6, 1.95, 0.6],
             TrackedData::IronGolem(_) => [1.4, 2.7, 1.4],
             TrackedData::Item(_) => [0.25, 0.25, 0.25],
-            TrackedData::ItemFrame(e) => return item_frame(self.position, e.get_rotation()),
+            TrackedData::ItemFrame(_) => {
+                return item_frame(self.position, self.item_frame_facing as i32)
+            }
             TrackedData::Fireball(_) => [1.0, 1.0, 1.0],
             TrackedData::LeashKnot(_) => [0.375, 0.5, 0.375],
             TrackedData::Lightning(_) => [0.0, 0.0, 0.0],
@@ -606,10 +1188,14 @@ impl McEntity {
 
     /// Sends the appropriate packets to initialize the entity. This will spawn
     /// the entity and initialize tracked data.
+    ///
+    /// `self_protocol_id` is the protocol ID of the player entity of the
+    /// client receiving the packets, which the client knows by the ID 0.
     pub(crate) fn write_init_packets(
         &self,
         mut writer: impl WritePacket,
         position: DVec3,
+        self_protocol_id: Option<i32>,
         scratch: &mut Vec<u8>,
     ) {
         let with_object_data = |data| SpawnEntity {
@@ -646,9 +1232,8 @@ impl McEntity {
                     head_yaw: ByteAngle::from_degrees(self.head_yaw),
                 });
             }
-            TrackedData::ItemFrame(e) => writer.write_packet(&with_object_data(e.get_rotation())),
-            TrackedData::GlowItemFrame(e) => {
-                writer.write_packet(&with_object_data(e.get_rotation()))
+            TrackedData::ItemFrame(_) | TrackedData::GlowItemFrame(_) => {
+                writer.write_packet(&with_object_data(self.item_frame_facing as i32))
             }
 
             TrackedData::Painting(_) => writer.write_packet(&with_object_data(
@@ -659,11 +1244,12 @@ impl McEntity {
                     _ => 5,
                 },
             )),
-            // TODO: set block state ID for falling block.
-            TrackedData::FallingBlock(_) => writer.write_packet(&with_object_data(1)),
-            TrackedData::FishingBobber(e) => {
-                writer.write_packet(&with_object_data(e.get_hook_entity_id()))
+            TrackedData::FallingBlock(_) => {
+                writer.write_packet(&with_object_data(self.falling_block_state.to_raw() as i32))
             }
+            TrackedData::FishingBobber(_) => writer.write_packet(&with_object_data(
+                viewer_protocol_id(self.fishing_bobber_owner, self_protocol_id),
+            )),
             TrackedData::Warden(e) => {
                 writer.write_packet(&with_object_data((e.get_pose() == Pose::Emerging).into()))
             }
@@ -678,48 +1264,150 @@ impl McEntity {
                 metadata: RawBytes(scratch),
             });
         }
+
+        if let TrackedData::FishingBobber(_) = &self.data {
+            if self.fishing_bobber_hooked.is_some() {
+                writer.write_packet(&self.fishing_bobber_hooked_packet(self_protocol_id, scratch));
+            }
+        }
+
+        if let (TrackedData::FireworkRocket(_), Some(shooter)) = (&self.data, self.firework_shooter)
+        {
+            let shooter = viewer_protocol_id(Some(shooter), self_protocol_id);
+
+            scratch.clear();
+            FireworkRocket::encode_shooter_entity_id(
+                &OptionalInt::new(shooter as u32).unwrap_or_default(),
+                scratch,
+            );
+            scratch.push(0xff);
+
+            writer.write_packet(&SetEntityMetadata {
+                entity_id: VarInt(self.protocol_id),
+                metadata: RawBytes(scratch),
+            });
+        }
+
+        let equipment = self.equipment_entries(|_| true);
+        if !equipment.is_empty() {
+            writer.write_packet(&SetEquipment {
+                entity_id: VarInt(self.protocol_id),
+                equipment,
+            });
+        }
+
+        if !self.passengers.is_empty() {
+            writer.write_packet(&self.passengers_packet());
+        }
+
+        if self.leash_holder.is_some() {
+            writer.write_packet(&self.leash_packet(self_protocol_id));
+        }
+    }
+
+    /// Returns the packet setting the passengers of this entity.
+    pub(crate) fn passengers_packet(&self) -> SetPassengers {
+        SetPassengers {
+            entity_id: VarInt(self.protocol_id),
+            passengers: self.passengers.iter().map(|&id| VarInt(id)).collect(),
+        }
+    }
+
+    /// Returns the packet attaching the leash of this entity to its holder, or
+    /// removing the leash if there is no holder. `self_protocol_id` is the
+    /// protocol ID of the player entity of the client receiving the packet.
+    pub(crate) fn leash_packet(&self, self_protocol_id: Option<i32>) -> LinkEntities {
+        LinkEntities {
+            attached_entity_id: self.protocol_id,
+            holding_entity_id: viewer_protocol_id(self.leash_holder, self_protocol_id),
+        }
+    }
+
+    /// Returns the packet setting the entity hooked by this fishing bobber.
+    /// `self_protocol_id` is the protocol ID of the player entity of the
+    /// client receiving the packet.
+    pub(crate) fn fishing_bobber_hooked_packet<'a>(
+        &self,
+        self_protocol_id: Option<i32>,
+        scratch: &'a mut Vec<u8>,
+    ) -> SetEntityMetadata<'a> {
+        let hooked = viewer_protocol_id(self.fishing_bobber_hooked, self_protocol_id);
+
+        // The hooked entity is sent as its ID plus one, or 0 if nothing is hooked.
+        scratch.clear();
+        FishingBobber::encode_hook_entity_id(&(hooked + 1), scratch);
+        scratch.push(0xff);
+
+        SetEntityMetadata {
+            entity_id: VarInt(self.protocol_id),
+            metadata: RawBytes(scratch),
+        }
+    }
+
+    /// Returns the equipment entries of the slots for which `filter` returns
+    /// `true`, skipping empty slots which are not modified.
+    fn equipment_entries(&self, filter: impl Fn(usize) -> bool) -> Vec<EquipmentEntry> {
+        self.equipment
+            .iter()
+            .enumerate()
+            .filter(|&(slot, item)| {
+                filter(slot) && (item.is_some() || self.equipment_modified >> slot & 1 == 1)
+            })
+            .map(|(slot, item)| EquipmentEntry {
+                slot: slot as i8,
+                item: item.clone(),
+            })
+            .collect()
     }
 
     /// Writes the appropriate packets to update the entity (Position, tracked
     /// data, events, animations).
-    pub(crate) fn write_update_packets(&self, mut writer: impl WritePacket, scratch: &mut Vec<u8>) {
+    ///
+    /// Entities which moved at least `teleport_threshold` blocks on any axis
+    /// are teleported instead of moved relatively.
+    pub(crate) fn write_update_packets(
+        &self,
+        mut writer: impl WritePacket,
+        scratch: &mut Vec<u8>,
+        teleport_threshold: f64,
+    ) {
         let entity_id = VarInt(self.protocol_id);
 
-        let position_delta = self.position - self.old_position;
-        let needs_teleport = position_delta.abs().max_element() >= 8.0;
-        let changed_position = self.position != self.old_position;
+        // The delta is taken between the rounded positions so the rounding
+        // errors of consecutive relative moves don't accumulate on the client.
+        let delta = (self.position * 4096.0).round() - (self.old_position * 4096.0).round();
+        let needs_teleport = self.position != self.old_position
+            && ((self.position - self.old_position).abs().max_element() >= teleport_threshold
+                || delta.abs().max_element() > i16::MAX as f64);
+        let changed_position = delta != DVec3::ZERO;
+        let delta = delta.to_array().map(|v| v as i16);
 
-        if changed_position && !needs_teleport && self.yaw_or_pitch_modified {
+        if needs_teleport {
+            // Teleports include the rotation.
+            writer.write_packet(&TeleportEntity {
+                entity_id,
+                position: self.position.to_array(),
+                yaw: ByteAngle::from_degrees(self.yaw),
+                pitch: ByteAngle::from_degrees(self.pitch),
+                on_ground: self.on_ground,
+            });
+        } else if changed_position && self.yaw_or_pitch_modified {
             writer.write_packet(&UpdateEntityPositionAndRotation {
                 entity_id,
-                delta: (position_delta * 4096.0).to_array().map(|v| v as i16),
+                delta,
                 yaw: ByteAngle::from_degrees(self.yaw),
                 pitch: ByteAngle::from_degrees(self.pitch),
                 on_ground: self.on_ground,
             });
-        } else {
-            if changed_position && !needs_teleport {
-                writer.write_packet(&UpdateEntityPosition {
-                    entity_id,
-                    delta: (position_delta * 4096.0).to_array().map(|v| v as i16),
-                    on_ground: self.on_ground,
-                });
-            }
-
-            if self.yaw_or_pitch_modified {
-                writer.write_packet(&UpdateEntityRotation {
-                    entity_id,
-                    yaw: ByteAngle::from_degrees(self.yaw),
-                    pitch: ByteAngle::from_degrees(self.pitch),
-                    on_ground: self.on_ground,
-                });
-            }
-        }
-
-        if needs_teleport {
-            writer.write_packet(&TeleportEntity {
+        } else if changed_position {
+            writer.write_packet(&UpdateEntityPosition {
+                entity_id,
+                delta,
+                on_ground: self.on_ground,
+            });
+        } else if self.yaw_or_pitch_modified {
+            writer.write_packet(&UpdateEntityRotation {
                 entity_id,
-                position: self.position.to_array(),
                 yaw: ByteAngle::from_degrees(self.yaw),
                 pitch: ByteAngle::from_degrees(self.pitch),
                 on_ground: self.on_ground,
@@ -749,8 +1437,27 @@ impl McEntity {
             });
         }
 
+        if self.equipment_modified != 0 {
+            writer.write_packet(&SetEquipment {
+                entity_id,
+                equipment: self.equipment_entries(|slot| self.equipment_modified >> slot & 1 == 1),
+            });
+        }
+
+        if self.passengers_modified {
+            writer.write_packet(&self.passengers_packet());
+        }
+
+        if self.leash_holder_modified {
+            writer.write_packet(&self.leash_packet(None));
+        }
+
+        if self.fishing_bobber_hooked_modified {
+            writer.write_packet(&self.fishing_bobber_hooked_packet(None, scratch));
+        }
+
         if self.statuses != 0 {
-            for i in 0..std::mem::size_of_val(&self.statuses) {
+            for i in 0..u64::BITS {
                 if (self.statuses >> i) & 1 == 1 {
                     writer.write_packet(&EntityEventS2c {
                         entity_id: entity_id.0,
@@ -761,7 +1468,7 @@ impl McEntity {
         }
 
         if self.animations != 0 {
-            for i in 0..std::mem::size_of_val(&self.animations) {
+            for i in 0..u8::BITS {
                 if (self.animations >> i) & 1 == 1 {
                     writer.write_packet(&EntityAnimationS2c {
                         entity_id,
@@ -773,9 +1480,81 @@ impl McEntity {
     }
 }
 
+/// The equipment slots of an entity, used with [`McEntity::set_equipment`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum EquipmentSlot {
+    MainHand,
+    OffHand,
+    Feet,
+    Legs,
+    Chest,
+    Head,
+}
+
+impl EquipmentSlot {
+    const COUNT: usize = 6;
+}
+
+/// The posable parts of an armor stand, used with
+/// [`McEntity::set_armor_stand_pose`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ArmorStandPart {
+    Head,
+    Body,
+    LeftArm,
+    RightArm,
+    LeftLeg,
+    RightLeg,
+}
+
+/// The sources of damage with their own effect, used with
+/// [`McEntity::play_damage_effect`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub enum DamageSource {
+    /// Any damage without a dedicated effect.
+    #[default]
+    Generic,
+    Thorns,
+    Drowning,
+    Fire,
+    BerryBush,
+    Freezing,
+}
+
+impl DamageSource {
+    /// Returns the entity status which plays the effect of this source.
+    pub fn status(self) -> EntityStatus {
+        match self {
+            DamageSource::Generic => EntityStatus::DamageFromGenericSource,
+            DamageSource::Thorns => EntityStatus::DamageFromThorns,
+            DamageSource::Drowning => EntityStatus::DamageFromDrowning,
+            DamageSource::Fire => EntityStatus::DamageFromFire,
+            DamageSource::BerryBush => EntityStatus::DamageFromBerryBush,
+            DamageSource::Freezing => EntityStatus::DamageFromFreezing,
+        }
+    }
+}
+
+/// Returns the ID the client with the player entity `self_protocol_id` knows
+/// the entity `id` by. Clients know their own player by the ID 0.
+fn viewer_protocol_id(id: Option<i32>, self_protocol_id: Option<i32>) -> i32 {
+    match id {
+        Some(id) if Some(id) == self_protocol_id => 0,
+        Some(id) => id,
+        None => NO_ENTITY_PROTOCOL_ID,
+    }
+}
+
+/// Returns if two angles in degrees are different once converted to
+/// [`ByteAngle`]s.
+fn angle_changed(a: f32, b: f32) -> bool {
+    ByteAngle::from_degrees(a) != ByteAngle::from_degrees(b)
+}
+
 #[inline]
 pub(crate) fn velocity_to_packet_units(vel: Vec3) -> [i16; 3] {
-    // The saturating casts to i16 are desirable.
+    // Velocities are applied by the client at the standard tick rate, whatever
+    // the server's tick rate is. The saturating casts to i16 are desirable.
     (8000.0 / DEFAULT_TPS as f32 * vel)
         .to_array()
         .map(|v| v as i16)
@@ -791,3 +1570,247 @@ impl fmt::Debug for McEntity {
             .finish_non_exhaustive()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::{ItemKind, PacketDecoder};
+
+    use super::*;
+    use crate::client::Client;
+    use crate::config::MAX_ENTITY_TELEPORT_THRESHOLD;
+    use crate::packet::PacketWriter;
+    use crate::unit_test::util::scenario_single_client;
+
+    /// Returns the names of the movement packets written to update `entity`.
+    fn movement_packets(entity: &McEntity, teleport_threshold: f64) -> Vec<&'static str> {
+        let mut buf = vec![];
+        let mut scratch = vec![];
+        let writer = PacketWriter::new(&mut buf, None, &mut scratch);
+        entity.write_update_packets(writer, &mut vec![], teleport_threshold);
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_slice(&buf);
+
+        dec.collect_into_vec::<S2cPlayPacket>()
+            .unwrap()
+            .into_iter()
+            .map(|pkt| match pkt {
+                S2cPlayPacket::UpdateEntityPosition(_) => "position",
+                S2cPlayPacket::UpdateEntityPositionAndRotation(_) => "position and rotation",
+                S2cPlayPacket::UpdateEntityRotation(_) => "rotation",
+                S2cPlayPacket::TeleportEntity(_) => "teleport",
+                S2cPlayPacket::SetHeadRotation(_) => "head rotation",
+                _ => "other",
+            })
+            .collect()
+    }
+
+    /// Returns the metadata of the packets written to spawn `entity` for the
+    /// client with the player entity `self_protocol_id`.
+    fn init_metadata(entity: &McEntity, self_protocol_id: Option<i32>) -> Vec<Vec<u8>> {
+        let mut buf = vec![];
+        let mut scratch = vec![];
+        let writer = PacketWriter::new(&mut buf, None, &mut scratch);
+        entity.write_init_packets(writer, entity.position(), self_protocol_id, &mut vec![]);
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_slice(&buf);
+
+        dec.collect_into_vec::<S2cPlayPacket>()
+            .unwrap()
+            .into_iter()
+            .filter_map(|pkt| match pkt {
+                S2cPlayPacket::SetEntityMetadata(pkt) => Some(pkt.metadata.0.to_vec()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn viewer_relative_entity_ids() {
+        let mut bobber = McEntity::new(EntityKind::FishingBobber, NULL_ENTITY);
+        bobber.set_fishing_bobber_hooked(Some(5));
+
+        // The hooked client knows itself by the ID 0.
+        assert!(init_metadata(&bobber, Some(5)).contains(&vec![8, 1, 1, 0xff]));
+        assert!(init_metadata(&bobber, Some(7)).contains(&vec![8, 1, 6, 0xff]));
+
+        let mut rocket = McEntity::new(EntityKind::FireworkRocket, NULL_ENTITY);
+        rocket.set_firework_shooter(Some(5));
+
+        assert!(init_metadata(&rocket, Some(5)).contains(&vec![9, 18, 1, 0xff]));
+        assert!(init_metadata(&rocket, None).contains(&vec![9, 18, 6, 0xff]));
+    }
+
+    #[test]
+    fn entity_flag_helpers() {
+        let mut entity = McEntity::new(EntityKind::Zombie, NULL_ENTITY);
+
+        entity.set_glowing(true);
+        entity.set_sneaking(true);
+
+        assert!(entity.is_glowing());
+        assert_eq!(entity.data().pose(), Pose::Sneaking);
+        assert_eq!(entity.flags_byte(), 1 << 6 | 1 << 1);
+
+        entity.set_sneaking(false);
+
+        assert_eq!(entity.data().pose(), Pose::Standing);
+        assert_eq!(entity.flags_byte(), 1 << 6);
+    }
+
+    #[test]
+    fn combat_effect_helpers() {
+        let mut entity = McEntity::new(EntityKind::Zombie, NULL_ENTITY);
+
+        entity.swing_arm(Hand::Off);
+        entity.play_crit_particles(true);
+        entity.play_damage_effect(DamageSource::Fire);
+
+        assert!(entity.has_triggered_animation(EntityAnimation::SwingOffHand));
+        assert!(entity.has_triggered_animation(EntityAnimation::EnchantedHit));
+        assert!(!entity.has_triggered_animation(EntityAnimation::Crit));
+        assert!(entity.has_triggered_status(EntityStatus::DamageFromFire));
+
+        let mut buf = vec![];
+        let mut scratch = vec![];
+        let writer = PacketWriter::new(&mut buf, None, &mut scratch);
+        entity.write_update_packets(writer, &mut vec![], MAX_ENTITY_TELEPORT_THRESHOLD);
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_slice(&buf);
+
+        let mut statuses = vec![];
+        let mut animations = vec![];
+
+        for pkt in dec.collect_into_vec::<S2cPlayPacket>().unwrap() {
+            match pkt {
+                S2cPlayPacket::EntityEvent(pkt) => statuses.push(pkt.entity_status),
+                S2cPlayPacket::EntityAnimationS2c(pkt) => animations.push(pkt.animation),
+                _ => {}
+            }
+        }
+
+        assert_eq!(statuses, [37]);
+        assert_eq!(animations, [3, 5]);
+    }
+
+    #[test]
+    fn armor_stand_helpers() {
+        let mut stand = McEntity::new(EntityKind::ArmorStand, NULL_ENTITY);
+        let pose = EulerAngle {
+            pitch: 45.0,
+            yaw: 0.0,
+            roll: 90.0,
+        };
+
+        stand.set_armor_stand_pose(ArmorStandPart::RightArm, pose);
+        stand.set_armor_stand_small(true);
+        stand.set_armor_stand_base_plate_hidden(true);
+
+        assert_eq!(stand.armor_stand_pose(ArmorStandPart::RightArm), Some(pose));
+        assert!(stand.is_armor_stand_small());
+        assert!(stand.is_armor_stand_base_plate_hidden());
+        assert!(!stand.is_armor_stand_marker());
+
+        let mut zombie = McEntity::new(EntityKind::Zombie, NULL_ENTITY);
+        zombie.set_armor_stand_small(true);
+
+        assert!(!zombie.is_armor_stand_small());
+        assert_eq!(zombie.armor_stand_pose(ArmorStandPart::Head), None);
+    }
+
+    #[test]
+    fn equipment_modifications() {
+        let mut entity = McEntity::new(EntityKind::Zombie, NULL_ENTITY);
+        let helmet = ItemStack::new(ItemKind::DiamondHelmet, 1, None);
+
+        assert_eq!(
+            entity.set_equipment(EquipmentSlot::Head, Some(helmet.clone())),
+            None
+        );
+        assert_eq!(entity.equipment(EquipmentSlot::Head), Some(&helmet));
+        assert_eq!(entity.equipment_entries(|_| true).len(), 1);
+
+        entity.equipment_modified = 0;
+        entity.set_equipment(EquipmentSlot::Head, None);
+
+        // Removed items are sent as empty slots.
+        let entries = entity.equipment_entries(|_| true);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].slot, EquipmentSlot::Head as i8);
+        assert_eq!(entries[0].item, None);
+    }
+
+    #[test]
+    fn removal_reasons() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let killed = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Zombie, instance),
+                RemovalReason::Killed,
+            ))
+            .id();
+        let teleported = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Enderman, instance),
+                RemovalReason::TeleportedAway,
+            ))
+            .id();
+
+        app.update();
+
+        // Entities are despawned the tick after their effect.
+        assert!(app.world.get::<Despawned>(teleported).is_some());
+        assert!(app.world.get::<Despawned>(killed).is_none());
+
+        for _ in 0..DEATH_ANIMATION_TICKS {
+            app.update();
+        }
+
+        assert!(app.world.get_entity(teleported).is_none());
+        assert!(app.world.get::<Despawned>(killed).is_some());
+
+        app.update();
+
+        assert!(app.world.get_entity(killed).is_none());
+    }
+
+    #[test]
+    fn movement_packet_selection() {
+        let mut entity = McEntity::new(EntityKind::Zombie, NULL_ENTITY);
+        let max = MAX_ENTITY_TELEPORT_THRESHOLD;
+
+        entity.set_position([2.0, 0.0, 0.0]);
+        entity.set_yaw(90.0);
+        assert_eq!(movement_packets(&entity, max), ["position and rotation"]);
+        // The rotation is part of the teleport.
+        assert_eq!(movement_packets(&entity, 1.0), ["teleport"]);
+
+        entity.set_position([10.0, 0.0, 0.0]);
+        assert_eq!(movement_packets(&entity, max), ["teleport"]);
+
+        entity.old_position = entity.position;
+        entity.old_yaw = entity.yaw;
+        entity.yaw_or_pitch_modified = false;
+
+        // Changes smaller than a step of a byte angle are not sent.
+        entity.set_yaw(90.5);
+        entity.set_head_yaw(0.5);
+        assert!(movement_packets(&entity, max).is_empty());
+
+        entity.set_yaw(95.0);
+        entity.set_head_yaw(10.0);
+        assert_eq!(
+            movement_packets(&entity, max),
+            ["rotation", "head rotation"]
+        );
+    }
+}
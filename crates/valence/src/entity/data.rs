@@ -5,6 +5,6 @@
 
 use uuid::Uuid;
 use valence_protocol::entity_meta::*;
-use valence_protocol::{BlockPos, BlockState, Encode, Text, VarInt};
+use valence_protocol::{BlockPos, BlockState, Encode, ItemStack, Text, VarInt};
 
 include!(concat!(env!("OUT_DIR"), "/entity.rs"));
@@ -0,0 +1,199 @@
+//! Automatic entity sounds.
+//!
+//! The vanilla client plays the sounds of some entity events on its own, but
+//! not for others. With the [`EntitySoundPlugin`], the server plays the
+//! missing sounds to the players nearby:
+//!
+//! - The hurt sound when the [`EntityAnimation::Damage`] animation is
+//!   triggered.
+//! - Any sound requested with a [`PlayEntitySound`] event, which is how other
+//!   modules play death and ambient sounds.
+//!
+//! The sounds for each kind of entity are taken from the generated sound data,
+//! e.g. `entity.zombie.hurt` for zombies. They can be turned off globally or
+//! overridden per entity kind through the [`EntitySounds`] resource.
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use rand::Rng;
+use rustc_hash::FxHashMap;
+use valence_protocol::types::SoundCategory;
+use valence_protocol::Sound;
+
+use crate::entity::{EntityAnimation, EntityKind, McEntity};
+use crate::instance::Instance;
+
+/// Adds the [`EntitySounds`] resource, the [`PlayEntitySound`] event and the
+/// system playing entity sounds.
+pub struct EntitySoundPlugin;
+
+impl Plugin for EntitySoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EntitySounds>()
+            .add_event::<PlayEntitySound>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                play_entity_sounds.before("valence_core"),
+            );
+    }
+}
+
+/// The situations in which an entity makes a sound.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum EntitySoundKind {
+    Hurt,
+    Death,
+    Ambient,
+}
+
+impl EntitySoundKind {
+    fn suffix(self) -> &'static str {
+        match self {
+            EntitySoundKind::Hurt => "hurt",
+            EntitySoundKind::Death => "death",
+            EntitySoundKind::Ambient => "ambient",
+        }
+    }
+}
+
+/// Requests that an entity plays its sound of the given kind, which is sent to
+/// the players near the entity. Sent by other systems or by users.
+#[derive(Clone, Debug)]
+pub struct PlayEntitySound {
+    pub entity: Entity,
+    pub kind: EntitySoundKind,
+}
+
+/// Configures which entity sounds are played by the [`EntitySoundPlugin`].
+#[derive(Resource, Clone, Debug)]
+pub struct EntitySounds {
+    /// If `false`, no sounds are played.
+    pub enabled: bool,
+    /// The volume of all entity sounds. Vanilla uses 1.0 for most entities.
+    pub volume: f32,
+    overrides: FxHashMap<(EntityKind, EntitySoundKind), Option<Sound>>,
+}
+
+impl EntitySounds {
+    /// Replaces the sound of entities of kind `entity_kind`. `None` silences
+    /// them.
+    pub fn set_override(
+        &mut self,
+        entity_kind: EntityKind,
+        sound_kind: EntitySoundKind,
+        sound: Option<Sound>,
+    ) {
+        self.overrides.insert((entity_kind, sound_kind), sound);
+    }
+
+    /// Removes an override added with [`Self::set_override`], restoring the
+    /// default sound.
+    pub fn clear_override(&mut self, entity_kind: EntityKind, sound_kind: EntitySoundKind) {
+        self.overrides.remove(&(entity_kind, sound_kind));
+    }
+
+    /// Returns the sound played for entities of kind `entity_kind`, taking
+    /// overrides into account.
+    pub fn sound(&self, entity_kind: EntityKind, sound_kind: EntitySoundKind) -> Option<Sound> {
+        match self.overrides.get(&(entity_kind, sound_kind)) {
+            Some(sound) => *sound,
+            None => default_sound(entity_kind, sound_kind),
+        }
+    }
+}
+
+impl Default for EntitySounds {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volume: 1.0,
+            overrides: FxHashMap::default(),
+        }
+    }
+}
+
+/// Returns the vanilla sound of the given kind for entities of kind
+/// `entity_kind`, if there is one.
+pub fn default_sound(entity_kind: EntityKind, sound_kind: EntitySoundKind) -> Option<Sound> {
+    // The translation key is "entity.minecraft.<name>".
+    let name = entity_kind.translation_key().rsplit('.').next()?;
+
+    Sound::from_str(&format!("entity.{name}.{}", sound_kind.suffix()))
+}
+
+fn play_entity_sounds(
+    sounds: Res<EntitySounds>,
+    entities: Query<(Entity, &McEntity)>,
+    mut instances: Query<&mut Instance>,
+    mut events: EventReader<PlayEntitySound>,
+) {
+    if !sounds.enabled {
+        events.clear();
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+
+    let hurt = entities
+        .iter()
+        .filter(|(_, e)| e.has_triggered_animation(EntityAnimation::Damage))
+        .map(|(id, _)| (id, EntitySoundKind::Hurt));
+
+    let requested: Vec<_> = events.iter().map(|e| (e.entity, e.kind)).collect();
+
+    for (id, kind) in hurt.chain(requested) {
+        let Ok((_, entity)) = entities.get(id) else {
+            continue
+        };
+
+        let Some(sound) = sounds.sound(entity.kind(), kind) else {
+            continue
+        };
+
+        let Ok(mut instance) = instances.get_mut(entity.instance()) else {
+            continue
+        };
+
+        let category = if entity.kind() == EntityKind::Player {
+            SoundCategory::Player
+        } else {
+            SoundCategory::Neutral
+        };
+
+        // Vanilla varies the pitch of entity sounds slightly.
+        let pitch = (rng.gen::<f32>() - rng.gen::<f32>()) * 0.2 + 1.0;
+
+        instance.play_sound(sound, category, entity.position(), sounds.volume, pitch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sound_overrides() {
+        let mut sounds = EntitySounds::default();
+
+        assert_eq!(
+            sounds.sound(EntityKind::Zombie, EntitySoundKind::Hurt),
+            Some(Sound::EntityZombieHurt)
+        );
+        assert_eq!(
+            sounds.sound(EntityKind::Player, EntitySoundKind::Death),
+            Some(Sound::EntityPlayerDeath)
+        );
+
+        sounds.set_override(EntityKind::Zombie, EntitySoundKind::Hurt, None);
+        assert_eq!(
+            sounds.sound(EntityKind::Zombie, EntitySoundKind::Hurt),
+            None
+        );
+
+        sounds.clear_override(EntityKind::Zombie, EntitySoundKind::Hurt);
+        assert_eq!(
+            sounds.sound(EntityKind::Zombie, EntitySoundKind::Hurt),
+            Some(Sound::EntityZombieHurt)
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Falling blocks.
+//!
+//! [`falling_block`] takes a block out of an instance and returns a falling
+//! block entity in its place. The [`FallingBlockPlugin`] moves falling blocks
+//! down with gravity like vanilla. When a falling block lands, it is placed
+//! back into the instance if the space it landed in can be replaced, like air
+//! or tall grass. A [`FallingBlockLanded`] event is sent either way.
+//!
+//! ```
+//! use valence::falling_block::falling_block;
+//! use valence::prelude::*;
+//!
+//! fn drop_block(mut commands: Commands, mut instances: Query<(Entity, &mut Instance)>) {
+//!     let (instance_ent, mut instance) = instances.single_mut();
+//!
+//!     if let Some(falling) = falling_block(instance_ent, &mut instance, [0, 80, 0]) {
+//!         commands.spawn(falling);
+//!     }
+//! }
+//! ```
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::{DVec3, Vec3};
+use valence_protocol::block::BlockState;
+use valence_protocol::BlockPos;
+
+use crate::config::DEFAULT_TPS;
+use crate::entity::{EntityKind, McEntity, TrackedData};
+use crate::instance::Instance;
+use crate::pause::gameplay_systems;
+use crate::server::Server;
+use crate::Despawned;
+
+/// The acceleration of falling blocks and primed TNT in blocks per second
+/// squared.
+pub(crate) const GRAVITY: f32 = 16.0;
+
+/// The fraction of their velocity falling blocks and primed TNT keep every
+/// tick at the default tick rate.
+pub(crate) const DRAG: f32 = 0.98;
+
+/// Adds the [`FallingBlockLanded`] event and the system moving falling
+/// blocks.
+pub struct FallingBlockPlugin;
+
+impl Plugin for FallingBlockPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FallingBlockLanded>()
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                gameplay_systems().with_system(move_falling_blocks.before("valence_core")),
+            );
+    }
+}
+
+/// A component for falling block entities moved by the
+/// [`FallingBlockPlugin`].
+#[derive(Component, Clone, Debug)]
+pub struct FallingBlock {
+    block: BlockState,
+    /// The velocity in blocks per second.
+    velocity: Vec3,
+}
+
+impl FallingBlock {
+    pub fn new(block: BlockState) -> Self {
+        Self {
+            block,
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    /// Returns the block placed when this falling block lands.
+    pub fn block(&self) -> BlockState {
+        self.block
+    }
+
+    /// Returns the velocity of this falling block in blocks per second.
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+}
+
+/// Sent when a falling block landed.
+#[derive(Clone, Debug)]
+pub struct FallingBlockLanded {
+    pub entity: Entity,
+    pub block: BlockState,
+    /// The position the block landed in.
+    pub pos: BlockPos,
+    /// If the block was placed at `pos`. Blocks are not placed if `pos` is
+    /// occupied by a block which can't be replaced.
+    pub placed: bool,
+}
+
+/// Replaces the block at `pos` in `instance` with air and returns the
+/// components of a falling block entity in its place. Returns `None` if there
+/// is no block at `pos` which can fall.
+pub fn falling_block(
+    instance_ent: Entity,
+    instance: &mut Instance,
+    pos: impl Into<BlockPos>,
+) -> Option<(McEntity, FallingBlock)> {
+    let pos = pos.into();
+
+    let state = instance.block(pos)?.state();
+
+    if state.is_air() || state.is_liquid() {
+        return None;
+    }
+
+    instance.set_block(pos, BlockState::AIR);
+
+    let mut entity = McEntity::new(EntityKind::FallingBlock, instance_ent);
+    entity.set_position([pos.x as f64 + 0.5, pos.y as f64, pos.z as f64 + 0.5]);
+    entity.set_falling_block_state(state);
+
+    if let TrackedData::FallingBlock(data) = entity.data_mut() {
+        data.set_block_pos(pos);
+    }
+
+    Some((entity, FallingBlock::new(state)))
+}
+
+/// Moves something at `position` falling with `velocity` for a tick. Returns
+/// the block it landed on, if any.
+pub(crate) fn fall(
+    instance: &Instance,
+    position: &mut DVec3,
+    velocity: &mut Vec3,
+    tps: i64,
+) -> Option<BlockPos> {
+    let seconds_per_tick = 1.0 / tps as f32;
+
+    velocity.y -= GRAVITY * seconds_per_tick;
+
+    let motion = (*velocity * seconds_per_tick).as_dvec3();
+
+    if let Some(hit) = instance.raycast_blocks(*position, motion, motion.length()) {
+        *position = hit.position;
+        *velocity = Vec3::ZERO;
+        return Some(hit.pos);
+    }
+
+    *position += motion;
+    *velocity *= DRAG.powf(DEFAULT_TPS as f32 / tps as f32);
+
+    None
+}
+
+fn move_falling_blocks(
+    mut commands: Commands,
+    server: Res<Server>,
+    mut falling_blocks: Query<(Entity, &mut McEntity, &mut FallingBlock)>,
+    mut instances: Query<&mut Instance>,
+    mut landed: EventWriter<FallingBlockLanded>,
+) {
+    for (entity, mut mc_entity, mut falling_block) in &mut falling_blocks {
+        let Ok(mut instance) = instances.get_mut(mc_entity.instance()) else {
+            continue
+        };
+
+        let mut position = mc_entity.position();
+
+        if position.y < (instance.min_y() - 64) as f64 {
+            commands.entity(entity).insert(Despawned);
+            continue;
+        }
+
+        let hit = fall(
+            &instance,
+            &mut position,
+            &mut falling_block.velocity,
+            server.tps(),
+        );
+
+        mc_entity.set_position(position);
+
+        if hit.is_none() {
+            continue;
+        }
+
+        // The block lands in the space above the surface it hit.
+        let pos = BlockPos::at(position + DVec3::new(0.0, 0.5, 0.0));
+
+        let placed = instance
+            .block(pos)
+            .map_or(false, |b| b.state().is_replaceable());
+
+        if placed {
+            instance.set_block(pos, falling_block.block);
+        }
+
+        landed.send(FallingBlockLanded {
+            entity,
+            block: falling_block.block,
+            pos,
+            placed,
+        });
+
+        commands.entity(entity).insert(Despawned);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn falling_block_lands() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(FallingBlockPlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let y = instance.min_y() + 10;
+
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([3, y, 3], BlockState::STONE);
+        instance.set_block([3, y + 8, 3], BlockState::GRAVEL);
+
+        let falling = falling_block(instance_ent, &mut instance, [3, y + 8, 3]).unwrap();
+        assert_eq!(falling.0.falling_block_state(), BlockState::GRAVEL);
+        assert!(falling_block(instance_ent, &mut instance, [3, y + 8, 3]).is_none());
+
+        let entity = app.world.spawn(falling).id();
+
+        for _ in 0..40 {
+            app.update();
+        }
+
+        assert!(app.world.get_entity(entity).is_none());
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert_eq!(
+            instance.block([3, y + 1, 3]).unwrap().state(),
+            BlockState::GRAVEL
+        );
+        assert!(instance.block([3, y + 8, 3]).unwrap().state().is_air());
+    }
+}
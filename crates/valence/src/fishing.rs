@@ -0,0 +1,596 @@
+//! Fishing rods.
+//!
+//! With the [`FishingPlugin`], clients using a fishing rod cast a fishing
+//! bobber like vanilla. Using the rod again reels the bobber back in:
+//!
+//! - Bobbers fall with gravity until they land on a block, hook an entity, or
+//!   float on water. While floating, a fish bites every now and then. Reeling
+//!   in during a bite catches it.
+//! - Hooked entities are pulled towards the client reeling them in. Clients
+//!   are pulled by changing their velocity. Other entities aren't moved by
+//!   valence, so their pull is only reported in the [`BobberReeledIn`] event.
+//!
+//! Bobbers are removed when their owner stops holding the rod, leaves the
+//! instance, or moves too far away. What is caught is left to the game, which
+//! receives a [`BobberCast`] event for every cast and a [`BobberReeledIn`]
+//! event for every bobber reeled in.
+//!
+//! Clients with a [`GrapplingHook`] use their fishing rod as a grappling hook
+//! instead.
+//!
+//! ```
+//! use valence::fishing::{BobberReeledIn, FishingPlugin};
+//! use valence::prelude::*;
+//!
+//! let mut app = App::new();
+//! app.add_plugin(FishingPlugin);
+//!
+//! fn give_fish(mut clients: Query<&mut Inventory>, mut events: EventReader<BobberReeledIn>) {
+//!     for event in events.iter() {
+//!         if event.caught_fish {
+//!             if let Ok(mut inventory) = clients.get_mut(event.client) {
+//!                 inventory.replace_slot(9, ItemStack::new(ItemKind::Cod, 1, None));
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! [`GrapplingHook`]: crate::movement::GrapplingHook
+
+use std::ops::Range;
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::{DVec3, Vec3};
+use rand::Rng;
+use valence_protocol::block::BlockKind;
+use valence_protocol::types::{GameMode, Hand};
+use valence_protocol::{BlockPos, ItemKind};
+
+use crate::client::event::UseItem;
+use crate::client::Client;
+use crate::config::DEFAULT_TPS;
+use crate::entity::{EntityKind, McEntity, TrackedData};
+use crate::instance::Instance;
+use crate::inventory::Inventory;
+use crate::movement::GrapplingHook;
+use crate::server::Server;
+use crate::Despawned;
+
+/// The distance from their owner at which bobbers are removed.
+pub const MAX_BOBBER_DISTANCE: f64 = 32.0;
+
+/// The acceleration of bobbers in blocks per second squared.
+const GRAVITY: f32 = 12.0;
+
+/// The fraction of their velocity bobbers keep every tick at the default tick
+/// rate.
+const DRAG: f32 = 0.92;
+
+/// Adds the [`FishingSettings`] resource, the fishing events, and the systems
+/// casting and moving bobbers.
+pub struct FishingPlugin;
+
+impl Plugin for FishingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FishingSettings>()
+            .add_event::<BobberCast>()
+            .add_event::<BobberReeledIn>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                use_fishing_rods.before("valence_core"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                move_bobbers.after(use_fishing_rods).before("valence_core"),
+            );
+    }
+}
+
+/// Configures the fishing of the [`FishingPlugin`].
+#[derive(Resource, Clone, Debug)]
+pub struct FishingSettings {
+    /// The speed of cast bobbers in blocks per second.
+    ///
+    /// # Default Value
+    ///
+    /// `12.0`
+    pub cast_speed: f32,
+    /// The range of ticks a floating bobber waits for a bite.
+    ///
+    /// # Default Value
+    ///
+    /// `100..600`
+    pub bite_delay: Range<i64>,
+    /// The range of ticks a fish stays on the hook.
+    ///
+    /// # Default Value
+    ///
+    /// `20..40`
+    pub bite_duration: Range<i64>,
+    /// How strongly hooked entities are pulled towards the client reeling
+    /// them in. The pull is the distance to the client multiplied by this
+    /// value, in blocks per second.
+    ///
+    /// # Default Value
+    ///
+    /// `2.0`
+    pub pull_strength: f32,
+}
+
+impl Default for FishingSettings {
+    fn default() -> Self {
+        Self {
+            cast_speed: 12.0,
+            bite_delay: 100..600,
+            bite_duration: 20..40,
+            pull_strength: 2.0,
+        }
+    }
+}
+
+/// A component for fishing bobbers cast by clients.
+#[derive(Component, Clone, Debug)]
+pub struct FishingBobber {
+    owner: Entity,
+    /// The velocity in blocks per second.
+    velocity: Vec3,
+    state: BobberState,
+}
+
+/// What a [`FishingBobber`] is doing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BobberState {
+    Flying,
+    /// Lying on the given block.
+    Grounded(BlockPos),
+    /// Floating on water. A fish bites once `bite_in` reaches zero and stays
+    /// on the hook for `bite_remaining` ticks.
+    Floating {
+        bite_in: i64,
+        bite_remaining: i64,
+    },
+    /// Attached to the given entity.
+    Hooked(Entity),
+}
+
+impl FishingBobber {
+    /// Returns the client which cast this bobber.
+    pub fn owner(&self) -> Entity {
+        self.owner
+    }
+
+    /// Returns the velocity of this bobber in blocks per second.
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    pub fn state(&self) -> BobberState {
+        self.state
+    }
+
+    /// Returns the entity hooked by this bobber, if any.
+    pub fn hooked(&self) -> Option<Entity> {
+        match self.state {
+            BobberState::Hooked(entity) => Some(entity),
+            _ => None,
+        }
+    }
+
+    /// Whether a fish is biting right now.
+    pub fn has_bite(&self) -> bool {
+        matches!(
+            self.state,
+            BobberState::Floating { bite_in: 0, bite_remaining } if bite_remaining > 0
+        )
+    }
+}
+
+/// Sent when a client cast a fishing bobber.
+#[derive(Clone, Debug)]
+pub struct BobberCast {
+    pub client: Entity,
+    pub bobber: Entity,
+}
+
+/// Sent when a client reeled in a fishing bobber.
+#[derive(Clone, Debug)]
+pub struct BobberReeledIn {
+    pub client: Entity,
+    pub bobber: Entity,
+    /// The entity which was hooked by the bobber, if any.
+    pub hooked: Option<Entity>,
+    /// The velocity added to the hooked entity in blocks per second. Only
+    /// applied if the hooked entity is a client.
+    pub pull: Vec3,
+    /// If a fish was biting when the bobber was reeled in.
+    pub caught_fish: bool,
+    /// If the bobber was lying on a block.
+    pub grounded: bool,
+}
+
+fn holds_fishing_rod(client: &Client, inventory: &Inventory) -> bool {
+    inventory
+        .slot(client.held_item_slot())
+        .map_or(false, |stack| stack.item == ItemKind::FishingRod)
+}
+
+fn use_fishing_rods(
+    mut commands: Commands,
+    settings: Res<FishingSettings>,
+    mut clients: Query<(
+        &mut Client,
+        Option<&Inventory>,
+        Option<&McEntity>,
+        Option<&GrapplingHook>,
+    )>,
+    bobbers: Query<(Entity, &FishingBobber)>,
+    entities: Query<&McEntity>,
+    mut events: EventReader<UseItem>,
+    mut cast: EventWriter<BobberCast>,
+    mut reeled_in: EventWriter<BobberReeledIn>,
+) {
+    for event in events.iter() {
+        if event.hand != Hand::Main {
+            continue;
+        }
+
+        let Ok((client, Some(inventory), player, None)) = clients.get(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator || !holds_fishing_rod(client, inventory) {
+            continue;
+        }
+
+        let owned = bobbers.iter().find(|(_, b)| b.owner == event.client);
+
+        if let Some((bobber_ent, bobber)) = owned {
+            let hooked = bobber.hooked();
+
+            let pull = hooked
+                .and_then(|h| entities.get(h).ok())
+                .map_or(Vec3::ZERO, |target| {
+                    (client.position() - target.position()).as_vec3() * settings.pull_strength
+                });
+
+            if let Some((mut target, ..)) = hooked.and_then(|h| clients.get_mut(h).ok()) {
+                target.add_velocity(pull);
+            }
+
+            reeled_in.send(BobberReeledIn {
+                client: event.client,
+                bobber: bobber_ent,
+                hooked,
+                pull,
+                caught_fish: bobber.has_bite(),
+                grounded: matches!(bobber.state, BobberState::Grounded(_)),
+            });
+
+            commands.entity(bobber_ent).insert(Despawned);
+            continue;
+        }
+
+        let velocity = client.look_direction() * settings.cast_speed;
+
+        let mut mc_entity = McEntity::new(EntityKind::FishingBobber, client.instance());
+        mc_entity.set_position(client.eye_position());
+        mc_entity.set_velocity(velocity);
+        mc_entity.set_fishing_bobber_owner(player.map(|p| p.protocol_id()));
+
+        let bobber = commands
+            .spawn((
+                mc_entity,
+                FishingBobber {
+                    owner: event.client,
+                    velocity,
+                    state: BobberState::Flying,
+                },
+            ))
+            .id();
+
+        cast.send(BobberCast {
+            client: event.client,
+            bobber,
+        });
+    }
+}
+
+fn move_bobbers(
+    mut commands: Commands,
+    server: Res<Server>,
+    settings: Res<FishingSettings>,
+    mut entities: Query<(Entity, &mut McEntity, Option<&mut FishingBobber>)>,
+    owners: Query<(&Client, &Inventory)>,
+    instances: Query<&Instance>,
+) {
+    let seconds_per_tick = 1.0 / server.tps() as f32;
+    let drag = DRAG.powf(DEFAULT_TPS as f32 / server.tps() as f32);
+
+    let mut rng = rand::thread_rng();
+
+    let bobber_ents: Vec<_> = entities
+        .iter()
+        .filter(|(_, _, b)| b.is_some())
+        .map(|(e, _, _)| e)
+        .collect();
+
+    for entity in bobber_ents {
+        let Ok((_, mc_entity, Some(bobber))) = entities.get(entity) else {
+            continue
+        };
+
+        let instance_ent = mc_entity.instance();
+        let position = mc_entity.position();
+        let state = bobber.state;
+        let velocity = bobber.velocity;
+        let owner = bobber.owner;
+
+        let owned = owners.get(owner).map_or(false, |(client, inventory)| {
+            client.instance() == instance_ent
+                && holds_fishing_rod(client, inventory)
+                && client.position().distance(position) < MAX_BOBBER_DISTANCE
+        });
+
+        let Ok(instance) = instances.get(instance_ent) else {
+            continue
+        };
+
+        if !owned || position.y < (instance.min_y() - 64) as f64 {
+            commands.entity(entity).insert(Despawned);
+            continue;
+        }
+
+        let mut new_state = state;
+        let mut new_position = position;
+        let mut new_velocity = velocity;
+        let mut hooked_id = None;
+
+        match state {
+            BobberState::Flying => {
+                let motion = (velocity * seconds_per_tick).as_dvec3();
+                let length = motion.length();
+
+                let block_hit = instance.raycast_blocks(position, motion, length);
+
+                let entity_hit = instance.raycast_entities(position, motion, length, |e| {
+                    if e == entity || e == owner {
+                        return None;
+                    }
+
+                    match entities.get(e) {
+                        Ok((_, target, None)) => Some(target.hitbox()),
+                        _ => None,
+                    }
+                });
+
+                match (block_hit, entity_hit) {
+                    (Some(block), Some(hit)) if hit.distance < block.distance => {
+                        new_state = BobberState::Hooked(hit.entity);
+                    }
+                    (None, Some(hit)) => new_state = BobberState::Hooked(hit.entity),
+                    (Some(block), _) => {
+                        new_position = block.position;
+                        new_velocity = Vec3::ZERO;
+                        new_state = BobberState::Grounded(block.pos);
+                    }
+                    (None, None) => {
+                        new_position += motion;
+                        new_velocity *= drag;
+                        new_velocity.y -= GRAVITY * seconds_per_tick;
+
+                        if let Some(surface) = water_surface(instance, new_position) {
+                            new_position.y = surface;
+                            new_velocity = Vec3::ZERO;
+                            new_state = BobberState::Floating {
+                                bite_in: rng.gen_range(settings.bite_delay.clone()),
+                                bite_remaining: 0,
+                            };
+                        }
+                    }
+                }
+            }
+            BobberState::Grounded(pos) => {
+                // The block is gone, so the bobber falls again.
+                if instance
+                    .block(pos)
+                    .map_or(true, |b| b.state().collision_shapes().next().is_none())
+                {
+                    new_state = BobberState::Flying;
+                }
+            }
+            BobberState::Floating {
+                bite_in,
+                bite_remaining,
+            } => {
+                if water_surface(instance, position).is_none() {
+                    new_state = BobberState::Flying;
+                } else if bite_in > 0 {
+                    new_state = BobberState::Floating {
+                        bite_in: bite_in - 1,
+                        bite_remaining: if bite_in == 1 {
+                            rng.gen_range(settings.bite_duration.clone())
+                        } else {
+                            0
+                        },
+                    };
+                } else if bite_remaining > 1 {
+                    new_state = BobberState::Floating {
+                        bite_in: 0,
+                        bite_remaining: bite_remaining - 1,
+                    };
+                } else {
+                    // The fish got away.
+                    new_state = BobberState::Floating {
+                        bite_in: rng.gen_range(settings.bite_delay.clone()),
+                        bite_remaining: 0,
+                    };
+                }
+            }
+            BobberState::Hooked(_) => {}
+        }
+
+        if let BobberState::Hooked(target) = new_state {
+            match entities.get(target) {
+                Ok((_, target, _)) if target.instance() == instance_ent => {
+                    // Vanilla attaches bobbers at 80% of the height of the hooked entity.
+                    let hitbox = target.hitbox();
+                    let height = hitbox.max.y - hitbox.min.y;
+                    let pos = target.position();
+
+                    new_position = DVec3::new(pos.x, hitbox.min.y + height * 0.8, pos.z);
+                    new_velocity = Vec3::ZERO;
+                    hooked_id = Some(target.protocol_id());
+                }
+                _ => new_state = BobberState::Flying,
+            }
+        }
+
+        let Ok((_, mut mc_entity, Some(mut bobber))) = entities.get_mut(entity) else {
+            continue
+        };
+
+        bobber.state = new_state;
+        bobber.velocity = new_velocity;
+
+        mc_entity.set_position(new_position);
+
+        if new_velocity == Vec3::ZERO {
+            mc_entity.set_velocity(Vec3::ZERO);
+        }
+
+        let has_bite = bobber.has_bite();
+
+        // Clients know the entity hooked by the bobber by a different ID if it's
+        // their own player, so the hooked entity isn't part of the tracked data.
+        mc_entity.set_fishing_bobber_hooked(hooked_id);
+
+        if let TrackedData::FishingBobber(data) = mc_entity.data_mut() {
+            data.set_caught_fish(has_bite);
+        }
+    }
+}
+
+/// Returns the height bobbers float at if `position` is in water.
+fn water_surface(instance: &Instance, position: DVec3) -> Option<f64> {
+    let is_water = |pos: BlockPos| {
+        instance
+            .block(pos)
+            .map_or(false, |b| b.state().to_kind() == BlockKind::Water)
+    };
+
+    let mut pos = BlockPos::at(position);
+
+    if !is_water(pos) {
+        return None;
+    }
+
+    while is_water(BlockPos::new(pos.x, pos.y + 1, pos.z)) {
+        pos.y += 1;
+    }
+
+    Some(pos.y as f64 + 0.9)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::block::BlockState;
+    use valence_protocol::ItemStack;
+
+    use super::*;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn hook_and_reel_in_entity() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(FishingPlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        app.world
+            .entity_mut(client_ent)
+            .insert(McEntity::new(EntityKind::Player, instance_ent));
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let y = instance.min_y() + 10;
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([4.5, y as f64 + 1.0, 4.5]);
+        let slot = client.held_item_slot();
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(slot, ItemStack::new(ItemKind::FishingRod, 1, None));
+
+        // A zombie in front of the client, which looks towards positive Z.
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        zombie.set_position([4.5, y as f64 + 1.0, 7.5]);
+        let zombie = app.world.spawn(zombie).id();
+
+        app.update();
+
+        app.world.send_event(UseItem {
+            client: client_ent,
+            hand: Hand::Main,
+            sequence: 0,
+        });
+
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let mut query = app.world.query::<(Entity, &FishingBobber, &McEntity)>();
+        let (bobber, state, mc_entity) = query.single(&app.world);
+        assert_eq!(state.hooked(), Some(zombie));
+
+        let zombie_id = app.world.get::<McEntity>(zombie).unwrap().protocol_id();
+        assert_eq!(mc_entity.fishing_bobber_hooked(), Some(zombie_id));
+
+        app.world.send_event(UseItem {
+            client: client_ent,
+            hand: Hand::Main,
+            sequence: 1,
+        });
+
+        app.update();
+
+        assert!(app.world.get_entity(bobber).is_none());
+
+        let events = app.world.resource::<Events<BobberReeledIn>>();
+        let mut reader = events.get_reader();
+        let reeled_in: Vec<_> = reader.iter(events).collect();
+
+        assert_eq!(reeled_in.len(), 1);
+        assert_eq!(reeled_in[0].hooked, Some(zombie));
+        assert!(!reeled_in[0].caught_fish);
+        assert!(reeled_in[0].pull.z < 0.0);
+    }
+
+    #[test]
+    fn water_surface_is_top_of_water() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let y = instance.min_y() + 10;
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([2, y, 2], BlockState::WATER);
+        instance.set_block([2, y + 1, 2], BlockState::WATER);
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+
+        assert_eq!(
+            water_surface(instance, DVec3::new(2.5, y as f64 + 0.2, 2.5)),
+            Some(y as f64 + 1.9)
+        );
+        assert_eq!(
+            water_surface(instance, DVec3::new(3.5, y as f64 + 0.2, 2.5)),
+            None
+        );
+    }
+}
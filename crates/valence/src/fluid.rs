@@ -0,0 +1,535 @@
+//! Water and lava flow.
+//!
+//! Adding a [`FluidSimulation`] component to an instance entity makes the
+//! water and lava in the instance spread like in vanilla once the
+//! [`FluidPlugin`] is added. Fluids are updated when blocks next to them
+//! change, so a world loaded with still water stays still until something
+//! disturbs it. Use [`FluidSimulation::schedule_update`] to make fluids flow
+//! without changing a block.
+//!
+//! ```
+//! use valence::fluid::{FluidPlugin, FluidSimulation};
+//! use valence::prelude::*;
+//!
+//! fn setup(world: &mut World) {
+//!     let instance = world
+//!         .resource::<Server>()
+//!         .new_instance(DimensionId::default());
+//!
+//!     world.spawn((instance, FluidSimulation::new()));
+//! }
+//!
+//! App::new()
+//!     .add_plugin(ServerPlugin::new(()))
+//!     .add_plugin(FluidPlugin)
+//!     .add_startup_system(setup);
+//! ```
+
+use std::collections::BTreeMap;
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashSet;
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::{BlockFace, BlockKind, BlockPos, BlockState};
+
+use crate::instance::Instance;
+use crate::pause::gameplay_systems;
+use crate::server::Server;
+
+/// Adds the system simulating fluids in instances with a [`FluidSimulation`].
+pub struct FluidPlugin;
+
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            gameplay_systems().with_system(simulate_fluids.before("valence_core")),
+        );
+    }
+}
+
+const HORIZONTAL: [BlockFace; 4] = [
+    BlockFace::North,
+    BlockFace::South,
+    BlockFace::West,
+    BlockFace::East,
+];
+
+/// The kinds of fluid which flow.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+
+impl Fluid {
+    /// Returns the fluid contained in a block, including waterlogged blocks.
+    pub fn of(state: BlockState) -> Option<Self> {
+        match state.to_kind() {
+            BlockKind::Water => Some(Self::Water),
+            BlockKind::Lava => Some(Self::Lava),
+            _ if state.get(PropName::Waterlogged) == Some(PropValue::True) => Some(Self::Water),
+            _ => None,
+        }
+    }
+
+    fn block(self) -> BlockState {
+        match self {
+            Fluid::Water => BlockState::WATER,
+            Fluid::Lava => BlockState::LAVA,
+        }
+    }
+}
+
+/// A component for instance entities which enables fluid flow in the instance.
+///
+/// The defaults match the overworld. Use [`FluidSimulation::ultrawarm`] for
+/// the faster lava of the nether.
+#[derive(Component, Clone, Debug)]
+pub struct FluidSimulation {
+    /// The number of ticks between updates of flowing water.
+    pub water_tick_rate: i64,
+    /// The number of ticks between updates of flowing lava.
+    pub lava_tick_rate: i64,
+    /// How much the level of lava decreases for every block it flows.
+    /// Water always decreases by one.
+    pub lava_drop_off: u8,
+    /// How far lava looks for a hole to flow towards. Water looks four blocks
+    /// far.
+    pub lava_slope_distance: u32,
+    /// Whether or not flowing water between two water sources becomes a new
+    /// source.
+    pub infinite_water: bool,
+    /// Positions to update keyed by the tick they are due.
+    pending: BTreeMap<i64, Vec<BlockPos>>,
+    scheduled: FxHashSet<BlockPos>,
+    /// Positions to schedule at the next update, for updates requested
+    /// outside of the simulation.
+    requested: Vec<BlockPos>,
+}
+
+impl FluidSimulation {
+    pub fn new() -> Self {
+        Self {
+            water_tick_rate: 5,
+            lava_tick_rate: 30,
+            lava_drop_off: 2,
+            lava_slope_distance: 2,
+            infinite_water: true,
+            pending: BTreeMap::new(),
+            scheduled: FxHashSet::default(),
+            requested: vec![],
+        }
+    }
+
+    /// Creates a simulation with the lava behavior of ultrawarm dimensions
+    /// such as the nether.
+    pub fn ultrawarm() -> Self {
+        Self {
+            lava_tick_rate: 10,
+            lava_drop_off: 1,
+            lava_slope_distance: 4,
+            ..Self::new()
+        }
+    }
+
+    /// Requests that the fluid at `pos`, if any, is updated.
+    pub fn schedule_update(&mut self, pos: impl Into<BlockPos>) {
+        self.requested.push(pos.into());
+    }
+
+    /// Returns the number of positions waiting to be updated.
+    pub fn pending_count(&self) -> usize {
+        self.scheduled.len()
+    }
+
+    fn tick_rate(&self, fluid: Fluid) -> i64 {
+        match fluid {
+            Fluid::Water => self.water_tick_rate,
+            Fluid::Lava => self.lava_tick_rate,
+        }
+    }
+
+    fn drop_off(&self, fluid: Fluid) -> u8 {
+        match fluid {
+            Fluid::Water => 1,
+            Fluid::Lava => self.lava_drop_off,
+        }
+    }
+
+    fn slope_distance(&self, fluid: Fluid) -> u32 {
+        match fluid {
+            Fluid::Water => 4,
+            Fluid::Lava => self.lava_slope_distance,
+        }
+    }
+
+    fn schedule(&mut self, pos: BlockPos, tick: i64) {
+        if self.scheduled.insert(pos) {
+            self.pending.entry(tick).or_default().push(pos);
+        }
+    }
+
+    /// Schedules updates for `pos` and its neighbors, if they contain a fluid.
+    fn schedule_around(&mut self, instance: &Instance, pos: BlockPos, current_tick: i64) {
+        for pos in [pos].into_iter().chain(neighbors(pos)) {
+            if let Some(fluid) = instance.block(pos).and_then(|b| Fluid::of(b.state())) {
+                self.schedule(pos, current_tick + self.tick_rate(fluid));
+            }
+        }
+    }
+}
+
+impl Default for FluidSimulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn neighbors(pos: BlockPos) -> [BlockPos; 6] {
+    [
+        pos.get_in_direction(BlockFace::Top),
+        pos.get_in_direction(BlockFace::Bottom),
+        pos.get_in_direction(BlockFace::North),
+        pos.get_in_direction(BlockFace::South),
+        pos.get_in_direction(BlockFace::West),
+        pos.get_in_direction(BlockFace::East),
+    ]
+}
+
+/// Returns the `level` property of fluid blocks, or 0 for waterlogged blocks.
+fn level(state: BlockState) -> u16 {
+    state
+        .get(PropName::Level)
+        .and_then(|l| l.to_u16())
+        .unwrap_or(0)
+}
+
+fn is_source(state: BlockState) -> bool {
+    level(state) == 0
+}
+
+fn is_falling(state: BlockState) -> bool {
+    level(state) >= 8
+}
+
+/// The amount of fluid in a block, from 8 for sources and falling fluid down
+/// to 1.
+fn amount(state: BlockState) -> u8 {
+    match level(state) {
+        0 | 8.. => 8,
+        l => 8 - l as u8,
+    }
+}
+
+fn flowing(fluid: Fluid, amount: u8, falling: bool) -> BlockState {
+    let level = if falling { 8 } else { 8 - amount as u16 };
+    let level = PropValue::from_u16(level).expect("level should be valid");
+    fluid.block().set(PropName::Level, level)
+}
+
+/// Whether or not `fluid` may flow into a block with the given state.
+fn can_flow_into(state: BlockState, fluid: Fluid) -> bool {
+    match Fluid::of(state) {
+        Some(f) if f == fluid => {
+            !is_source(state) && matches!(state.to_kind(), BlockKind::Water | BlockKind::Lava)
+        }
+        Some(_) => false,
+        None => state.is_air() || state.is_replaceable(),
+    }
+}
+
+/// Whether or not fluid flowing into `pos` can fall down from there.
+fn is_hole(instance: &Instance, pos: BlockPos, fluid: Fluid) -> bool {
+    instance
+        .block(pos.get_in_direction(BlockFace::Bottom))
+        .map_or(false, |b| {
+            let state = b.state();
+            can_flow_into(state, fluid) || Fluid::of(state) == Some(fluid)
+        })
+}
+
+/// Finds the distance to the closest hole in the horizontal directions
+/// starting from `pos`, not going back in the direction `from`.
+fn slope_distance(
+    instance: &Instance,
+    pos: BlockPos,
+    from: BlockFace,
+    depth: u32,
+    max_depth: u32,
+    fluid: Fluid,
+) -> u32 {
+    let mut min = u32::MAX;
+
+    for dir in HORIZONTAL {
+        if dir == from {
+            continue;
+        }
+
+        let next = pos.get_in_direction(dir);
+        let Some(state) = instance.block(next).map(|b| b.state()) else {
+            continue
+        };
+
+        if !can_flow_into(state, fluid) {
+            continue;
+        }
+
+        if is_hole(instance, next, fluid) {
+            return depth;
+        }
+
+        if depth < max_depth {
+            min = min.min(slope_distance(
+                instance,
+                next,
+                dir.opposite(),
+                depth + 1,
+                max_depth,
+                fluid,
+            ));
+        }
+    }
+
+    min
+}
+
+/// Computes the state a non-source fluid block should have based on its
+/// neighbors.
+fn new_fluid_state(
+    sim: &FluidSimulation,
+    instance: &Instance,
+    pos: BlockPos,
+    fluid: Fluid,
+) -> BlockState {
+    let get = |pos: BlockPos| instance.block(pos).map(|b| b.state());
+
+    let above = get(pos.get_in_direction(BlockFace::Top));
+    if above.and_then(Fluid::of) == Some(fluid) {
+        return flowing(fluid, 8, true);
+    }
+
+    let mut max_amount = 0;
+    let mut sources = 0;
+
+    for dir in HORIZONTAL {
+        let Some(neighbor) = get(pos.get_in_direction(dir)) else {
+            continue
+        };
+
+        if Fluid::of(neighbor) == Some(fluid) {
+            if is_source(neighbor) {
+                sources += 1;
+            }
+
+            max_amount = max_amount.max(amount(neighbor));
+        }
+    }
+
+    if fluid == Fluid::Water && sim.infinite_water && sources >= 2 {
+        let below = get(pos.get_in_direction(BlockFace::Bottom));
+        let supported = below.map_or(false, |b| {
+            !b.is_replaceable() || (Fluid::of(b) == Some(fluid) && is_source(b))
+        });
+
+        if supported {
+            return fluid.block();
+        }
+    }
+
+    match max_amount.checked_sub(sim.drop_off(fluid)) {
+        Some(amount) if amount > 0 => flowing(fluid, amount, false),
+        _ => BlockState::AIR,
+    }
+}
+
+/// Sets a block and schedules updates for the fluids around it.
+fn set_block(
+    sim: &mut FluidSimulation,
+    instance: &mut Instance,
+    pos: BlockPos,
+    state: BlockState,
+    current_tick: i64,
+) {
+    instance.set_block(pos, state);
+    sim.schedule_around(instance, pos, current_tick);
+}
+
+fn update_fluid(sim: &mut FluidSimulation, instance: &mut Instance, pos: BlockPos, tick: i64) {
+    let Some(mut state) = instance.block(pos).map(|b| b.state()) else {
+        return
+    };
+
+    let Some(fluid) = Fluid::of(state) else {
+        return
+    };
+
+    // Lava touching water hardens.
+    if fluid == Fluid::Lava && state.to_kind() == BlockKind::Lava {
+        let touches_water = [
+            BlockFace::Top,
+            BlockFace::North,
+            BlockFace::South,
+            BlockFace::West,
+            BlockFace::East,
+        ]
+        .into_iter()
+        .filter_map(|dir| instance.block(pos.get_in_direction(dir)))
+        .any(|b| Fluid::of(b.state()) == Some(Fluid::Water));
+
+        if touches_water {
+            let hardened = if is_source(state) {
+                BlockState::OBSIDIAN
+            } else {
+                BlockState::COBBLESTONE
+            };
+
+            set_block(sim, instance, pos, hardened, tick);
+            return;
+        }
+    }
+
+    // Update the level of flowing fluid. Waterlogged blocks are always sources.
+    if !is_source(state) {
+        let new_state = new_fluid_state(sim, instance, pos, fluid);
+
+        if new_state != state {
+            set_block(sim, instance, pos, new_state, tick);
+
+            if Fluid::of(new_state).is_none() {
+                return;
+            }
+
+            state = new_state;
+        }
+    }
+
+    // Flow down if possible.
+    let below_pos = pos.get_in_direction(BlockFace::Bottom);
+    if let Some(below) = instance.block(below_pos).map(|b| b.state()) {
+        if fluid == Fluid::Lava && Fluid::of(below) == Some(Fluid::Water) {
+            set_block(sim, instance, below_pos, BlockState::STONE, tick);
+            return;
+        }
+
+        if can_flow_into(below, fluid) {
+            if below != flowing(fluid, 8, true) {
+                set_block(sim, instance, below_pos, flowing(fluid, 8, true), tick);
+            }
+
+            return;
+        }
+    }
+
+    // Otherwise spread horizontally towards the nearest holes.
+    let spread_amount = if is_falling(state) {
+        8u8.checked_sub(sim.drop_off(fluid))
+    } else {
+        amount(state).checked_sub(sim.drop_off(fluid))
+    };
+
+    let Some(spread_amount) = spread_amount.filter(|&a| a > 0) else {
+        return
+    };
+
+    let mut targets = Vec::with_capacity(4);
+    let mut min_distance = u32::MAX;
+
+    for dir in HORIZONTAL {
+        let next = pos.get_in_direction(dir);
+        let Some(next_state) = instance.block(next).map(|b| b.state()) else {
+            continue
+        };
+
+        if !can_flow_into(next_state, fluid) {
+            continue;
+        }
+
+        let distance = if is_hole(instance, next, fluid) {
+            0
+        } else {
+            slope_distance(
+                instance,
+                next,
+                dir.opposite(),
+                1,
+                sim.slope_distance(fluid),
+                fluid,
+            )
+        };
+
+        if distance < min_distance {
+            min_distance = distance;
+            targets.clear();
+        }
+
+        if distance == min_distance {
+            targets.push((next, next_state));
+        }
+    }
+
+    for (next, next_state) in targets {
+        if Fluid::of(next_state) == Some(fluid) && amount(next_state) >= spread_amount {
+            continue;
+        }
+
+        set_block(
+            sim,
+            instance,
+            next,
+            flowing(fluid, spread_amount, false),
+            tick,
+        );
+    }
+}
+
+fn simulate_fluids(
+    server: Res<Server>,
+    mut instances: Query<(&mut Instance, &mut FluidSimulation)>,
+) {
+    let current_tick = server.current_tick();
+
+    for (mut instance, mut sim) in &mut instances {
+        let instance = &mut *instance;
+        let sim = &mut *sim;
+
+        // Wake up fluids next to blocks changed since the last tick.
+        let changed: Vec<_> = instance.changed_blocks().collect();
+        for pos in changed.into_iter().chain(std::mem::take(&mut sim.requested)) {
+            sim.schedule_around(instance, pos, current_tick);
+        }
+
+        while let Some(&tick) = sim.pending.keys().next() {
+            if tick > current_tick {
+                break;
+            }
+
+            for pos in sim.pending.remove(&tick).unwrap_or_default() {
+                sim.scheduled.remove(&pos);
+                update_fluid(sim, instance, pos, current_tick);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fluid_levels() {
+        assert_eq!(amount(BlockState::WATER), 8);
+        assert_eq!(amount(flowing(Fluid::Water, 7, false)), 7);
+        assert_eq!(amount(flowing(Fluid::Lava, 3, true)), 8);
+        assert!(is_falling(flowing(Fluid::Water, 8, true)));
+        assert!(!is_source(flowing(Fluid::Water, 8, true)));
+
+        let waterlogged = BlockState::OAK_SLAB.set(PropName::Waterlogged, PropValue::True);
+        assert_eq!(Fluid::of(waterlogged), Some(Fluid::Water));
+        assert!(is_source(waterlogged));
+        assert!(!can_flow_into(waterlogged, Fluid::Water));
+        assert!(can_flow_into(BlockState::GRASS, Fluid::Lava));
+        assert!(!can_flow_into(BlockState::WATER, Fluid::Lava));
+    }
+}
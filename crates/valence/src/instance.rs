@@ -1,25 +1,41 @@
 use std::collections::hash_map::Entry;
 use std::collections::BTreeSet;
 use std::iter::FusedIterator;
+use std::ops::Range;
+use std::sync::Arc;
 
 use bevy_ecs::prelude::*;
+pub use block_update_queue::BlockUpdateQueue;
+use bytes::Bytes;
 pub use chunk_entry::*;
 use glam::{DVec3, Vec3};
 use num::integer::div_ceil;
-use rustc_hash::FxHashMap;
+use rand::Rng;
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use valence_protocol::block::BlockState;
 use valence_protocol::packets::s2c::particle::{Particle, ParticleS2c};
-use valence_protocol::packets::s2c::play::{SetActionBarText, SoundEffect};
+use valence_protocol::packets::s2c::play::{Explosion, SetActionBarText, SoundEffect};
 use valence_protocol::types::SoundCategory;
 use valence_protocol::{BlockPos, EncodePacket, LengthPrefixedArray, Sound, Text};
 
+use crate::biome::BiomeId;
+use crate::block_breaking::blast_resistance;
+use crate::config::DEFAULT_TPS;
 use crate::dimension::DimensionId;
-use crate::entity::McEntity;
-pub use crate::instance::chunk::{Block, BlockMut, BlockRef, Chunk};
+use crate::entity::{EntityKind, McEntity};
+pub use crate::instance::chunk::{
+    Block, BlockMut, BlockRef, Chunk, ChunkEncoder, EncodedChunk, SECTION_BIOME_COUNT,
+    SECTION_BLOCK_COUNT,
+};
+use crate::math::Aabb;
 use crate::packet::{PacketWriter, WritePacket};
 use crate::server::{Server, SharedServer};
-use crate::view::ChunkPos;
+use crate::timings::TickTimings;
+use crate::view::{ChunkPos, ChunkView};
 use crate::Despawned;
 
+mod block_update_queue;
 mod chunk;
 mod chunk_entry;
 mod paletted_container;
@@ -48,15 +64,137 @@ mod paletted_container;
 /// # let instance = server.new_instance(DimensionId::default());
 /// let instance_entity = app.world.spawn(instance);
 /// ```
+///
+/// # Parallelism
+///
+/// Every [`Chunk`] owns its data, so any operation on a `&mut Chunk` is safe to
+/// perform on many chunks at once. [`Instance::par_chunks_mut`] hands out all
+/// loaded chunks to a rayon parallel iterator for this purpose. Operations on
+/// the instance itself, such as [`Instance::set_block`] or writing packets,
+/// need exclusive access to the whole instance and must happen on one thread.
+/// Block changes computed in parallel which span multiple chunks can be
+/// collected in a [`BlockUpdateQueue`] and applied afterwards.
+///
+/// Entities are stored separately from the instance. Use
+/// [`Instance::par_entities`] to visit the entities of the instance in
+/// parallel for read-only queries, or Bevy's `Query::par_for_each_mut` to
+/// modify them.
 #[derive(Component)]
 pub struct Instance {
     pub(crate) partition: FxHashMap<ChunkPos, PartitionCell>,
-    pub(crate) info: InstanceInfo,
+    /// Shared with the [`ChunkEncoder`]s of this instance.
+    pub(crate) info: Arc<InstanceInfo>,
     /// Packet data to send to all clients in this instance at the end of the
     /// tick.
     pub(crate) packet_buf: Vec<u8>,
+    /// The contents of `packet_buf` once clients are updated. Clients
+    /// reference these bytes instead of copying them.
+    pub(crate) packet_bytes: Bytes,
     /// Scratch space for writing packets.
     scratch: Vec<u8>,
+    max_view_distance: Option<u8>,
+    max_simulation_distance: Option<u8>,
+    entity_tracking_ranges: FxHashMap<EntityKind, u8>,
+    /// The explosions created this tick.
+    explosions: Vec<InstanceExplosion>,
+}
+
+/// The result of [`Instance::raycast_entities`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EntityRaycastHit {
+    /// The entity which was hit.
+    pub entity: Entity,
+    /// The distance from the origin of the ray to the hitbox in blocks.
+    pub distance: f64,
+    /// The point where the ray entered the hitbox.
+    pub position: DVec3,
+}
+
+/// The result of [`Instance::raycast_blocks`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BlockRaycastHit {
+    /// The position of the block which was hit.
+    pub pos: BlockPos,
+    /// The distance from the origin of the ray to the block in blocks.
+    pub distance: f64,
+    /// The point where the ray hit the block.
+    pub position: DVec3,
+}
+
+/// Options for [`Instance::explode`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ExplosionOptions {
+    /// If the blocks destroyed by the explosion are removed from the instance.
+    pub block_damage: bool,
+    /// If clients near the explosion are knocked back.
+    pub knockback: bool,
+    /// If entities near the explosion are damaged. Damage is dealt by the
+    /// [`CombatPlugin`](crate::combat::CombatPlugin) to entities with
+    /// [`Health`](crate::combat::Health).
+    pub damage_entities: bool,
+    /// The entity which caused the explosion, like primed TNT.
+    pub source: Option<Entity>,
+}
+
+impl Default for ExplosionOptions {
+    fn default() -> Self {
+        Self {
+            block_damage: true,
+            knockback: true,
+            damage_entities: true,
+            source: None,
+        }
+    }
+}
+
+/// An explosion created with [`Instance::explode`] this tick.
+#[derive(Clone, PartialEq, Debug)]
+pub struct InstanceExplosion {
+    pub position: DVec3,
+    pub power: f32,
+    pub options: ExplosionOptions,
+    /// The blocks destroyed by the explosion, paired with the state they had
+    /// before.
+    pub destroyed: Vec<(BlockPos, BlockState)>,
+}
+
+/// The effect of an explosion on an entity, returned by
+/// [`InstanceExplosion::impact`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ExplosionImpact {
+    /// The damage dealt to the entity.
+    pub damage: f32,
+    /// The knockback velocity of the entity in meters per second.
+    pub knockback: Vec3,
+}
+
+impl InstanceExplosion {
+    /// Returns the effect of this explosion on an entity at `position` with
+    /// the given hitbox, or `None` if the entity is out of range. Blocks
+    /// between the explosion and the hitbox reduce the impact like vanilla.
+    pub fn impact(
+        &self,
+        instance: &Instance,
+        position: DVec3,
+        hitbox: Aabb,
+    ) -> Option<ExplosionImpact> {
+        let radius = self.power as f64 * 2.0;
+        let distance = position.distance(self.position);
+
+        if distance >= radius {
+            return None;
+        }
+
+        let exposure = instance.explosion_exposure(self.position, hitbox);
+        let impact = ((1.0 - distance / radius) * exposure) as f32;
+
+        let direction = (position - self.position).normalize_or_zero().as_vec3();
+
+        Some(ExplosionImpact {
+            damage: ((impact * impact + impact) / 2.0 * 7.0 * radius as f32 + 1.0).floor(),
+            knockback: direction * impact * DEFAULT_TPS as f32,
+        })
+    }
 }
 
 pub(crate) struct InstanceInfo {
@@ -65,6 +203,7 @@ pub(crate) struct InstanceInfo {
     min_y: i32,
     biome_registry_len: usize,
     compression_threshold: Option<u32>,
+    compression_level: u32,
     filler_sky_light_mask: Box<[u64]>,
     /// Sending filler light data causes the vanilla client to lag
     /// less. Hopefully we can remove this in the future.
@@ -77,6 +216,9 @@ pub(crate) struct PartitionCell {
     pub(crate) chunk: Option<Chunk<true>>,
     /// If `chunk` went from `Some` to `None` this tick.
     pub(crate) chunk_removed: bool,
+    /// If `chunk` is sent again to the clients in view of this cell at the
+    /// end of the tick. See [`Instance::resend_chunk`].
+    pub(crate) resend_chunk: bool,
     /// Minecraft entities in this cell.
     pub(crate) entities: BTreeSet<Entity>,
     /// Minecraft entities that have entered the chunk this tick, paired with
@@ -88,6 +230,12 @@ pub(crate) struct PartitionCell {
     /// A cache of packets to send to all clients that are in view of this cell
     /// at the end of the tick.
     pub(crate) packet_buf: Vec<u8>,
+    /// The contents of `packet_buf` once clients are updated. Clients
+    /// reference these bytes instead of copying them.
+    pub(crate) packet_bytes: Bytes,
+    /// The range of `packet_buf` containing the update packets of each entity
+    /// in this cell.
+    pub(crate) entity_update_ranges: Vec<(Entity, Range<usize>)>,
 }
 
 impl Instance {
@@ -104,21 +252,27 @@ impl Instance {
 
         Self {
             partition: FxHashMap::default(),
-            info: InstanceInfo {
+            info: Arc::new(InstanceInfo {
                 dimension,
                 section_count: (dim.height / 16) as usize,
                 min_y: dim.min_y,
                 biome_registry_len: shared.biomes().len(),
                 compression_threshold: shared.compression_threshold(),
+                compression_level: shared.compression_level(),
                 filler_sky_light_mask: sky_light_mask.into(),
                 filler_sky_light_arrays: vec![
                     LengthPrefixedArray([0xff; 2048]);
                     light_section_count
                 ]
                 .into(),
-            },
+            }),
             packet_buf: vec![],
+            packet_bytes: Bytes::new(),
             scratch: vec![],
+            max_view_distance: None,
+            max_simulation_distance: None,
+            entity_tracking_ranges: shared.entity_tracking_ranges().clone(),
+            explosions: vec![],
         }
     }
 
@@ -130,6 +284,84 @@ impl Instance {
         self.info.section_count
     }
 
+    /// Returns the y coordinate of the lowest block in this instance.
+    pub fn min_y(&self) -> i32 {
+        self.info.min_y
+    }
+
+    /// Gets the maximum view distance of the clients in this instance set by
+    /// [`Self::set_max_view_distance`].
+    pub fn max_view_distance(&self) -> Option<u8> {
+        self.max_view_distance
+    }
+
+    /// Limits the view distance of all clients in this instance, in chunks.
+    /// Chunks and entities past this distance are not sent to the clients,
+    /// whatever their own view distance is. `None` removes the limit.
+    ///
+    /// The distance is clamped to `2..=32`.
+    pub fn set_max_view_distance(&mut self, dist: Option<u8>) {
+        self.max_view_distance = dist.map(|d| d.clamp(2, 32));
+    }
+
+    /// Gets the maximum simulation distance of the clients in this instance
+    /// set by [`Self::set_max_simulation_distance`].
+    pub fn max_simulation_distance(&self) -> Option<u8> {
+        self.max_simulation_distance
+    }
+
+    /// Limits the simulation distance of all clients in this instance, in
+    /// chunks. See [`Client::set_simulation_distance`]. `None` removes the
+    /// limit.
+    ///
+    /// The distance is clamped to `2..=32`.
+    ///
+    /// [`Client::set_simulation_distance`]: crate::client::Client::set_simulation_distance
+    pub fn set_max_simulation_distance(&mut self, dist: Option<u8>) {
+        self.max_simulation_distance = dist.map(|d| d.clamp(2, 32));
+    }
+
+    /// Gets the tracking range of entities of the given kind in this instance,
+    /// in chunks. `None` means entities of the kind are tracked up to the view
+    /// distance of each client.
+    ///
+    /// Instances start with the ranges in
+    /// [`ServerPlugin::entity_tracking_ranges`].
+    ///
+    /// [`ServerPlugin::entity_tracking_ranges`]: crate::config::ServerPlugin::entity_tracking_ranges
+    pub fn entity_tracking_range(&self, kind: EntityKind) -> Option<u8> {
+        self.entity_tracking_ranges.get(&kind).copied()
+    }
+
+    /// Sets the tracking range of entities of the given kind in this instance,
+    /// in chunks. Clients only see entities of the kind within this many
+    /// chunks of them, or within their view distance if that is smaller.
+    /// `None` tracks entities of the kind up to the view distance of each
+    /// client.
+    ///
+    /// The new range applies to entities as they move in and out of the view
+    /// of clients. Entities already spawned for a client are not despawned.
+    pub fn set_entity_tracking_range(&mut self, kind: EntityKind, range: Option<u8>) {
+        match range {
+            Some(range) => self.entity_tracking_ranges.insert(kind, range),
+            None => self.entity_tracking_ranges.remove(&kind),
+        };
+    }
+
+    /// Returns the view of entities of the given kind for a client with the
+    /// given chunk view.
+    pub(crate) fn entity_view(&self, kind: EntityKind, view: ChunkView) -> ChunkView {
+        match self.entity_tracking_ranges.get(&kind) {
+            Some(&range) if range < view.dist => view.with_dist(range),
+            _ => view,
+        }
+    }
+
+    /// If any entity kind has a tracking range in this instance.
+    pub(crate) fn has_entity_tracking_ranges(&self) -> bool {
+        !self.entity_tracking_ranges.is_empty()
+    }
+
     /// Get a reference to the chunk at the given position, if it is loaded.
     pub fn chunk(&self, pos: impl Into<ChunkPos>) -> Option<&Chunk<true>> {
         self.partition
@@ -157,6 +389,50 @@ impl Instance {
         }
     }
 
+    /// Returns a [`ChunkEncoder`] for encoding chunks of this instance on
+    /// other threads before they are inserted.
+    pub fn chunk_encoder(&self) -> ChunkEncoder {
+        ChunkEncoder {
+            info: self.info.clone(),
+        }
+    }
+
+    /// Inserts a chunk encoded with a [`ChunkEncoder`] at the position it was
+    /// encoded for. Clients are sent the packet encoded beforehand, unless the
+    /// chunk was encoded for another instance.
+    pub fn insert_encoded_chunk(&mut self, chunk: EncodedChunk) -> Option<Chunk> {
+        let (pos, chunk, init_packets) = chunk.into_parts(&self.info);
+
+        let (old, chunk) = match self.chunk_entry(pos) {
+            ChunkEntry::Occupied(mut oe) => (Some(oe.insert(chunk)), oe.into_mut()),
+            ChunkEntry::Vacant(ve) => (None, ve.insert(chunk)),
+        };
+
+        if let Some(init_packets) = init_packets {
+            chunk.set_init_packets(init_packets);
+        }
+
+        old
+    }
+
+    /// Sends the chunk at the given position again to all clients in view of
+    /// it at the end of the tick. This discards any changes clients were sent
+    /// separately, such as blocks overridden for a single client. Returns
+    /// `false` if no chunk is loaded at the position.
+    ///
+    /// To resend a chunk to only one client, see [`Client::resend_chunk`].
+    ///
+    /// [`Client::resend_chunk`]: crate::client::Client::resend_chunk
+    pub fn resend_chunk(&mut self, pos: impl Into<ChunkPos>) -> bool {
+        match self.partition.get_mut(&pos.into()) {
+            Some(cell) if cell.chunk.is_some() => {
+                cell.resend_chunk = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Unload the chunk at the given position, if it is loaded. Returns the
     /// chunk if it was loaded.
     pub fn remove_chunk(&mut self, pos: impl Into<ChunkPos>) -> Option<Chunk> {
@@ -207,6 +483,367 @@ impl Instance {
             .flat_map(|(&pos, par)| par.chunk.as_mut().map(|c| (pos, c)))
     }
 
+    /// Get a parallel iterator over all loaded chunks in the instance. The order
+    /// of the chunks is undefined.
+    pub fn par_chunks(&self) -> impl ParallelIterator<Item = (ChunkPos, &Chunk<true>)> + '_ {
+        self.partition
+            .par_iter()
+            .filter_map(|(&pos, par)| par.chunk.as_ref().map(|c| (pos, c)))
+    }
+
+    /// Get a parallel iterator over all loaded chunks in the instance, mutably.
+    /// The order of the chunks is undefined.
+    ///
+    /// Changes made to the chunks are sent to clients as usual.
+    pub fn par_chunks_mut(
+        &mut self,
+    ) -> impl ParallelIterator<Item = (ChunkPos, &mut Chunk<true>)> + '_ {
+        self.partition
+            .par_iter_mut()
+            .filter_map(|(&pos, par)| par.chunk.as_mut().map(|c| (pos, c)))
+    }
+
+    /// Get a parallel iterator over the Minecraft entities in this instance
+    /// along with the position of the chunk they are in. Entities which were
+    /// added to the instance this tick are not included until the end of the
+    /// tick.
+    pub fn par_entities(&self) -> impl ParallelIterator<Item = (ChunkPos, Entity)> + '_ {
+        self.partition
+            .par_iter()
+            .flat_map_iter(|(&pos, par)| par.entities.iter().map(move |&e| (pos, e)))
+    }
+
+    /// Finds the first entity hit by a ray from `origin` in `direction`
+    /// within `max_distance` blocks.
+    ///
+    /// `hitbox` returns the hitbox of an entity in the instance, or `None` to
+    /// ignore the entity (e.g. the entity casting the ray). It is usually
+    /// [`McEntity::hitbox`] looked up in a query. Like with
+    /// [`Self::par_entities`], entities added to the instance this tick are not
+    /// considered.
+    ///
+    /// ```
+    /// # use valence::prelude::*;
+    /// # fn f(instance: &Instance, entities: &Query<&McEntity>, client: &Client, me: Entity) {
+    /// let direction = valence::math::from_yaw_and_pitch(client.yaw(), client.pitch());
+    ///
+    /// let hit = instance.raycast_entities(
+    ///     client.eye_position(),
+    ///     direction.as_dvec3(),
+    ///     6.0,
+    ///     |e| (e != me).then(|| entities.get(e).ok()).flatten().map(|e| e.hitbox()),
+    /// );
+    /// # }
+    /// ```
+    pub fn raycast_entities(
+        &self,
+        origin: DVec3,
+        direction: DVec3,
+        max_distance: f64,
+        mut hitbox: impl FnMut(Entity) -> Option<Aabb>,
+    ) -> Option<EntityRaycastHit> {
+        let direction = direction.normalize_or_zero();
+
+        if direction == DVec3::ZERO {
+            return None;
+        }
+
+        let end = origin + direction * max_distance;
+
+        // Entities are stored in the chunk containing their position, but
+        // their hitboxes can extend into the neighboring chunks.
+        let min = ChunkPos::from_dvec3(origin.min(end));
+        let max = ChunkPos::from_dvec3(origin.max(end));
+
+        let mut closest: Option<EntityRaycastHit> = None;
+
+        for z in min.z - 1..=max.z + 1 {
+            for x in min.x - 1..=max.x + 1 {
+                let Some(cell) = self.partition.get(&ChunkPos::new(x, z)) else {
+                    continue
+                };
+
+                for &entity in &cell.entities {
+                    let Some(distance) =
+                        hitbox(entity).and_then(|aabb| aabb.ray_intersection(origin, direction))
+                    else {
+                        continue
+                    };
+
+                    if distance <= max_distance
+                        && closest.map_or(true, |hit| distance < hit.distance)
+                    {
+                        closest = Some(EntityRaycastHit {
+                            entity,
+                            distance,
+                            position: origin + direction * distance,
+                        });
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+
+    /// Finds the first block hit by a ray from `origin` in `direction` within
+    /// `max_distance` blocks. Blocks are hit where their collision shapes are,
+    /// so the ray passes through air, liquids and plants.
+    ///
+    /// The ray ends at the first unloaded chunk it enters and once it leaves
+    /// the world vertically, so `max_distance` can be infinite.
+    pub fn raycast_blocks(
+        &self,
+        origin: DVec3,
+        direction: DVec3,
+        max_distance: f64,
+    ) -> Option<BlockRaycastHit> {
+        let direction = direction.normalize_or_zero();
+
+        if direction == DVec3::ZERO {
+            return None;
+        }
+
+        // Step through the blocks along the ray. `next` is the distance along
+        // the ray to the next block boundary on each axis.
+        let boundary = |o: f64, d: f64| {
+            if d > 0.0 {
+                (o.floor() + 1.0 - o) / d
+            } else if d < 0.0 {
+                (o - o.floor()) / -d
+            } else {
+                f64::INFINITY
+            }
+        };
+
+        let step = direction.signum().as_ivec3();
+        let delta = direction.abs().recip();
+        let mut next = DVec3::new(
+            boundary(origin.x, direction.x),
+            boundary(origin.y, direction.y),
+            boundary(origin.z, direction.z),
+        );
+        let mut pos = BlockPos::at(origin);
+
+        let min_y = self.min_y();
+        let max_y = min_y + self.section_count() as i32 * 16;
+
+        loop {
+            if self.chunk(ChunkPos::from_block_pos(pos)).is_none()
+                || (pos.y < min_y && step.y <= 0)
+                || (pos.y >= max_y && step.y >= 0)
+            {
+                return None;
+            }
+
+            if let Some(block) = self.block(pos) {
+                let corner = DVec3::new(pos.x as f64, pos.y as f64, pos.z as f64);
+
+                let distance = block
+                    .state()
+                    .collision_shapes()
+                    .filter_map(|[x0, y0, z0, x1, y1, z1]| {
+                        Aabb::new(
+                            corner + DVec3::new(x0, y0, z0),
+                            corner + DVec3::new(x1, y1, z1),
+                        )
+                        .ray_intersection(origin, direction)
+                    })
+                    .min_by(f64::total_cmp);
+
+                if let Some(distance) = distance.filter(|&d| d <= max_distance) {
+                    return Some(BlockRaycastHit {
+                        pos,
+                        distance,
+                        position: origin + direction * distance,
+                    });
+                }
+            }
+
+            let axis = if next.x < next.y && next.x < next.z {
+                0
+            } else if next.y < next.z {
+                1
+            } else {
+                2
+            };
+
+            if next[axis] > max_distance {
+                return None;
+            }
+
+            next[axis] += delta[axis];
+
+            match axis {
+                0 => pos.x += step.x,
+                1 => pos.y += step.y,
+                _ => pos.z += step.z,
+            }
+        }
+    }
+
+    /// Returns an iterator over the entities in this instance whose position
+    /// is inside `aabb`.
+    ///
+    /// `position` returns the position of an entity in the instance, or `None`
+    /// to ignore the entity. It is usually [`McEntity::position`] looked up in
+    /// a query. Like with [`Self::par_entities`], entities added to the
+    /// instance this tick are not considered.
+    pub fn entities_in_aabb<'a>(
+        &'a self,
+        aabb: Aabb,
+        mut position: impl FnMut(Entity) -> Option<DVec3> + 'a,
+    ) -> impl Iterator<Item = Entity> + 'a {
+        self.entities_in_chunk_range(
+            ChunkPos::from_dvec3(aabb.min),
+            ChunkPos::from_dvec3(aabb.max),
+        )
+        .filter(move |&entity| {
+            position(entity).map_or(false, |pos| {
+                aabb.min.cmple(pos).all() && pos.cmple(aabb.max).all()
+            })
+        })
+    }
+
+    /// Returns an iterator over the entities in this instance whose position
+    /// is within `radius` blocks of `center`.
+    ///
+    /// `position` works the same as in [`Self::entities_in_aabb`].
+    pub fn entities_within_radius<'a>(
+        &'a self,
+        center: DVec3,
+        radius: f64,
+        mut position: impl FnMut(Entity) -> Option<DVec3> + 'a,
+    ) -> impl Iterator<Item = Entity> + 'a {
+        let radius = radius.max(0.0);
+
+        self.entities_in_chunk_range(
+            ChunkPos::from_dvec3(center - radius),
+            ChunkPos::from_dvec3(center + radius),
+        )
+        .filter(move |&entity| {
+            position(entity).map_or(false, |pos| pos.distance_squared(center) <= radius * radius)
+        })
+    }
+
+    /// Finds the entity in this instance closest to `pos` within
+    /// `max_distance` blocks. Returns the entity and its distance from `pos`.
+    ///
+    /// `position` works the same as in [`Self::entities_in_aabb`]. Chunks are
+    /// searched outward from `pos`, so nearby entities are found without
+    /// visiting the rest of the instance.
+    ///
+    /// ```
+    /// # use valence::prelude::*;
+    /// # fn f(instance: &Instance, entities: &Query<&McEntity>, client: &Client, me: Entity) {
+    /// let nearest = instance.nearest_entity(client.position(), 16.0, |e| {
+    ///     (e != me).then(|| entities.get(e).ok()).flatten().map(|e| e.position())
+    /// });
+    /// # }
+    /// ```
+    pub fn nearest_entity(
+        &self,
+        pos: DVec3,
+        max_distance: f64,
+        mut position: impl FnMut(Entity) -> Option<DVec3>,
+    ) -> Option<(Entity, f64)> {
+        if max_distance.is_nan() || max_distance < 0.0 {
+            return None;
+        }
+
+        let center = ChunkPos::from_dvec3(pos);
+        // An entity in a chunk `r` chunks away from the center is at least
+        // `(r - 1) * 16` blocks away from `pos`.
+        let max_ring = (max_distance / 16.0).ceil().min(i32::MAX as f64 / 2.0) as i32 + 1;
+
+        let mut closest: Option<(Entity, f64)> = None;
+        let mut cells_visited = 0;
+
+        for ring in 0..=max_ring {
+            if closest.map_or(false, |(_, dist)| dist <= (ring - 1) as f64 * 16.0)
+                || cells_visited == self.partition.len()
+            {
+                break;
+            }
+
+            for z in center.z - ring..=center.z + ring {
+                // Only the edge of the square is part of this ring.
+                let step = if z == center.z - ring || z == center.z + ring {
+                    1
+                } else {
+                    (ring * 2).max(1) as usize
+                };
+
+                for x in (center.x - ring..=center.x + ring).step_by(step) {
+                    let Some(cell) = self.partition.get(&ChunkPos::new(x, z)) else {
+                        continue
+                    };
+
+                    cells_visited += 1;
+
+                    for &entity in &cell.entities {
+                        let Some(entity_pos) = position(entity) else {
+                            continue
+                        };
+
+                        let dist = entity_pos.distance(pos);
+
+                        if dist <= max_distance && closest.map_or(true, |(_, d)| dist < d) {
+                            closest = Some((entity, dist));
+                        }
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+
+    /// Returns the entities in the partition cells between `min` and `max`
+    /// (inclusive).
+    fn entities_in_chunk_range(
+        &self,
+        min: ChunkPos,
+        max: ChunkPos,
+    ) -> Box<dyn Iterator<Item = Entity> + '_> {
+        let area = (max.x as i64 - min.x as i64 + 1) * (max.z as i64 - min.z as i64 + 1);
+
+        if area > self.partition.len() as i64 {
+            // Cheaper to go through the occupied cells than the whole range.
+            Box::new(
+                self.partition
+                    .iter()
+                    .filter(move |(pos, _)| {
+                        (min.x..=max.x).contains(&pos.x) && (min.z..=max.z).contains(&pos.z)
+                    })
+                    .flat_map(|(_, cell)| cell.entities.iter().copied()),
+            )
+        } else {
+            Box::new(
+                (min.z..=max.z)
+                    .flat_map(move |z| (min.x..=max.x).map(move |x| ChunkPos::new(x, z)))
+                    .filter_map(|pos| self.partition.get(&pos))
+                    .flat_map(|cell| cell.entities.iter().copied()),
+            )
+        }
+    }
+
+    /// Returns the positions of all blocks changed in this instance since the
+    /// last tick.
+    pub(crate) fn changed_blocks(&self) -> impl Iterator<Item = BlockPos> + '_ {
+        let min_y = self.info.min_y;
+
+        self.chunks().flat_map(move |(pos, chunk)| {
+            chunk.block_updates().map(move |[x, y, z]| {
+                BlockPos::new(
+                    pos.x * 16 + x as i32,
+                    min_y + y as i32,
+                    pos.z * 16 + z as i32,
+                )
+            })
+        })
+    }
+
     /// Optimizes the memory usage of the instance.
     pub fn optimize(&mut self) {
         for (_, chunk) in self.chunks_mut() {
@@ -303,6 +940,64 @@ impl Instance {
         ))
     }
 
+    /// Gets the biome of the 4x4x4 biome cell containing an absolute block
+    /// position in world space. Only works for blocks in loaded chunks.
+    ///
+    /// If the position is not inside of a chunk, then [`Option::None`] is
+    /// returned.
+    pub fn biome(&self, pos: impl Into<BlockPos>) -> Option<BiomeId> {
+        let pos = pos.into();
+
+        let Some(y) = pos.y.checked_sub(self.info.min_y).and_then(|y| usize::try_from(y).ok()) else {
+            return None;
+        };
+
+        if y >= self.info.section_count * 16 {
+            return None;
+        }
+
+        let Some(chunk) = self.chunk(ChunkPos::from_block_pos(pos)) else {
+            return None;
+        };
+
+        Some(chunk.biome(
+            pos.x.rem_euclid(16) as usize / 4,
+            y / 4,
+            pos.z.rem_euclid(16) as usize / 4,
+        ))
+    }
+
+    /// Sets the biome of the 4x4x4 biome cell containing an absolute block
+    /// position in world space. The previous biome of the cell is returned.
+    ///
+    /// Changing biomes resends the whole chunk to clients, so biomes should be
+    /// set before the chunk is loaded when possible.
+    ///
+    /// If the position is not within a loaded chunk or otherwise out of bounds,
+    /// then [`Option::None`] is returned with no effect.
+    pub fn set_biome(&mut self, pos: impl Into<BlockPos>, biome: BiomeId) -> Option<BiomeId> {
+        let pos = pos.into();
+
+        let Some(y) = pos.y.checked_sub(self.info.min_y).and_then(|y| usize::try_from(y).ok()) else {
+            return None;
+        };
+
+        if y >= self.info.section_count * 16 {
+            return None;
+        }
+
+        let Some(chunk) = self.chunk_mut(ChunkPos::from_block_pos(pos)) else {
+            return None;
+        };
+
+        Some(chunk.set_biome(
+            pos.x.rem_euclid(16) as usize / 4,
+            y / 4,
+            pos.z.rem_euclid(16) as usize / 4,
+            biome,
+        ))
+    }
+
     /// Writes a packet into the global packet buffer of this instance. All
     /// clients in the instance will receive the packet.
     ///
@@ -317,6 +1012,7 @@ impl Instance {
             self.info.compression_threshold,
             &mut self.scratch,
         )
+        .with_compression_level(self.info.compression_level)
         .write_packet(pkt);
     }
 
@@ -347,6 +1043,7 @@ impl Instance {
                     self.info.compression_threshold,
                     &mut self.scratch,
                 )
+                .with_compression_level(self.info.compression_level)
                 .write_packet(pkt);
             }
         }
@@ -426,13 +1123,187 @@ impl Instance {
             action_bar_text: text.into().into(),
         });
     }
+
+    /// Creates an explosion at `position` with the given power. TNT has a
+    /// power of 4.
+    ///
+    /// The blocks in range are destroyed by casting rays from the center like
+    /// vanilla, where blocks with a higher [`blast_resistance`] absorb more of
+    /// the explosion. The explosion is shown to all clients in view of
+    /// `position`. Knockback and damage to entities are applied later in the
+    /// tick, see [`ExplosionOptions`]. To damage entities, explosions must be
+    /// created before [`CoreStage::PostUpdate`].
+    ///
+    /// [`CoreStage::PostUpdate`]: bevy_app::CoreStage::PostUpdate
+    ///
+    /// Returns the blocks which were destroyed, paired with the state they had
+    /// before. Nothing is destroyed if [`ExplosionOptions::block_damage`] is
+    /// disabled.
+    pub fn explode(
+        &mut self,
+        position: impl Into<DVec3>,
+        power: f32,
+        options: ExplosionOptions,
+    ) -> Vec<(BlockPos, BlockState)> {
+        let position = position.into();
+
+        let destroyed: Vec<_> = if options.block_damage {
+            self.explosion_blocks(position, power)
+                .into_iter()
+                .filter_map(|pos| Some((pos, self.set_block(pos, BlockState::AIR)?.state())))
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let origin = BlockPos::at(position);
+
+        let records = destroyed
+            .iter()
+            .filter_map(|(pos, _)| {
+                Some([
+                    i8::try_from(pos.x - origin.x).ok()?,
+                    i8::try_from(pos.y - origin.y).ok()?,
+                    i8::try_from(pos.z - origin.z).ok()?,
+                ])
+            })
+            .collect();
+
+        // Knockback is sent to each client separately.
+        self.write_packet_at(
+            &Explosion {
+                position: position.to_array(),
+                strength: power,
+                records,
+                player_motion: [0.0; 3],
+            },
+            ChunkPos::from_dvec3(position),
+        );
+
+        self.explosions.push(InstanceExplosion {
+            position,
+            power,
+            options,
+            destroyed: destroyed.clone(),
+        });
+
+        destroyed
+    }
+
+    /// Returns the explosions created with [`Self::explode`] this tick.
+    pub fn explosions(&self) -> &[InstanceExplosion] {
+        &self.explosions
+    }
+
+    /// Returns the blocks an explosion at `center` with the given power
+    /// destroys, using the vanilla algorithm of casting rays from the center.
+    /// Blocks with a higher [`blast_resistance`] absorb more of the explosion.
+    /// The blocks are not removed.
+    pub fn explosion_blocks(&self, center: DVec3, power: f32) -> Vec<BlockPos> {
+        const STEP: f64 = 0.3;
+
+        let mut rng = rand::thread_rng();
+        let mut destroyed = vec![];
+        // Many rays pass through the same blocks, so they are only added once.
+        let mut visited = FxHashSet::default();
+
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    // Only cast rays through the boundary of the cube.
+                    if !(x == 0 || x == 15 || y == 0 || y == 15 || z == 0 || z == 15) {
+                        continue;
+                    }
+
+                    let dir = DVec3::new(x as f64, y as f64, z as f64) / 15.0 * 2.0 - 1.0;
+                    let dir = dir.normalize() * STEP;
+
+                    let mut intensity = power * rng.gen_range(0.7..1.3);
+                    let mut pos = center;
+
+                    while intensity > 0.0 {
+                        let block_pos = BlockPos::at(pos);
+
+                        let Some(block) = self.block(block_pos) else {
+                            break
+                        };
+
+                        let state = block.state();
+
+                        if !state.is_air() {
+                            intensity -= (blast_resistance(state) + 0.3) * 0.3;
+
+                            if intensity > 0.0 && visited.insert(block_pos) {
+                                destroyed.push(block_pos);
+                            }
+                        }
+
+                        pos += dir;
+                        intensity -= 0.225;
+                    }
+                }
+            }
+        }
+
+        destroyed
+    }
+
+    /// Returns the fraction of points in `hitbox` with no blocks between them
+    /// and `center`.
+    fn explosion_exposure(&self, center: DVec3, hitbox: Aabb) -> f64 {
+        let size = hitbox.max - hitbox.min;
+        let step = (size * 2.0 + 1.0).recip();
+
+        // Center the points horizontally like vanilla.
+        let offset_x = (1.0 - (1.0 / step.x).floor() * step.x) / 2.0;
+        let offset_z = (1.0 - (1.0 / step.z).floor() * step.z) / 2.0;
+
+        let mut visible = 0;
+        let mut total = 0;
+
+        let mut fx = 0.0;
+        while fx <= 1.0 {
+            let mut fy = 0.0;
+            while fy <= 1.0 {
+                let mut fz = 0.0;
+                while fz <= 1.0 {
+                    let point = hitbox.min
+                        + size * DVec3::new(fx, fy, fz)
+                        + DVec3::new(offset_x, 0.0, offset_z);
+
+                    let to_center = center - point;
+
+                    if self
+                        .raycast_blocks(point, to_center, to_center.length())
+                        .is_none()
+                    {
+                        visible += 1;
+                    }
+
+                    total += 1;
+                    fz += step.z;
+                }
+                fy += step.y;
+            }
+            fx += step.x;
+        }
+
+        if total == 0 {
+            0.0
+        } else {
+            visible as f64 / total as f64
+        }
+    }
 }
 
 pub(crate) fn update_instances_pre_client(
     mut instances: Query<&mut Instance>,
     mut entities: Query<(Entity, &mut McEntity, Option<&Despawned>)>,
     server: Res<Server>,
+    timings: Res<TickTimings>,
 ) {
+    let entity_timer = timings.time("entity updates");
+
     for (entity_id, entity, despawned) in &entities {
         let pos = ChunkPos::at(entity.position().x, entity.position().z);
         let old_pos = ChunkPos::at(entity.old_position().x, entity.old_position().z);
@@ -476,10 +1347,13 @@ pub(crate) fn update_instances_pre_client(
                         ve.insert(PartitionCell {
                             chunk: None,
                             chunk_removed: false,
+                            resend_chunk: false,
                             entities: BTreeSet::from([entity_id]),
                             incoming: vec![(entity_id, None)],
                             outgoing: vec![],
                             packet_buf: vec![],
+                            packet_bytes: Bytes::new(),
+                            entity_update_ranges: vec![],
                         });
                     }
                 }
@@ -508,10 +1382,13 @@ pub(crate) fn update_instances_pre_client(
                         ve.insert(PartitionCell {
                             chunk: None,
                             chunk_removed: false,
+                            resend_chunk: false,
                             entities: BTreeSet::from([entity_id]),
                             incoming: vec![(entity_id, Some(old_pos))],
                             outgoing: vec![],
                             packet_buf: vec![],
+                            packet_bytes: Bytes::new(),
+                            entity_update_ranges: vec![],
                         });
                     }
                 }
@@ -522,51 +1399,88 @@ pub(crate) fn update_instances_pre_client(
         }
     }
 
-    let mut scratch_1 = vec![];
-    let mut scratch_2 = vec![];
+    drop(entity_timer);
+
+    let compression_threshold = server.compression_threshold();
+    let compression_level = server.compression_level();
+    let teleport_threshold = server.entity_teleport_threshold();
 
     for instance in &mut instances {
         let instance = instance.into_inner();
+        let info = &instance.info;
 
-        for (&pos, cell) in &mut instance.partition {
-            // Cache chunk update packets into the packet buffer of this cell.
-            if let Some(chunk) = &mut cell.chunk {
-                let writer = PacketWriter::new(
-                    &mut cell.packet_buf,
-                    server.compression_threshold(),
-                    &mut scratch_2,
-                );
-
-                chunk.write_update_packets(writer, &mut scratch_1, pos, &instance.info);
+        // Cache chunk update packets into the packet buffer of each cell. Encoding
+        // and compressing chunk data packets is expensive, so chunks are processed
+        // in parallel. The chunk data packets are cached in the chunks, so clients
+        // loading the chunk later share the same bytes.
+        let chunk_timer = timings.time("chunk updates");
 
-                chunk.clear_viewed();
-            }
+        instance.partition.par_iter_mut().for_each_init(
+            || (vec![], vec![]),
+            |(scratch_1, scratch_2), (&pos, cell)| {
+                if let Some(chunk) = &mut cell.chunk {
+                    let writer =
+                        PacketWriter::new(&mut cell.packet_buf, compression_threshold, scratch_2)
+                            .with_compression_level(compression_level);
 
-            // Cache entity update packets into the packet buffer of this cell.
-            for &id in &cell.entities {
-                let (_, mut entity, despawned) = entities
-                    .get_mut(id)
-                    .expect("missing entity in partition cell");
+                    chunk.write_update_packets(writer, scratch_1, pos, info, cell.resend_chunk);
 
-                if despawned.is_some() {
-                    continue;
+                    chunk.clear_viewed();
                 }
+            },
+        );
 
-                let start = cell.packet_buf.len();
+        drop(chunk_timer);
 
-                let writer = PacketWriter::new(
-                    &mut cell.packet_buf,
-                    server.compression_threshold(),
-                    &mut scratch_2,
-                );
+        let _entity_timer = timings.time("entity updates");
+
+        // Cache entity update packets into the packet buffer of each cell. The
+        // packets are encoded once per cell no matter how many clients are in
+        // view of it, and the cells are independent so they are processed in
+        // parallel.
+        let entities_ref = &entities;
 
-                entity.write_update_packets(writer, &mut scratch_1);
+        instance.partition.par_iter_mut().for_each_init(
+            || (vec![], vec![]),
+            |(scratch_1, scratch_2), (_, cell)| {
+                for &id in &cell.entities {
+                    let (_, entity, despawned) = entities_ref
+                        .get(id)
+                        .expect("missing entity in partition cell");
+
+                    if despawned.is_some() {
+                        cell.entity_update_ranges.push((id, 0..0));
+                        continue;
+                    }
 
-                let end = cell.packet_buf.len();
+                    let start = cell.packet_buf.len();
 
-                entity.self_update_range = start..end;
+                    let writer =
+                        PacketWriter::new(&mut cell.packet_buf, compression_threshold, scratch_2)
+                            .with_compression_level(compression_level);
+
+                    entity.write_update_packets(writer, scratch_1, teleport_threshold);
+
+                    let end = cell.packet_buf.len();
+
+                    cell.entity_update_ranges.push((id, start..end));
+                }
+
+                // The buffer is not copied. Its capacity is lost, but that is cheaper
+                // than copying the bytes for every client in view.
+                cell.packet_bytes = std::mem::take(&mut cell.packet_buf).into();
+            },
+        );
+
+        for cell in instance.partition.values_mut() {
+            for (id, range) in cell.entity_update_ranges.drain(..) {
+                if let Ok((_, mut entity, _)) = entities.get_mut(id) {
+                    entity.self_update_range = range;
+                }
             }
         }
+
+        instance.packet_bytes = std::mem::take(&mut instance.packet_buf).into();
     }
 }
 
@@ -574,7 +1488,9 @@ pub(crate) fn update_instances_post_client(mut instances: Query<&mut Instance>)
     for mut instance in &mut instances {
         instance.partition.retain(|_, cell| {
             cell.packet_buf.clear();
+            cell.packet_bytes.clear();
             cell.chunk_removed = false;
+            cell.resend_chunk = false;
             cell.incoming.clear();
             cell.outgoing.clear();
 
@@ -586,6 +1502,8 @@ pub(crate) fn update_instances_post_client(mut instances: Query<&mut Instance>)
         });
 
         instance.packet_buf.clear();
+        instance.packet_bytes.clear();
+        instance.explosions.clear();
     }
 }
 
@@ -605,3 +1523,168 @@ pub(crate) fn check_instance_invariants(instances: Query<&Instance>, entities: Q
     let _ = instances;
     let _ = entities;
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::block::BlockState;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::client::Client;
+    use crate::entity::EntityKind;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn biome_cells() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let min_y = instance.min_y();
+        let biome = BiomeId(1);
+
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        assert_eq!(
+            instance.set_biome([5, min_y + 20, 9], biome),
+            Some(BiomeId::default())
+        );
+        assert_eq!(instance.biome([7, min_y + 23, 11]), Some(biome));
+        assert_eq!(instance.biome([8, min_y + 20, 9]), Some(BiomeId::default()));
+        assert_eq!(instance.chunk([0, 0]).unwrap().biome(1, 5, 2), biome);
+
+        assert_eq!(instance.biome([5, min_y - 1, 9]), None);
+        assert_eq!(instance.set_biome([-5, min_y, 9], biome), None);
+    }
+
+    #[test]
+    fn raycast_blocks() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let y = instance.min_y() + 10;
+
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([5, y, 0], BlockState::STONE);
+
+        let origin = DVec3::new(0.5, y as f64 + 0.5, 0.5);
+
+        let hit = instance.raycast_blocks(origin, DVec3::X, 10.0).unwrap();
+        assert_eq!(hit.pos, BlockPos::new(5, y, 0));
+        assert_eq!(hit.distance, 4.5);
+        assert_eq!(hit.position, DVec3::new(5.0, y as f64 + 0.5, 0.5));
+
+        let hit = instance
+            .raycast_blocks(origin + DVec3::new(8.0, 0.0, 0.0), -DVec3::X, 10.0)
+            .unwrap();
+        assert_eq!(hit.position.x, 6.0);
+
+        assert_eq!(instance.raycast_blocks(origin, DVec3::X, 4.0), None);
+        assert_eq!(instance.raycast_blocks(origin, DVec3::Z, 10.0), None);
+    }
+
+    #[test]
+    fn spatial_entity_queries() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut spawn = |pos: [f64; 3]| {
+            let mut entity = McEntity::new(EntityKind::Zombie, instance_ent);
+            entity.set_position(pos);
+            app.world.spawn(entity).id()
+        };
+
+        let near = spawn([1.0, 0.0, 1.0]);
+        let middle = spawn([20.0, 0.0, -3.0]);
+        let far = spawn([200.0, 0.0, 200.0]);
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        let position = |e| app.world.get::<McEntity>(e).map(|e| e.position());
+
+        let mut in_aabb: Vec<_> = instance
+            .entities_in_aabb(Aabb::new([-5.0, -5.0, -5.0], [25.0, 5.0, 5.0]), position)
+            .collect();
+        in_aabb.sort();
+        let mut expected = vec![near, middle];
+        expected.sort();
+        assert_eq!(in_aabb, expected);
+
+        let in_radius: Vec<_> = instance
+            .entities_within_radius(DVec3::new(0.0, 0.0, 0.0), 10.0, position)
+            .collect();
+        assert_eq!(in_radius, [near]);
+
+        let (nearest, dist) = instance
+            .nearest_entity(DVec3::new(19.0, 0.0, -3.0), f64::INFINITY, position)
+            .unwrap();
+        assert_eq!(nearest, middle);
+        assert_eq!(dist, 1.0);
+
+        let (nearest, _) = instance
+            .nearest_entity(DVec3::new(150.0, 0.0, 150.0), f64::INFINITY, position)
+            .unwrap();
+        assert_eq!(nearest, far);
+
+        assert_eq!(
+            instance.nearest_entity(DVec3::new(150.0, 0.0, 150.0), 10.0, position),
+            None
+        );
+        // Ignored entities are skipped.
+        let (nearest, _) = instance
+            .nearest_entity(DVec3::ZERO, 100.0, |e| {
+                (e != near).then(|| position(e)).flatten()
+            })
+            .unwrap();
+        assert_eq!(nearest, middle);
+    }
+
+    #[test]
+    fn entity_updates_cached_per_cell() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut spawn = |pos: [f64; 3]| {
+            let mut entity = McEntity::new(EntityKind::Zombie, instance_ent);
+            entity.set_position(pos);
+            app.world.spawn(entity).id()
+        };
+
+        let a = spawn([1.0, 0.0, 1.0]);
+        let b = spawn([2.0, 0.0, 2.0]);
+        let c = spawn([20.0, 0.0, 1.0]);
+
+        app.update();
+        client_helper.clear_sent();
+
+        for id in [a, c] {
+            let mut entity = app.world.get_mut::<McEntity>(id).unwrap();
+            let pos = entity.position();
+            entity.set_position(pos + DVec3::new(0.5, 0.0, 0.0));
+        }
+
+        app.update();
+
+        let range = |id| {
+            app.world
+                .get::<McEntity>(id)
+                .unwrap()
+                .self_update_range
+                .clone()
+        };
+        assert!(!range(a).is_empty());
+        assert!(range(b).is_empty());
+        assert!(!range(c).is_empty());
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 2, S2cPlayPacket::UpdateEntityPosition(_));
+    }
+}
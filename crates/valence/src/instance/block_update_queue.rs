@@ -0,0 +1,96 @@
+use parking_lot::Mutex;
+use rayon::prelude::*;
+
+use super::*;
+
+/// A queue of block changes which can be filled from multiple threads at once
+/// and then applied to an [`Instance`] in parallel.
+///
+/// This is useful when the blocks to change are computed in parallel but
+/// don't belong to a single chunk, which rules out
+/// [`Instance::par_chunks_mut`].
+///
+/// ```
+/// use rayon::prelude::*;
+/// use valence::instance::BlockUpdateQueue;
+/// # use valence::prelude::*;
+///
+/// fn fill_floor(instance: &mut Instance) {
+///     let queue = BlockUpdateQueue::new();
+///
+///     (-64..64).into_par_iter().for_each(|x| {
+///         for z in -64..64 {
+///             queue.push([x, 0, z], BlockState::STONE);
+///         }
+///     });
+///
+///     queue.apply(instance);
+/// }
+/// ```
+#[derive(Default, Debug)]
+pub struct BlockUpdateQueue {
+    updates: Mutex<Vec<(BlockPos, Block)>>,
+}
+
+impl BlockUpdateQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a block change to the queue. If the same position is changed more
+    /// than once, the change pushed last wins.
+    pub fn push(&self, pos: impl Into<BlockPos>, block: impl Into<Block>) {
+        self.updates.lock().push((pos.into(), block.into()));
+    }
+
+    /// Adds multiple block changes to the queue at once, which is faster than
+    /// calling [`Self::push`] repeatedly.
+    pub fn extend(&self, updates: impl IntoIterator<Item = (BlockPos, Block)>) {
+        self.updates.lock().extend(updates);
+    }
+
+    pub fn len(&self) -> usize {
+        self.updates.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.lock().is_empty()
+    }
+
+    /// Applies all changes in the queue to `instance`, leaving the queue empty.
+    /// Chunks are modified in parallel. Changes to positions outside of the
+    /// instance's loaded chunks are discarded.
+    pub fn apply(&self, instance: &mut Instance) {
+        let updates = std::mem::take(&mut *self.updates.lock());
+
+        let min_y = instance.info.min_y;
+        let height = instance.info.section_count * 16;
+
+        let mut by_chunk: FxHashMap<ChunkPos, Vec<([usize; 3], Block)>> = FxHashMap::default();
+
+        for (pos, block) in updates {
+            let Some(y) = pos.y.checked_sub(min_y).and_then(|y| usize::try_from(y).ok()) else {
+                continue
+            };
+
+            if y >= height {
+                continue;
+            }
+
+            let offsets = [pos.x.rem_euclid(16) as usize, y, pos.z.rem_euclid(16) as usize];
+
+            by_chunk
+                .entry(ChunkPos::from_block_pos(pos))
+                .or_default()
+                .push((offsets, block));
+        }
+
+        instance.par_chunks_mut().for_each(|(pos, chunk)| {
+            if let Some(updates) = by_chunk.get(&pos) {
+                for ([x, y, z], block) in updates {
+                    chunk.set_block(*x, *y, *z, block);
+                }
+            }
+        });
+    }
+}
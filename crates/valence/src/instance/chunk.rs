@@ -1,12 +1,15 @@
 use std::borrow::Cow;
 use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 // Using nonstandard mutex to avoid poisoning API.
+use bytes::Bytes;
 use parking_lot::Mutex;
 use valence_nbt::{compound, Compound};
-use valence_protocol::block::{BlockEntity, BlockState};
+use valence_protocol::block::{BlockEntity, BlockState, PropName, PropValue};
 use valence_protocol::packets::s2c::play::{
     BlockEntityData, BlockUpdate, ChunkDataAndUpdateLightEncode, UpdateSectionBlocksEncode,
 };
@@ -29,7 +32,7 @@ pub struct Chunk<const LOADED: bool = false> {
     sections: Vec<Section>,
     /// Cached bytes of the chunk data packet. The cache is considered
     /// invalidated if empty.
-    cached_init_packets: Mutex<Vec<u8>>,
+    cached_init_packets: Mutex<Bytes>,
     /// If clients should receive the chunk data packet instead of block change
     /// packets on update.
     refresh: bool,
@@ -39,6 +42,7 @@ pub struct Chunk<const LOADED: bool = false> {
     /// Block entities in this chunk
     block_entities: BTreeMap<u32, BlockEntity>,
     modified_block_entities: BTreeSet<u32>,
+    heightmaps: Heightmaps,
 }
 
 #[derive(Clone, Default, Debug)]
@@ -53,6 +57,27 @@ struct Section {
     section_updates: Vec<VarLong>,
 }
 
+/// The heights of the highest blocks in each column of a chunk, kept up to
+/// date as blocks are changed.
+#[derive(Clone, Debug)]
+struct Heightmaps {
+    /// For each column at index `x + z * 16`, one more than the Y offset of the
+    /// highest non-air block, or zero if the column is empty.
+    world_surface: [u16; 16 * 16],
+    /// Like `world_surface`, but for the highest block which blocks motion or
+    /// contains a fluid.
+    motion_blocking: [u16; 16 * 16],
+}
+
+impl Default for Heightmaps {
+    fn default() -> Self {
+        Self {
+            world_surface: [0; 16 * 16],
+            motion_blocking: [0; 16 * 16],
+        }
+    }
+}
+
 /// Represents a block with an optional block entity
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Block {
@@ -171,8 +196,10 @@ impl<'a> BlockMut<'a> {
     }
 }
 
-const SECTION_BLOCK_COUNT: usize = 16 * 16 * 16;
-const SECTION_BIOME_COUNT: usize = 4 * 4 * 4;
+/// The number of blocks in a chunk section.
+pub const SECTION_BLOCK_COUNT: usize = 16 * 16 * 16;
+/// The number of biomes in a chunk section.
+pub const SECTION_BIOME_COUNT: usize = 4 * 4 * 4;
 
 impl Chunk<false> {
     /// Constructs a new chunk containing only [`BlockState::AIR`] and
@@ -181,11 +208,12 @@ impl Chunk<false> {
     pub fn new(section_count: usize) -> Self {
         let mut chunk = Self {
             sections: vec![],
-            cached_init_packets: Mutex::new(vec![]),
+            cached_init_packets: Mutex::new(Bytes::new()),
             refresh: true,
             viewed: AtomicBool::new(false),
             block_entities: BTreeMap::new(),
             modified_block_entities: BTreeSet::new(),
+            heightmaps: Heightmaps::default(),
         };
 
         chunk.resize(section_count);
@@ -204,8 +232,9 @@ impl Chunk<false> {
                 .reserve_exact(new_section_count - old_section_count);
             self.sections
                 .resize_with(new_section_count, Section::default);
-        } else {
+        } else if new_section_count < old_section_count {
             self.sections.truncate(new_section_count);
+            self.recompute_heightmaps();
         }
     }
 
@@ -220,6 +249,7 @@ impl Chunk<false> {
             viewed: AtomicBool::new(false),
             block_entities: self.block_entities,
             modified_block_entities: self.modified_block_entities,
+            heightmaps: self.heightmaps,
         }
     }
 }
@@ -234,15 +264,92 @@ impl Clone for Chunk {
     fn clone(&self) -> Self {
         Self {
             sections: self.sections.clone(),
-            cached_init_packets: Mutex::new(vec![]),
+            cached_init_packets: Mutex::new(Bytes::new()),
             refresh: true,
             viewed: AtomicBool::new(false),
             block_entities: self.block_entities.clone(),
             modified_block_entities: BTreeSet::new(),
+            heightmaps: self.heightmaps.clone(),
+        }
+    }
+}
+
+/// Encodes the chunk data packets of chunks for an [`Instance`] ahead of time,
+/// e.g. on the threads chunks are generated or loaded on. Created with
+/// [`Instance::chunk_encoder`].
+///
+/// Inserting an [`EncodedChunk`] with [`Instance::insert_encoded_chunk`] does
+/// not encode or copy the chunk, and clients loading the chunk are sent the
+/// packet encoded beforehand.
+///
+/// [`Instance`]: super::Instance
+/// [`Instance::chunk_encoder`]: super::Instance::chunk_encoder
+/// [`Instance::insert_encoded_chunk`]: super::Instance::insert_encoded_chunk
+#[derive(Clone)]
+pub struct ChunkEncoder {
+    pub(super) info: Arc<InstanceInfo>,
+}
+
+impl ChunkEncoder {
+    /// Encodes the chunk data packet of `chunk` at `pos`. The chunk is resized
+    /// to the section count of the instance first.
+    pub fn encode(&self, pos: impl Into<ChunkPos>, mut chunk: Chunk) -> EncodedChunk {
+        let pos = pos.into();
+
+        chunk.resize(self.info.section_count);
+        let init_packets = chunk.encode_init_packets(&self.info, pos, &mut vec![]);
+
+        EncodedChunk {
+            pos,
+            chunk,
+            init_packets,
+            info: self.info.clone(),
         }
     }
 }
 
+/// A chunk and its chunk data packet encoded by a [`ChunkEncoder`]. The chunk
+/// can't be modified, since the packet would no longer match it.
+pub struct EncodedChunk {
+    pos: ChunkPos,
+    chunk: Chunk,
+    init_packets: Bytes,
+    info: Arc<InstanceInfo>,
+}
+
+impl EncodedChunk {
+    /// Returns the position the chunk was encoded for.
+    pub fn pos(&self) -> ChunkPos {
+        self.pos
+    }
+
+    pub fn chunk(&self) -> &Chunk {
+        &self.chunk
+    }
+
+    /// Returns the chunk, discarding the encoded packet.
+    pub fn into_chunk(self) -> Chunk {
+        self.chunk
+    }
+
+    /// Returns the position and the chunk, and the encoded packet if it was
+    /// encoded for the instance with `info`.
+    pub(super) fn into_parts(self, info: &Arc<InstanceInfo>) -> (ChunkPos, Chunk, Option<Bytes>) {
+        let init_packets = Arc::ptr_eq(&self.info, info).then_some(self.init_packets);
+        (self.pos, self.chunk, init_packets)
+    }
+}
+
+impl fmt::Debug for EncodedChunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncodedChunk")
+            .field("pos", &self.pos)
+            .field("chunk", &self.chunk)
+            .field("init_packets_len", &self.init_packets.len())
+            .finish()
+    }
+}
+
 impl Chunk<true> {
     /// Creates an unloaded clone of this loaded chunk.
     pub fn to_unloaded(&self) -> Chunk {
@@ -253,7 +360,7 @@ impl Chunk<true> {
                 Section {
                     block_states: sect.block_states.clone(),
                     biomes: sect.biomes.clone(),
-                    non_air_count: 0,
+                    non_air_count: sect.non_air_count,
                     section_updates: vec![], // Don't clone the section updates.
                 }
             })
@@ -261,11 +368,12 @@ impl Chunk<true> {
 
         Chunk {
             sections,
-            cached_init_packets: Mutex::new(vec![]),
+            cached_init_packets: Mutex::new(Bytes::new()),
             refresh: true,
             viewed: AtomicBool::new(false),
             block_entities: self.block_entities.clone(),
             modified_block_entities: BTreeSet::new(),
+            heightmaps: self.heightmaps.clone(),
         }
     }
 
@@ -304,6 +412,7 @@ impl Chunk<true> {
             viewed: AtomicBool::new(false),
             block_entities: self.block_entities,
             modified_block_entities: self.modified_block_entities,
+            heightmaps: self.heightmaps,
         }
     }
 
@@ -313,8 +422,9 @@ impl Chunk<true> {
         scratch: &mut Vec<u8>,
         pos: ChunkPos,
         info: &InstanceInfo,
+        resend: bool,
     ) {
-        if self.refresh {
+        if self.refresh || resend {
             self.write_init_packets(info, pos, writer, scratch)
         } else {
             for (sect_y, sect) in &mut self.sections.iter_mut().enumerate() {
@@ -378,76 +488,17 @@ impl Chunk<true> {
         let mut lck = self.cached_init_packets.lock();
 
         if lck.is_empty() {
-            scratch.clear();
-
-            for sect in &self.sections {
-                sect.non_air_count.encode(&mut *scratch).unwrap();
-
-                sect.block_states
-                    .encode_mc_format(
-                        &mut *scratch,
-                        |b| b.to_raw().into(),
-                        4,
-                        8,
-                        bit_width(BlockState::max_raw().into()),
-                    )
-                    .expect("failed to encode block paletted container");
-
-                sect.biomes
-                    .encode_mc_format(
-                        &mut *scratch,
-                        |b| b.0.into(),
-                        0,
-                        3,
-                        bit_width(info.biome_registry_len - 1),
-                    )
-                    .expect("failed to encode biome paletted container");
-            }
-
-            let mut compression_scratch = vec![];
-
-            let mut writer = PacketWriter::new(
-                &mut lck,
-                info.compression_threshold,
-                &mut compression_scratch,
-            );
-
-            let block_entities: Vec<_> = self
-                .block_entities
-                .iter()
-                .map(|(idx, block_entity)| {
-                    let x = idx % 16;
-                    let z = idx / 16 % 16;
-                    let y = (idx / 16 / 16) as i16 + info.min_y as i16;
-
-                    ChunkDataBlockEntity {
-                        packed_xz: ((x << 4) | z) as i8,
-                        y,
-                        kind: block_entity.kind,
-                        data: Cow::Borrowed(&block_entity.nbt),
-                    }
-                })
-                .collect();
-
-            writer.write_packet(&ChunkDataAndUpdateLightEncode {
-                chunk_x: pos.x,
-                chunk_z: pos.z,
-                heightmaps: &compound! {
-                    // TODO: MOTION_BLOCKING heightmap
-                },
-                blocks_and_biomes: scratch,
-                block_entities: &block_entities,
-                trust_edges: true,
-                sky_light_mask: &info.filler_sky_light_mask,
-                block_light_mask: &[],
-                empty_sky_light_mask: &[],
-                empty_block_light_mask: &[],
-                sky_light_arrays: &info.filler_sky_light_arrays,
-                block_light_arrays: &[],
-            });
+            // Clients loading this chunk share the bytes from now on.
+            *lck = self.encode_init_packets(info, pos, scratch);
         }
 
-        writer.write_packet_bytes(&lck);
+        writer.write_shared_packet_bytes(&lck);
+    }
+
+    /// Sets the chunk data packet of a chunk which was encoded with a
+    /// [`ChunkEncoder`] before it was inserted.
+    pub(super) fn set_init_packets(&mut self, bytes: Bytes) {
+        *self.cached_init_packets.get_mut() = bytes;
     }
 
     pub(super) fn update_post_client(&mut self) {
@@ -458,15 +509,247 @@ impl Chunk<true> {
         }
         self.modified_block_entities.clear();
     }
+
+    /// Returns the number of individual block changes made to this chunk
+    /// since the last tick.
+    pub(crate) fn block_update_count(&self) -> usize {
+        self.sections.iter().map(|s| s.section_updates.len()).sum()
+    }
+
+    /// Returns the offsets of the blocks changed in this chunk since the last
+    /// tick. Changes made while the chunk is being refreshed are not included.
+    pub(crate) fn block_updates(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        self.sections.iter().enumerate().flat_map(|(sect_y, sect)| {
+            sect.section_updates.iter().map(move |update| {
+                let packed = update.0 as u64;
+                let offset_y = (packed & 0b1111) as usize;
+                let offset_z = ((packed >> 4) & 0b1111) as usize;
+                let offset_x = ((packed >> 8) & 0b1111) as usize;
+
+                [offset_x, sect_y * 16 + offset_y, offset_z]
+            })
+        })
+    }
 }
 
 impl<const LOADED: bool> Chunk<LOADED> {
+    /// Encodes the chunk data packet for this chunk with the given position.
+    fn encode_init_packets(
+        &self,
+        info: &InstanceInfo,
+        pos: ChunkPos,
+        scratch: &mut Vec<u8>,
+    ) -> Bytes {
+        scratch.clear();
+
+        for sect in &self.sections {
+            sect.non_air_count.encode(&mut *scratch).unwrap();
+
+            sect.block_states
+                .encode_mc_format(
+                    &mut *scratch,
+                    |b| b.to_raw().into(),
+                    4,
+                    8,
+                    bit_width(BlockState::max_raw().into()),
+                )
+                .expect("failed to encode block paletted container");
+
+            sect.biomes
+                .encode_mc_format(
+                    &mut *scratch,
+                    |b| b.0.into(),
+                    0,
+                    3,
+                    bit_width(info.biome_registry_len - 1),
+                )
+                .expect("failed to encode biome paletted container");
+        }
+
+        let mut buf = vec![];
+        let mut compression_scratch = vec![];
+
+        let mut writer = PacketWriter::new(
+            &mut buf,
+            info.compression_threshold,
+            &mut compression_scratch,
+        )
+        .with_compression_level(info.compression_level);
+
+        let block_entities: Vec<_> = self
+            .block_entities
+            .iter()
+            .map(|(idx, block_entity)| {
+                let x = idx % 16;
+                let z = idx / 16 % 16;
+                let y = (idx / 16 / 16) as i16 + info.min_y as i16;
+
+                ChunkDataBlockEntity {
+                    packed_xz: ((x << 4) | z) as i8,
+                    y,
+                    kind: block_entity.kind,
+                    data: Cow::Borrowed(&block_entity.nbt),
+                }
+            })
+            .collect();
+
+        writer.write_packet(&ChunkDataAndUpdateLightEncode {
+            chunk_x: pos.x,
+            chunk_z: pos.z,
+            heightmaps: &compound! {
+                "MOTION_BLOCKING" => self.encode_heightmap(&self.heightmaps.motion_blocking),
+                "WORLD_SURFACE" => self.encode_heightmap(&self.heightmaps.world_surface),
+            },
+            blocks_and_biomes: scratch,
+            block_entities: &block_entities,
+            trust_edges: true,
+            sky_light_mask: &info.filler_sky_light_mask,
+            block_light_mask: &[],
+            empty_sky_light_mask: &[],
+            empty_block_light_mask: &[],
+            sky_light_arrays: &info.filler_sky_light_arrays,
+            block_light_arrays: &[],
+        });
+
+        // Converting the buffer does not copy it.
+        buf.into()
+    }
+
     /// Returns the number of sections in this chunk. To get the height of the
     /// chunk in meters, multiply the result by 16.
     pub fn section_count(&self) -> usize {
         self.sections.len()
     }
 
+    /// Returns `true` if the section at `sect_y` contains only air.
+    pub(crate) fn section_is_empty(&self, sect_y: usize) -> bool {
+        self.sections[sect_y].non_air_count == 0
+    }
+
+    /// Returns the Y offset of the highest non-air block in the column at
+    /// `(x, z)`, or `None` if the column only contains air.
+    ///
+    /// **Note**: The arguments and result of this function are offsets from
+    /// the minimum corner of the chunk in _chunk space_ rather than _world
+    /// space_.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` or `z` is not less than 16.
+    #[track_caller]
+    pub fn top_block_y(&self, x: usize, z: usize) -> Option<usize> {
+        assert!(
+            x < 16 && z < 16,
+            "chunk column offsets of ({x}, {z}) are out of bounds"
+        );
+
+        (self.heightmaps.world_surface[x + z * 16] as usize).checked_sub(1)
+    }
+
+    /// Like [`Self::top_block_y`], but returns the highest block which blocks
+    /// motion or contains a fluid. Standing on top of this block is safe from
+    /// falling, which is useful for teleporting players to the surface.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` or `z` is not less than 16.
+    #[track_caller]
+    pub fn motion_blocking_y(&self, x: usize, z: usize) -> Option<usize> {
+        assert!(
+            x < 16 && z < 16,
+            "chunk column offsets of ({x}, {z}) are out of bounds"
+        );
+
+        (self.heightmaps.motion_blocking[x + z * 16] as usize).checked_sub(1)
+    }
+
+    /// Updates the heightmaps after the block at the offsets was set to
+    /// `block`.
+    fn update_heightmaps(&mut self, x: usize, y: usize, z: usize, block: BlockState) {
+        let idx = x + z * 16;
+        let height = y as u16 + 1;
+
+        let world_surface = self.heightmaps.world_surface[idx];
+        if !block.is_air() {
+            self.heightmaps.world_surface[idx] = world_surface.max(height);
+        } else if world_surface == height {
+            // The highest block was removed.
+            self.heightmaps.world_surface[idx] = self.column_height(x, z, y, |b| !b.is_air());
+        }
+
+        let motion_blocking = self.heightmaps.motion_blocking[idx];
+        if is_motion_blocking(block) {
+            self.heightmaps.motion_blocking[idx] = motion_blocking.max(height);
+        } else if motion_blocking == height {
+            self.heightmaps.motion_blocking[idx] = self.column_height(x, z, y, is_motion_blocking);
+        }
+    }
+
+    /// Recomputes the heightmaps from scratch after many blocks were changed.
+    fn recompute_heightmaps(&mut self) {
+        let top = self.section_count() * 16;
+
+        for z in 0..16 {
+            for x in 0..16 {
+                let idx = x + z * 16;
+                let world_surface = self.column_height(x, z, top, |b| !b.is_air());
+
+                // Motion blocking blocks are never air, so they can't be higher.
+                self.heightmaps.motion_blocking[idx] =
+                    self.column_height(x, z, world_surface as usize, is_motion_blocking);
+                self.heightmaps.world_surface[idx] = world_surface;
+            }
+        }
+    }
+
+    /// Returns one more than the Y offset of the highest block below `top` in
+    /// the column at `(x, z)` matching `f`, or zero if there is none.
+    fn column_height(
+        &self,
+        x: usize,
+        z: usize,
+        top: usize,
+        mut f: impl FnMut(BlockState) -> bool,
+    ) -> u16 {
+        let mut y = top;
+
+        while y > 0 {
+            let sect = &self.sections[(y - 1) / 16];
+
+            if sect.non_air_count == 0 {
+                // Skip to the top of the section below.
+                y = (y - 1) / 16 * 16;
+                continue;
+            }
+
+            if f(sect.block_states.get(x + z * 16 + (y - 1) % 16 * 16 * 16)) {
+                return y as u16;
+            }
+
+            y -= 1;
+        }
+
+        0
+    }
+
+    /// Packs a heightmap into longs like the vanilla client expects, with each
+    /// height using just enough bits to store every height in the chunk.
+    fn encode_heightmap(&self, heights: &[u16; 16 * 16]) -> Vec<i64> {
+        let bits = bit_width(self.section_count() * 16);
+        let per_long = 64 / bits;
+
+        heights
+            .chunks(per_long)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0_u64, |acc, (i, &h)| acc | (h as u64) << (i * bits))
+                    as i64
+            })
+            .collect()
+    }
+
     /// Gets the block state at the provided offsets in the chunk.
     ///
     /// **Note**: The arguments to this function are offsets from the minimum
@@ -526,11 +809,17 @@ impl<const LOADED: bool> Chunk<LOADED> {
                 _ => {}
             }
 
-            if LOADED && !self.refresh {
+            if LOADED {
                 self.cached_init_packets.get_mut().clear();
-                let compact = (block.to_raw() as i64) << 12 | (x << 8 | z << 4 | (y % 16)) as i64;
-                sect.section_updates.push(VarLong(compact));
+
+                if !self.refresh {
+                    let compact =
+                        (block.to_raw() as i64) << 12 | (x << 8 | z << 4 | (y % 16)) as i64;
+                    sect.section_updates.push(VarLong(compact));
+                }
             }
+
+            self.update_heightmaps(x, y, z, block);
         }
 
         old_block
@@ -589,6 +878,9 @@ impl<const LOADED: bool> Chunk<LOADED> {
                     }
                 }
             }
+        } else if LOADED {
+            // The chunk data packet might have been encoded ahead of time.
+            self.cached_init_packets.get_mut().clear();
         }
 
         if !block.is_air() {
@@ -598,6 +890,159 @@ impl<const LOADED: bool> Chunk<LOADED> {
         }
 
         sect.block_states.fill(block);
+
+        self.recompute_heightmaps();
+    }
+
+    /// Copies every block state in the section at `sect_y` to `states`. The
+    /// block at offsets `(x, y, z)` in the section is at index
+    /// `x + z * 16 + y * 16 * 16`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sect_y` is out of bounds. `sect_y` must be less than the
+    /// section count.
+    #[track_caller]
+    pub fn section_block_states(
+        &self,
+        sect_y: usize,
+        states: &mut [BlockState; SECTION_BLOCK_COUNT],
+    ) {
+        let sect = self.section(sect_y);
+
+        for (idx, state) in states.iter_mut().enumerate() {
+            *state = sect.block_states.get(idx);
+        }
+    }
+
+    /// Returns the palette of the section at `sect_y`, which contains every
+    /// block state in the section. The palette may also contain block states
+    /// which are no longer in the section until [`Self::optimize`] is called.
+    ///
+    /// Returns `None` if the section contains too many distinct block states
+    /// to use a palette.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sect_y` is out of bounds. `sect_y` must be less than the
+    /// section count.
+    #[track_caller]
+    pub fn section_palette(&self, sect_y: usize) -> Option<&[BlockState]> {
+        self.section(sect_y).block_states.palette()
+    }
+
+    /// Sets every block state in the section at `sect_y` from `states`, which
+    /// is indexed like in [`Self::section_block_states`].
+    ///
+    /// This is semantically equivalent to setting every block in the section
+    /// with [`set_block_state`], but faster.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sect_y` is out of bounds. `sect_y` must be less than the
+    /// section count.
+    ///
+    /// [`set_block_state`]: Self::set_block_state
+    #[track_caller]
+    pub fn set_section_block_states(
+        &mut self,
+        sect_y: usize,
+        states: &[BlockState; SECTION_BLOCK_COUNT],
+    ) {
+        let mut block_states = PalettedContainer::new();
+        block_states.fill(states[0]);
+
+        for (idx, &state) in states.iter().enumerate().skip(1) {
+            block_states.set(idx, state);
+        }
+
+        self.replace_block_states(sect_y, block_states);
+    }
+
+    /// Copies the block states and biomes of the section at `src_sect_y` in
+    /// `src` to the section at `sect_y` in this chunk. Block entities are not
+    /// copied.
+    ///
+    /// The section's data is copied as a whole, which is much faster than
+    /// copying individual blocks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sect_y` or `src_sect_y` is out of bounds.
+    #[track_caller]
+    pub fn copy_section_from<const SRC_LOADED: bool>(
+        &mut self,
+        sect_y: usize,
+        src: &Chunk<SRC_LOADED>,
+        src_sect_y: usize,
+    ) {
+        let src_sect = src.section(src_sect_y);
+        let biomes = src_sect.biomes.clone();
+
+        self.replace_block_states(sect_y, src_sect.block_states.clone());
+
+        self.sections[sect_y].biomes = biomes;
+        self.cached_init_packets.get_mut().clear();
+        self.refresh = true;
+    }
+
+    #[track_caller]
+    fn section(&self, sect_y: usize) -> &Section {
+        let Some(sect) = self.sections.get(sect_y) else {
+            panic!(
+                "section index {sect_y} out of bounds for chunk with {} sections",
+                self.section_count()
+            )
+        };
+
+        sect
+    }
+
+    /// Replaces the block states of a section while keeping the non-air count
+    /// and section updates accurate.
+    #[track_caller]
+    fn replace_block_states(
+        &mut self,
+        sect_y: usize,
+        block_states: PalettedContainer<
+            BlockState,
+            SECTION_BLOCK_COUNT,
+            { SECTION_BLOCK_COUNT / 2 },
+        >,
+    ) {
+        let track_changes = LOADED && !self.refresh;
+
+        let Some(sect) = self.sections.get_mut(sect_y) else {
+            panic!(
+                "section index {sect_y} out of bounds for chunk with {} sections",
+                self.section_count()
+            )
+        };
+
+        let mut non_air_count = 0;
+
+        for idx in 0..SECTION_BLOCK_COUNT {
+            let block = block_states.get(idx);
+
+            if !block.is_air() {
+                non_air_count += 1;
+            }
+
+            if track_changes && block != sect.block_states.get(idx) {
+                let (x, z, y) = (idx % 16, idx / 16 % 16, idx / (16 * 16));
+                let compact = (block.to_raw() as i64) << 12 | (x << 8 | z << 4 | y) as i64;
+                sect.section_updates.push(VarLong(compact));
+            }
+        }
+
+        if LOADED && (self.refresh || !sect.section_updates.is_empty()) {
+            self.cached_init_packets.get_mut().clear();
+        }
+
+        sect.non_air_count = non_air_count;
+        sect.block_states = block_states;
+
+        self.recompute_heightmaps();
     }
 
     /// Gets a reference to the block entity at the provided offsets in the
@@ -645,8 +1090,10 @@ impl<const LOADED: bool> Chunk<LOADED> {
         );
         let idx = (x + z * 16 + y * 16 * 16) as _;
         let old = self.block_entities.insert(idx, block_entity);
-        if LOADED && !self.refresh {
-            self.modified_block_entities.insert(idx);
+        if LOADED {
+            if !self.refresh {
+                self.modified_block_entities.insert(idx);
+            }
             self.cached_init_packets.get_mut().clear();
         }
         old
@@ -671,13 +1118,24 @@ impl<const LOADED: bool> Chunk<LOADED> {
         let idx = (x + z * 16 + y * 16 * 16) as _;
 
         let res = self.block_entities.get_mut(&idx);
-        if LOADED && res.is_some() && !self.refresh {
-            self.modified_block_entities.insert(idx);
+        if LOADED && res.is_some() {
+            if !self.refresh {
+                self.modified_block_entities.insert(idx);
+            }
             self.cached_init_packets.get_mut().clear();
         }
         self.block_entities.get_mut(&idx)
     }
 
+    /// Returns an iterator over all block entities in the chunk and their
+    /// offsets in the chunk.
+    pub fn block_entities(&self) -> impl Iterator<Item = ([usize; 3], &BlockEntity)> + '_ {
+        self.block_entities.iter().map(|(&idx, be)| {
+            let idx = idx as usize;
+            ([idx % 16, idx / (16 * 16), idx / 16 % 16], be)
+        })
+    }
+
     /// Sets the block at the provided offsets in the chunk. The previous
     /// block at the position is returned.
     ///
@@ -720,6 +1178,10 @@ impl<const LOADED: bool> Chunk<LOADED> {
             old_state
         };
 
+        if state != old_state {
+            self.update_heightmaps(x, y, z, state);
+        }
+
         let idx = (x + z * 16 + y * 16 * 16) as _;
         let old_block_entity = match nbt.and_then(|nbt| {
             state
@@ -729,8 +1191,10 @@ impl<const LOADED: bool> Chunk<LOADED> {
             Some(block_entity) => self.block_entities.insert(idx, block_entity),
             None => self.block_entities.remove(&idx),
         };
-        if LOADED && !self.refresh {
-            self.modified_block_entities.insert(idx);
+        if LOADED {
+            if !self.refresh {
+                self.modified_block_entities.insert(idx);
+            }
             self.cached_init_packets.get_mut().clear();
         }
 
@@ -871,11 +1335,16 @@ impl<const LOADED: bool> Chunk<LOADED> {
             )
         };
 
+        if matches!(sect.biomes, PalettedContainer::Single(b) if b == biome) {
+            return;
+        }
+
         sect.biomes.fill(biome);
 
-        // TODO: this is set unconditionally, but it doesn't have to be.
-        self.cached_init_packets.get_mut().clear();
-        self.refresh = true;
+        if LOADED {
+            self.cached_init_packets.get_mut().clear();
+            self.refresh = true;
+        }
     }
 
     /// Optimizes this chunk to use the minimum amount of memory possible. It
@@ -886,7 +1355,7 @@ impl<const LOADED: bool> Chunk<LOADED> {
     /// via [`Self::set_block_state`] and [`Self::set_biome`].
     pub fn optimize(&mut self) {
         self.sections.shrink_to_fit();
-        self.cached_init_packets.get_mut().shrink_to_fit();
+        *self.cached_init_packets.get_mut() = Bytes::new();
 
         for sect in &mut self.sections {
             sect.section_updates.shrink_to_fit();
@@ -896,12 +1365,23 @@ impl<const LOADED: bool> Chunk<LOADED> {
     }
 }
 
+/// Returns if the block is in the `MOTION_BLOCKING` heightmap.
+fn is_motion_blocking(block: BlockState) -> bool {
+    block.collision_shapes().len() != 0
+        || block.is_liquid()
+        || block.get(PropName::Waterlogged) == Some(PropValue::True)
+}
+
 #[cfg(test)]
 mod tests {
+    use bevy_app::App;
     use valence_protocol::block::BlockEntityKind;
 
     use super::*;
+    use crate::config::ServerPlugin;
+    use crate::dimension::DimensionId;
     use crate::protocol::block::BlockState;
+    use crate::server::Server;
 
     fn check<const LOADED: bool>(chunk: &Chunk<LOADED>, total_expected_change_count: usize) {
         assert!(!chunk.refresh, "chunk should not be refreshed for the test");
@@ -944,6 +1424,61 @@ mod tests {
         check(&chunk, 6);
     }
 
+    #[test]
+    fn heightmaps() {
+        let mut chunk = Chunk::new(4);
+
+        assert_eq!(chunk.top_block_y(3, 5), None);
+
+        chunk.set_block_state(3, 20, 5, BlockState::STONE);
+        chunk.set_block_state(3, 40, 5, BlockState::GLASS_PANE);
+        chunk.set_block_state(3, 50, 5, BlockState::TORCH);
+        assert_eq!(chunk.top_block_y(3, 5), Some(50));
+        assert_eq!(chunk.motion_blocking_y(3, 5), Some(40));
+        assert_eq!(chunk.top_block_y(5, 3), None);
+
+        chunk.set_block_state(3, 50, 5, BlockState::AIR);
+        chunk.set_block(3, 40, 5, BlockState::AIR);
+        assert_eq!(chunk.top_block_y(3, 5), Some(20));
+        assert_eq!(chunk.motion_blocking_y(3, 5), Some(20));
+
+        chunk.fill_block_states(3, BlockState::WATER);
+        assert_eq!(chunk.top_block_y(0, 0), Some(63));
+        assert_eq!(chunk.motion_blocking_y(15, 15), Some(63));
+
+        chunk.resize(2);
+        assert_eq!(chunk.top_block_y(3, 5), Some(20));
+        assert_eq!(chunk.top_block_y(0, 0), None);
+
+        let chunk = chunk.into_loaded();
+        assert_eq!(chunk.to_unloaded().motion_blocking_y(3, 5), Some(20));
+    }
+
+    #[test]
+    fn section_block_state_changes() {
+        let mut chunk = Chunk::new(2).into_loaded();
+        chunk.refresh = false;
+
+        let mut states = [BlockState::AIR; SECTION_BLOCK_COUNT];
+        states[0] = BlockState::STONE;
+        states[SECTION_BLOCK_COUNT - 1] = BlockState::DIRT;
+
+        chunk.set_section_block_states(1, &states);
+        check(&chunk, 2);
+        assert_eq!(chunk.block_state(15, 31, 15), BlockState::DIRT);
+        assert_eq!(
+            chunk.section_palette(1),
+            Some([BlockState::STONE, BlockState::AIR, BlockState::DIRT].as_slice())
+        );
+
+        chunk.set_section_block_states(1, &states);
+        check(&chunk, 2);
+
+        let mut read = [BlockState::AIR; SECTION_BLOCK_COUNT];
+        chunk.section_block_states(1, &mut read);
+        assert_eq!(read, states);
+    }
+
     #[test]
     fn block_entity_changes() {
         let mut chunk = Chunk::new(5).into_loaded();
@@ -961,4 +1496,36 @@ mod tests {
         chunk.set_block(0, 0, 0, BlockState::STONE);
         assert!(chunk.block_entity(0, 0, 0).is_none());
     }
+
+    #[test]
+    fn insert_encoded_chunk() {
+        let mut app = App::new();
+        app.add_plugin(ServerPlugin::new(()).with_listen(false));
+        let mut instance = app
+            .world
+            .resource::<Server>()
+            .new_instance(DimensionId::default());
+
+        let encoder = instance.chunk_encoder();
+        let encoded = std::thread::spawn(move || {
+            let mut chunk = Chunk::default();
+            chunk.resize(1);
+            chunk.set_block_state(0, 0, 0, BlockState::STONE);
+            encoder.encode([1, 2], chunk)
+        })
+        .join()
+        .unwrap();
+
+        let init_packets = encoded.init_packets.clone();
+        assert_eq!(encoded.chunk().section_count(), instance.section_count());
+        assert!(instance.insert_encoded_chunk(encoded).is_none());
+
+        let chunk = instance.chunk_mut([1, 2]).unwrap();
+        assert_eq!(chunk.block_state(0, 0, 0), BlockState::STONE);
+        assert_eq!(*chunk.cached_init_packets.get_mut(), init_packets);
+
+        // Modifying the chunk before it is sent discards the encoded packet.
+        chunk.set_block_state(0, 0, 0, BlockState::DIRT);
+        assert!(chunk.cached_init_packets.get_mut().is_empty());
+    }
 }
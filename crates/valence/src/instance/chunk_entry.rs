@@ -100,10 +100,13 @@ impl<'a> VacantChunkEntry<'a> {
         let cell = self.entry.or_insert_with(|| PartitionCell {
             chunk: None,
             chunk_removed: false,
+            resend_chunk: false,
             entities: BTreeSet::new(),
             incoming: vec![],
             outgoing: vec![],
             packet_buf: vec![],
+            packet_bytes: Bytes::new(),
+            entity_update_ranges: vec![],
         });
 
         debug_assert!(cell.chunk.is_none());
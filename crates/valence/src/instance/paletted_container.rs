@@ -85,6 +85,15 @@ impl<T: Copy + Eq + Default, const LEN: usize, const HALF_LEN: usize>
         }
     }
 
+    /// Returns every distinct element if the container uses a palette.
+    pub fn palette(&self) -> Option<&[T]> {
+        match self {
+            Self::Single(elem) => Some(std::slice::from_ref(elem)),
+            Self::Indirect(ind) => Some(ind.palette.as_slice()),
+            Self::Direct(_) => None,
+        }
+    }
+
     pub fn optimize(&mut self) {
         match self {
             Self::Single(_) => {}
@@ -0,0 +1,252 @@
+//! Lookup of instances by name.
+//!
+//! Instance entities can be given an [`InstanceName`] so that systems and
+//! configuration files can refer to worlds like `"lobby"` or `"arena_3"`
+//! instead of passing [`Entity`] IDs around. The [`InstanceNamePlugin`] keeps
+//! the [`InstanceRegistry`] up to date and sends [`InstanceRegistered`] and
+//! [`InstanceUnregistered`] events when names are added, changed, or removed.
+//!
+//! ```
+//! use valence::instance_name::{InstanceName, InstanceRegistry};
+//! use valence::prelude::*;
+//!
+//! fn setup(mut commands: Commands, server: Res<Server>) {
+//!     let instance = server.new_instance(DimensionId::default());
+//!     commands.spawn((instance, InstanceName::new("lobby")));
+//! }
+//!
+//! fn send_to_lobby(registry: Res<InstanceRegistry>, mut clients: Query<&mut Client>) {
+//!     let Some(lobby) = registry.get("lobby") else {
+//!         return
+//!     };
+//!
+//!     for mut client in &mut clients {
+//!         client.set_instance(lobby);
+//!     }
+//! }
+//! ```
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashMap;
+use tracing::warn;
+
+use crate::instance::Instance;
+use crate::Despawned;
+
+/// Adds the [`InstanceRegistry`] resource, the registration events, and the
+/// system registering [`InstanceName`]s.
+pub struct InstanceNamePlugin;
+
+impl Plugin for InstanceNamePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InstanceRegistry>()
+            .add_event::<InstanceRegistered>()
+            .add_event::<InstanceUnregistered>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_instance_registry.before("valence_core"),
+            );
+    }
+}
+
+/// A component for instance entities registering the instance under a unique
+/// name in the [`InstanceRegistry`].
+///
+/// If two instances have the same name, the instance registered first keeps
+/// the name and the other one is not registered. Once the name is free again,
+/// one of the remaining instances with the name is registered under it.
+#[derive(Component, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct InstanceName(Box<str>);
+
+impl InstanceName {
+    pub fn new(name: impl Into<Box<str>>) -> Self {
+        Self(name.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The instances with an [`InstanceName`], keyed by name.
+///
+/// The registry is updated at the end of each tick, so names given in the
+/// current tick are not registered yet.
+#[derive(Resource, Default, Debug)]
+pub struct InstanceRegistry {
+    by_name: FxHashMap<Box<str>, Entity>,
+    by_instance: FxHashMap<Entity, Box<str>>,
+}
+
+impl InstanceRegistry {
+    /// Gets the instance registered under `name`.
+    pub fn get(&self, name: &str) -> Option<Entity> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Gets the name `instance` is registered under.
+    pub fn name_of(&self, instance: Entity) -> Option<&str> {
+        self.by_instance.get(&instance).map(|name| &**name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.by_name.contains_key(name)
+    }
+
+    /// Returns an iterator over the names and instances in the registry in
+    /// no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Entity)> + '_ {
+        self.by_name
+            .iter()
+            .map(|(name, &instance)| (&**name, instance))
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    fn register(&mut self, name: &str, instance: Entity) -> bool {
+        if self.by_name.contains_key(name) {
+            return false;
+        }
+
+        self.by_name.insert(name.into(), instance);
+        self.by_instance.insert(instance, name.into());
+        true
+    }
+
+    fn unregister(&mut self, instance: Entity) -> Option<Box<str>> {
+        let name = self.by_instance.remove(&instance)?;
+        self.by_name.remove(&name);
+        Some(name)
+    }
+}
+
+/// Sent when an instance is registered under a name.
+#[derive(Clone, Debug)]
+pub struct InstanceRegistered {
+    pub instance: Entity,
+    pub name: Box<str>,
+}
+
+/// Sent when the name of an instance is unregistered because the instance was
+/// despawned or its [`InstanceName`] was changed or removed.
+#[derive(Clone, Debug)]
+pub struct InstanceUnregistered {
+    pub instance: Entity,
+    pub name: Box<str>,
+}
+
+fn update_instance_registry(
+    mut registry: ResMut<InstanceRegistry>,
+    named: Query<(Entity, &InstanceName), (Changed<InstanceName>, With<Instance>)>,
+    all_named: Query<(Entity, &InstanceName), (With<Instance>, Without<Despawned>)>,
+    despawned: Query<Entity, (With<InstanceName>, With<Despawned>)>,
+    removed: RemovedComponents<InstanceName>,
+    mut registered: EventWriter<InstanceRegistered>,
+    mut unregistered: EventWriter<InstanceUnregistered>,
+) {
+    // Names which were unregistered this tick and might be held by other
+    // instances.
+    let mut freed = vec![];
+
+    let mut unregister = |registry: &mut InstanceRegistry, instance| {
+        if let Some(name) = registry.unregister(instance) {
+            freed.push(name.clone());
+            unregistered.send(InstanceUnregistered { instance, name });
+        }
+    };
+
+    for instance in removed.iter().chain(&despawned) {
+        unregister(&mut registry, instance);
+    }
+
+    for (instance, name) in &named {
+        if registry.name_of(instance) == Some(name.as_str()) {
+            continue;
+        }
+
+        unregister(&mut registry, instance);
+
+        if registry.register(name.as_str(), instance) {
+            registered.send(InstanceRegistered {
+                instance,
+                name: name.0.clone(),
+            });
+        } else {
+            warn!(
+                instance = ?instance,
+                "instance name \"{}\" is already taken",
+                name.as_str()
+            );
+        }
+    }
+
+    for name in freed {
+        if registry.contains(&name) {
+            continue;
+        }
+
+        let holder = all_named.iter().find(|&(instance, other)| {
+            other.as_str() == &*name && registry.name_of(instance).is_none()
+        });
+
+        if let Some((instance, _)) = holder {
+            registry.register(&name, instance);
+            registered.send(InstanceRegistered { instance, name });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerPlugin;
+    use crate::dimension::DimensionId;
+    use crate::server::Server;
+
+    #[test]
+    fn instance_registry() {
+        let mut app = App::new();
+        app.add_plugin(ServerPlugin::new(()).with_listen(false))
+            .add_plugin(InstanceNamePlugin);
+
+        let server = app.world.resource::<Server>();
+        let lobby = server.new_instance(DimensionId::default());
+        let other = server.new_instance(DimensionId::default());
+
+        let lobby = app.world.spawn((lobby, InstanceName::new("lobby"))).id();
+        let other = app.world.spawn((other, InstanceName::new("lobby"))).id();
+
+        app.update();
+
+        let registry = app.world.resource::<InstanceRegistry>();
+        assert_eq!(registry.len(), 1);
+        let first = registry.get("lobby").unwrap();
+        assert!(first == lobby || first == other);
+
+        app.world
+            .entity_mut(first)
+            .insert(InstanceName::new("arena"));
+        app.update();
+
+        // The other instance takes over the name once it is free.
+        let second = if first == lobby { other } else { lobby };
+
+        let registry = app.world.resource::<InstanceRegistry>();
+        assert_eq!(registry.get("arena"), Some(first));
+        assert_eq!(registry.get("lobby"), Some(second));
+
+        app.world.entity_mut(first).insert(Despawned);
+        app.world.entity_mut(second).insert(Despawned);
+        app.update();
+
+        let registry = app.world.resource::<InstanceRegistry>();
+        assert!(registry.is_empty());
+    }
+}
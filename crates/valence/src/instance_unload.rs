@@ -0,0 +1,184 @@
+//! Unloading instances while the server is running.
+//!
+//! Marking an instance with [`Despawned`] directly leaves clients in a
+//! nonexistent instance, which disconnects them, and leaves the entities of
+//! the instance behind. Inserting the [`UnloadInstance`] component instead
+//! unloads the instance over two ticks with the [`InstanceUnloadPlugin`]:
+//!
+//! 1. An [`InstanceUnloading`] event is sent and the clients in the instance
+//!    are transferred to another instance or kicked. The chunks are still
+//!    loaded during the following tick, so systems saving the instance should
+//!    do so when they receive the event.
+//! 2. The remaining clients are transferred or kicked, the entities of the
+//!    instance are despawned, the chunks are released, and the instance is
+//!    despawned. An [`InstanceUnloaded`] event is sent.
+//!
+//! ```
+//! use valence::instance_unload::UnloadInstance;
+//! use valence::prelude::*;
+//!
+//! fn close_arena(mut commands: Commands, arena: Entity, lobby: Entity) {
+//!     commands.entity(arena).insert(UnloadInstance::Transfer {
+//!         instance: lobby,
+//!         position: [0.0, 64.0, 0.0].into(),
+//!     });
+//! }
+//! ```
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use rustc_hash::{FxHashMap, FxHashSet};
+use valence_protocol::Text;
+
+use crate::client::Client;
+use crate::entity::McEntity;
+use crate::instance::Instance;
+use crate::Despawned;
+
+/// Adds the unloading events and the system unloading instances with the
+/// [`UnloadInstance`] component.
+pub struct InstanceUnloadPlugin;
+
+impl Plugin for InstanceUnloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<InstanceUnloading>()
+            .add_event::<InstanceUnloaded>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                unload_instances.before("valence_core"),
+            );
+    }
+}
+
+/// A component for instance entities which unloads the instance. Determines
+/// what happens to the clients in the instance.
+#[derive(Component, Clone, Debug)]
+pub enum UnloadInstance {
+    /// Moves the clients to another instance at the given position with
+    /// [`Client::transfer_to_instance`]. The instance must not be unloaded
+    /// itself.
+    Transfer { instance: Entity, position: DVec3 },
+    /// Kicks the clients with the given reason.
+    Kick { reason: Text },
+}
+
+/// Sent when an instance starts unloading. The instance and its chunks are
+/// available until the end of the next tick.
+#[derive(Clone, Debug)]
+pub struct InstanceUnloading {
+    pub instance: Entity,
+}
+
+/// Sent when an instance has been unloaded. The instance entity is despawned
+/// at the end of the next tick.
+#[derive(Clone, Debug)]
+pub struct InstanceUnloaded {
+    pub instance: Entity,
+}
+
+/// Marks instances which started unloading in a previous tick.
+#[derive(Component)]
+struct Unloading;
+
+fn unload_instances(
+    mut commands: Commands,
+    mut instances: Query<
+        (Entity, &UnloadInstance, &mut Instance, Option<&Unloading>),
+        Without<Despawned>,
+    >,
+    mut clients: Query<(&mut Client, Option<&mut McEntity>)>,
+    entities: Query<(Entity, &McEntity), Without<Client>>,
+    mut unloading_events: EventWriter<InstanceUnloading>,
+    mut unloaded_events: EventWriter<InstanceUnloaded>,
+) {
+    let mut unloading = FxHashMap::default();
+    let mut unloaded = FxHashSet::default();
+
+    for (id, unload, mut instance, is_unloading) in &mut instances {
+        unloading.insert(id, unload.clone());
+
+        if is_unloading.is_none() {
+            commands.entity(id).insert(Unloading);
+            unloading_events.send(InstanceUnloading { instance: id });
+        } else {
+            instance.clear_chunks();
+            unloaded.insert(id);
+            commands.entity(id).insert(Despawned);
+            unloaded_events.send(InstanceUnloaded { instance: id });
+        }
+    }
+
+    if unloading.is_empty() {
+        return;
+    }
+
+    for (mut client, entity) in &mut clients {
+        match unloading.get(&client.instance()) {
+            Some(UnloadInstance::Transfer { instance, position }) => {
+                client.transfer_to_instance(*instance, *position);
+
+                // Move the client's own entity along with the client.
+                if let Some(mut entity) = entity {
+                    entity.set_instance(*instance);
+                    entity.set_position(*position);
+                }
+            }
+            Some(UnloadInstance::Kick { reason }) => client.kick(reason.clone()),
+            None => {}
+        }
+    }
+
+    for (id, entity) in &entities {
+        if unloaded.contains(&entity.instance()) {
+            commands.entity(id).insert(Despawned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dimension::DimensionId;
+    use crate::entity::EntityKind;
+    use crate::server::Server;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn unload_instance_transfers_clients() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(InstanceUnloadPlugin);
+
+        let old = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let server = app.world.resource::<Server>();
+        let lobby = server.new_instance(DimensionId::default());
+        let lobby = app.world.spawn(lobby).id();
+
+        let zombie = app.world.spawn(McEntity::new(EntityKind::Zombie, old)).id();
+
+        app.update();
+
+        app.world.entity_mut(old).insert(UnloadInstance::Transfer {
+            instance: lobby,
+            position: DVec3::new(0.0, 64.0, 0.0),
+        });
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.instance(), lobby);
+        assert!(app.world.get::<Despawned>(old).is_none());
+
+        app.update();
+
+        assert!(app.world.get::<Despawned>(zombie).is_some());
+        assert!(app.world.get::<Despawned>(old).is_some());
+
+        app.update();
+
+        assert!(app.world.get_entity(zombie).is_none());
+        assert!(app.world.get_entity(old).is_none());
+    }
+}
@@ -2,8 +2,10 @@ use std::iter::FusedIterator;
 
 use bevy_ecs::prelude::*;
 use tracing::{debug, warn};
+use valence_protocol::enchant::EnchantmentKind;
 use valence_protocol::packets::s2c::play::{
-    CloseContainerS2c, OpenScreen, SetContainerContentEncode, SetContainerSlotEncode,
+    CloseContainerS2c, OpenScreen, SetContainerContentEncode, SetContainerProperty,
+    SetContainerSlotEncode,
 };
 use valence_protocol::types::{GameMode, WindowType};
 use valence_protocol::{ItemStack, Text, VarInt};
@@ -18,6 +20,11 @@ pub struct Inventory {
     slots: Box<[Option<ItemStack>]>,
     /// Contains a set bit for each modified slot in `slots`.
     modified: u64,
+    /// The properties of special inventories like furnaces, enchanting tables
+    /// and beacons. See [`InventoryKind::property_count`].
+    properties: Box<[i16]>,
+    /// Contains a set bit for each modified property in `properties`.
+    modified_properties: u16,
 }
 
 impl Inventory {
@@ -32,6 +39,8 @@ impl Inventory {
             kind,
             slots: vec![None; kind.slot_count()].into(),
             modified: 0,
+            properties: kind.default_properties(),
+            modified_properties: 0,
         }
     }
 
@@ -104,9 +113,183 @@ impl Inventory {
         std::mem::replace(&mut self.title, title.into())
     }
 
+    /// Returns the value of the property at the given index. Properties are
+    /// used by the client to render parts of special inventories, like the
+    /// progress arrow of furnaces.
+    #[track_caller]
+    pub fn property(&self, idx: u16) -> i16 {
+        *self
+            .properties
+            .get(idx as usize)
+            .expect("property index out of range")
+    }
+
+    /// Sets the value of the property at the given index. The new value is sent
+    /// to all clients viewing this inventory.
+    #[track_caller]
+    pub fn set_property(&mut self, idx: u16, value: i16) -> i16 {
+        assert!(
+            (idx as usize) < self.properties.len(),
+            "property index out of range"
+        );
+
+        let old = &mut self.properties[idx as usize];
+
+        if *old != value {
+            self.modified_properties |= 1 << idx;
+        }
+
+        std::mem::replace(old, value)
+    }
+
+    /// Returns the number of properties of this inventory, which is
+    /// determined by its [kind](InventoryKind::property_count).
+    pub fn property_count(&self) -> u16 {
+        self.properties.len() as u16
+    }
+
+    /// Returns the repair cost in levels shown in an anvil inventory.
+    #[track_caller]
+    pub fn anvil_repair_cost(&self) -> i16 {
+        self.assert_kind(InventoryKind::Anvil);
+        self.property(0)
+    }
+
+    /// Sets the repair cost in levels shown in an anvil inventory. Clients
+    /// not in creative mode show "Too Expensive!" for costs of 40 and above.
+    ///
+    /// The text typed by clients into the name field is sent with the
+    /// [`RenameItem`] event.
+    ///
+    /// [`RenameItem`]: crate::client::event::RenameItem
+    #[track_caller]
+    pub fn set_anvil_repair_cost(&mut self, cost: i16) {
+        self.assert_kind(InventoryKind::Anvil);
+        self.set_property(0, cost);
+    }
+
+    /// Sets the seed used by clients to generate the enchantment names shown
+    /// on the buttons of an enchanting table. Like vanilla, only the lower 16
+    /// bits of the seed are sent, with the lowest 4 bits cleared.
+    #[track_caller]
+    pub fn set_enchantment_seed(&mut self, seed: i32) {
+        self.assert_kind(InventoryKind::Enchantment);
+        self.set_property(3, (seed & -16) as i16);
+    }
+
+    /// Returns the enchantment option of the button at the given index (0 to
+    /// 2) in an enchanting table, or `None` if the button is disabled.
+    #[track_caller]
+    pub fn enchantment_option(&self, idx: u16) -> Option<EnchantmentOption> {
+        self.assert_kind(InventoryKind::Enchantment);
+        assert!(idx < 3, "enchantment option index out of range");
+
+        let level_requirement = self.property(idx);
+
+        if level_requirement <= 0 {
+            return None;
+        }
+
+        let hint = u16::try_from(self.property(idx + 4))
+            .ok()
+            .and_then(EnchantmentKind::from_raw)
+            .map(|enchantment| (enchantment, self.property(idx + 7)));
+
+        Some(EnchantmentOption {
+            level_requirement,
+            hint,
+        })
+    }
+
+    /// Sets the enchantment option of the button at the given index (0 to 2)
+    /// in an enchanting table. `None` disables the button.
+    ///
+    /// Clients selecting an option send the [`ClickContainerButton`] event
+    /// with the index of the option as the button ID.
+    ///
+    /// [`ClickContainerButton`]: crate::client::event::ClickContainerButton
+    #[track_caller]
+    pub fn set_enchantment_option(&mut self, idx: u16, option: Option<EnchantmentOption>) {
+        self.assert_kind(InventoryKind::Enchantment);
+        assert!(idx < 3, "enchantment option index out of range");
+
+        let (level_requirement, hint) = match option {
+            Some(option) => (option.level_requirement, option.hint),
+            None => (0, None),
+        };
+
+        let (enchantment, level) = match hint {
+            Some((enchantment, level)) => (enchantment.to_raw() as i16, level),
+            None => (-1, -1),
+        };
+
+        self.set_property(idx, level_requirement);
+        self.set_property(idx + 4, enchantment);
+        self.set_property(idx + 7, level);
+    }
+
+    /// Returns the number of pyramid levels below the beacon of this beacon
+    /// inventory.
+    #[track_caller]
+    pub fn beacon_power_level(&self) -> i16 {
+        self.assert_kind(InventoryKind::Beacon);
+        self.property(0)
+    }
+
+    /// Sets the number of pyramid levels (0 to 4) below the beacon, which
+    /// determines the effects clients can select in a beacon inventory.
+    #[track_caller]
+    pub fn set_beacon_power_level(&mut self, level: i16) {
+        self.assert_kind(InventoryKind::Beacon);
+        self.set_property(0, level);
+    }
+
+    /// Returns the primary and secondary status effect IDs selected in a beacon
+    /// inventory.
+    #[track_caller]
+    pub fn beacon_effects(&self) -> (Option<i32>, Option<i32>) {
+        self.assert_kind(InventoryKind::Beacon);
+
+        let effect = |idx| {
+            Some(self.property(idx))
+                .filter(|&id| id >= 0)
+                .map(i32::from)
+        };
+
+        (effect(1), effect(2))
+    }
+
+    /// Sets the primary and secondary status effect IDs selected in a beacon
+    /// inventory.
+    ///
+    /// Clients confirming their selection send the [`SetBeaconEffect`] event.
+    ///
+    /// [`SetBeaconEffect`]: crate::client::event::SetBeaconEffect
+    #[track_caller]
+    pub fn set_beacon_effects(&mut self, primary: Option<i32>, secondary: Option<i32>) {
+        self.assert_kind(InventoryKind::Beacon);
+        self.set_property(1, primary.map_or(-1, |id| id as i16));
+        self.set_property(2, secondary.map_or(-1, |id| id as i16));
+    }
+
     fn slot_slice(&self) -> &[Option<ItemStack>] {
         self.slots.as_ref()
     }
+
+    #[track_caller]
+    fn assert_kind(&self, kind: InventoryKind) {
+        assert_eq!(self.kind, kind, "wrong inventory kind");
+    }
+}
+
+/// An option on one of the three buttons of an enchanting table.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EnchantmentOption {
+    /// The experience level required for this option, which is shown on the
+    /// button.
+    pub level_requirement: i16,
+    /// The enchantment and its level shown when hovering over the button.
+    pub hint: Option<(EnchantmentKind, i16)>,
 }
 
 /// Send updates for each client's player inventory.
@@ -235,6 +418,36 @@ impl InventoryKind {
             InventoryKind::Player => 46,
         }
     }
+
+    /// The number of properties of this inventory, which are sent to clients
+    /// with the `SetContainerProperty` packet.
+    pub const fn property_count(self) -> usize {
+        match self {
+            InventoryKind::Anvil => 1,
+            InventoryKind::Beacon => 3,
+            InventoryKind::BlastFurnace => 4,
+            InventoryKind::BrewingStand => 2,
+            InventoryKind::Enchantment => 10,
+            InventoryKind::Furnace => 4,
+            InventoryKind::Lectern => 1,
+            InventoryKind::Loom => 1,
+            InventoryKind::Smoker => 4,
+            InventoryKind::Stonecutter => 1,
+            _ => 0,
+        }
+    }
+
+    fn default_properties(self) -> Box<[i16]> {
+        match self {
+            // Enchantment IDs and levels use -1 for no hint.
+            InventoryKind::Enchantment => [0, 0, 0, 0, -1, -1, -1, -1, -1, -1].into(),
+            // -1 for no selected effects.
+            InventoryKind::Beacon => [0, -1, -1].into(),
+            // -1 for no selected recipe.
+            InventoryKind::Stonecutter => [-1].into(),
+            _ => vec![0; self.property_count()].into(),
+        }
+    }
 }
 
 impl From<InventoryKind> for WindowType {
@@ -323,6 +536,12 @@ impl OpenInventory {
     pub fn entity(&self) -> Entity {
         self.entity
     }
+
+    /// Sends the changes made to the slot at `idx` of the open inventory to the
+    /// client, even if the client changed the slot itself this tick.
+    pub(crate) fn resend_slot(&mut self, idx: u16) {
+        self.client_modified &= !(1 << idx);
+    }
 }
 
 /// Handles the `OpenInventory` component being added to a client, which
@@ -367,6 +586,15 @@ pub(crate) fn update_open_inventories(
                 carried_item: &client.cursor_item.clone(),
             };
             client.write_packet(&packet);
+
+            let window_id = client.window_id;
+            for (i, &value) in inventory.properties.iter().enumerate() {
+                client.write_packet(&SetContainerProperty {
+                    window_id,
+                    property: i as i16,
+                    value,
+                });
+            }
         } else {
             // the client is already viewing the inventory
             if inventory.modified == u64::MAX {
@@ -399,6 +627,19 @@ pub(crate) fn update_open_inventories(
                     }
                 }
             }
+
+            if inventory.modified_properties != 0 {
+                let window_id = client.window_id;
+                for (i, &value) in inventory.properties.iter().enumerate() {
+                    if (inventory.modified_properties >> i) & 1 == 1 {
+                        client.write_packet(&SetContainerProperty {
+                            window_id,
+                            property: i as i16,
+                            value,
+                        });
+                    }
+                }
+            }
         }
 
         open_inventory.client_modified = 0;
@@ -411,6 +652,7 @@ pub(crate) fn update_open_inventories(
         if let Ok(mut inventory) = inventories.get_component_mut::<Inventory>(open_inventory.entity)
         {
             inventory.modified = 0;
+            inventory.modified_properties = 0;
         }
     }
 }
@@ -969,6 +1211,72 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_should_sync_inventory_properties() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut inventory = Inventory::new(InventoryKind::Enchantment);
+        let option = EnchantmentOption {
+            level_requirement: 30,
+            hint: Some((EnchantmentKind::Sharpness, 5)),
+        };
+        inventory.set_enchantment_option(2, Some(option));
+        let inventory_ent = app.world.spawn(inventory).id();
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_sent();
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(OpenInventory::new(inventory_ent));
+
+        app.update();
+
+        // All properties are sent when the inventory is opened.
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 10, S2cPlayPacket::SetContainerProperty(_));
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::OpenScreen(_),
+            S2cPlayPacket::SetContainerProperty(_)
+        );
+
+        let mut inventory = app
+            .world
+            .get_mut::<Inventory>(inventory_ent)
+            .expect("could not find inventory");
+        assert_eq!(inventory.enchantment_option(2), Some(option));
+        assert_eq!(inventory.enchantment_option(0), None);
+
+        inventory.set_enchantment_option(2, None);
+
+        app.update();
+
+        // Only the modified properties are sent afterwards.
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 3, S2cPlayPacket::SetContainerProperty(_));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_beacon_and_anvil_properties() {
+        let mut beacon = Inventory::new(InventoryKind::Beacon);
+        assert_eq!(beacon.beacon_effects(), (None, None));
+
+        beacon.set_beacon_power_level(4);
+        beacon.set_beacon_effects(Some(1), None);
+        assert_eq!(beacon.beacon_power_level(), 4);
+        assert_eq!(beacon.beacon_effects(), (Some(1), None));
+
+        let mut anvil = Inventory::new(InventoryKind::Anvil);
+        anvil.set_anvil_repair_cost(5);
+        assert_eq!(anvil.anvil_repair_cost(), 5);
+        assert_eq!(anvil.property(0), 5);
+    }
+
     #[test]
     fn test_set_creative_mode_slot_handling() {
         let mut app = App::new();
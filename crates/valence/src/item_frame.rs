@@ -0,0 +1,183 @@
+//! Interaction with item frames.
+//!
+//! The item and rotation of (glow) item frames are set with
+//! [`McEntity::set_item_frame_item`] and [`McEntity::set_item_frame_rotation`].
+//! The [`ItemFramePlugin`] handles clients interacting with item frames like
+//! vanilla: right-clicking a frame with an item rotates the item and
+//! attacking it removes the item. An [`ItemFrameRotated`] or
+//! [`ItemFrameItemRemoved`] event is sent for every change. Frames with the
+//! [`FixedItemFrame`] component cannot be changed by clients.
+//!
+//! Putting items into empty frames is left to the [`InteractWithEntity`]
+//! events, since it depends on the inventory rules of the server.
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::protocol::entity_meta::Facing;
+//!
+//! fn spawn_frame(mut commands: Commands, instance: Entity) {
+//!     let mut frame = McEntity::new(EntityKind::GlowItemFrame, instance);
+//!     frame.set_position([0.0, 64.0, 0.0]);
+//!     frame.set_item_frame_facing(Facing::North);
+//!     frame.set_item_frame_item(Some(ItemStack::new(ItemKind::Diamond, 1, None)));
+//!
+//!     commands.spawn(frame);
+//! }
+//! ```
+//!
+//! [`InteractWithEntity`]: crate::client::event::InteractWithEntity
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use valence_protocol::types::EntityInteraction;
+use valence_protocol::ItemStack;
+
+use crate::client::event::InteractWithEntity;
+use crate::entity::{McEntity, McEntityManager};
+
+/// Adds the item frame events and the system handling interactions with item
+/// frames.
+pub struct ItemFramePlugin;
+
+impl Plugin for ItemFramePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ItemFrameRotated>()
+            .add_event::<ItemFrameItemRemoved>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interact_with_item_frames.before("valence_core"),
+            );
+    }
+}
+
+/// A component for item frame entities which prevents clients from rotating
+/// or removing their items.
+#[derive(Component, Copy, Clone, Default, Debug)]
+pub struct FixedItemFrame;
+
+/// Sent when a client rotated the item in an item frame.
+#[derive(Clone, Debug)]
+pub struct ItemFrameRotated {
+    pub client: Entity,
+    pub item_frame: Entity,
+    /// The new rotation from 0 to 7.
+    pub rotation: i32,
+}
+
+/// Sent when a client removed the item from an item frame.
+#[derive(Clone, Debug)]
+pub struct ItemFrameItemRemoved {
+    pub client: Entity,
+    pub item_frame: Entity,
+    pub item: ItemStack,
+}
+
+fn interact_with_item_frames(
+    manager: Res<McEntityManager>,
+    mut frames: Query<&mut McEntity, Without<FixedItemFrame>>,
+    mut interactions: EventReader<InteractWithEntity>,
+    mut rotated: EventWriter<ItemFrameRotated>,
+    mut removed: EventWriter<ItemFrameItemRemoved>,
+) {
+    for event in interactions.iter() {
+        let Some(item_frame) = manager.get_with_protocol_id(event.entity_id) else {
+            continue
+        };
+
+        let Ok(mut frame) = frames.get_mut(item_frame) else {
+            continue
+        };
+
+        let Some(rotation) = frame.item_frame_rotation() else {
+            continue
+        };
+
+        if frame.item_frame_item().is_none() {
+            continue;
+        }
+
+        match event.interact {
+            EntityInteraction::Interact(_) => {
+                frame.set_item_frame_rotation(rotation + 1);
+
+                rotated.send(ItemFrameRotated {
+                    client: event.client,
+                    item_frame,
+                    rotation: (rotation + 1).rem_euclid(8),
+                });
+            }
+            EntityInteraction::Attack => {
+                let item = frame.item_frame_item().cloned().unwrap();
+
+                frame.set_item_frame_item(None);
+                frame.set_item_frame_rotation(0);
+
+                removed.send(ItemFrameItemRemoved {
+                    client: event.client,
+                    item_frame,
+                    item,
+                });
+            }
+            // Clients send this in addition to `Interact`.
+            EntityInteraction::InteractAt { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::types::Hand;
+    use valence_protocol::ItemKind;
+
+    use super::*;
+    use crate::entity::EntityKind;
+    use crate::instance::Instance;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn item_frame_interactions() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(ItemFramePlugin);
+
+        let instance = app
+            .world
+            .query_filtered::<Entity, With<Instance>>()
+            .single(&app.world);
+
+        let item = ItemStack::new(ItemKind::Diamond, 1, None);
+
+        let mut frame = McEntity::new(EntityKind::ItemFrame, instance);
+        frame.set_item_frame_item(Some(item.clone()));
+        frame.set_item_frame_rotation(7);
+        let frame = app.world.spawn(frame).id();
+
+        app.update();
+
+        let entity_id = app.world.get::<McEntity>(frame).unwrap().protocol_id();
+        let interact = |app: &mut App, interact| {
+            app.world.send_event(InteractWithEntity {
+                client: client_ent,
+                entity_id,
+                sneaking: false,
+                interact,
+            });
+            app.update();
+        };
+
+        interact(&mut app, EntityInteraction::Interact(Hand::Main));
+
+        let mc_entity = app.world.get::<McEntity>(frame).unwrap();
+        assert_eq!(mc_entity.item_frame_rotation(), Some(0));
+
+        interact(&mut app, EntityInteraction::Attack);
+
+        let mc_entity = app.world.get::<McEntity>(frame).unwrap();
+        assert_eq!(mc_entity.item_frame_item(), None);
+
+        let events = app.world.resource::<Events<ItemFrameItemRemoved>>();
+        let removed: Vec<_> = events.get_reader().iter(events).cloned().collect();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].item, item);
+    }
+}
@@ -0,0 +1,453 @@
+//! Leads and leash knots.
+//!
+//! The [`LeashPlugin`] lets clients leash entities with a [`Leashable`]
+//! component like vanilla:
+//!
+//! - Clients holding a lead leash an entity by interacting with it, and
+//!   release it by interacting with it again. Leads are not used up.
+//! - Clients leading entities tie them to a fence by using the fence, which
+//!   creates a leash knot on it. Interacting with the knot releases the
+//!   entities tied to it, and so does attacking it or removing the fence.
+//! - Leashes break when the holder is removed, leaves the instance, or moves
+//!   more than [`MAX_LEASH_DISTANCE`] blocks away.
+//!
+//! Holders are shown holding the leash with the attach-entity packet, which
+//! needs the holder to have an [`McEntity`], like the player entity of a
+//! client. Only mobs are shown with a leash by the client. Leashed entities
+//! are not pulled along, which is left to the game.
+//!
+//! Leashes can also be changed from any system with
+//! [`Leashable::set_holder`]. [`EntityLeashed`] and [`EntityUnleashed`] events
+//! are only sent for the interactions of clients and for broken leashes.
+//!
+//! ```
+//! use valence::leash::{LeashPlugin, Leashable};
+//! use valence::prelude::*;
+//!
+//! let mut app = App::new();
+//! app.add_plugin(LeashPlugin);
+//!
+//! fn spawn_cow(mut commands: Commands, instance: Entity) {
+//!     let mut cow = McEntity::new(EntityKind::Cow, instance);
+//!     cow.set_position([0.0, 64.0, 0.0]);
+//!     commands.spawn((cow, Leashable::new()));
+//! }
+//! ```
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use valence_protocol::types::{EntityInteraction, GameMode, Hand};
+use valence_protocol::{BlockPos, ItemKind};
+
+use crate::client::event::{InteractWithEntity, UseItemOnBlock};
+use crate::client::Client;
+use crate::entity::{EntityKind, McEntity, McEntityManager};
+use crate::instance::Instance;
+use crate::inventory::Inventory;
+use crate::Despawned;
+
+/// The distance between a leashed entity and its holder at which the leash
+/// breaks.
+pub const MAX_LEASH_DISTANCE: f64 = 10.0;
+
+/// Adds the leash events and the systems for leads and leash knots.
+pub struct LeashPlugin;
+
+impl Plugin for LeashPlugin {
+    fn build(&self, app: &mut App) {
+        // Knots are spawned in the update stage so that they exist when the
+        // leashes are updated.
+        app.add_event::<EntityLeashed>()
+            .add_event::<EntityUnleashed>()
+            .add_system_to_stage(CoreStage::Update, use_leads)
+            .add_system_to_stage(CoreStage::Update, tie_to_fences)
+            .add_system_to_stage(CoreStage::PostUpdate, update_leashes.before("valence_core"));
+    }
+}
+
+/// A component for entities which can be leashed.
+#[derive(Component, Clone, Default, Debug)]
+pub struct Leashable {
+    holder: Option<Entity>,
+}
+
+impl Leashable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the entity holding the leash, if any.
+    pub fn holder(&self) -> Option<Entity> {
+        self.holder
+    }
+
+    /// Leashes this entity to `holder`, or removes the leash with `None`.
+    pub fn set_holder(&mut self, holder: Option<Entity>) {
+        self.holder = holder;
+    }
+}
+
+/// A component for leash knots on fences.
+#[derive(Component, Clone, Debug)]
+pub struct LeashKnot {
+    pos: BlockPos,
+}
+
+impl LeashKnot {
+    /// Returns the position of the fence this knot is tied to.
+    pub fn pos(&self) -> BlockPos {
+        self.pos
+    }
+}
+
+/// Sent when an entity was leashed by a client, either to the client itself
+/// or to a leash knot.
+#[derive(Clone, Debug)]
+pub struct EntityLeashed {
+    pub entity: Entity,
+    pub holder: Entity,
+}
+
+/// Sent when the leash of an entity was removed by a client or broke.
+#[derive(Clone, Debug)]
+pub struct EntityUnleashed {
+    pub entity: Entity,
+    /// The entity which was holding the leash.
+    pub holder: Entity,
+}
+
+/// Returns the components of a leash knot tied to the fence at `pos`.
+pub fn leash_knot(instance: Entity, pos: impl Into<BlockPos>) -> (McEntity, LeashKnot) {
+    let pos = pos.into();
+
+    let mut entity = McEntity::new(EntityKind::LeashKnot, instance);
+    entity.set_position([pos.x as f64 + 0.5, pos.y as f64 + 0.375, pos.z as f64 + 0.5]);
+
+    (entity, LeashKnot { pos })
+}
+
+fn is_fence(instance: &Instance, pos: BlockPos) -> bool {
+    instance
+        .block(pos)
+        .map_or(false, |b| b.state().to_kind().to_str().ends_with("_fence"))
+}
+
+fn use_leads(
+    mut commands: Commands,
+    manager: Res<McEntityManager>,
+    clients: Query<(&Client, &Inventory)>,
+    mut leashables: Query<&mut Leashable>,
+    knots: Query<&LeashKnot>,
+    mut events: EventReader<InteractWithEntity>,
+    mut leashed: EventWriter<EntityLeashed>,
+    mut unleashed: EventWriter<EntityUnleashed>,
+) {
+    for event in events.iter() {
+        let Ok((client, inventory)) = clients.get(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator {
+            continue;
+        }
+
+        let Some(target) = manager.get_with_protocol_id(event.entity_id) else {
+            continue
+        };
+
+        // Interacting with or attacking a knot releases the entities tied to it.
+        if knots.contains(target) {
+            if matches!(
+                event.interact,
+                EntityInteraction::Interact(Hand::Main) | EntityInteraction::Attack
+            ) {
+                commands.entity(target).insert(Despawned);
+            }
+
+            continue;
+        }
+
+        if event.interact != EntityInteraction::Interact(Hand::Main) {
+            continue;
+        }
+
+        let Ok(mut leashable) = leashables.get_mut(target) else {
+            continue
+        };
+
+        match leashable.holder {
+            Some(holder) if holder == event.client => {
+                leashable.holder = None;
+
+                unleashed.send(EntityUnleashed {
+                    entity: target,
+                    holder,
+                });
+            }
+            None => {
+                let holds_lead = inventory
+                    .slot(client.held_item_slot())
+                    .map_or(false, |stack| stack.item == ItemKind::Lead);
+
+                if holds_lead {
+                    leashable.holder = Some(event.client);
+
+                    leashed.send(EntityLeashed {
+                        entity: target,
+                        holder: event.client,
+                    });
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+fn tie_to_fences(
+    mut commands: Commands,
+    clients: Query<&Client>,
+    instances: Query<&Instance>,
+    mut leashables: Query<(Entity, &mut Leashable)>,
+    knots: Query<(Entity, &McEntity, &LeashKnot)>,
+    mut events: EventReader<UseItemOnBlock>,
+    mut leashed: EventWriter<EntityLeashed>,
+) {
+    for event in events.iter() {
+        if event.hand != Hand::Main {
+            continue;
+        }
+
+        let Ok(client) = clients.get(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator {
+            continue;
+        }
+
+        let Ok(instance) = instances.get(client.instance()) else {
+            continue
+        };
+
+        if !is_fence(instance, event.position)
+            || !leashables
+                .iter()
+                .any(|(_, l)| l.holder == Some(event.client))
+        {
+            continue;
+        }
+
+        let knot = knots
+            .iter()
+            .find(|(_, mc_entity, knot)| {
+                knot.pos == event.position && mc_entity.instance() == client.instance()
+            })
+            .map(|(entity, ..)| entity)
+            .unwrap_or_else(|| {
+                commands
+                    .spawn(leash_knot(client.instance(), event.position))
+                    .id()
+            });
+
+        for (entity, mut leashable) in &mut leashables {
+            if leashable.holder == Some(event.client) {
+                leashable.holder = Some(knot);
+
+                leashed.send(EntityLeashed {
+                    entity,
+                    holder: knot,
+                });
+            }
+        }
+    }
+}
+
+fn update_leashes(
+    mut commands: Commands,
+    mut entities: Query<(
+        Entity,
+        &mut McEntity,
+        Option<&mut Leashable>,
+        Option<&LeashKnot>,
+    )>,
+    instances: Query<&Instance>,
+    mut unleashed: EventWriter<EntityUnleashed>,
+) {
+    let mut tied_knots = vec![];
+
+    let leashed: Vec<_> = entities
+        .iter()
+        .filter_map(|(entity, _, leashable, _)| Some((entity, leashable?.holder)))
+        .collect();
+
+    for (entity, holder) in leashed {
+        let Ok((_, mc_entity, ..)) = entities.get(entity) else {
+            continue
+        };
+
+        let instance = mc_entity.instance();
+        let position = mc_entity.position();
+
+        let holder_id = holder.and_then(|holder| {
+            let (_, holder_entity, _, knot) = entities.get(holder).ok()?;
+
+            let in_range = holder_entity.instance() == instance
+                && holder_entity.position().distance(position) <= MAX_LEASH_DISTANCE;
+
+            if !in_range {
+                return None;
+            }
+
+            if knot.is_some() {
+                tied_knots.push(holder);
+            }
+
+            Some(holder_entity.protocol_id())
+        });
+
+        let Ok((_, mut mc_entity, Some(mut leashable), _)) = entities.get_mut(entity) else {
+            continue
+        };
+
+        if let (Some(holder), None) = (holder, holder_id) {
+            leashable.holder = None;
+            unleashed.send(EntityUnleashed { entity, holder });
+        }
+
+        mc_entity.set_leash_holder(holder_id);
+    }
+
+    // Knots are removed once nothing is tied to them or the fence is gone.
+    for (entity, mc_entity, _, knot) in &entities {
+        let Some(knot) = knot else {
+            continue
+        };
+
+        let on_fence = instances
+            .get(mc_entity.instance())
+            .map_or(false, |instance| is_fence(instance, knot.pos));
+
+        if !on_fence || !tied_knots.contains(&entity) {
+            commands.entity(entity).insert(Despawned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::block::BlockState;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::{BlockFace, ItemStack};
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn leash_and_tie_to_fence() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(LeashPlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let y = instance.min_y() + 10;
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([6, y, 4], BlockState::OAK_FENCE);
+
+        let mut player = McEntity::new(EntityKind::Player, instance_ent);
+        player.set_position([4.5, y as f64, 4.5]);
+        app.world.entity_mut(client_ent).insert(player);
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([4.5, y as f64, 4.5]);
+        let slot = client.held_item_slot();
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(slot, ItemStack::new(ItemKind::Lead, 1, None));
+
+        let mut cow = McEntity::new(EntityKind::Cow, instance_ent);
+        cow.set_position([5.5, y as f64, 5.5]);
+        let cow = app.world.spawn((cow, Leashable::new())).id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let entity_id = app.world.get::<McEntity>(cow).unwrap().protocol_id();
+        app.world.send_event(InteractWithEntity {
+            client: client_ent,
+            entity_id,
+            sneaking: false,
+            interact: EntityInteraction::Interact(Hand::Main),
+        });
+
+        app.update();
+
+        assert_eq!(
+            app.world.get::<Leashable>(cow).unwrap().holder(),
+            Some(client_ent)
+        );
+
+        let player_id = app.world.get::<McEntity>(client_ent).unwrap().protocol_id();
+        assert_eq!(
+            app.world.get::<McEntity>(cow).unwrap().leash_holder(),
+            Some(player_id)
+        );
+
+        // The client knows its own player by the ID 0, so the packet sent to all
+        // viewers is followed by one for the holder.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 2, S2cPlayPacket::LinkEntities(_));
+
+        let holder_ids: Vec<_> = sent_packets
+            .iter()
+            .filter_map(|pkt| match pkt {
+                S2cPlayPacket::LinkEntities(pkt) => Some(pkt.holding_entity_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(holder_ids, [player_id, 0]);
+
+        app.world.send_event(UseItemOnBlock {
+            client: client_ent,
+            hand: Hand::Main,
+            position: BlockPos::new(6, y, 4),
+            face: BlockFace::Top,
+            cursor_pos: [0.5, 1.0, 0.5].into(),
+            head_inside_block: false,
+            sequence: 0,
+        });
+
+        app.update();
+
+        let mut query = app.world.query::<(Entity, &LeashKnot, &McEntity)>();
+        let (knot, _, knot_entity) = query.single(&app.world);
+        let knot_id = knot_entity.protocol_id();
+
+        assert_eq!(
+            app.world.get::<Leashable>(cow).unwrap().holder(),
+            Some(knot)
+        );
+        assert_eq!(
+            app.world.get::<McEntity>(cow).unwrap().leash_holder(),
+            Some(knot_id)
+        );
+
+        // Removing the fence releases the cow.
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .set_block([6, y, 4], BlockState::AIR);
+
+        app.update();
+        app.update();
+
+        assert!(app.world.get_entity(knot).is_none());
+        assert_eq!(app.world.get::<Leashable>(cow).unwrap().holder(), None);
+        assert_eq!(app.world.get::<McEntity>(cow).unwrap().leash_holder(), None);
+    }
+}
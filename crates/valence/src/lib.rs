@@ -32,33 +32,71 @@ pub use {
 };
 
 pub mod biome;
+pub mod block_breaking;
+pub mod block_interaction;
+pub mod block_placement;
+pub mod block_tick;
 pub mod client;
+pub mod combat;
 pub mod config;
+pub mod config_file;
+pub mod container;
 pub mod dimension;
+pub mod elytra;
 pub mod entity;
+pub mod entity_sound;
+pub mod falling_block;
+pub mod fishing;
+pub mod fluid;
 pub mod instance;
+pub mod instance_name;
+pub mod instance_unload;
 pub mod inventory;
+pub mod item_frame;
+pub mod leash;
+pub mod lobby;
+pub mod localization;
 pub mod math;
+pub mod merchant;
+pub mod metrics;
+pub mod movement;
 mod packet;
+pub mod pause;
 pub mod player_list;
 pub mod player_textures;
+pub mod projectile;
+pub mod quota;
+pub mod redstone;
+pub mod registry;
+pub mod resource_pack;
 pub mod server;
+pub mod sign;
+pub mod team;
+pub mod testing;
+pub mod timings;
+pub mod tnt;
 #[cfg(any(test, doctest))]
 mod unit_test;
+pub mod vehicle;
 pub mod view;
+pub mod world_border;
+pub mod world_gen;
+pub mod zone_damage;
 
 pub mod prelude {
     pub use async_trait::async_trait;
     pub use bevy_app::App;
     pub use bevy_ecs::prelude::*;
     pub use biome::{Biome, BiomeId};
-    pub use client::Client;
+    pub use client::{Client, ClientSettings, Weather};
     pub use config::{
-        AsyncCallbacks, ConnectionMode, PlayerSampleEntry, ServerListPing, ServerPlugin,
+        AsyncCallbacks, ConnectionContext, ConnectionMode, PlayerSampleEntry, ServerListPing,
+        ServerPlugin, TickLagPolicy,
     };
     pub use dimension::{Dimension, DimensionId};
     pub use entity::{
-        EntityAnimation, EntityKind, EntityStatus, McEntity, McEntityManager, TrackedData,
+        ArmorStandPart, DamageSource, EntityAnimation, EntityKind, EntityStatus, EquipmentSlot,
+        McEntity, McEntityManager, RemovalReason, TrackedData,
     };
     pub use glam::DVec3;
     pub use instance::{Block, BlockMut, BlockRef, Chunk, Instance};
@@ -85,12 +123,14 @@ pub mod prelude {
 /// In Valence, some built-in components such as [`McEntity`] are not allowed to
 /// be removed from the [`World`] directly. Instead, you must give the entities
 /// you wish to despawn the `Despawned` component. At the end of the tick,
-/// Valence will despawn all entities with this component for you.
+/// Valence will despawn all entities with this component for you. To show
+/// viewers why an entity disappeared, use [`RemovalReason`] instead.
 ///
 /// It is legal to remove components or delete entities that Valence does not
 /// know about at any time.
 ///
 /// [`McEntity`]: entity::McEntity
+/// [`RemovalReason`]: entity::RemovalReason
 #[derive(Copy, Clone, Component)]
 pub struct Despawned;
 
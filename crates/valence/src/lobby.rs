@@ -0,0 +1,304 @@
+//! Common defaults for hub worlds.
+//!
+//! The [`LobbyPreset`] plugin applies the following to clients in instances
+//! with the [`Lobby`] component:
+//!
+//! - Clients are put in adventure mode and their health and food bars are
+//!   filled. Valence doesn't simulate hunger, so the food bar stays full, but
+//!   clients can still be damaged by plugins such as the [`CombatPlugin`].
+//! - Clients falling below the void level are teleported back to the spawn.
+//! - Sneaking in the air launches clients forward once until they land.
+//! - Clients entering and leaving the lobby are announced in the chat of the
+//!   lobby.
+//! - Clients are given a menu item, a compass by default. Using it sends a
+//!   [`LobbyMenuOpened`] event so that the server can show its menu.
+//!
+//! Each feature can be disabled with the builder methods of [`LobbyPreset`] or
+//! by changing the [`LobbySettings`] resource at runtime.
+//!
+//! ```
+//! use valence::lobby::{Lobby, LobbyPreset};
+//! use valence::prelude::*;
+//!
+//! let mut app = App::new();
+//! app.add_plugin(LobbyPreset::new([0.0, 64.0, 0.0]).with_join_messages(false));
+//!
+//! fn setup(mut commands: Commands, server: Res<Server>) {
+//!     let instance = server.new_instance(DimensionId::default());
+//!     commands.spawn((instance, Lobby));
+//! }
+//! ```
+//!
+//! [`CombatPlugin`]: crate::combat::CombatPlugin
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::{DVec3, Vec3};
+use valence_protocol::packets::s2c::play::SetHealth;
+use valence_protocol::text::{Color, TextFormat};
+use valence_protocol::types::{GameMode, Hand};
+use valence_protocol::{ItemKind, ItemStack, VarInt};
+
+use crate::client::event::{StartSneaking, UseItem};
+use crate::client::Client;
+use crate::inventory::Inventory;
+use crate::math::from_yaw_and_pitch;
+
+/// The slot of the player inventory the menu item is put in, which is the
+/// first slot of the hotbar.
+pub const MENU_ITEM_SLOT: u16 = 36;
+
+/// Adds the [`LobbySettings`] resource, the [`LobbyMenuOpened`] event, and the
+/// systems for clients in [`Lobby`] instances.
+pub struct LobbyPreset {
+    settings: LobbySettings,
+}
+
+impl LobbyPreset {
+    /// Creates the plugin with all features enabled. Clients falling into the
+    /// void are teleported to `spawn`.
+    pub fn new(spawn: impl Into<DVec3>) -> Self {
+        Self {
+            settings: LobbySettings {
+                spawn: spawn.into(),
+                void_level: -64.0,
+                double_jump_speed: Some((24.0, 16.0)),
+                join_messages: true,
+                menu_item: Some(ItemStack::new(ItemKind::Compass, 1, None)),
+            },
+        }
+    }
+
+    #[must_use]
+    pub fn with_void_level(mut self, y: f64) -> Self {
+        self.settings.void_level = y;
+        self
+    }
+
+    #[must_use]
+    pub fn with_double_jump_speed(mut self, speed: Option<(f32, f32)>) -> Self {
+        self.settings.double_jump_speed = speed;
+        self
+    }
+
+    #[must_use]
+    pub fn with_join_messages(mut self, enabled: bool) -> Self {
+        self.settings.join_messages = enabled;
+        self
+    }
+
+    #[must_use]
+    pub fn with_menu_item(mut self, item: Option<ItemStack>) -> Self {
+        self.settings.menu_item = item;
+        self
+    }
+}
+
+impl Plugin for LobbyPreset {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .add_event::<LobbyMenuOpened>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                enter_and_leave_lobbies.before("valence_core"),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_lobby_clients
+                    .after(enter_and_leave_lobbies)
+                    .before("valence_core"),
+            );
+    }
+}
+
+/// A component for instance entities which makes the instance a lobby.
+#[derive(Component, Copy, Clone, Default, Debug)]
+pub struct Lobby;
+
+/// The settings of the [`LobbyPreset`] plugin.
+#[derive(Resource, Clone, Debug)]
+pub struct LobbySettings {
+    /// The position clients falling into the void are teleported to.
+    pub spawn: DVec3,
+    /// The Y coordinate below which clients are teleported to the spawn.
+    pub void_level: f64,
+    /// The horizontal and vertical speed in blocks per second clients are
+    /// launched with when they sneak in the air, or `None` to disable double
+    /// jumps.
+    pub double_jump_speed: Option<(f32, f32)>,
+    /// Whether clients entering and leaving the lobby are announced.
+    pub join_messages: bool,
+    /// The item put in the first hotbar slot of clients entering the lobby.
+    pub menu_item: Option<ItemStack>,
+}
+
+/// Sent when a client in a lobby uses the menu item.
+#[derive(Clone, Debug)]
+pub struct LobbyMenuOpened {
+    pub client: Entity,
+}
+
+/// Marks clients in a lobby.
+#[derive(Component)]
+struct InLobby {
+    /// The lobby instance the client is in.
+    instance: Entity,
+    /// If the client used its double jump since it was last on the ground.
+    double_jumped: bool,
+}
+
+fn enter_and_leave_lobbies(
+    mut commands: Commands,
+    settings: Res<LobbySettings>,
+    lobbies: Query<(), With<Lobby>>,
+    mut clients: Query<(
+        Entity,
+        &mut Client,
+        Option<&mut Inventory>,
+        Option<&InLobby>,
+    )>,
+) {
+    let mut messages = vec![];
+
+    for (entity, mut client, inventory, in_lobby) in &mut clients {
+        let lobby = (!client.is_disconnected() && lobbies.contains(client.instance()))
+            .then(|| client.instance());
+
+        let old_lobby = in_lobby.map(|in_lobby| in_lobby.instance);
+
+        if lobby == old_lobby {
+            continue;
+        }
+
+        if let Some(old_lobby) = old_lobby {
+            commands.entity(entity).remove::<InLobby>();
+            messages.push((old_lobby, format!("{} left the lobby", client.username())));
+        }
+
+        if let Some(lobby) = lobby {
+            client.set_game_mode(GameMode::Adventure);
+            client.write_packet(&SetHealth {
+                health: 20.0,
+                food: VarInt(20),
+                food_saturation: 5.0,
+            });
+
+            if let (Some(item), Some(mut inventory)) = (&settings.menu_item, inventory) {
+                inventory.replace_slot(MENU_ITEM_SLOT, item.clone());
+            }
+
+            commands.entity(entity).insert(InLobby {
+                instance: lobby,
+                double_jumped: false,
+            });
+
+            messages.push((lobby, format!("{} joined the lobby", client.username())));
+        }
+    }
+
+    if !settings.join_messages || messages.is_empty() {
+        return;
+    }
+
+    for (_, mut client, _, _) in &mut clients {
+        for (lobby, msg) in &messages {
+            if *lobby == client.instance() {
+                client.send_message(msg.clone().color(Color::YELLOW));
+            }
+        }
+    }
+}
+
+fn update_lobby_clients(
+    settings: Res<LobbySettings>,
+    mut clients: Query<(&mut Client, &mut InLobby, Option<&Inventory>)>,
+    mut sneaking: EventReader<StartSneaking>,
+    mut use_item: EventReader<UseItem>,
+    mut menu_opened: EventWriter<LobbyMenuOpened>,
+) {
+    for (mut client, mut in_lobby, _) in &mut clients {
+        if client.position().y < settings.void_level {
+            client.set_position(settings.spawn);
+            client.set_velocity(Vec3::ZERO);
+        }
+
+        if client.on_ground() && in_lobby.double_jumped {
+            in_lobby.double_jumped = false;
+        }
+    }
+
+    if let Some((horizontal, vertical)) = settings.double_jump_speed {
+        for event in sneaking.iter() {
+            let Ok((mut client, mut in_lobby, _)) = clients.get_mut(event.client) else {
+                continue
+            };
+
+            if client.on_ground() || in_lobby.double_jumped {
+                continue;
+            }
+
+            let velocity = from_yaw_and_pitch(client.yaw(), 0.0) * horizontal + Vec3::Y * vertical;
+
+            client.set_velocity(velocity);
+            in_lobby.double_jumped = true;
+        }
+    }
+
+    if let Some(menu_item) = &settings.menu_item {
+        for event in use_item.iter() {
+            if event.hand != Hand::Main {
+                continue;
+            }
+
+            let Ok((client, _, Some(inventory))) = clients.get(event.client) else {
+                continue
+            };
+
+            if inventory
+                .slot(client.held_item_slot())
+                .map_or(false, |item| item.item == menu_item.item)
+            {
+                menu_opened.send(LobbyMenuOpened {
+                    client: event.client,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn lobby_defaults() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(LobbyPreset::new([0.0, 64.0, 0.0]));
+
+        let instance = app.world.get::<Client>(client_ent).unwrap().instance();
+        app.world.entity_mut(instance).insert(Lobby);
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.game_mode(), GameMode::Adventure);
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(
+            inventory.slot(MENU_ITEM_SLOT).map(|item| item.item),
+            Some(ItemKind::Compass)
+        );
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([0.0, -100.0, 0.0]);
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.position(), DVec3::new(0.0, 64.0, 0.0));
+    }
+}
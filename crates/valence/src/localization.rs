@@ -0,0 +1,306 @@
+//! Server-side translation of messages.
+//!
+//! The vanilla client only translates the keys of its own language files.
+//! With the [`LocalizationPlugin`], messages are instead translated on the
+//! server using [`LocaleBundle`]s registered in the [`Localization`] resource,
+//! so custom strings are shown in each player's language. The language of a
+//! client is taken from its settings and available with [`Client::locale`].
+//!
+//! Bundles map message keys to templates, in which `{0}`, `{1}`, etc. are
+//! replaced with the message arguments. `{{` and `}}` are literal braces.
+//! Bundles can be loaded from JSON objects in the format of vanilla language
+//! files.
+//!
+//! ```
+//! use valence::localization::{LocaleBundle, Localization};
+//! use valence::msg;
+//! use valence::prelude::*;
+//!
+//! fn setup(mut localization: ResMut<Localization>) {
+//!     let mut en_us = LocaleBundle::new();
+//!     en_us.insert("shop.purchase", "You bought {0}!");
+//!     localization.add_bundle("en_us", en_us);
+//!
+//!     let de_de = LocaleBundle::from_json(r#"{"shop.purchase": "Du hast {0} gekauft!"}"#);
+//!     localization.add_bundle("de_de", de_de.unwrap());
+//! }
+//!
+//! fn purchase(mut clients: Query<&mut Client>) {
+//!     for mut client in &mut clients {
+//!         msg!(client, "shop.purchase", "an apple".color(Color::GOLD));
+//!     }
+//! }
+//! ```
+//!
+//! [`Client::locale`]: crate::client::Client::locale
+
+use std::borrow::Cow;
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashMap;
+use valence_protocol::text::Text;
+
+use crate::client::Client;
+
+/// Adds the [`Localization`] resource if it does not exist and the system
+/// sending the messages queued with [`msg!`](crate::msg).
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Localization>().add_system_to_stage(
+            CoreStage::PostUpdate,
+            send_localized_messages.before("valence_core"),
+        );
+    }
+}
+
+/// The locale used when a client's locale has no bundle.
+pub const DEFAULT_FALLBACK_LOCALE: &str = "en_us";
+
+/// The message bundles of all locales.
+#[derive(Resource, Clone, Debug)]
+pub struct Localization {
+    bundles: FxHashMap<String, LocaleBundle>,
+    fallback_locale: String,
+}
+
+impl Localization {
+    pub fn new() -> Self {
+        Self {
+            bundles: FxHashMap::default(),
+            fallback_locale: DEFAULT_FALLBACK_LOCALE.into(),
+        }
+    }
+
+    /// Sets the locale whose bundle is used when there is no bundle for a
+    /// client's locale or the bundle does not contain a message.
+    #[must_use]
+    pub fn with_fallback_locale(mut self, locale: impl Into<String>) -> Self {
+        self.fallback_locale = locale.into().to_lowercase();
+        self
+    }
+
+    pub fn fallback_locale(&self) -> &str {
+        &self.fallback_locale
+    }
+
+    /// Adds the messages in `bundle` to the bundle of `locale`, e.g. `en_us`.
+    /// Existing messages with the same keys are replaced.
+    pub fn add_bundle(&mut self, locale: impl Into<String>, bundle: LocaleBundle) {
+        self.bundles
+            .entry(locale.into().to_lowercase())
+            .or_default()
+            .messages
+            .extend(bundle.messages);
+    }
+
+    pub fn bundle(&self, locale: &str) -> Option<&LocaleBundle> {
+        self.bundles.get(&locale.to_lowercase())
+    }
+
+    /// Returns the template of the message `key` for `locale`.
+    ///
+    /// If the locale has no such message, the message is looked up in another
+    /// locale of the same language, e.g. `en_gb` for `en_us`, and then in the
+    /// fallback locale.
+    pub fn template(&self, locale: &str, key: &str) -> Option<&str> {
+        let locale = locale.to_lowercase();
+
+        if let Some(template) = self.bundles.get(&locale).and_then(|b| b.get(key)) {
+            return Some(template);
+        }
+
+        let language = locale.split('_').next().unwrap_or_default();
+
+        let same_language = self
+            .bundles
+            .iter()
+            .filter(|(l, _)| l.split('_').next() == Some(language))
+            .filter_map(|(l, b)| Some((l, b.get(key)?)))
+            .min_by_key(|&(l, _)| l)
+            .map(|(_, template)| template);
+
+        same_language.or_else(|| self.bundles.get(&self.fallback_locale)?.get(key))
+    }
+
+    /// Translates the message `key` for `locale`, replacing the placeholders
+    /// with `args`. If no locale has the message, the key itself is returned
+    /// so that missing translations are easy to spot.
+    pub fn translate(&self, locale: &str, key: &str, args: &[Text]) -> Text {
+        match self.template(locale, key) {
+            Some(template) => render(template, args),
+            None => Text::text(key.to_owned()),
+        }
+    }
+}
+
+impl Default for Localization {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The messages of a single locale, keyed by message key.
+#[derive(Clone, Default, Debug)]
+pub struct LocaleBundle {
+    messages: FxHashMap<String, String>,
+}
+
+impl LocaleBundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a bundle from a JSON object mapping keys to templates, like the
+    /// vanilla language files.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        Ok(Self {
+            messages: serde_json::from_str(json)?,
+        })
+    }
+
+    /// Adds a message, returning the previous template for the key.
+    pub fn insert(
+        &mut self,
+        key: impl Into<String>,
+        template: impl Into<String>,
+    ) -> Option<String> {
+        self.messages.insert(key.into(), template.into())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.messages.get(key).map(|s| s.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// Replaces the `{N}` placeholders in `template` with the corresponding
+/// arguments. Placeholders without an argument are kept as they are.
+fn render(template: &str, args: &[Text]) -> Text {
+    let mut text = Text::default();
+    let mut literal = String::new();
+    let mut rest = template;
+
+    while let Some(idx) = rest.find(['{', '}']) {
+        literal.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            literal.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let arg = rest[1..]
+            .split_once('}')
+            .and_then(|(n, _)| Some((n.len(), args.get(n.parse::<usize>().ok()?)?)));
+
+        match arg {
+            Some((len, arg)) => {
+                if !literal.is_empty() {
+                    text += Text::text(std::mem::take(&mut literal));
+                }
+
+                text += arg.clone();
+                rest = &rest[len + 2..];
+            }
+            None => {
+                literal.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    literal.push_str(rest);
+
+    if !literal.is_empty() {
+        text += Text::text(literal);
+    }
+
+    text
+}
+
+/// Queues a message for a client which is translated into the client's
+/// language by the [`LocalizationPlugin`] and sent in the same tick.
+///
+/// The first argument is the client, the second is the message key, and the
+/// remaining arguments are anything convertible into [`Text`] which replace
+/// the placeholders of the message.
+///
+/// ```
+/// # use valence::prelude::*;
+/// # use valence::msg;
+/// # fn f(client: &mut Client) {
+/// msg!(client, "shop.purchase", "an apple");
+/// # }
+/// ```
+///
+/// [`Text`]: crate::protocol::text::Text
+#[macro_export]
+macro_rules! msg {
+    ($client:expr, $key:expr $(, $arg:expr)* $(,)?) => {
+        $client.send_localized_message(
+            $key,
+            ::std::vec![$(::std::convert::Into::<$crate::protocol::text::Text>::into($arg)),*],
+        )
+    };
+}
+
+fn send_localized_messages(localization: Res<Localization>, mut clients: Query<&mut Client>) {
+    for mut client in &mut clients {
+        if !client.has_localized_messages() {
+            continue;
+        }
+
+        for (key, args) in client.take_localized_messages() {
+            let msg = localization.translate(client.locale(), &key, &args);
+            client.send_message(msg);
+        }
+    }
+}
+
+/// A message key and its arguments queued with [`msg!`](crate::msg).
+pub(crate) type LocalizedMessage = (Cow<'static, str>, Vec<Text>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_fallback() {
+        let mut en_us = LocaleBundle::new();
+        en_us.insert("greeting", "Hello");
+        en_us.insert("farewell", "Bye");
+
+        let mut de_de = LocaleBundle::new();
+        de_de.insert("greeting", "Hallo");
+
+        let mut localization = Localization::new();
+        localization.add_bundle("en_US", en_us);
+        localization.add_bundle("de_de", de_de);
+
+        assert_eq!(localization.template("de_de", "greeting"), Some("Hallo"));
+        assert_eq!(localization.template("de_at", "greeting"), Some("Hallo"));
+        assert_eq!(localization.template("de_de", "farewell"), Some("Bye"));
+        assert_eq!(localization.template("fr_fr", "greeting"), Some("Hello"));
+        assert_eq!(localization.template("fr_fr", "missing"), None);
+    }
+
+    #[test]
+    fn render_placeholders() {
+        let args = ["apple".into(), "3".into()];
+
+        let rendered = render("{1}x {0} for {{5}} {2}", &args);
+
+        assert_eq!(rendered.to_string(), "3x apple for {5} {2}");
+    }
+}
@@ -35,6 +35,36 @@ impl Aabb {
             },
         }
     }
+
+    /// Returns the squared distance from `p` to the closest point in the box,
+    /// which is zero if the box contains `p`.
+    pub fn distance_squared_to_point(&self, p: DVec3) -> f64 {
+        p.clamp(self.min, self.max).distance_squared(p)
+    }
+
+    /// Returns if this box and `other` overlap. Boxes which only touch do not
+    /// overlap.
+    pub fn intersects(&self, other: Aabb) -> bool {
+        self.min.cmplt(other.max).all() && other.min.cmplt(self.max).all()
+    }
+
+    /// Returns the distance along the ray at which it enters the box, or
+    /// `None` if the ray misses. The distance is measured in multiples of
+    /// `direction`, so it is in blocks if `direction` is normalized. Rays
+    /// starting inside the box hit at a distance of zero.
+    pub fn ray_intersection(&self, origin: DVec3, direction: DVec3) -> Option<f64> {
+        let inv = direction.recip();
+
+        let t0 = (self.min - origin) * inv;
+        let t1 = (self.max - origin) * inv;
+
+        // NaNs from zero direction components on the faces of the box are
+        // ignored by `min` and `max`.
+        let near = t0.min(t1).max_element().max(0.0);
+        let far = t0.max(t1).min_element();
+
+        (near <= far).then_some(near)
+    }
 }
 
 /// Takes a normalized direction vector and returns a `(yaw, pitch)` tuple in
@@ -74,6 +104,39 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn aabb_ray_intersection() {
+        let aabb = Aabb::new([0.0, 0.0, 0.0], [1.0, 2.0, 1.0]);
+
+        let hit = aabb.ray_intersection(DVec3::new(-2.0, 1.0, 0.5), DVec3::X);
+        assert_eq!(hit, Some(2.0));
+
+        // Starting inside the box.
+        let hit = aabb.ray_intersection(DVec3::new(0.5, 1.0, 0.5), DVec3::NEG_Y);
+        assert_eq!(hit, Some(0.0));
+
+        // Pointing away from the box.
+        assert_eq!(
+            aabb.ray_intersection(DVec3::new(-2.0, 1.0, 0.5), DVec3::NEG_X),
+            None
+        );
+
+        // Parallel to the box but outside of it.
+        assert_eq!(
+            aabb.ray_intersection(DVec3::new(-2.0, 3.0, 0.5), DVec3::X),
+            None
+        );
+
+        assert_eq!(
+            aabb.distance_squared_to_point(DVec3::new(3.0, 1.0, 0.5)),
+            4.0
+        );
+        assert_eq!(
+            aabb.distance_squared_to_point(DVec3::new(0.5, 1.0, 0.5)),
+            0.0
+        );
+    }
+
     #[test]
     fn yaw_pitch_round_trip() {
         for _ in 0..=100 {
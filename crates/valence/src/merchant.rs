@@ -0,0 +1,592 @@
+//! The trading screen of villagers and wandering traders.
+//!
+//! Trade offers are defined with a [`Merchant`] component on an entity with an
+//! [`Inventory`] of kind [`InventoryKind::Merchant`]. When clients open the
+//! inventory, the [`MerchantPlugin`] sends them the offers. The offer whose
+//! inputs are placed in the two input slots is put in the output slot, and
+//! taking the output sends a [`TradePerformed`] event after increasing the
+//! uses of the offer.
+//!
+//! Clicking an offer in the list sends a [`TradeSelected`] event. Unlike the
+//! vanilla server, the plugin does not move the inputs of the selected offer
+//! from the inventory of the client into the input slots.
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use valence_protocol::packets::s2c::play::MerchantOffers;
+use valence_protocol::types::MerchantTrade;
+use valence_protocol::{ItemStack, VarInt};
+
+use crate::client::event::{ClickContainer, SelectTrade};
+use crate::client::Client;
+use crate::inventory::{
+    handle_click_container, update_open_inventories, Inventory, InventoryKind, OpenInventory,
+};
+
+// The slots of merchant inventories.
+const FIRST_INPUT_SLOT: u16 = 0;
+const SECOND_INPUT_SLOT: u16 = 1;
+const OUTPUT_SLOT: u16 = 2;
+
+/// Adds the [`TradeSelected`] and [`TradePerformed`] events and the systems
+/// for trading with [`Merchant`]s.
+pub struct MerchantPlugin;
+
+impl Plugin for MerchantPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TradeSelected>()
+            .add_event::<TradePerformed>()
+            .add_system_to_stage(CoreStage::Update, select_trades)
+            .add_system_to_stage(CoreStage::Update, perform_trades)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_trade_output
+                    .after(handle_click_container)
+                    .before(update_open_inventories),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                send_offers
+                    .after(update_open_inventories)
+                    .before("valence_core"),
+            );
+    }
+}
+
+/// A component for the trade offers of a merchant inventory.
+#[derive(Component, Clone, Debug)]
+pub struct Merchant {
+    offers: Vec<TradeOffer>,
+    level: i32,
+    experience: i32,
+    is_regular_villager: bool,
+    can_restock: bool,
+    /// The offer last selected by a client.
+    selected_offer: Option<usize>,
+    /// The offer whose output is in the output slot.
+    output_offer: Option<usize>,
+    modified: bool,
+}
+
+impl Merchant {
+    /// Creates a novice villager with the given offers.
+    pub fn new(offers: Vec<TradeOffer>) -> Self {
+        Self {
+            offers,
+            level: 1,
+            experience: 0,
+            is_regular_villager: true,
+            can_restock: true,
+            selected_offer: None,
+            output_offer: None,
+            modified: true,
+        }
+    }
+
+    pub fn offers(&self) -> &[TradeOffer] {
+        &self.offers
+    }
+
+    /// Returns the offers of this merchant. Changes are sent to all clients
+    /// trading with this merchant.
+    pub fn offers_mut(&mut self) -> &mut Vec<TradeOffer> {
+        self.modified = true;
+        &mut self.offers
+    }
+
+    pub fn level(&self) -> i32 {
+        self.level
+    }
+
+    /// Sets the level of the villager from 1 (novice) to 5 (master), which is
+    /// shown in the title of the trading screen.
+    pub fn set_level(&mut self, level: i32) {
+        self.modified |= self.level != level;
+        self.level = level;
+    }
+
+    pub fn experience(&self) -> i32 {
+        self.experience
+    }
+
+    /// Sets the total experience of the villager, which is shown by the
+    /// experience bar of the trading screen.
+    pub fn set_experience(&mut self, experience: i32) {
+        self.modified |= self.experience != experience;
+        self.experience = experience;
+    }
+
+    pub fn is_regular_villager(&self) -> bool {
+        self.is_regular_villager
+    }
+
+    /// Sets whether the level and experience bar are shown. Wandering traders
+    /// are not regular villagers.
+    pub fn set_regular_villager(&mut self, is_regular_villager: bool) {
+        self.modified |= self.is_regular_villager != is_regular_villager;
+        self.is_regular_villager = is_regular_villager;
+    }
+
+    pub fn can_restock(&self) -> bool {
+        self.can_restock
+    }
+
+    /// Sets whether the tooltip of offers which are used up tells clients
+    /// that the villager restocks.
+    pub fn set_can_restock(&mut self, can_restock: bool) {
+        self.modified |= self.can_restock != can_restock;
+        self.can_restock = can_restock;
+    }
+
+    /// Returns the index of the offer last selected by a client.
+    pub fn selected_offer(&self) -> Option<usize> {
+        self.selected_offer
+    }
+
+    /// Finds the offer which can be bought with the given inputs, preferring
+    /// the selected offer.
+    fn find_offer(&self, first: Option<&ItemStack>, second: Option<&ItemStack>) -> Option<usize> {
+        self.selected_offer
+            .into_iter()
+            .chain(0..self.offers.len())
+            .find(|&idx| {
+                self.offers
+                    .get(idx)
+                    .map_or(false, |offer| offer.is_satisfied_by(first, second))
+            })
+    }
+}
+
+/// An offer of a [`Merchant`], which exchanges one or two input items for an
+/// output item.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TradeOffer {
+    pub first_input: ItemStack,
+    pub second_input: Option<ItemStack>,
+    pub output: ItemStack,
+    /// How often this offer was used.
+    pub uses: i32,
+    /// How often this offer can be used until it is disabled.
+    pub max_uses: i32,
+    /// The experience the villager gains from this offer.
+    pub xp: i32,
+    /// Added to the count of the first input, like the discount of villagers
+    /// after they were cured.
+    pub special_price: i32,
+    /// Scales the demand added to the count of the first input.
+    pub price_multiplier: f32,
+    pub demand: i32,
+}
+
+impl TradeOffer {
+    /// Creates an offer with the defaults of most vanilla offers.
+    pub fn new(first_input: ItemStack, output: ItemStack) -> Self {
+        Self {
+            first_input,
+            second_input: None,
+            output,
+            uses: 0,
+            max_uses: 12,
+            xp: 1,
+            special_price: 0,
+            price_multiplier: 0.05,
+            demand: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn with_second_input(mut self, second_input: ItemStack) -> Self {
+        self.second_input = Some(second_input);
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_uses(mut self, max_uses: i32) -> Self {
+        self.max_uses = max_uses;
+        self
+    }
+
+    #[must_use]
+    pub fn with_xp(mut self, xp: i32) -> Self {
+        self.xp = xp;
+        self
+    }
+
+    #[must_use]
+    pub fn with_special_price(mut self, special_price: i32) -> Self {
+        self.special_price = special_price;
+        self
+    }
+
+    #[must_use]
+    pub fn with_price_multiplier(mut self, price_multiplier: f32) -> Self {
+        self.price_multiplier = price_multiplier;
+        self
+    }
+
+    #[must_use]
+    pub fn with_demand(mut self, demand: i32) -> Self {
+        self.demand = demand;
+        self
+    }
+
+    /// Whether this offer is used up.
+    pub fn is_disabled(&self) -> bool {
+        self.uses >= self.max_uses
+    }
+
+    /// The count of the first input after the demand and special price are
+    /// applied, like it is shown to clients.
+    pub fn price(&self) -> u8 {
+        let base = self.first_input.count() as i32;
+        let demand = ((base * self.demand) as f32 * self.price_multiplier)
+            .floor()
+            .max(0.0) as i32;
+
+        (base + demand + self.special_price).clamp(1, self.first_input.item.max_stack() as i32)
+            as u8
+    }
+
+    fn is_satisfied_by(&self, first: Option<&ItemStack>, second: Option<&ItemStack>) -> bool {
+        fn satisfies(offered: Option<&ItemStack>, required: &ItemStack, count: u8) -> bool {
+            offered.map_or(false, |stack| {
+                stack.item == required.item
+                    && (required.nbt.is_none() || stack.nbt == required.nbt)
+                    && stack.count() >= count
+            })
+        }
+
+        !self.is_disabled()
+            && satisfies(first, &self.first_input, self.price())
+            && match &self.second_input {
+                Some(required) => satisfies(second, required, required.count()),
+                None => second.is_none(),
+            }
+    }
+
+    fn to_trade(&self) -> MerchantTrade {
+        MerchantTrade {
+            input_one: Some(self.first_input.clone()),
+            output_item: Some(self.output.clone()),
+            input_two: self.second_input.clone(),
+            trade_disabled: self.is_disabled(),
+            number_of_trade_uses: self.uses,
+            max_trade_uses: self.max_uses,
+            xp: self.xp,
+            special_price: self.special_price,
+            price_multiplier: self.price_multiplier,
+            demand: self.demand,
+        }
+    }
+}
+
+/// Sent when a client selects an offer in the list of a [`Merchant`].
+#[derive(Clone, Debug)]
+pub struct TradeSelected {
+    pub client: Entity,
+    /// The entity with the [`Merchant`] component.
+    pub merchant: Entity,
+    /// The index of the selected offer.
+    pub offer: usize,
+}
+
+/// Sent when a client takes the output of an offer. The uses of the offer are
+/// already increased.
+#[derive(Clone, Debug)]
+pub struct TradePerformed {
+    pub client: Entity,
+    /// The entity with the [`Merchant`] component.
+    pub merchant: Entity,
+    /// The index of the offer.
+    pub offer: usize,
+    /// How often the offer was used at once, which is more than once if the
+    /// output was shift clicked.
+    pub count: i32,
+}
+
+fn select_trades(
+    clients: Query<&OpenInventory, With<Client>>,
+    mut merchants: Query<&mut Merchant>,
+    mut events: EventReader<SelectTrade>,
+    mut selected: EventWriter<TradeSelected>,
+) {
+    for event in events.iter() {
+        let Ok(open_inventory) = clients.get(event.client) else {
+            continue
+        };
+
+        let Ok(mut merchant) = merchants.get_mut(open_inventory.entity()) else {
+            continue
+        };
+
+        let Ok(offer) = usize::try_from(event.slot) else {
+            continue
+        };
+
+        if offer >= merchant.offers.len() {
+            continue;
+        }
+
+        merchant.selected_offer = Some(offer);
+
+        selected.send(TradeSelected {
+            client: event.client,
+            merchant: open_inventory.entity(),
+            offer,
+        });
+    }
+}
+
+/// Detects clients taking the output before the inventory is changed by the
+/// click.
+fn perform_trades(
+    clients: Query<(&Client, &OpenInventory)>,
+    mut merchants: Query<(&mut Merchant, &Inventory)>,
+    mut events: EventReader<ClickContainer>,
+    mut performed: EventWriter<TradePerformed>,
+) {
+    for event in events.iter() {
+        if event.slot_id != OUTPUT_SLOT as i16 {
+            continue;
+        }
+
+        let Ok((client, open_inventory)) = clients.get(event.client) else {
+            continue
+        };
+
+        // Clicks with an outdated state are ignored by the inventory.
+        if event.window_id != client.window_id || event.state_id != client.inventory_state_id.0 {
+            continue;
+        }
+
+        let Ok((mut merchant, inventory)) = merchants.get_mut(open_inventory.entity()) else {
+            continue
+        };
+
+        if inventory.kind() != InventoryKind::Merchant {
+            continue;
+        }
+
+        let Some(idx) = merchant.output_offer else {
+            continue
+        };
+
+        let Some(new_first) = event
+            .slot_changes
+            .iter()
+            .find(|(slot, _)| *slot == FIRST_INPUT_SLOT as i16)
+            .map(|(_, stack)| stack.as_ref())
+        else {
+            continue
+        };
+
+        let old_count = inventory.slot(FIRST_INPUT_SLOT).map_or(0, |s| s.count()) as i32;
+        let new_count = new_first.map_or(0, |s| s.count()) as i32;
+
+        let Some(offer) = merchant.offers.get_mut(idx) else {
+            continue
+        };
+
+        let count = (old_count - new_count) / offer.price() as i32;
+
+        if count <= 0 {
+            continue;
+        }
+
+        // Clients count the uses themselves, so the offers are not sent again.
+        offer.uses = (offer.uses + count).min(offer.max_uses);
+
+        performed.send(TradePerformed {
+            client: event.client,
+            merchant: open_inventory.entity(),
+            offer: idx,
+            count,
+        });
+    }
+}
+
+fn update_trade_output(
+    mut merchants: Query<(Entity, &mut Merchant, &mut Inventory)>,
+    mut viewers: Query<&mut OpenInventory>,
+) {
+    for (entity, mut merchant, mut inventory) in &mut merchants {
+        if inventory.kind() != InventoryKind::Merchant {
+            continue;
+        }
+
+        let offer = merchant.find_offer(
+            inventory.slot(FIRST_INPUT_SLOT),
+            inventory.slot(SECOND_INPUT_SLOT),
+        );
+
+        merchant.output_offer = offer;
+
+        let output = offer.map(|idx| merchant.offers[idx].output.clone());
+
+        if inventory.slot(OUTPUT_SLOT) != output.as_ref() {
+            inventory.replace_slot(OUTPUT_SLOT, output);
+
+            // The client taking the output changed the slot too, so the new
+            // output would not be sent to it otherwise.
+            for mut open_inventory in &mut viewers {
+                if open_inventory.entity() == entity {
+                    open_inventory.resend_slot(OUTPUT_SLOT);
+                }
+            }
+        }
+    }
+}
+
+fn send_offers(
+    mut clients: Query<(&mut Client, &OpenInventory, ChangeTrackers<OpenInventory>)>,
+    mut merchants: Query<&mut Merchant>,
+) {
+    for (mut client, open_inventory, open_tracker) in &mut clients {
+        let Ok(merchant) = merchants.get(open_inventory.entity()) else {
+            continue
+        };
+
+        if open_tracker.is_added() || merchant.modified {
+            let window_id = VarInt(client.window_id.into());
+
+            client.write_packet(&MerchantOffers {
+                window_id,
+                trades: merchant.offers.iter().map(TradeOffer::to_trade).collect(),
+                villager_level: VarInt(merchant.level),
+                experience: VarInt(merchant.experience),
+                is_regular_villager: merchant.is_regular_villager,
+                can_restock: merchant.can_restock,
+            });
+        }
+    }
+
+    for mut merchant in &mut merchants {
+        if merchant.modified {
+            merchant.modified = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::c2s::play::ClickContainer as ClickContainerPacket;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::types::ClickContainerMode;
+    use valence_protocol::ItemKind;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+    use crate::{assert_packet_count, assert_packet_order};
+
+    #[test]
+    fn price_with_demand() {
+        let offer = TradeOffer::new(
+            ItemStack::new(ItemKind::Emerald, 10, None),
+            ItemStack::new(ItemKind::Bread, 6, None),
+        )
+        .with_demand(4)
+        .with_special_price(-3);
+
+        assert_eq!(offer.price(), 9);
+        assert!(offer.is_satisfied_by(Some(&ItemStack::new(ItemKind::Emerald, 9, None)), None));
+        assert!(!offer.is_satisfied_by(Some(&ItemStack::new(ItemKind::Emerald, 8, None)), None));
+    }
+
+    #[test]
+    fn trade_with_merchant() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(MerchantPlugin);
+
+        let offer = TradeOffer::new(
+            ItemStack::new(ItemKind::Emerald, 1, None),
+            ItemStack::new(ItemKind::Bread, 6, None),
+        );
+
+        let merchant_ent = app
+            .world
+            .spawn((
+                Inventory::new(InventoryKind::Merchant),
+                Merchant::new(vec![offer.clone()]),
+            ))
+            .id();
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_sent();
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(OpenInventory::new(merchant_ent));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::MerchantOffers(_));
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::OpenScreen(_),
+            S2cPlayPacket::MerchantOffers(_)
+        );
+
+        // Placing the input shows the output.
+        app.world
+            .get_mut::<Inventory>(merchant_ent)
+            .unwrap()
+            .replace_slot(FIRST_INPUT_SLOT, ItemStack::new(ItemKind::Emerald, 3, None));
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(merchant_ent).unwrap();
+        assert_eq!(inventory.slot(OUTPUT_SLOT), Some(&offer.output));
+
+        client_helper.clear_sent();
+
+        // Take the output.
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        let window_id = client.window_id;
+        let state_id = client.inventory_state_id.0;
+
+        client_helper.send(&ClickContainerPacket {
+            window_id,
+            button: 0,
+            mode: ClickContainerMode::Click,
+            state_id: VarInt(state_id),
+            slot_idx: OUTPUT_SLOT as i16,
+            slots: vec![
+                (
+                    FIRST_INPUT_SLOT as i16,
+                    Some(ItemStack::new(ItemKind::Emerald, 2, None)),
+                ),
+                (OUTPUT_SLOT as i16, None),
+            ],
+            carried_item: Some(offer.output.clone()),
+        });
+
+        app.update();
+
+        let merchant = app.world.get::<Merchant>(merchant_ent).unwrap();
+        assert_eq!(merchant.offers()[0].uses, 1);
+
+        let events = app.world.resource::<Events<TradePerformed>>();
+        let performed: Vec<_> = events.get_reader().iter(events).cloned().collect();
+        assert_eq!(performed.len(), 1);
+        assert_eq!(performed[0].offer, 0);
+        assert_eq!(performed[0].count, 1);
+
+        // The output is shown again for the remaining input, including to the
+        // client which took the old output.
+        let inventory = app.world.get::<Inventory>(merchant_ent).unwrap();
+        assert_eq!(inventory.slot(OUTPUT_SLOT), Some(&offer.output));
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert!(sent_packets.iter().any(|pkt| matches!(
+            pkt,
+            S2cPlayPacket::SetContainerSlot(pkt)
+                if pkt.slot_idx == OUTPUT_SLOT as i16
+                    && pkt.slot_data.as_ref() == Some(&offer.output)
+        )));
+
+        Ok(())
+    }
+}
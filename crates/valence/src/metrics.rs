@@ -0,0 +1,403 @@
+//! Server metrics for monitoring in production.
+//!
+//! The [`MetricsPlugin`] measures the duration of ticks, the number of players
+//! online and chunks loaded, and the traffic of all clients. The measurements
+//! are kept in the [`ServerMetrics`] resource and passed to the
+//! [`MetricsRecorder`]s in the [`MetricsRecorders`] resource once per second,
+//! so they can be forwarded to any monitoring system.
+//!
+//! The plugin can also serve the metrics in the Prometheus text format over
+//! HTTP with [`MetricsPlugin::with_prometheus_address`]. Every path serves the
+//! metrics, so Prometheus can scrape e.g. `http://localhost:9100/metrics`.
+//!
+//! ```no_run
+//! use valence::metrics::MetricsPlugin;
+//! use valence::prelude::*;
+//!
+//! let mut app = App::new();
+//! app.add_plugin(ServerPlugin::new(()))
+//!     .add_plugin(MetricsPlugin::new().with_prometheus_address(([0, 0, 0, 0], 9100)));
+//! ```
+
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bevy_app::{App, CoreStage, Plugin, StartupStage};
+use bevy_ecs::prelude::*;
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::error;
+
+use crate::client::Client;
+use crate::instance::Instance;
+use crate::server::{spawn_task, Server, ShutdownHooks};
+
+/// Adds the [`ServerMetrics`] and [`MetricsRecorders`] resources and the
+/// systems measuring the server.
+#[derive(Clone, Default)]
+pub struct MetricsPlugin {
+    prometheus_address: Option<SocketAddr>,
+}
+
+impl MetricsPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves the metrics in the Prometheus text format at `address`.
+    #[must_use]
+    pub fn with_prometheus_address(mut self, address: impl Into<SocketAddr>) -> Self {
+        self.prometheus_address = Some(address.into());
+        self
+    }
+}
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        let mut recorders = MetricsRecorders::default();
+
+        if let Some(address) = self.prometheus_address {
+            let text = Arc::new(Mutex::new(String::new()));
+            recorders.add(PrometheusRecorder { text: text.clone() });
+
+            app.add_startup_system_to_stage(
+                StartupStage::PostStartup,
+                move |server: Res<Server>, mut hooks: ResMut<ShutdownHooks>| {
+                    let _guard = server.tokio_handle().enter();
+                    let task = spawn_task("prometheus", serve_prometheus(address, text.clone()));
+
+                    // Stop listening for scrapes when the server shuts down.
+                    hooks.add(move |_| task.abort());
+                },
+            );
+        }
+
+        app.init_resource::<ServerMetrics>()
+            .insert_resource(recorders)
+            .insert_resource(TickStart(Instant::now()))
+            .init_resource::<MetricsWindow>()
+            .add_system_to_stage(CoreStage::First, start_tick)
+            .add_system_to_stage(CoreStage::PostUpdate, measure_tick.after("valence_core"))
+            .add_system_to_stage(CoreStage::PostUpdate, record_metrics.after(measure_tick));
+    }
+}
+
+/// Measurements of the server, updated once per second. The totals count
+/// everything since the plugin was added.
+#[derive(Resource, Clone, Default, Debug)]
+pub struct ServerMetrics {
+    /// The average time spent running a tick during the last second.
+    pub tick_duration: Duration,
+    /// The longest tick during the last second.
+    pub max_tick_duration: Duration,
+    pub players_online: usize,
+    /// The number of chunks loaded in all instances.
+    pub chunks_loaded: usize,
+    pub packets_sent_per_second: u64,
+    pub packets_received_per_second: u64,
+    /// The bytes sent to clients during the last second, after compression
+    /// and encryption.
+    pub bytes_sent_per_second: u64,
+    pub bytes_received_per_second: u64,
+    pub packets_sent_total: u64,
+    pub packets_received_total: u64,
+    pub bytes_sent_total: u64,
+    pub bytes_received_total: u64,
+}
+
+/// Receives the [`ServerMetrics`] once per second.
+pub trait MetricsRecorder: Send + Sync + 'static {
+    fn record(&mut self, metrics: &ServerMetrics);
+}
+
+impl<F> MetricsRecorder for F
+where
+    F: FnMut(&ServerMetrics) + Send + Sync + 'static,
+{
+    fn record(&mut self, metrics: &ServerMetrics) {
+        self(metrics)
+    }
+}
+
+/// The recorders the [`MetricsPlugin`] passes the metrics to.
+///
+/// ```
+/// use valence::metrics::{MetricsRecorders, ServerMetrics};
+/// use valence::prelude::*;
+///
+/// fn setup(mut recorders: ResMut<MetricsRecorders>) {
+///     recorders.add(|metrics: &ServerMetrics| {
+///         println!("{} players online", metrics.players_online);
+///     });
+/// }
+/// ```
+#[derive(Resource, Default)]
+pub struct MetricsRecorders(Vec<Box<dyn MetricsRecorder>>);
+
+impl MetricsRecorders {
+    pub fn add(&mut self, recorder: impl MetricsRecorder) {
+        self.0.push(Box::new(recorder));
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Resource)]
+struct TickStart(Instant);
+
+/// The measurements of the current second.
+#[derive(Resource, Default)]
+struct MetricsWindow {
+    ticks: u32,
+    tick_duration: Duration,
+    max_tick_duration: Duration,
+    packets_sent: u64,
+    packets_received: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+/// The traffic counters of a client the last time they were counted.
+#[derive(Copy, Clone, Default)]
+struct Traffic {
+    packets_sent: u64,
+    packets_received: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+fn start_tick(mut tick_start: ResMut<TickStart>) {
+    tick_start.0 = Instant::now();
+}
+
+/// Adds the duration of the tick and the traffic of all clients since the
+/// last tick to the window. This runs after the packets are flushed but before
+/// disconnected clients are despawned, so the traffic of every tick is
+/// counted. The rest of the tick is short enough not to be measured.
+fn measure_tick(
+    clients: Query<(Entity, &Client)>,
+    mut last_traffic: Local<FxHashMap<Entity, Traffic>>,
+    mut window: ResMut<MetricsWindow>,
+    tick_start: Res<TickStart>,
+) {
+    let mut traffic = FxHashMap::default();
+
+    for (entity, client) in &clients {
+        let current = Traffic {
+            packets_sent: client.packets_sent(),
+            packets_received: client.packets_received(),
+            bytes_sent: client.bytes_sent(),
+            bytes_received: client.bytes_received(),
+        };
+
+        let last = last_traffic.get(&entity).copied().unwrap_or_default();
+
+        window.packets_sent += current.packets_sent - last.packets_sent;
+        window.packets_received += current.packets_received - last.packets_received;
+        window.bytes_sent += current.bytes_sent - last.bytes_sent;
+        window.bytes_received += current.bytes_received - last.bytes_received;
+
+        traffic.insert(entity, current);
+    }
+
+    *last_traffic = traffic;
+
+    let tick_duration = tick_start.0.elapsed();
+    window.ticks += 1;
+    window.tick_duration += tick_duration;
+    window.max_tick_duration = window.max_tick_duration.max(tick_duration);
+}
+
+fn record_metrics(
+    server: Res<Server>,
+    mut window: ResMut<MetricsWindow>,
+    mut metrics: ResMut<ServerMetrics>,
+    mut recorders: ResMut<MetricsRecorders>,
+    clients: Query<&Client>,
+    instances: Query<&Instance>,
+) {
+    if server.current_tick() % server.tps() != 0 {
+        return;
+    }
+
+    let window = std::mem::take(&mut *window);
+
+    metrics.tick_duration = window.tick_duration / window.ticks.max(1);
+    metrics.max_tick_duration = window.max_tick_duration;
+    metrics.players_online = clients.iter().filter(|c| !c.is_disconnected()).count();
+    metrics.chunks_loaded = instances.iter().map(|i| i.chunks().count()).sum();
+    metrics.packets_sent_per_second = window.packets_sent;
+    metrics.packets_received_per_second = window.packets_received;
+    metrics.bytes_sent_per_second = window.bytes_sent;
+    metrics.bytes_received_per_second = window.bytes_received;
+    metrics.packets_sent_total += window.packets_sent;
+    metrics.packets_received_total += window.packets_received;
+    metrics.bytes_sent_total += window.bytes_sent;
+    metrics.bytes_received_total += window.bytes_received;
+
+    for recorder in &mut recorders.0 {
+        recorder.record(&metrics);
+    }
+}
+
+/// Renders the metrics in the Prometheus text format for the HTTP endpoint.
+struct PrometheusRecorder {
+    text: Arc<Mutex<String>>,
+}
+
+impl MetricsRecorder for PrometheusRecorder {
+    fn record(&mut self, metrics: &ServerMetrics) {
+        *self.text.lock() = prometheus_text(metrics);
+    }
+}
+
+fn prometheus_text(metrics: &ServerMetrics) -> String {
+    let mut text = String::new();
+
+    let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+        let _ = write!(
+            text,
+            "# HELP valence_{name} {help}\n# TYPE valence_{name} {kind}\nvalence_{name} {value}\n"
+        );
+    };
+
+    metric(
+        "tick_duration_seconds",
+        "gauge",
+        "The average duration of a tick during the last second.",
+        metrics.tick_duration.as_secs_f64(),
+    );
+    metric(
+        "max_tick_duration_seconds",
+        "gauge",
+        "The longest tick during the last second.",
+        metrics.max_tick_duration.as_secs_f64(),
+    );
+    metric(
+        "players_online",
+        "gauge",
+        "The number of players online.",
+        metrics.players_online as f64,
+    );
+    metric(
+        "chunks_loaded",
+        "gauge",
+        "The number of chunks loaded in all instances.",
+        metrics.chunks_loaded as f64,
+    );
+    metric(
+        "packets_sent_total",
+        "counter",
+        "The number of packets sent to clients.",
+        metrics.packets_sent_total as f64,
+    );
+    metric(
+        "packets_received_total",
+        "counter",
+        "The number of packets received from clients.",
+        metrics.packets_received_total as f64,
+    );
+    metric(
+        "bytes_sent_total",
+        "counter",
+        "The number of bytes sent to clients.",
+        metrics.bytes_sent_total as f64,
+    );
+    metric(
+        "bytes_received_total",
+        "counter",
+        "The number of bytes received from clients.",
+        metrics.bytes_received_total as f64,
+    );
+
+    text
+}
+
+/// The time a Prometheus connection has to send its request before it is
+/// closed.
+const PROMETHEUS_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the last metrics rendered by the [`PrometheusRecorder`] to every
+/// HTTP request.
+async fn serve_prometheus(address: SocketAddr, text: Arc<Mutex<String>>) {
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("failed to start Prometheus endpoint: {e}");
+            return;
+        }
+    };
+
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                error!("failed to accept Prometheus connection: {e}");
+                continue;
+            }
+        };
+
+        let body = text.lock().clone();
+
+        spawn_task("prometheus_request", async move {
+            // The request is read but ignored, since every path is served the
+            // same metrics.
+            let mut request = [0; 1024];
+            let read = tokio::time::timeout(PROMETHEUS_READ_TIMEOUT, stream.read(&mut request));
+
+            if read.await.is_err() {
+                return;
+            }
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: \
+                 {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn metrics_recorded_every_second() {
+        let mut app = App::new();
+        let (_client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(MetricsPlugin::new());
+
+        let recorded = Arc::new(Mutex::new(vec![]));
+        let recorded_clone = recorded.clone();
+        app.world
+            .resource_mut::<MetricsRecorders>()
+            .add(move |metrics: &ServerMetrics| recorded_clone.lock().push(metrics.clone()));
+
+        for _ in 0..app.world.resource::<Server>().tps() {
+            app.update();
+        }
+
+        let recorded = recorded.lock();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].players_online, 1);
+        assert!(recorded[0].packets_sent_total > 0);
+        assert!(recorded[0].bytes_sent_total > 0);
+
+        let text = prometheus_text(&recorded[0]);
+        assert!(text.contains("# TYPE valence_players_online gauge\nvalence_players_online 1\n"));
+    }
+}
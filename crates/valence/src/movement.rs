@@ -0,0 +1,427 @@
+//! Movement abilities beyond vanilla movement.
+//!
+//! The [`MovementPlugin`] implements these abilities for clients with the
+//! corresponding component:
+//!
+//! - [`DoubleJump`]: Clients are allowed to fly while on the ground. When they
+//!   double tap the jump key to start flying, the flight is cancelled and they
+//!   are launched forward and up instead.
+//! - [`GrapplingHook`]: Using a fishing rod throws a hook which sticks to the
+//!   first block it hits. Using the rod again pulls the client towards the
+//!   hook.
+//!
+//! Dashes can be triggered from any system with [`dash`].
+//!
+//! Abilities are only usable again after their cooldown has passed. The
+//! cooldowns are tracked by the [`AbilityCooldowns`] component, which the
+//! abilities above use if it is present on the client entity. It can be used
+//! for custom abilities as well.
+//!
+//! ```
+//! use valence::movement::{dash, AbilityCooldowns, DoubleJump};
+//! use valence::prelude::*;
+//!
+//! fn setup_clients(mut commands: Commands, clients: Query<Entity, Added<Client>>) {
+//!     for entity in &clients {
+//!         commands
+//!             .entity(entity)
+//!             .insert((DoubleJump::default(), AbilityCooldowns::default()));
+//!     }
+//! }
+//!
+//! fn dash_on_sprint(
+//!     server: Res<Server>,
+//!     mut clients: Query<(&mut Client, &mut AbilityCooldowns)>,
+//!     mut events: EventReader<StartSprinting>,
+//! ) {
+//!     for event in events.iter() {
+//!         if let Ok((mut client, mut cooldowns)) = clients.get_mut(event.client) {
+//!             if cooldowns.try_start("dash", server.current_tick(), 40) {
+//!                 dash(&mut client, 30.0);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::borrow::Cow;
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::{DVec3, Vec3};
+use rustc_hash::FxHashMap;
+use valence_protocol::types::{GameMode, Hand};
+use valence_protocol::{BlockPos, ItemKind};
+
+use crate::client::event::{StartFlying, UseItem};
+use crate::client::Client;
+use crate::entity::{EntityKind, McEntity};
+use crate::instance::Instance;
+use crate::inventory::Inventory;
+use crate::server::Server;
+use crate::Despawned;
+
+/// The name of the [`DoubleJump`] cooldown in [`AbilityCooldowns`].
+pub const DOUBLE_JUMP: &str = "double_jump";
+/// The name of the [`GrapplingHook`] cooldown in [`AbilityCooldowns`].
+pub const GRAPPLING_HOOK: &str = "grappling_hook";
+
+/// The distance from their owner at which grappling hooks are removed.
+pub const MAX_HOOK_DISTANCE: f64 = 48.0;
+
+/// The acceleration of thrown grappling hooks in blocks per second squared.
+const HOOK_GRAVITY: f32 = 12.0;
+
+/// Adds the systems for the [`DoubleJump`] and [`GrapplingHook`] abilities.
+pub struct MovementPlugin;
+
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_double_jumps.before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            use_grappling_hooks.before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            move_hooks.after(use_grappling_hooks).before("valence_core"),
+        );
+    }
+}
+
+/// A component for client entities which keeps track of when their
+/// abilities can be used again.
+///
+/// Cooldowns are identified by name and measured in ticks of
+/// [`Server::current_tick`].
+#[derive(Component, Clone, Default, Debug)]
+pub struct AbilityCooldowns {
+    ready_ticks: FxHashMap<Cow<'static, str>, i64>,
+}
+
+impl AbilityCooldowns {
+    /// If the cooldown of `ability` has passed at `current_tick`.
+    pub fn is_ready(&self, ability: &str, current_tick: i64) -> bool {
+        self.remaining(ability, current_tick) == 0
+    }
+
+    /// Returns the number of ticks until `ability` can be used again.
+    pub fn remaining(&self, ability: &str, current_tick: i64) -> i64 {
+        self.ready_ticks
+            .get(ability)
+            .map_or(0, |&ready| (ready - current_tick).max(0))
+    }
+
+    /// Starts a cooldown of `ticks` ticks for `ability`, replacing the
+    /// current one.
+    pub fn start(&mut self, ability: impl Into<Cow<'static, str>>, current_tick: i64, ticks: i64) {
+        self.ready_ticks.insert(ability.into(), current_tick + ticks);
+    }
+
+    /// Starts a cooldown for `ability` if it is ready. Returns whether the
+    /// ability is ready and can be used.
+    pub fn try_start(
+        &mut self,
+        ability: impl Into<Cow<'static, str>>,
+        current_tick: i64,
+        ticks: i64,
+    ) -> bool {
+        let ability = ability.into();
+
+        if !self.is_ready(&ability, current_tick) {
+            return false;
+        }
+
+        self.start(ability, current_tick, ticks);
+        true
+    }
+
+    /// Ends the cooldown of `ability` early.
+    pub fn reset(&mut self, ability: &str) {
+        self.ready_ticks.remove(ability);
+    }
+}
+
+/// Launches the client in the direction it is looking with `speed` in blocks
+/// per second.
+pub fn dash(client: &mut Client, speed: f32) {
+    let velocity = client.look_direction() * speed;
+    client.set_velocity(velocity);
+}
+
+/// A component for client entities which lets them jump a second time in the
+/// air by double tapping the jump key.
+///
+/// Only clients in survival and adventure mode can double jump, since the
+/// double tap makes clients in creative mode fly.
+#[derive(Component, Copy, Clone, Debug)]
+pub struct DoubleJump {
+    /// The horizontal speed of the jump in blocks per second.
+    pub horizontal: f32,
+    /// The vertical speed of the jump in blocks per second.
+    pub vertical: f32,
+    /// The number of ticks after a jump before the client can jump again. The
+    /// client also has to land first.
+    pub cooldown: i64,
+}
+
+impl Default for DoubleJump {
+    fn default() -> Self {
+        Self {
+            horizontal: 12.0,
+            vertical: 12.0,
+            cooldown: 0,
+        }
+    }
+}
+
+/// A component for client entities which turns fishing rods into grappling
+/// hooks.
+#[derive(Component, Clone, Debug)]
+pub struct GrapplingHook {
+    /// The speed of thrown hooks in blocks per second.
+    pub throw_speed: f32,
+    /// The speed the client is pulled towards the hook with in blocks per
+    /// second.
+    pub pull_speed: f32,
+    /// The number of ticks after a pull before the next hook can be thrown.
+    pub cooldown: i64,
+    hook: Option<Entity>,
+}
+
+impl GrapplingHook {
+    pub fn new(throw_speed: f32, pull_speed: f32, cooldown: i64) -> Self {
+        Self {
+            throw_speed,
+            pull_speed,
+            cooldown,
+            hook: None,
+        }
+    }
+
+    /// Returns the entity of the thrown hook, if any.
+    pub fn hook(&self) -> Option<Entity> {
+        self.hook
+    }
+}
+
+impl Default for GrapplingHook {
+    fn default() -> Self {
+        Self::new(30.0, 25.0, 20)
+    }
+}
+
+/// Returns the velocity to pull something at `from` towards `to` with
+/// `speed` in blocks per second. The velocity is slightly raised so that
+/// the pull clears the edges of blocks.
+pub fn grapple_velocity(from: DVec3, to: DVec3, speed: f32) -> Vec3 {
+    let direction = (to - from).as_vec3().normalize_or_zero();
+    direction * speed + Vec3::Y * speed * 0.25
+}
+
+/// A component for thrown grappling hooks.
+#[derive(Component)]
+struct Hook {
+    owner: Entity,
+    velocity: Vec3,
+    stuck: bool,
+}
+
+fn update_double_jumps(
+    server: Res<Server>,
+    mut clients: Query<(&mut Client, &DoubleJump, Option<&mut AbilityCooldowns>)>,
+    removed: RemovedComponents<DoubleJump>,
+    mut flying: Query<&mut Client, Without<DoubleJump>>,
+    mut events: EventReader<StartFlying>,
+) {
+    let current_tick = server.current_tick();
+
+    for entity in removed.iter() {
+        if let Ok(mut client) = flying.get_mut(entity) {
+            client.set_allow_flying(false);
+        }
+    }
+
+    for event in events.iter() {
+        let Ok((mut client, jump, cooldowns)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        if !matches!(client.game_mode(), GameMode::Survival | GameMode::Adventure) {
+            continue;
+        }
+
+        // Stops the flight.
+        client.set_allow_flying(false);
+
+        let horizontal = client.look_direction() * Vec3::new(1.0, 0.0, 1.0);
+        let velocity = horizontal.normalize_or_zero() * jump.horizontal + Vec3::Y * jump.vertical;
+        client.set_velocity(velocity);
+
+        if let Some(mut cooldowns) = cooldowns {
+            cooldowns.start(DOUBLE_JUMP, current_tick, jump.cooldown);
+        }
+    }
+
+    for (mut client, _, cooldowns) in &mut clients {
+        let ready = cooldowns.map_or(true, |c| c.is_ready(DOUBLE_JUMP, current_tick));
+
+        if client.on_ground() && ready && !client.allow_flying() {
+            client.set_allow_flying(true);
+        }
+    }
+}
+
+fn use_grappling_hooks(
+    mut commands: Commands,
+    server: Res<Server>,
+    mut clients: Query<(
+        &mut Client,
+        &mut GrapplingHook,
+        &Inventory,
+        Option<&mut AbilityCooldowns>,
+        Option<&McEntity>,
+    )>,
+    hooks: Query<&McEntity, With<Hook>>,
+    mut events: EventReader<UseItem>,
+) {
+    let current_tick = server.current_tick();
+
+    for event in events.iter() {
+        if event.hand != Hand::Main {
+            continue;
+        }
+
+        let Ok((mut client, mut grappling_hook, inventory, cooldowns, player)) =
+            clients.get_mut(event.client)
+        else {
+            continue;
+        };
+
+        if inventory
+            .slot(client.held_item_slot())
+            .map_or(true, |item| item.item != ItemKind::FishingRod)
+        {
+            continue;
+        }
+
+        if let Some(hook) = grappling_hook.hook.take() {
+            if let Ok(hook_entity) = hooks.get(hook) {
+                let velocity = grapple_velocity(
+                    client.position(),
+                    hook_entity.position(),
+                    grappling_hook.pull_speed,
+                );
+                client.set_velocity(velocity);
+
+                if let Some(mut cooldowns) = cooldowns {
+                    cooldowns.start(GRAPPLING_HOOK, current_tick, grappling_hook.cooldown);
+                }
+            }
+
+            commands.entity(hook).insert(Despawned);
+            continue;
+        }
+
+        if !cooldowns.map_or(true, |c| c.is_ready(GRAPPLING_HOOK, current_tick)) {
+            continue;
+        }
+
+        let velocity = client.look_direction() * grappling_hook.throw_speed;
+
+        let mut hook = McEntity::new(EntityKind::FishingBobber, client.instance());
+        hook.set_position(client.eye_position());
+        hook.set_velocity(velocity);
+        hook.set_fishing_bobber_owner(player.map(|p| p.protocol_id()));
+
+        let hook = commands
+            .spawn((
+                hook,
+                Hook {
+                    owner: event.client,
+                    velocity,
+                    stuck: false,
+                },
+            ))
+            .id();
+
+        grappling_hook.hook = Some(hook);
+    }
+}
+
+fn move_hooks(
+    mut commands: Commands,
+    server: Res<Server>,
+    mut hooks: Query<(Entity, &mut McEntity, &mut Hook)>,
+    owners: Query<(&Client, &GrapplingHook)>,
+    instances: Query<&Instance>,
+) {
+    let seconds_per_tick = 1.0 / server.tps() as f32;
+
+    for (entity, mut mc_entity, mut hook) in &mut hooks {
+        let owned = owners
+            .get(hook.owner)
+            .map_or(false, |(client, grappling_hook)| {
+                grappling_hook.hook == Some(entity)
+                    && client.position().distance(mc_entity.position()) < MAX_HOOK_DISTANCE
+            });
+
+        if !owned {
+            commands.entity(entity).insert(Despawned);
+            continue;
+        }
+
+        if hook.stuck {
+            continue;
+        }
+
+        let Ok(instance) = instances.get(mc_entity.instance()) else {
+            continue
+        };
+
+        let new_pos = mc_entity.position() + (hook.velocity * seconds_per_tick).as_dvec3();
+
+        let hit = instance
+            .block(BlockPos::at(new_pos))
+            .map_or(false, |block| {
+                !block.state().is_air() && !block.state().is_liquid()
+            });
+
+        if hit {
+            hook.stuck = true;
+            mc_entity.set_velocity(Vec3::ZERO);
+        } else {
+            mc_entity.set_position(new_pos);
+            hook.velocity.y -= HOOK_GRAVITY * seconds_per_tick;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ability_cooldowns() {
+        let mut cooldowns = AbilityCooldowns::default();
+
+        assert!(cooldowns.try_start("dash", 10, 20));
+        assert!(!cooldowns.try_start("dash", 15, 20));
+        assert_eq!(cooldowns.remaining("dash", 15), 15);
+        assert!(cooldowns.is_ready(DOUBLE_JUMP, 15));
+        assert!(cooldowns.is_ready("dash", 30));
+
+        cooldowns.start("dash", 30, 20);
+        cooldowns.reset("dash");
+        assert!(cooldowns.is_ready("dash", 31));
+    }
+
+    #[test]
+    fn grapple_velocity_points_at_target() {
+        let velocity = grapple_velocity(DVec3::ZERO, DVec3::new(10.0, 0.0, 0.0), 20.0);
+        assert_eq!(velocity, Vec3::new(20.0, 5.0, 0.0));
+    }
+}
@@ -1,7 +1,11 @@
 use std::io::Write;
 
+use bytes::Bytes;
 use tracing::warn;
-use valence_protocol::{encode_packet, encode_packet_compressed, EncodePacket, PacketEncoder};
+use valence_protocol::{
+    encode_packet, encode_packet_compressed_with_level, EncodePacket, PacketEncoder,
+    DEFAULT_COMPRESSION_LEVEL,
+};
 
 pub(crate) trait WritePacket {
     fn write_packet<P>(&mut self, packet: &P)
@@ -9,6 +13,12 @@ pub(crate) trait WritePacket {
         P: EncodePacket + ?Sized;
 
     fn write_packet_bytes(&mut self, bytes: &[u8]);
+
+    /// Like [`Self::write_packet_bytes`], but the writer may keep a reference
+    /// to `bytes` instead of copying them.
+    fn write_shared_packet_bytes(&mut self, bytes: &Bytes) {
+        self.write_packet_bytes(bytes)
+    }
 }
 
 impl<W: WritePacket> WritePacket for &mut W {
@@ -22,11 +32,16 @@ impl<W: WritePacket> WritePacket for &mut W {
     fn write_packet_bytes(&mut self, bytes: &[u8]) {
         (*self).write_packet_bytes(bytes)
     }
+
+    fn write_shared_packet_bytes(&mut self, bytes: &Bytes) {
+        (*self).write_shared_packet_bytes(bytes)
+    }
 }
 
 pub(crate) struct PacketWriter<'a> {
     buf: &'a mut Vec<u8>,
     threshold: Option<u32>,
+    level: u32,
     scratch: &'a mut Vec<u8>,
 }
 
@@ -35,9 +50,16 @@ impl<'a> PacketWriter<'a> {
         Self {
             buf,
             threshold,
+            level: DEFAULT_COMPRESSION_LEVEL,
             scratch,
         }
     }
+
+    #[must_use]
+    pub fn with_compression_level(mut self, level: u32) -> Self {
+        self.level = level;
+        self
+    }
 }
 
 impl WritePacket for PacketWriter<'_> {
@@ -46,7 +68,7 @@ impl WritePacket for PacketWriter<'_> {
         P: EncodePacket + ?Sized,
     {
         let res = if let Some(threshold) = self.threshold {
-            encode_packet_compressed(self.buf, pkt, threshold, self.scratch)
+            encode_packet_compressed_with_level(self.buf, pkt, threshold, self.level, self.scratch)
         } else {
             encode_packet(self.buf, pkt)
         };
@@ -76,4 +98,8 @@ impl WritePacket for PacketEncoder {
     fn write_packet_bytes(&mut self, bytes: &[u8]) {
         self.append_bytes(bytes)
     }
+
+    fn write_shared_packet_bytes(&mut self, bytes: &Bytes) {
+        self.append_shared(bytes.clone())
+    }
 }
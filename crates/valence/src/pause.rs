@@ -0,0 +1,358 @@
+//! Pausing the game simulation.
+//!
+//! When the [`ServerPause`] resource is paused, the systems in
+//! [`CoreStage::Update`] and the [gameplay systems] of other stages are not
+//! run, so the game stops while clients stay connected: Valence keeps sending
+//! keepalives and handling packets, and clients are shown a message in the
+//! action bar. This is useful to inspect a live server, e.g. while a debugger
+//! is attached.
+//!
+//! The block ticks, fluids, redstone, projectiles, falling blocks, vehicles,
+//! combat and zone damage of Valence's plugins are all gameplay systems.
+//!
+//! [`Server::current_tick`] keeps counting while the server is paused because
+//! connection upkeep depends on it. [`ServerPause::game_tick`] only counts
+//! ticks in which the game was running. Systems in other stages, such as
+//! [`EventLoop`] handlers, can be paused by adding them to
+//! [`gameplay_systems`] or with the [`run_if_not_paused`] run criteria.
+//!
+//! ```
+//! use valence::pause::{PausePlugin, ServerPause};
+//! use valence::prelude::*;
+//!
+//! let mut app = App::new();
+//! app.add_plugin(PausePlugin::new().with_command("pause"));
+//!
+//! fn freeze_on_crash(mut pause: ResMut<ServerPause>) {
+//!     pause.pause();
+//! }
+//! ```
+//!
+//! [`Server::current_tick`]: crate::server::Server::current_tick
+//! [`EventLoop`]: crate::server::EventLoop
+//! [gameplay systems]: gameplay_systems
+
+use std::borrow::Cow;
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use bevy_ecs::schedule::ShouldRun;
+use valence_protocol::text::{Color, Text, TextFormat};
+
+use crate::client::event::ChatCommand;
+use crate::client::Client;
+use crate::server::Server;
+
+/// The op level required to use the pause command.
+pub const PAUSE_COMMAND_OP_LEVEL: u8 = 4;
+
+/// Adds the [`ServerPause`] resource and pauses [`CoreStage::Update`] and the
+/// [`gameplay_systems`] while the server is paused.
+pub struct PausePlugin {
+    command: Option<Cow<'static, str>>,
+}
+
+impl PausePlugin {
+    pub fn new() -> Self {
+        Self { command: None }
+    }
+
+    /// Enables a command which toggles the pause, e.g. `"pause"` for `/pause`.
+    /// The command is only available to clients with an op level of at least
+    /// [`PAUSE_COMMAND_OP_LEVEL`].
+    #[must_use]
+    pub fn with_command(mut self, command: impl Into<Cow<'static, str>>) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+}
+
+impl Default for PausePlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerPause>()
+            .stage(CoreStage::Update, |stage: &mut SystemStage| {
+                stage.set_run_criteria(run_if_not_paused)
+            })
+            .add_system_to_stage(CoreStage::PostUpdate, update_pause.before("valence_core"));
+
+        if let Some(command) = &self.command {
+            let command = command.clone();
+
+            app.add_system_to_stage(
+                CoreStage::PostUpdate,
+                (move |pause: ResMut<ServerPause>,
+                       clients: Query<&mut Client>,
+                       events: EventReader<ChatCommand>| {
+                    handle_pause_command(&command, pause, clients, events)
+                })
+                .before(update_pause),
+            );
+        }
+    }
+}
+
+/// Whether or not the game is paused, and the message shown to clients while
+/// it is.
+#[derive(Resource, Clone, Debug)]
+pub struct ServerPause {
+    paused: bool,
+    /// If the pause state changed since the last tick.
+    changed: bool,
+    message: Text,
+    game_tick: i64,
+}
+
+impl ServerPause {
+    pub fn new() -> Self {
+        Self {
+            paused: false,
+            changed: false,
+            message: "The game is paused".color(Color::GOLD),
+            game_tick: 0,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses the game starting with the next run of [`CoreStage::Update`].
+    pub fn pause(&mut self) {
+        self.set_paused(true);
+    }
+
+    /// Resumes the game starting with the next run of [`CoreStage::Update`].
+    pub fn resume(&mut self) {
+        self.set_paused(false);
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if self.paused != paused {
+            self.paused = paused;
+            self.changed = !self.changed;
+        }
+    }
+
+    /// The message shown in the action bar of all clients while the game is
+    /// paused.
+    pub fn message(&self) -> &Text {
+        &self.message
+    }
+
+    pub fn set_message(&mut self, message: impl Into<Text>) {
+        self.message = message.into();
+    }
+
+    /// Returns the number of ticks in which the game was not paused.
+    pub fn game_tick(&self) -> i64 {
+        self.game_tick
+    }
+}
+
+impl Default for ServerPause {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns a system set for gameplay systems outside of [`CoreStage::Update`].
+/// The systems are labeled `"gameplay"` and are not run while the game is
+/// paused.
+pub fn gameplay_systems() -> SystemSet {
+    SystemSet::new()
+        .label("gameplay")
+        .with_run_criteria(run_if_not_paused)
+}
+
+/// A run criteria which runs systems only while the game is not paused.
+pub fn run_if_not_paused(pause: Option<Res<ServerPause>>) -> ShouldRun {
+    if pause.map_or(false, |pause| pause.is_paused()) {
+        ShouldRun::No
+    } else {
+        ShouldRun::Yes
+    }
+}
+
+fn handle_pause_command(
+    command: &str,
+    mut pause: ResMut<ServerPause>,
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<ChatCommand>,
+) {
+    for event in events.iter() {
+        if &*event.command != command {
+            continue;
+        }
+
+        let Ok(mut client) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        if client.op_level() < PAUSE_COMMAND_OP_LEVEL {
+            client.send_message("You do not have permission to pause the game".color(Color::RED));
+            continue;
+        }
+
+        let paused = !pause.is_paused();
+        pause.set_paused(paused);
+    }
+}
+
+fn update_pause(
+    server: Res<Server>,
+    mut pause: ResMut<ServerPause>,
+    mut clients: Query<&mut Client>,
+) {
+    if !pause.is_paused() {
+        pause.game_tick += 1;
+    }
+
+    let changed = pause.changed;
+    pause.changed = false;
+
+    if pause.is_paused() {
+        // The action bar fades out after a few seconds, so the message is
+        // sent again every second.
+        if changed || server.current_tick() % server.tps() == 0 {
+            for mut client in &mut clients {
+                client.set_action_bar(pause.message.clone());
+            }
+        }
+    } else if changed {
+        for mut client in &mut clients {
+            client.set_action_bar(Text::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{DVec3, Vec3};
+    use valence_protocol::BlockState;
+
+    use super::*;
+    use crate::entity::McEntity;
+    use crate::fluid::{FluidPlugin, FluidSimulation};
+    use crate::instance::{Chunk, Instance};
+    use crate::projectile::{launch, ProjectileKind, ProjectilePlugin};
+    use crate::unit_test::util::scenario_single_client;
+
+    #[derive(Resource, Default)]
+    struct Counter(u32);
+
+    fn count(mut counter: ResMut<Counter>) {
+        counter.0 += 1;
+    }
+
+    #[test]
+    fn pause_stops_update_stage() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(PausePlugin::new())
+            .init_resource::<Counter>()
+            .add_system(count);
+
+        app.update();
+        assert_eq!(app.world.resource::<Counter>().0, 1);
+
+        app.world.resource_mut::<ServerPause>().pause();
+        let tick = app.world.resource::<Server>().current_tick();
+
+        app.update();
+        app.update();
+
+        assert_eq!(app.world.resource::<Counter>().0, 1);
+        assert_eq!(app.world.resource::<ServerPause>().game_tick(), 1);
+        assert_eq!(app.world.resource::<Server>().current_tick(), tick + 2);
+        assert!(!app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .is_disconnected());
+
+        app.world.resource_mut::<ServerPause>().resume();
+        app.update();
+
+        assert_eq!(app.world.resource::<Counter>().0, 2);
+        assert_eq!(app.world.resource::<ServerPause>().game_tick(), 2);
+    }
+
+    #[test]
+    fn pause_stops_gameplay_systems() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(PausePlugin::new())
+            .add_plugin(FluidPlugin)
+            .add_plugin(ProjectilePlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let y = instance.min_y() + 10;
+
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        for z in 0..16 {
+            for x in 0..16 {
+                instance.set_block([x, y, z], BlockState::STONE);
+            }
+        }
+
+        instance.set_block([8, y + 1, 8], BlockState::WATER);
+
+        app.world
+            .entity_mut(instance_ent)
+            .insert(FluidSimulation::new());
+
+        let arrow = app
+            .world
+            .spawn(launch(
+                ProjectileKind::Arrow,
+                instance_ent,
+                None,
+                DVec3::new(8.5, y as f64 + 8.0, 8.5),
+                Vec3::new(0.0, 0.0, 1.0),
+            ))
+            .id();
+
+        // The water is scheduled to flow in the first tick, but only flows once
+        // the game is resumed.
+        app.update();
+        app.world.resource_mut::<ServerPause>().pause();
+
+        let arrow_pos = app.world.get::<McEntity>(arrow).unwrap().position();
+
+        for _ in 0..20 {
+            app.update();
+        }
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert_eq!(
+            instance.block([9, y + 1, 8]).unwrap().state(),
+            BlockState::AIR
+        );
+        assert_eq!(
+            app.world.get::<McEntity>(arrow).unwrap().position(),
+            arrow_pos
+        );
+
+        app.world.resource_mut::<ServerPause>().resume();
+
+        for _ in 0..20 {
+            app.update();
+        }
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert!(instance.block([9, y + 1, 8]).unwrap().state().is_liquid());
+        assert_ne!(
+            app.world.get::<McEntity>(arrow).unwrap().position(),
+            arrow_pos
+        );
+    }
+}
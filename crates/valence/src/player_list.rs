@@ -18,6 +18,10 @@ use crate::client::Client;
 use crate::packet::{PacketWriter, WritePacket};
 use crate::server::Server;
 
+/// How often [`PlayerList::default_system_set`] publishes the pings of the
+/// clients, in seconds.
+pub const PING_UPDATE_INTERVAL_SECS: i64 = 5;
+
 /// The global list of players on a server visible by pressing the tab key by
 /// default.
 ///
@@ -51,7 +55,10 @@ pub struct PlayerList {
 impl PlayerList {
     /// Returns a set of systems for maintaining the player list in a reasonable
     /// default way. When clients connect, they are added to the player list.
-    /// When clients disconnect, they are removed from the player list.
+    /// When clients disconnect, they are removed from the player list. The game
+    /// modes in the player list are kept in sync with the clients' game modes,
+    /// which the clients use to hide spectators from other players. The pings
+    /// of the clients are published every few seconds.
     pub fn default_system_set() -> SystemSet {
         fn add_new_clients_to_player_list(
             clients: Query<&Client, Added<Client>>,
@@ -79,9 +86,40 @@ impl PlayerList {
             }
         }
 
+        fn update_game_modes_in_player_list(
+            clients: Query<&Client>,
+            mut player_list: ResMut<PlayerList>,
+        ) {
+            for client in &clients {
+                if let Some(entry) = player_list.get_mut(client.uuid()) {
+                    if entry.game_mode() != client.game_mode() {
+                        entry.set_game_mode(client.game_mode());
+                    }
+                }
+            }
+        }
+
+        fn update_pings_in_player_list(
+            server: Res<Server>,
+            clients: Query<&Client>,
+            mut player_list: ResMut<PlayerList>,
+        ) {
+            if server.current_tick() % (server.tps() * PING_UPDATE_INTERVAL_SECS) != 0 {
+                return;
+            }
+
+            for client in &clients {
+                if let Some(entry) = player_list.get_mut(client.uuid()) {
+                    entry.set_ping(client.ping());
+                }
+            }
+        }
+
         SystemSet::new()
             .with_system(add_new_clients_to_player_list)
             .with_system(remove_disconnected_clients_from_player_list)
+            .with_system(update_game_modes_in_player_list)
+            .with_system(update_pings_in_player_list)
     }
 }
 
@@ -561,7 +599,8 @@ pub(crate) fn update_player_list(
         &mut pl.cached_update_packets,
         server.compression_threshold(),
         &mut scratch,
-    );
+    )
+    .with_compression_level(server.compression_level());
 
     let mut removed = vec![];
 
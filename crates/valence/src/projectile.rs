@@ -0,0 +1,504 @@
+//! Arrows, snowballs and ender pearls.
+//!
+//! The [`ProjectilePlugin`] moves entities with a [`Projectile`] component
+//! like vanilla: they fall with gravity, slow down with drag, and stop at the
+//! first block or entity in their path. Every hit sends a [`ProjectileHit`]
+//! event. What happens next depends on the [`ProjectileKind`]:
+//!
+//! - Arrows stick in blocks until the block is removed and disappear after a
+//!   minute. Arrows hitting entities disappear.
+//! - Snowballs burst into particles.
+//! - Ender pearls teleport their owner to the point of impact.
+//!
+//! Clients throw snowballs and ender pearls by using them. Projectiles can be
+//! launched from any system with [`launch`]. Damage is left to the game, e.g.
+//! with [`Health::hurt`] when a [`ProjectileHit`] event is received.
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::projectile::{launch, ProjectileKind};
+//!
+//! fn shoot_arrow(mut commands: Commands, instance: Entity) {
+//!     commands.spawn(launch(
+//!         ProjectileKind::Arrow,
+//!         instance,
+//!         None,
+//!         [0.0, 80.0, 0.0].into(),
+//!         [40.0, 10.0, 0.0].into(),
+//!     ));
+//! }
+//! ```
+//!
+//! [`Health::hurt`]: crate::combat::Health::hurt
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::{DVec3, Vec3};
+use valence_protocol::types::{GameMode, Hand};
+use valence_protocol::{BlockPos, ItemKind};
+
+use crate::client::event::UseItem;
+use crate::client::Client;
+use crate::config::DEFAULT_TPS;
+use crate::entity::{EntityKind, EntityStatus, McEntity};
+use crate::instance::Instance;
+use crate::inventory::Inventory;
+use crate::math::to_yaw_and_pitch;
+use crate::pause::gameplay_systems;
+use crate::server::Server;
+use crate::Despawned;
+
+/// The speed of thrown snowballs and ender pearls in blocks per second.
+pub const THROW_SPEED: f32 = 30.0;
+
+/// The number of ticks arrows stay stuck in blocks before they disappear.
+pub const ARROW_DESPAWN_TICKS: i64 = 1200;
+
+/// The number of ticks after a launch in which a projectile can't hit its
+/// owner.
+const OWNER_IMMUNITY_TICKS: u32 = 5;
+
+/// Adds the [`ProjectileHit`] event and the systems throwing and moving
+/// projectiles.
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ProjectileHit>().add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            gameplay_systems()
+                .with_system(throw_projectiles.before("valence_core"))
+                .with_system(
+                    move_projectiles
+                        .after(throw_projectiles)
+                        .before("valence_core"),
+                ),
+        );
+    }
+}
+
+/// The kinds of projectiles simulated by the [`ProjectilePlugin`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ProjectileKind {
+    Arrow,
+    Snowball,
+    EnderPearl,
+}
+
+impl ProjectileKind {
+    pub fn entity_kind(self) -> EntityKind {
+        match self {
+            ProjectileKind::Arrow => EntityKind::Arrow,
+            ProjectileKind::Snowball => EntityKind::Snowball,
+            ProjectileKind::EnderPearl => EntityKind::EnderPearl,
+        }
+    }
+
+    /// Returns the acceleration of the projectile towards the ground in blocks
+    /// per second squared.
+    pub fn gravity(self) -> f32 {
+        match self {
+            ProjectileKind::Arrow => 20.0,
+            ProjectileKind::Snowball | ProjectileKind::EnderPearl => 12.0,
+        }
+    }
+
+    /// Returns the fraction of its velocity the projectile keeps every tick
+    /// at the default tick rate, in the air and in liquids.
+    pub fn drag(self, in_liquid: bool) -> f32 {
+        match (self, in_liquid) {
+            (_, false) => 0.99,
+            (ProjectileKind::Arrow, true) => 0.6,
+            (_, true) => 0.8,
+        }
+    }
+}
+
+/// A component for projectile entities moved by the [`ProjectilePlugin`].
+#[derive(Component, Clone, Debug)]
+pub struct Projectile {
+    kind: ProjectileKind,
+    owner: Option<Entity>,
+    /// The velocity in blocks per second.
+    velocity: Vec3,
+    state: ProjectileState,
+    /// The number of ticks since the launch.
+    age: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum ProjectileState {
+    Flying,
+    /// Stuck in the block since the given tick.
+    Stuck {
+        pos: BlockPos,
+        since: i64,
+    },
+    /// Hit something this tick and is removed in the next.
+    Removed,
+}
+
+impl Projectile {
+    pub fn new(kind: ProjectileKind, owner: Option<Entity>, velocity: impl Into<Vec3>) -> Self {
+        Self {
+            kind,
+            owner,
+            velocity: velocity.into(),
+            state: ProjectileState::Flying,
+            age: 0,
+        }
+    }
+
+    pub fn kind(&self) -> ProjectileKind {
+        self.kind
+    }
+
+    /// Returns the entity which launched this projectile, if any.
+    pub fn owner(&self) -> Option<Entity> {
+        self.owner
+    }
+
+    /// Returns the velocity of this projectile in blocks per second.
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    /// Returns the block this projectile is stuck in, if any.
+    pub fn stuck_in(&self) -> Option<BlockPos> {
+        match self.state {
+            ProjectileState::Stuck { pos, .. } => Some(pos),
+            _ => None,
+        }
+    }
+}
+
+/// Sent when a projectile hit a block or an entity.
+#[derive(Clone, Debug)]
+pub struct ProjectileHit {
+    pub projectile: Entity,
+    pub kind: ProjectileKind,
+    pub owner: Option<Entity>,
+    pub target: ProjectileTarget,
+    /// The point where the projectile hit.
+    pub position: DVec3,
+    /// The velocity of the projectile when it hit, in blocks per second.
+    pub velocity: Vec3,
+}
+
+/// What a projectile hit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProjectileTarget {
+    Block(BlockPos),
+    Entity(Entity),
+}
+
+/// Returns the components of a projectile of `kind` launched from
+/// `position` with `velocity` in blocks per second.
+pub fn launch(
+    kind: ProjectileKind,
+    instance: Entity,
+    owner: Option<Entity>,
+    position: DVec3,
+    velocity: Vec3,
+) -> (McEntity, Projectile) {
+    let mut entity = McEntity::new(kind.entity_kind(), instance);
+    entity.set_position(position);
+    entity.set_velocity(velocity);
+
+    if velocity != Vec3::ZERO {
+        let (yaw, pitch) = to_yaw_and_pitch(velocity.normalize());
+        entity.set_yaw(yaw);
+        entity.set_pitch(pitch);
+    }
+
+    (entity, Projectile::new(kind, owner, velocity))
+}
+
+fn throw_projectiles(
+    mut commands: Commands,
+    mut clients: Query<(&Client, &mut Inventory)>,
+    mut events: EventReader<UseItem>,
+) {
+    for event in events.iter() {
+        if event.hand != Hand::Main {
+            continue;
+        }
+
+        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        let slot = client.held_item_slot();
+
+        let Some(item) = inventory.slot(slot) else {
+            continue
+        };
+
+        let kind = match item.item {
+            ItemKind::Snowball => ProjectileKind::Snowball,
+            ItemKind::EnderPearl => ProjectileKind::EnderPearl,
+            _ => continue,
+        };
+
+        if client.game_mode() != GameMode::Creative {
+            let mut item = item.clone();
+            let count = item.count() - 1;
+            item.set_count(count);
+            inventory.replace_slot(slot, (count > 0).then_some(item));
+        }
+
+        commands.spawn(launch(
+            kind,
+            client.instance(),
+            Some(event.client),
+            client.eye_position(),
+            client.look_direction() * THROW_SPEED,
+        ));
+    }
+}
+
+/// The result of moving a projectile for a tick.
+enum Movement {
+    Flying(DVec3),
+    Hit(ProjectileTarget, DVec3),
+}
+
+fn move_projectiles(
+    mut commands: Commands,
+    server: Res<Server>,
+    mut entities: Query<(Entity, &mut McEntity, Option<&mut Projectile>)>,
+    mut clients: Query<&mut Client>,
+    instances: Query<&Instance>,
+    mut hits: EventWriter<ProjectileHit>,
+) {
+    let seconds_per_tick = 1.0 / server.tps() as f32;
+    let ticks_per_vanilla_tick = DEFAULT_TPS as f32 / server.tps() as f32;
+
+    let mut movements = vec![];
+
+    for (entity, mc_entity, projectile) in entities.iter() {
+        let Some(projectile) = projectile else {
+            continue
+        };
+
+        let Ok(instance) = instances.get(mc_entity.instance()) else {
+            continue
+        };
+
+        let position = mc_entity.position();
+
+        if position.y < (instance.min_y() - 64) as f64 {
+            commands.entity(entity).insert(Despawned);
+            continue;
+        }
+
+        match projectile.state {
+            ProjectileState::Flying => {}
+            ProjectileState::Stuck { pos, since } => {
+                if server.current_tick() - since >= ARROW_DESPAWN_TICKS {
+                    commands.entity(entity).insert(Despawned);
+                } else if instance
+                    .block(pos)
+                    .map_or(true, |b| b.state().collision_shapes().next().is_none())
+                {
+                    // The block is gone, so the arrow falls again.
+                    movements.push((entity, Movement::Flying(position)));
+                }
+
+                continue;
+            }
+            ProjectileState::Removed => {
+                commands.entity(entity).insert(Despawned);
+                continue;
+            }
+        }
+
+        let motion = (projectile.velocity * seconds_per_tick).as_dvec3();
+        let length = motion.length();
+
+        let block_hit = instance.raycast_blocks(position, motion, length);
+
+        let ignores_owner = projectile.age < OWNER_IMMUNITY_TICKS;
+
+        let entity_hit = instance.raycast_entities(position, motion, length, |e| {
+            if e == entity || (ignores_owner && Some(e) == projectile.owner) {
+                return None;
+            }
+
+            match entities.get(e) {
+                Ok((_, target, None)) => Some(target.hitbox()),
+                _ => None,
+            }
+        });
+
+        let movement = match (block_hit, entity_hit) {
+            (Some(block), Some(hit)) if hit.distance < block.distance => {
+                Movement::Hit(ProjectileTarget::Entity(hit.entity), hit.position)
+            }
+            (Some(block), _) => Movement::Hit(ProjectileTarget::Block(block.pos), block.position),
+            (None, Some(hit)) => Movement::Hit(ProjectileTarget::Entity(hit.entity), hit.position),
+            (None, None) => Movement::Flying(position + motion),
+        };
+
+        movements.push((entity, movement));
+    }
+
+    for (entity, movement) in movements {
+        let Ok((_, mut mc_entity, Some(mut projectile))) = entities.get_mut(entity) else {
+            continue
+        };
+
+        projectile.age += 1;
+
+        match movement {
+            Movement::Flying(new_pos) => {
+                let in_liquid = instances
+                    .get(mc_entity.instance())
+                    .ok()
+                    .and_then(|i| i.block(BlockPos::at(new_pos)))
+                    .map_or(false, |b| b.state().is_liquid());
+
+                let drag = projectile.kind.drag(in_liquid).powf(ticks_per_vanilla_tick);
+
+                projectile.velocity *= drag;
+                projectile.velocity.y -= projectile.kind.gravity() * seconds_per_tick;
+                projectile.state = ProjectileState::Flying;
+
+                mc_entity.set_position(new_pos);
+            }
+            Movement::Hit(target, position) => {
+                hits.send(ProjectileHit {
+                    projectile: entity,
+                    kind: projectile.kind,
+                    owner: projectile.owner,
+                    target,
+                    position,
+                    velocity: projectile.velocity,
+                });
+
+                mc_entity.set_position(position);
+                mc_entity.set_velocity(Vec3::ZERO);
+                projectile.velocity = Vec3::ZERO;
+
+                projectile.state = match (projectile.kind, target) {
+                    (ProjectileKind::Arrow, ProjectileTarget::Block(pos)) => {
+                        ProjectileState::Stuck {
+                            pos,
+                            since: server.current_tick(),
+                        }
+                    }
+                    _ => ProjectileState::Removed,
+                };
+
+                match projectile.kind {
+                    ProjectileKind::Arrow => {}
+                    ProjectileKind::Snowball => {
+                        mc_entity.trigger_status(
+                            EntityStatus::PlayDeathSoundOrAddProjectileHitParticles,
+                        );
+                    }
+                    ProjectileKind::EnderPearl => {
+                        if let Some(mut client) = projectile
+                            .owner
+                            .and_then(|owner| clients.get_mut(owner).ok())
+                            .filter(|c| c.instance() == mc_entity.instance())
+                        {
+                            client.set_position(position);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::block::BlockState;
+
+    use super::*;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn projectile_hits() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(ProjectilePlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let y = instance.min_y() + 10;
+
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        for z in 0..16 {
+            for x in 0..16 {
+                instance.set_block([x, y, z], BlockState::STONE);
+            }
+        }
+
+        let top = y as f64 + 1.0;
+
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        zombie.set_position([12.5, top, 2.5]);
+        let zombie = app.world.spawn(zombie).id();
+
+        let pearl = app
+            .world
+            .spawn(launch(
+                ProjectileKind::EnderPearl,
+                instance_ent,
+                Some(client_ent),
+                DVec3::new(4.5, top + 3.0, 4.5),
+                Vec3::new(0.0, -10.0, 0.0),
+            ))
+            .id();
+
+        let arrow = app
+            .world
+            .spawn(launch(
+                ProjectileKind::Arrow,
+                instance_ent,
+                None,
+                DVec3::new(8.5, top + 4.0, 8.5),
+                Vec3::new(0.0, -20.0, 0.0),
+            ))
+            .id();
+
+        let snowball = app
+            .world
+            .spawn(launch(
+                ProjectileKind::Snowball,
+                instance_ent,
+                None,
+                DVec3::new(2.5, top + 1.0, 2.5),
+                Vec3::new(30.0, 0.0, 0.0),
+            ))
+            .id();
+
+        let mut reader = app.world.resource::<Events<ProjectileHit>>().get_reader();
+        let mut targets = vec![];
+
+        for _ in 0..20 {
+            app.update();
+
+            let events = app.world.resource::<Events<ProjectileHit>>();
+            targets.extend(reader.iter(events).map(|hit| (hit.projectile, hit.target)));
+        }
+
+        assert!(targets.contains(&(pearl, ProjectileTarget::Block(BlockPos::new(4, y, 4)))));
+        assert!(targets.contains(&(arrow, ProjectileTarget::Block(BlockPos::new(8, y, 8)))));
+        assert!(targets.contains(&(snowball, ProjectileTarget::Entity(zombie))));
+        assert_eq!(targets.len(), 3);
+
+        // The pearl teleported the client and is gone, while the arrow is stuck.
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(client.position().distance(DVec3::new(4.5, top, 4.5)) < 1e-6);
+        assert!(app.world.get::<Projectile>(pearl).is_none());
+
+        let arrow = app.world.get::<Projectile>(arrow).unwrap();
+        assert_eq!(arrow.stuck_in(), Some(BlockPos::new(8, y, 8)));
+    }
+}
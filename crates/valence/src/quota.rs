@@ -0,0 +1,229 @@
+//! Soft limits on the resources used by instances.
+//!
+//! Servers hosting game modes they don't fully trust can attach an
+//! [`InstanceQuota`] to instance entities to limit the number of loaded
+//! chunks, entities, and block updates per tick. The [`QuotaPlugin`] checks the
+//! limits every tick, sends a [`QuotaExceeded`] event for every limit that was
+//! exceeded, and then applies the instance's [`QuotaPolicy`].
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::quota::{InstanceQuota, QuotaPolicy};
+//!
+//! let quota = InstanceQuota::new()
+//!     .with_max_chunks(1024)
+//!     .with_max_entities(500)
+//!     .with_policy(QuotaPolicy::Enforce);
+//!
+//! assert_eq!(quota.max_chunks, Some(1024));
+//! ```
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashMap;
+
+use crate::client::Client;
+use crate::entity::McEntity;
+use crate::instance::Instance;
+use crate::view::ChunkPos;
+use crate::Despawned;
+
+/// Adds the [`QuotaExceeded`] event and the system enforcing
+/// [`InstanceQuota`]s.
+pub struct QuotaPlugin;
+
+impl Plugin for QuotaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<QuotaExceeded>().add_system_to_stage(
+            CoreStage::PostUpdate,
+            check_instance_quotas.before("valence_core"),
+        );
+    }
+}
+
+/// A component for instance entities limiting the resources the instance may
+/// use. Limits set to `None` are not checked.
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct InstanceQuota {
+    /// The maximum number of loaded chunks.
+    pub max_chunks: Option<usize>,
+    /// The maximum number of entities in the instance, not counting clients.
+    pub max_entities: Option<usize>,
+    /// The maximum number of blocks changed in a single tick.
+    pub max_block_updates: Option<usize>,
+    /// What to do when a limit is exceeded.
+    pub policy: QuotaPolicy,
+}
+
+impl InstanceQuota {
+    /// Creates a new quota without any limits which only sends events.
+    pub fn new() -> Self {
+        Self {
+            max_chunks: None,
+            max_entities: None,
+            max_block_updates: None,
+            policy: QuotaPolicy::Notify,
+        }
+    }
+
+    #[must_use]
+    pub fn with_max_chunks(mut self, max_chunks: usize) -> Self {
+        self.max_chunks = Some(max_chunks);
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_entities(mut self, max_entities: usize) -> Self {
+        self.max_entities = Some(max_entities);
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_block_updates(mut self, max_block_updates: usize) -> Self {
+        self.max_block_updates = Some(max_block_updates);
+        self
+    }
+
+    #[must_use]
+    pub fn with_policy(mut self, policy: QuotaPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl Default for InstanceQuota {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Determines what happens when an instance exceeds its [`InstanceQuota`].
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum QuotaPolicy {
+    /// Only send a [`QuotaExceeded`] event.
+    #[default]
+    Notify,
+    /// Send a [`QuotaExceeded`] event and bring the instance back within its
+    /// limits. The chunks furthest from the origin are unloaded, and the most
+    /// recently spawned entities are despawned.
+    ///
+    /// Block updates cannot be undone, so exceeding the block update limit
+    /// only sends an event.
+    Enforce,
+    /// Send a [`QuotaExceeded`] event and despawn the instance.
+    DespawnInstance,
+}
+
+/// The resource limited by an [`InstanceQuota`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum QuotaResource {
+    Chunks,
+    Entities,
+    BlockUpdates,
+}
+
+/// Sent when an instance exceeds one of the limits in its [`InstanceQuota`].
+#[derive(Clone, Debug)]
+pub struct QuotaExceeded {
+    pub instance: Entity,
+    pub resource: QuotaResource,
+    /// The amount of the resource used by the instance before the policy was
+    /// applied.
+    pub usage: usize,
+    pub limit: usize,
+}
+
+fn check_instance_quotas(
+    mut commands: Commands,
+    mut instances: Query<(Entity, &mut Instance, &InstanceQuota), Without<Despawned>>,
+    entities: Query<(Entity, &McEntity), (Without<Client>, Without<Despawned>)>,
+    mut exceeded: EventWriter<QuotaExceeded>,
+) {
+    // Only count entities if at least one instance limits them.
+    let mut entities_by_instance: FxHashMap<Entity, Vec<Entity>> = FxHashMap::default();
+
+    if instances.iter().any(|(_, _, q)| q.max_entities.is_some()) {
+        for (id, entity) in &entities {
+            entities_by_instance
+                .entry(entity.instance())
+                .or_default()
+                .push(id);
+        }
+    }
+
+    for (instance_id, mut instance, quota) in &mut instances {
+        let mut any_exceeded = false;
+
+        if let Some(limit) = quota.max_chunks {
+            let usage = instance.chunks().count();
+
+            if usage > limit {
+                any_exceeded = true;
+                exceeded.send(QuotaExceeded {
+                    instance: instance_id,
+                    resource: QuotaResource::Chunks,
+                    usage,
+                    limit,
+                });
+
+                if quota.policy == QuotaPolicy::Enforce {
+                    let mut positions: Vec<_> = instance.chunks().map(|(pos, _)| pos).collect();
+                    positions.sort_unstable_by_key(|&pos| pos.distance_squared(ChunkPos::new(0, 0)));
+
+                    for pos in &positions[limit..] {
+                        instance.remove_chunk(*pos);
+                    }
+                }
+            }
+        }
+
+        if let Some(limit) = quota.max_entities {
+            let ids = entities_by_instance
+                .get_mut(&instance_id)
+                .map(|ids| ids.as_mut_slice())
+                .unwrap_or_default();
+
+            if ids.len() > limit {
+                any_exceeded = true;
+                exceeded.send(QuotaExceeded {
+                    instance: instance_id,
+                    resource: QuotaResource::Entities,
+                    usage: ids.len(),
+                    limit,
+                });
+
+                if quota.policy == QuotaPolicy::Enforce {
+                    // Entity IDs are not strictly increasing because they are reused, but
+                    // newer entities generally have larger indices.
+                    ids.sort_unstable();
+
+                    for &id in &ids[limit..] {
+                        commands.entity(id).insert(Despawned);
+                    }
+                }
+            }
+        }
+
+        if let Some(limit) = quota.max_block_updates {
+            let usage = instance
+                .chunks()
+                .map(|(_, chunk)| chunk.block_update_count())
+                .sum();
+
+            if usage > limit {
+                any_exceeded = true;
+                exceeded.send(QuotaExceeded {
+                    instance: instance_id,
+                    resource: QuotaResource::BlockUpdates,
+                    usage,
+                    limit,
+                });
+            }
+        }
+
+        if any_exceeded && quota.policy == QuotaPolicy::DespawnInstance {
+            commands.entity(instance_id).insert(Despawned);
+        }
+    }
+}
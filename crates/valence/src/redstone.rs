@@ -0,0 +1,620 @@
+//! Basic redstone.
+//!
+//! Adding a [`RedstoneSimulation`] component to an instance entity and the
+//! [`RedstonePlugin`] to the app makes redstone in the instance work:
+//!
+//! - Redstone wire carries power from levers, buttons, redstone torches,
+//!   redstone blocks and repeaters, losing one level per block.
+//! - Solid blocks are powered by the components attached to them and by wire
+//!   on top of or pointing into them.
+//! - Redstone torches turn off when the block they are attached to is
+//!   powered.
+//! - Repeaters refresh the signal after their delay.
+//! - Redstone lamps, pistons, doors, trapdoors and fence gates react to
+//!   power. Pistons only change their `extended` property and don't move
+//!   blocks.
+//!
+//! Updates are triggered by block changes and processed in order of position,
+//! so the same circuit always behaves the same. Comparators, observers and
+//! quasi-connectivity are not simulated.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashSet;
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::{BlockFace, BlockKind, BlockPos, BlockState};
+
+use crate::instance::Instance;
+use crate::pause::gameplay_systems;
+use crate::server::Server;
+
+/// Adds the system simulating redstone in instances with a
+/// [`RedstoneSimulation`].
+pub struct RedstonePlugin;
+
+impl Plugin for RedstonePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            gameplay_systems().with_system(simulate_redstone.before("valence_core")),
+        );
+    }
+}
+
+/// The delay of redstone torches in ticks.
+const TORCH_DELAY: i64 = 2;
+
+const HORIZONTAL: [BlockFace; 4] = [
+    BlockFace::North,
+    BlockFace::South,
+    BlockFace::West,
+    BlockFace::East,
+];
+
+const ALL: [BlockFace; 6] = [
+    BlockFace::Bottom,
+    BlockFace::Top,
+    BlockFace::North,
+    BlockFace::South,
+    BlockFace::West,
+    BlockFace::East,
+];
+
+/// A component for instance entities which enables redstone in the instance.
+#[derive(Component, Clone, Debug)]
+pub struct RedstoneSimulation {
+    /// The maximum number of block updates processed in a single tick.
+    /// Updates beyond this limit are postponed to the next tick, which
+    /// protects the server from runaway circuits.
+    pub max_updates_per_tick: usize,
+    /// Delayed updates of torches and repeaters keyed by the tick they are
+    /// due.
+    pending: BTreeMap<i64, Vec<BlockPos>>,
+    scheduled: FxHashSet<BlockPos>,
+    /// Positions which need to be updated.
+    dirty: BTreeSet<BlockPos>,
+}
+
+impl RedstoneSimulation {
+    pub fn new() -> Self {
+        Self {
+            max_updates_per_tick: 65536,
+            pending: BTreeMap::new(),
+            scheduled: FxHashSet::default(),
+            dirty: BTreeSet::new(),
+        }
+    }
+
+    /// Requests that the redstone components around `pos` are updated, for
+    /// instance after loading a chunk containing a circuit.
+    pub fn schedule_update(&mut self, pos: impl Into<BlockPos>) {
+        self.mark_around(pos.into());
+    }
+
+    /// Marks every position which may be affected by a change at `pos`. Wire
+    /// reaches diagonally and powered blocks pass power on to their
+    /// neighbors, so this includes everything within two blocks.
+    fn mark_around(&mut self, pos: BlockPos) {
+        for dx in -2i32..=2 {
+            for dy in -2i32..=2 {
+                for dz in -2i32..=2 {
+                    if dx.abs() + dy.abs() + dz.abs() <= 2 {
+                        self.dirty
+                            .insert(BlockPos::new(pos.x + dx, pos.y + dy, pos.z + dz));
+                    }
+                }
+            }
+        }
+    }
+
+    fn schedule(&mut self, pos: BlockPos, tick: i64) {
+        if self.scheduled.insert(pos) {
+            self.pending.entry(tick).or_default().push(pos);
+        }
+    }
+}
+
+impl Default for RedstoneSimulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn facing(state: BlockState) -> Option<BlockFace> {
+    match state.get(PropName::Facing)? {
+        PropValue::North => Some(BlockFace::North),
+        PropValue::South => Some(BlockFace::South),
+        PropValue::West => Some(BlockFace::West),
+        PropValue::East => Some(BlockFace::East),
+        PropValue::Up => Some(BlockFace::Top),
+        PropValue::Down => Some(BlockFace::Bottom),
+        _ => None,
+    }
+}
+
+fn wire_prop(face: BlockFace) -> PropName {
+    match face {
+        BlockFace::North => PropName::North,
+        BlockFace::South => PropName::South,
+        BlockFace::West => PropName::West,
+        _ => PropName::East,
+    }
+}
+
+fn is_true(state: BlockState, name: PropName) -> bool {
+    state.get(name) == Some(PropValue::True)
+}
+
+fn is_wire(state: BlockState) -> bool {
+    state.to_kind() == BlockKind::RedstoneWire
+}
+
+fn is_torch(state: BlockState) -> bool {
+    matches!(
+        state.to_kind(),
+        BlockKind::RedstoneTorch | BlockKind::RedstoneWallTorch
+    )
+}
+
+fn is_switch(state: BlockState) -> bool {
+    let name = state.to_kind().to_str();
+    name == "lever" || name.ends_with("_button")
+}
+
+/// Whether or not power passes through a block.
+fn is_conductor(state: BlockState) -> bool {
+    state.is_opaque() && state.to_kind() != BlockKind::RedstoneBlock
+}
+
+fn wire_power(state: BlockState) -> u8 {
+    state
+        .get(PropName::Power)
+        .and_then(|p| p.to_u16())
+        .unwrap_or(0) as u8
+}
+
+/// Returns the position of the block a torch, lever or button is attached to.
+fn attached_to(pos: BlockPos, state: BlockState) -> Option<BlockPos> {
+    let face = match state.to_kind() {
+        BlockKind::RedstoneTorch => BlockFace::Bottom,
+        BlockKind::RedstoneWallTorch => facing(state)?.opposite(),
+        _ => match state.get(PropName::Face)? {
+            PropValue::Floor => BlockFace::Bottom,
+            PropValue::Ceiling => BlockFace::Top,
+            _ => facing(state)?.opposite(),
+        },
+    };
+
+    Some(pos.get_in_direction(face))
+}
+
+/// Returns the position a repeater outputs power into.
+fn repeater_output(pos: BlockPos, state: BlockState) -> Option<BlockPos> {
+    Some(pos.get_in_direction(facing(state)?.opposite()))
+}
+
+struct Circuit<'a> {
+    instance: &'a Instance,
+}
+
+impl Circuit<'_> {
+    fn get(&self, pos: BlockPos) -> BlockState {
+        self.instance
+            .block(pos)
+            .map_or(BlockState::AIR, |b| b.state())
+    }
+
+    /// Power sent by a source block (not wire) at `src` to the block at `to`.
+    fn source_power(&self, src: BlockPos, state: BlockState, to: BlockPos) -> u8 {
+        let powered = match state.to_kind() {
+            BlockKind::RedstoneBlock => true,
+            BlockKind::RedstoneTorch | BlockKind::RedstoneWallTorch => {
+                is_true(state, PropName::Lit) && attached_to(src, state) != Some(to)
+            }
+            BlockKind::Repeater => {
+                is_true(state, PropName::Powered) && repeater_output(src, state) == Some(to)
+            }
+            _ => is_switch(state) && is_true(state, PropName::Powered),
+        };
+
+        if powered {
+            15
+        } else {
+            0
+        }
+    }
+
+    /// Whether or not the wire at `wire_pos` points into `to`, which is one of
+    /// its neighbors.
+    fn wire_points_to(&self, wire_pos: BlockPos, wire: BlockState, to: BlockPos) -> bool {
+        if to == wire_pos.get_in_direction(BlockFace::Bottom) {
+            return true;
+        }
+
+        let Some(dir) = HORIZONTAL.into_iter().find(|&d| wire_pos.get_in_direction(d) == to) else {
+            return false
+        };
+
+        let is_dot = HORIZONTAL
+            .iter()
+            .all(|&d| wire.get(wire_prop(d)) == Some(PropValue::None));
+
+        is_dot || wire.get(wire_prop(dir)) != Some(PropValue::None)
+    }
+
+    /// The power of a solid block which is passed on to wire.
+    fn strong_power(&self, pos: BlockPos) -> u8 {
+        ALL.into_iter()
+            .map(|dir| {
+                let n = pos.get_in_direction(dir);
+                let state = self.get(n);
+
+                let powers = match state.to_kind() {
+                    // Torches only strongly power the block above them.
+                    BlockKind::RedstoneTorch | BlockKind::RedstoneWallTorch => {
+                        dir == BlockFace::Bottom && is_true(state, PropName::Lit)
+                    }
+                    BlockKind::Repeater => {
+                        is_true(state, PropName::Powered) && repeater_output(n, state) == Some(pos)
+                    }
+                    _ => {
+                        is_switch(state)
+                            && is_true(state, PropName::Powered)
+                            && attached_to(n, state) == Some(pos)
+                    }
+                };
+
+                if powers {
+                    15
+                } else {
+                    0
+                }
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The power of a solid block which is passed on to components other than
+    /// wire.
+    fn weak_power(&self, pos: BlockPos) -> u8 {
+        let from_wire = ALL
+            .into_iter()
+            .filter(|&dir| dir != BlockFace::Bottom)
+            .map(|dir| {
+                let n = pos.get_in_direction(dir);
+                let state = self.get(n);
+
+                if is_wire(state) && self.wire_points_to(n, state, pos) {
+                    wire_power(state)
+                } else {
+                    0
+                }
+            })
+            .max()
+            .unwrap_or(0);
+
+        from_wire.max(self.strong_power(pos))
+    }
+
+    /// The power a component at `pos` receives from its neighbor at `from`.
+    fn power_from(&self, pos: BlockPos, from: BlockPos) -> u8 {
+        let state = self.get(from);
+
+        if is_wire(state) {
+            if self.wire_points_to(from, state, pos) {
+                wire_power(state)
+            } else {
+                0
+            }
+        } else if is_conductor(state) {
+            self.weak_power(from)
+        } else {
+            self.source_power(from, state, pos)
+        }
+    }
+
+    /// The highest power a component at `pos` receives from any side.
+    fn input_power(&self, pos: BlockPos) -> u8 {
+        ALL.into_iter()
+            .map(|dir| self.power_from(pos, pos.get_in_direction(dir)))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Computes the power level of wire at `pos`.
+    fn compute_wire_power(&self, pos: BlockPos) -> u8 {
+        let mut power = 0;
+
+        let above_conducts = is_conductor(self.get(pos.get_in_direction(BlockFace::Top)));
+
+        for dir in ALL {
+            let n = pos.get_in_direction(dir);
+            let state = self.get(n);
+
+            let p = if is_wire(state) {
+                // Wire only connects to wire horizontally.
+                match dir {
+                    BlockFace::Top | BlockFace::Bottom => 0,
+                    _ => wire_power(state).saturating_sub(1),
+                }
+            } else if is_conductor(state) {
+                let mut p = self.strong_power(n);
+
+                // Wire going up the side of the block.
+                let up = self.get(n.get_in_direction(BlockFace::Top));
+                if dir != BlockFace::Top
+                    && dir != BlockFace::Bottom
+                    && !above_conducts
+                    && is_wire(up)
+                {
+                    p = p.max(wire_power(up).saturating_sub(1));
+                }
+
+                p
+            } else {
+                let mut p = self.source_power(n, state, pos);
+
+                // Wire going down the side of the block.
+                let down = self.get(n.get_in_direction(BlockFace::Bottom));
+                if dir != BlockFace::Top && dir != BlockFace::Bottom && is_wire(down) {
+                    p = p.max(wire_power(down).saturating_sub(1));
+                }
+
+                p
+            };
+
+            power = power.max(p);
+        }
+
+        power
+    }
+
+    /// Computes the connections of wire at `pos`.
+    fn wire_shape(&self, pos: BlockPos, mut state: BlockState) -> BlockState {
+        let above_conducts = is_conductor(self.get(pos.get_in_direction(BlockFace::Top)));
+
+        let mut sides = [PropValue::None; 4];
+
+        for (i, dir) in HORIZONTAL.into_iter().enumerate() {
+            let n = pos.get_in_direction(dir);
+            let neighbor = self.get(n);
+
+            let connects = is_wire(neighbor)
+                || is_torch(neighbor)
+                || is_switch(neighbor)
+                || neighbor.to_kind() == BlockKind::RedstoneBlock
+                || (neighbor.to_kind() == BlockKind::Repeater
+                    && matches!(facing(neighbor), Some(f) if f == dir || f == dir.opposite()));
+
+            if is_conductor(neighbor) {
+                if !above_conducts && is_wire(self.get(n.get_in_direction(BlockFace::Top))) {
+                    sides[i] = PropValue::Up;
+                }
+            } else if connects || is_wire(self.get(n.get_in_direction(BlockFace::Bottom))) {
+                sides[i] = PropValue::Side;
+            }
+        }
+
+        // A wire connected on one side extends to the opposite side.
+        let connected: Vec<_> = (0..4).filter(|&i| sides[i] != PropValue::None).collect();
+        if let [i] = connected[..] {
+            // North and south, and west and east are adjacent in `HORIZONTAL`.
+            sides[i ^ 1] = PropValue::Side;
+        }
+
+        for (i, dir) in HORIZONTAL.into_iter().enumerate() {
+            state = state.set(wire_prop(dir), sides[i]);
+        }
+
+        state
+    }
+}
+
+enum Update {
+    None,
+    Now(BlockState),
+    Delayed(i64),
+}
+
+/// Determines how the component at `pos` should change.
+fn compute_update(circuit: &Circuit, pos: BlockPos, state: BlockState) -> Update {
+    let kind = state.to_kind();
+    let name = kind.to_str();
+
+    match kind {
+        BlockKind::RedstoneWire => {
+            let power = circuit.compute_wire_power(pos);
+            let new_state = circuit.wire_shape(pos, state).set(
+                PropName::Power,
+                PropValue::from_u16(power as u16).expect("power should be valid"),
+            );
+
+            if new_state != state {
+                Update::Now(new_state)
+            } else {
+                Update::None
+            }
+        }
+        BlockKind::RedstoneTorch | BlockKind::RedstoneWallTorch => {
+            let Some(attached) = attached_to(pos, state) else {
+                return Update::None
+            };
+
+            let lit = circuit.weak_power(attached) == 0;
+
+            if lit != is_true(state, PropName::Lit) {
+                Update::Delayed(TORCH_DELAY)
+            } else {
+                Update::None
+            }
+        }
+        BlockKind::Repeater => {
+            let Some(dir) = facing(state) else {
+                return Update::None
+            };
+
+            let powered = circuit.power_from(pos, pos.get_in_direction(dir)) > 0;
+
+            if powered != is_true(state, PropName::Powered) {
+                let delay = state
+                    .get(PropName::Delay)
+                    .and_then(|d| d.to_u16())
+                    .unwrap_or(1);
+
+                Update::Delayed(delay as i64 * 2)
+            } else {
+                Update::None
+            }
+        }
+        BlockKind::RedstoneLamp => {
+            let lit = circuit.input_power(pos) > 0;
+
+            if lit != is_true(state, PropName::Lit) {
+                Update::Now(state.set(PropName::Lit, PropValue::from_bool(lit)))
+            } else {
+                Update::None
+            }
+        }
+        BlockKind::Piston | BlockKind::StickyPiston => {
+            let extended = circuit.input_power(pos) > 0;
+
+            if extended != is_true(state, PropName::Extended) {
+                Update::Now(state.set(PropName::Extended, PropValue::from_bool(extended)))
+            } else {
+                Update::None
+            }
+        }
+        _ if name.ends_with("_door")
+            || name.ends_with("_trapdoor")
+            || name.ends_with("_fence_gate") =>
+        {
+            let mut powered = circuit.input_power(pos) > 0;
+
+            // Both halves of a door are powered together.
+            if name.ends_with("_door") {
+                let other = match state.get(PropName::Half) {
+                    Some(PropValue::Lower) => pos.get_in_direction(BlockFace::Top),
+                    _ => pos.get_in_direction(BlockFace::Bottom),
+                };
+
+                if circuit.get(other).to_kind() == kind {
+                    powered |= circuit.input_power(other) > 0;
+                }
+            }
+
+            // Only open or close when the power changes, so that doors can
+            // still be opened by hand.
+            if powered != is_true(state, PropName::Powered) {
+                let value = PropValue::from_bool(powered);
+                Update::Now(
+                    state
+                        .set(PropName::Powered, value)
+                        .set(PropName::Open, value),
+                )
+            } else {
+                Update::None
+            }
+        }
+        _ => Update::None,
+    }
+}
+
+/// Computes the state of a torch or repeater whose delayed update is due.
+fn delayed_state(circuit: &Circuit, pos: BlockPos, state: BlockState) -> Option<BlockState> {
+    match state.to_kind() {
+        BlockKind::RedstoneTorch | BlockKind::RedstoneWallTorch => {
+            let lit = circuit.weak_power(attached_to(pos, state)?) == 0;
+            Some(state.set(PropName::Lit, PropValue::from_bool(lit)))
+        }
+        BlockKind::Repeater => {
+            let powered = circuit.power_from(pos, pos.get_in_direction(facing(state)?)) > 0;
+            Some(state.set(PropName::Powered, PropValue::from_bool(powered)))
+        }
+        _ => None,
+    }
+}
+
+fn simulate_redstone(
+    server: Res<Server>,
+    mut instances: Query<(&mut Instance, &mut RedstoneSimulation)>,
+) {
+    let current_tick = server.current_tick();
+
+    for (mut instance, mut sim) in &mut instances {
+        let instance = &mut *instance;
+        let sim = &mut *sim;
+
+        for pos in instance.changed_blocks() {
+            sim.mark_around(pos);
+        }
+
+        while let Some(&tick) = sim.pending.keys().next() {
+            if tick > current_tick {
+                break;
+            }
+
+            let mut due = sim.pending.remove(&tick).unwrap_or_default();
+            due.sort_unstable();
+
+            for pos in due {
+                sim.scheduled.remove(&pos);
+
+                let state = instance.block(pos).map_or(BlockState::AIR, |b| b.state());
+                let new_state = delayed_state(&Circuit { instance }, pos, state);
+
+                if let Some(new_state) = new_state.filter(|&s| s != state) {
+                    instance.set_block(pos, new_state);
+                    sim.mark_around(pos);
+                }
+            }
+        }
+
+        let mut updates = 0;
+
+        while updates < sim.max_updates_per_tick {
+            let Some(&pos) = sim.dirty.iter().next() else {
+                break
+            };
+
+            sim.dirty.remove(&pos);
+            updates += 1;
+
+            let Some(state) = instance.block(pos).map(|b| b.state()) else {
+                continue
+            };
+
+            match compute_update(&Circuit { instance }, pos, state) {
+                Update::None => {}
+                Update::Now(new_state) => {
+                    instance.set_block(pos, new_state);
+                    sim.mark_around(pos);
+                }
+                Update::Delayed(delay) => sim.schedule(pos, current_tick + delay),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attachment() {
+        let pos = BlockPos::new(0, 0, 0);
+
+        let torch = BlockState::REDSTONE_WALL_TORCH.set(PropName::Facing, PropValue::North);
+        assert_eq!(attached_to(pos, torch), Some(BlockPos::new(0, 0, 1)));
+
+        let lever = BlockState::LEVER.set(PropName::Face, PropValue::Ceiling);
+        assert_eq!(attached_to(pos, lever), Some(BlockPos::new(0, 1, 0)));
+
+        let repeater = BlockState::REPEATER.set(PropName::Facing, PropValue::East);
+        assert_eq!(
+            repeater_output(pos, repeater),
+            Some(BlockPos::new(-1, 0, 0))
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Runtime reflection of the game data known to the server.
+//!
+//! The functions in this module describe every block state, item, and biome
+//! so that tools such as web editors, exporters, and admin interfaces can
+//! introspect the game data without parsing the extractor's JSON files
+//! themselves. All the descriptions implement [`Serialize`], so they can be
+//! written out in any format supported by serde.
+//!
+//! ```
+//! use valence::registry;
+//!
+//! let stone = registry::block_states()
+//!     .find(|info| info.name == "stone")
+//!     .unwrap();
+//!
+//! assert!(stone.properties.is_empty());
+//! assert!(stone.is_opaque);
+//!
+//! let json = serde_json::to_string(&registry::items().collect::<Vec<_>>()).unwrap();
+//! assert!(json.contains("\"diamond_sword\""));
+//! ```
+
+use std::iter::FusedIterator;
+
+use serde::Serialize;
+use valence_protocol::block::{BlockEntityKind, BlockKind, BlockState};
+use valence_protocol::ident::Ident;
+use valence_protocol::ItemKind;
+
+use crate::biome::{Biome, BiomeId};
+use crate::server::Server;
+
+/// Describes a single block state.
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct BlockStateInfo {
+    #[serde(skip)]
+    pub state: BlockState,
+    /// The raw block state ID.
+    pub id: u16,
+    /// The name of the block kind of this state, such as `oak_stairs`.
+    pub name: &'static str,
+    /// The values of the block kind's properties in this state, in the order
+    /// returned by [`BlockKind::props`].
+    pub properties: Vec<(&'static str, &'static str)>,
+    /// If this is the default state of the block kind.
+    pub is_default: bool,
+    pub is_air: bool,
+    pub is_liquid: bool,
+    pub is_opaque: bool,
+    pub is_replaceable: bool,
+    pub luminance: u8,
+    /// The collision boxes of this state as `[min_x, min_y, min_z, max_x,
+    /// max_y, max_z]`, relative to the block position.
+    pub collision_shapes: Vec<[f64; 6]>,
+    /// The identifier of the block entity placed with this state, if any.
+    pub block_entity: Option<Ident<&'static str>>,
+    /// The name of the item of the block kind, if the block has an item.
+    pub item: Option<&'static str>,
+}
+
+impl BlockStateInfo {
+    pub fn new(state: BlockState) -> Self {
+        let kind = state.to_kind();
+        let item = kind.to_item_kind();
+
+        Self {
+            state,
+            id: state.to_raw(),
+            name: kind.to_str(),
+            properties: kind
+                .props()
+                .iter()
+                .filter_map(|&name| Some((name.to_str(), state.get(name)?.to_str())))
+                .collect(),
+            is_default: kind.to_state() == state,
+            is_air: state.is_air(),
+            is_liquid: state.is_liquid(),
+            is_opaque: state.is_opaque(),
+            is_replaceable: state.is_replaceable(),
+            luminance: state.luminance(),
+            collision_shapes: state.collision_shapes().collect(),
+            block_entity: state.block_entity_kind().map(BlockEntityKind::ident),
+            item: (item != ItemKind::Air).then(|| item.to_str()),
+        }
+    }
+}
+
+/// Describes a single item kind and its components.
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct ItemInfo {
+    #[serde(skip)]
+    pub kind: ItemKind,
+    /// The raw item ID.
+    pub id: u16,
+    /// The name of the item, such as `diamond_sword`.
+    pub name: &'static str,
+    pub translation_key: &'static str,
+    pub max_stack: u8,
+    /// The durability of the item, or zero if it can't be damaged.
+    pub max_durability: u16,
+    pub enchantability: u8,
+    pub fireproof: bool,
+    /// The food component, if the item can be eaten.
+    pub food: Option<FoodInfo>,
+    /// The name of the block kind the item places, if any.
+    pub block: Option<&'static str>,
+}
+
+/// The food component of an [`ItemInfo`].
+#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
+pub struct FoodInfo {
+    pub hunger: u16,
+    pub saturation: f32,
+    pub always_edible: bool,
+    pub meat: bool,
+    pub snack: bool,
+}
+
+impl ItemInfo {
+    pub fn new(kind: ItemKind) -> Self {
+        Self {
+            kind,
+            id: kind.to_raw(),
+            name: kind.to_str(),
+            translation_key: kind.translation_key(),
+            max_stack: kind.max_stack(),
+            max_durability: kind.max_durability(),
+            enchantability: kind.enchantability(),
+            fireproof: kind.fireproof(),
+            food: kind.food_component().map(|food| FoodInfo {
+                hunger: food.hunger,
+                saturation: food.saturation,
+                always_edible: food.always_edible,
+                meat: food.meat,
+                snack: food.snack,
+            }),
+            block: kind.to_block_kind().map(BlockKind::to_str),
+        }
+    }
+}
+
+/// Describes a biome added to the server.
+#[derive(Clone, Debug, Serialize)]
+pub struct BiomeInfo<'a> {
+    #[serde(skip)]
+    pub id: BiomeId,
+    /// The index of the biome in the registry sent to clients.
+    #[serde(rename = "id")]
+    pub raw_id: u16,
+    #[serde(flatten)]
+    pub biome: &'a Biome,
+}
+
+/// Returns an iterator over the descriptions of all block states in ascending
+/// order of their raw IDs.
+pub fn block_states() -> impl ExactSizeIterator<Item = BlockStateInfo> + FusedIterator + Clone {
+    (0..=BlockState::max_raw())
+        .map(|id| BlockStateInfo::new(BlockState::from_raw(id).expect("invalid block state ID")))
+}
+
+/// Returns an iterator over the descriptions of all item kinds in ascending
+/// order of their raw IDs.
+pub fn items() -> impl ExactSizeIterator<Item = ItemInfo> + FusedIterator + Clone {
+    ItemKind::ALL.into_iter().map(ItemInfo::new)
+}
+
+/// Returns an iterator over the descriptions of the biomes added to the
+/// server in ascending order of their [`BiomeId`]s.
+pub fn biomes(server: &Server) -> impl ExactSizeIterator<Item = BiomeInfo> + FusedIterator + Clone {
+    server.biomes().map(|(id, biome)| BiomeInfo {
+        id,
+        raw_id: id.0,
+        biome,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_state_properties() {
+        assert_eq!(block_states().len(), BlockState::max_raw() as usize + 1);
+
+        let stairs = BlockStateInfo::new(BlockKind::OakStairs.to_state());
+        assert_eq!(stairs.name, "oak_stairs");
+        assert!(stairs.is_default);
+        assert!(stairs.properties.contains(&("facing", "north")));
+        assert_eq!(stairs.item, Some("oak_stairs"));
+
+        let chest = BlockStateInfo::new(BlockState::CHEST);
+        assert_eq!(chest.block_entity.unwrap().path(), "chest");
+
+        assert_eq!(BlockStateInfo::new(BlockState::AIR).item, None);
+    }
+
+    #[test]
+    fn item_components() {
+        assert_eq!(items().len(), ItemKind::ALL.len());
+
+        let apple = ItemInfo::new(ItemKind::Apple);
+        assert_eq!(apple.name, "apple");
+        assert!(apple.food.is_some());
+        assert_eq!(apple.block, None);
+
+        let stone = ItemInfo::new(ItemKind::Stone);
+        assert_eq!(stone.max_stack, 64);
+        assert_eq!(stone.block, Some("stone"));
+    }
+}
@@ -0,0 +1,495 @@
+//! Generating resource packs for custom items, fonts, and sounds.
+//!
+//! A [`ResourcePackBuilder`] collects the custom content declared by the
+//! server and produces a [`ResourcePack`]: the zipped pack and its SHA-1 hash.
+//! The pack is expected to be hosted somewhere clients can download it from,
+//! after which [`ResourcePack::prompt`] can be used to ask clients to enable
+//! it.
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::resource_pack::ResourcePackBuilder;
+//!
+//! let mut builder = ResourcePackBuilder::new("My custom content");
+//!
+//! // Diamond swords with `CustomModelData: 1` will use the `ruby_sword` model.
+//! builder.add_item_model(
+//!     ItemKind::DiamondSword,
+//!     1,
+//!     ident!("my_server:item/ruby_sword"),
+//! );
+//!
+//! let pack = builder.build();
+//! assert_eq!(pack.hash().len(), 40);
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::{fs, io};
+
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use valence_protocol::text::space_advances;
+use valence_protocol::{Ident, ItemKind, Text};
+
+use crate::client::Client;
+
+/// The `pack_format` understood by the version of Minecraft Valence targets.
+pub const PACK_FORMAT: i32 = 12;
+
+/// The range of characters [`ResourcePackBuilder::add_glyph`] assigns glyphs
+/// from.
+const PRIVATE_USE_START: u32 = 0xe000;
+const PRIVATE_USE_END: char = '\u{f7ff}';
+
+/// Collects custom content and generates the resource pack files for it.
+///
+/// Paths given to the builder are relative to the root of the pack, e.g.
+/// `assets/minecraft/textures/item/stick.png`.
+#[derive(Clone, Debug)]
+pub struct ResourcePackBuilder {
+    description: Text,
+    files: BTreeMap<String, Vec<u8>>,
+    /// Maps vanilla items to their list of `(custom_model_data, model)`
+    /// overrides.
+    item_overrides: BTreeMap<&'static str, Vec<(i32, String)>>,
+    /// Maps font files to their list of providers.
+    fonts: BTreeMap<String, Vec<Value>>,
+    /// Maps font files to the next unused character for [`Self::add_glyph`].
+    next_glyph: BTreeMap<String, u32>,
+    /// Maps namespaces to the contents of their `sounds.json`.
+    sounds: BTreeMap<String, serde_json::Map<String, Value>>,
+}
+
+impl ResourcePackBuilder {
+    /// Creates a new empty builder. `description` is displayed in the client's
+    /// resource pack menu.
+    pub fn new(description: impl Into<Text>) -> Self {
+        Self {
+            description: description.into(),
+            files: BTreeMap::new(),
+            item_overrides: BTreeMap::new(),
+            fonts: BTreeMap::new(),
+            next_glyph: BTreeMap::new(),
+            sounds: BTreeMap::new(),
+        }
+    }
+
+    /// Adds an arbitrary file to the pack at `path`, replacing any previous
+    /// file at that path.
+    pub fn add_file(&mut self, path: impl Into<String>, data: impl Into<Vec<u8>>) -> &mut Self {
+        self.files.insert(path.into(), data.into());
+        self
+    }
+
+    /// Adds a PNG texture. `texture` is the name used to refer to the texture
+    /// from models and fonts, e.g. `my_server:item/ruby_sword`.
+    pub fn add_texture(
+        &mut self,
+        texture: Ident<impl AsRef<str>>,
+        png: impl Into<Vec<u8>>,
+    ) -> &mut Self {
+        self.add_file(
+            format!(
+                "assets/{}/textures/{}.png",
+                texture.namespace(),
+                texture.path()
+            ),
+            png,
+        )
+    }
+
+    /// Adds a JSON model. `model` is the name used to refer to the model, e.g.
+    /// `my_server:item/ruby_sword`.
+    pub fn add_model(&mut self, model: Ident<impl AsRef<str>>, json: &Value) -> &mut Self {
+        self.add_file(
+            format!("assets/{}/models/{}.json", model.namespace(), model.path()),
+            json.to_string(),
+        )
+    }
+
+    /// Makes stacks of `item` with the `CustomModelData` tag set to
+    /// `custom_model_data` render with `model` instead of the vanilla model.
+    ///
+    /// The model itself must be added separately with [`Self::add_model`]
+    /// unless it already exists on the client.
+    ///
+    /// **NOTE**: The generated vanilla model uses `item/generated` as its
+    /// parent, which is incorrect for blocks and items with special rendering
+    /// such as tools held in hand. Use [`Self::add_file`] to override the
+    /// vanilla model completely for such items.
+    pub fn add_item_model(
+        &mut self,
+        item: ItemKind,
+        custom_model_data: i32,
+        model: Ident<impl AsRef<str>>,
+    ) -> &mut Self {
+        let overrides = self.item_overrides.entry(item.to_str()).or_default();
+
+        match overrides.binary_search_by_key(&custom_model_data, |(cmd, _)| *cmd) {
+            Ok(i) => overrides[i].1 = model.to_string(),
+            Err(i) => overrides.insert(i, (custom_model_data, model.to_string())),
+        }
+
+        self
+    }
+
+    /// Adds a bitmap font provider to `font`. Each string in `chars` is a row
+    /// of characters in the texture, and every row must have the same number
+    /// of characters.
+    ///
+    /// Use the `minecraft:default` font to add glyphs to regular text, or a
+    /// custom font to only use the glyphs in text with the font set.
+    pub fn add_bitmap_font(
+        &mut self,
+        font: Ident<impl AsRef<str>>,
+        texture: Ident<impl AsRef<str>>,
+        height: i32,
+        ascent: i32,
+        chars: &[&str],
+    ) -> &mut Self {
+        self.fonts
+            .entry(font_path(font.as_str_ident()))
+            .or_default()
+            .push(json!({
+                "type": "bitmap",
+                "file": format!("{texture}.png"),
+                "height": height,
+                "ascent": ascent,
+                "chars": chars,
+            }));
+
+        self
+    }
+
+    /// Adds a single image as a glyph of `font` and returns the character it
+    /// was assigned. Characters are allocated from the private use area
+    /// starting at `U+E000`, so they don't conflict with regular text.
+    ///
+    /// The glyph is displayed by text containing the returned character with
+    /// the font set, e.g. `Text::text(c.to_string()).font("my_server:hud")`.
+    /// `ascent` can be used to shift the glyph vertically.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the font has run out of private use characters.
+    pub fn add_glyph(
+        &mut self,
+        font: Ident<impl AsRef<str>>,
+        texture: Ident<impl AsRef<str>>,
+        height: i32,
+        ascent: i32,
+    ) -> char {
+        let next = self
+            .next_glyph
+            .entry(font_path(font.as_str_ident()))
+            .or_insert(PRIVATE_USE_START);
+
+        let c = char::from_u32(*next)
+            .filter(|&c| c <= PRIVATE_USE_END)
+            .expect("font has too many glyphs");
+        *next += 1;
+
+        self.add_bitmap_font(font, texture, height, ascent, &[&c.to_string()]);
+        c
+    }
+
+    /// Adds a `space` provider to `font` containing the characters produced
+    /// by [`Text::space`]. Use text with this font to offset the text that
+    /// follows it, which is needed to position glyphs added with
+    /// [`Self::add_glyph`] on the screen.
+    ///
+    /// The space characters can be added to a font which also contains glyphs.
+    pub fn add_space_font(&mut self, font: Ident<impl AsRef<str>>) -> &mut Self {
+        let advances: serde_json::Map<_, _> = space_advances()
+            .map(|(c, advance)| (c.to_string(), advance.into()))
+            .collect();
+
+        self.fonts
+            .entry(font_path(font.as_str_ident()))
+            .or_default()
+            .push(json!({
+                "type": "space",
+                "advances": advances,
+            }));
+
+        self
+    }
+
+    /// Adds a sound event named `event` which plays one of the given sound
+    /// files at random. Sound files are referred to like textures, e.g.
+    /// `my_server:music/boss` for `assets/my_server/sounds/music/boss.ogg`.
+    ///
+    /// The resulting sound event can be played with [`Sound`] if it replaces a
+    /// vanilla sound, or with a custom sound packet otherwise.
+    ///
+    /// [`Sound`]: valence_protocol::Sound
+    pub fn add_sound(
+        &mut self,
+        event: Ident<impl AsRef<str>>,
+        subtitle: Option<&str>,
+        sounds: &[Ident<impl AsRef<str>>],
+    ) -> &mut Self {
+        let mut entry = json!({
+            "sounds": sounds.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+        });
+
+        if let Some(subtitle) = subtitle {
+            entry["subtitle"] = subtitle.into();
+        }
+
+        self.sounds
+            .entry(event.namespace().to_owned())
+            .or_default()
+            .insert(event.path().to_owned(), entry);
+
+        self
+    }
+
+    /// Returns every file in the pack paired with its contents.
+    fn generate_files(&self) -> BTreeMap<String, Vec<u8>> {
+        let mut files = self.files.clone();
+
+        files.insert(
+            "pack.mcmeta".into(),
+            json!({
+                "pack": {
+                    "pack_format": PACK_FORMAT,
+                    "description": self.description,
+                }
+            })
+            .to_string()
+            .into_bytes(),
+        );
+
+        for (item, overrides) in &self.item_overrides {
+            let model = json!({
+                "parent": "minecraft:item/generated",
+                "textures": {
+                    "layer0": format!("minecraft:item/{item}"),
+                },
+                "overrides": overrides
+                    .iter()
+                    .map(|(cmd, model)| json!({
+                        "predicate": { "custom_model_data": cmd },
+                        "model": model,
+                    }))
+                    .collect::<Vec<_>>(),
+            });
+
+            files.insert(
+                format!("assets/minecraft/models/item/{item}.json"),
+                model.to_string().into_bytes(),
+            );
+        }
+
+        for (path, providers) in &self.fonts {
+            files.insert(
+                path.clone(),
+                json!({ "providers": providers }).to_string().into_bytes(),
+            );
+        }
+
+        for (namespace, sounds) in &self.sounds {
+            files.insert(
+                format!("assets/{namespace}/sounds.json"),
+                Value::Object(sounds.clone()).to_string().into_bytes(),
+            );
+        }
+
+        files
+    }
+
+    /// Writes the uncompressed pack to the directory at `path`, creating it if
+    /// it does not exist. This is useful for inspecting the generated pack or
+    /// for loading it into the client's `resourcepacks` folder during
+    /// development.
+    pub fn write_to_dir(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let root = path.as_ref();
+
+        for (file, data) in self.generate_files() {
+            let file = root.join(file);
+
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            fs::write(file, data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Zips the pack and computes its hash.
+    pub fn build(&self) -> ResourcePack {
+        let mut zip = vec![];
+        write_zip(&mut zip, &self.generate_files()).expect("writing to a `Vec` cannot fail");
+
+        let hash = Sha1::digest(&zip)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+
+        ResourcePack { data: zip, hash }
+    }
+}
+
+/// A zipped resource pack generated by a [`ResourcePackBuilder`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ResourcePack {
+    data: Vec<u8>,
+    hash: String,
+}
+
+impl ResourcePack {
+    /// The bytes of the zip file. This is what should be served at the URL
+    /// given to clients.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The SHA-1 hash of the zip file as a 40-character hexadecimal string.
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// Writes the zip file to `path`.
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, &self.data)
+    }
+
+    /// Requests that `client` download this pack from `url` and enable it. See
+    /// [`Client::set_resource_pack`] for the meaning of the arguments.
+    pub fn prompt(
+        &self,
+        client: &mut Client,
+        url: &str,
+        forced: bool,
+        prompt_message: Option<Text>,
+    ) {
+        client.set_resource_pack(url, &self.hash, forced, prompt_message);
+    }
+}
+
+fn font_path(font: Ident<&str>) -> String {
+    format!("assets/{}/font/{}.json", font.namespace(), font.path())
+}
+
+/// Writes an uncompressed ("stored") zip archive containing `files`.
+///
+/// The archive is deterministic so that identical packs have identical
+/// hashes. The client doesn't care about compression, and most packs consist
+/// of already compressed PNG and OGG files anyway.
+fn write_zip(mut w: impl Write, files: &BTreeMap<String, Vec<u8>>) -> io::Result<()> {
+    // MS-DOS date for 1980-01-01 00:00.
+    const DOS_DATE: u16 = 0x21;
+
+    let mut central_dir = vec![];
+    let mut offset = 0_u32;
+
+    for (name, data) in files {
+        let crc = crc32(data);
+        let size = data.len() as u32;
+        let name_len = name.len() as u16;
+
+        // Local file header.
+        let mut header = vec![];
+        header.extend_from_slice(&0x04034b50_u32.to_le_bytes());
+        header.extend_from_slice(&10_u16.to_le_bytes()); // Version needed.
+        header.extend_from_slice(&0_u16.to_le_bytes()); // Flags.
+        header.extend_from_slice(&0_u16.to_le_bytes()); // Compression method.
+        header.extend_from_slice(&0_u16.to_le_bytes()); // Modification time.
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes()); // Compressed size.
+        header.extend_from_slice(&size.to_le_bytes()); // Uncompressed size.
+        header.extend_from_slice(&name_len.to_le_bytes());
+        header.extend_from_slice(&0_u16.to_le_bytes()); // Extra field length.
+        header.extend_from_slice(name.as_bytes());
+
+        w.write_all(&header)?;
+        w.write_all(data)?;
+
+        // Central directory file header.
+        central_dir.extend_from_slice(&0x02014b50_u32.to_le_bytes());
+        central_dir.extend_from_slice(&10_u16.to_le_bytes()); // Version made by.
+        central_dir.extend_from_slice(&header[4..30]);
+        central_dir.extend_from_slice(&0_u16.to_le_bytes()); // Comment length.
+        central_dir.extend_from_slice(&0_u16.to_le_bytes()); // Disk number.
+        central_dir.extend_from_slice(&0_u16.to_le_bytes()); // Internal attributes.
+        central_dir.extend_from_slice(&0_u32.to_le_bytes()); // External attributes.
+        central_dir.extend_from_slice(&offset.to_le_bytes());
+        central_dir.extend_from_slice(name.as_bytes());
+
+        offset += header.len() as u32 + size;
+    }
+
+    w.write_all(&central_dir)?;
+
+    // End of central directory record.
+    let count = files.len() as u16;
+    w.write_all(&0x06054b50_u32.to_le_bytes())?;
+    w.write_all(&0_u16.to_le_bytes())?; // Disk number.
+    w.write_all(&0_u16.to_le_bytes())?; // Disk with central directory.
+    w.write_all(&count.to_le_bytes())?;
+    w.write_all(&count.to_le_bytes())?;
+    w.write_all(&(central_dir.len() as u32).to_le_bytes())?;
+    w.write_all(&offset.to_le_bytes())?;
+    w.write_all(&0_u16.to_le_bytes()) // Comment length.
+}
+
+/// Computes the CRC-32 (IEEE) checksum of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::ident;
+
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn build_is_deterministic() {
+        let mut builder = ResourcePackBuilder::new("test");
+        builder
+            .add_item_model(ItemKind::Stick, 2, ident!("test:item/wand"))
+            .add_item_model(ItemKind::Stick, 1, ident!("test:item/staff"))
+            .add_sound(ident!("test:boss"), None, &[ident!("test:music/boss")]);
+
+        let files = builder.generate_files();
+        assert!(files.contains_key("pack.mcmeta"));
+        assert!(files.contains_key("assets/minecraft/models/item/stick.json"));
+        assert!(files.contains_key("assets/test/sounds.json"));
+
+        assert_eq!(builder.build(), builder.build());
+    }
+
+    #[test]
+    fn glyphs_are_allocated_per_font() {
+        let mut builder = ResourcePackBuilder::new("test");
+
+        let a = builder.add_glyph(ident!("test:hud"), ident!("test:font/a"), 8, 7);
+        let b = builder.add_glyph(ident!("test:hud"), ident!("test:font/b"), 8, 7);
+        let c = builder.add_glyph(ident!("test:other"), ident!("test:font/c"), 8, 7);
+
+        assert_eq!(a, '\u{e000}');
+        assert_eq!(b, '\u{e001}');
+        assert_eq!(c, '\u{e000}');
+    }
+}
@@ -1,3 +1,4 @@
+use std::future::Future;
 use std::iter::FusedIterator;
 use std::net::{IpAddr, SocketAddr};
 use std::ops::Deref;
@@ -11,23 +12,33 @@ use bevy_app::AppExit;
 use bevy_ecs::event::ManualEventReader;
 use bevy_ecs::prelude::*;
 use flume::{Receiver, Sender};
+use parking_lot::Mutex;
 use rand::rngs::OsRng;
 use rsa::{PublicKeyParts, RsaPrivateKey};
+use rustc_hash::FxHashMap;
 use tokio::runtime::{Handle, Runtime};
 use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use tracing::{info_span, warn};
 use uuid::Uuid;
 use valence_nbt::{compound, Compound, List};
 use valence_protocol::types::Property;
-use valence_protocol::{ident, Username};
+use valence_protocol::{ident, Text, Username};
 
 use crate::biome::{validate_biomes, Biome, BiomeId};
 use crate::client::event::{event_loop_run_criteria, register_client_events};
-use crate::client::{update_clients, Client};
-use crate::config::{AsyncCallbacks, ConnectionMode, ServerPlugin};
+use crate::client::{
+    apply_explosion_knockback, update_client_settings, update_clients, Client, ClientTimedOut,
+    GameModeChanged, TeleportTimedOut,
+};
+use crate::config::{
+    first_tcp_address, AsyncCallbacks, ConnectionMode, Listener, OutgoingOverflowPolicy,
+    ServerPlugin, TickLagPolicy, MAX_ENTITY_TELEPORT_THRESHOLD,
+};
 use crate::dimension::{validate_dimensions, Dimension, DimensionId};
 use crate::entity::{
-    check_entity_invariants, deinit_despawned_entities, init_entities, update_entities,
-    McEntityManager,
+    check_entity_invariants, deinit_despawned_entities, init_entities, remove_entities,
+    update_entities, EntityKind, McEntity, McEntityManager,
 };
 use crate::instance::{
     check_instance_invariants, update_instances_post_client, update_instances_pre_client, Instance,
@@ -39,17 +50,32 @@ use crate::inventory::{
 };
 use crate::player_list::{update_player_list, PlayerList};
 use crate::server::connect::do_accept_loop;
+use crate::server::status_cache::StatusCache;
+use crate::testing::SingleThreadedStages;
+use crate::timings::{finish_tick_timings, start_tick_timings, TickTimings};
 use crate::Despawned;
 
 mod byte_channel;
 mod connect;
 pub(crate) mod connection;
+mod local_client;
+mod proxy_protocol;
+mod segment_channel;
+mod status_cache;
+
+pub use local_client::LocalClient;
 
 /// Contains global server state accessible as a [`Resource`].
 #[derive(Resource)]
 pub struct Server {
     /// Incremented on every tick.
     current_tick: i64,
+    /// The [`McEntity`] entities by UUID.
+    uuid_to_entity: FxHashMap<Uuid, Entity>,
+    /// The [`Client`] entities by UUID.
+    uuid_to_client: FxHashMap<Uuid, Entity>,
+    /// Set when the server is shutting down.
+    shutdown_reason: Option<Text>,
     shared: SharedServer,
 }
 
@@ -71,6 +97,95 @@ impl Server {
     pub fn current_tick(&self) -> i64 {
         self.current_tick
     }
+
+    /// Gets the entity with the [`McEntity`] component with the given UUID.
+    ///
+    /// The index is updated at the end of each tick, so entities spawned in
+    /// the current tick are not found yet.
+    pub fn entity_by_uuid(&self, uuid: Uuid) -> Option<Entity> {
+        self.uuid_to_entity.get(&uuid).copied()
+    }
+
+    /// Gets the entity with the [`Client`] component with the given UUID.
+    ///
+    /// The index is updated when clients are spawned at the start of each
+    /// tick and when they are despawned at the end of each tick.
+    pub fn client_by_uuid(&self, uuid: Uuid) -> Option<Entity> {
+        self.uuid_to_client.get(&uuid).copied()
+    }
+
+    /// Shuts the server down at the end of the current tick.
+    ///
+    /// All clients are kicked with `reason`, the [`ShutdownHooks`] are run,
+    /// and an [`AppExit`] event is sent. Before exiting, the server waits until
+    /// the kick messages are written to the clients, but no longer than
+    /// [`SHUTDOWN_GRACE_PERIOD`]. In embedded mode, exiting is up to the
+    /// caller.
+    ///
+    /// Calling this again while the server is shutting down has no effect.
+    pub fn shutdown(&mut self, reason: impl Into<Text>) {
+        if self.shutdown_reason.is_none() {
+            self.shutdown_reason = Some(reason.into());
+        }
+    }
+
+    /// Returns the reason given to [`Server::shutdown`] if the server is
+    /// shutting down.
+    pub fn shutdown_reason(&self) -> Option<&Text> {
+        self.shutdown_reason.as_ref()
+    }
+}
+
+/// An event sent before a tick when the server is behind its tick schedule by
+/// more than one tick. What happens to the missed ticks depends on the
+/// [`ServerPlugin::tick_lag_policy`].
+///
+/// This event is not sent in embedded mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ServerLagging {
+    /// How long after its scheduled time the tick started.
+    pub behind: Duration,
+    /// The number of ticks that were skipped. Ticks which are caught up on are
+    /// not counted.
+    pub skipped_ticks: u64,
+}
+
+/// The maximum time the packets still queued for a client, such as the reason
+/// it was kicked for, are written for after the client is dropped. This is
+/// also the maximum time the server waits for the kick messages to be written
+/// when shutting down.
+pub const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// Functions run with access to the [`World`] when the server shuts down,
+/// e.g. to save the world. Hooks run in the order they were added, after all
+/// clients have been kicked.
+///
+/// ```
+/// use valence::prelude::*;
+/// use valence::server::ShutdownHooks;
+///
+/// fn setup(mut hooks: ResMut<ShutdownHooks>) {
+///     hooks.add(|world: &mut World| {
+///         let instances = world.query::<&Instance>().iter(world).count();
+///         println!("saving {instances} instances");
+///     });
+/// }
+/// ```
+#[derive(Resource, Default)]
+pub struct ShutdownHooks(Vec<Box<dyn FnOnce(&mut World) + Send + Sync>>);
+
+impl ShutdownHooks {
+    pub fn add(&mut self, hook: impl FnOnce(&mut World) + Send + Sync + 'static) {
+        self.0.push(Box::new(hook));
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 /// The subset of global server state which can be shared between threads.
@@ -80,13 +195,27 @@ impl Server {
 pub struct SharedServer(Arc<SharedServerInner>);
 
 struct SharedServerInner {
-    address: SocketAddr,
+    listeners: Arc<[Listener]>,
     tps: i64,
     connection_mode: ConnectionMode,
+    session_server_url: Arc<str>,
     compression_threshold: Option<u32>,
+    compression_level: u32,
+    compress_loopback: bool,
+    /// Cached server list ping responses and ping counts.
+    status_cache: StatusCache,
+    max_chunk_sends_per_tick: Option<usize>,
+    max_chunk_bytes_per_tick: Option<usize>,
+    max_client_chunk_sends_per_tick: Option<usize>,
+    max_client_chunk_bytes_per_tick: Option<usize>,
+    entity_teleport_threshold: f64,
+    entity_tracking_ranges: FxHashMap<EntityKind, u8>,
+    keepalive_interval: Duration,
+    client_timeout: Duration,
     max_connections: usize,
     incoming_capacity: usize,
     outgoing_capacity: usize,
+    outgoing_overflow_policy: OutgoingOverflowPolicy,
     /// The tokio handle used by the server.
     tokio_handle: Handle,
     /// Holding a runtime handle is not enough to keep tokio working. We need
@@ -106,6 +235,10 @@ struct SharedServerInner {
     /// A semaphore used to limit the number of simultaneous connections to the
     /// server. Closing this semaphore stops new connections.
     connection_sema: Arc<Semaphore>,
+    /// The tasks accepting connections on each listener. The accept loops
+    /// hold a connection permit while waiting for a connection, so they are
+    /// aborted before waiting for the open connections on shutdown.
+    accept_loops: Mutex<Vec<JoinHandle<()>>>,
     /// The RSA keypair used for encryption with clients.
     rsa_key: RsaPrivateKey,
     /// The public part of `rsa_key` encoded in DER, which is an ASN.1 format.
@@ -122,9 +255,40 @@ impl SharedServer {
         Instance::new(dimension, self)
     }
 
-    /// Gets the socket address this server is bound to.
+    /// Gets the listeners this server accepts connections on.
+    pub fn listeners(&self) -> &[Listener] {
+        &self.0.listeners
+    }
+
+    /// Gets the address of the first TCP listener.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no listener is bound to a TCP address.
+    #[deprecated = "use `listeners` instead"]
     pub fn address(&self) -> SocketAddr {
-        self.0.address
+        first_tcp_address(&self.0.listeners).expect("no TCP listener is configured")
+    }
+
+    /// Waits until all connections to the server are closed, but no longer
+    /// than `timeout`. No new connections are accepted while waiting. Returns
+    /// whether the connections were closed in time.
+    fn wait_for_connections(&self, timeout: Duration) -> bool {
+        let permits = u32::try_from(self.0.max_connections).unwrap_or(u32::MAX);
+
+        // Stop accepting connections. This also releases the permits the
+        // accept loops hold while waiting for a connection.
+        for accept_loop in self.0.accept_loops.lock().drain(..) {
+            accept_loop.abort();
+        }
+
+        self.0
+            .tokio_handle
+            .block_on(tokio::time::timeout(
+                timeout,
+                self.0.connection_sema.acquire_many(permits),
+            ))
+            .is_ok()
     }
 
     /// Gets the configured ticks per second of this server.
@@ -137,12 +301,74 @@ impl SharedServer {
         &self.0.connection_mode
     }
 
+    /// Gets the base URL of the session server used in online mode.
+    pub fn session_server_url(&self) -> &str {
+        &self.0.session_server_url
+    }
+
     /// Gets the compression threshold for packets. `None` indicates no
     /// compression.
     pub fn compression_threshold(&self) -> Option<u32> {
         self.0.compression_threshold
     }
 
+    /// Gets the zlib compression level for packets from 0 to 9.
+    pub fn compression_level(&self) -> u32 {
+        self.0.compression_level
+    }
+
+    /// Gets the maximum number of chunks sent to all clients per tick. `None`
+    /// indicates no limit.
+    pub fn max_chunk_sends_per_tick(&self) -> Option<usize> {
+        self.0.max_chunk_sends_per_tick
+    }
+
+    /// Gets the maximum number of bytes of chunk data sent to all clients per
+    /// tick. `None` indicates no limit.
+    pub fn max_chunk_bytes_per_tick(&self) -> Option<usize> {
+        self.0.max_chunk_bytes_per_tick
+    }
+
+    /// Gets the maximum number of chunks sent to a single client per tick.
+    /// `None` indicates no limit.
+    pub fn max_client_chunk_sends_per_tick(&self) -> Option<usize> {
+        self.0.max_client_chunk_sends_per_tick
+    }
+
+    /// Gets the maximum number of bytes of chunk data sent to a single client
+    /// per tick. `None` indicates no limit.
+    pub fn max_client_chunk_bytes_per_tick(&self) -> Option<usize> {
+        self.0.max_client_chunk_bytes_per_tick
+    }
+
+    /// Gets the distance an entity must move in a tick to be teleported
+    /// instead of moved relatively.
+    pub fn entity_teleport_threshold(&self) -> f64 {
+        self.0.entity_teleport_threshold
+    }
+
+    /// Gets the configured tracking range of entities of the given kind, in
+    /// chunks. `None` means entities of the kind are tracked up to the view
+    /// distance of each client.
+    pub fn entity_tracking_range(&self, kind: EntityKind) -> Option<u8> {
+        self.0.entity_tracking_ranges.get(&kind).copied()
+    }
+
+    pub(crate) fn entity_tracking_ranges(&self) -> &FxHashMap<EntityKind, u8> {
+        &self.0.entity_tracking_ranges
+    }
+
+    /// Gets the time between keepalive packets sent to each client.
+    pub fn keepalive_interval(&self) -> Duration {
+        self.0.keepalive_interval
+    }
+
+    /// Gets the time clients have to respond to a keepalive packet before they
+    /// time out.
+    pub fn client_timeout(&self) -> Duration {
+        self.0.client_timeout
+    }
+
     /// Gets the maximum number of connections allowed to the server at once.
     pub fn max_connections(&self) -> usize {
         self.0.max_connections
@@ -158,6 +384,11 @@ impl SharedServer {
         self.0.outgoing_capacity
     }
 
+    /// Gets the configured outgoing overflow policy.
+    pub fn outgoing_overflow_policy(&self) -> OutgoingOverflowPolicy {
+        self.0.outgoing_overflow_policy
+    }
+
     /// Gets a handle to the tokio instance this server is using.
     pub fn tokio_handle(&self) -> &Handle {
         &self.0.tokio_handle
@@ -233,6 +464,18 @@ pub fn build_plugin(
         plugin.tps > 0,
         "configured tick rate must be greater than zero"
     );
+    ensure!(
+        plugin.compression_level <= 9,
+        "configured compression level must be in 0..=9"
+    );
+    ensure!(
+        !plugin.listen || !plugin.listeners.is_empty(),
+        "at least one listener must be configured"
+    );
+    ensure!(
+        plugin.entity_teleport_threshold >= 0.0,
+        "configured entity teleport threshold must be nonnegative"
+    );
     ensure!(
         plugin.incoming_capacity > 0,
         "configured incoming packet capacity must be nonzero"
@@ -267,13 +510,28 @@ pub fn build_plugin(
     let (new_clients_send, new_clients_recv) = flume::bounded(64);
 
     let shared = SharedServer(Arc::new(SharedServerInner {
-        address: plugin.address,
+        listeners: plugin.listeners.clone(),
         tps: plugin.tps,
         connection_mode: plugin.connection_mode.clone(),
+        session_server_url: plugin.session_server_url.clone(),
         compression_threshold: plugin.compression_threshold,
+        compression_level: plugin.compression_level,
+        compress_loopback: plugin.compress_loopback,
+        status_cache: StatusCache::new(plugin.ping_cache_ttl, plugin.ping_rate_limit),
+        max_chunk_sends_per_tick: plugin.max_chunk_sends_per_tick,
+        max_chunk_bytes_per_tick: plugin.max_chunk_bytes_per_tick,
+        max_client_chunk_sends_per_tick: plugin.max_client_chunk_sends_per_tick,
+        max_client_chunk_bytes_per_tick: plugin.max_client_chunk_bytes_per_tick,
+        entity_teleport_threshold: plugin
+            .entity_teleport_threshold
+            .min(MAX_ENTITY_TELEPORT_THRESHOLD),
+        entity_tracking_ranges: plugin.entity_tracking_ranges.clone(),
+        keepalive_interval: plugin.keepalive_interval,
+        client_timeout: plugin.client_timeout,
         max_connections: plugin.max_connections,
         incoming_capacity: plugin.incoming_capacity,
         outgoing_capacity: plugin.outgoing_capacity,
+        outgoing_overflow_policy: plugin.outgoing_overflow_policy,
         tokio_handle,
         _tokio_runtime: runtime,
         dimensions: plugin.dimensions.clone(),
@@ -283,6 +541,7 @@ pub fn build_plugin(
         new_clients_send,
         new_clients_recv,
         connection_sema: Arc::new(Semaphore::new(plugin.max_connections)),
+        accept_loops: Mutex::new(vec![]),
         rsa_key,
         public_key_der,
         http_client: Default::default(),
@@ -290,6 +549,9 @@ pub fn build_plugin(
 
     let server = Server {
         current_tick: 0,
+        uuid_to_entity: FxHashMap::default(),
+        uuid_to_client: FxHashMap::default(),
+        shutdown_reason: None,
         shared,
     };
 
@@ -299,8 +561,15 @@ pub fn build_plugin(
     let start_accept_loop = move || {
         let _guard = shared.tokio_handle().enter();
 
-        // Start accepting new connections.
-        tokio::spawn(do_accept_loop(shared.clone(), callbacks.clone()));
+        // Start accepting new connections on every listener.
+        let mut accept_loops = shared.0.accept_loops.lock();
+
+        for listener in shared.listeners().iter().cloned() {
+            accept_loops.push(spawn_task(
+                "accept_loop",
+                do_accept_loop(shared.clone(), callbacks.clone(), listener),
+            ));
+        }
     };
 
     let shared = server.shared.clone();
@@ -312,7 +581,15 @@ pub fn build_plugin(
                 break
             };
 
-            world.spawn((client, Inventory::new(InventoryKind::Player)));
+            let uuid = client.uuid();
+            let entity = world
+                .spawn((client, Inventory::new(InventoryKind::Player)))
+                .id();
+
+            world
+                .resource_mut::<Server>()
+                .uuid_to_client
+                .insert(uuid, entity);
         }
     };
 
@@ -320,31 +597,51 @@ pub fn build_plugin(
 
     // Start accepting connections in PostStartup to allow user startup code to run
     // first.
-    app.add_startup_system_to_stage(StartupStage::PostStartup, start_accept_loop);
+    if plugin.listen {
+        app.add_startup_system_to_stage(StartupStage::PostStartup, start_accept_loop);
+    }
 
     // Insert resources.
     app.insert_resource(server)
         .insert_resource(McEntityManager::new())
-        .insert_resource(PlayerList::new());
+        .insert_resource(PlayerList::new())
+        .init_resource::<ShutdownHooks>()
+        .init_resource::<TickTimings>()
+        .add_event::<ClientTimedOut>()
+        .add_event::<GameModeChanged>()
+        .add_event::<TeleportTimedOut>()
+        .add_event::<ServerLagging>();
     register_client_events(&mut app.world);
 
+    let event_loop_stage = if app.world.contains_resource::<SingleThreadedStages>() {
+        SystemStage::single_threaded()
+    } else {
+        SystemStage::parallel()
+    };
+
     // Add core systems and stages. User code is expected to run in
     // `CoreStage::Update` and `EventLoop`.
-    app.add_system_to_stage(CoreStage::PreUpdate, spawn_new_clients)
+    app.add_system_to_stage(CoreStage::First, start_tick_timings)
+        .add_system_to_stage(CoreStage::PreUpdate, spawn_new_clients)
         .add_stage_before(
             CoreStage::Update,
             EventLoop,
-            SystemStage::parallel().with_run_criteria(event_loop_run_criteria),
+            event_loop_stage.with_run_criteria(event_loop_run_criteria),
         )
+        .add_system_to_stage(EventLoop, update_client_settings)
         .add_system_set_to_stage(
             CoreStage::PostUpdate,
             SystemSet::new()
                 .label("valence_core")
                 .with_system(init_entities)
+                .with_system(update_uuid_index.before(despawn_marked_entities))
                 .with_system(check_entity_invariants)
                 .with_system(check_instance_invariants.after(check_entity_invariants))
                 .with_system(update_player_list.before(update_instances_pre_client))
+                .with_system(remove_entities.before(update_instances_pre_client))
                 .with_system(update_instances_pre_client.after(init_entities))
+                .with_system(kick_clients_on_shutdown.before(update_clients))
+                .with_system(apply_explosion_knockback.before(update_clients))
                 .with_system(update_clients.after(update_instances_pre_client))
                 .with_system(update_instances_post_client.after(update_clients))
                 .with_system(deinit_despawned_entities.after(update_instances_post_client))
@@ -372,39 +669,105 @@ pub fn build_plugin(
                         .before(update_player_inventories),
                 ),
         )
+        .add_system_to_stage(CoreStage::Last, run_shutdown_hooks)
+        .add_system_to_stage(CoreStage::Last, finish_tick_timings)
         .add_system_to_stage(CoreStage::Last, inc_current_tick);
 
+    // In embedded mode, the user drives the ticks by calling `App::update`.
+    if !plugin.listen {
+        return Ok(());
+    }
+
     let tick_duration = Duration::from_secs_f64((shared.tps() as f64).recip());
+    let tick_lag_policy = plugin.tick_lag_policy;
 
     // Overwrite the app's runner.
     app.set_runner(move |mut app: App| {
         let mut app_exit_event_reader = ManualEventReader::<AppExit>::default();
+        let mut next_tick = Instant::now();
 
         loop {
-            let tick_start = Instant::now();
-
             // Stop the server if there was an AppExit event.
-            if let Some(app_exit_events) = app.world.get_resource_mut::<Events<AppExit>>() {
-                if app_exit_event_reader
-                    .iter(&app_exit_events)
-                    .last()
-                    .is_some()
-                {
+            if let Some(app_exit_events) = app.world.get_resource::<Events<AppExit>>() {
+                if app_exit_event_reader.iter(app_exit_events).last().is_some() {
+                    let server = app.world.resource::<Server>();
+                    let shutting_down = server.shutdown_reason().is_some();
+                    let shared = server.shared().clone();
+
+                    // Dropping the clients closes their connections once the
+                    // kick messages are written.
+                    drop(app);
+
+                    if shutting_down && !shared.wait_for_connections(SHUTDOWN_GRACE_PERIOD) {
+                        warn!("not all kick messages were written before shutting down");
+                    }
+
                     return;
                 }
             }
 
+            let behind = next_tick.elapsed();
+
+            if behind > tick_duration {
+                let skipped_ticks = skipped_ticks(tick_lag_policy, behind, tick_duration);
+                next_tick += tick_duration.mul_f64(skipped_ticks as f64);
+
+                app.world
+                    .resource_mut::<Events<ServerLagging>>()
+                    .send(ServerLagging {
+                        behind,
+                        skipped_ticks,
+                    });
+            }
+
             // Run the scheduled stages.
-            app.update();
+            let tick = app.world.resource::<Server>().current_tick();
+            info_span!("tick", tick).in_scope(|| app.update());
 
-            // Sleep until the next tick.
-            thread::sleep(tick_duration.saturating_sub(tick_start.elapsed()));
+            // Sleep until the next tick. When catching up on missed ticks, the
+            // next tick is already due.
+            next_tick += tick_duration;
+            thread::sleep(next_tick.saturating_duration_since(Instant::now()));
         }
     });
 
     Ok(())
 }
 
+/// Returns the number of ticks to skip when the next tick is `behind` its
+/// scheduled time.
+fn skipped_ticks(policy: TickLagPolicy, behind: Duration, tick_duration: Duration) -> u64 {
+    let missed_ticks = (behind.as_secs_f64() / tick_duration.as_secs_f64()) as u64;
+
+    match policy {
+        TickLagPolicy::Skip => missed_ticks,
+        TickLagPolicy::CatchUp { max_ticks } => missed_ticks.saturating_sub(max_ticks as u64),
+    }
+}
+
+/// Spawns a task on the current tokio runtime. The task is shown with `name`
+/// in tokio-console if the `tokio-console` feature is enabled.
+#[cfg(all(tokio_unstable, feature = "tokio-console"))]
+pub(crate) fn spawn_task<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::task::Builder::new()
+        .name(name)
+        .spawn(future)
+        .expect("failed to spawn task")
+}
+
+#[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+pub(crate) fn spawn_task<F>(_name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(future)
+}
+
 /// The stage label for the special "event loop" stage.
 #[derive(StageLabel)]
 pub struct EventLoop;
@@ -417,6 +780,68 @@ fn despawn_marked_entities(mut commands: Commands, entities: Query<Entity, With<
     }
 }
 
+/// Adds new entities and clients to the UUID index of the [`Server`] and
+/// removes despawned ones.
+fn update_uuid_index(
+    mut server: ResMut<Server>,
+    entities: Query<(Entity, &McEntity), Added<McEntity>>,
+    clients: Query<(Entity, &Client), Added<Client>>,
+    despawned: Query<(Entity, Option<&McEntity>, Option<&Client>), With<Despawned>>,
+) {
+    for (entity, mc_entity) in &entities {
+        server.uuid_to_entity.insert(mc_entity.uuid(), entity);
+    }
+
+    // Clients spawned by Valence are already indexed, but clients can also be
+    // spawned by users.
+    for (entity, client) in &clients {
+        server.uuid_to_client.insert(client.uuid(), entity);
+    }
+
+    for (entity, mc_entity, client) in &despawned {
+        // Another entity with the same UUID might have taken its place.
+        if let Some(mc_entity) = mc_entity {
+            if server.uuid_to_entity.get(&mc_entity.uuid()) == Some(&entity) {
+                server.uuid_to_entity.remove(&mc_entity.uuid());
+            }
+        }
+
+        if let Some(client) = client {
+            if server.uuid_to_client.get(&client.uuid()) == Some(&entity) {
+                server.uuid_to_client.remove(&client.uuid());
+            }
+        }
+    }
+}
+
+fn kick_clients_on_shutdown(server: Res<Server>, mut clients: Query<&mut Client>) {
+    let Some(reason) = server.shutdown_reason() else {
+        return
+    };
+
+    for mut client in &mut clients {
+        if !client.is_disconnected() {
+            client.kick(reason.clone());
+        }
+    }
+}
+
+fn run_shutdown_hooks(world: &mut World) {
+    if world.resource::<Server>().shutdown_reason().is_none() {
+        return;
+    }
+
+    let hooks = std::mem::take(&mut world.resource_mut::<ShutdownHooks>().0);
+
+    for hook in hooks {
+        hook(world);
+    }
+
+    if let Some(mut events) = world.get_resource_mut::<Events<AppExit>>() {
+        events.send(AppExit);
+    }
+}
+
 fn inc_current_tick(mut server: ResMut<Server>) {
     server.current_tick += 1;
 }
@@ -449,3 +874,226 @@ fn make_registry_codec(dimensions: &[Dimension], biomes: &[Biome]) -> Compound {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::{PacketDecoder, PacketEncoder};
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::config::ConnectionContext;
+    use crate::entity::EntityKind;
+    use crate::server::connection::InitialConnection;
+    use crate::testing::TestServer;
+    use crate::unit_test::util::{gen_client_info, scenario_single_client};
+
+    #[test]
+    fn session_server_url() {
+        let server = TestServer::with_plugin(
+            ServerPlugin::new(()).with_session_server_url("https://auth.example.com/api/"),
+        );
+        let shared = server.server().shared();
+
+        let url = shared.tokio_handle().block_on(().session_server(
+            shared,
+            Username::new("test").unwrap(),
+            "digest",
+            &IpAddr::from([127, 0, 0, 1]),
+        ));
+
+        assert_eq!(
+            url,
+            "https://auth.example.com/api/session/minecraft/hasJoined?username=test&serverId=digest"
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_address_getters() {
+        let first = SocketAddr::from(([127, 0, 0, 1], 25565));
+        let second = SocketAddr::from(([127, 0, 0, 1], 25566));
+
+        let plugin = ServerPlugin::new(())
+            .with_listeners([Listener::new(first), Listener::new(second)].as_slice());
+        assert_eq!(plugin.address(), first);
+
+        let server = TestServer::with_plugin(plugin);
+        assert_eq!(server.server().shared().address(), first);
+    }
+
+    #[test]
+    fn tick_lag_policies() {
+        let tick = Duration::from_millis(50);
+        let behind = Duration::from_millis(260);
+
+        assert_eq!(skipped_ticks(TickLagPolicy::Skip, behind, tick), 5);
+        assert_eq!(
+            skipped_ticks(TickLagPolicy::CatchUp { max_ticks: 3 }, behind, tick),
+            2
+        );
+        assert_eq!(
+            skipped_ticks(TickLagPolicy::CatchUp { max_ticks: 10 }, behind, tick),
+            0
+        );
+    }
+
+    #[test]
+    fn uuid_index() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app
+            .world
+            .query_filtered::<Entity, With<Instance>>()
+            .single(&app.world);
+
+        let uuid = Uuid::from_u128(1);
+        let entity = app
+            .world
+            .spawn(McEntity::with_uuid(EntityKind::Zombie, instance_ent, uuid))
+            .id();
+
+        app.update();
+
+        let client_uuid = app.world.get::<Client>(client_ent).unwrap().uuid();
+        let server = app.world.resource::<Server>();
+        assert_eq!(server.entity_by_uuid(uuid), Some(entity));
+        assert_eq!(server.client_by_uuid(client_uuid), Some(client_ent));
+
+        app.world.entity_mut(entity).insert(Despawned);
+        app.world.entity_mut(client_ent).insert(Despawned);
+        app.update();
+
+        let server = app.world.resource::<Server>();
+        assert_eq!(server.entity_by_uuid(uuid), None);
+        assert_eq!(server.client_by_uuid(client_uuid), None);
+    }
+
+    #[test]
+    fn shutdown_kicks_clients_and_runs_hooks() {
+        #[derive(Resource)]
+        struct Saved;
+
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world
+            .resource_mut::<ShutdownHooks>()
+            .add(|world: &mut World| world.insert_resource(Saved));
+        app.world.resource_mut::<Server>().shutdown("Server closed");
+
+        app.update();
+
+        assert!(app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .is_disconnected());
+        assert!(app.world.contains_resource::<Saved>());
+        assert!(app.world.resource::<ShutdownHooks>().is_empty());
+        assert!(!app.world.resource::<Events<AppExit>>().is_empty());
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::DisconnectPlay(_));
+    }
+
+    #[test]
+    fn shutdown_waits_for_kick_messages() {
+        let mut app = App::new();
+        app.add_plugin(
+            ServerPlugin::new(())
+                .with_compression_threshold(None)
+                .with_connection_mode(ConnectionMode::Offline)
+                .with_listen(false),
+        );
+
+        let server = app.world.resource::<Server>();
+        let shared = server.shared().clone();
+        let instance = server.new_instance(DimensionId::default());
+        let instance_ent = app.world.spawn(instance).id();
+
+        let (mut remote, mut client) = shared.tokio_handle().block_on(async {
+            let (remote, local) = tokio::io::duplex(1 << 20);
+            let (read, write) = tokio::io::split(local);
+
+            let permit = shared
+                .0
+                .connection_sema
+                .clone()
+                .try_acquire_owned()
+                .unwrap();
+            let client = InitialConnection::new(
+                read,
+                write,
+                PacketEncoder::new(),
+                PacketDecoder::new(),
+                Duration::from_secs(5),
+                permit,
+            )
+            .into_client(
+                gen_client_info("test"),
+                ConnectionContext::new(([127, 0, 0, 1], 0).into()),
+                shared.incoming_capacity(),
+                shared.outgoing_capacity(),
+            );
+
+            (remote, client)
+        });
+
+        client.set_instance(instance_ent);
+        let client_ent = app.world.spawn(client).id();
+
+        app.update();
+        app.world.resource_mut::<Server>().shutdown("Server closed");
+        app.update();
+
+        // The kick message is still written after the client is dropped.
+        app.world.despawn(client_ent);
+        assert!(shared.wait_for_connections(Duration::from_secs(5)));
+
+        let mut bytes = vec![];
+        shared
+            .tokio_handle()
+            .block_on(remote.read_to_end(&mut bytes))
+            .unwrap();
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_slice(&bytes);
+        let sent_packets = dec.collect_into_vec::<S2cPlayPacket>().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::DisconnectPlay(_));
+    }
+
+    #[test]
+    fn shutdown_stops_accept_loops() {
+        let mut app = App::new();
+        app.add_plugin(
+            ServerPlugin::new(())
+                .with_connection_mode(ConnectionMode::Offline)
+                .with_address(([127, 0, 0, 1], 0).into()),
+        );
+
+        // Start the accept loop.
+        app.update();
+
+        let shared = app.world.resource::<Server>().shared().clone();
+        let max_permits = shared.0.max_connections;
+
+        // Wait for the accept loop to take a permit while it waits for a
+        // connection.
+        let start = Instant::now();
+        while shared.0.connection_sema.available_permits() == max_permits {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        drop(app);
+
+        assert!(shared.wait_for_connections(Duration::from_secs(5)));
+        assert!(shared.0.accept_loops.lock().is_empty());
+    }
+}
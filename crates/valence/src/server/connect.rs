@@ -1,9 +1,15 @@
 //! Handles new connections to the server and the log-in process.
 
+#[cfg(unix)]
+use std::fs;
 use std::io;
 use std::net::SocketAddr;
+#[cfg(unix)]
+use std::net::{Ipv4Addr, SocketAddrV4};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, bail, ensure, Context};
 use base64::prelude::*;
@@ -16,10 +22,14 @@ use serde::Deserialize;
 use serde_json::{json, Value};
 use sha1::Sha1;
 use sha2::{Digest, Sha256};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
-use tokio::net::{TcpListener, TcpStream};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
 use tokio::sync::OwnedSemaphorePermit;
-use tracing::{error, info, instrument, trace, warn};
+use tokio::time::timeout;
+use tracing::{error, info, instrument, trace, warn, Span};
 use uuid::Uuid;
 use valence_protocol::packets::c2s::handshake::HandshakeOwned;
 use valence_protocol::packets::c2s::login::{EncryptionResponse, LoginPluginResponse, LoginStart};
@@ -34,59 +44,168 @@ use valence_protocol::{
     MINECRAFT_VERSION, PROTOCOL_VERSION,
 };
 
-use crate::config::{AsyncCallbacks, ConnectionMode, ServerListPing};
+use crate::config::{
+    AsyncCallbacks, ConnectionContext, ConnectionMode, ListenAddress, Listener, ServerListPing,
+};
 use crate::server::connection::InitialConnection;
-use crate::server::{NewClientInfo, SharedServer};
+use crate::server::proxy_protocol::read_proxy_header;
+use crate::server::{spawn_task, NewClientInfo, SharedServer};
 
-/// Accepts new connections to the server as they occur.
-#[instrument(skip_all)]
-pub async fn do_accept_loop(shared: SharedServer, callbacks: Arc<impl AsyncCallbacks>) {
-    let listener = match TcpListener::bind(shared.0.address).await {
-        Ok(listener) => listener,
+/// The time a proxy has to send the PROXY protocol header of a connection.
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The remote address given to clients connected through a Unix socket.
+#[cfg(unix)]
+const UNIX_REMOTE_ADDR: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+
+/// A bound listener of any kind.
+enum BoundListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+/// Accepts new connections to the server on a listener as they occur.
+#[instrument(skip(shared, callbacks))]
+pub async fn do_accept_loop(
+    shared: SharedServer,
+    callbacks: Arc<impl AsyncCallbacks>,
+    listener: Listener,
+) {
+    let bound = match bind(&listener.address) {
+        Ok(bound) => bound,
         Err(e) => {
-            error!("failed to start TCP listener: {e}");
+            error!("failed to start listener on {}: {e}", listener.address);
             return;
         }
     };
 
     loop {
-        match shared.0.connection_sema.clone().acquire_owned().await {
-            Ok(permit) => match listener.accept().await {
+        // Closed semaphore indicates server shutdown.
+        let Ok(permit) = shared.0.connection_sema.clone().acquire_owned().await else {
+            return
+        };
+
+        match &bound {
+            BoundListener::Tcp(tcp_listener) => match tcp_listener.accept().await {
                 Ok((stream, remote_addr)) => {
-                    tokio::spawn(handle_connection(
-                        shared.clone(),
-                        callbacks.clone(),
-                        stream,
-                        remote_addr,
-                        permit,
-                    ));
+                    if let Err(e) = stream.set_nodelay(true) {
+                        error!("failed to set TCP_NODELAY: {e}");
+                    }
+
+                    let (read, write) = stream.into_split();
+
+                    spawn_task(
+                        "connection",
+                        handle_connection(
+                            shared.clone(),
+                            callbacks.clone(),
+                            read,
+                            write,
+                            remote_addr,
+                            listener.proxy_protocol,
+                            permit,
+                        ),
+                    );
+                }
+                Err(e) => {
+                    error!("failed to accept incoming connection: {e}");
+                }
+            },
+            #[cfg(unix)]
+            BoundListener::Unix(unix_listener) => match unix_listener.accept().await {
+                Ok((stream, _)) => {
+                    let (read, write) = stream.into_split();
+
+                    spawn_task(
+                        "connection",
+                        handle_connection(
+                            shared.clone(),
+                            callbacks.clone(),
+                            read,
+                            write,
+                            UNIX_REMOTE_ADDR,
+                            listener.proxy_protocol,
+                            permit,
+                        ),
+                    );
                 }
                 Err(e) => {
                     error!("failed to accept incoming connection: {e}");
                 }
             },
-            // Closed semaphore indicates server shutdown.
-            Err(_) => return,
         }
     }
 }
 
-#[instrument(skip(shared, callbacks, stream, permit))]
-async fn handle_connection(
+/// Binds a listener to `address`. IPv6 listeners only accept IPv6
+/// connections so that an IPv4 listener can be bound to the same port.
+fn bind(address: &ListenAddress) -> io::Result<BoundListener> {
+    match address {
+        ListenAddress::Tcp(address) => {
+            let socket = Socket::new(
+                Domain::for_address(*address),
+                Type::STREAM,
+                Some(Protocol::TCP),
+            )?;
+
+            if address.is_ipv6() {
+                socket.set_only_v6(true)?;
+            }
+
+            socket.set_reuse_address(true)?;
+            socket.set_nonblocking(true)?;
+            socket.bind(&(*address).into())?;
+            socket.listen(1024)?;
+
+            Ok(BoundListener::Tcp(TcpListener::from_std(socket.into())?))
+        }
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            // Remove the socket file left behind by a previous run.
+            if fs::symlink_metadata(path).map_or(false, |meta| meta.file_type().is_socket()) {
+                fs::remove_file(path)?;
+            }
+
+            Ok(BoundListener::Unix(UnixListener::bind(path)?))
+        }
+    }
+}
+
+#[instrument(skip(shared, callbacks, read, write, proxy_protocol, permit))]
+async fn handle_connection<R, W>(
     shared: SharedServer,
     callbacks: Arc<impl AsyncCallbacks>,
-    stream: TcpStream,
-    remote_addr: SocketAddr,
+    mut read: R,
+    write: W,
+    mut remote_addr: SocketAddr,
+    proxy_protocol: bool,
     permit: OwnedSemaphorePermit,
-) {
+) where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
     trace!("handling connection");
 
-    if let Err(e) = stream.set_nodelay(true) {
-        error!("failed to set TCP_NODELAY: {e}");
+    if proxy_protocol {
+        match timeout(PROXY_HEADER_TIMEOUT, read_proxy_header(&mut read)).await {
+            Ok(Ok(Some(addr))) => {
+                trace!("connection proxied for {addr}");
+                remote_addr = addr;
+            }
+            // Connections made by the proxy itself keep the proxy's address.
+            Ok(Ok(None)) => {}
+            Ok(Err(e)) => {
+                warn!("failed to read PROXY protocol header: {e:#}");
+                return;
+            }
+            Err(_) => {
+                warn!("timed out reading PROXY protocol header");
+                return;
+            }
+        }
     }
 
-    let (read, write) = stream.into_split();
-
     let conn = InitialConnection::new(
         read,
         write,
@@ -110,38 +229,73 @@ async fn handle_connection(
     }
 }
 
-async fn handle_handshake(
+#[instrument(skip_all)]
+async fn handle_handshake<R, W>(
     shared: SharedServer,
     callbacks: Arc<impl AsyncCallbacks>,
-    mut conn: InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
+    mut conn: InitialConnection<R, W>,
     remote_addr: SocketAddr,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
     let handshake = conn.recv_packet::<HandshakeOwned>().await?;
 
     ensure!(
-        matches!(shared.connection_mode(), ConnectionMode::BungeeCord)
-            || handshake.server_address.chars().count() <= 255,
+        matches!(
+            shared.connection_mode(),
+            ConnectionMode::BungeeCord | ConnectionMode::BungeeGuard { .. }
+        ) || handshake.server_address.chars().count() <= 255,
         "handshake server address is too long"
     );
 
+    // Pings over the rate limit are dropped before the connect callback, which
+    // might be expensive.
+    if handshake.next_state == HandshakeNextState::Status
+        && !shared
+            .0
+            .status_cache
+            .allow_ping(remote_addr.ip(), Instant::now())
+    {
+        trace!("ping rate limit exceeded by {remote_addr}");
+        return Ok(());
+    }
+
+    let mut ctx = ConnectionContext::new(remote_addr);
+    ctx.protocol_version = handshake.protocol_version.0;
+    // BungeeCord appends the forwarded player data to the address.
+    ctx.server_address = handshake
+        .server_address
+        .split('\0')
+        .next()
+        .unwrap_or_default()
+        .to_owned();
+    ctx.server_port = handshake.server_port;
+
+    callbacks.connect(&shared, &mut ctx).await;
+
     match handshake.next_state {
-        HandshakeNextState::Status => {
-            handle_status(shared, callbacks, conn, remote_addr, handshake)
-                .await
-                .context("error handling status")
-        }
+        HandshakeNextState::Status => handle_status(shared, callbacks, conn, &ctx)
+            .await
+            .context("error handling status"),
         HandshakeNextState::Login => {
-            match handle_login(&shared, callbacks, &mut conn, remote_addr, handshake)
+            match handle_login(&shared, callbacks, &mut conn, &ctx, handshake)
                 .await
                 .context("error handling login")?
             {
                 Some(info) => {
-                    let client = conn.into_client(
+                    let mut client = conn.into_client(
                         info,
+                        ctx,
                         shared.0.incoming_capacity,
                         shared.0.outgoing_capacity,
                     );
 
+                    if remote_addr.ip().is_loopback() {
+                        client.set_compression_enabled(shared.0.compress_loopback);
+                    }
+
                     let _ = shared.0.new_clients_send.send_async(client).await;
 
                     Ok(())
@@ -152,19 +306,47 @@ async fn handle_handshake(
     }
 }
 
-async fn handle_status(
+#[instrument(skip_all)]
+async fn handle_status<R, W>(
     shared: SharedServer,
     callbacks: Arc<impl AsyncCallbacks>,
-    mut conn: InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
-    remote_addr: SocketAddr,
-    handshake: HandshakeOwned,
-) -> anyhow::Result<()> {
+    mut conn: InitialConnection<R, W>,
+    ctx: &ConnectionContext,
+) -> anyhow::Result<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let cache = &shared.0.status_cache;
+
     conn.recv_packet::<StatusRequest>().await?;
 
-    match callbacks
-        .server_list_ping(&shared, remote_addr, handshake.protocol_version.0)
-        .await
-    {
+    let status = match cache.get(ctx.protocol_version, Instant::now()) {
+        Some(status) => status,
+        None => {
+            let status = status_json(callbacks.server_list_ping(&shared, ctx).await);
+            cache.insert(ctx.protocol_version, status.clone(), Instant::now());
+            status
+        }
+    };
+
+    let Some(json) = status else {
+        return Ok(())
+    };
+
+    conn.send_packet(&StatusResponse { json: &json }).await?;
+
+    let PingRequest { payload } = conn.recv_packet().await?;
+
+    conn.send_packet(&PingResponse { payload }).await?;
+
+    Ok(())
+}
+
+/// Serializes the response to a server list ping. Returns `None` if the ping
+/// is ignored.
+fn status_json(ping: ServerListPing) -> Option<Arc<str>> {
+    match ping {
         ServerListPing::Respond {
             online_players,
             max_players,
@@ -191,29 +373,27 @@ async fn handle_status(
                 json["favicon"] = Value::String(buf);
             }
 
-            conn.send_packet(&StatusResponse {
-                json: &json.to_string(),
-            })
-            .await?;
+            Some(json.to_string().into())
         }
-        ServerListPing::Ignore => return Ok(()),
+        ServerListPing::Ignore => None,
     }
-
-    let PingRequest { payload } = conn.recv_packet().await?;
-
-    conn.send_packet(&PingResponse { payload }).await?;
-
-    Ok(())
 }
 
 /// Handle the login process and return the new client's data if successful.
-async fn handle_login(
+#[instrument(skip_all, fields(username))]
+async fn handle_login<R, W>(
     shared: &SharedServer,
     callbacks: Arc<impl AsyncCallbacks>,
-    conn: &mut InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
-    remote_addr: SocketAddr,
+    conn: &mut InitialConnection<R, W>,
+    ctx: &ConnectionContext,
     handshake: HandshakeOwned,
-) -> anyhow::Result<Option<NewClientInfo>> {
+) -> anyhow::Result<Option<NewClientInfo>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let remote_addr = ctx.remote_addr;
+
     if handshake.protocol_version.0 != PROTOCOL_VERSION {
         // TODO: send translated disconnect msg?
         return Ok(None);
@@ -226,12 +406,17 @@ async fn handle_login(
 
     let username = username.to_owned_username();
 
-    let info = match shared.connection_mode() {
+    Span::current().record("username", username.as_str());
+
+    let mut info = match shared.connection_mode() {
         ConnectionMode::Online { .. } => {
             login_online(shared, &callbacks, conn, remote_addr, username).await?
         }
         ConnectionMode::Offline => login_offline(remote_addr, username)?,
         ConnectionMode::BungeeCord => login_bungeecord(&handshake.server_address, username)?,
+        ConnectionMode::BungeeGuard { tokens } => {
+            login_bungeeguard(&handshake.server_address, username, tokens)?
+        }
         ConnectionMode::Velocity { secret } => login_velocity(conn, username, secret).await?,
     };
 
@@ -242,9 +427,10 @@ async fn handle_login(
         .await?;
 
         conn.set_compression(Some(threshold));
+        conn.set_compression_level(shared.0.compression_level);
     }
 
-    if let Err(reason) = callbacks.login(shared, &info).await {
+    if let Err(reason) = callbacks.login(shared, ctx, &mut info).await {
         info!("disconnect at login: \"{reason}\"");
         conn.send_packet(&DisconnectLogin {
             reason: reason.into(),
@@ -264,13 +450,17 @@ async fn handle_login(
 }
 
 /// Login procedure for online mode.
-pub(super) async fn login_online(
+pub(super) async fn login_online<R, W>(
     shared: &SharedServer,
     callbacks: &Arc<impl AsyncCallbacks>,
-    conn: &mut InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
+    conn: &mut InitialConnection<R, W>,
     remote_addr: SocketAddr,
     username: Username<String>,
-) -> anyhow::Result<NewClientInfo> {
+) -> anyhow::Result<NewClientInfo>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
     let my_verify_token: [u8; 16] = rand::random();
 
     conn.send_packet(&EncryptionRequest {
@@ -405,12 +595,59 @@ pub(super) fn login_bungeecord(
     })
 }
 
+/// The name of the player property containing the token forwarded by
+/// BungeeGuard.
+const BUNGEEGUARD_TOKEN_PROPERTY: &str = "bungeeguard-token";
+
+/// Login procedure for BungeeCord with BungeeGuard.
+pub(super) fn login_bungeeguard(
+    server_address: &str,
+    username: Username<String>,
+    tokens: &[Arc<str>],
+) -> anyhow::Result<NewClientInfo> {
+    let mut info = login_bungeecord(server_address, username)?;
+
+    let mut forwarded = info
+        .properties
+        .iter()
+        .filter(|prop| prop.name == BUNGEEGUARD_TOKEN_PROPERTY);
+
+    let (Some(token), None) = (forwarded.next(), forwarded.next()) else {
+        bail!("expected exactly one BungeeGuard token")
+    };
+
+    // Check every token so the time taken does not reveal which one matched.
+    let valid = tokens.iter().fold(false, |valid, t| {
+        valid | constant_time_eq(t.as_bytes(), token.value.as_bytes())
+    });
+
+    ensure!(valid, "invalid BungeeGuard token");
+
+    info.properties
+        .retain(|prop| prop.name != BUNGEEGUARD_TOKEN_PROPERTY);
+
+    Ok(info)
+}
+
+/// Compares two byte strings in time that depends only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 /// Login procedure for Velocity.
-pub(super) async fn login_velocity(
-    conn: &mut InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
+pub(super) async fn login_velocity<R, W>(
+    conn: &mut InitialConnection<R, W>,
     username: Username<String>,
     velocity_secret: &str,
-) -> anyhow::Result<NewClientInfo> {
+) -> anyhow::Result<NewClientInfo>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
     const VELOCITY_MIN_SUPPORTED_VERSION: u8 = 1;
     const VELOCITY_MODERN_FORWARDING_WITH_KEY_V2: i32 = 3;
 
@@ -481,9 +718,15 @@ pub(super) async fn login_velocity(
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
     use sha1::Digest;
+    use tokio::io::AsyncWriteExt;
 
     use super::*;
+    use crate::config::ServerPlugin;
+    use crate::testing::TestServer;
 
     #[test]
     fn auth_digest_usernames() {
@@ -500,4 +743,122 @@ mod tests {
             "88e16a1019277b15d58faf0541e11910eb756f6"
         );
     }
+
+    #[test]
+    fn bungeeguard_tokens() {
+        let tokens: [Arc<str>; 2] = ["first".into(), "second".into()];
+        let uuid = Uuid::from_u128(42);
+
+        let address =
+            |properties: &str| format!("localhost\0127.0.0.1\0{}\0{properties}", uuid.simple());
+        let login = |properties: &str| {
+            login_bungeeguard(
+                &address(properties),
+                Username::new("test".into()).unwrap(),
+                &tokens,
+            )
+        };
+
+        let info = login(
+            r#"[{"name":"textures","value":"abc"},{"name":"bungeeguard-token","value":"second"}]"#,
+        )
+        .unwrap();
+        assert_eq!(info.uuid, uuid);
+        assert_eq!(info.properties.len(), 1);
+        assert_eq!(info.properties[0].name, "textures");
+
+        assert!(login(r#"[{"name":"bungeeguard-token","value":"third"}]"#).is_err());
+        assert!(login(r#"[{"name":"bungeeguard-token","value":"secon"}]"#).is_err());
+        assert!(login(r#"[{"name":"textures","value":"abc"}]"#).is_err());
+        assert!(login(
+            r#"[{"name":"bungeeguard-token","value":"first"},{"name":"bungeeguard-token","value":"second"}]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rate_limited_pings_skip_connect_callback() {
+        #[derive(Default)]
+        struct CountConnects(AtomicUsize);
+
+        #[async_trait]
+        impl AsyncCallbacks for CountConnects {
+            async fn connect(&self, _shared: &SharedServer, _ctx: &mut ConnectionContext) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let callbacks = Arc::new(CountConnects::default());
+        let server = TestServer::with_plugin(
+            ServerPlugin::new(callbacks.clone()).with_ping_rate_limit(Some(1)),
+        );
+        let shared = server.server().shared().clone();
+        let remote_addr = SocketAddr::from(([1, 2, 3, 4], 25565));
+
+        let ping = |shared: SharedServer| {
+            let callbacks = callbacks.clone();
+
+            shared.tokio_handle().clone().block_on(async move {
+                let (mut client, server) = tokio::io::duplex(1024);
+                let (read, write) = tokio::io::split(server);
+
+                let mut enc = PacketEncoder::new();
+                enc.append_packet(&HandshakeOwned {
+                    protocol_version: VarInt(PROTOCOL_VERSION),
+                    server_address: "localhost".into(),
+                    server_port: 25565,
+                    next_state: HandshakeNextState::Status,
+                })
+                .unwrap();
+                client.write_all(&enc.take()).await.unwrap();
+                // Closing the client side ends the status exchange early.
+                drop(client);
+
+                let permit = shared
+                    .0
+                    .connection_sema
+                    .clone()
+                    .try_acquire_owned()
+                    .unwrap();
+                let conn = InitialConnection::new(
+                    read,
+                    write,
+                    PacketEncoder::new(),
+                    PacketDecoder::new(),
+                    Duration::from_secs(5),
+                    permit,
+                );
+
+                let _ = handle_handshake(shared, callbacks, conn, remote_addr).await;
+            });
+        };
+
+        ping(shared.clone());
+        assert_eq!(callbacks.0.load(Ordering::SeqCst), 1);
+
+        ping(shared);
+        assert_eq!(callbacks.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn bind_unix_socket() {
+        let path = std::env::temp_dir().join(format!("valence-{}.sock", Uuid::new_v4()));
+        let address = ListenAddress::Unix(path.clone());
+
+        let BoundListener::Unix(listener) = bind(&address).unwrap() else {
+            panic!("expected a Unix listener")
+        };
+        drop(listener);
+
+        // The socket file left behind is replaced.
+        let BoundListener::Unix(listener) = bind(&address).unwrap() else {
+            panic!("expected a Unix listener")
+        };
+
+        let _stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        listener.accept().await.unwrap();
+
+        fs::remove_file(path).unwrap();
+    }
 }
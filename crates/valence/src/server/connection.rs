@@ -3,19 +3,22 @@ use std::io::ErrorKind;
 use std::time::Duration;
 
 use anyhow::bail;
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::runtime::Handle;
 use tokio::sync::OwnedSemaphorePermit;
 use tokio::task::JoinHandle;
 use tokio::time::timeout;
-use tracing::debug;
+use tracing::{debug, debug_span, instrument, Instrument};
 use valence_protocol::{DecodePacket, EncodePacket, PacketDecoder, PacketEncoder};
 
 use crate::client::{Client, ClientConnection};
-use crate::server::byte_channel::{
-    byte_channel, ByteReceiver, ByteSender, TryRecvError, TrySendError,
+use crate::config::ConnectionContext;
+use crate::server::byte_channel::{byte_channel, ByteReceiver, TryRecvError};
+use crate::server::segment_channel::{
+    segment_channel, write_all_vectored, SegmentSender, TrySendError,
 };
-use crate::server::NewClientInfo;
+use crate::server::{spawn_task, NewClientInfo, SHUTDOWN_GRACE_PERIOD};
 
 pub(super) struct InitialConnection<R, W> {
     reader: R,
@@ -51,6 +54,7 @@ where
         }
     }
 
+    #[instrument(level = "trace", skip_all, fields(packet = std::any::type_name::<P>()))]
     pub async fn send_packet<P>(&mut self, pkt: &P) -> anyhow::Result<()>
     where
         P: EncodePacket + ?Sized,
@@ -61,6 +65,7 @@ where
         Ok(())
     }
 
+    #[instrument(level = "trace", skip_all, fields(packet = std::any::type_name::<P>()))]
     pub async fn recv_packet<'a, P>(&'a mut self) -> anyhow::Result<P>
     where
         P: DecodePacket<'a>,
@@ -114,6 +119,10 @@ where
         self.dec.set_compression(threshold.is_some());
     }
 
+    pub fn set_compression_level(&mut self, level: u32) {
+        self.enc.set_compression_level(level);
+    }
+
     pub fn enable_encryption(&mut self, key: &[u8; 16]) {
         self.enc.enable_encryption(key);
         self.dec.enable_encryption(key);
@@ -122,6 +131,7 @@ where
     pub fn into_client(
         mut self,
         info: NewClientInfo,
+        ctx: ConnectionContext,
         incoming_limit: usize,
         outgoing_limit: usize,
     ) -> Client
@@ -131,7 +141,9 @@ where
     {
         let (mut incoming_sender, incoming_receiver) = byte_channel(incoming_limit);
 
-        let reader_task = tokio::spawn(async move {
+        let span = debug_span!("client_io", username = %info.username, ip = %info.ip);
+
+        let reader = async move {
             loop {
                 let mut buf = incoming_sender.take_capacity(READ_BUF_SIZE);
 
@@ -150,34 +162,52 @@ where
                     break;
                 }
             }
-        });
+        };
+
+        let reader_task = spawn_task(
+            "client_reader",
+            reader.instrument(debug_span!(parent: &span, "reader")),
+        );
+
+        let (outgoing_sender, mut outgoing_receiver) = segment_channel(outgoing_limit);
+        let permit = self.permit;
 
-        let (outgoing_sender, mut outgoing_receiver) = byte_channel(outgoing_limit);
+        let writer = async move {
+            // The connection counts towards the connection limit until all of
+            // its packets are written.
+            let _permit = permit;
 
-        let writer_task = tokio::spawn(async move {
             loop {
-                let bytes = match outgoing_receiver.recv_async().await {
-                    Ok(bytes) => bytes,
+                let segments = match outgoing_receiver.recv_async().await {
+                    Ok(segments) => segments,
                     Err(e) => {
                         debug!("error receiving packet data: {e}");
                         break;
                     }
                 };
 
-                if let Err(e) = self.writer.write_all(&bytes).await {
+                // Shared packet data such as cached chunks is written straight from
+                // the shared buffers.
+                if let Err(e) = write_all_vectored(&mut self.writer, segments).await {
                     debug!("error writing packet data: {e}");
                 }
             }
-        });
+        };
+
+        let writer_task = spawn_task(
+            "client_writer",
+            writer.instrument(debug_span!(parent: &span, "writer")),
+        );
 
         Client::new(
             info,
+            ctx,
             Box::new(RealClientConnection {
                 send: outgoing_sender,
                 recv: incoming_receiver,
-                _permit: self.permit,
                 reader_task,
-                writer_task,
+                writer_task: Some(writer_task),
+                tokio_handle: Handle::current(),
             }),
             self.enc,
             self.dec,
@@ -186,34 +216,62 @@ where
 }
 
 struct RealClientConnection {
-    send: ByteSender,
+    send: SegmentSender,
     recv: ByteReceiver,
-    /// Ensures that we don't allow more connections to the server until the
-    /// client is dropped.
-    _permit: OwnedSemaphorePermit,
     reader_task: JoinHandle<()>,
-    writer_task: JoinHandle<()>,
+    /// Holds the permit which ensures that we don't allow more connections to
+    /// the server until the packets of the client are written.
+    writer_task: Option<JoinHandle<()>>,
+    tokio_handle: Handle,
 }
 
 impl Drop for RealClientConnection {
     fn drop(&mut self) {
-        self.writer_task.abort();
         self.reader_task.abort();
+
+        // The writer stops by itself once the packets still queued, such as
+        // the reason the client was kicked for, are written. Clients which
+        // don't read them get at most the grace period.
+        if let Some(mut writer_task) = self.writer_task.take() {
+            self.tokio_handle.spawn(async move {
+                if timeout(SHUTDOWN_GRACE_PERIOD, &mut writer_task)
+                    .await
+                    .is_err()
+                {
+                    writer_task.abort();
+                }
+            });
+        }
     }
 }
 
 impl ClientConnection for RealClientConnection {
     fn try_send(&mut self, bytes: BytesMut) -> anyhow::Result<()> {
-        match self.send.try_send(bytes) {
+        self.try_send_vectored(vec![bytes.freeze()])
+    }
+
+    fn try_send_vectored(&mut self, segments: Vec<Bytes>) -> anyhow::Result<()> {
+        match self.send.try_send(segments) {
             Ok(()) => Ok(()),
-            Err(TrySendError::Full(_)) => bail!(
+            Err(TrySendError::Full) => bail!(
                 "reached configured outgoing limit of {} bytes",
                 self.send.limit()
             ),
-            Err(TrySendError::Disconnected(_)) => bail!("client disconnected"),
+            Err(TrySendError::Disconnected) => bail!("client disconnected"),
+        }
+    }
+
+    fn send_urgent_vectored(&mut self, segments: Vec<Bytes>) -> anyhow::Result<()> {
+        match self.send.send_unbounded(segments) {
+            Ok(()) => Ok(()),
+            Err(_) => bail!("client disconnected"),
         }
     }
 
+    fn queued_len(&self) -> usize {
+        self.send.queued_len()
+    }
+
     fn try_recv(&mut self) -> anyhow::Result<BytesMut> {
         match self.recv.try_recv() {
             Ok(bytes) => Ok(bytes),
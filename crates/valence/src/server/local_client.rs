@@ -0,0 +1,140 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use anyhow::bail;
+use bytes::BytesMut;
+use parking_lot::Mutex;
+use valence_protocol::packets::S2cPlayPacket;
+use valence_protocol::{EncodePacket, PacketDecoder, PacketEncoder};
+
+use super::*;
+use crate::client::ClientConnection;
+use crate::config::ConnectionContext;
+
+impl SharedServer {
+    /// Adds a client to the server which is connected through memory instead
+    /// of the network, and returns the other end of the connection.
+    ///
+    /// The client skips the login process and is spawned at the start of the
+    /// next tick, like clients connecting over the network. This works
+    /// regardless of [`ServerPlugin::listen`].
+    ///
+    /// [`ServerPlugin::listen`]: crate::config::ServerPlugin::listen
+    pub fn connect_local_client(
+        &self,
+        username: Username<String>,
+        uuid: Uuid,
+    ) -> anyhow::Result<LocalClient> {
+        let threshold = self.0.compression_threshold;
+
+        let conn = LocalConnection {
+            buffers: Arc::new(Mutex::new(LocalBuffers::default())),
+        };
+
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(threshold);
+        enc.set_compression_level(self.0.compression_level);
+
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(threshold.is_some());
+
+        let info = NewClientInfo {
+            username,
+            uuid,
+            ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            properties: vec![],
+        };
+
+        let ctx = ConnectionContext::new(SocketAddr::new(info.ip, 0));
+
+        let mut client = Client::new(info, ctx, Box::new(conn.clone()), enc, dec);
+        client.set_compression_enabled(self.0.compress_loopback);
+
+        if self.0.new_clients_send.try_send(client).is_err() {
+            bail!("too many clients are waiting to join");
+        }
+
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(threshold);
+
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(threshold.is_some());
+
+        Ok(LocalClient { conn, enc, dec })
+    }
+}
+
+/// The client side of a connection made with
+/// [`SharedServer::connect_local_client`].
+///
+/// Packets sent by the server are buffered until they are collected with
+/// [`LocalClient::collect_received`].
+pub struct LocalClient {
+    conn: LocalConnection,
+    enc: PacketEncoder,
+    dec: PacketDecoder,
+}
+
+impl LocalClient {
+    /// Sends a packet to the server as if it was sent by the client. It is
+    /// processed in the next tick.
+    pub fn send(&mut self, pkt: &impl EncodePacket) -> anyhow::Result<()> {
+        self.enc.append_packet(pkt)?;
+        self.conn.buffers.lock().recv.unsplit(self.enc.take());
+        Ok(())
+    }
+
+    /// Decodes all packets the server sent to the client since the last call.
+    pub fn collect_received(&mut self) -> anyhow::Result<Vec<S2cPlayPacket>> {
+        let bytes = self.conn.buffers.lock().send.split();
+        self.dec.queue_bytes(bytes);
+        self.dec.collect_into_vec()
+    }
+
+    /// Discards all packets the server sent to the client.
+    pub fn clear_received(&mut self) {
+        self.conn.buffers.lock().send.clear();
+    }
+
+    /// Disconnects the client from the server. The server notices at the next
+    /// tick.
+    pub fn disconnect(self) {
+        self.conn.buffers.lock().disconnected = true;
+    }
+}
+
+#[derive(Clone)]
+struct LocalConnection {
+    buffers: Arc<Mutex<LocalBuffers>>,
+}
+
+#[derive(Default)]
+struct LocalBuffers {
+    /// Packet data sent by the client to the server.
+    recv: BytesMut,
+    /// Packet data sent by the server to the client.
+    send: BytesMut,
+    disconnected: bool,
+}
+
+impl ClientConnection for LocalConnection {
+    fn try_send(&mut self, bytes: BytesMut) -> anyhow::Result<()> {
+        let mut buffers = self.buffers.lock();
+
+        if buffers.disconnected {
+            bail!("local client disconnected");
+        }
+
+        buffers.send.unsplit(bytes);
+        Ok(())
+    }
+
+    fn try_recv(&mut self) -> anyhow::Result<BytesMut> {
+        let mut buffers = self.buffers.lock();
+
+        if buffers.disconnected {
+            bail!("local client disconnected");
+        }
+
+        Ok(buffers.recv.split())
+    }
+}
@@ -0,0 +1,170 @@
+//! Parsing of the [PROXY protocol] header sent by proxies and load balancers
+//! before the Minecraft connection begins.
+//!
+//! [PROXY protocol]: https://www.haproxy.org/download/2.7/doc/proxy-protocol.txt
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use anyhow::{anyhow, bail, ensure, Context};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The bytes at the start of a v2 header.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// The maximum length of a v1 header, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+
+/// Reads a PROXY protocol header (v1 or v2) from the start of `stream` and
+/// returns the source address it contains. Returns `None` if the proxy did not
+/// forward an address, which is the case for its own health checks.
+pub(super) async fn read_proxy_header(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> anyhow::Result<Option<SocketAddr>> {
+    let mut header = [0; 16];
+    stream.read_exact(&mut header[..12]).await?;
+
+    if header[..12] == V2_SIGNATURE {
+        stream.read_exact(&mut header[12..]).await?;
+
+        let len = u16::from_be_bytes([header[14], header[15]]);
+        let mut addresses = vec![0; len as usize];
+        stream.read_exact(&mut addresses).await?;
+
+        parse_v2(header[12], header[13], &addresses)
+    } else if header.starts_with(b"PROXY ") {
+        // The v1 header is a single line. Read it byte by byte so nothing
+        // after the header is consumed.
+        let mut line = header[..12].to_vec();
+
+        while !line.ends_with(b"\r\n") {
+            ensure!(
+                line.len() < V1_MAX_LEN,
+                "PROXY protocol v1 header is too long"
+            );
+            line.push(stream.read_u8().await?);
+        }
+
+        let line = std::str::from_utf8(&line[..line.len() - 2])
+            .context("PROXY protocol v1 header is not valid UTF-8")?;
+
+        parse_v1(line)
+    } else {
+        bail!("missing PROXY protocol header")
+    }
+}
+
+/// Parses a v1 header without the trailing CRLF.
+fn parse_v1(line: &str) -> anyhow::Result<Option<SocketAddr>> {
+    let mut parts = line.split(' ');
+
+    ensure!(
+        parts.next() == Some("PROXY"),
+        "malformed PROXY protocol v1 header"
+    );
+
+    match parts.next() {
+        Some("TCP4" | "TCP6") => {}
+        Some("UNKNOWN") => return Ok(None),
+        _ => bail!("unsupported protocol in PROXY protocol v1 header"),
+    }
+
+    let [src_ip, _dst_ip, src_port, _dst_port]: [&str; 4] = parts
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| anyhow!("malformed PROXY protocol v1 header"))?;
+
+    let ip: IpAddr = src_ip.parse().context("invalid source address")?;
+    let port: u16 = src_port.parse().context("invalid source port")?;
+
+    Ok(Some(SocketAddr::new(ip, port)))
+}
+
+/// Parses the part of a v2 header following the signature.
+fn parse_v2(
+    version_command: u8,
+    family: u8,
+    addresses: &[u8],
+) -> anyhow::Result<Option<SocketAddr>> {
+    ensure!(
+        version_command >> 4 == 2,
+        "unsupported PROXY protocol version"
+    );
+
+    match version_command & 0xf {
+        // LOCAL: The connection was made by the proxy itself.
+        0 => return Ok(None),
+        // PROXY: The connection was relayed for a client.
+        1 => {}
+        command => bail!("unknown PROXY protocol command {command}"),
+    }
+
+    match family {
+        // TCP over IPv4.
+        0x11 => {
+            ensure!(addresses.len() >= 12, "truncated PROXY protocol addresses");
+
+            let ip = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+
+            Ok(Some(SocketAddr::new(ip.into(), port)))
+        }
+        // TCP over IPv6.
+        0x21 => {
+            ensure!(addresses.len() >= 36, "truncated PROXY protocol addresses");
+
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&addresses[..16]);
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+
+            Ok(Some(SocketAddr::new(Ipv6Addr::from(octets).into(), port)))
+        }
+        // Unspecified or not a TCP connection.
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn proxy_header_v1() {
+        let mut stream: &[u8] = b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 25565\r\n\x10";
+
+        let addr = read_proxy_header(&mut stream).await.unwrap();
+        assert_eq!(addr, Some("192.0.2.1:56324".parse().unwrap()));
+        // The handshake after the header is left in the stream.
+        assert_eq!(stream, b"\x10");
+
+        let mut stream: &[u8] = b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 25565\r\n";
+        let addr = read_proxy_header(&mut stream).await.unwrap();
+        assert_eq!(addr, Some("[2001:db8::1]:56324".parse().unwrap()));
+
+        let mut stream: &[u8] = b"PROXY UNKNOWN\r\n";
+        assert_eq!(read_proxy_header(&mut stream).await.unwrap(), None);
+
+        let mut stream: &[u8] = b"\x10\x00\xf9\x05\x09localhost\x63\xdd\x02";
+        assert!(read_proxy_header(&mut stream).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn proxy_header_v2() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend([0x21, 0x11, 0, 12]);
+        header.extend([192, 0, 2, 1, 198, 51, 100, 1]);
+        header.extend(56324_u16.to_be_bytes());
+        header.extend(25565_u16.to_be_bytes());
+        header.push(0x10);
+
+        let mut stream = header.as_slice();
+        let addr = read_proxy_header(&mut stream).await.unwrap();
+        assert_eq!(addr, Some("192.0.2.1:56324".parse().unwrap()));
+        assert_eq!(stream, b"\x10");
+
+        let mut local = V2_SIGNATURE.to_vec();
+        local.extend([0x20, 0x00, 0, 0]);
+        assert_eq!(
+            read_proxy_header(&mut local.as_slice()).await.unwrap(),
+            None
+        );
+    }
+}
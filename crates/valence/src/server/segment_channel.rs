@@ -0,0 +1,256 @@
+//! A channel for sending sequences of shared byte buffers, which are written
+//! to the receiving end with vectored writes.
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::IoSlice;
+use std::sync::{Arc, Mutex};
+
+use bytes::{Buf, Bytes};
+use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Notify;
+
+/// The maximum number of buffers passed to a single vectored write.
+const MAX_IO_SLICES: usize = 64;
+
+pub fn segment_channel(limit: usize) -> (SegmentSender, SegmentReceiver) {
+    let shared = Arc::new(Shared {
+        mtx: Mutex::new(Inner {
+            segments: VecDeque::new(),
+            len: 0,
+            disconnected: false,
+        }),
+        notify: Notify::new(),
+        limit,
+    });
+
+    let sender = SegmentSender {
+        shared: shared.clone(),
+    };
+
+    let receiver = SegmentReceiver { shared };
+
+    (sender, receiver)
+}
+
+pub struct SegmentSender {
+    shared: Arc<Shared>,
+}
+
+pub struct SegmentReceiver {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    mtx: Mutex<Inner>,
+    notify: Notify,
+    limit: usize,
+}
+
+struct Inner {
+    segments: VecDeque<Bytes>,
+    /// The total number of bytes in `segments`.
+    len: usize,
+    disconnected: bool,
+}
+
+impl SegmentSender {
+    /// Queues the segments in order. Nothing is queued if the total length
+    /// would exceed the limit of the channel.
+    pub fn try_send(&mut self, segments: Vec<Bytes>) -> Result<(), TrySendError> {
+        let mut lck = self.shared.mtx.lock().unwrap();
+
+        if lck.disconnected {
+            return Err(TrySendError::Disconnected);
+        }
+
+        let len: usize = segments.iter().map(|s| s.len()).sum();
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        if len > self.shared.limit.saturating_sub(lck.len) {
+            return Err(TrySendError::Full);
+        }
+
+        lck.len += len;
+        lck.segments
+            .extend(segments.into_iter().filter(|s| !s.is_empty()));
+        self.shared.notify.notify_waiters();
+
+        Ok(())
+    }
+
+    /// Queues the segments in order, even if the total length exceeds the
+    /// limit of the channel.
+    pub fn send_unbounded(&mut self, segments: Vec<Bytes>) -> Result<(), TrySendError> {
+        let mut lck = self.shared.mtx.lock().unwrap();
+
+        if lck.disconnected {
+            return Err(TrySendError::Disconnected);
+        }
+
+        lck.len += segments.iter().map(|s| s.len()).sum::<usize>();
+        lck.segments
+            .extend(segments.into_iter().filter(|s| !s.is_empty()));
+        self.shared.notify.notify_waiters();
+
+        Ok(())
+    }
+
+    /// Returns the total number of bytes queued.
+    pub fn queued_len(&self) -> usize {
+        self.shared.mtx.lock().unwrap().len
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.shared.mtx.lock().unwrap().disconnected
+    }
+
+    pub fn limit(&self) -> usize {
+        self.shared.limit
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
+pub enum TrySendError {
+    #[error("sender disconnected")]
+    Disconnected,
+    #[error("channel full (see `Config::outgoing_capacity`)")]
+    Full,
+}
+
+impl SegmentReceiver {
+    /// Waits until segments are queued and takes all of them.
+    pub async fn recv_async(&mut self) -> Result<VecDeque<Bytes>, RecvError> {
+        loop {
+            // Created before checking for segments so that a notification sent
+            // in between is not missed.
+            let notified = self.shared.notify.notified();
+
+            {
+                let mut lck = self.shared.mtx.lock().unwrap();
+
+                if !lck.segments.is_empty() {
+                    lck.len = 0;
+                    return Ok(std::mem::take(&mut lck.segments));
+                }
+
+                if lck.disconnected {
+                    return Err(RecvError::Disconnected);
+                }
+            }
+
+            notified.await;
+        }
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        self.shared.mtx.lock().unwrap().disconnected
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
+pub enum RecvError {
+    #[error("receiver disconnected")]
+    Disconnected,
+}
+
+impl Drop for SegmentSender {
+    fn drop(&mut self) {
+        self.shared.mtx.lock().unwrap().disconnected = true;
+        self.shared.notify.notify_waiters();
+    }
+}
+
+impl Drop for SegmentReceiver {
+    fn drop(&mut self) {
+        self.shared.mtx.lock().unwrap().disconnected = true;
+    }
+}
+
+/// Writes all segments to `writer` using vectored writes.
+pub async fn write_all_vectored<W>(writer: &mut W, mut segments: VecDeque<Bytes>) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    while !segments.is_empty() {
+        let mut n = {
+            let mut slices = [IoSlice::new(&[]); MAX_IO_SLICES];
+            let mut count = 0;
+
+            for (slice, segment) in slices.iter_mut().zip(&segments) {
+                *slice = IoSlice::new(segment);
+                count += 1;
+            }
+
+            writer.write_vectored(&slices[..count]).await?
+        };
+
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+
+        // Remove the segments that were written completely.
+        while let Some(front) = segments.front_mut() {
+            if n < front.len() {
+                front.advance(n);
+                break;
+            }
+
+            n -= front.len();
+            segments.pop_front();
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_channel_limit() {
+        let (mut sender, _receiver) = segment_channel(4);
+
+        assert_eq!(
+            sender.try_send(vec![Bytes::from_static(b"hel"), Bytes::from_static(b"lo")]),
+            Err(TrySendError::Full)
+        );
+        assert_eq!(sender.try_send(vec![Bytes::from_static(b"hell")]), Ok(()));
+        assert_eq!(
+            sender.send_unbounded(vec![Bytes::from_static(b"o")]),
+            Ok(())
+        );
+        assert_eq!(sender.queued_len(), 5);
+    }
+
+    #[tokio::test]
+    async fn segment_channel_write() {
+        let (mut sender, mut receiver) = segment_channel(64);
+
+        let t = tokio::spawn(async move {
+            let mut out = vec![];
+
+            while let Ok(segments) = receiver.recv_async().await {
+                write_all_vectored(&mut out, segments).await.unwrap();
+            }
+
+            assert_eq!(out, b"hello world");
+        });
+
+        let shared = Bytes::from_static(b" ");
+
+        sender
+            .try_send(vec![Bytes::from_static(b"hello"), shared, Bytes::new()])
+            .unwrap();
+        sender.try_send(vec![Bytes::from_static(b"world")]).unwrap();
+
+        drop(sender);
+
+        t.await.unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+//! Caching of server list ping responses and rate limiting of pings, since
+//! server list scanners can send large numbers of pings.
+
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rustc_hash::FxHashMap;
+
+/// The window in which the pings of each IP address are counted.
+pub(super) const PING_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// A status response as JSON, or `None` if the ping was ignored.
+pub(super) type CachedStatus = Option<Arc<str>>;
+
+pub(super) struct StatusCache {
+    ttl: Duration,
+    rate_limit: Option<u32>,
+    /// Responses keyed by the protocol version of the client.
+    responses: Mutex<FxHashMap<i32, (Instant, CachedStatus)>>,
+    pings: Mutex<PingCounts>,
+}
+
+struct PingCounts {
+    window_start: Instant,
+    counts: FxHashMap<IpAddr, u32>,
+}
+
+impl StatusCache {
+    pub fn new(ttl: Duration, rate_limit: Option<u32>) -> Self {
+        Self {
+            ttl,
+            rate_limit,
+            responses: Mutex::new(FxHashMap::default()),
+            pings: Mutex::new(PingCounts {
+                window_start: Instant::now(),
+                counts: FxHashMap::default(),
+            }),
+        }
+    }
+
+    /// Counts a ping from `ip` and returns whether it is within the rate
+    /// limit.
+    pub fn allow_ping(&self, ip: IpAddr, now: Instant) -> bool {
+        let Some(limit) = self.rate_limit else {
+            return true
+        };
+
+        let mut pings = self.pings.lock().unwrap();
+
+        if now.saturating_duration_since(pings.window_start) >= PING_RATE_LIMIT_WINDOW {
+            pings.window_start = now;
+            pings.counts.clear();
+        }
+
+        let count = pings.counts.entry(ip).or_insert(0);
+        *count = count.saturating_add(1);
+
+        *count <= limit
+    }
+
+    /// Returns the cached response for `protocol_version` if it has not
+    /// expired.
+    pub fn get(&self, protocol_version: i32, now: Instant) -> Option<CachedStatus> {
+        let responses = self.responses.lock().unwrap();
+        let (time, status) = responses.get(&protocol_version)?;
+
+        (now.saturating_duration_since(*time) < self.ttl).then(|| status.clone())
+    }
+
+    pub fn insert(&self, protocol_version: i32, status: CachedStatus, now: Instant) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        let mut responses = self.responses.lock().unwrap();
+
+        // Clients can send any protocol version, so expired responses are
+        // removed to keep the cache small.
+        responses.retain(|_, (time, _)| now.saturating_duration_since(*time) < self.ttl);
+        responses.insert(protocol_version, (now, status));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn ping_rate_limit() {
+        let cache = StatusCache::new(Duration::ZERO, Some(2));
+        let now = Instant::now();
+        let a = IpAddr::V4(Ipv4Addr::new(1, 2, 3, 4));
+        let b = IpAddr::V4(Ipv4Addr::new(5, 6, 7, 8));
+
+        assert!(cache.allow_ping(a, now));
+        assert!(cache.allow_ping(a, now));
+        assert!(!cache.allow_ping(a, now));
+        assert!(cache.allow_ping(b, now));
+        assert!(cache.allow_ping(a, now + PING_RATE_LIMIT_WINDOW));
+    }
+
+    #[test]
+    fn status_cache_ttl() {
+        let cache = StatusCache::new(Duration::from_secs(1), None);
+        let now = Instant::now();
+
+        cache.insert(761, Some("{}".into()), now);
+        cache.insert(760, None, now);
+
+        assert_eq!(cache.get(761, now), Some(Some("{}".into())));
+        assert_eq!(cache.get(760, now), Some(None));
+        assert_eq!(cache.get(759, now), None);
+        assert_eq!(cache.get(761, now + Duration::from_secs(1)), None);
+    }
+}
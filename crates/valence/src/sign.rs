@@ -0,0 +1,230 @@
+//! Editing the text of signs.
+//!
+//! Clients can only write on a sign after the server opened the sign editor
+//! with [`Client::open_sign_editor`], which vanilla servers do after a client
+//! placed a sign. The [`SignPlugin`] writes the text sent by the client to the
+//! sign's block entity, which shows it to every client viewing the sign, and
+//! sends a [`SignEdited`] event.
+//!
+//! ```
+//! # use valence::prelude::*;
+//! # use valence::client::event::UseItemOnBlock;
+//! use valence::block_placement::compute_placement;
+//! use valence::sign::is_sign;
+//!
+//! fn place_blocks(
+//!     mut clients: Query<(&mut Client, &Inventory)>,
+//!     mut instances: Query<&mut Instance>,
+//!     mut events: EventReader<UseItemOnBlock>,
+//! ) {
+//!     for event in events.iter() {
+//!         let Ok((mut client, inventory)) = clients.get_mut(event.client) else {
+//!             continue;
+//!         };
+//!
+//!         let Some(kind) = inventory
+//!             .slot(client.held_item_slot())
+//!             .and_then(|stack| stack.item.to_block_kind())
+//!         else {
+//!             continue;
+//!         };
+//!
+//!         let Ok(mut instance) = instances.get_mut(client.instance()) else {
+//!             continue;
+//!         };
+//!
+//!         if let Some(placement) = compute_placement(&instance, &client, event, kind) {
+//!             placement.apply(&mut instance);
+//!
+//!             if is_sign(placement.state) {
+//!                 client.open_sign_editor(placement.position);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use valence_protocol::block::BlockEntityKind;
+use valence_protocol::{BlockPos, BlockState, Text};
+
+use crate::client::event::UpdateSign;
+use crate::client::Client;
+use crate::instance::Instance;
+
+/// Adds the [`SignEdited`] event and the system writing the text of edited
+/// signs.
+pub struct SignPlugin;
+
+impl Plugin for SignPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SignEdited>()
+            .add_system_to_stage(CoreStage::PostUpdate, edit_signs.before("valence_core"));
+    }
+}
+
+/// Sent when a client wrote on a sign after [`Client::open_sign_editor`]. The
+/// text is already written to the sign.
+#[derive(Clone, Debug)]
+pub struct SignEdited {
+    pub client: Entity,
+    pub position: BlockPos,
+    /// The lines of the sign, without formatting codes.
+    pub lines: [Box<str>; 4],
+}
+
+/// Whether or not the given block is a (hanging) sign which can be edited.
+pub fn is_sign(state: BlockState) -> bool {
+    matches!(
+        state.block_entity_kind(),
+        Some(BlockEntityKind::Sign | BlockEntityKind::HangingSign)
+    )
+}
+
+/// Removes the `§` formatting codes from a line of text like vanilla does for
+/// the lines of signs.
+fn strip_formatting(line: &str) -> Box<str> {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            stripped.push(c);
+        }
+    }
+
+    stripped.into()
+}
+
+fn edit_signs(
+    mut clients: Query<&mut Client>,
+    mut instances: Query<&mut Instance>,
+    mut events: EventReader<UpdateSign>,
+    mut edited: EventWriter<SignEdited>,
+) {
+    for UpdateSign {
+        client: client_ent,
+        position,
+        lines,
+    } in events.iter()
+    {
+        let Ok(mut client) = clients.get_mut(*client_ent) else {
+            continue
+        };
+
+        // Clients may only edit the sign they were asked to edit.
+        if client.editing_sign() != Some(*position) {
+            continue;
+        }
+
+        client.stop_editing_sign();
+
+        let Ok(mut instance) = instances.get_mut(client.instance()) else {
+            continue
+        };
+
+        let Some(mut block) = instance.block_mut(*position) else {
+            continue
+        };
+
+        if !is_sign(block.state()) {
+            continue;
+        }
+
+        let Some(nbt) = block.nbt_mut() else {
+            continue
+        };
+
+        let lines = lines.clone().map(|line| strip_formatting(&line));
+
+        for (i, line) in lines.iter().enumerate() {
+            nbt.insert(format!("Text{}", i + 1), Text::from(line.to_string()));
+        }
+
+        edited.send(SignEdited {
+            client: *client_ent,
+            position: *position,
+            lines,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::Value;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn edit_sign() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(SignPlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        let pos = BlockPos::new(1, 0, 1);
+        instance.set_block(pos, BlockState::OAK_SIGN);
+
+        app.update();
+        client_helper.clear_sent();
+
+        // The sign cannot be edited before the editor was opened.
+        let update = UpdateSign {
+            client: client_ent,
+            position: pos,
+            lines: ["§cfirst".into(), "second".into(), "".into(), "".into()],
+        };
+
+        app.world.send_event(update.clone());
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        let nbt = instance.block(pos).unwrap().nbt().unwrap();
+        assert!(nbt.get("Text1").is_none());
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .open_sign_editor(pos);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::OpenSignEditor(_));
+
+        app.world.send_event(update);
+        app.update();
+        app.update();
+
+        assert_eq!(
+            app.world.get::<Client>(client_ent).unwrap().editing_sign(),
+            None
+        );
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        let nbt = instance.block(pos).unwrap().nbt().unwrap();
+        assert_eq!(
+            nbt.get("Text1"),
+            Some(&Value::from(Text::from("first".to_owned())))
+        );
+
+        // The new text is sent to the viewers of the sign.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::BlockEntityData(_));
+
+        let events = app.world.resource::<Events<SignEdited>>();
+        let edited: Vec<_> = events.get_reader().iter(events).cloned().collect();
+        assert_eq!(edited.len(), 1);
+        assert_eq!(&*edited[0].lines[0], "first");
+    }
+}
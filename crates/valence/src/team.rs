@@ -0,0 +1,526 @@
+//! Scoreboard teams.
+//!
+//! Teams change how the names of their members are displayed, whether members
+//! collide with each other, and the color of glowing members. Members are
+//! identified by the username for players and by the hyphenated UUID for other
+//! entities.
+//!
+//! Teams are stored in the global [`Teams`] resource. The [`TeamPlugin`] sends
+//! only the changes to the teams to clients every tick.
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::team::{NameTagVisibility, Team, TeamColor, Teams};
+//!
+//! fn setup(mut teams: ResMut<Teams>) {
+//!     let red = Team::new()
+//!         .with_color(TeamColor::Red)
+//!         .with_prefix("[Red] ".color(Color::RED))
+//!         .with_name_tag_visibility(NameTagVisibility::HideForOtherTeams);
+//!
+//!     teams.insert("red", red);
+//! }
+//!
+//! fn join_red(mut teams: ResMut<Teams>, clients: Query<&Client, Added<Client>>) {
+//!     for client in &clients {
+//!         if let Some(red) = teams.get_mut("red") {
+//!             red.add_member(client.username().as_str());
+//!         }
+//!     }
+//! }
+//! ```
+
+use std::borrow::Cow;
+use std::mem;
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use valence_protocol::packets::s2c::play::UpdateTeams;
+pub use valence_protocol::packets::s2c::update_teams::{
+    CollisionRule, NameTagVisibility, TeamColor,
+};
+use valence_protocol::packets::s2c::update_teams::{TeamFlags, UpdateTeamsMode};
+use valence_protocol::Text;
+
+use crate::client::Client;
+use crate::packet::{PacketWriter, WritePacket};
+use crate::server::Server;
+
+/// Adds the [`Teams`] resource and the system sending teams to clients.
+pub struct TeamPlugin;
+
+impl Plugin for TeamPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Teams>()
+            .add_system_to_stage(CoreStage::PostUpdate, update_teams.before("valence_core"));
+    }
+}
+
+/// The scoreboard teams visible to all clients, keyed by team name.
+#[derive(Resource, Default, Debug)]
+pub struct Teams {
+    cached_update_packets: Vec<u8>,
+    teams: FxHashMap<String, Team>,
+    /// Teams removed since the last update which clients know about.
+    removed: Vec<String>,
+}
+
+impl Teams {
+    pub fn get(&self, name: &str) -> Option<&Team> {
+        self.teams.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Team> {
+        self.teams.get_mut(name)
+    }
+
+    /// Adds a team, replacing the team with the same name. The team name can
+    /// be at most 16 characters long.
+    pub fn insert(&mut self, name: impl Into<String>, mut team: Team) -> Option<Team> {
+        let name = name.into();
+
+        team.is_new = true;
+        team.modified_info = false;
+        team.member_changes.clear();
+
+        let old = self.teams.insert(name.clone(), team);
+
+        if old.as_ref().map_or(false, |t| !t.is_new) {
+            self.removed.push(name);
+        }
+
+        old
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Team> {
+        let (name, team) = self.teams.remove_entry(name)?;
+
+        if !team.is_new {
+            self.removed.push(name);
+        }
+
+        Some(team)
+    }
+
+    /// Returns the name of the first team `member` belongs to.
+    pub fn team_of(&self, member: &str) -> Option<&str> {
+        self.teams
+            .iter()
+            .find(|(_, team)| team.has_member(member))
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Team)> + '_ {
+        self.teams.iter().map(|(name, team)| (name.as_str(), team))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut Team)> + '_ {
+        self.teams
+            .iter_mut()
+            .map(|(name, team)| (name.as_str(), team))
+    }
+
+    pub fn len(&self) -> usize {
+        self.teams.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.teams.is_empty()
+    }
+
+    pub(crate) fn write_init_packets(&self, mut writer: impl WritePacket) {
+        for (name, team) in &self.teams {
+            writer.write_packet(&UpdateTeams {
+                team_name: name,
+                mode: team.create_mode(),
+            });
+        }
+    }
+}
+
+/// A scoreboard team.
+///
+/// Members should only be added to one team, since the client moves a member
+/// to the team it was added to last.
+#[derive(Clone, Debug)]
+pub struct Team {
+    display_name: Text,
+    prefix: Text,
+    suffix: Text,
+    color: TeamColor,
+    name_tag_visibility: NameTagVisibility,
+    collision_rule: CollisionRule,
+    friendly_fire: bool,
+    see_invisible_teammates: bool,
+    members: FxHashSet<String>,
+    /// If the team needs to be created on the clients.
+    is_new: bool,
+    modified_info: bool,
+    /// Members added (`true`) or removed (`false`) since the last update.
+    member_changes: FxHashMap<String, bool>,
+}
+
+impl Default for Team {
+    fn default() -> Self {
+        Self {
+            display_name: Text::default(),
+            prefix: Text::default(),
+            suffix: Text::default(),
+            color: TeamColor::Reset,
+            name_tag_visibility: NameTagVisibility::Always,
+            collision_rule: CollisionRule::Always,
+            friendly_fire: true,
+            see_invisible_teammates: false,
+            members: FxHashSet::default(),
+            is_new: true,
+            modified_info: false,
+            member_changes: FxHashMap::default(),
+        }
+    }
+}
+
+impl Team {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_display_name(mut self, display_name: impl Into<Text>) -> Self {
+        self.display_name = display_name.into();
+        self
+    }
+
+    /// Sets the text shown before the names of the members.
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: impl Into<Text>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the text shown after the names of the members.
+    #[must_use]
+    pub fn with_suffix(mut self, suffix: impl Into<Text>) -> Self {
+        self.suffix = suffix.into();
+        self
+    }
+
+    /// Sets the color of the names and the glow of the members.
+    #[must_use]
+    pub fn with_color(mut self, color: TeamColor) -> Self {
+        self.color = color;
+        self
+    }
+
+    #[must_use]
+    pub fn with_name_tag_visibility(mut self, visibility: NameTagVisibility) -> Self {
+        self.name_tag_visibility = visibility;
+        self
+    }
+
+    #[must_use]
+    pub fn with_collision_rule(mut self, rule: CollisionRule) -> Self {
+        self.collision_rule = rule;
+        self
+    }
+
+    #[must_use]
+    pub fn with_friendly_fire(mut self, friendly_fire: bool) -> Self {
+        self.friendly_fire = friendly_fire;
+        self
+    }
+
+    #[must_use]
+    pub fn with_see_invisible_teammates(mut self, see_invisible_teammates: bool) -> Self {
+        self.see_invisible_teammates = see_invisible_teammates;
+        self
+    }
+
+    #[must_use]
+    pub fn with_members<S: Into<String>>(mut self, members: impl IntoIterator<Item = S>) -> Self {
+        self.members.extend(members.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn display_name(&self) -> &Text {
+        &self.display_name
+    }
+
+    pub fn set_display_name(&mut self, display_name: impl Into<Text>) -> Text {
+        self.modified_info = true;
+        mem::replace(&mut self.display_name, display_name.into())
+    }
+
+    pub fn prefix(&self) -> &Text {
+        &self.prefix
+    }
+
+    pub fn set_prefix(&mut self, prefix: impl Into<Text>) -> Text {
+        self.modified_info = true;
+        mem::replace(&mut self.prefix, prefix.into())
+    }
+
+    pub fn suffix(&self) -> &Text {
+        &self.suffix
+    }
+
+    pub fn set_suffix(&mut self, suffix: impl Into<Text>) -> Text {
+        self.modified_info = true;
+        mem::replace(&mut self.suffix, suffix.into())
+    }
+
+    pub fn color(&self) -> TeamColor {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: TeamColor) {
+        if self.color != color {
+            self.color = color;
+            self.modified_info = true;
+        }
+    }
+
+    pub fn name_tag_visibility(&self) -> NameTagVisibility {
+        self.name_tag_visibility
+    }
+
+    pub fn set_name_tag_visibility(&mut self, visibility: NameTagVisibility) {
+        if self.name_tag_visibility != visibility {
+            self.name_tag_visibility = visibility;
+            self.modified_info = true;
+        }
+    }
+
+    pub fn collision_rule(&self) -> CollisionRule {
+        self.collision_rule
+    }
+
+    pub fn set_collision_rule(&mut self, rule: CollisionRule) {
+        if self.collision_rule != rule {
+            self.collision_rule = rule;
+            self.modified_info = true;
+        }
+    }
+
+    pub fn friendly_fire(&self) -> bool {
+        self.friendly_fire
+    }
+
+    pub fn set_friendly_fire(&mut self, friendly_fire: bool) {
+        if self.friendly_fire != friendly_fire {
+            self.friendly_fire = friendly_fire;
+            self.modified_info = true;
+        }
+    }
+
+    pub fn see_invisible_teammates(&self) -> bool {
+        self.see_invisible_teammates
+    }
+
+    pub fn set_see_invisible_teammates(&mut self, see_invisible_teammates: bool) {
+        if self.see_invisible_teammates != see_invisible_teammates {
+            self.see_invisible_teammates = see_invisible_teammates;
+            self.modified_info = true;
+        }
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = &str> + '_ {
+        self.members.iter().map(|m| m.as_str())
+    }
+
+    pub fn has_member(&self, member: &str) -> bool {
+        self.members.contains(member)
+    }
+
+    /// Adds a member to the team. Returns `true` if the member was not
+    /// already in the team.
+    pub fn add_member(&mut self, member: impl Into<String>) -> bool {
+        let member = member.into();
+
+        if self.members.contains(&member) {
+            return false;
+        }
+
+        self.members.insert(member.clone());
+        self.record_member_change(member, true);
+        true
+    }
+
+    /// Removes a member from the team. Returns `true` if the member was in the
+    /// team.
+    pub fn remove_member(&mut self, member: &str) -> bool {
+        if !self.members.remove(member) {
+            return false;
+        }
+
+        self.record_member_change(member.to_owned(), false);
+        true
+    }
+
+    fn record_member_change(&mut self, member: String, added: bool) {
+        if self.is_new {
+            return;
+        }
+
+        // Adding and removing a member in the same tick cancel out.
+        match self.member_changes.get(&member) {
+            Some(&change) if change != added => {
+                self.member_changes.remove(&member);
+            }
+            _ => {
+                self.member_changes.insert(member, added);
+            }
+        }
+    }
+
+    fn flags(&self) -> TeamFlags {
+        TeamFlags::new()
+            .with_friendly_fire(self.friendly_fire)
+            .with_see_invisible_teammates(self.see_invisible_teammates)
+    }
+
+    fn create_mode(&self) -> UpdateTeamsMode<'_> {
+        UpdateTeamsMode::CreateTeam {
+            team_display_name: Cow::Borrowed(&self.display_name),
+            friendly_flags: self.flags(),
+            name_tag_visibility: self.name_tag_visibility,
+            collision_rule: self.collision_rule,
+            team_color: self.color,
+            team_prefix: Cow::Borrowed(&self.prefix),
+            team_suffix: Cow::Borrowed(&self.suffix),
+            entities: self.members().collect(),
+        }
+    }
+}
+
+fn update_teams(teams: ResMut<Teams>, server: Res<Server>, mut clients: Query<&mut Client>) {
+    let teams = teams.into_inner();
+
+    let mut scratch = vec![];
+    teams.cached_update_packets.clear();
+
+    let mut writer = PacketWriter::new(
+        &mut teams.cached_update_packets,
+        server.compression_threshold(),
+        &mut scratch,
+    )
+    .with_compression_level(server.compression_level());
+
+    for name in teams.removed.drain(..) {
+        writer.write_packet(&UpdateTeams {
+            team_name: &name,
+            mode: UpdateTeamsMode::RemoveTeam,
+        });
+    }
+
+    for (name, team) in &mut teams.teams {
+        if team.is_new {
+            team.is_new = false;
+            team.modified_info = false;
+
+            writer.write_packet(&UpdateTeams {
+                team_name: name,
+                mode: team.create_mode(),
+            });
+
+            continue;
+        }
+
+        if team.modified_info {
+            team.modified_info = false;
+
+            writer.write_packet(&UpdateTeams {
+                team_name: name,
+                mode: UpdateTeamsMode::UpdateTeamInfo {
+                    team_display_name: Cow::Borrowed(&team.display_name),
+                    friendly_flags: team.flags(),
+                    name_tag_visibility: team.name_tag_visibility,
+                    collision_rule: team.collision_rule,
+                    team_color: team.color,
+                    team_prefix: Cow::Borrowed(&team.prefix),
+                    team_suffix: Cow::Borrowed(&team.suffix),
+                },
+            });
+        }
+
+        if !team.member_changes.is_empty() {
+            let (added, removed): (Vec<_>, Vec<_>) =
+                team.member_changes.iter().partition(|(_, &added)| added);
+
+            if !removed.is_empty() {
+                writer.write_packet(&UpdateTeams {
+                    team_name: name,
+                    mode: UpdateTeamsMode::RemoveEntities {
+                        entities: removed.into_iter().map(|(m, _)| m.as_str()).collect(),
+                    },
+                });
+            }
+
+            if !added.is_empty() {
+                writer.write_packet(&UpdateTeams {
+                    team_name: name,
+                    mode: UpdateTeamsMode::AddEntities {
+                        entities: added.into_iter().map(|(m, _)| m.as_str()).collect(),
+                    },
+                });
+            }
+
+            team.member_changes.clear();
+        }
+    }
+
+    for mut client in &mut clients {
+        if client.is_new() {
+            teams.write_init_packets(client.into_inner());
+        } else {
+            client.write_packet_bytes(&teams.cached_update_packets);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn team_updates() {
+        let mut app = App::new();
+        let (_, mut client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(TeamPlugin);
+
+        app.world
+            .resource_mut::<Teams>()
+            .insert("red", Team::new().with_members(["alice"]));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::UpdateTeams(_));
+
+        // Changes which cancel out are not sent.
+        let mut teams = app.world.resource_mut::<Teams>();
+        let red = teams.get_mut("red").unwrap();
+        red.add_member("bob");
+        red.remove_member("bob");
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::UpdateTeams(_));
+
+        let mut teams = app.world.resource_mut::<Teams>();
+        let red = teams.get_mut("red").unwrap();
+        red.set_color(TeamColor::Red);
+        red.remove_member("alice");
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 2, S2cPlayPacket::UpdateTeams(_));
+    }
+}
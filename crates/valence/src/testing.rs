@@ -0,0 +1,289 @@
+//! A headless harness for integration tests of game logic.
+//!
+//! [`TestServer`] runs the server schedule in embedded mode, so no sockets are
+//! opened and every tick is driven by the test. All stages run their systems
+//! one at a time in a fixed order, which makes the results of a test
+//! reproducible between runs. Clients are connected through memory with
+//! [`LocalClient`]s, which inject serverbound packets and decode the
+//! clientbound packets sent to them.
+//!
+//! Packet captures recorded by the packet inspector can be fed back into a
+//! test server with [`TestServer::replay_capture`] to debug desyncs.
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::protocol::packets::S2cPlayPacket;
+//! use valence::testing::TestServer;
+//!
+//! let mut server = TestServer::new();
+//! let instance = server.new_instance(DimensionId::default());
+//! let (client, mut local_client) = server.connect_client("test", instance);
+//!
+//! server.tick();
+//!
+//! let packets = local_client.collect_received().unwrap();
+//! assert!(packets
+//!     .iter()
+//!     .any(|p| matches!(p, S2cPlayPacket::LoginPlay(_))));
+//!
+//! let client = server.world().get::<Client>(client).unwrap();
+//! assert_eq!(client.username().as_str(), "test");
+//! ```
+
+use std::time::Duration;
+
+use anyhow::Context;
+use bevy_app::prelude::*;
+use bevy_app::{AppExit, StartupSchedule};
+use bevy_ecs::prelude::*;
+use bevy_ecs::schedule::ShouldRun;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use valence_protocol::capture::{read_capture, PacketDirection, PacketState};
+use valence_protocol::packets::C2sPlayPacket;
+use valence_protocol::Username;
+
+use crate::client::Client;
+use crate::config::{AsyncCallbacks, ServerPlugin};
+use crate::dimension::DimensionId;
+use crate::server::{LocalClient, Server};
+
+/// Runs the stages added by Valence on a single thread when present. Inserted
+/// by [`TestServer`] before the [`ServerPlugin`] is added.
+#[derive(Resource)]
+pub(crate) struct SingleThreadedStages;
+
+/// An embedded server for tests which runs its systems in a deterministic
+/// order. See the [module documentation](self).
+pub struct TestServer {
+    app: App,
+}
+
+impl TestServer {
+    /// Creates a test server with the default [`ServerPlugin`].
+    pub fn new() -> Self {
+        Self::with_plugin(ServerPlugin::new(()))
+    }
+
+    /// Creates a test server with the given [`ServerPlugin`]. The server does
+    /// not listen for connections, whatever [`ServerPlugin::listen`] is set
+    /// to.
+    pub fn with_plugin<A: AsyncCallbacks>(plugin: ServerPlugin<A>) -> Self {
+        let mut app = App::empty();
+
+        app.insert_resource(SingleThreadedStages)
+            .add_stage(CoreStage::First, SystemStage::single_threaded())
+            .add_stage(
+                StartupSchedule,
+                Schedule::default()
+                    .with_run_criteria(ShouldRun::once)
+                    .with_stage(StartupStage::PreStartup, SystemStage::single_threaded())
+                    .with_stage(StartupStage::Startup, SystemStage::single_threaded())
+                    .with_stage(StartupStage::PostStartup, SystemStage::single_threaded()),
+            )
+            .add_stage(CoreStage::PreUpdate, SystemStage::single_threaded())
+            .add_stage(CoreStage::Update, SystemStage::single_threaded())
+            .add_stage(CoreStage::PostUpdate, SystemStage::single_threaded())
+            .add_stage(CoreStage::Last, SystemStage::single_threaded())
+            .add_event::<AppExit>()
+            // Done by `App::new` for the default stages. Without it, removed
+            // components and change detection would never be reset.
+            .add_system_to_stage(CoreStage::Last, World::clear_trackers.at_end())
+            .add_plugin(plugin.with_listen(false));
+
+        Self { app }
+    }
+
+    pub fn app(&self) -> &App {
+        &self.app
+    }
+
+    /// Returns the app to add the plugins and systems under test.
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    pub fn world(&self) -> &World {
+        &self.app.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.app.world
+    }
+
+    pub fn server(&self) -> &Server {
+        self.app.world.resource::<Server>()
+    }
+
+    /// Runs a single tick.
+    pub fn tick(&mut self) {
+        self.app.update();
+    }
+
+    /// Runs `ticks` ticks.
+    pub fn run_ticks(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.app.update();
+        }
+    }
+
+    /// Spawns a new [`Instance`] in the given dimension and returns its
+    /// entity.
+    ///
+    /// [`Instance`]: crate::instance::Instance
+    pub fn new_instance(&mut self, dimension: DimensionId) -> Entity {
+        let instance = self.server().new_instance(dimension);
+        self.app.world.spawn(instance).id()
+    }
+
+    /// Connects a client with the given username and runs a tick to spawn it.
+    /// The client is then moved to `instance`, which it joins in the next
+    /// tick. Returns the client's entity and the client side of its
+    /// connection.
+    ///
+    /// The client's UUID is derived from the username as in offline mode, so
+    /// tests connecting the same usernames get the same UUIDs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the username is invalid.
+    #[track_caller]
+    pub fn connect_client(&mut self, username: &str, instance: Entity) -> (Entity, LocalClient) {
+        let username = Username::new(username.to_owned()).expect("invalid username");
+        let uuid = Uuid::from_slice(&Sha256::digest(username.as_str())[..16])
+            .expect("digest is too short");
+
+        let local_client = self
+            .server()
+            .connect_local_client(username, uuid)
+            .expect("failed to connect local client");
+
+        self.tick();
+
+        let client = self
+            .server()
+            .client_by_uuid(uuid)
+            .expect("local client was not spawned");
+
+        self.app
+            .world
+            .get_mut::<Client>(client)
+            .expect("missing client component")
+            .set_instance(instance);
+
+        (client, local_client)
+    }
+
+    /// Connects a client to `instance` like [`Self::connect_client`] and
+    /// sends it the serverbound play packets of a capture, as recorded by the
+    /// packet inspector.
+    ///
+    /// Ticks are run between the packets according to the time they were
+    /// captured at, so the server receives the packets in the same ticks
+    /// relative to each other as the original server did. A tick is run after
+    /// the last packet.
+    pub fn replay_capture(
+        &mut self,
+        capture: &[u8],
+        instance: Entity,
+    ) -> anyhow::Result<(Entity, LocalClient)> {
+        let packets = read_capture(capture)?;
+
+        let (client, mut local_client) = self.connect_client("replay", instance);
+        self.tick();
+
+        let tick_duration = Duration::from_secs(1) / self.server().tps() as u32;
+        let mut next_tick = None;
+
+        for (i, captured) in packets.iter().enumerate() {
+            if captured.direction != PacketDirection::Serverbound
+                || captured.state != PacketState::Play
+            {
+                continue;
+            }
+
+            let timestamp = captured.timestamp();
+            let next_tick = next_tick.get_or_insert(timestamp + tick_duration);
+
+            while timestamp >= *next_tick {
+                self.tick();
+                *next_tick += tick_duration;
+            }
+
+            let pkt = captured
+                .decode::<C2sPlayPacket>()
+                .with_context(|| format!("failed to decode captured packet #{i}"))?;
+
+            local_client.send(&pkt)?;
+        }
+
+        self.tick();
+
+        Ok((client, local_client))
+    }
+}
+
+impl Default for TestServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::capture::CaptureWriter;
+    use valence_protocol::packets::c2s::play::SwingArm;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::types::Hand;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::client::event::SwingArm as SwingArmEvent;
+
+    #[test]
+    fn test_server_clients() {
+        let mut server = TestServer::new();
+        let instance = server.new_instance(DimensionId::default());
+
+        let (client, mut local_client) = server.connect_client("test", instance);
+        server.tick();
+
+        let sent_packets = local_client.collect_received().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::LoginPlay(_));
+
+        local_client.send(&SwingArm { hand: Hand::Main }).unwrap();
+        server.tick();
+
+        let events = server.world().resource::<Events<SwingArmEvent>>();
+        let swings: Vec<_> = events.get_reader().iter(events).collect();
+        assert_eq!(swings.len(), 1);
+        assert_eq!(swings[0].client, client);
+    }
+
+    #[test]
+    fn replay_capture() {
+        let mut writer = CaptureWriter::new(vec![]).unwrap();
+
+        for _ in 0..3 {
+            writer
+                .write_packet(
+                    PacketDirection::Serverbound,
+                    PacketState::Play,
+                    &SwingArm { hand: Hand::Main },
+                )
+                .unwrap();
+        }
+
+        let mut server = TestServer::new();
+        let instance = server.new_instance(DimensionId::default());
+
+        let (client, _local_client) = server
+            .replay_capture(&writer.into_inner(), instance)
+            .unwrap();
+
+        let events = server.world().resource::<Events<SwingArmEvent>>();
+        let swings: Vec<_> = events.get_reader().iter(events).collect();
+        assert_eq!(swings.len(), 3);
+        assert!(swings.iter().all(|swing| swing.client == client));
+    }
+}
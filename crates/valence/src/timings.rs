@@ -0,0 +1,257 @@
+//! Profiling of the time spent in each tick.
+//!
+//! While the [`TickTimings`] resource is enabled, Valence measures how long
+//! its internal systems take every tick, such as handling packets, updating
+//! chunks and entities, and flushing packets to clients. The measurements of
+//! the last tick and statistics since the timings were last reset can be read
+//! from the resource, or formatted with [`TickTimings::report`] like the
+//! `/timings` command of other servers.
+//!
+//! Systems of the game can be measured the same way with [`TickTimings::time`].
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::timings::TickTimings;
+//!
+//! fn setup(mut timings: ResMut<TickTimings>) {
+//!     timings.set_enabled(true);
+//! }
+//!
+//! fn expensive_system(timings: Res<TickTimings>) {
+//!     let _timer = timings.time("expensive system");
+//!     // ...
+//! }
+//!
+//! fn print_timings(timings: Res<TickTimings>, server: Res<Server>) {
+//!     if server.current_tick() % 1200 == 0 {
+//!         println!("{}", timings.report());
+//!     }
+//! }
+//! ```
+
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+use bevy_ecs::prelude::*;
+use parking_lot::Mutex;
+
+/// Measures the time spent in the sections of each tick. Disabled by default.
+#[derive(Resource)]
+pub struct TickTimings {
+    enabled: bool,
+    tick_start: Instant,
+    /// The time spent in each section during the current tick. Sections can be
+    /// timed from systems running in parallel, so this is behind a mutex.
+    current: Mutex<Vec<(&'static str, Duration)>>,
+    last_tick: Vec<(&'static str, Duration)>,
+    last_tick_duration: Duration,
+    tick_stats: TimingStats,
+    section_stats: Vec<(&'static str, TimingStats)>,
+}
+
+/// Statistics of the time spent in a section of the tick, or in whole ticks.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct TimingStats {
+    pub total: Duration,
+    pub max: Duration,
+    /// The number of ticks measured.
+    pub count: u32,
+}
+
+impl TimingStats {
+    /// Returns the average time spent per measured tick.
+    pub fn average(&self) -> Duration {
+        self.total / self.count.max(1)
+    }
+
+    fn add(&mut self, duration: Duration) {
+        self.total += duration;
+        self.max = self.max.max(duration);
+        self.count += 1;
+    }
+}
+
+impl Default for TickTimings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tick_start: Instant::now(),
+            current: Mutex::new(vec![]),
+            last_tick: vec![],
+            last_tick_duration: Duration::ZERO,
+            tick_stats: TimingStats::default(),
+            section_stats: vec![],
+        }
+    }
+}
+
+impl TickTimings {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enables or disables measuring ticks. The statistics are kept while the
+    /// timings are disabled.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Clears the statistics.
+    pub fn reset(&mut self) {
+        self.tick_stats = TimingStats::default();
+        self.section_stats.clear();
+    }
+
+    /// Measures the time until the returned guard is dropped as part of
+    /// `section`. Sections measured several times in a tick are added up.
+    pub fn time(&self, section: &'static str) -> TimingGuard {
+        TimingGuard {
+            timer: self
+                .enabled
+                .then(|| (&self.current, section, Instant::now())),
+        }
+    }
+
+    /// Returns the time spent in each section during the last tick.
+    pub fn last_tick(&self) -> &[(&'static str, Duration)] {
+        &self.last_tick
+    }
+
+    /// Returns the duration of the last tick.
+    pub fn last_tick_duration(&self) -> Duration {
+        self.last_tick_duration
+    }
+
+    /// Returns the statistics of whole ticks since the timings were reset.
+    pub fn tick_stats(&self) -> TimingStats {
+        self.tick_stats
+    }
+
+    /// Returns the statistics of each section since the timings were reset.
+    pub fn section_stats(&self) -> &[(&'static str, TimingStats)] {
+        &self.section_stats
+    }
+
+    /// Formats the statistics as a report with the sections sorted by the time
+    /// spent in them.
+    pub fn report(&self) -> String {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+
+        let mut report = format!(
+            "Timings of {} ticks (average {:.2} ms, max {:.2} ms):",
+            self.tick_stats.count,
+            ms(self.tick_stats.average()),
+            ms(self.tick_stats.max),
+        );
+
+        let mut sections = self.section_stats.clone();
+        sections.sort_by(|(_, a), (_, b)| b.total.cmp(&a.total));
+
+        for (section, stats) in sections {
+            let percent = if self.tick_stats.total.is_zero() {
+                0.0
+            } else {
+                stats.total.as_secs_f64() / self.tick_stats.total.as_secs_f64() * 100.0
+            };
+
+            let _ = write!(
+                report,
+                "\n  {section}: average {:.2} ms, max {:.2} ms, {percent:.1}% of ticks",
+                ms(stats.average()),
+                ms(stats.max),
+            );
+        }
+
+        report
+    }
+}
+
+/// Adds the time until it is dropped to a section of the [`TickTimings`].
+#[must_use = "the section is measured until the guard is dropped"]
+pub struct TimingGuard<'a> {
+    timer: Option<(
+        &'a Mutex<Vec<(&'static str, Duration)>>,
+        &'static str,
+        Instant,
+    )>,
+}
+
+impl Drop for TimingGuard<'_> {
+    fn drop(&mut self) {
+        let Some((current, section, start)) = self.timer else {
+            return
+        };
+
+        let elapsed = start.elapsed();
+        let mut current = current.lock();
+
+        match current.iter_mut().find(|(s, _)| *s == section) {
+            Some((_, duration)) => *duration += elapsed,
+            None => current.push((section, elapsed)),
+        }
+    }
+}
+
+pub(crate) fn start_tick_timings(mut timings: ResMut<TickTimings>) {
+    timings.tick_start = Instant::now();
+}
+
+pub(crate) fn finish_tick_timings(mut timings: ResMut<TickTimings>) {
+    if !timings.enabled {
+        return;
+    }
+
+    let timings = &mut *timings;
+
+    timings.last_tick_duration = timings.tick_start.elapsed();
+    timings.tick_stats.add(timings.last_tick_duration);
+
+    timings.last_tick.clear();
+    timings.last_tick.append(timings.current.get_mut());
+
+    for &(section, duration) in &timings.last_tick {
+        match timings
+            .section_stats
+            .iter_mut()
+            .find(|(s, _)| *s == section)
+        {
+            Some((_, stats)) => stats.add(duration),
+            None => {
+                let mut stats = TimingStats::default();
+                stats.add(duration);
+                timings.section_stats.push((section, stats));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn tick_timings_report() {
+        let mut app = App::new();
+        let (_client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        assert_eq!(app.world.resource::<TickTimings>().tick_stats().count, 0);
+
+        app.world.resource_mut::<TickTimings>().set_enabled(true);
+
+        for _ in 0..3 {
+            app.update();
+        }
+
+        let timings = app.world.resource::<TickTimings>();
+        assert_eq!(timings.tick_stats().count, 3);
+
+        for section in ["client updates", "packet flush", "chunk updates"] {
+            assert!(timings.last_tick().iter().any(|(s, _)| *s == section));
+            assert!(timings.report().contains(section));
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Primed TNT and explosions.
+//!
+//! [`prime_tnt`] returns the components of a primed TNT entity. With the
+//! [`TntPlugin`], primed TNT falls with gravity and explodes once its fuse runs
+//! out. Clients using flint and steel on a TNT block prime it like vanilla.
+//!
+//! TNT explodes with [`Instance::explode`], destroying blocks in the instance
+//! if [`TntSettings::block_damage`] is enabled. A [`TntExploded`] event is
+//! sent for every explosion.
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::{DVec3, Vec3};
+use rand::Rng;
+use valence_protocol::block::{BlockKind, BlockState};
+use valence_protocol::types::{GameMode, Hand};
+use valence_protocol::{BlockPos, ItemKind};
+
+use crate::client::event::UseItemOnBlock;
+use crate::client::Client;
+use crate::config::DEFAULT_TPS;
+use crate::entity::{EntityKind, McEntity, TrackedData};
+use crate::falling_block::fall;
+use crate::instance::{ExplosionOptions, Instance};
+use crate::inventory::Inventory;
+use crate::server::Server;
+use crate::Despawned;
+
+/// Adds the [`TntExploded`] event and the systems for primed TNT.
+///
+/// The behavior of explosions is configured with the [`TntSettings`]
+/// resource.
+#[derive(Default)]
+pub struct TntPlugin {
+    pub settings: TntSettings,
+}
+
+impl Plugin for TntPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .add_event::<TntExploded>()
+            .add_system_to_stage(CoreStage::Update, ignite_tnt)
+            .add_system_to_stage(CoreStage::Update, tick_tnt.after(ignite_tnt));
+    }
+}
+
+/// Settings for primed TNT and its explosions.
+#[derive(Resource, Clone, Debug)]
+pub struct TntSettings {
+    /// The fuse of TNT primed by clients in ticks.
+    ///
+    /// # Default Value
+    ///
+    /// `80`
+    pub fuse: i64,
+    /// The power of TNT primed by clients.
+    ///
+    /// # Default Value
+    ///
+    /// `4.0`
+    pub power: f32,
+    /// If explosions destroy blocks in the instance.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub block_damage: bool,
+    /// If explosions prime the TNT blocks they destroy. Only applies if
+    /// [`Self::block_damage`] is enabled.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub chain_reaction: bool,
+}
+
+impl Default for TntSettings {
+    fn default() -> Self {
+        Self {
+            fuse: 80,
+            power: 4.0,
+            block_damage: true,
+            chain_reaction: true,
+        }
+    }
+}
+
+/// A component for primed TNT entities handled by the [`TntPlugin`].
+#[derive(Component, Clone, Debug)]
+pub struct PrimedTnt {
+    /// The remaining ticks until the TNT explodes.
+    pub fuse: i64,
+    /// The power of the explosion.
+    pub power: f32,
+    /// The velocity in blocks per second.
+    velocity: Vec3,
+}
+
+impl PrimedTnt {
+    pub fn new(fuse: i64, power: f32) -> Self {
+        Self {
+            fuse,
+            power,
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    /// Returns the velocity of this TNT in blocks per second.
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+}
+
+/// Sent when primed TNT exploded.
+#[derive(Clone, Debug)]
+pub struct TntExploded {
+    pub entity: Entity,
+    pub instance: Entity,
+    pub position: DVec3,
+    /// The blocks destroyed by the explosion. Empty if
+    /// [`TntSettings::block_damage`] is disabled.
+    pub destroyed: Vec<BlockPos>,
+}
+
+/// Returns the components of a primed TNT entity at `position` exploding
+/// after `fuse` ticks with the given power.
+///
+/// Like vanilla, the TNT jumps up slightly when it is primed.
+pub fn prime_tnt(
+    instance: Entity,
+    position: impl Into<DVec3>,
+    fuse: i64,
+    power: f32,
+) -> (McEntity, PrimedTnt) {
+    let mut entity = McEntity::new(EntityKind::Tnt, instance);
+    entity.set_position(position);
+
+    if let TrackedData::Tnt(data) = entity.data_mut() {
+        data.set_fuse(fuse.clamp(0, i32::MAX as i64) as i32);
+    }
+
+    let mut tnt = PrimedTnt::new(fuse, power);
+    tnt.velocity = Vec3::new(0.0, 0.2 * DEFAULT_TPS as f32, 0.0);
+
+    entity.set_velocity(tnt.velocity);
+
+    (entity, tnt)
+}
+
+fn ignite_tnt(
+    mut commands: Commands,
+    settings: Res<TntSettings>,
+    clients: Query<(&Client, &Inventory)>,
+    mut instances: Query<&mut Instance>,
+    mut events: EventReader<UseItemOnBlock>,
+) {
+    for event in events.iter() {
+        if event.hand != Hand::Main {
+            continue;
+        }
+
+        let Ok((client, inventory)) = clients.get(event.client) else {
+            continue
+        };
+
+        if matches!(
+            client.game_mode(),
+            GameMode::Spectator | GameMode::Adventure
+        ) {
+            continue;
+        }
+
+        let holds_flint_and_steel = inventory
+            .slot(client.held_item_slot())
+            .map_or(false, |stack| stack.item == ItemKind::FlintAndSteel);
+
+        if !holds_flint_and_steel {
+            continue;
+        }
+
+        let Ok(mut instance) = instances.get_mut(client.instance()) else {
+            continue
+        };
+
+        let is_tnt = instance
+            .block(event.position)
+            .map_or(false, |b| b.state().to_kind() == BlockKind::Tnt);
+
+        if !is_tnt {
+            continue;
+        }
+
+        instance.set_block(event.position, BlockState::AIR);
+
+        commands.spawn(prime_tnt(
+            client.instance(),
+            block_center(event.position),
+            settings.fuse,
+            settings.power,
+        ));
+    }
+}
+
+fn tick_tnt(
+    mut commands: Commands,
+    server: Res<Server>,
+    settings: Res<TntSettings>,
+    mut tnts: Query<(Entity, &mut McEntity, &mut PrimedTnt)>,
+    mut instances: Query<&mut Instance>,
+    mut exploded: EventWriter<TntExploded>,
+) {
+    for (entity, mut mc_entity, mut tnt) in &mut tnts {
+        let instance_ent = mc_entity.instance();
+
+        let Ok(mut instance) = instances.get_mut(instance_ent) else {
+            continue
+        };
+
+        if tnt.fuse > 0 {
+            tnt.fuse -= 1;
+
+            let mut position = mc_entity.position();
+            fall(&instance, &mut position, &mut tnt.velocity, server.tps());
+            mc_entity.set_position(position);
+
+            if let TrackedData::Tnt(data) = mc_entity.data_mut() {
+                data.set_fuse(tnt.fuse as i32);
+            }
+
+            continue;
+        }
+
+        // Explosions originate from the center of the TNT.
+        let center = mc_entity.position() + DVec3::new(0.0, 0.49, 0.0);
+
+        let destroyed = instance.explode(
+            center,
+            tnt.power,
+            ExplosionOptions {
+                block_damage: settings.block_damage,
+                source: Some(entity),
+                ..Default::default()
+            },
+        );
+
+        if settings.chain_reaction {
+            for &(pos, state) in &destroyed {
+                if state.to_kind() == BlockKind::Tnt {
+                    // Vanilla gives TNT primed by explosions a short random fuse.
+                    let fuse = rand::thread_rng().gen_range(10..30);
+                    commands.spawn(prime_tnt(instance_ent, block_center(pos), fuse, tnt.power));
+                }
+            }
+        }
+
+        exploded.send(TntExploded {
+            entity,
+            instance: instance_ent,
+            position: center,
+            destroyed: destroyed.into_iter().map(|(pos, _)| pos).collect(),
+        });
+
+        commands.entity(entity).insert(Despawned);
+    }
+}
+
+fn block_center(pos: BlockPos) -> DVec3 {
+    DVec3::new(pos.x as f64 + 0.5, pos.y as f64, pos.z as f64 + 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn tnt_explodes() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(TntPlugin::default());
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let y = instance.min_y() + 10;
+
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        for x in 0..16 {
+            for z in 0..16 {
+                instance.set_block([x, y, z], BlockState::DIRT);
+            }
+        }
+
+        instance.set_block([8, y, 9], BlockState::BEDROCK);
+
+        app.world
+            .spawn(prime_tnt(instance_ent, [8.5, y as f64 + 1.0, 8.5], 5, 4.0));
+
+        app.update();
+        client_helper.clear_sent();
+
+        for _ in 0..10 {
+            app.update();
+        }
+
+        let events = app.world.resource::<Events<TntExploded>>();
+        let mut reader = events.get_reader();
+        let exploded: Vec<_> = reader.iter(events).collect();
+
+        assert_eq!(exploded.len(), 1);
+        assert!(!exploded[0].destroyed.is_empty());
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert!(instance.block([8, y, 8]).unwrap().state().is_air());
+        assert_eq!(
+            instance.block([8, y, 9]).unwrap().state(),
+            BlockState::BEDROCK
+        );
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::Explosion(_));
+    }
+}
@@ -53,10 +53,13 @@ use crate::unit_test::util::scenario_single_client;
 /// Some of the tests in this file may be inferior duplicates of real tests.
 #[cfg(test)]
 mod tests {
+    use uuid::Uuid;
     use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::Username;
 
     use super::*;
     use crate::client::Client;
+    use crate::dimension::DimensionId;
     use crate::inventory::{Inventory, InventoryKind, OpenInventory};
     use crate::{assert_packet_count, assert_packet_order};
 
@@ -133,4 +136,34 @@ mod tests {
 
         Ok(())
     }
+
+    /// A client connected through memory in embedded mode is spawned like a
+    /// regular client.
+    #[test]
+    fn example_test_local_client() -> anyhow::Result<()> {
+        let mut app = App::new();
+        app.add_plugin(ServerPlugin::new(()).with_listen(false));
+
+        let server = app.world.resource::<Server>();
+        let instance = server.new_instance(DimensionId::default());
+        let instance_ent = app.world.spawn(instance).id();
+
+        let server = app.world.resource::<Server>();
+        let username = Username::new("test".to_owned()).unwrap();
+        let mut local_client = server.connect_local_client(username, Uuid::new_v4())?;
+
+        app.update();
+
+        let mut clients = app.world.query::<&mut Client>();
+        let mut client = clients.single_mut(&mut app.world);
+        assert_eq!(client.username().as_str(), "test");
+        client.set_instance(instance_ent);
+
+        app.update();
+
+        let sent_packets = local_client.collect_received()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::LoginPlay(_));
+
+        Ok(())
+    }
 }
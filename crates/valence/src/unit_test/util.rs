@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 
 use bevy_app::App;
@@ -7,7 +8,7 @@ use valence_protocol::packets::S2cPlayPacket;
 use valence_protocol::{EncodePacket, PacketDecoder, PacketEncoder, Username};
 
 use crate::client::{Client, ClientConnection};
-use crate::config::{ConnectionMode, ServerPlugin};
+use crate::config::{ConnectionContext, ConnectionMode, ServerPlugin};
 use crate::dimension::DimensionId;
 use crate::inventory::{Inventory, InventoryKind};
 use crate::server::{NewClientInfo, Server};
@@ -20,7 +21,14 @@ pub fn create_mock_client(client_info: NewClientInfo) -> (Client, MockClientHelp
     let mock_connection = MockClientConnection::new();
     let enc = PacketEncoder::new();
     let dec = PacketDecoder::new();
-    let client = Client::new(client_info, Box::new(mock_connection.clone()), enc, dec);
+    let ctx = ConnectionContext::new(SocketAddr::new(client_info.ip, 0));
+    let client = Client::new(
+        client_info,
+        ctx,
+        Box::new(mock_connection.clone()),
+        enc,
+        dec,
+    );
     (client, MockClientHelper::new(mock_connection))
 }
 
@@ -49,6 +57,8 @@ struct MockClientBuffers {
     recv_buf: BytesMut,
     /// The queue of packets to send from the server to the client.
     send_buf: BytesMut,
+    /// The number of bytes reported as not yet written to the client.
+    queued_len: usize,
 }
 
 impl MockClientConnection {
@@ -57,6 +67,7 @@ impl MockClientConnection {
             buffers: Arc::new(Mutex::new(MockClientBuffers {
                 recv_buf: BytesMut::new(),
                 send_buf: BytesMut::new(),
+                queued_len: 0,
             })),
         }
     }
@@ -83,6 +94,10 @@ impl ClientConnection for MockClientConnection {
     fn try_recv(&mut self) -> anyhow::Result<BytesMut> {
         Ok(self.buffers.lock().unwrap().recv_buf.split())
     }
+
+    fn queued_len(&self) -> usize {
+        self.buffers.lock().unwrap().queued_len
+    }
 }
 
 #[cfg(test)]
@@ -150,6 +165,12 @@ impl MockClientHelper {
     pub fn clear_sent(&mut self) {
         self.conn.clear_sent();
     }
+
+    /// Sets the number of bytes the connection reports as not yet written to
+    /// the client, to simulate a client which can't keep up.
+    pub fn set_queued_len(&mut self, len: usize) {
+        self.conn.buffers.lock().unwrap().queued_len = len;
+    }
 }
 
 /// Sets up valence with a single mock client. Returns the Entity of the client
@@ -160,7 +181,8 @@ pub fn scenario_single_client(app: &mut App) -> (Entity, MockClientHelper) {
     app.add_plugin(
         ServerPlugin::new(())
             .with_compression_threshold(None)
-            .with_connection_mode(ConnectionMode::Offline),
+            .with_connection_mode(ConnectionMode::Offline)
+            .with_listen(false),
     );
     let server = app.world.resource::<Server>();
     let instance = server.new_instance(DimensionId::default());
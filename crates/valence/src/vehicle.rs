@@ -0,0 +1,828 @@
+//! Rideable boats and minecarts.
+//!
+//! The [`VehiclePlugin`] lets clients ride entities with a [`Vehicle`]
+//! component:
+//!
+//! - Clients mount an empty boat or minecart by interacting with it and
+//!   dismount by sneaking. The player entity of the client, which is the
+//!   [`McEntity`] on the client entity, is shown riding the vehicle.
+//! - Boats are steered by their passenger. The movements sent by the client
+//!   are rejected if they are too fast or move the boat into blocks, which
+//!   puts the boat back where it was.
+//! - Minecarts follow rails and are pushed by their passenger in the direction
+//!   the passenger is looking. They speed up going down slopes and slow down
+//!   going up.
+//!
+//! [`VehicleEntered`] and [`VehicleExited`] events are sent whenever a client
+//! starts or stops riding a vehicle. Clients riding a vehicle have a [`Riding`]
+//! component, and their position follows the vehicle.
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::vehicle::{Vehicle, VehiclePlugin};
+//!
+//! let mut app = App::new();
+//! app.add_plugin(VehiclePlugin);
+//!
+//! fn spawn_boat(mut commands: Commands, instance: Entity) {
+//!     let mut boat = McEntity::new(EntityKind::Boat, instance);
+//!     boat.set_position([0.0, 64.0, 0.0]);
+//!     commands.spawn((boat, Vehicle::new()));
+//! }
+//! ```
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::{DVec3, IVec2, Vec2};
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::packets::s2c::play::MoveVehicle as MoveVehicleS2c;
+use valence_protocol::types::{EntityInteraction, GameMode, Hand};
+use valence_protocol::{BlockPos, BlockState};
+
+use crate::client::event::{InteractWithEntity, MoveVehicle, PaddleBoat, PlayerInput};
+use crate::client::Client;
+use crate::entity::{EntityKind, McEntity, McEntityManager, TrackedData};
+use crate::instance::Instance;
+use crate::math::{from_yaw_and_pitch, Aabb};
+use crate::pause::gameplay_systems;
+use crate::server::Server;
+
+/// The height of minecarts above the bottom of the rail block they are on.
+const RAIL_HEIGHT: f64 = 0.0625;
+
+/// The acceleration of minecarts on slopes in meters per second squared.
+const SLOPE_ACCELERATION: f32 = 3.125;
+
+/// The maximum squared distance from which clients can mount vehicles.
+const MAX_MOUNT_DISTANCE_SQUARED: f64 = 36.0;
+
+/// The height of the feet of a player riding a boat relative to the boat.
+const BOAT_SEAT_HEIGHT: f64 = -0.45;
+
+/// The height of the feet of a player riding a minecart relative to the
+/// minecart.
+const MINECART_SEAT_HEIGHT: f64 = -0.35;
+
+/// Adds the [`VehicleSettings`] resource, the vehicle events, and the systems
+/// for riding vehicles.
+pub struct VehiclePlugin;
+
+impl Plugin for VehiclePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VehicleSettings>()
+            .add_event::<VehicleEntered>()
+            .add_event::<VehicleExited>()
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                gameplay_systems()
+                    .with_system(mount_vehicles.before("valence_core"))
+                    .with_system(
+                        handle_vehicle_input
+                            .after(mount_vehicles)
+                            .before("valence_core"),
+                    )
+                    .with_system(
+                        move_boats
+                            .after(handle_vehicle_input)
+                            .before("valence_core"),
+                    )
+                    .with_system(
+                        move_minecarts
+                            .after(handle_vehicle_input)
+                            .before("valence_core"),
+                    )
+                    .with_system(
+                        move_passengers
+                            .after(move_boats)
+                            .after(move_minecarts)
+                            .before("valence_core"),
+                    ),
+            );
+    }
+}
+
+/// Configures the vehicles of the [`VehiclePlugin`].
+#[derive(Resource, Clone, Debug)]
+pub struct VehicleSettings {
+    /// The maximum speed of boats steered by clients in meters per second.
+    /// Faster movements are rejected.
+    ///
+    /// # Default Value
+    ///
+    /// `200.0`
+    pub max_boat_speed: f64,
+    /// The acceleration of minecarts pushed by their passenger in meters per
+    /// second squared.
+    ///
+    /// # Default Value
+    ///
+    /// `2.0`
+    pub minecart_acceleration: f32,
+    /// The maximum speed of minecarts in meters per second.
+    ///
+    /// # Default Value
+    ///
+    /// `8.0`
+    pub max_minecart_speed: f32,
+}
+
+impl Default for VehicleSettings {
+    fn default() -> Self {
+        Self {
+            max_boat_speed: 200.0,
+            minecart_acceleration: 2.0,
+            max_minecart_speed: 8.0,
+        }
+    }
+}
+
+/// A component for boats and minecarts which clients can ride.
+#[derive(Component, Clone, Default, Debug)]
+pub struct Vehicle {
+    passenger: Option<Entity>,
+    /// The forward input of the passenger in `-1.0..=1.0`.
+    forward: f32,
+    /// The speed of a minecart along the rails in meters per second.
+    speed: f32,
+    /// The horizontal direction a minecart moves in along the rails.
+    direction: IVec2,
+}
+
+impl Vehicle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the client riding this vehicle.
+    pub fn passenger(&self) -> Option<Entity> {
+        self.passenger
+    }
+
+    /// Returns the speed of this vehicle along the rails in meters per second
+    /// if it is a minecart.
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+}
+
+/// A component on clients riding a [`Vehicle`].
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Riding(Entity);
+
+impl Riding {
+    /// Returns the vehicle the client is riding.
+    pub fn vehicle(&self) -> Entity {
+        self.0
+    }
+}
+
+/// Sent when a client started riding a vehicle.
+#[derive(Clone, Debug)]
+pub struct VehicleEntered {
+    pub client: Entity,
+    pub vehicle: Entity,
+}
+
+/// Sent when a client stopped riding a vehicle.
+#[derive(Clone, Debug)]
+pub struct VehicleExited {
+    pub client: Entity,
+    pub vehicle: Entity,
+}
+
+fn is_boat(kind: EntityKind) -> bool {
+    matches!(kind, EntityKind::Boat | EntityKind::ChestBoat)
+}
+
+/// Returns the height of the feet of a passenger relative to a vehicle of
+/// the given kind.
+fn seat_height(kind: EntityKind) -> f64 {
+    if is_boat(kind) {
+        BOAT_SEAT_HEIGHT
+    } else {
+        MINECART_SEAT_HEIGHT
+    }
+}
+
+fn mount_vehicles(
+    mut commands: Commands,
+    manager: Res<McEntityManager>,
+    clients: Query<(&Client, &McEntity), Without<Vehicle>>,
+    mut vehicles: Query<(&mut Vehicle, &mut McEntity)>,
+    mut events: EventReader<InteractWithEntity>,
+    mut entered: EventWriter<VehicleEntered>,
+) {
+    for event in events.iter() {
+        if event.sneaking || event.interact != EntityInteraction::Interact(Hand::Main) {
+            continue;
+        }
+
+        let Ok((client, player)) = clients.get(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator
+            || vehicles
+                .iter()
+                .any(|(v, _)| v.passenger == Some(event.client))
+        {
+            continue;
+        }
+
+        let Some(target) = manager.get_with_protocol_id(event.entity_id) else {
+            continue
+        };
+
+        let Ok((mut vehicle, mut mc_entity)) = vehicles.get_mut(target) else {
+            continue
+        };
+
+        if !(is_boat(mc_entity.kind()) || mc_entity.kind() == EntityKind::Minecart)
+            || vehicle.passenger.is_some()
+            || mc_entity.instance() != client.instance()
+            || mc_entity.position().distance_squared(client.position()) > MAX_MOUNT_DISTANCE_SQUARED
+        {
+            continue;
+        }
+
+        vehicle.passenger = Some(event.client);
+        vehicle.forward = 0.0;
+        mc_entity.set_passengers(vec![player.protocol_id()]);
+        commands.entity(event.client).insert(Riding(target));
+
+        entered.send(VehicleEntered {
+            client: event.client,
+            vehicle: target,
+        });
+    }
+}
+
+fn handle_vehicle_input(
+    mut commands: Commands,
+    mut clients: Query<(&mut Client, Option<&Riding>)>,
+    riders: Query<(Entity, &Riding)>,
+    mut vehicles: Query<(Entity, &mut Vehicle, &mut McEntity)>,
+    mut events: EventReader<PlayerInput>,
+    mut exited: EventWriter<VehicleExited>,
+) {
+    for event in events.iter() {
+        let Ok((mut client, Some(&Riding(vehicle_ent)))) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        let Ok((_, mut vehicle, mut mc_entity)) = vehicles.get_mut(vehicle_ent) else {
+            continue
+        };
+
+        if vehicle.passenger != Some(event.client) {
+            continue;
+        }
+
+        if !event.unmount {
+            vehicle.forward = event.forward.clamp(-1.0, 1.0);
+            continue;
+        }
+
+        vehicle.passenger = None;
+        vehicle.forward = 0.0;
+        mc_entity.set_passengers(vec![]);
+        commands.entity(event.client).remove::<Riding>();
+
+        // Put the client on top of the vehicle.
+        let pos = mc_entity.position();
+        client.set_position([pos.x, mc_entity.hitbox().max.y, pos.z]);
+
+        exited.send(VehicleExited {
+            client: event.client,
+            vehicle: vehicle_ent,
+        });
+    }
+
+    // Remove passengers which disconnected or left the instance.
+    for (vehicle_ent, mut vehicle, mut mc_entity) in &mut vehicles {
+        let Some(passenger) = vehicle.passenger else {
+            continue
+        };
+
+        let left = clients.get(passenger).map_or(true, |(c, _)| {
+            c.is_disconnected() || c.instance() != mc_entity.instance()
+        });
+
+        if left {
+            vehicle.passenger = None;
+            vehicle.forward = 0.0;
+            mc_entity.set_passengers(vec![]);
+
+            if let Some(mut entity) = commands.get_entity(passenger) {
+                entity.remove::<Riding>();
+            }
+
+            exited.send(VehicleExited {
+                client: passenger,
+                vehicle: vehicle_ent,
+            });
+        }
+    }
+
+    // Remove clients from vehicles which were despawned.
+    for (client_ent, &Riding(vehicle_ent)) in &riders {
+        if !vehicles.contains(vehicle_ent) {
+            commands.entity(client_ent).remove::<Riding>();
+
+            exited.send(VehicleExited {
+                client: client_ent,
+                vehicle: vehicle_ent,
+            });
+        }
+    }
+}
+
+fn move_boats(
+    server: Res<Server>,
+    settings: Res<VehicleSettings>,
+    mut clients: Query<(&mut Client, Option<&Riding>)>,
+    instances: Query<&Instance>,
+    mut vehicles: Query<&mut McEntity, With<Vehicle>>,
+    mut moves: EventReader<MoveVehicle>,
+    mut paddles: EventReader<PaddleBoat>,
+) {
+    let max_distance = settings.max_boat_speed / server.tps() as f64;
+
+    for event in moves.iter() {
+        let Ok((mut client, Some(&Riding(vehicle_ent)))) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        let Ok(mut boat) = vehicles.get_mut(vehicle_ent) else {
+            continue
+        };
+
+        if !is_boat(boat.kind()) {
+            continue;
+        }
+
+        let Ok(instance) = instances.get(boat.instance()) else {
+            continue
+        };
+
+        let delta = event.position - boat.position();
+        let hitbox = boat.hitbox();
+
+        // Boats resting on the ground or against walls touch the blocks around
+        // them, so the hitbox is shrunk slightly.
+        let moved = Aabb::new(hitbox.min + delta + 1e-3, hitbox.max + delta - 1e-3);
+
+        if delta.length() <= max_distance && !collides_with_blocks(instance, moved) {
+            boat.set_position(event.position);
+            boat.set_yaw(event.yaw);
+            boat.set_pitch(event.pitch);
+        } else {
+            client.write_packet(&MoveVehicleS2c {
+                position: boat.position().to_array(),
+                yaw: boat.yaw(),
+                pitch: boat.pitch(),
+            });
+        }
+    }
+
+    for event in paddles.iter() {
+        let Ok((_, Some(&Riding(vehicle_ent)))) = clients.get(event.client) else {
+            continue
+        };
+
+        let Ok(mut boat) = vehicles.get_mut(vehicle_ent) else {
+            continue
+        };
+
+        match boat.data_mut() {
+            TrackedData::Boat(data) => {
+                data.set_left_paddle_moving(event.left_paddle_turning);
+                data.set_right_paddle_moving(event.right_paddle_turning);
+            }
+            TrackedData::ChestBoat(data) => {
+                data.set_left_paddle_moving(event.left_paddle_turning);
+                data.set_right_paddle_moving(event.right_paddle_turning);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns if `aabb` overlaps with the collision shape of any block in
+/// `instance`.
+fn collides_with_blocks(instance: &Instance, aabb: Aabb) -> bool {
+    let min = BlockPos::at(aabb.min);
+    let max = BlockPos::at(aabb.max);
+
+    // Fences and walls extend into the block above them.
+    for y in min.y - 1..=max.y {
+        for z in min.z..=max.z {
+            for x in min.x..=max.x {
+                let Some(block) = instance.block([x, y, z]) else {
+                    continue
+                };
+
+                let corner = DVec3::new(x as f64, y as f64, z as f64);
+
+                let collides = block
+                    .state()
+                    .collision_shapes()
+                    .any(|[x0, y0, z0, x1, y1, z1]| {
+                        Aabb::new(
+                            corner + DVec3::new(x0, y0, z0),
+                            corner + DVec3::new(x1, y1, z1),
+                        )
+                        .intersects(aabb)
+                    });
+
+                if collides {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+fn move_minecarts(
+    server: Res<Server>,
+    settings: Res<VehicleSettings>,
+    clients: Query<&Client>,
+    instances: Query<&Instance>,
+    mut vehicles: Query<(&mut Vehicle, &mut McEntity)>,
+) {
+    let tps = server.tps() as f32;
+
+    for (mut vehicle, mut minecart) in &mut vehicles {
+        if minecart.kind() != EntityKind::Minecart {
+            continue;
+        }
+
+        let Ok(instance) = instances.get(minecart.instance()) else {
+            continue
+        };
+
+        let mut position = minecart.position();
+
+        let Some((_, rail)) = find_rail(instance, position) else {
+            vehicle.speed = 0.0;
+            continue
+        };
+
+        // Push the minecart in the direction the passenger is looking.
+        let passenger = vehicle.passenger.and_then(|p| clients.get(p).ok());
+
+        if let Some(client) = passenger.filter(|_| vehicle.forward != 0.0) {
+            let look = from_yaw_and_pitch(client.yaw(), 0.0);
+            let push = Vec2::new(look.x, look.z) * vehicle.forward;
+
+            if vehicle.speed == 0.0 {
+                vehicle.direction = rail
+                    .exits
+                    .into_iter()
+                    .max_by(|a, b| push.dot(a.as_vec2()).total_cmp(&push.dot(b.as_vec2())))
+                    .unwrap();
+            }
+
+            vehicle.speed +=
+                push.dot(vehicle.direction.as_vec2()) * settings.minecart_acceleration / tps;
+        }
+
+        if let Some(up) = rail.ascending {
+            if vehicle.speed == 0.0 {
+                vehicle.direction = -up;
+            }
+
+            if vehicle.direction == up {
+                vehicle.speed -= SLOPE_ACCELERATION / tps;
+            } else {
+                vehicle.speed += SLOPE_ACCELERATION / tps;
+            }
+        }
+
+        if vehicle.speed < 0.0 {
+            vehicle.direction = -vehicle.direction;
+            vehicle.speed = -vehicle.speed;
+        }
+
+        // Minecarts with a passenger keep more of their speed.
+        let drag: f32 = if passenger.is_some() { 0.997 } else { 0.96 };
+
+        vehicle.speed = (vehicle.speed * drag.powf(20.0 / tps)).min(settings.max_minecart_speed);
+
+        if vehicle.speed < 1e-3 || vehicle.direction == IVec2::ZERO {
+            vehicle.speed = 0.0;
+            continue;
+        }
+
+        let distance = (vehicle.speed / tps) as f64;
+
+        if !follow_rails(instance, &mut position, &mut vehicle.direction, distance) {
+            vehicle.speed = 0.0;
+        }
+
+        minecart.set_position(position);
+    }
+}
+
+/// Moves the clients riding vehicles along with their vehicle. Riding clients
+/// only send the movements of the vehicle, so their position is never updated
+/// otherwise.
+fn move_passengers(mut clients: Query<&mut Client>, vehicles: Query<(&Vehicle, &McEntity)>) {
+    for (vehicle, mc_entity) in &vehicles {
+        let Some(mut client) = vehicle.passenger.and_then(|p| clients.get_mut(p).ok()) else {
+            continue
+        };
+
+        let seat = mc_entity.position() + DVec3::new(0.0, seat_height(mc_entity.kind()), 0.0);
+        client.set_position_unsynced(seat);
+    }
+}
+
+/// The shape of a rail.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Rail {
+    /// The horizontal directions the rail leads to.
+    exits: [IVec2; 2],
+    /// The direction the rail ascends towards, if it is a slope.
+    ascending: Option<IVec2>,
+}
+
+const NORTH: IVec2 = IVec2::new(0, -1);
+const SOUTH: IVec2 = IVec2::new(0, 1);
+const WEST: IVec2 = IVec2::new(-1, 0);
+const EAST: IVec2 = IVec2::new(1, 0);
+
+fn rail_shape(state: BlockState) -> Option<Rail> {
+    let (exits, ascending) = match state.get(PropName::Shape)? {
+        PropValue::NorthSouth => ([NORTH, SOUTH], None),
+        PropValue::EastWest => ([EAST, WEST], None),
+        PropValue::AscendingNorth => ([NORTH, SOUTH], Some(NORTH)),
+        PropValue::AscendingSouth => ([SOUTH, NORTH], Some(SOUTH)),
+        PropValue::AscendingEast => ([EAST, WEST], Some(EAST)),
+        PropValue::AscendingWest => ([WEST, EAST], Some(WEST)),
+        PropValue::SouthEast => ([SOUTH, EAST], None),
+        PropValue::SouthWest => ([SOUTH, WEST], None),
+        PropValue::NorthWest => ([NORTH, WEST], None),
+        PropValue::NorthEast => ([NORTH, EAST], None),
+        _ => return None,
+    };
+
+    Some(Rail { exits, ascending })
+}
+
+/// Finds the rail a minecart at `position` is on. Minecarts on the upper half
+/// of a slope are above the block of the rail.
+fn find_rail(instance: &Instance, position: DVec3) -> Option<(BlockPos, Rail)> {
+    let pos = BlockPos::at(position);
+    let below = BlockPos::new(pos.x, pos.y - 1, pos.z);
+
+    [pos, below].into_iter().find_map(|pos| {
+        let rail = rail_shape(instance.block(pos)?.state())?;
+        Some((pos, rail))
+    })
+}
+
+/// Returns the height of a minecart at `position` on `rail` at `pos`.
+fn rail_height(pos: BlockPos, rail: Rail, position: DVec3) -> f64 {
+    let progress = match rail.ascending {
+        Some(EAST) => position.x - pos.x as f64,
+        Some(WEST) => (pos.x + 1) as f64 - position.x,
+        Some(SOUTH) => position.z - pos.z as f64,
+        Some(NORTH) => (pos.z + 1) as f64 - position.z,
+        _ => 0.0,
+    };
+
+    pos.y as f64 + RAIL_HEIGHT + progress.clamp(0.0, 1.0)
+}
+
+/// Moves a minecart at `position` traveling in `direction` along the rails
+/// by `distance` blocks. Curves change the direction at the center of their
+/// block. Returns `false` if the minecart reached the end of the track.
+fn follow_rails(
+    instance: &Instance,
+    position: &mut DVec3,
+    direction: &mut IVec2,
+    mut distance: f64,
+) -> bool {
+    // Nudges the minecart over the edges of blocks.
+    const EPSILON: f64 = 1e-4;
+
+    // Each iteration moves at least to the next center or edge of a block.
+    for _ in 0..64 {
+        let Some((pos, rail)) = find_rail(instance, *position) else {
+            return false
+        };
+
+        let exit = if rail.exits.contains(direction) {
+            *direction
+        } else if rail.exits[0] == -*direction {
+            rail.exits[1]
+        } else if rail.exits[1] == -*direction {
+            rail.exits[0]
+        } else {
+            return false;
+        };
+
+        let dir = DVec3::new(direction.x as f64, 0.0, direction.y as f64);
+        let center = DVec3::new(pos.x as f64 + 0.5, position.y, pos.z as f64 + 0.5);
+
+        if exit != *direction {
+            let to_center = (center - *position).dot(dir);
+
+            if to_center > distance {
+                *position += dir * distance;
+                return true;
+            }
+
+            *position = center;
+            distance -= to_center.max(0.0);
+            *direction = exit;
+            continue;
+        }
+
+        // Keep the minecart on the center line of the rail.
+        if direction.x == 0 {
+            position.x = center.x;
+        } else {
+            position.z = center.z;
+        }
+
+        let to_edge = match *direction {
+            EAST => (pos.x + 1) as f64 - position.x,
+            WEST => position.x - pos.x as f64,
+            SOUTH => (pos.z + 1) as f64 - position.z,
+            _ => position.z - pos.z as f64,
+        };
+
+        let moved = to_edge.min(distance);
+        *position += dir * moved;
+        position.y = rail_height(pos, rail, *position);
+        distance -= moved;
+
+        if distance <= 0.0 {
+            return true;
+        }
+
+        *position += dir * EPSILON;
+        distance -= EPSILON;
+
+        if distance <= 0.0 {
+            return true;
+        }
+
+        let Some((next_pos, next_rail)) = find_rail(instance, *position) else {
+            return false
+        };
+
+        position.y = rail_height(next_pos, next_rail, *position);
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn rails_lead_around_curves_and_up_slopes() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let y = instance.min_y() + 10;
+
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        // East along z = 2, turning south at x = 4, then up a slope at z = 4.
+        for x in 0..4 {
+            instance.set_block([x, y, 2], BlockState::RAIL);
+        }
+
+        let curve = BlockState::RAIL.set(PropName::Shape, PropValue::SouthWest);
+        let slope = BlockState::RAIL.set(PropName::Shape, PropValue::AscendingSouth);
+        let straight = BlockState::RAIL.set(PropName::Shape, PropValue::NorthSouth);
+
+        instance.set_block([4, y, 2], curve);
+        instance.set_block([4, y, 3], straight);
+        instance.set_block([4, y, 4], slope);
+        instance.set_block([4, y + 1, 5], straight);
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+
+        let mut position = DVec3::new(0.5, y as f64 + RAIL_HEIGHT, 2.5);
+        let mut direction = EAST;
+
+        assert!(follow_rails(instance, &mut position, &mut direction, 4.0));
+        assert_eq!(direction, SOUTH);
+        assert!(position.distance(DVec3::new(4.5, y as f64 + RAIL_HEIGHT, 2.5)) < 1e-3);
+
+        assert!(follow_rails(instance, &mut position, &mut direction, 2.5));
+        assert!(position.distance(DVec3::new(4.5, (y + 1) as f64 + RAIL_HEIGHT, 5.0)) < 1e-3);
+
+        // The track ends in the middle of the last rail.
+        assert!(!follow_rails(instance, &mut position, &mut direction, 2.0));
+    }
+
+    #[test]
+    fn mount_and_steer_boat() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(VehiclePlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        app.world
+            .entity_mut(client_ent)
+            .insert(McEntity::new(EntityKind::Player, instance_ent));
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let y = instance.min_y() + 10;
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([8, y + 1, 4], BlockState::STONE);
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([4.0, y as f64 + 1.0, 4.0]);
+
+        let mut boat = McEntity::new(EntityKind::Boat, instance_ent);
+        boat.set_position([5.0, y as f64 + 1.0, 4.0]);
+        let boat = app.world.spawn((boat, Vehicle::new())).id();
+
+        app.update();
+
+        let entity_id = app.world.get::<McEntity>(boat).unwrap().protocol_id();
+        app.world.send_event(InteractWithEntity {
+            client: client_ent,
+            entity_id,
+            sneaking: false,
+            interact: EntityInteraction::Interact(Hand::Main),
+        });
+
+        app.update();
+
+        assert_eq!(
+            app.world.get::<Vehicle>(boat).unwrap().passenger(),
+            Some(client_ent)
+        );
+
+        let player_id = app.world.get::<McEntity>(client_ent).unwrap().protocol_id();
+        assert_eq!(
+            app.world.get::<McEntity>(boat).unwrap().passengers(),
+            &[player_id]
+        );
+        assert_eq!(app.world.get::<Riding>(client_ent), Some(&Riding(boat)));
+
+        // A valid move, then a move into the stone.
+        for x in [6.0, 8.0] {
+            app.world.send_event(MoveVehicle {
+                client: client_ent,
+                position: DVec3::new(x, y as f64 + 1.0, 4.0),
+                yaw: 0.0,
+                pitch: 0.0,
+            });
+        }
+
+        client_helper.clear_sent();
+        app.update();
+
+        let boat_pos = app.world.get::<McEntity>(boat).unwrap().position();
+        assert_eq!(boat_pos, DVec3::new(6.0, y as f64 + 1.0, 4.0));
+
+        // The client rides along with the boat.
+        assert_eq!(
+            app.world.get::<Client>(client_ent).unwrap().position(),
+            boat_pos + DVec3::new(0.0, BOAT_SEAT_HEIGHT, 0.0)
+        );
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::MoveVehicle(_));
+
+        app.world.send_event(PlayerInput {
+            client: client_ent,
+            sideways: 0.0,
+            forward: 0.0,
+            jump: false,
+            unmount: true,
+        });
+
+        app.update();
+
+        assert_eq!(app.world.get::<Vehicle>(boat).unwrap().passenger(), None);
+        assert!(app
+            .world
+            .get::<McEntity>(boat)
+            .unwrap()
+            .passengers()
+            .is_empty());
+        assert_eq!(app.world.get::<Riding>(client_ent), None);
+    }
+}
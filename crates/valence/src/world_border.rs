@@ -0,0 +1,448 @@
+//! World borders of instances and clients.
+//!
+//! Inserting a [`WorldBorder`] component on an instance entity shows the
+//! border to all clients in the instance. Inserting one on a client entity
+//! shows that client a border of its own instead, which is useful for borders
+//! that only apply to some players, e.g. the shrinking circle of a battle
+//! royale. Removing the client's border shows the client the border of its
+//! instance again.
+//!
+//! The border can be resized instantly with [`WorldBorder::set_diameter`] or
+//! animated over time with [`WorldBorder::lerp_target`]. Clients interpolate
+//! the animation themselves, so only the start of it is sent.
+//!
+//! The [`WorldBorderPlugin`] sends the border again after clients join, move
+//! to another instance, or respawn, since clients forget the border then.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use valence::prelude::*;
+//! use valence::world_border::WorldBorder;
+//!
+//! fn setup(mut commands: Commands, server: Res<Server>) {
+//!     let instance = server.new_instance(DimensionId::default());
+//!     commands.spawn((instance, WorldBorder::new([0.0, 0.0], 500.0)));
+//! }
+//!
+//! fn shrink(mut borders: Query<&mut WorldBorder, With<Instance>>) {
+//!     for mut border in &mut borders {
+//!         border.lerp_target(50.0, Duration::from_secs(60));
+//!     }
+//! }
+//! ```
+
+use std::time::Duration;
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use valence_protocol::packets::s2c::play::{
+    SetBorderCenter, SetBorderLerpSize, SetBorderSize, SetBorderWarningDelay,
+    SetBorderWarningDistance, WorldBorderInitialize,
+};
+use valence_protocol::{VarInt, VarLong};
+
+use crate::client::Client;
+use crate::server::Server;
+
+/// The diameter of the vanilla world border.
+pub const DEFAULT_DIAMETER: f64 = 59_999_968.0;
+
+/// The distance from the origin past which vanilla clients refuse to teleport
+/// through portals.
+pub const DEFAULT_PORTAL_TELEPORT_BOUNDARY: i32 = 29_999_984;
+
+/// Adds the systems sending [`WorldBorder`]s to clients.
+pub struct WorldBorderPlugin;
+
+impl Plugin for WorldBorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            send_world_borders.before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_world_borders
+                .after(send_world_borders)
+                .before("valence_core"),
+        );
+    }
+}
+
+/// A component for instance and client entities describing a world border.
+///
+/// See the [module level documentation](self) for more information.
+#[derive(Component, Clone, Debug)]
+pub struct WorldBorder {
+    center: [f64; 2],
+    /// The diameter at the start of the current animation.
+    old_diameter: f64,
+    /// The diameter at the end of the current animation.
+    new_diameter: f64,
+    lerp_duration: Duration,
+    lerp_remaining: Duration,
+    warning_blocks: i32,
+    warning_time: i32,
+    portal_teleport_boundary: i32,
+    center_modified: bool,
+    diameter_modified: bool,
+    warning_blocks_modified: bool,
+    warning_time_modified: bool,
+}
+
+impl WorldBorder {
+    pub fn new(center: impl Into<[f64; 2]>, diameter: f64) -> Self {
+        Self {
+            center: center.into(),
+            old_diameter: diameter,
+            new_diameter: diameter,
+            lerp_duration: Duration::ZERO,
+            lerp_remaining: Duration::ZERO,
+            warning_blocks: 5,
+            warning_time: 15,
+            portal_teleport_boundary: DEFAULT_PORTAL_TELEPORT_BOUNDARY,
+            center_modified: false,
+            diameter_modified: false,
+            warning_blocks_modified: false,
+            warning_time_modified: false,
+        }
+    }
+
+    /// The X and Z coordinates of the center of the border.
+    pub fn center(&self) -> [f64; 2] {
+        self.center
+    }
+
+    pub fn set_center(&mut self, center: impl Into<[f64; 2]>) {
+        let center = center.into();
+
+        if self.center != center {
+            self.center = center;
+            self.center_modified = true;
+        }
+    }
+
+    /// Returns the current diameter of the border, which is between the old
+    /// and the target diameter while the border is animated.
+    pub fn diameter(&self) -> f64 {
+        if self.lerp_remaining.is_zero() {
+            return self.new_diameter;
+        }
+
+        let progress = 1.0 - self.lerp_remaining.as_secs_f64() / self.lerp_duration.as_secs_f64();
+        self.old_diameter + (self.new_diameter - self.old_diameter) * progress
+    }
+
+    /// Returns the diameter the border is animated towards, or the current
+    /// diameter if it isn't animated.
+    pub fn target_diameter(&self) -> f64 {
+        self.new_diameter
+    }
+
+    /// Sets the diameter of the border immediately, stopping any animation.
+    pub fn set_diameter(&mut self, diameter: f64) {
+        self.lerp_target(diameter, Duration::ZERO);
+    }
+
+    /// Animates the border from its current diameter to `diameter` over
+    /// `duration`.
+    pub fn lerp_target(&mut self, diameter: f64, duration: Duration) {
+        self.old_diameter = self.diameter();
+        self.new_diameter = diameter;
+        self.lerp_duration = duration;
+        self.lerp_remaining = duration;
+        self.diameter_modified = true;
+    }
+
+    /// Returns the time left until the border reaches its target diameter.
+    pub fn lerp_remaining(&self) -> Duration {
+        self.lerp_remaining
+    }
+
+    /// If the border is currently being animated.
+    pub fn is_moving(&self) -> bool {
+        !self.lerp_remaining.is_zero()
+    }
+
+    /// The distance in blocks from the border at which clients are warned
+    /// with a red vignette.
+    pub fn warning_blocks(&self) -> i32 {
+        self.warning_blocks
+    }
+
+    pub fn set_warning_blocks(&mut self, blocks: i32) {
+        if self.warning_blocks != blocks {
+            self.warning_blocks = blocks;
+            self.warning_blocks_modified = true;
+        }
+    }
+
+    /// The number of seconds before a shrinking border reaches clients at
+    /// which they are warned with a red vignette.
+    pub fn warning_time(&self) -> i32 {
+        self.warning_time
+    }
+
+    pub fn set_warning_time(&mut self, seconds: i32) {
+        if self.warning_time != seconds {
+            self.warning_time = seconds;
+            self.warning_time_modified = true;
+        }
+    }
+
+    pub fn portal_teleport_boundary(&self) -> i32 {
+        self.portal_teleport_boundary
+    }
+
+    /// Sets the portal teleport boundary. This is only sent when the border is
+    /// sent in full, e.g. when a client joins.
+    pub fn set_portal_teleport_boundary(&mut self, boundary: i32) {
+        self.portal_teleport_boundary = boundary;
+    }
+
+    /// If the X and Z coordinates of `pos` are inside the current border.
+    pub fn contains(&self, pos: impl Into<DVec3>) -> bool {
+        self.distance_to_edge(pos) >= 0.0
+    }
+
+    /// Returns the horizontal distance from `pos` to the nearest edge of the
+    /// current border. The distance is negative if `pos` is outside the
+    /// border.
+    pub fn distance_to_edge(&self, pos: impl Into<DVec3>) -> f64 {
+        let pos = pos.into();
+        let radius = self.diameter() / 2.0;
+
+        let dx = radius - (pos.x - self.center[0]).abs();
+        let dz = radius - (pos.z - self.center[1]).abs();
+
+        if dx < 0.0 && dz < 0.0 {
+            -dx.hypot(dz)
+        } else {
+            dx.min(dz)
+        }
+    }
+
+    fn initialize_packet(&self) -> WorldBorderInitialize {
+        WorldBorderInitialize {
+            x: self.center[0],
+            z: self.center[1],
+            old_diameter: self.diameter(),
+            new_diameter: self.new_diameter,
+            speed: VarLong(self.lerp_remaining.as_millis() as i64),
+            portal_teleport_boundary: VarInt(self.portal_teleport_boundary),
+            warning_blocks: VarInt(self.warning_blocks),
+            warning_time: VarInt(self.warning_time),
+        }
+    }
+
+    fn write_changes(&self, client: &mut Client) {
+        if self.center_modified {
+            client.write_packet(&SetBorderCenter {
+                xz_position: self.center,
+            });
+        }
+
+        if self.diameter_modified {
+            if self.lerp_remaining.is_zero() {
+                client.write_packet(&SetBorderSize {
+                    diameter: self.new_diameter,
+                });
+            } else {
+                client.write_packet(&SetBorderLerpSize {
+                    old_diameter: self.old_diameter,
+                    new_diameter: self.new_diameter,
+                    speed: VarLong(self.lerp_remaining.as_millis() as i64),
+                });
+            }
+        }
+
+        if self.warning_blocks_modified {
+            client.write_packet(&SetBorderWarningDistance {
+                warning_blocks: VarInt(self.warning_blocks),
+            });
+        }
+
+        if self.warning_time_modified {
+            client.write_packet(&SetBorderWarningDelay {
+                warning_time: VarInt(self.warning_time),
+            });
+        }
+    }
+
+    fn is_modified(&self) -> bool {
+        self.center_modified
+            || self.diameter_modified
+            || self.warning_blocks_modified
+            || self.warning_time_modified
+    }
+}
+
+impl Default for WorldBorder {
+    fn default() -> Self {
+        Self::new([0.0, 0.0], DEFAULT_DIAMETER)
+    }
+}
+
+/// Tracks which border a client was last sent.
+#[derive(Component, Debug)]
+struct ClientBorderState {
+    /// The entity whose border the client was sent, if any.
+    source: Option<Entity>,
+    /// Set when the client forgot its border because it joined or respawned.
+    /// The border is sent in the next tick, after the respawn packet.
+    needs_init: bool,
+}
+
+fn send_world_borders(
+    mut commands: Commands,
+    mut clients: Query<(
+        Entity,
+        &mut Client,
+        Option<&WorldBorder>,
+        Option<&mut ClientBorderState>,
+    )>,
+    instance_borders: Query<&WorldBorder, Without<Client>>,
+) {
+    for (entity, mut client, client_border, state) in &mut clients {
+        let respawning = client.is_new() || client.needs_respawn();
+
+        let Some(mut state) = state else {
+            commands.entity(entity).insert(ClientBorderState {
+                source: None,
+                needs_init: true,
+            });
+            continue
+        };
+
+        if respawning {
+            // The client resets its border when it respawns, so whatever is
+            // sent now would be lost.
+            state.source = None;
+            state.needs_init = true;
+            continue;
+        }
+
+        let (source, border) = match client_border {
+            Some(border) => (Some(entity), Some(border)),
+            None => match instance_borders.get(client.instance()) {
+                Ok(border) => (Some(client.instance()), Some(border)),
+                Err(_) => (None, None),
+            },
+        };
+
+        if state.needs_init || state.source != source {
+            match border {
+                Some(border) => client.write_packet(&border.initialize_packet()),
+                // Show the client the vanilla border again.
+                None if !state.needs_init => {
+                    client.write_packet(&WorldBorder::default().initialize_packet())
+                }
+                None => {}
+            }
+
+            state.source = source;
+            state.needs_init = false;
+        } else if let Some(border) = border {
+            if border.is_modified() {
+                border.write_changes(&mut client);
+            }
+        }
+    }
+}
+
+fn update_world_borders(server: Res<Server>, mut borders: Query<&mut WorldBorder>) {
+    let tick_duration = Duration::from_secs(1) / server.tps() as u32;
+
+    for mut border in &mut borders {
+        // Avoid triggering change detection for unchanged borders.
+        if !border.is_moving() && !border.is_modified() {
+            continue;
+        }
+
+        let border = &mut *border;
+
+        border.lerp_remaining = border.lerp_remaining.saturating_sub(tick_duration);
+        border.center_modified = false;
+        border.diameter_modified = false;
+        border.warning_blocks_modified = false;
+        border.warning_time_modified = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::dimension::DimensionId;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn world_border_resent_after_transfer() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(WorldBorderPlugin);
+
+        let instance = app.world.get::<Client>(client_ent).unwrap().instance();
+        app.world
+            .entity_mut(instance)
+            .insert(WorldBorder::new([0.0, 0.0], 100.0));
+
+        app.update();
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::WorldBorderInitialize(_));
+
+        app.world
+            .get_mut::<WorldBorder>(instance)
+            .unwrap()
+            .lerp_target(10.0, Duration::from_secs(10));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetBorderLerpSize(_));
+
+        let border = app.world.get::<WorldBorder>(instance).unwrap();
+        assert!(border.is_moving());
+        assert!(border.diameter() < 100.0 && border.diameter() > 10.0);
+
+        // The client's own border takes precedence over the instance's.
+        app.world
+            .entity_mut(client_ent)
+            .insert(WorldBorder::new([5.0, 5.0], 20.0));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::WorldBorderInitialize(_));
+
+        app.world.entity_mut(client_ent).remove::<WorldBorder>();
+
+        let server = app.world.resource::<Server>();
+        let other = server.new_instance(DimensionId::default());
+        let other = app
+            .world
+            .spawn((other, WorldBorder::new([0.0, 0.0], 30.0)))
+            .id();
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_instance(other);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::WorldBorderInitialize(_));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::WorldBorderInitialize(_));
+    }
+}
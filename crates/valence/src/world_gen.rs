@@ -0,0 +1,449 @@
+//! Chunk generators for instances.
+//!
+//! The [`WorldGenPlugin`] fills in the missing chunks in view of clients in
+//! instances with a [`FlatGenerator`] or [`VoidGenerator`] component, so
+//! examples and lobby servers don't need their own chunk generation. With the
+//! `noise` feature, the `OverworldGenerator` component generates
+//! vanilla-like terrain. Instances with a [`Features`] component are
+//! decorated with trees, ores, lakes, and other features after generation.
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::world_gen::{FlatGenerator, Features, TreeFeature, WorldGenPlugin};
+//!
+//! let mut app = App::new();
+//! app.add_plugin(WorldGenPlugin);
+//!
+//! fn setup(mut commands: Commands, server: Res<Server>) {
+//!     let instance = server.new_instance(DimensionId::default());
+//!     let generator = FlatGenerator::parse("bedrock,2*dirt,grass_block").unwrap();
+//!     commands.spawn((instance, generator, Features::new(0).with(TreeFeature::oak())));
+//! }
+//! ```
+//!
+//! Other generators can be used in the same way by implementing
+//! [`ChunkGenerator`] and adding the [`generate_chunks`] system for them.
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+pub use feature::*;
+#[cfg(feature = "noise")]
+pub use overworld::*;
+use rayon::prelude::*;
+use thiserror::Error;
+use valence_protocol::block::{BlockKind, BlockState};
+use valence_protocol::BlockPos;
+
+use crate::biome::BiomeId;
+use crate::client::Client;
+use crate::instance::{Chunk, Instance};
+use crate::view::ChunkPos;
+
+mod feature;
+#[cfg(feature = "noise")]
+mod overworld;
+
+/// Adds the [`generate_chunks`] systems for the generators in this module.
+pub struct WorldGenPlugin;
+
+impl Plugin for WorldGenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            generate_chunks::<FlatGenerator>.before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            generate_chunks::<VoidGenerator>.before("valence_core"),
+        );
+
+        #[cfg(feature = "noise")]
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            generate_chunks::<OverworldGenerator>.before("valence_core"),
+        );
+    }
+}
+
+/// A component for instance entities which creates the chunks of the
+/// instance.
+///
+/// Chunks are generated in parallel, so `generate` should only depend on the
+/// position of the chunk and not on the order chunks are generated in.
+pub trait ChunkGenerator: Component {
+    /// Creates the chunk at `pos` in `instance`. The chunk is resized to the
+    /// section count of the instance when it is inserted.
+    fn generate(&self, pos: ChunkPos, instance: &Instance) -> Chunk;
+
+    /// Returns the Y coordinate and block of the highest solid block the
+    /// generator places in the block column at `(x, z)`, without generating
+    /// its chunk. Used by [`Features`] to place features spanning chunks.
+    ///
+    /// Returns `None` by default, so features which need to know the surface
+    /// aren't placed.
+    fn surface(&self, x: i32, z: i32, instance: &Instance) -> Option<(i32, BlockState)> {
+        let _ = (x, z, instance);
+        None
+    }
+}
+
+/// Inserts the chunks in view of clients which are missing from instances
+/// with the generator `G`, and decorates them if the instance has
+/// [`Features`]. The missing chunks of each client are generated in parallel
+/// on the rayon thread pool.
+///
+/// Chunks are never removed, so instances should only be generated in a
+/// limited area or have their unviewed chunks removed by another system.
+pub fn generate_chunks<G: ChunkGenerator>(
+    mut instances: Query<(&mut Instance, &G, Option<&Features>)>,
+    clients: Query<&Client>,
+) {
+    for client in &clients {
+        let Ok((mut instance, generator, features)) = instances.get_mut(client.instance()) else {
+            continue
+        };
+
+        let missing: Vec<_> = client
+            .view()
+            .iter()
+            .filter(|&pos| instance.chunk(pos).is_none())
+            .collect();
+
+        if missing.is_empty() {
+            continue;
+        }
+
+        let shared: &Instance = &instance;
+
+        let chunks: Vec<_> = missing
+            .into_par_iter()
+            .map(|pos| {
+                let mut chunk = generator.generate(pos, shared);
+
+                if let Some(features) = features {
+                    let surface = |x, z| generator.surface(x, z, shared);
+                    features.decorate(pos, &mut chunk, shared.min_y(), &surface);
+                }
+
+                (pos, chunk)
+            })
+            .collect();
+
+        for (pos, chunk) in chunks {
+            instance.insert_chunk(pos, chunk);
+        }
+    }
+}
+
+/// Generates a superflat world made of horizontal layers of blocks.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct FlatGenerator {
+    /// The layers from the bottom of the world upwards. Layers above the top
+    /// of the world are cut off.
+    pub layers: Vec<FlatLayer>,
+    pub biome: BiomeId,
+}
+
+/// A layer of a [`FlatGenerator`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FlatLayer {
+    pub block: BlockState,
+    /// The number of blocks the layer is high.
+    pub height: usize,
+}
+
+/// An error returned by [`FlatGenerator::parse`].
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+pub enum ParseFlatError {
+    #[error("empty layer")]
+    EmptyLayer,
+    #[error("invalid layer height \"{0}\"")]
+    InvalidHeight(String),
+    #[error("unknown block \"{0}\"")]
+    UnknownBlock(String),
+}
+
+impl FlatGenerator {
+    pub fn new(layers: impl Into<Vec<FlatLayer>>) -> Self {
+        Self {
+            layers: layers.into(),
+            biome: BiomeId::default(),
+        }
+    }
+
+    /// Parses the layers of a vanilla superflat preset code, such as
+    /// `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block`.
+    ///
+    /// Layers are separated by commas and listed from the bottom up. Each
+    /// layer is a block name, optionally preceded by a height and `*`. The
+    /// `minecraft:` namespace may be omitted. Anything after the first `;`,
+    /// like the biome of a preset code, is ignored. The biome is set with
+    /// [`Self::with_biome`] instead.
+    pub fn parse(spec: &str) -> Result<Self, ParseFlatError> {
+        let layers = spec.split(';').next().unwrap_or_default();
+
+        let layers = layers
+            .split(',')
+            .map(|layer| {
+                let layer = layer.trim();
+
+                if layer.is_empty() {
+                    return Err(ParseFlatError::EmptyLayer);
+                }
+
+                let (height, name) = match layer.split_once('*') {
+                    Some((height, name)) => match height.trim().parse() {
+                        Ok(height) if height > 0 => (height, name.trim()),
+                        _ => return Err(ParseFlatError::InvalidHeight(height.into())),
+                    },
+                    None => (1, layer),
+                };
+
+                let path = name.strip_prefix("minecraft:").unwrap_or(name);
+
+                match BlockKind::from_str(path) {
+                    Some(kind) => Ok(FlatLayer {
+                        block: kind.to_state(),
+                        height,
+                    }),
+                    None => Err(ParseFlatError::UnknownBlock(name.into())),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::new(layers))
+    }
+
+    #[must_use]
+    pub fn with_biome(mut self, biome: BiomeId) -> Self {
+        self.biome = biome;
+        self
+    }
+}
+
+impl Default for FlatGenerator {
+    /// The layers of the vanilla "Classic Flat" preset: bedrock, two layers of
+    /// dirt, and grass.
+    fn default() -> Self {
+        Self::new([
+            FlatLayer {
+                block: BlockState::BEDROCK,
+                height: 1,
+            },
+            FlatLayer {
+                block: BlockState::DIRT,
+                height: 2,
+            },
+            FlatLayer {
+                block: BlockState::GRASS_BLOCK,
+                height: 1,
+            },
+        ])
+    }
+}
+
+impl ChunkGenerator for FlatGenerator {
+    fn generate(&self, _pos: ChunkPos, instance: &Instance) -> Chunk {
+        let mut chunk = Chunk::new(instance.section_count());
+        let height = instance.section_count() * 16;
+
+        fill_biomes(&mut chunk, self.biome);
+
+        let mut y = 0;
+
+        'layers: for layer in &self.layers {
+            for _ in 0..layer.height {
+                if y >= height {
+                    break 'layers;
+                }
+
+                if !layer.block.is_air() {
+                    for z in 0..16 {
+                        for x in 0..16 {
+                            chunk.set_block_state(x, y, z, layer.block);
+                        }
+                    }
+                }
+
+                y += 1;
+            }
+        }
+
+        chunk
+    }
+
+    fn surface(&self, _x: i32, _z: i32, instance: &Instance) -> Option<(i32, BlockState)> {
+        let height = instance.section_count() * 16;
+        let mut top = None;
+        let mut y = 0;
+
+        for layer in &self.layers {
+            let bottom = y;
+            y = (y + layer.height).min(height);
+
+            if !layer.block.is_air() && y > bottom {
+                top = Some((instance.min_y() + y as i32 - 1, layer.block));
+            }
+        }
+
+        top
+    }
+}
+
+/// Generates an empty world, optionally with a platform to spawn on.
+#[derive(Component, Clone, Default, PartialEq, Debug)]
+pub struct VoidGenerator {
+    pub platform: Option<SpawnPlatform>,
+    pub biome: BiomeId,
+}
+
+/// A square platform of a [`VoidGenerator`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SpawnPlatform {
+    /// The position of the block in the middle of the platform.
+    pub center: BlockPos,
+    /// The number of blocks the platform extends from the center in each
+    /// horizontal direction.
+    pub radius: u32,
+    pub block: BlockState,
+}
+
+impl VoidGenerator {
+    /// Creates a generator for an empty world without a platform.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a square platform of `block` centered on `center` with sides
+    /// `radius * 2 + 1` blocks long.
+    #[must_use]
+    pub fn with_platform(
+        mut self,
+        center: impl Into<BlockPos>,
+        radius: u32,
+        block: BlockState,
+    ) -> Self {
+        self.platform = Some(SpawnPlatform {
+            center: center.into(),
+            radius,
+            block,
+        });
+        self
+    }
+
+    #[must_use]
+    pub fn with_biome(mut self, biome: BiomeId) -> Self {
+        self.biome = biome;
+        self
+    }
+}
+
+impl ChunkGenerator for VoidGenerator {
+    fn generate(&self, pos: ChunkPos, instance: &Instance) -> Chunk {
+        let mut chunk = Chunk::new(instance.section_count());
+
+        fill_biomes(&mut chunk, self.biome);
+
+        if let Some(platform) = &self.platform {
+            let y = platform.center.y - instance.min_y();
+            let radius = platform.radius as i32;
+
+            if (0..chunk.section_count() as i32 * 16).contains(&y) {
+                for z in 0..16 {
+                    for x in 0..16 {
+                        let dx = pos.x * 16 + x as i32 - platform.center.x;
+                        let dz = pos.z * 16 + z as i32 - platform.center.z;
+
+                        if dx.abs() <= radius && dz.abs() <= radius {
+                            chunk.set_block_state(x, y as usize, z, platform.block);
+                        }
+                    }
+                }
+            }
+        }
+
+        chunk
+    }
+
+    fn surface(&self, x: i32, z: i32, _instance: &Instance) -> Option<(i32, BlockState)> {
+        let platform = self.platform.as_ref()?;
+        let radius = platform.radius as i32;
+
+        ((x - platform.center.x).abs() <= radius && (z - platform.center.z).abs() <= radius)
+            .then_some((platform.center.y, platform.block))
+    }
+}
+
+fn fill_biomes(chunk: &mut Chunk, biome: BiomeId) {
+    if biome != BiomeId::default() {
+        for sect_y in 0..chunk.section_count() {
+            chunk.fill_biomes(sect_y, biome);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn parse_flat_layers() {
+        let generator =
+            FlatGenerator::parse("minecraft:bedrock, 2*dirt,grass_block;minecraft:plains").unwrap();
+
+        assert_eq!(generator.layers, FlatGenerator::default().layers);
+
+        assert_eq!(
+            FlatGenerator::parse("stone,,dirt"),
+            Err(ParseFlatError::EmptyLayer)
+        );
+        assert_eq!(
+            FlatGenerator::parse("0*stone"),
+            Err(ParseFlatError::InvalidHeight("0".into()))
+        );
+        assert_eq!(
+            FlatGenerator::parse("minecraft:not_a_block"),
+            Err(ParseFlatError::UnknownBlock("minecraft:not_a_block".into()))
+        );
+    }
+
+    #[test]
+    fn generate_flat_and_void() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(WorldGenPlugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        app.world
+            .entity_mut(instance_ent)
+            .insert(FlatGenerator::default());
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        let min_y = instance.min_y();
+        let block = |y| instance.block([5, min_y + y, -7]).map(|b| b.state());
+
+        assert_eq!(block(0), Some(BlockState::BEDROCK));
+        assert_eq!(block(2), Some(BlockState::DIRT));
+        assert_eq!(block(3), Some(BlockState::GRASS_BLOCK));
+        assert_eq!(block(4), Some(BlockState::AIR));
+
+        let void = VoidGenerator::new().with_platform([0, 64, 0], 2, BlockState::STONE);
+        let chunk = void.generate(ChunkPos::new(-1, 0), instance);
+
+        assert_eq!(
+            chunk.block_state(14, (64 - min_y) as usize, 2),
+            BlockState::STONE
+        );
+        assert_eq!(
+            chunk.block_state(13, (64 - min_y) as usize, 2),
+            BlockState::AIR
+        );
+        assert_eq!(
+            chunk.block_state(14, (64 - min_y) as usize, 3),
+            BlockState::AIR
+        );
+    }
+}
@@ -0,0 +1,540 @@
+use std::fmt;
+use std::sync::Arc;
+
+use bevy_ecs::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use valence_protocol::block::{BlockState, PropName, PropValue};
+use valence_protocol::BlockPos;
+
+use crate::instance::Chunk;
+use crate::view::ChunkPos;
+
+/// A component for instance entities which decorates generated chunks with
+/// [`Feature`]s, like trees, ore veins, and lakes.
+///
+/// Features are placed after the terrain of a chunk is generated by the
+/// instance's [`ChunkGenerator`]. Each feature is placed once per chunk, but
+/// can extend into the neighboring chunks. When a chunk is generated, the
+/// features of the chunks around it are placed again and only their blocks
+/// inside the chunk are kept, so features spanning chunks are complete no
+/// matter which chunks are generated first.
+///
+/// [`ChunkGenerator`]: super::ChunkGenerator
+#[derive(Component, Clone, Default)]
+pub struct Features {
+    seed: u64,
+    features: Vec<Arc<dyn Feature>>,
+}
+
+/// A decoration placed in generated chunks. See [`Features`].
+pub trait Feature: Send + Sync + 'static {
+    /// The number of chunks the blocks of the feature can be away from the
+    /// chunk the feature is placed for.
+    fn radius(&self) -> u32 {
+        1
+    }
+
+    /// Places the feature for the chunk at [`FeatureContext::origin`].
+    ///
+    /// Only the blocks in the chunk being generated are written, and reading
+    /// blocks outside of it returns `None`. For features to line up across
+    /// chunks, the random numbers used and the positions written must not
+    /// depend on the blocks read.
+    fn place(&self, ctx: &mut FeatureContext);
+}
+
+/// The view of a chunk being decorated by a [`Feature`].
+pub struct FeatureContext<'a> {
+    origin: ChunkPos,
+    chunk_pos: ChunkPos,
+    chunk: &'a mut Chunk,
+    min_y: i32,
+    surface: &'a dyn Fn(i32, i32) -> Option<(i32, BlockState)>,
+    rng: StdRng,
+}
+
+impl Features {
+    /// Creates an empty set of features. The seed determines where features
+    /// are placed.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            features: vec![],
+        }
+    }
+
+    /// Lakes, coal, iron, and diamond ores, and oak trees.
+    pub fn overworld(seed: u64) -> Self {
+        Self::new(seed)
+            .with(LakeFeature::water())
+            .with(OreFeature::coal())
+            .with(OreFeature::iron())
+            .with(OreFeature::diamond())
+            .with(TreeFeature::oak())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Adds a feature. Features are placed in the order they are added.
+    #[must_use]
+    pub fn with(mut self, feature: impl Feature) -> Self {
+        self.push(feature);
+        self
+    }
+
+    /// Adds a feature. Features are placed in the order they are added.
+    pub fn push(&mut self, feature: impl Feature) {
+        self.features.push(Arc::new(feature));
+    }
+
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// Places the features overlapping the chunk at `pos`. `surface` returns
+    /// the Y coordinate and block of the top of the terrain at a block column,
+    /// as returned by [`ChunkGenerator::surface`].
+    ///
+    /// [`ChunkGenerator::surface`]: super::ChunkGenerator::surface
+    pub fn decorate(
+        &self,
+        pos: ChunkPos,
+        chunk: &mut Chunk,
+        min_y: i32,
+        surface: &dyn Fn(i32, i32) -> Option<(i32, BlockState)>,
+    ) {
+        for (idx, feature) in self.features.iter().enumerate() {
+            let radius = feature.radius() as i32;
+
+            for origin_z in pos.z - radius..=pos.z + radius {
+                for origin_x in pos.x - radius..=pos.x + radius {
+                    let seed = mix(self.seed ^ mix(idx as u64))
+                        ^ mix(origin_x as u64)
+                        ^ mix((origin_z as u64).rotate_left(32));
+
+                    let mut ctx = FeatureContext {
+                        origin: ChunkPos::new(origin_x, origin_z),
+                        chunk_pos: pos,
+                        chunk: &mut *chunk,
+                        min_y,
+                        surface,
+                        rng: StdRng::seed_from_u64(seed),
+                    };
+
+                    feature.place(&mut ctx);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Features")
+            .field("seed", &self.seed)
+            .field("len", &self.features.len())
+            .finish()
+    }
+}
+
+impl FeatureContext<'_> {
+    /// Returns the position of the chunk the feature is placed for. This is
+    /// not necessarily the chunk being generated.
+    pub fn origin(&self) -> ChunkPos {
+        self.origin
+    }
+
+    /// Returns a random number generator seeded by the position of the origin
+    /// chunk and the feature.
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    /// Returns a random block column in the origin chunk.
+    pub fn random_column(&mut self) -> (i32, i32) {
+        let x = self.origin.x * 16 + self.rng.gen_range(0..16);
+        let z = self.origin.z * 16 + self.rng.gen_range(0..16);
+        (x, z)
+    }
+
+    /// Returns the minimum Y coordinate of the instance.
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    /// Returns the Y coordinate and block of the top of the terrain at a block
+    /// column before any features were placed. Works for columns in any chunk,
+    /// but returns `None` if the generator doesn't know the surface.
+    pub fn surface(&self, x: i32, z: i32) -> Option<(i32, BlockState)> {
+        (self.surface)(x, z)
+    }
+
+    /// Gets the block state at a position, or `None` if the position is
+    /// outside of the chunk being generated.
+    pub fn block_state(&self, pos: impl Into<BlockPos>) -> Option<BlockState> {
+        let (x, y, z) = self.offsets(pos.into())?;
+        Some(self.chunk.block_state(x, y, z))
+    }
+
+    /// Sets the block state at a position. Returns `false` and does nothing if
+    /// the position is outside of the chunk being generated.
+    pub fn set_block_state(&mut self, pos: impl Into<BlockPos>, block: BlockState) -> bool {
+        match self.offsets(pos.into()) {
+            Some((x, y, z)) => {
+                self.chunk.set_block_state(x, y, z, block);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn offsets(&self, pos: BlockPos) -> Option<(usize, usize, usize)> {
+        let x = pos.x - self.chunk_pos.x * 16;
+        let y = pos.y - self.min_y;
+        let z = pos.z - self.chunk_pos.z * 16;
+
+        let height = self.chunk.section_count() as i32 * 16;
+
+        ((0..16).contains(&x) && (0..height).contains(&y) && (0..16).contains(&z))
+            .then_some((x as usize, y as usize, z as usize))
+    }
+}
+
+/// Trees with a trunk and a rounded crown of leaves.
+#[derive(Clone, Debug)]
+pub struct TreeFeature {
+    pub log: BlockState,
+    pub leaves: BlockState,
+    /// The range of trunk heights.
+    pub min_height: u32,
+    pub max_height: u32,
+    /// The number of trees tried to be placed in each chunk.
+    pub attempts: u32,
+    /// The blocks trees can grow on.
+    pub soil: Vec<BlockState>,
+}
+
+impl TreeFeature {
+    pub fn oak() -> Self {
+        Self {
+            log: BlockState::OAK_LOG,
+            leaves: BlockState::OAK_LEAVES.set(PropName::Persistent, PropValue::True),
+            min_height: 4,
+            max_height: 6,
+            attempts: 2,
+            soil: vec![BlockState::GRASS_BLOCK, BlockState::DIRT],
+        }
+    }
+
+    #[must_use]
+    pub fn with_height(mut self, min: u32, max: u32) -> Self {
+        self.min_height = min;
+        self.max_height = max;
+        self
+    }
+
+    #[must_use]
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+}
+
+impl Feature for TreeFeature {
+    fn place(&self, ctx: &mut FeatureContext) {
+        for _ in 0..self.attempts {
+            let (x, z) = ctx.random_column();
+            let height = ctx.rng().gen_range(self.min_height..=self.max_height) as i32;
+
+            let Some((y, soil)) = ctx.surface(x, z) else {
+                continue
+            };
+
+            if !self.soil.contains(&soil) {
+                continue;
+            }
+
+            for dy in height - 2..=height + 1 {
+                let radius: i32 = if dy < height { 2 } else { 1 };
+
+                for dz in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let corner = dx.abs() == radius && dz.abs() == radius;
+                        let pos = [x + dx, y + 1 + dy, z + dz];
+
+                        if corner && dy >= height - 1 {
+                            continue;
+                        }
+
+                        if matches!(ctx.block_state(pos), Some(b) if b.is_air()) {
+                            ctx.set_block_state(pos, self.leaves);
+                        }
+                    }
+                }
+            }
+
+            ctx.set_block_state([x, y, z], BlockState::DIRT);
+
+            for dy in 0..height {
+                ctx.set_block_state([x, y + 1 + dy, z], self.log);
+            }
+        }
+    }
+}
+
+/// Veins of ore replacing underground blocks.
+#[derive(Clone, Debug)]
+pub struct OreFeature {
+    /// Pairs of blocks to replace and the ore to replace them with.
+    pub targets: Vec<(BlockState, BlockState)>,
+    /// The number of veins in each chunk.
+    pub veins: u32,
+    /// The number of blocks in each vein, some of which may overlap.
+    pub size: u32,
+    /// The range of Y coordinates veins start in.
+    pub min_y: i32,
+    pub max_y: i32,
+}
+
+impl OreFeature {
+    pub fn new(
+        targets: impl Into<Vec<(BlockState, BlockState)>>,
+        veins: u32,
+        size: u32,
+        min_y: i32,
+        max_y: i32,
+    ) -> Self {
+        Self {
+            targets: targets.into(),
+            veins,
+            size,
+            min_y,
+            max_y,
+        }
+    }
+
+    pub fn coal() -> Self {
+        Self::new(
+            [
+                (BlockState::STONE, BlockState::COAL_ORE),
+                (BlockState::DEEPSLATE, BlockState::DEEPSLATE_COAL_ORE),
+            ],
+            16,
+            12,
+            0,
+            128,
+        )
+    }
+
+    pub fn iron() -> Self {
+        Self::new(
+            [
+                (BlockState::STONE, BlockState::IRON_ORE),
+                (BlockState::DEEPSLATE, BlockState::DEEPSLATE_IRON_ORE),
+            ],
+            10,
+            8,
+            -32,
+            72,
+        )
+    }
+
+    pub fn diamond() -> Self {
+        Self::new(
+            [
+                (BlockState::STONE, BlockState::DIAMOND_ORE),
+                (BlockState::DEEPSLATE, BlockState::DEEPSLATE_DIAMOND_ORE),
+            ],
+            2,
+            6,
+            -64,
+            16,
+        )
+    }
+}
+
+impl Feature for OreFeature {
+    fn place(&self, ctx: &mut FeatureContext) {
+        for _ in 0..self.veins {
+            let (mut x, mut z) = ctx.random_column();
+            let mut y = ctx.rng().gen_range(self.min_y..=self.max_y);
+
+            for _ in 0..self.size {
+                if let Some(block) = ctx.block_state([x, y, z]) {
+                    if let Some(&(_, ore)) = self.targets.iter().find(|(b, _)| *b == block) {
+                        ctx.set_block_state([x, y, z], ore);
+                    }
+                }
+
+                x += ctx.rng().gen_range(-1..=1);
+                y += ctx.rng().gen_range(-1..=1);
+                z += ctx.rng().gen_range(-1..=1);
+            }
+        }
+    }
+}
+
+/// Shallow pools of fluid sunk into flat ground.
+#[derive(Clone, Debug)]
+pub struct LakeFeature {
+    pub fluid: BlockState,
+    /// The chance of a lake being placed in each chunk, between 0 and 1.
+    pub chance: f64,
+    /// The largest horizontal radius of lakes, which must be at least 2.
+    pub radius: u32,
+    /// The depth of lakes in the middle.
+    pub depth: u32,
+    /// The blocks lakes can be sunk into.
+    pub soil: Vec<BlockState>,
+}
+
+impl LakeFeature {
+    pub fn water() -> Self {
+        Self {
+            fluid: BlockState::WATER,
+            chance: 0.06,
+            radius: 6,
+            depth: 3,
+            soil: vec![BlockState::GRASS_BLOCK],
+        }
+    }
+
+    #[must_use]
+    pub fn with_chance(mut self, chance: f64) -> Self {
+        self.chance = chance;
+        self
+    }
+}
+
+impl Feature for LakeFeature {
+    fn place(&self, ctx: &mut FeatureContext) {
+        if !ctx.rng().gen_bool(self.chance) {
+            return;
+        }
+
+        let (x, z) = ctx.random_column();
+        let radius_x = ctx.rng().gen_range(2..=self.radius as i32);
+        let radius_z = ctx.rng().gen_range(2..=self.radius as i32);
+
+        let Some((y, soil)) = ctx.surface(x, z) else {
+            return
+        };
+
+        if !self.soil.contains(&soil) {
+            return;
+        }
+
+        // Only place lakes on ground flat enough to hold the fluid.
+        for (edge_x, edge_z) in [
+            (x - radius_x, z),
+            (x + radius_x, z),
+            (x, z - radius_z),
+            (x, z + radius_z),
+        ] {
+            match ctx.surface(edge_x, edge_z) {
+                Some((edge_y, _)) if (y..=y + 2).contains(&edge_y) => {}
+                _ => return,
+            }
+        }
+
+        for dz in -radius_z..=radius_z {
+            for dx in -radius_x..=radius_x {
+                let dist =
+                    (dx as f64 / radius_x as f64).powi(2) + (dz as f64 / radius_z as f64).powi(2);
+
+                if dist > 1.0 {
+                    continue;
+                }
+
+                let depth = ((1.0 - dist).sqrt() * self.depth as f64).ceil() as i32;
+
+                for dy in 0..depth {
+                    ctx.set_block_state([x + dx, y - dy, z + dz], self.fluid);
+                }
+
+                // Clear plants and snow above the lake.
+                for dy in 1..=3 {
+                    ctx.set_block_state([x + dx, y + dy, z + dz], BlockState::AIR);
+                }
+            }
+        }
+    }
+}
+
+fn mix(mut h: u64) -> u64 {
+    h = h.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A line of stone reaching 4 blocks into the next chunk.
+    struct Line;
+
+    impl Feature for Line {
+        fn place(&self, ctx: &mut FeatureContext) {
+            let origin = ctx.origin();
+
+            for x in 10..20 {
+                ctx.set_block_state([origin.x * 16 + x, 5, origin.z * 16], BlockState::STONE);
+            }
+        }
+    }
+
+    #[test]
+    fn features_span_chunks() {
+        let features = Features::new(0).with(Line);
+        let mut chunk = Chunk::new(1);
+
+        features.decorate(ChunkPos::new(1, 0), &mut chunk, 0, &|_, _| None);
+
+        // From the chunk to the west.
+        assert_eq!(chunk.block_state(3, 5, 0), BlockState::STONE);
+        assert_eq!(chunk.block_state(4, 5, 0), BlockState::AIR);
+        // From the chunk itself.
+        assert_eq!(chunk.block_state(10, 5, 0), BlockState::STONE);
+        assert_eq!(chunk.block_state(15, 5, 0), BlockState::STONE);
+        assert_eq!(chunk.block_state(9, 5, 0), BlockState::AIR);
+    }
+
+    #[test]
+    fn trees_are_deterministic() {
+        let features = Features::new(7).with(TreeFeature::oak().with_attempts(16));
+        let surface = |_: i32, _: i32| Some((10, BlockState::GRASS_BLOCK));
+
+        let generate = || {
+            let mut chunk = Chunk::new(4);
+            features.decorate(ChunkPos::new(2, -3), &mut chunk, 0, &surface);
+            chunk
+        };
+
+        let (a, b) = (generate(), generate());
+        let mut logs = 0;
+
+        for y in 0..64 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    assert_eq!(a.block_state(x, y, z), b.block_state(x, y, z));
+
+                    if a.block_state(x, y, z) == BlockState::OAK_LOG {
+                        assert!(y > 10);
+                        logs += 1;
+                    }
+                }
+            }
+        }
+
+        assert!(logs > 0);
+    }
+}
@@ -0,0 +1,415 @@
+use bevy_ecs::prelude::*;
+use noise::{NoiseFn, SuperSimplex};
+use valence_protocol::block::{BlockState, PropName, PropValue};
+
+use super::{fill_biomes, ChunkGenerator};
+use crate::biome::BiomeId;
+use crate::instance::{Chunk, Instance};
+use crate::view::ChunkPos;
+
+/// Generates vanilla-like overworld terrain from noise, with oceans and
+/// continents, hills, caves, beaches, snowy peaks, and grass.
+///
+/// The same seed and settings always generate the same terrain. Add
+/// [`Features`] to the instance to decorate the terrain with trees, ores, and
+/// lakes.
+///
+/// [`Features`]: super::Features
+#[derive(Component, Clone, Debug)]
+pub struct OverworldGenerator {
+    seed: u32,
+    /// The Y coordinate below which empty space is filled with water.
+    pub sea_level: i32,
+    /// The average Y coordinate of the ground on continents.
+    pub base_height: i32,
+    /// The largest height in blocks hills and mountains rise above the base
+    /// height.
+    pub hill_height: f64,
+    /// The approximate width in blocks of continents and oceans.
+    pub continent_scale: f64,
+    /// The Y coordinate above which grass is covered in snow.
+    pub snow_line: i32,
+    pub caves: bool,
+    pub biome: BiomeId,
+    noises: Noises,
+}
+
+#[derive(Clone, Debug)]
+struct Noises {
+    continent: SuperSimplex,
+    hilly: SuperSimplex,
+    density: SuperSimplex,
+    cave_a: SuperSimplex,
+    cave_b: SuperSimplex,
+    grass: SuperSimplex,
+}
+
+/// The vertical range of a column in which terrain blends into air.
+struct Column {
+    lower: f64,
+    upper: f64,
+}
+
+impl OverworldGenerator {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            sea_level: 63,
+            base_height: 68,
+            hill_height: 100.0,
+            continent_scale: 1000.0,
+            snow_line: 150,
+            caves: true,
+            biome: BiomeId::default(),
+            noises: Noises {
+                continent: SuperSimplex::new(seed),
+                hilly: SuperSimplex::new(seed.wrapping_add(1)),
+                density: SuperSimplex::new(seed.wrapping_add(2)),
+                cave_a: SuperSimplex::new(seed.wrapping_add(3)),
+                cave_b: SuperSimplex::new(seed.wrapping_add(4)),
+                grass: SuperSimplex::new(seed.wrapping_add(5)),
+            },
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    #[must_use]
+    pub fn with_sea_level(mut self, y: i32) -> Self {
+        self.sea_level = y;
+        self
+    }
+
+    #[must_use]
+    pub fn with_base_height(mut self, y: i32) -> Self {
+        self.base_height = y;
+        self
+    }
+
+    #[must_use]
+    pub fn with_hill_height(mut self, height: f64) -> Self {
+        self.hill_height = height;
+        self
+    }
+
+    #[must_use]
+    pub fn with_continent_scale(mut self, scale: f64) -> Self {
+        self.continent_scale = scale;
+        self
+    }
+
+    #[must_use]
+    pub fn with_snow_line(mut self, y: i32) -> Self {
+        self.snow_line = y;
+        self
+    }
+
+    #[must_use]
+    pub fn with_caves(mut self, enabled: bool) -> Self {
+        self.caves = enabled;
+        self
+    }
+
+    #[must_use]
+    pub fn with_biome(mut self, biome: BiomeId) -> Self {
+        self.biome = biome;
+        self
+    }
+
+    fn column(&self, x: i32, z: i32) -> Column {
+        let p = [x as f64, z as f64];
+
+        // Oceans are below 0.4 and continents above 0.5, with coasts in between.
+        let continent = fbm(
+            &self.noises.continent,
+            p.map(|c| c / self.continent_scale),
+            4,
+        );
+        let land = smoothstep(0.4, 0.5, continent);
+
+        let ground = lerp((self.sea_level - 24) as f64, self.base_height as f64, land);
+        let hilly = lerp(0.1, 1.0, noise01(&self.noises.hilly, p.map(|c| c / 400.0))).powi(2);
+        let hills = self.hill_height * hilly * land;
+
+        Column {
+            lower: ground - hills * 0.25,
+            upper: ground + hills,
+        }
+    }
+
+    fn is_solid(&self, column: &Column, x: i32, y: i32, z: i32) -> bool {
+        let y = y as f64;
+
+        if y <= column.lower {
+            return true;
+        } else if y >= column.upper {
+            return false;
+        }
+
+        let density = 1.0 - (y - column.lower) / (column.upper - column.lower);
+        let p = [x as f64, y, z as f64].map(|c| c / 100.0);
+
+        fbm(&self.noises.density, p, 4) < density
+    }
+
+    fn is_cave(&self, x: i32, y: i32, z: i32) -> bool {
+        // Tunnels form where the zero surfaces of two noises intersect.
+        let p = [x as f64 / 40.0, y as f64 / 25.0, z as f64 / 40.0];
+
+        self.noises.cave_a.get(p).abs() < 0.08 && self.noises.cave_b.get(p).abs() < 0.08
+    }
+
+    /// Returns the Y coordinate of the highest solid block in the column
+    /// below `end`, not counting caves.
+    fn top(&self, column: &Column, x: i32, z: i32, min_y: i32, end: i32) -> Option<i32> {
+        (min_y..end.min(column.upper.ceil() as i32))
+            .rev()
+            .find(|&y| self.is_solid(column, x, y, z))
+    }
+
+    /// Returns the blocks at the top of the terrain and below it for a column
+    /// whose top is at `y`.
+    fn surface_blocks(&self, y: i32) -> (BlockState, BlockState) {
+        if y < self.sea_level - 4 {
+            (BlockState::GRAVEL, BlockState::GRAVEL)
+        } else if y < self.sea_level + 2 {
+            (BlockState::SAND, BlockState::SAND)
+        } else {
+            (BlockState::GRASS_BLOCK, BlockState::DIRT)
+        }
+    }
+
+    /// Returns a deterministic random number in `[0, 1)` for a position.
+    fn random(&self, x: i32, y: i32, z: i32) -> f64 {
+        let mut h = self.seed as u64
+            ^ (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+            ^ (z as u64).wrapping_mul(0x1656_67b1_9e37_79f9);
+
+        h ^= h >> 30;
+        h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h ^= h >> 27;
+        h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+
+        (h >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Fills in the terrain, water, and surface blocks of a column.
+    fn fill_column(&self, chunk: &mut Chunk, min_y: i32, x: i32, z: i32) {
+        let (offset_x, offset_z) = (x.rem_euclid(16) as usize, z.rem_euclid(16) as usize);
+        let height = chunk.section_count() as i32 * 16;
+        let column = self.column(x, z);
+
+        // Everything above this is air.
+        let start = (column.upper.ceil() as i32).max(self.sea_level) - min_y;
+
+        let mut top = None;
+        let mut filler = None;
+        let mut depth = 0;
+
+        for offset_y in (0..start.clamp(0, height)).rev() {
+            let y = min_y + offset_y;
+
+            if !self.is_solid(&column, x, y, z) {
+                if y < self.sea_level {
+                    chunk.set_block_state(offset_x, offset_y as usize, offset_z, BlockState::WATER);
+                }
+
+                filler = None;
+                continue;
+            }
+
+            let top = *top.get_or_insert(y);
+
+            let block = if offset_y == 0
+                || (offset_y < 5 && self.random(x, y, z) < (5 - offset_y) as f64 / 5.0)
+            {
+                BlockState::BEDROCK
+            } else if let Some(block) = filler.filter(|_| depth > 0) {
+                depth -= 1;
+                block
+            } else if y == top {
+                depth = 3 + (self.random(x, 0, z) * 2.0) as i32;
+
+                let (surface, below) = self.surface_blocks(y);
+                filler = Some(below);
+                surface
+            } else if y < 0 || (y < 8 && self.random(x, y, z) < (8 - y) as f64 / 8.0) {
+                filler = Some(BlockState::STONE);
+                BlockState::DEEPSLATE
+            } else {
+                filler = Some(BlockState::STONE);
+                BlockState::STONE
+            };
+
+            // Keep the surface block for features placed on it, and the sea floor
+            // so that oceans don't leak into caves.
+            let protected = y == top || (top < self.sea_level && y > top - 5);
+
+            if self.caves && block != BlockState::BEDROCK && !protected && self.is_cave(x, y, z) {
+                continue;
+            }
+
+            chunk.set_block_state(offset_x, offset_y as usize, offset_z, block);
+        }
+    }
+
+    /// Places plants and snow on top of the column.
+    fn decorate_column(&self, chunk: &mut Chunk, min_y: i32, x: i32, z: i32) {
+        let (offset_x, offset_z) = (x.rem_euclid(16) as usize, z.rem_euclid(16) as usize);
+        let height = chunk.section_count() * 16;
+
+        let Some(top) = chunk.top_block_y(offset_x, offset_z) else {
+            return
+        };
+
+        if top + 2 >= height
+            || chunk.block_state(offset_x, top, offset_z) != BlockState::GRASS_BLOCK
+        {
+            return;
+        }
+
+        let y = top as i32 + 1 + min_y;
+
+        if y > self.snow_line {
+            chunk.set_block_state(offset_x, top + 1, offset_z, BlockState::SNOW);
+            return;
+        }
+
+        let density = fbm(&self.noises.grass, [x, y, z].map(|c| c as f64 / 5.0), 4);
+
+        if density > 0.7 && chunk.block_state(offset_x, top + 2, offset_z).is_air() {
+            let lower = BlockState::TALL_GRASS.set(PropName::Half, PropValue::Lower);
+            let upper = BlockState::TALL_GRASS.set(PropName::Half, PropValue::Upper);
+
+            chunk.set_block_state(offset_x, top + 1, offset_z, lower);
+            chunk.set_block_state(offset_x, top + 2, offset_z, upper);
+        } else if density > 0.55 {
+            chunk.set_block_state(offset_x, top + 1, offset_z, BlockState::GRASS);
+        }
+    }
+}
+
+impl ChunkGenerator for OverworldGenerator {
+    fn generate(&self, pos: ChunkPos, instance: &Instance) -> Chunk {
+        let mut chunk = Chunk::new(instance.section_count());
+        let min_y = instance.min_y();
+
+        fill_biomes(&mut chunk, self.biome);
+
+        for offset_z in 0..16 {
+            for offset_x in 0..16 {
+                self.fill_column(
+                    &mut chunk,
+                    min_y,
+                    pos.x * 16 + offset_x,
+                    pos.z * 16 + offset_z,
+                );
+            }
+        }
+
+        for offset_z in 0..16 {
+            for offset_x in 0..16 {
+                self.decorate_column(
+                    &mut chunk,
+                    min_y,
+                    pos.x * 16 + offset_x,
+                    pos.z * 16 + offset_z,
+                );
+            }
+        }
+
+        chunk
+    }
+
+    fn surface(&self, x: i32, z: i32, instance: &Instance) -> Option<(i32, BlockState)> {
+        let min_y = instance.min_y();
+        let end = min_y + instance.section_count() as i32 * 16;
+
+        let y = self.top(&self.column(x, z), x, z, min_y, end)?;
+
+        Some((y, self.surface_blocks(y).0))
+    }
+}
+
+/// Fractal noise with values in `[0, 1]`.
+fn fbm<const N: usize>(noise: &SuperSimplex, p: [f64; N], octaves: u32) -> f64
+where
+    SuperSimplex: NoiseFn<f64, N>,
+{
+    let mut freq = 1.0;
+    let mut amp = 1.0;
+    let mut amp_sum = 0.0;
+    let mut sum = 0.0;
+
+    for _ in 0..octaves {
+        sum += noise01(noise, p.map(|c| c * freq)) * amp;
+        amp_sum += amp;
+
+        freq *= 2.0;
+        amp *= 0.5;
+    }
+
+    sum / amp_sum
+}
+
+fn noise01<const N: usize>(noise: &SuperSimplex, p: [f64; N]) -> f64
+where
+    SuperSimplex: NoiseFn<f64, N>,
+{
+    (noise.get(p) + 1.0) / 2.0
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a * (1.0 - t) + b * t
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn overworld_is_deterministic() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+
+        let generator = OverworldGenerator::new(42);
+        let pos = ChunkPos::new(3, -2);
+
+        let a = generator.generate(pos, instance);
+        let b = generator.clone().generate(pos, instance);
+
+        for z in 0..16 {
+            for x in 0..16 {
+                assert_eq!(a.top_block_y(x, z), b.top_block_y(x, z));
+                assert_eq!(a.block_state(x, 0, z), BlockState::BEDROCK);
+
+                let top = a.top_block_y(x, z).unwrap() as i32 + instance.min_y();
+                assert!(top >= generator.sea_level - 1);
+
+                let world_x = pos.x * 16 + x as i32;
+                let world_z = pos.z * 16 + z as i32;
+                let (surface, _) = generator.surface(world_x, world_z, instance).unwrap();
+                let offset_y = (surface - instance.min_y()) as usize;
+
+                assert!(surface <= top);
+                assert!(!a.block_state(x, offset_y, z).is_air());
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Damage outside of world borders.
+//!
+//! The [`ZoneDamagePlugin`] periodically damages clients and
+//! [`ZoneDamageTarget`] entities which are outside the [`WorldBorder`] that
+//! applies to them, like vanilla does with its world border. Clients are
+//! checked against their own border if they have one, so the zone of a battle
+//! royale can be given only to its participants.
+//!
+//! Valence does not keep track of health, so the damage is reported with
+//! [`ZoneDamaged`] events for the game to apply. The plugin plays the hurt
+//! animation of damaged entities and shows clients a warning in the action
+//! bar.
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::zone_damage::{ZoneDamagePlugin, ZoneDamaged};
+//!
+//! let mut app = App::new();
+//! app.add_plugin(ZoneDamagePlugin);
+//!
+//! fn apply_zone_damage(mut events: EventReader<ZoneDamaged>) {
+//!     for event in events.iter() {
+//!         println!("{:?} took {} damage", event.entity, event.amount);
+//!     }
+//! }
+//! ```
+
+use bevy_app::{App, CoreStage, Plugin};
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use valence_protocol::text::{Color, Text, TextFormat};
+
+use crate::client::Client;
+use crate::entity::{EntityAnimation, McEntity};
+use crate::instance::Instance;
+use crate::pause::gameplay_systems;
+use crate::server::Server;
+use crate::world_border::WorldBorder;
+
+/// Adds the [`ZoneDamageSettings`] resource, the [`ZoneDamaged`] event, and
+/// the system damaging entities outside of world borders.
+pub struct ZoneDamagePlugin;
+
+impl Plugin for ZoneDamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ZoneDamageSettings>()
+            .add_event::<ZoneDamaged>()
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                gameplay_systems().with_system(damage_outside_zone.before("valence_core")),
+            );
+    }
+}
+
+/// Configures the damage dealt by the [`ZoneDamagePlugin`]. The defaults
+/// match vanilla, except that damage is dealt once a second.
+#[derive(Resource, Clone, Debug)]
+pub struct ZoneDamageSettings {
+    /// The damage dealt per block an entity is outside of the safe zone.
+    pub damage_per_block: f32,
+    /// The distance in blocks outside of the border in which entities are not
+    /// damaged.
+    pub safe_zone: f64,
+    /// The number of ticks between two hits.
+    pub interval: i64,
+    /// The message shown in the action bar of clients taking damage.
+    pub warning: Option<Text>,
+}
+
+impl Default for ZoneDamageSettings {
+    fn default() -> Self {
+        Self {
+            damage_per_block: 0.2,
+            safe_zone: 5.0,
+            interval: 20,
+            warning: Some("You are outside of the zone!".color(Color::RED)),
+        }
+    }
+}
+
+/// A component for non-client entities which are damaged outside the world
+/// border of their instance.
+#[derive(Component, Copy, Clone, Default, Debug)]
+pub struct ZoneDamageTarget;
+
+/// Sent when a client or [`ZoneDamageTarget`] entity is damaged for being
+/// outside of its world border.
+#[derive(Clone, Debug)]
+pub struct ZoneDamaged {
+    pub entity: Entity,
+    pub amount: f32,
+    /// The distance from the entity to the border.
+    pub distance: f64,
+}
+
+impl ZoneDamageSettings {
+    /// Returns the damage dealt at `pos`, if any.
+    fn damage(&self, border: &WorldBorder, pos: DVec3) -> Option<(f32, f64)> {
+        let distance = -border.distance_to_edge(pos);
+        let outside = distance - self.safe_zone;
+
+        if outside <= 0.0 || self.damage_per_block <= 0.0 {
+            return None;
+        }
+
+        // Like vanilla, at least one point of damage is dealt.
+        let amount = (outside as f32 * self.damage_per_block).floor().max(1.0);
+        Some((amount, distance))
+    }
+}
+
+fn damage_outside_zone(
+    server: Res<Server>,
+    settings: Res<ZoneDamageSettings>,
+    mut clients: Query<(
+        Entity,
+        &mut Client,
+        Option<&mut McEntity>,
+        Option<&WorldBorder>,
+    )>,
+    mut targets: Query<(Entity, &mut McEntity), (With<ZoneDamageTarget>, Without<Client>)>,
+    instances: Query<&WorldBorder, With<Instance>>,
+    mut events: EventWriter<ZoneDamaged>,
+) {
+    if settings.interval <= 0 || server.current_tick() % settings.interval != 0 {
+        return;
+    }
+
+    for (entity, mut client, mc_entity, own_border) in &mut clients {
+        let Some(border) = own_border.or_else(|| instances.get(client.instance()).ok()) else {
+            continue
+        };
+
+        let Some((amount, distance)) = settings.damage(border, client.position()) else {
+            continue
+        };
+
+        if let Some(mut mc_entity) = mc_entity {
+            mc_entity.trigger_animation(EntityAnimation::Damage);
+        }
+
+        if let Some(warning) = &settings.warning {
+            client.set_action_bar(warning.clone());
+        }
+
+        events.send(ZoneDamaged {
+            entity,
+            amount,
+            distance,
+        });
+    }
+
+    for (entity, mut mc_entity) in &mut targets {
+        let Ok(border) = instances.get(mc_entity.instance()) else {
+            continue
+        };
+
+        let Some((amount, distance)) = settings.damage(border, mc_entity.position()) else {
+            continue
+        };
+
+        mc_entity.trigger_animation(EntityAnimation::Damage);
+
+        events.send(ZoneDamaged {
+            entity,
+            amount,
+            distance,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityKind;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn damage_outside_of_border() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        app.add_plugin(ZoneDamagePlugin);
+        app.world.resource_mut::<ZoneDamageSettings>().interval = 1;
+
+        let instance = app.world.get::<Client>(client_ent).unwrap().instance();
+        app.world
+            .entity_mut(instance)
+            .insert(WorldBorder::new([0.0, 0.0], 20.0));
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([30.0, 64.0, 0.0]);
+
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance);
+        zombie.set_position([0.0, 64.0, 0.0]);
+        let zombie = app.world.spawn((zombie, ZoneDamageTarget)).id();
+
+        app.update();
+
+        let events = app.world.resource::<Events<ZoneDamaged>>();
+        let damaged: Vec<_> = events.get_reader().iter(events).cloned().collect();
+        assert_eq!(damaged.len(), 1);
+        assert_eq!(damaged[0].entity, client_ent);
+        assert_eq!(damaged[0].distance, 20.0);
+        assert_eq!(damaged[0].amount, 3.0);
+        assert!(damaged.iter().all(|e| e.entity != zombie));
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::{decode_chunk, AnvilChunk, ChunkLocation, ReadChunkError, SECTOR_SIZE};
+
+/// An async version of [`AnvilWorld`] using tokio for file I/O.
+///
+/// Reads take `&self`, so a single world can be shared between tasks (e.g.
+/// in an [`Arc`]). Reads of chunks in different region files run
+/// concurrently, while reads of chunks in the same region file wait for each
+/// other.
+///
+/// [`AnvilWorld`]: crate::AnvilWorld
+#[derive(Debug)]
+pub struct AsyncAnvilWorld {
+    /// Terrain in the "region" subdirectory of the world root.
+    terrain: AsyncRegionFolder,
+    /// Entities in the "entities" subdirectory of the world root.
+    entities: AsyncRegionFolder,
+    /// Points of interest in the "poi" subdirectory of the world root.
+    poi: AsyncRegionFolder,
+}
+
+#[derive(Debug)]
+struct AsyncRegionFolder {
+    /// Path to the directory containing the region files.
+    root: PathBuf,
+    /// Maps region (x, z) positions to region files. A region is `None` until
+    /// its file has been opened.
+    regions: Mutex<BTreeMap<(i32, i32), Arc<tokio::sync::Mutex<Option<AsyncRegion>>>>>,
+}
+
+#[derive(Debug)]
+struct AsyncRegion {
+    file: File,
+    /// The first 8 KiB in the file.
+    header: Box<[u8; SECTOR_SIZE * 2]>,
+}
+
+impl AsyncAnvilWorld {
+    pub fn new(world_root: impl Into<PathBuf>) -> Self {
+        let world_root = world_root.into();
+
+        Self {
+            terrain: AsyncRegionFolder::new(world_root.join("region")),
+            entities: AsyncRegionFolder::new(world_root.join("entities")),
+            poi: AsyncRegionFolder::new(world_root.join("poi")),
+        }
+    }
+
+    /// Reads a chunk from the file system with the given chunk coordinates. If
+    /// no chunk exists at the position, then `None` is returned.
+    ///
+    /// The chunk data is decompressed and decoded on the calling task after
+    /// the region file has been released for other reads.
+    pub async fn read_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<AnvilChunk>, ReadChunkError> {
+        self.terrain.read_chunk(chunk_x, chunk_z).await
+    }
+
+    /// Reads the entities stored in the chunk at the given chunk coordinates.
+    /// See [`AnvilWorld::read_entities`].
+    ///
+    /// [`AnvilWorld::read_entities`]: crate::AnvilWorld::read_entities
+    pub async fn read_entities(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<AnvilChunk>, ReadChunkError> {
+        self.entities.read_chunk(chunk_x, chunk_z).await
+    }
+
+    /// Reads the points of interest stored in the chunk at the given chunk
+    /// coordinates. See [`AnvilWorld::read_poi`].
+    ///
+    /// [`AnvilWorld::read_poi`]: crate::AnvilWorld::read_poi
+    pub async fn read_poi(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<AnvilChunk>, ReadChunkError> {
+        self.poi.read_chunk(chunk_x, chunk_z).await
+    }
+}
+
+impl AsyncRegionFolder {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            regions: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    async fn read_chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<AnvilChunk>, ReadChunkError> {
+        let region_x = chunk_x.div_euclid(32);
+        let region_z = chunk_z.div_euclid(32);
+
+        let slot = self
+            .regions
+            .lock()
+            .unwrap()
+            .entry((region_x, region_z))
+            .or_default()
+            .clone();
+
+        let mut guard = slot.lock().await;
+
+        let region = match &mut *guard {
+            Some(region) => region,
+            empty => {
+                // Load the region file if it exists. Otherwise, the chunk is considered absent.
+
+                let path = self.root.join(format!("r.{region_x}.{region_z}.mca"));
+
+                let mut file = match File::open(path).await {
+                    Ok(file) => file,
+                    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                };
+
+                let mut header = Box::new([0; SECTOR_SIZE * 2]);
+
+                file.read_exact(&mut header[..]).await?;
+
+                empty.insert(AsyncRegion { file, header })
+            }
+        };
+
+        let Some(location) = ChunkLocation::new(&region.header, chunk_x, chunk_z)? else {
+            return Ok(None)
+        };
+
+        // Seek to the beginning of the chunk's data.
+        region.file.seek(SeekFrom::Start(location.offset)).await?;
+
+        let exact_chunk_size = region.file.read_u32().await? as usize;
+
+        location.check_size(exact_chunk_size)?;
+
+        let mut data_buf = vec![0; exact_chunk_size].into_boxed_slice();
+        region.file.read_exact(&mut data_buf).await?;
+
+        // Let other reads of this region continue while the chunk is decoded.
+        drop(guard);
+
+        decode_chunk(&data_buf, location.timestamp).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use valence_nbt::{compound, to_binary_writer};
+
+    use super::*;
+    use crate::AnvilWorld;
+
+    /// Writes a region file with one uncompressed chunk at the region's (1, 2)
+    /// position.
+    fn write_region(root: &std::path::Path) -> AnvilChunk {
+        let chunk = AnvilChunk {
+            data: compound! { "DataVersion" => 3218, "Status" => "full" },
+            timestamp: 12345,
+        };
+
+        let mut nbt = vec![];
+        to_binary_writer(&mut nbt, &chunk.data, "").unwrap();
+
+        let mut file = vec![0; SECTOR_SIZE * 2];
+        let idx = 1 + 2 * 32;
+        // Sector offset of 2 and sector count of 1.
+        file[idx * 4..idx * 4 + 4].copy_from_slice(&(2_u32 << 8 | 1).to_be_bytes());
+        file[idx * 4 + SECTOR_SIZE..idx * 4 + SECTOR_SIZE + 4]
+            .copy_from_slice(&chunk.timestamp.to_be_bytes());
+        file.extend_from_slice(&(nbt.len() as u32 + 1).to_be_bytes());
+        // Uncompressed
+        file.push(3);
+        file.extend_from_slice(&nbt);
+        file.resize(SECTOR_SIZE * 3, 0);
+
+        fs::create_dir_all(root.join("region")).unwrap();
+        fs::write(root.join("region/r.-1.0.mca"), file).unwrap();
+
+        chunk
+    }
+
+    #[tokio::test]
+    async fn async_read_matches_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let expected = write_region(dir.path());
+
+        let world = Arc::new(AsyncAnvilWorld::new(dir.path()));
+
+        let tasks = [(-31, 2), (-32, 0), (5, 5)].map(|(x, z)| {
+            let world = world.clone();
+            tokio::spawn(async move { world.read_chunk(x, z).await.unwrap() })
+        });
+
+        let mut results = vec![];
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+
+        assert_eq!(results, [Some(expected.clone()), None, None]);
+
+        let mut sync_world = AnvilWorld::new(dir.path());
+        assert_eq!(sync_world.read_chunk(-31, 2).unwrap(), Some(expected));
+    }
+}
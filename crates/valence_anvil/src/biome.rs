@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value as Json;
+use thiserror::Error;
+use valence::biome::{
+    Biome, BiomeAdditionsSound, BiomeGrassColorModifier, BiomeId, BiomeMoodSound, BiomeMusic,
+    BiomeParticle, BiomePrecipitation,
+};
+use valence::protocol::Ident;
+use valence::server::SharedServer;
+use valence_nbt::{List, Value};
+
+use crate::LevelDat;
+
+/// Maps the biome names found in Anvil chunks to the biomes registered on the
+/// server, so that [`to_valence`] can be used without writing a biome mapping
+/// function by hand:
+///
+/// ```ignore
+/// let biomes = BiomeMapping::new(&server);
+/// to_valence(&nbt, &mut chunk, 4, |name| biomes.get(name))?;
+/// ```
+///
+/// Biomes which aren't registered on the server are mapped to a fallback
+/// configured with [`BiomeMapping::with_fallback`], or to the default biome
+/// otherwise.
+///
+/// [`to_valence`]: crate::to_valence
+#[derive(Clone, Debug)]
+pub struct BiomeMapping {
+    /// Maps the full names of the server's biomes to their IDs.
+    ids: HashMap<String, BiomeId>,
+    /// Maps the full names of unknown biomes to the biomes used instead.
+    fallbacks: HashMap<String, BiomeId>,
+    default: BiomeId,
+}
+
+impl BiomeMapping {
+    /// Creates a mapping from every biome registered on the server. Unknown
+    /// biomes are mapped to [`BiomeId::default`].
+    pub fn new(server: &SharedServer) -> Self {
+        Self {
+            ids: server
+                .biomes()
+                .map(|(id, biome)| (full_name(biome.name.as_str_ident()), id))
+                .collect(),
+            fallbacks: HashMap::new(),
+            default: BiomeId::default(),
+        }
+    }
+
+    /// Sets the biome used for biomes which aren't registered on the server
+    /// and have no fallback.
+    #[must_use]
+    pub fn with_default(mut self, default: BiomeId) -> Self {
+        self.default = default;
+        self
+    }
+
+    /// Uses the biome `id` for the biome named `name` if it isn't registered
+    /// on the server, e.g. to replace a biome from a data pack with a similar
+    /// vanilla biome.
+    #[must_use]
+    pub fn with_fallback(mut self, name: Ident<&str>, id: BiomeId) -> Self {
+        self.fallbacks.insert(full_name(name), id);
+        self
+    }
+
+    /// Gets the ID of the biome to use for the biome named `name`.
+    pub fn get(&self, name: Ident<&str>) -> BiomeId {
+        let name = full_name(name);
+
+        self.ids
+            .get(&name)
+            .or_else(|| self.fallbacks.get(&name))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Returns the name with its namespace, since `minecraft:` may be left out.
+fn full_name(name: Ident<&str>) -> String {
+    format!("{}:{}", name.namespace(), name.path())
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ReadBiomesError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("failed to parse biome file {path}: {source}")]
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("invalid biome name \"{0}\"")]
+    BadName(String),
+    #[error("field \"{field}\" of biome \"{name}\" is invalid")]
+    BadField { name: String, field: &'static str },
+}
+
+/// Reads the custom biomes defined by the data packs enabled in a world, so
+/// they can be registered with [`ServerPlugin::with_biomes`] before chunks
+/// using them are loaded.
+///
+/// Data packs are read from the `datapacks` subdirectory of `world_root` in
+/// the order they are enabled in `level`. Only data packs stored as
+/// directories are read. Vanilla biomes aren't stored in the world and are not
+/// included.
+///
+/// [`ServerPlugin::with_biomes`]: valence::config::ServerPlugin::with_biomes
+pub fn read_datapack_biomes(
+    world_root: impl AsRef<Path>,
+    level: &LevelDat,
+) -> Result<Vec<Biome>, ReadBiomesError> {
+    let Some(Value::Compound(data_packs)) = level.nbt.get("DataPacks") else {
+        return Ok(vec![]);
+    };
+
+    let Some(Value::List(List::String(enabled))) = data_packs.get("Enabled") else {
+        return Ok(vec![]);
+    };
+
+    let mut biomes: Vec<Biome> = vec![];
+
+    for pack in enabled {
+        let Some(dir_name) = pack.strip_prefix("file/") else {
+            // Built-in data packs such as "vanilla".
+            continue;
+        };
+
+        let data_dir = world_root
+            .as_ref()
+            .join("datapacks")
+            .join(dir_name)
+            .join("data");
+
+        if !data_dir.is_dir() {
+            continue;
+        }
+
+        for namespace in fs::read_dir(data_dir)? {
+            let namespace = namespace?;
+            let Some(namespace_name) = namespace.file_name().to_str().map(str::to_owned) else {
+                continue;
+            };
+
+            let biome_dir = namespace.path().join("worldgen").join("biome");
+
+            let mut files = vec![];
+            collect_json_files(&biome_dir, &biome_dir, &mut files)?;
+
+            for (path, biome_path) in files {
+                let name = format!("{namespace_name}:{biome_path}");
+                let biome = read_biome(&path, name)?;
+
+                // Later data packs override biomes of earlier ones.
+                match biomes.iter_mut().find(|b| b.name == biome.name) {
+                    Some(existing) => *existing = biome,
+                    None => biomes.push(biome),
+                }
+            }
+        }
+    }
+
+    Ok(biomes)
+}
+
+/// Collects the JSON files in `dir` and its subdirectories along with their
+/// paths relative to `root` without the extension.
+fn collect_json_files(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<(PathBuf, String)>,
+) -> io::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect_json_files(root, &path, files)?;
+        } else if path.extension().map_or(false, |ext| ext == "json") {
+            let Ok(relative) = path
+                .with_extension("")
+                .strip_prefix(root)
+                .map(Path::to_owned)
+            else {
+                continue;
+            };
+
+            let relative = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            files.push((path, relative));
+        }
+    }
+
+    Ok(())
+}
+
+fn read_biome(path: &Path, name: String) -> Result<Biome, ReadBiomesError> {
+    let json: Json =
+        serde_json::from_slice(&fs::read(path)?).map_err(|source| ReadBiomesError::Json {
+            path: path.to_owned(),
+            source,
+        })?;
+
+    biome_from_json(name, &json)
+}
+
+/// Converts a biome definition in the JSON format used by data packs to a
+/// [`Biome`]. Missing effects take the values of [`Biome::default`].
+fn biome_from_json(name: String, json: &Json) -> Result<Biome, ReadBiomesError> {
+    let Ok(ident) = Ident::new(name.clone()) else {
+        return Err(ReadBiomesError::BadName(name));
+    };
+
+    let bad = |field: &'static str| ReadBiomesError::BadField {
+        name: name.clone(),
+        field,
+    };
+
+    let color = |effects: &Json, field: &'static str| match effects.get(field) {
+        None => Ok(None),
+        Some(v) => v.as_u64().map(|c| Some(c as u32)).ok_or_else(|| bad(field)),
+    };
+
+    let sound = |v: &Json, field: &'static str| {
+        // Sound events are either a name or an object with a name.
+        v.as_str()
+            .or_else(|| v.get("sound_id").and_then(Json::as_str))
+            .and_then(|s| Ident::new(s.to_owned()).ok())
+            .ok_or_else(|| bad(field))
+    };
+
+    let mut biome = Biome {
+        name: ident,
+        ..Default::default()
+    };
+
+    biome.precipitation = match json.get("precipitation").and_then(Json::as_str) {
+        None | Some("rain") => BiomePrecipitation::Rain,
+        Some("snow") => BiomePrecipitation::Snow,
+        Some("none") => BiomePrecipitation::None,
+        Some(_) => return Err(bad("precipitation")),
+    };
+
+    let Some(effects) = json.get("effects") else {
+        return Ok(biome);
+    };
+
+    if let Some(c) = color(effects, "sky_color")? {
+        biome.sky_color = c;
+    }
+    if let Some(c) = color(effects, "fog_color")? {
+        biome.fog_color = c;
+    }
+    if let Some(c) = color(effects, "water_color")? {
+        biome.water_color = c;
+    }
+    if let Some(c) = color(effects, "water_fog_color")? {
+        biome.water_fog_color = c;
+    }
+    biome.foliage_color = color(effects, "foliage_color")?;
+    biome.grass_color = color(effects, "grass_color")?;
+
+    biome.grass_color_modifier = match effects.get("grass_color_modifier").and_then(Json::as_str) {
+        None | Some("none") => BiomeGrassColorModifier::None,
+        Some("swamp") => BiomeGrassColorModifier::Swamp,
+        Some("dark_forest") => BiomeGrassColorModifier::DarkForest,
+        Some(_) => return Err(bad("grass_color_modifier")),
+    };
+
+    if let Some(v) = effects.get("ambient_sound") {
+        biome.ambient_sound = Some(sound(v, "ambient_sound")?);
+    }
+
+    if let Some(v) = effects.get("music") {
+        biome.music = Some(BiomeMusic {
+            replace_current_music: v
+                .get("replace_current_music")
+                .and_then(Json::as_bool)
+                .unwrap_or(false),
+            sound: sound(v.get("sound").ok_or_else(|| bad("music"))?, "music")?,
+            min_delay: v.get("min_delay").and_then(Json::as_i64).unwrap_or(0) as i32,
+            max_delay: v.get("max_delay").and_then(Json::as_i64).unwrap_or(0) as i32,
+        });
+    }
+
+    if let Some(v) = effects.get("additions_sound") {
+        biome.additions_sound = Some(BiomeAdditionsSound {
+            sound: sound(
+                v.get("sound").ok_or_else(|| bad("additions_sound"))?,
+                "additions_sound",
+            )?,
+            tick_chance: v.get("tick_chance").and_then(Json::as_f64).unwrap_or(0.0),
+        });
+    }
+
+    if let Some(v) = effects.get("mood_sound") {
+        biome.mood_sound = Some(BiomeMoodSound {
+            sound: sound(
+                v.get("sound").ok_or_else(|| bad("mood_sound"))?,
+                "mood_sound",
+            )?,
+            tick_delay: v.get("tick_delay").and_then(Json::as_i64).unwrap_or(0) as i32,
+            offset: v.get("offset").and_then(Json::as_f64).unwrap_or(0.0),
+            block_search_extent: v
+                .get("block_search_extent")
+                .and_then(Json::as_i64)
+                .unwrap_or(0) as i32,
+        });
+    }
+
+    if let Some(v) = effects.get("particle") {
+        let kind = v
+            .get("options")
+            .and_then(|o| o.get("type"))
+            .and_then(Json::as_str)
+            .and_then(|s| Ident::new(s.to_owned()).ok())
+            .ok_or_else(|| bad("particle"))?;
+
+        biome.particle = Some(BiomeParticle {
+            probability: v.get("probability").and_then(Json::as_f64).unwrap_or(0.0) as f32,
+            kind,
+        });
+    }
+
+    Ok(biome)
+}
+
+#[cfg(test)]
+mod tests {
+    use valence::config::ServerPlugin;
+    use valence::protocol::ident;
+    use valence::testing::TestServer;
+    use valence_nbt::compound;
+
+    use super::*;
+
+    #[test]
+    fn datapack_biomes_and_mapping() {
+        let dir = tempfile::tempdir().unwrap();
+        let biome_dir = dir
+            .path()
+            .join("datapacks/my_pack/data/example/worldgen/biome/nested");
+        fs::create_dir_all(&biome_dir).unwrap();
+        fs::write(
+            biome_dir.join("glade.json"),
+            r#"{
+                "precipitation": "snow",
+                "temperature": 0.5,
+                "downfall": 0.5,
+                "effects": {
+                    "sky_color": 123,
+                    "fog_color": 456,
+                    "water_color": 789,
+                    "water_fog_color": 1011,
+                    "grass_color_modifier": "swamp",
+                    "ambient_sound": "minecraft:ambient.cave",
+                    "particle": { "probability": 0.1, "options": { "type": "minecraft:ash" } }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let level = LevelDat::from_nbt(&compound! {
+            "Data" => compound! {
+                "DataPacks" => compound! {
+                    "Enabled" => List::String(vec!["vanilla".into(), "file/my_pack".into()]),
+                },
+            },
+        })
+        .unwrap();
+
+        let biomes = read_datapack_biomes(dir.path(), &level).unwrap();
+
+        assert_eq!(biomes.len(), 1);
+        let glade = &biomes[0];
+        assert_eq!(glade.name, ident!("example:nested/glade"));
+        assert_eq!(glade.precipitation, BiomePrecipitation::Snow);
+        assert_eq!(glade.sky_color, 123);
+        assert_eq!(glade.grass_color_modifier, BiomeGrassColorModifier::Swamp);
+        assert_eq!(glade.ambient_sound, Some(ident!("ambient.cave")));
+        assert_eq!(glade.particle.as_ref().unwrap().kind, ident!("ash"));
+
+        let server = TestServer::with_plugin(
+            ServerPlugin::new(()).with_biomes(vec![Biome::default(), glade.clone()]),
+        );
+        let glade_id = server
+            .server()
+            .biomes()
+            .find(|(_, b)| b.name == glade.name)
+            .unwrap()
+            .0;
+
+        let mapping = BiomeMapping::new(server.server());
+
+        assert_eq!(
+            mapping.get(ident!("example:nested/glade").as_str_ident()),
+            glade_id
+        );
+        assert_eq!(
+            mapping.get(ident!("minecraft:plains").as_str_ident()),
+            BiomeId::default()
+        );
+        assert_eq!(
+            mapping.get(ident!("unknown").as_str_ident()),
+            BiomeId::default()
+        );
+
+        let mapping = mapping
+            .with_default(glade_id)
+            .with_fallback(ident!("forest").as_str_ident(), BiomeId::default());
+
+        assert_eq!(mapping.get(ident!("unknown").as_str_ident()), glade_id);
+        assert_eq!(
+            mapping.get(ident!("minecraft:forest").as_str_ident()),
+            BiomeId::default()
+        );
+    }
+}
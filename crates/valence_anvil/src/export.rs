@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use thiserror::Error;
+use valence::instance::Instance;
+use valence::protocol::{BlockPos, MINECRAFT_VERSION};
+use valence::server::SharedServer;
+use valence_nbt::{compound, Compound, List};
+
+use crate::{from_valence, DATA_VERSION, SECTOR_SIZE};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ExportAnvilError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Nbt(#[from] valence_nbt::Error),
+    #[error("chunk at ({0}, {1}) is too large for a region file")]
+    ChunkTooLarge(i32, i32),
+}
+
+/// A copy of an instance's chunks in Anvil form, which can be written to disk
+/// as a world folder that vanilla can open.
+///
+/// Creating the snapshot requires access to the instance, but writing it does
+/// not. To avoid stalling the server while a large world is written, create
+/// the snapshot in a system and move it to another thread:
+///
+/// ```no_run
+/// use valence::prelude::*;
+/// use valence_anvil::AnvilSnapshot;
+///
+/// fn save(server: Res<Server>, instances: Query<&Instance>) {
+///     for instance in &instances {
+///         let snapshot = AnvilSnapshot::new(instance, &server);
+///
+///         std::thread::spawn(move || snapshot.write("export").unwrap());
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct AnvilSnapshot {
+    /// Chunk NBT data grouped by region position.
+    regions: BTreeMap<(i32, i32), Vec<(i32, i32, Compound)>>,
+    level_name: String,
+    spawn_pos: BlockPos,
+}
+
+impl AnvilSnapshot {
+    /// Takes a snapshot of all chunks in `instance`.
+    ///
+    /// Vanilla uses its own overworld dimension type for the exported world,
+    /// so blocks outside of Y -64..320 are lost if the instance's dimension is
+    /// taller.
+    pub fn new(instance: &Instance, server: &SharedServer) -> Self {
+        let sect_offset = server.dimension(instance.dimension()).min_y.div_euclid(16);
+
+        let mut regions: BTreeMap<_, Vec<_>> = BTreeMap::new();
+
+        for (pos, chunk) in instance.chunks() {
+            let nbt = from_valence(chunk, pos.x, pos.z, sect_offset, |biome| {
+                server.biome(biome).name.clone()
+            });
+
+            regions
+                .entry((pos.x.div_euclid(32), pos.z.div_euclid(32)))
+                .or_default()
+                .push((pos.x, pos.z, nbt));
+        }
+
+        Self {
+            regions,
+            level_name: "Valence".into(),
+            spawn_pos: BlockPos::new(0, 64, 0),
+        }
+    }
+
+    /// Sets the name of the world shown in the singleplayer world list.
+    #[must_use]
+    pub fn with_level_name(mut self, level_name: impl Into<String>) -> Self {
+        self.level_name = level_name.into();
+        self
+    }
+
+    /// Sets the world spawn position.
+    #[must_use]
+    pub fn with_spawn_pos(mut self, spawn_pos: impl Into<BlockPos>) -> Self {
+        self.spawn_pos = spawn_pos.into();
+        self
+    }
+
+    /// Returns the number of chunks in the snapshot.
+    pub fn chunk_count(&self) -> usize {
+        self.regions.values().map(|r| r.len()).sum()
+    }
+
+    /// Writes the snapshot to the world folder at `world_root`, which is
+    /// created if it does not exist. Region files of chunks in the snapshot
+    /// and `level.dat` are overwritten.
+    ///
+    /// The world uses an empty generator, so no terrain is generated outside
+    /// of the exported chunks.
+    pub fn write(&self, world_root: impl AsRef<Path>) -> Result<(), ExportAnvilError> {
+        let world_root = world_root.as_ref();
+        let region_root = world_root.join("region");
+
+        fs::create_dir_all(&region_root)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        for (&(region_x, region_z), chunks) in &self.regions {
+            let path = region_root.join(format!("r.{region_x}.{region_z}.mca"));
+            write_region(&path, chunks, timestamp.as_secs() as u32)?;
+        }
+
+        let level = compound! {
+            "Data" => compound! {
+                "DataVersion" => DATA_VERSION,
+                "version" => 19133,
+                "Version" => compound! {
+                    "Id" => DATA_VERSION,
+                    "Name" => MINECRAFT_VERSION,
+                    "Series" => "main",
+                    "Snapshot" => false,
+                },
+                "LevelName" => self.level_name.clone(),
+                "GameType" => 1,
+                "Difficulty" => 0_i8,
+                "hardcore" => false,
+                "allowCommands" => true,
+                "initialized" => true,
+                "SpawnX" => self.spawn_pos.x,
+                "SpawnY" => self.spawn_pos.y,
+                "SpawnZ" => self.spawn_pos.z,
+                "Time" => 0_i64,
+                "DayTime" => 6000_i64,
+                "LastPlayed" => timestamp.as_millis() as i64,
+                "DataPacks" => compound! {
+                    "Enabled" => List::String(vec!["vanilla".into()]),
+                    "Disabled" => List::End,
+                },
+                "WorldGenSettings" => compound! {
+                    "seed" => 0_i64,
+                    "generate_features" => false,
+                    "bonus_chest" => false,
+                    "dimensions" => compound! {
+                        "minecraft:overworld" => compound! {
+                            "type" => "minecraft:overworld",
+                            "generator" => compound! {
+                                "type" => "minecraft:flat",
+                                "settings" => compound! {
+                                    "biome" => "minecraft:the_void",
+                                    "layers" => List::End,
+                                    "features" => false,
+                                    "lakes" => false,
+                                    "structure_overrides" => List::End,
+                                },
+                            },
+                        },
+                    },
+                },
+            },
+        };
+
+        let file = File::create(world_root.join("level.dat"))?;
+        let mut enc = GzEncoder::new(BufWriter::new(file), Compression::default());
+        valence_nbt::to_binary_writer(&mut enc, &level, "")?;
+        enc.finish()?.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Writes a complete region file containing `chunks`.
+fn write_region(
+    path: &Path,
+    chunks: &[(i32, i32, Compound)],
+    timestamp: u32,
+) -> Result<(), ExportAnvilError> {
+    let mut header = [0; SECTOR_SIZE * 2];
+    let mut body = vec![];
+
+    for (chunk_x, chunk_z, nbt) in chunks {
+        let mut enc = ZlibEncoder::new(vec![], Compression::default());
+        valence_nbt::to_binary_writer(&mut enc, nbt, "")?;
+        let compressed = enc.finish()?;
+
+        let sector_offset = 2 + body.len() / SECTOR_SIZE;
+
+        // Length of the data including the compression scheme byte.
+        body.extend_from_slice(&(compressed.len() as u32 + 1).to_be_bytes());
+        // Zlib
+        body.push(2);
+        body.extend_from_slice(&compressed);
+        body.resize(
+            num_integer::div_ceil(body.len(), SECTOR_SIZE) * SECTOR_SIZE,
+            0,
+        );
+
+        let sector_count = 2 + body.len() / SECTOR_SIZE - sector_offset;
+
+        if sector_count > u8::MAX as usize {
+            return Err(ExportAnvilError::ChunkTooLarge(*chunk_x, *chunk_z));
+        }
+
+        let chunk_idx = (chunk_x.rem_euclid(32) + chunk_z.rem_euclid(32) * 32) as usize;
+        let location = (sector_offset as u32) << 8 | sector_count as u32;
+
+        header[chunk_idx * 4..chunk_idx * 4 + 4].copy_from_slice(&location.to_be_bytes());
+        header[SECTOR_SIZE + chunk_idx * 4..SECTOR_SIZE + chunk_idx * 4 + 4]
+            .copy_from_slice(&timestamp.to_be_bytes());
+    }
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&header)?;
+    file.write_all(&body)?;
+    file.flush()?;
+
+    Ok(())
+}
+
+/// Adds [`export_anvil`] to [`Instance`].
+///
+/// [`export_anvil`]: ExportAnvil::export_anvil
+pub trait ExportAnvil {
+    /// Writes all chunks of the instance to a vanilla world folder at
+    /// `world_root`. This is a shorthand for creating an [`AnvilSnapshot`] and
+    /// writing it immediately.
+    fn export_anvil(
+        &self,
+        server: &SharedServer,
+        world_root: impl AsRef<Path>,
+    ) -> Result<(), ExportAnvilError>;
+}
+
+impl ExportAnvil for Instance {
+    fn export_anvil(
+        &self,
+        server: &SharedServer,
+        world_root: impl AsRef<Path>,
+    ) -> Result<(), ExportAnvilError> {
+        AnvilSnapshot::new(self, server).write(world_root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence::instance::Chunk;
+    use valence::protocol::block::BlockState;
+    use valence::protocol::Ident;
+
+    use super::*;
+    use crate::{to_valence, AnvilWorld};
+
+    #[test]
+    fn region_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let region_root = dir.path().join("region");
+        fs::create_dir_all(&region_root).unwrap();
+
+        let mut chunk = Chunk::new(2);
+        chunk.set_block_state(0, 0, 0, BlockState::GOLD_BLOCK);
+
+        let to_anvil = |x, z| {
+            let nbt = from_valence(&chunk, x, z, 0, |_| {
+                Ident::new("minecraft:plains".to_owned()).unwrap()
+            });
+            (x, z, nbt)
+        };
+
+        write_region(
+            &region_root.join("r.0.0.mca"),
+            &[to_anvil(0, 0), to_anvil(31, 5)],
+            0,
+        )
+        .unwrap();
+        write_region(&region_root.join("r.-1.-1.mca"), &[to_anvil(-1, -1)], 0).unwrap();
+
+        let mut world = AnvilWorld::new(dir.path());
+
+        for (x, z) in [(0, 0), (31, 5), (-1, -1)] {
+            let anvil = world.read_chunk(x, z).unwrap().unwrap();
+
+            let mut loaded = Chunk::new(2);
+            to_valence(&anvil.data, &mut loaded, 0, |_| Default::default()).unwrap();
+
+            assert_eq!(loaded.block_state(0, 0, 0), BlockState::GOLD_BLOCK);
+        }
+
+        assert!(world.read_chunk(1, 0).unwrap().is_none());
+    }
+}
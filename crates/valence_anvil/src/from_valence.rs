@@ -0,0 +1,228 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use num_integer::div_ceil;
+use valence::biome::BiomeId;
+use valence::instance::Chunk;
+use valence::protocol::block::BlockState;
+use valence::protocol::Ident;
+use valence_nbt::{compound, Compound, List, Value};
+
+/// The data version of the chunks and `level.dat` written by this crate.
+/// Vanilla upgrades data with older versions when it is loaded.
+pub const DATA_VERSION: i32 = 3218;
+
+/// Takes a Valence [`Chunk`] and converts it to an Anvil chunk in NBT form.
+/// This is the inverse of [`to_valence`].
+///
+/// # Arguments
+///
+/// - `chunk`: The Valence chunk to read from.
+/// - `chunk_x`, `chunk_z`: The position of the chunk, which is stored in the
+///   NBT data.
+/// - `sect_offset`: A constant to add to all section Y positions in `chunk`.
+///   This is usually the minimum Y of the chunk's dimension divided by 16.
+/// - `map_biome`: A function to map Valence [`BiomeId`]s to biome resource
+///   identifiers.
+///
+/// The block light and sky light are not stored, so they are recomputed by
+/// vanilla when the chunk is loaded.
+///
+/// [`to_valence`]: crate::to_valence
+pub fn from_valence<F, const LOADED: bool>(
+    chunk: &Chunk<LOADED>,
+    chunk_x: i32,
+    chunk_z: i32,
+    sect_offset: i32,
+    mut map_biome: F,
+) -> Compound
+where
+    F: FnMut(BiomeId) -> Ident<String>,
+{
+    let mut sections = vec![];
+
+    let mut block_palette = vec![];
+    let mut block_indices = HashMap::new();
+    let mut biome_palette = vec![];
+    let mut biome_indices = HashMap::new();
+    let mut idxs = vec![];
+
+    for sect_y in 0..chunk.section_count() {
+        block_palette.clear();
+        block_indices.clear();
+        idxs.clear();
+
+        for i in 0..BLOCKS_PER_SECTION {
+            let x = i % 16;
+            let z = i / 16 % 16;
+            let y = i / (16 * 16);
+
+            let state = chunk.block_state(x, sect_y * 16 + y, z);
+
+            let idx = match block_indices.entry(state) {
+                Entry::Occupied(oe) => *oe.get(),
+                Entry::Vacant(ve) => {
+                    block_palette.push(state);
+                    *ve.insert(block_palette.len() - 1)
+                }
+            };
+
+            idxs.push(idx);
+        }
+
+        let mut block_states = compound! {
+            "palette" => List::Compound(block_palette.iter().map(|&s| block_to_nbt(s)).collect()),
+        };
+
+        if block_palette.len() > 1 {
+            let bits_per_idx = bit_width(block_palette.len() - 1).max(4);
+            block_states.insert("data", pack_indices(&idxs, bits_per_idx));
+        }
+
+        biome_palette.clear();
+        biome_indices.clear();
+        idxs.clear();
+
+        for i in 0..BIOMES_PER_SECTION {
+            let x = i % 4;
+            let z = i / 4 % 4;
+            let y = i / (4 * 4);
+
+            let biome = chunk.biome(x, sect_y * 4 + y, z);
+
+            let idx = match biome_indices.entry(biome) {
+                Entry::Occupied(oe) => *oe.get(),
+                Entry::Vacant(ve) => {
+                    biome_palette.push(map_biome(biome).to_string());
+                    *ve.insert(biome_palette.len() - 1)
+                }
+            };
+
+            idxs.push(idx);
+        }
+
+        let mut biomes = compound! {
+            "palette" => List::String(biome_palette.clone()),
+        };
+
+        if biome_palette.len() > 1 {
+            let bits_per_idx = bit_width(biome_palette.len() - 1);
+            biomes.insert("data", pack_indices(&idxs, bits_per_idx));
+        }
+
+        sections.push(compound! {
+            "Y" => (sect_y as i32 + sect_offset) as i8,
+            "block_states" => block_states,
+            "biomes" => biomes,
+        });
+    }
+
+    let block_entities = chunk
+        .block_entities()
+        .map(|([x, y, z], block_entity)| {
+            let mut nbt = block_entity.nbt.clone();
+
+            nbt.insert("id", block_entity.kind.ident().to_string());
+            nbt.insert("x", chunk_x * 16 + x as i32);
+            nbt.insert("y", y as i32 + sect_offset * 16);
+            nbt.insert("z", chunk_z * 16 + z as i32);
+            nbt.insert("keepPacked", false);
+
+            nbt
+        })
+        .collect();
+
+    compound! {
+        "DataVersion" => DATA_VERSION,
+        "xPos" => chunk_x,
+        "yPos" => sect_offset,
+        "zPos" => chunk_z,
+        "Status" => "full",
+        "isLightOn" => false,
+        "sections" => List::Compound(sections),
+        "block_entities" => List::Compound(block_entities),
+    }
+}
+
+const BLOCKS_PER_SECTION: usize = 16 * 16 * 16;
+const BIOMES_PER_SECTION: usize = 4 * 4 * 4;
+
+fn block_to_nbt(state: BlockState) -> Compound {
+    let kind = state.to_kind();
+
+    let mut nbt = compound! {
+        "Name" => format!("minecraft:{}", kind.to_str()),
+    };
+
+    let props: Compound = kind
+        .props()
+        .iter()
+        .filter_map(|&name| {
+            let value = state.get(name)?;
+            Some((name.to_str().to_owned(), Value::from(value.to_str())))
+        })
+        .collect();
+
+    if !props.is_empty() {
+        nbt.insert("Properties", props);
+    }
+
+    nbt
+}
+
+/// Packs palette indices into longs without letting indices span across
+/// longs, like the format read by [`to_valence`](crate::to_valence).
+fn pack_indices(idxs: &[usize], bits_per_idx: usize) -> Vec<i64> {
+    let idxs_per_long = 64 / bits_per_idx;
+    let mut data = vec![0_i64; div_ceil(idxs.len(), idxs_per_long)];
+
+    for (i, &idx) in idxs.iter().enumerate() {
+        let long = &mut data[i / idxs_per_long];
+        *long |= (idx as i64) << (i % idxs_per_long * bits_per_idx);
+    }
+
+    data
+}
+
+/// Returns the minimum number of bits needed to represent the integer `n`.
+const fn bit_width(n: usize) -> usize {
+    (usize::BITS - n.leading_zeros()) as _
+}
+
+#[cfg(test)]
+mod tests {
+    use valence::protocol::block::{BlockKind, PropName, PropValue};
+
+    use super::*;
+    use crate::to_valence;
+
+    #[test]
+    fn round_trip() {
+        let mut chunk = Chunk::new(4);
+
+        chunk.set_block_state(1, 2, 3, BlockState::STONE);
+        chunk.set_block_state(
+            15,
+            63,
+            0,
+            BlockKind::OakStairs
+                .to_state()
+                .set(PropName::Facing, PropValue::West),
+        );
+
+        let nbt = from_valence(&chunk, 2, -3, -4, |_| {
+            Ident::new("minecraft:plains".to_owned()).unwrap()
+        });
+
+        let mut loaded = Chunk::new(4);
+        to_valence(&nbt, &mut loaded, 4, |_| BiomeId::default()).unwrap();
+
+        for y in 0..64 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    assert_eq!(chunk.block_state(x, y, z), loaded.block_state(x, y, z));
+                }
+            }
+        }
+    }
+}
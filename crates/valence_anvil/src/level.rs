@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use flate2::bufread::GzDecoder;
+use thiserror::Error;
+use valence_nbt::{Compound, Value};
+
+/// The world metadata stored in a world's `level.dat` file.
+///
+/// Missing fields take the values vanilla Minecraft uses for new worlds.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LevelDat {
+    /// The name of the world shown in the world list.
+    pub name: String,
+    /// The data version of the game which last saved the world.
+    pub data_version: Option<i32>,
+    /// The block position of the world spawn point.
+    pub spawn: [i32; 3],
+    /// The yaw players face when spawning at the world spawn point.
+    pub spawn_angle: f32,
+    /// The default game mode as a vanilla game mode ID.
+    pub game_type: i32,
+    pub hardcore: bool,
+    /// The difficulty as a vanilla difficulty ID.
+    pub difficulty: i8,
+    /// The number of ticks the world has run for.
+    pub time: i64,
+    /// The time of day in ticks. Not reset at the end of each day.
+    pub day_time: i64,
+    pub raining: bool,
+    pub thundering: bool,
+    /// Maps game rule names to their values, which are always stored as
+    /// strings.
+    pub game_rules: BTreeMap<String, String>,
+    pub world_gen: WorldGenSettings,
+    pub border: LevelBorder,
+    /// The complete `Data` compound, for reading anything not covered by the
+    /// other fields.
+    pub nbt: Compound,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct WorldGenSettings {
+    pub seed: i64,
+    pub generate_features: bool,
+    pub bonus_chest: bool,
+    /// Maps dimension names to their generator settings.
+    pub dimensions: Compound,
+}
+
+/// The world border stored in `level.dat`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LevelBorder {
+    pub center: [f64; 2],
+    pub size: f64,
+    /// The diameter the border is moving towards.
+    pub size_lerp_target: f64,
+    /// The number of milliseconds left until the border reaches
+    /// `size_lerp_target`.
+    pub size_lerp_time: i64,
+    pub damage_per_block: f64,
+    pub safe_zone: f64,
+    pub warning_blocks: f64,
+    pub warning_time: f64,
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ReadLevelError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Nbt(#[from] valence_nbt::Error),
+    #[error("missing level data compound")]
+    MissingData,
+    #[error("level data field \"{0}\" has the wrong type")]
+    BadFieldType(&'static str),
+}
+
+impl LevelDat {
+    /// Reads and parses the GZip compressed `level.dat` file at `path`.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ReadLevelError> {
+        let mut buf = vec![];
+        GzDecoder::new(BufReader::new(File::open(path)?)).read_to_end(&mut buf)?;
+
+        let (root, _) = valence_nbt::from_binary_slice(&mut buf.as_slice())?;
+
+        Self::from_nbt(&root)
+    }
+
+    /// Parses the uncompressed root compound of a `level.dat` file.
+    pub fn from_nbt(root: &Compound) -> Result<Self, ReadLevelError> {
+        let Some(Value::Compound(data)) = root.get("Data") else {
+            return Err(ReadLevelError::MissingData);
+        };
+
+        let game_rules = match data.get("GameRules") {
+            None => BTreeMap::new(),
+            Some(Value::Compound(rules)) => rules
+                .iter()
+                .map(|(k, v)| match v {
+                    Value::String(v) => Ok((k.clone(), v.clone())),
+                    _ => Err(ReadLevelError::BadFieldType("GameRules")),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(ReadLevelError::BadFieldType("GameRules")),
+        };
+
+        let world_gen = match data.get("WorldGenSettings") {
+            None => WorldGenSettings {
+                seed: 0,
+                generate_features: true,
+                bonus_chest: false,
+                dimensions: Compound::new(),
+            },
+            Some(Value::Compound(settings)) => WorldGenSettings {
+                seed: get_long(settings, "seed", 0)?,
+                generate_features: get_bool(settings, "generate_features", true)?,
+                bonus_chest: get_bool(settings, "bonus_chest", false)?,
+                dimensions: match settings.get("dimensions") {
+                    None => Compound::new(),
+                    Some(Value::Compound(dims)) => dims.clone(),
+                    Some(_) => return Err(ReadLevelError::BadFieldType("dimensions")),
+                },
+            },
+            Some(_) => return Err(ReadLevelError::BadFieldType("WorldGenSettings")),
+        };
+
+        let size = get_double(data, "BorderSize", 59_999_968.0)?;
+
+        Ok(Self {
+            name: match data.get("LevelName") {
+                None => String::new(),
+                Some(Value::String(name)) => name.clone(),
+                Some(_) => return Err(ReadLevelError::BadFieldType("LevelName")),
+            },
+            data_version: match data.get("DataVersion") {
+                None => None,
+                Some(Value::Int(v)) => Some(*v),
+                Some(_) => return Err(ReadLevelError::BadFieldType("DataVersion")),
+            },
+            spawn: [
+                get_int(data, "SpawnX", 0)?,
+                get_int(data, "SpawnY", 64)?,
+                get_int(data, "SpawnZ", 0)?,
+            ],
+            spawn_angle: match data.get("SpawnAngle") {
+                None => 0.0,
+                Some(Value::Float(v)) => *v,
+                Some(_) => return Err(ReadLevelError::BadFieldType("SpawnAngle")),
+            },
+            game_type: get_int(data, "GameType", 0)?,
+            hardcore: get_bool(data, "hardcore", false)?,
+            difficulty: match data.get("Difficulty") {
+                None => 2,
+                Some(Value::Byte(v)) => *v,
+                Some(_) => return Err(ReadLevelError::BadFieldType("Difficulty")),
+            },
+            time: get_long(data, "Time", 0)?,
+            day_time: get_long(data, "DayTime", 0)?,
+            raining: get_bool(data, "raining", false)?,
+            thundering: get_bool(data, "thundering", false)?,
+            game_rules,
+            world_gen,
+            border: LevelBorder {
+                center: [
+                    get_double(data, "BorderCenterX", 0.0)?,
+                    get_double(data, "BorderCenterZ", 0.0)?,
+                ],
+                size,
+                size_lerp_target: get_double(data, "BorderSizeLerpTarget", size)?,
+                size_lerp_time: get_long(data, "BorderSizeLerpTime", 0)?,
+                damage_per_block: get_double(data, "BorderDamagePerBlock", 0.2)?,
+                safe_zone: get_double(data, "BorderSafeZone", 5.0)?,
+                warning_blocks: get_double(data, "BorderWarningBlocks", 5.0)?,
+                warning_time: get_double(data, "BorderWarningTime", 15.0)?,
+            },
+            nbt: data.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "valence")]
+impl LevelBorder {
+    /// Creates a Valence world border with the center, diameter, movement and
+    /// warnings of this border.
+    pub fn to_world_border(&self) -> valence::world_border::WorldBorder {
+        let mut border = valence::world_border::WorldBorder::new(self.center, self.size);
+
+        if self.size_lerp_time > 0 {
+            border.lerp_target(
+                self.size_lerp_target,
+                std::time::Duration::from_millis(self.size_lerp_time as u64),
+            );
+        }
+
+        border.set_warning_blocks(self.warning_blocks as i32);
+        border.set_warning_time(self.warning_time as i32);
+
+        border
+    }
+}
+
+fn get_int(data: &Compound, key: &'static str, default: i32) -> Result<i32, ReadLevelError> {
+    match data.get(key) {
+        None => Ok(default),
+        Some(Value::Int(v)) => Ok(*v),
+        Some(_) => Err(ReadLevelError::BadFieldType(key)),
+    }
+}
+
+fn get_long(data: &Compound, key: &'static str, default: i64) -> Result<i64, ReadLevelError> {
+    match data.get(key) {
+        None => Ok(default),
+        Some(Value::Long(v)) => Ok(*v),
+        Some(_) => Err(ReadLevelError::BadFieldType(key)),
+    }
+}
+
+fn get_double(data: &Compound, key: &'static str, default: f64) -> Result<f64, ReadLevelError> {
+    match data.get(key) {
+        None => Ok(default),
+        Some(Value::Double(v)) => Ok(*v),
+        Some(_) => Err(ReadLevelError::BadFieldType(key)),
+    }
+}
+
+fn get_bool(data: &Compound, key: &'static str, default: bool) -> Result<bool, ReadLevelError> {
+    match data.get(key) {
+        None => Ok(default),
+        Some(Value::Byte(v)) => Ok(*v != 0),
+        Some(_) => Err(ReadLevelError::BadFieldType(key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+
+    #[test]
+    fn parse_level_dat() {
+        let root = compound! {
+            "Data" => compound! {
+                "LevelName" => "My World",
+                "SpawnX" => 10,
+                "SpawnY" => 70,
+                "SpawnZ" => -20,
+                "SpawnAngle" => 90.0_f32,
+                "hardcore" => 1_i8,
+                "GameRules" => compound! { "doDaylightCycle" => "false" },
+                "WorldGenSettings" => compound! { "seed" => 1234_i64 },
+                "BorderSize" => 100.0,
+            },
+        };
+
+        let level = LevelDat::from_nbt(&root).unwrap();
+
+        assert_eq!(level.name, "My World");
+        assert_eq!(level.spawn, [10, 70, -20]);
+        assert_eq!(level.spawn_angle, 90.0);
+        assert!(level.hardcore);
+        assert_eq!(level.difficulty, 2);
+        assert_eq!(
+            level.game_rules.get("doDaylightCycle").map(String::as_str),
+            Some("false")
+        );
+        assert_eq!(level.world_gen.seed, 1234);
+        assert!(level.world_gen.generate_features);
+        assert_eq!(level.border.size, 100.0);
+        assert_eq!(level.border.size_lerp_target, 100.0);
+        assert_eq!(level.border.center, [0.0, 0.0]);
+
+        let bad = compound! { "Data" => compound! { "SpawnX" => "zero" } };
+        assert!(matches!(
+            LevelDat::from_nbt(&bad),
+            Err(ReadLevelError::BadFieldType("SpawnX"))
+        ));
+        assert!(matches!(
+            LevelDat::from_nbt(&Compound::new()),
+            Err(ReadLevelError::MissingData)
+        ));
+    }
+}
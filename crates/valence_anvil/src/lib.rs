@@ -2,25 +2,48 @@ use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
 use std::fs::File;
 use std::io;
-use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
 use std::path::PathBuf;
 
 use byteorder::{BigEndian, ReadBytesExt};
 use flate2::bufread::{GzDecoder, ZlibDecoder};
 use thiserror::Error;
+#[cfg(feature = "tokio")]
+pub use async_world::*;
+#[cfg(feature = "valence")]
+pub use biome::*;
+#[cfg(feature = "valence")]
+pub use export::*;
+#[cfg(feature = "valence")]
+pub use from_valence::*;
+pub use level::*;
 #[cfg(feature = "valence")]
 pub use to_valence::*;
-use valence_nbt::Compound;
+use valence_nbt::stream::{Event, NbtReader};
+use valence_nbt::{Compound, Value};
 
+#[cfg(feature = "tokio")]
+mod async_world;
+#[cfg(feature = "valence")]
+mod biome;
+#[cfg(feature = "valence")]
+mod export;
+#[cfg(feature = "valence")]
+mod from_valence;
+mod level;
 #[cfg(feature = "valence")]
 mod to_valence;
 
 #[derive(Debug)]
 pub struct AnvilWorld {
-    /// Path to the "region" subdirectory in the world root.
-    region_root: PathBuf,
-    /// Maps region (x, z) positions to region files.
-    regions: BTreeMap<(i32, i32), Region>,
+    /// Terrain in the "region" subdirectory of the world root.
+    terrain: RegionFolder,
+    /// Entities in the "entities" subdirectory of the world root.
+    entities: RegionFolder,
+    /// Points of interest in the "poi" subdirectory of the world root.
+    poi: RegionFolder,
+    /// The world metadata, if it was read when the world was opened.
+    level: Option<LevelDat>,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -49,6 +72,14 @@ pub enum ReadChunkError {
     IncompleteNbtRead,
 }
 
+#[derive(Debug)]
+struct RegionFolder {
+    /// Path to the directory containing the region files.
+    root: PathBuf,
+    /// Maps region (x, z) positions to region files.
+    regions: BTreeMap<(i32, i32), Region>,
+}
+
 #[derive(Debug)]
 struct Region {
     file: File,
@@ -59,22 +90,88 @@ struct Region {
 const SECTOR_SIZE: usize = 4096;
 
 impl AnvilWorld {
+    /// Creates an Anvil world without reading its `level.dat` file. Use
+    /// [`AnvilWorld::open`] to read it as well.
     pub fn new(world_root: impl Into<PathBuf>) -> Self {
-        let mut region_root = world_root.into();
-        region_root.push("region");
+        let world_root = world_root.into();
 
         Self {
-            region_root,
-            regions: BTreeMap::new(),
+            terrain: RegionFolder::new(world_root.join("region")),
+            entities: RegionFolder::new(world_root.join("entities")),
+            poi: RegionFolder::new(world_root.join("poi")),
+            level: None,
         }
     }
 
+    /// Creates an Anvil world and reads the world metadata from the
+    /// `level.dat` file in the world root.
+    pub fn open(world_root: impl Into<PathBuf>) -> Result<Self, ReadLevelError> {
+        let world_root = world_root.into();
+        let level = LevelDat::read(world_root.join("level.dat"))?;
+
+        Ok(Self {
+            level: Some(level),
+            ..Self::new(world_root)
+        })
+    }
+
+    /// Returns the world metadata if the world was created with
+    /// [`AnvilWorld::open`].
+    pub fn level(&self) -> Option<&LevelDat> {
+        self.level.as_ref()
+    }
+
     /// Reads a chunk from the file system with the given chunk coordinates. If
     /// no chunk exists at the position, then `None` is returned.
     pub fn read_chunk(
         &mut self,
         chunk_x: i32,
         chunk_z: i32,
+    ) -> Result<Option<AnvilChunk>, ReadChunkError> {
+        self.terrain.read_chunk(chunk_x, chunk_z)
+    }
+
+    /// Reads the entities stored in the chunk at the given chunk coordinates.
+    /// If the chunk has no entity data, then `None` is returned.
+    ///
+    /// Since 1.17, entities are kept in the "entities" subdirectory instead of
+    /// with the terrain. The entities can be converted with
+    /// `to_valence_entities`.
+    pub fn read_entities(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<AnvilChunk>, ReadChunkError> {
+        self.entities.read_chunk(chunk_x, chunk_z)
+    }
+
+    /// Reads the points of interest (beds, workstations, nether portals, etc.)
+    /// stored in the chunk at the given chunk coordinates. If the chunk has no
+    /// point of interest data, then `None` is returned.
+    ///
+    /// The data contains a `Sections` compound which maps section Y
+    /// coordinates to the `Records` of that section.
+    pub fn read_poi(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<AnvilChunk>, ReadChunkError> {
+        self.poi.read_chunk(chunk_x, chunk_z)
+    }
+}
+
+impl RegionFolder {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            regions: BTreeMap::new(),
+        }
+    }
+
+    fn read_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
     ) -> Result<Option<AnvilChunk>, ReadChunkError> {
         let region_x = chunk_x.div_euclid(32);
         let region_z = chunk_z.div_euclid(32);
@@ -83,9 +180,7 @@ impl AnvilWorld {
             Entry::Vacant(ve) => {
                 // Load the region file if it exists. Otherwise, the chunk is considered absent.
 
-                let path = self
-                    .region_root
-                    .join(format!("r.{region_x}.{region_z}.mca"));
+                let path = self.root.join(format!("r.{region_x}.{region_z}.mca"));
 
                 let mut file = match File::options().read(true).write(true).open(path) {
                     Ok(file) => file,
@@ -102,10 +197,57 @@ impl AnvilWorld {
             Entry::Occupied(oe) => oe.into_mut(),
         };
 
+        region.read_chunk(chunk_x, chunk_z)
+    }
+}
+
+impl Region {
+    fn read_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<AnvilChunk>, ReadChunkError> {
+        let Some(location) = ChunkLocation::new(&self.header, chunk_x, chunk_z)? else {
+            return Ok(None)
+        };
+
+        // Seek to the beginning of the chunk's data.
+        self.file.seek(SeekFrom::Start(location.offset))?;
+
+        let exact_chunk_size = self.file.read_u32::<BigEndian>()? as usize;
+
+        location.check_size(exact_chunk_size)?;
+
+        let mut data_buf = vec![0; exact_chunk_size].into_boxed_slice();
+        self.file.read_exact(&mut data_buf)?;
+
+        decode_chunk(&data_buf, location.timestamp).map(Some)
+    }
+}
+
+/// Where a chunk is stored in a region file, read from the region header.
+struct ChunkLocation {
+    /// Byte offset of the chunk's data from the start of the file.
+    offset: u64,
+    /// Size of the sectors allocated to the chunk in bytes.
+    max_size: usize,
+    /// The time the chunk was last modified measured in seconds since the
+    /// epoch.
+    timestamp: u32,
+}
+
+impl ChunkLocation {
+    /// Looks up a chunk in the header of its region file. Returns `None` if
+    /// the chunk is absent.
+    fn new(
+        header: &[u8; SECTOR_SIZE * 2],
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<Self>, ReadChunkError> {
         let chunk_idx = (chunk_x.rem_euclid(32) + chunk_z.rem_euclid(32) * 32) as usize;
 
-        let location_bytes = (&region.header[chunk_idx * 4..]).read_u32::<BigEndian>()?;
-        let timestamp = (&region.header[chunk_idx * 4 + SECTOR_SIZE..]).read_u32::<BigEndian>()?;
+        let location_bytes = (&header[chunk_idx * 4..]).read_u32::<BigEndian>()?;
+        let timestamp = (&header[chunk_idx * 4 + SECTOR_SIZE..]).read_u32::<BigEndian>()?;
 
         if location_bytes == 0 {
             // No chunk exists at this position.
@@ -121,51 +263,59 @@ impl AnvilWorld {
             return Err(ReadChunkError::BadSectorOffset);
         }
 
-        // Seek to the beginning of the chunk's data.
-        region
-            .file
-            .seek(SeekFrom::Start(sector_offset * SECTOR_SIZE as u64))?;
-
-        let exact_chunk_size = region.file.read_u32::<BigEndian>()? as usize;
+        Ok(Some(Self {
+            offset: sector_offset * SECTOR_SIZE as u64,
+            max_size: sector_count * SECTOR_SIZE,
+            timestamp,
+        }))
+    }
 
-        if exact_chunk_size > sector_count * SECTOR_SIZE {
+    fn check_size(&self, exact_chunk_size: usize) -> Result<(), ReadChunkError> {
+        if exact_chunk_size > self.max_size {
             // Sector size of this chunk must always be >= the exact size.
             return Err(ReadChunkError::BadChunkSize);
         }
 
-        let mut data_buf = vec![0; exact_chunk_size].into_boxed_slice();
-        region.file.read_exact(&mut data_buf)?;
-
-        let mut r = data_buf.as_ref();
+        Ok(())
+    }
+}
 
-        let mut decompress_buf = vec![];
+/// Decompresses and decodes the data of a chunk following its length prefix.
+///
+/// The NBT is decoded with an [`NbtReader`] straight out of the decompressor,
+/// so the decompressed data is never buffered as a whole.
+fn decode_chunk(mut r: &[u8], timestamp: u32) -> Result<AnvilChunk, ReadChunkError> {
+    // What compression does the chunk use?
+    let data = match r.read_u8()? {
+        // GZip
+        1 => read_chunk_nbt(BufReader::new(GzDecoder::new(r)))?,
+        // Zlib
+        2 => read_chunk_nbt(BufReader::new(ZlibDecoder::new(r)))?,
+        // Uncompressed
+        3 => read_chunk_nbt(r)?,
+        // Unknown
+        b => return Err(ReadChunkError::UnknownCompressionScheme(b)),
+    };
 
-        // What compression does the chunk use?
-        let mut nbt_slice = match r.read_u8()? {
-            // GZip
-            1 => {
-                let mut z = GzDecoder::new(r);
-                z.read_to_end(&mut decompress_buf)?;
-                decompress_buf.as_slice()
-            }
-            // Zlib
-            2 => {
-                let mut z = ZlibDecoder::new(r);
-                z.read_to_end(&mut decompress_buf)?;
-                decompress_buf.as_slice()
-            }
-            // Uncompressed
-            3 => r,
-            // Unknown
-            b => return Err(ReadChunkError::UnknownCompressionScheme(b)),
-        };
+    Ok(AnvilChunk { data, timestamp })
+}
 
-        let (data, _) = valence_nbt::from_binary_slice(&mut nbt_slice)?;
+/// Reads the root compound of a chunk and checks that nothing follows it.
+fn read_chunk_nbt(r: impl Read) -> Result<Compound, ReadChunkError> {
+    let mut reader = NbtReader::new(r);
 
-        if !nbt_slice.is_empty() {
-            return Err(ReadChunkError::IncompleteNbtRead);
-        }
+    let data = match reader.next_event()? {
+        Some(Event::Name(_)) => match reader.read_value()? {
+            Value::Compound(data) => data,
+            _ => unreachable!("the root value is always a compound"),
+        },
+        // The chunk starts with `TAG_End`.
+        _ => Compound::new(),
+    };
 
-        Ok(Some(AnvilChunk { data, timestamp }))
+    if reader.into_inner().read(&mut [0])? != 0 {
+        return Err(ReadChunkError::IncompleteNbtRead);
     }
+
+    Ok(data)
 }
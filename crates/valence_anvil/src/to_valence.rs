@@ -1,14 +1,26 @@
 use num_integer::{div_ceil, Integer};
 use thiserror::Error;
+use valence::bevy_ecs::prelude::Entity;
 use valence::biome::BiomeId;
+use valence::entity::{EntityKind, McEntity};
 use valence::instance::Chunk;
+use valence::prelude::DVec3;
 use valence::protocol::block::{BlockEntity, BlockEntityKind, BlockKind, PropName, PropValue};
 use valence::protocol::Ident;
+use valence::uuid::Uuid;
 use valence_nbt::{Compound, List, Value};
 
+use crate::DATA_VERSION;
+
 #[derive(Clone, Debug, Error)]
 #[non_exhaustive]
 pub enum ToValenceError {
+    #[error(
+        "unsupported chunk data version of {0} (expected {} to {})",
+        MIN_DATA_VERSION,
+        DATA_VERSION
+    )]
+    UnsupportedDataVersion(i32),
     #[error("missing chunk sections")]
     MissingSections,
     #[error("missing chunk section Y")]
@@ -59,12 +71,37 @@ pub enum ToValenceError {
     UnknownBlockEntityIdent(String),
     #[error("invalid block entity position")]
     InvalidBlockEntityPosition,
+    #[error("missing entities")]
+    MissingEntities,
+    #[error("missing entity ident")]
+    MissingEntityIdent,
+    #[error("invalid entity ident of \"{0}\"")]
+    UnknownEntityIdent(String),
+    #[error("invalid entity UUID")]
+    InvalidEntityUuid,
+    #[error("invalid entity position")]
+    InvalidEntityPosition,
 }
 
+/// The oldest data version of chunks accepted by [`to_valence`], which is the
+/// version of Minecraft 1.18. Older chunks use a different layout and must be
+/// upgraded by loading the world in a newer version of the game first.
+pub const MIN_DATA_VERSION: i32 = 2860;
+
 /// Takes an Anvil chunk in NBT form and writes its data to a Valence [`Chunk`].
 /// An error is returned if the NBT data does not match the expected structure
 /// for an Anvil chunk.
 ///
+/// The data version of the chunk is validated, but chunks are not converted.
+/// Chunks with a data version between [`MIN_DATA_VERSION`] and
+/// [`DATA_VERSION`] are accepted, which covers every release from 1.18 to the
+/// version supported by Valence. The chunk layout, block names and biome names
+/// are the same across these versions, so they are read as they are. Chunks
+/// with any other data version are rejected with
+/// [`ToValenceError::UnsupportedDataVersion`] rather than being read with a
+/// layout they don't have. Chunks without a data version are read with the
+/// current layout.
+///
 /// # Arguments
 ///
 /// - `nbt`: The Anvil chunk to read from. This is usually the value returned by
@@ -74,9 +111,11 @@ pub enum ToValenceError {
 ///   applying the offset, only the sectors in the range
 ///   `0..chunk.sector_count()` are written.
 /// - `map_biome`: A function to map biome resource identifiers in the NBT data
-///   to Valence [`BiomeId`]s.
+///   to Valence [`BiomeId`]s. Use [`BiomeMapping::get`] to map them to the
+///   biomes registered on the server with the same names.
 ///
 /// [`AnvilWorld::read_chunk`]: crate::AnvilWorld::read_chunk
+/// [`BiomeMapping::get`]: crate::BiomeMapping::get
 pub fn to_valence<F, const LOADED: bool>(
     nbt: &Compound,
     chunk: &mut Chunk<LOADED>,
@@ -86,6 +125,12 @@ pub fn to_valence<F, const LOADED: bool>(
 where
     F: FnMut(Ident<&str>) -> BiomeId,
 {
+    if let Some(&Value::Int(data_version)) = nbt.get("DataVersion") {
+        if !(MIN_DATA_VERSION..=DATA_VERSION).contains(&data_version) {
+            return Err(ToValenceError::UnsupportedDataVersion(data_version));
+        }
+    }
+
     let Some(Value::List(List::Compound(sections))) = nbt.get("sections") else {
         return Err(ToValenceError::MissingSections)
     };
@@ -275,7 +320,9 @@ where
                 return Err(ToValenceError::UnknownBlockEntityIdent(ident.clone()));
             };
             let Some(kind) = BlockEntityKind::from_ident(ident) else {
-                return Err(ToValenceError::UnknownBlockEntityIdent(ident.as_str().to_string()));
+                return Err(ToValenceError::UnknownBlockEntityIdent(
+                    ident.as_str().to_string(),
+                ));
             };
             let block_entity = BlockEntity {
                 kind,
@@ -307,6 +354,145 @@ where
     Ok(())
 }
 
+/// An entity read from Anvil entity data. Use [`AnvilEntity::to_mc_entity`]
+/// to spawn it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AnvilEntity {
+    pub kind: EntityKind,
+    pub uuid: Uuid,
+    pub position: DVec3,
+    /// The velocity of the entity in meters per second.
+    pub velocity: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub on_ground: bool,
+    pub glowing: bool,
+    /// The complete NBT data of the entity, for reading anything not covered
+    /// by the other fields.
+    pub nbt: Compound,
+}
+
+impl AnvilEntity {
+    /// Creates an [`McEntity`] with the kind, UUID, position, rotation and
+    /// velocity of this entity in the given instance.
+    pub fn to_mc_entity(&self, instance: Entity) -> McEntity {
+        let mut entity = McEntity::with_uuid(self.kind, instance, self.uuid);
+
+        entity.set_position(self.position);
+        entity.set_yaw(self.yaw);
+        entity.set_pitch(self.pitch);
+        entity.set_head_yaw(self.yaw);
+        entity.set_velocity(self.velocity);
+        entity.set_on_ground(self.on_ground);
+        entity.set_glowing(self.glowing);
+
+        entity
+    }
+}
+
+/// Takes the entity data of an Anvil chunk in NBT form and converts the
+/// entities in it to [`AnvilEntity`]s. Passengers are included after the
+/// entity they are riding. An error is returned if the NBT data does not match
+/// the expected structure for Anvil entity data.
+///
+/// # Arguments
+///
+/// - `nbt`: The entity data to read from. This is usually the value returned
+///   by [`AnvilWorld::read_entities`].
+/// - `sect_offset`: A constant number of sections to add to the Y position of
+///   every entity, the same as the argument to [`to_valence`].
+///
+/// [`AnvilWorld::read_entities`]: crate::AnvilWorld::read_entities
+pub fn to_valence_entities(
+    nbt: &Compound,
+    sect_offset: i32,
+) -> Result<Vec<AnvilEntity>, ToValenceError> {
+    let Some(Value::List(entities)) = nbt.get("Entities") else {
+        return Err(ToValenceError::MissingEntities);
+    };
+
+    let mut res = vec![];
+
+    if let List::Compound(entities) = entities {
+        for comp in entities {
+            read_entity(comp, sect_offset, &mut res)?;
+        }
+    }
+
+    Ok(res)
+}
+
+fn read_entity(
+    comp: &Compound,
+    sect_offset: i32,
+    res: &mut Vec<AnvilEntity>,
+) -> Result<(), ToValenceError> {
+    let Some(Value::String(ident)) = comp.get("id") else {
+        return Err(ToValenceError::MissingEntityIdent);
+    };
+    let Ok(ident) = Ident::new(&ident[..]) else {
+        return Err(ToValenceError::UnknownEntityIdent(ident.clone()));
+    };
+    let Some(kind) = EntityKind::from_ident(ident) else {
+        return Err(ToValenceError::UnknownEntityIdent(
+            ident.as_str().to_string(),
+        ));
+    };
+    let Some(Value::IntArray(uuid)) = comp.get("UUID") else {
+        return Err(ToValenceError::InvalidEntityUuid);
+    };
+    let &[a, b, c, d] = uuid.as_slice() else {
+        return Err(ToValenceError::InvalidEntityUuid);
+    };
+    let uuid = Uuid::from_u64_pair(
+        (a as u32 as u64) << 32 | b as u32 as u64,
+        (c as u32 as u64) << 32 | d as u32 as u64,
+    );
+    let Some(Value::List(List::Double(pos))) = comp.get("Pos") else {
+        return Err(ToValenceError::InvalidEntityPosition);
+    };
+    let &[x, y, z] = pos.as_slice() else {
+        return Err(ToValenceError::InvalidEntityPosition);
+    };
+
+    // Motion is stored in meters per tick.
+    let velocity = match comp.get("Motion") {
+        Some(Value::List(List::Double(motion))) if motion.len() == 3 => [
+            (motion[0] * 20.0) as f32,
+            (motion[1] * 20.0) as f32,
+            (motion[2] * 20.0) as f32,
+        ],
+        _ => [0.0; 3],
+    };
+
+    let (yaw, pitch) = match comp.get("Rotation") {
+        Some(Value::List(List::Float(rot))) if rot.len() == 2 => (rot[0], rot[1]),
+        _ => (0.0, 0.0),
+    };
+
+    let flag = |name| matches!(comp.get(name), Some(Value::Byte(b)) if *b != 0);
+
+    res.push(AnvilEntity {
+        kind,
+        uuid,
+        position: DVec3::new(x, y + sect_offset as f64 * 16.0, z),
+        velocity,
+        yaw,
+        pitch,
+        on_ground: flag("OnGround"),
+        glowing: flag("Glowing"),
+        nbt: comp.clone(),
+    });
+
+    if let Some(Value::List(List::Compound(passengers))) = comp.get("Passengers") {
+        for passenger in passengers {
+            read_entity(passenger, sect_offset, res)?;
+        }
+    }
+
+    Ok(())
+}
+
 const BLOCKS_PER_SECTION: usize = 16 * 16 * 16;
 const BIOMES_PER_SECTION: usize = 4 * 4 * 4;
 
@@ -322,3 +508,78 @@ fn ident_path(ident: &str) -> &str {
 const fn bit_width(n: usize) -> usize {
     (usize::BITS - n.leading_zeros()) as _
 }
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+
+    #[test]
+    fn data_version() {
+        let mut chunk = Chunk::new(4);
+
+        for version in [MIN_DATA_VERSION - 1, DATA_VERSION + 1] {
+            let nbt = compound! { "DataVersion" => version };
+            assert!(matches!(
+                to_valence(&nbt, &mut chunk, 4, |_| BiomeId::default()),
+                Err(ToValenceError::UnsupportedDataVersion(v)) if v == version
+            ));
+        }
+
+        let mut nbt = compound! {
+            "DataVersion" => MIN_DATA_VERSION,
+            "sections" => List::Compound(vec![]),
+            "block_entities" => List::Compound(vec![]),
+        };
+        to_valence(&nbt, &mut chunk, 4, |_| BiomeId::default()).unwrap();
+
+        // Chunks without a data version are read like current ones.
+        nbt.remove("DataVersion");
+        to_valence(&nbt, &mut chunk, 4, |_| BiomeId::default()).unwrap();
+    }
+
+    #[test]
+    fn entities() {
+        let nbt = compound! {
+            "Entities" => List::Compound(vec![compound! {
+                "id" => "minecraft:pig",
+                "UUID" => vec![0, 1, 0, 2],
+                "Pos" => List::Double(vec![1.5, -60.0, 3.5]),
+                "Motion" => List::Double(vec![0.0, -0.1, 0.0]),
+                "Rotation" => List::Float(vec![90.0, 10.0]),
+                "OnGround" => 1_i8,
+                "Passengers" => List::Compound(vec![compound! {
+                    "id" => "zombie",
+                    "UUID" => vec![0, 0, 0, 3],
+                    "Pos" => List::Double(vec![1.5, -59.0, 3.5]),
+                }]),
+            }]),
+        };
+
+        let entities = to_valence_entities(&nbt, 4).unwrap();
+
+        assert_eq!(entities.len(), 2);
+
+        let pig = &entities[0];
+        assert_eq!(pig.kind, EntityKind::Pig);
+        assert_eq!(pig.uuid, Uuid::from_u64_pair(1, 2));
+        assert_eq!(pig.position, DVec3::new(1.5, 4.0, 3.5));
+        assert_eq!(pig.velocity, [0.0, -2.0, 0.0]);
+        assert_eq!((pig.yaw, pig.pitch), (90.0, 10.0));
+        assert!(pig.on_ground);
+        assert!(!pig.glowing);
+
+        assert_eq!(entities[1].kind, EntityKind::Zombie);
+        assert_eq!(entities[1].position, DVec3::new(1.5, 5.0, 3.5));
+
+        let unknown = compound! {
+            "Entities" => List::Compound(vec![compound! { "id" => "minecraft:unicorn" }]),
+        };
+
+        assert!(matches!(
+            to_valence_entities(&unknown, 0),
+            Err(ToValenceError::UnknownEntityIdent(_))
+        ));
+    }
+}
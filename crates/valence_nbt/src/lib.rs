@@ -46,8 +46,10 @@
 //! # Features
 //!
 //! - `preserve_order`: Causes the order of fields in [`Compound`]s to be
-//! preserved during insertion and deletion at a slight cost to performance.
-//! The iterators on `Compound` can then implement [`DoubleEndedIterator`].
+//!   preserved during insertion and deletion at a slight cost to performance.
+//!   The iterators on `Compound` can then implement [`DoubleEndedIterator`].
+//! - `serde`: Adds the `serde` module for converting types implementing
+//!   serde's `Serialize` and `Deserialize` traits to and from NBT.
 
 #![deny(
     rustdoc::broken_intra_doc_links,
@@ -77,7 +79,10 @@ pub mod compound;
 mod error;
 mod from_binary_slice;
 mod modified_utf8;
+#[cfg(feature = "serde")]
+pub mod serde;
 pub mod snbt;
+pub mod stream;
 mod to_binary_writer;
 pub mod value;
 
@@ -0,0 +1,174 @@
+//! Support for [serde], enabled with the `serde` feature.
+//!
+//! Types implementing `Serialize` and `Deserialize` are converted to and
+//! from NBT with [`to_value`], [`to_compound`], [`from_value`] and
+//! [`from_compound`]. [`Value`], [`Compound`] and [`List`] also implement the
+//! serde traits, so they can be used as fields of other types.
+//!
+//! Rust types are mapped to NBT as follows:
+//!
+//! - `bool` is a byte which is `0` or `1`.
+//! - `i8`, `i16`, `i32` and `i64` are bytes, shorts, ints and longs.
+//!   Unsigned integers use the next larger signed type, since NBT has no
+//!   unsigned types.
+//! - `f32` and `f64` are floats and doubles.
+//! - `char` and strings are strings.
+//! - Sequences and tuples are lists, whose elements must all have the same NBT
+//!   type. Use [`byte_array`], [`int_array`] or [`long_array`] with
+//!   `#[serde(with = "...")]` to write arrays instead.
+//! - Structs and maps are compounds. Map keys must be strings, characters or
+//!   integers. Fields which are `None` are left out.
+//! - Unit variants are strings containing the name of the variant. Other
+//!   variants are compounds with the name of the variant as the only key.
+//!
+//! # Example
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//! use valence_nbt::serde::{from_compound, int_array, to_compound};
+//! use valence_nbt::{compound, List};
+//!
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! struct Player {
+//!     name: String,
+//!     health: f32,
+//!     #[serde(with = "int_array")]
+//!     uuid: Vec<i32>,
+//!     pos: [f64; 3],
+//!     spawn: Option<[i32; 3]>,
+//! }
+//!
+//! let player = Player {
+//!     name: "Steve".into(),
+//!     health: 20.0,
+//!     uuid: vec![1, 2, 3, 4],
+//!     pos: [0.5, 64.0, 0.5],
+//!     spawn: None,
+//! };
+//!
+//! let c = to_compound(&player).unwrap();
+//!
+//! assert_eq!(
+//!     c,
+//!     compound! {
+//!         "name" => "Steve",
+//!         "health" => 20.0_f32,
+//!         "uuid" => vec![1, 2, 3, 4],
+//!         "pos" => List::Double(vec![0.5, 64.0, 0.5]),
+//!     }
+//! );
+//!
+//! assert_eq!(from_compound::<Player>(c).unwrap(), player);
+//! ```
+//!
+//! [serde]: https://serde.rs
+
+use std::fmt::Display;
+
+pub use de::{from_compound, from_value};
+pub use ser::{to_compound, to_value};
+
+use crate::tag::Tag;
+use crate::{Error, List, Value};
+
+mod de;
+mod ser;
+#[cfg(test)]
+mod tests;
+
+/// Newtype struct names marking sequences as arrays.
+const BYTE_ARRAY_TOKEN: &str = "__valence_nbt_byte_array";
+const INT_ARRAY_TOKEN: &str = "__valence_nbt_int_array";
+const LONG_ARRAY_TOKEN: &str = "__valence_nbt_long_array";
+/// Newtype struct name used when deserializing a [`Value`], which asks the
+/// deserializer to mark arrays so they aren't read back as lists.
+const VALUE_TOKEN: &str = "__valence_nbt_value";
+
+macro_rules! array_module {
+    ($name:ident, $ty:ty, $token:ident, $nbt_name:literal) => {
+        #[doc = concat!(
+            "Writes a sequence of `", stringify!($ty), "` as an NBT ", $nbt_name,
+            " instead of a list. Use with `#[serde(with = \"", stringify!($name), "\")]`.\n\n",
+            "Other serde formats see a plain sequence."
+        )]
+        pub mod $name {
+            use ::serde::{Deserialize, Deserializer, Serializer};
+
+            pub fn serialize<S: Serializer>(
+                array: &[$ty],
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct(super::$token, array)
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<Vec<$ty>, D::Error> {
+                Vec::deserialize(deserializer)
+            }
+        }
+    };
+}
+
+array_module!(byte_array, i8, BYTE_ARRAY_TOKEN, "byte array");
+array_module!(int_array, i32, INT_ARRAY_TOKEN, "int array");
+array_module!(long_array, i64, LONG_ARRAY_TOKEN, "long array");
+
+impl ::serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new_owned(msg.to_string())
+    }
+}
+
+impl ::serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new_owned(msg.to_string())
+    }
+}
+
+/// Collects values into a list. Fails if the values have different types.
+fn values_to_list(values: Vec<Value>) -> Result<List, Error> {
+    let Some(first) = values.first() else {
+        return Ok(List::End)
+    };
+
+    macro_rules! collect {
+        ($($variant:ident),*) => {
+            match first.get_tag() {
+                Tag::End => unreachable!("values cannot have the end tag"),
+                $(
+                    Tag::$variant => values
+                        .into_iter()
+                        .map(|v| match v {
+                            Value::$variant(v) => Ok(v),
+                            _ => Err(Error::new_static("NBT list elements must have the same type")),
+                        })
+                        .collect::<Result<_, _>>()
+                        .map(List::$variant),
+                )*
+            }
+        };
+    }
+
+    collect!(
+        Byte, Short, Int, Long, Float, Double, ByteArray, String, List, Compound, IntArray,
+        LongArray
+    )
+}
+
+/// Converts the elements of a list to values.
+fn list_to_values(list: List) -> Vec<Value> {
+    macro_rules! convert {
+        ($($variant:ident),*) => {
+            match list {
+                List::End => vec![],
+                $(List::$variant(v) => v.into_iter().map(Value::$variant).collect(),)*
+            }
+        };
+    }
+
+    convert!(
+        Byte, Short, Int, Long, Float, Double, ByteArray, String, List, Compound, IntArray,
+        LongArray
+    )
+}
@@ -0,0 +1,431 @@
+use std::fmt;
+use std::iter;
+
+use ::serde::de::value::{MapDeserializer, SeqDeserializer, StringDeserializer};
+use ::serde::de::{
+    DeserializeOwned, DeserializeSeed, EnumAccess, Error as _, IntoDeserializer, MapAccess,
+    SeqAccess, Unexpected, VariantAccess, Visitor,
+};
+use ::serde::{forward_to_deserialize_any, Deserialize, Deserializer};
+
+use super::{
+    list_to_values, values_to_list, BYTE_ARRAY_TOKEN, INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN,
+    VALUE_TOKEN,
+};
+use crate::{Compound, Error, List, Value};
+
+/// Converts NBT to a value of type `T`.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, Error> {
+    T::deserialize(value)
+}
+
+/// Converts an NBT compound to a value of type `T`.
+pub fn from_compound<T: DeserializeOwned>(compound: Compound) -> Result<T, Error> {
+    T::deserialize(Value::Compound(compound))
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(VALUE_TOKEN, ValueVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Compound {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CompoundVisitor;
+
+        impl<'de> Visitor<'de> for CompoundVisitor {
+            type Value = Compound;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an NBT compound")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Compound, A::Error> {
+                let mut compound = Compound::new();
+
+                while let Some((k, v)) = map.next_entry::<String, Value>()? {
+                    compound.insert(k, v);
+                }
+
+                Ok(compound)
+            }
+        }
+
+        deserializer.deserialize_map(CompoundVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for List {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::List(list) => Ok(list),
+            _ => Err(D::Error::custom("expected an NBT list")),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an NBT value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Byte(v as i8))
+    }
+
+    fn visit_i8<E>(self, v: i8) -> Result<Value, E> {
+        Ok(Value::Byte(v))
+    }
+
+    fn visit_i16<E>(self, v: i16) -> Result<Value, E> {
+        Ok(Value::Short(v))
+    }
+
+    fn visit_i32<E>(self, v: i32) -> Result<Value, E> {
+        Ok(Value::Int(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Long(v))
+    }
+
+    fn visit_u8<E>(self, v: u8) -> Result<Value, E> {
+        Ok(Value::Short(v.into()))
+    }
+
+    fn visit_u16<E>(self, v: u16) -> Result<Value, E> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn visit_u32<E>(self, v: u32) -> Result<Value, E> {
+        Ok(Value::Long(v.into()))
+    }
+
+    fn visit_u64<E: ::serde::de::Error>(self, v: u64) -> Result<Value, E> {
+        i64::try_from(v)
+            .map(Value::Long)
+            .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_f32<E>(self, v: f32) -> Result<Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Double(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.into()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::ByteArray(v.iter().map(|&b| b as i8).collect()))
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+
+        while let Some(v) = seq.next_element::<Value>()? {
+            values.push(v);
+        }
+
+        values_to_list(values)
+            .map(Value::List)
+            .map_err(A::Error::custom)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let Some(first_key) = map.next_key::<String>()? else {
+            return Ok(Value::Compound(Compound::new()))
+        };
+
+        // Arrays are marked by the NBT deserializer with a map containing a
+        // token as the only key.
+        match first_key.as_str() {
+            BYTE_ARRAY_TOKEN => return Ok(Value::ByteArray(map.next_value()?)),
+            INT_ARRAY_TOKEN => return Ok(Value::IntArray(map.next_value()?)),
+            LONG_ARRAY_TOKEN => return Ok(Value::LongArray(map.next_value()?)),
+            _ => {}
+        }
+
+        let mut compound = Compound::new();
+        compound.insert(first_key, map.next_value::<Value>()?);
+
+        while let Some((k, v)) = map.next_entry::<String, Value>()? {
+            compound.insert(k, v);
+        }
+
+        Ok(Value::Compound(compound))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Passes the elements of `iter` to `visitor` as a sequence.
+fn visit_seq<'de, V, I>(iter: I, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+    I: Iterator,
+    I::Item: IntoDeserializer<'de, Error>,
+{
+    let mut seq = SeqDeserializer::new(iter);
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+/// Passes the entries of `iter` to `visitor` as a map.
+fn visit_map<'de, V, I, K>(iter: I, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+    I: Iterator<Item = (K, Value)>,
+    K: IntoDeserializer<'de, Error>,
+{
+    let mut map = MapDeserializer::new(iter);
+    let value = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(value)
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Byte(v) => visitor.visit_i8(v),
+            Value::Short(v) => visitor.visit_i16(v),
+            Value::Int(v) => visitor.visit_i32(v),
+            Value::Long(v) => visitor.visit_i64(v),
+            Value::Float(v) => visitor.visit_f32(v),
+            Value::Double(v) => visitor.visit_f64(v),
+            Value::ByteArray(v) => visit_seq(v.into_iter(), visitor),
+            Value::String(v) => visitor.visit_string(v),
+            Value::List(v) => visit_seq(list_to_values(v).into_iter(), visitor),
+            Value::Compound(v) => {
+                visit_map(v.into_iter().map(|(k, v)| (KeyDeserializer(k), v)), visitor)
+            }
+            Value::IntArray(v) => visit_seq(v.into_iter(), visitor),
+            Value::LongArray(v) => visit_seq(v.into_iter(), visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Byte(v) => visitor.visit_bool(v != 0),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // Missing fields are `None`, so any value that is present is `Some`.
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Compound(c) if c.is_empty() => visitor.visit_unit(),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if name != VALUE_TOKEN {
+            return visitor.visit_newtype_struct(self);
+        }
+
+        // Mark arrays so the visitor doesn't read them as lists.
+        match self {
+            Value::ByteArray(v) => {
+                visit_map(iter::once((BYTE_ARRAY_TOKEN, Value::ByteArray(v))), visitor)
+            }
+            Value::IntArray(v) => {
+                visit_map(iter::once((INT_ARRAY_TOKEN, Value::IntArray(v))), visitor)
+            }
+            Value::LongArray(v) => {
+                visit_map(iter::once((LONG_ARRAY_TOKEN, Value::LongArray(v))), visitor)
+            }
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::ByteArray(v) => visitor.visit_byte_buf(v.into_iter().map(|b| b as u8).collect()),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            // Unit variants.
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            // Other variants, which have a compound with the variant as the only key.
+            Value::Compound(c) if c.len() == 1 => {
+                let (variant, value) = c.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            }
+            Value::Compound(_) => Err(Error::new_static("enum compound must have exactly one key")),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Value,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = Value;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Value), Error> {
+        let variant: StringDeserializer<Error> = self.variant.into_deserializer();
+        let variant = seed.deserialize(variant)?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Value {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+}
+
+/// Deserializes the keys of compounds. Keys are parsed if an integer is
+/// expected, since integer map keys are serialized as strings.
+struct KeyDeserializer(String);
+
+impl<'de> IntoDeserializer<'de, Error> for KeyDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => visitor.visit_string(self.0),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for KeyDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let variant: StringDeserializer<Error> = self.0.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 char str string bytes byte_buf unit unit_struct
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
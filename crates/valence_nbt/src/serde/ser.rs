@@ -0,0 +1,603 @@
+use ::serde::ser::{
+    Impossible, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant, Serializer,
+};
+
+use super::{values_to_list, BYTE_ARRAY_TOKEN, INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN};
+use crate::{Compound, Error, List, Value};
+
+/// Converts a value to NBT. Fails if `value` is `None` or can't be represented
+/// in NBT.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, Error> {
+    value
+        .serialize(ValueSerializer)?
+        .ok_or_else(|| Error::new_static("`None` can only be serialized as a field"))
+}
+
+/// Converts a value to an NBT compound. Fails if `value` does not serialize to
+/// a compound, like structs and maps do.
+pub fn to_compound<T: Serialize + ?Sized>(value: &T) -> Result<Compound, Error> {
+    match to_value(value)? {
+        Value::Compound(c) => Ok(c),
+        _ => Err(Error::new_static("value is not a compound")),
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Byte(v) => serializer.serialize_i8(*v),
+            Value::Short(v) => serializer.serialize_i16(*v),
+            Value::Int(v) => serializer.serialize_i32(*v),
+            Value::Long(v) => serializer.serialize_i64(*v),
+            Value::Float(v) => serializer.serialize_f32(*v),
+            Value::Double(v) => serializer.serialize_f64(*v),
+            Value::ByteArray(v) => Array(BYTE_ARRAY_TOKEN, v.as_slice()).serialize(serializer),
+            Value::String(v) => serializer.serialize_str(v),
+            Value::List(v) => v.serialize(serializer),
+            Value::Compound(v) => v.serialize(serializer),
+            Value::IntArray(v) => Array(INT_ARRAY_TOKEN, v.as_slice()).serialize(serializer),
+            Value::LongArray(v) => Array(LONG_ARRAY_TOKEN, v.as_slice()).serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for Compound {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self)
+    }
+}
+
+impl Serialize for List {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            List::End => serializer.serialize_seq(Some(0))?.end(),
+            List::Byte(v) => v.serialize(serializer),
+            List::Short(v) => v.serialize(serializer),
+            List::Int(v) => v.serialize(serializer),
+            List::Long(v) => v.serialize(serializer),
+            List::Float(v) => v.serialize(serializer),
+            List::Double(v) => v.serialize(serializer),
+            List::ByteArray(v) => {
+                serializer.collect_seq(v.iter().map(|a| Array(BYTE_ARRAY_TOKEN, a.as_slice())))
+            }
+            List::String(v) => v.serialize(serializer),
+            List::List(v) => v.serialize(serializer),
+            List::Compound(v) => v.serialize(serializer),
+            List::IntArray(v) => {
+                serializer.collect_seq(v.iter().map(|a| Array(INT_ARRAY_TOKEN, a.as_slice())))
+            }
+            List::LongArray(v) => {
+                serializer.collect_seq(v.iter().map(|a| Array(LONG_ARRAY_TOKEN, a.as_slice())))
+            }
+        }
+    }
+}
+
+/// An array marked with one of the array tokens.
+struct Array<'a, T>(&'static str, &'a [T]);
+
+impl<T: Serialize> Serialize for Array<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(self.0, self.1)
+    }
+}
+
+/// Serializes to a [`Value`], or to `None` for `None`, which is left out of
+/// compounds.
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeVariant<SerializeList>;
+    type SerializeMap = SerializeCompound;
+    type SerializeStruct = SerializeCompound;
+    type SerializeStructVariant = SerializeVariant<SerializeCompound>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Byte(v as i8)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Long(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Short(v.into())))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Int(v.into())))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Long(v.into())))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        let v = i64::try_from(v).map_err(|_| Error::new_static("u64 is too large for NBT"))?;
+        Ok(Some(Value::Long(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::String(v.into())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::String(v.into())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::ByteArray(v.iter().map(|&b| b as i8).collect())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Compound(Compound::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        if !matches!(name, BYTE_ARRAY_TOKEN | INT_ARRAY_TOKEN | LONG_ARRAY_TOKEN) {
+            return value.serialize(self);
+        }
+
+        let Some(Value::List(list)) = value.serialize(self)? else {
+            return Err(Error::new_static("NBT array is not a sequence"))
+        };
+
+        Ok(Some(match (name, list) {
+            (BYTE_ARRAY_TOKEN, List::End) => Value::ByteArray(vec![]),
+            (INT_ARRAY_TOKEN, List::End) => Value::IntArray(vec![]),
+            (LONG_ARRAY_TOKEN, List::End) => Value::LongArray(vec![]),
+            (BYTE_ARRAY_TOKEN, List::Byte(v)) => Value::ByteArray(v),
+            (INT_ARRAY_TOKEN, List::Int(v)) => Value::IntArray(v),
+            (LONG_ARRAY_TOKEN, List::Long(v)) => Value::LongArray(v),
+            _ => return Err(Error::new_static("NBT array elements have the wrong type")),
+        }))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        let mut compound = Compound::new();
+
+        if let Some(value) = value.serialize(self)? {
+            compound.insert(variant, value);
+        }
+
+        Ok(Some(Value::Compound(compound)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Ok(SerializeList {
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(SerializeCompound {
+            compound: Compound::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SerializeList {
+    values: Vec<Value>,
+}
+
+impl SerializeSeq for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let value = value
+            .serialize(ValueSerializer)?
+            .ok_or_else(|| Error::new_static("NBT lists cannot contain `None`"))?;
+
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::List(values_to_list(self.values)?)))
+    }
+}
+
+impl SerializeTuple for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl SerializeTupleStruct for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+struct SerializeCompound {
+    compound: Compound,
+    /// The key of the entry being serialized.
+    key: Option<String>,
+}
+
+impl SerializeMap for SerializeCompound {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::new_static("map value serialized before its key"))?;
+
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            self.compound.insert(key, value);
+        }
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(Value::Compound(self.compound)))
+    }
+}
+
+impl SerializeStruct for SerializeCompound {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            self.compound.insert(key, value);
+        }
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        SerializeMap::end(self)
+    }
+}
+
+/// Wraps the value of an enum variant in a compound with the name of the
+/// variant as the only key.
+struct SerializeVariant<T> {
+    variant: &'static str,
+    inner: T,
+}
+
+impl<T> SerializeVariant<T> {
+    fn wrap(variant: &'static str, value: Option<Value>) -> Option<Value> {
+        let mut compound = Compound::new();
+
+        if let Some(value) = value {
+            compound.insert(variant, value);
+        }
+
+        Some(Value::Compound(compound))
+    }
+}
+
+impl SerializeTupleVariant for SerializeVariant<SerializeList> {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Self::wrap(self.variant, SerializeSeq::end(self.inner)?))
+    }
+}
+
+impl SerializeStructVariant for SerializeVariant<SerializeCompound> {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Self::wrap(self.variant, SerializeMap::end(self.inner)?))
+    }
+}
+
+/// Serializes the keys of maps, which must be strings in NBT. Characters and
+/// integers are converted to strings.
+struct KeySerializer;
+
+impl KeySerializer {
+    fn unsupported<T>() -> Result<T, Error> {
+        Err(Error::new_static(
+            "NBT compound keys must be strings, characters or integers",
+        ))
+    }
+}
+
+impl Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<String, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.into())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Self::unsupported()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Self::unsupported()
+    }
+}
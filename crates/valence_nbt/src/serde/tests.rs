@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use ::serde::{Deserialize, Serialize};
+
+use super::*;
+use crate::{compound, Compound};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Point(i32, i32),
+    Rect { w: u16, h: u16 },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Everything {
+    flag: bool,
+    small: u8,
+    big: i64,
+    name: String,
+    missing: Option<String>,
+    present: Option<i16>,
+    #[serde(with = "byte_array")]
+    bytes: Vec<i8>,
+    #[serde(with = "long_array")]
+    longs: Vec<i64>,
+    list: Vec<(i32, i32)>,
+    shape: Shape,
+    shapes: Vec<Shape>,
+    scores: BTreeMap<u32, f32>,
+    extra: Compound,
+}
+
+#[test]
+fn struct_round_trip() {
+    let value = Everything {
+        flag: true,
+        small: 200,
+        big: -5,
+        name: "hello".into(),
+        missing: None,
+        present: Some(3),
+        bytes: vec![1, -2],
+        longs: vec![],
+        list: vec![(1, 2), (3, 4)],
+        shape: Shape::Empty,
+        shapes: vec![
+            Shape::Circle(1.5),
+            Shape::Point(1, 2),
+            Shape::Rect { w: 3, h: 4 },
+        ],
+        scores: BTreeMap::from([(7, 1.0)]),
+        extra: compound! { "heights" => vec![1_i64, 2, 3], "uuid" => vec![1, 2, 3, 4] },
+    };
+
+    let c = to_compound(&value).unwrap();
+
+    assert_eq!(c.get("flag"), Some(&Value::Byte(1)));
+    assert_eq!(c.get("small"), Some(&Value::Short(200)));
+    assert_eq!(c.get("missing"), None);
+    assert_eq!(c.get("bytes"), Some(&Value::ByteArray(vec![1, -2])));
+    assert_eq!(c.get("longs"), Some(&Value::LongArray(vec![])));
+    assert_eq!(
+        c.get("list"),
+        Some(&Value::List(List::List(vec![
+            List::Int(vec![1, 2]),
+            List::Int(vec![3, 4])
+        ])))
+    );
+    assert_eq!(c.get("shape"), Some(&Value::String("Empty".into())));
+    assert_eq!(
+        c.get("shapes"),
+        Some(&Value::List(List::Compound(vec![
+            compound! { "Circle" => 1.5 },
+            compound! { "Point" => List::Int(vec![1, 2]) },
+            compound! { "Rect" => compound! { "w" => 3, "h" => 4 } },
+        ])))
+    );
+    assert_eq!(c.get("scores"), Some(&compound! { "7" => 1.0_f32 }.into()));
+    // Arrays in compounds are preserved.
+    assert_eq!(c.get("extra"), Some(&value.extra.clone().into()));
+
+    assert_eq!(from_compound::<Everything>(c).unwrap(), value);
+}
+
+#[test]
+fn mixed_list_is_error() {
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Either {
+        Int(i32),
+        Str(&'static str),
+    }
+
+    assert!(to_value(&vec![Either::Int(1), Either::Str("a")]).is_err());
+    assert!(to_value(&Option::<i32>::None).is_err());
+    assert!(to_compound(&5).is_err());
+}
+
+#[test]
+fn value_round_trip() {
+    let value: Value = compound! {
+        "byte_array" => vec![1_i8, 2],
+        "int_array" => vec![1, 2],
+        "lists" => List::LongArray(vec![vec![1, 2], vec![]]),
+        "empty" => List::End,
+    }
+    .into();
+
+    assert_eq!(to_value(&value).unwrap(), value);
+    assert_eq!(from_value::<Value>(value.clone()).unwrap(), value);
+}
@@ -368,36 +368,16 @@ impl<'a> SnbtReader<'a> {
     }
 
     fn parse_primitive(&mut self) -> Result<Value> {
-        macro_rules! try_ret {
-            // Try possible solution until one works
-            ($v:expr) => {{
-                match $v {
-                    Ok(v) => return Ok(v.into()),
-                    Err(_) => (),
-                }
-            }};
-        }
         let target = self.read_unquoted_string()?;
-        match target
-            .bytes()
-            .last()
-            .ok_or_else(|| self.make_error(SnbtErrorKind::ExpectValue))?
-        {
-            b'b' | b'B' => try_ret!(target[..target.len() - 1].parse::<i8>()),
-            b's' | b'S' => try_ret!(target[..target.len() - 1].parse::<i16>()),
-            b'l' | b'L' => try_ret!(target[..target.len() - 1].parse::<i64>()),
-            b'f' | b'F' => try_ret!(target[..target.len() - 1].parse::<f32>()),
-            b'd' | b'D' => try_ret!(target[..target.len() - 1].parse::<f64>()),
-            _ => (),
-        }
-        match target.as_str() {
-            "true" => return Ok(Value::Byte(1)),
-            "false" => return Ok(Value::Byte(0)),
-            _ => {
-                try_ret!(target.parse::<i32>());
-                try_ret!(target.parse::<f64>());
-            }
-        };
+
+        if target.is_empty() {
+            return Err(self.make_error(SnbtErrorKind::ExpectValue));
+        }
+
+        if let Some(value) = parse_unquoted_number(&target) {
+            return Ok(value);
+        }
+
         if target.len() > STRING_MAX_LEN {
             return Err(self.make_error(SnbtErrorKind::LongString));
         }
@@ -439,7 +419,7 @@ impl<'a> SnbtReader<'a> {
 /// See [the wiki](https://minecraft.gamepedia.com/NBT_format#SNBT_format) for more information.
 /// # Example
 /// ```
-/// use valence_nbt::snbt::SnbtReader;
+/// use valence_nbt::snbt::from_snbt_str;
 /// use valence_nbt::Value;
 /// let value = from_snbt_str("1f").unwrap();
 /// assert_eq!(value, Value::Float(1.0));
@@ -448,53 +428,198 @@ pub fn from_snbt_str(snbt: &str) -> Result<Value> {
     SnbtReader::new(snbt).read()
 }
 
+/// Parses an unquoted string as a number or boolean the way vanilla does.
+/// Returns `None` if the string is not one and should be read as a string.
+fn parse_unquoted_number(s: &str) -> Option<Value> {
+    match s {
+        "true" => return Some(Value::Byte(1)),
+        "false" => return Some(Value::Byte(0)),
+        _ => {}
+    }
+
+    let (body, suffix) = s.split_at(s.len().saturating_sub(1));
+
+    match suffix {
+        "b" | "B" if is_integer(body) => return body.parse::<i8>().ok().map(Value::Byte),
+        "s" | "S" if is_integer(body) => return body.parse::<i16>().ok().map(Value::Short),
+        "l" | "L" if is_integer(body) => return body.parse::<i64>().ok().map(Value::Long),
+        "f" | "F" if is_decimal(body, true) => return body.parse::<f32>().ok().map(Value::Float),
+        "d" | "D" if is_decimal(body, true) => return body.parse::<f64>().ok().map(Value::Double),
+        _ => {}
+    }
+
+    if is_integer(s) {
+        // Integers which don't fit are strings, like in vanilla.
+        s.parse::<i32>().ok().map(Value::Int)
+    } else if is_decimal(s, false) {
+        s.parse::<f64>().ok().map(Value::Double)
+    } else {
+        None
+    }
+}
+
+/// Matches `[-+]?(0|[1-9][0-9]*)`.
+fn is_integer(s: &str) -> bool {
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+
+    match digits.as_bytes() {
+        [b'0'] => true,
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    }
+}
+
+/// Matches `[-+]?([0-9]+\.|[0-9]*\.[0-9]+)(e[-+]?[0-9]+)?`, ignoring case. The
+/// decimal point is optional when `dot_optional` is set, which is the case for
+/// numbers with a suffix.
+fn is_decimal(s: &str, dot_optional: bool) -> bool {
+    let s = s.strip_prefix(['-', '+']).unwrap_or(s);
+
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+
+    let mantissa_ok = match mantissa.split_once('.') {
+        Some((int, frac)) => {
+            (!int.is_empty() || !frac.is_empty())
+                && int.bytes().all(|b| b.is_ascii_digit())
+                && frac.bytes().all(|b| b.is_ascii_digit())
+        }
+        None => {
+            dot_optional && !mantissa.is_empty() && mantissa.bytes().all(|b| b.is_ascii_digit())
+        }
+    };
+
+    let exponent_ok = match exponent {
+        Some(e) => {
+            let e = e.strip_prefix(['-', '+']).unwrap_or(e);
+            !e.is_empty() && e.bytes().all(|b| b.is_ascii_digit())
+        }
+        None => true,
+    };
+
+    mantissa_ok && exponent_ok
+}
+
+/// Returns if `c` can be part of an unquoted string.
+fn is_unquoted_char(c: char) -> bool {
+    matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' | '+' | '.')
+}
+
 pub struct SnbtWriter<'a> {
     output: &'a mut String,
+    /// The string used to indent nested values, or `None` to write everything
+    /// on one line.
+    indent: Option<&'a str>,
+    depth: usize,
 }
 
 impl<'a> SnbtWriter<'a> {
     pub fn new(output: &'a mut String) -> Self {
-        Self { output }
+        Self {
+            output,
+            indent: None,
+            depth: 0,
+        }
     }
 
-    fn write_string(&mut self, s: &str) {
-        let mut need_quote = false;
-        for c in s.chars() {
-            if !matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' | '+' | '.') {
-                need_quote = true;
-                break;
-            }
+    /// Creates a writer which puts the entries of compounds and lists of
+    /// compounds or lists on their own lines, indented with `indent`.
+    pub fn new_pretty(output: &'a mut String, indent: &'a str) -> Self {
+        Self {
+            output,
+            indent: Some(indent),
+            depth: 0,
         }
-        if need_quote {
-            self.output.push('"');
-            for c in s.chars() {
-                match c {
-                    '"' => self.output.push_str("\\\""),
-                    '\\' => self.output.push_str("\\\\"),
-                    _ => self.output.push(c),
-                }
+    }
+
+    fn new_line(&mut self) {
+        if let Some(indent) = self.indent {
+            self.output.push('\n');
+            for _ in 0..self.depth {
+                self.output.push_str(indent);
             }
-            self.output.push('"');
+        }
+    }
+
+    fn write_key(&mut self, k: &str) {
+        if !k.is_empty() && k.chars().all(is_unquoted_char) {
+            self.output.push_str(k);
         } else {
+            self.write_quoted(k);
+        }
+    }
+
+    fn write_string(&mut self, s: &str) {
+        // Strings which would be read back as numbers or booleans are quoted.
+        if !s.is_empty() && s.chars().all(is_unquoted_char) && parse_unquoted_number(s).is_none() {
             self.output.push_str(s);
+        } else {
+            self.write_quoted(s);
         }
     }
 
-    fn write_primitive_array<'b>(
+    fn write_quoted(&mut self, s: &str) {
+        self.output.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.output.push_str("\\\""),
+                '\\' => self.output.push_str("\\\\"),
+                _ => self.output.push(c),
+            }
+        }
+        self.output.push('"');
+    }
+
+    /// Writes a list or array. `prefix` is the type of the array, if any.
+    /// Elements are put on their own lines if `multiline` is set and the
+    /// writer is pretty.
+    fn write_seq<T>(
         &mut self,
         prefix: &str,
-        iter: impl Iterator<Item = &'b (impl Into<Value> + 'b + Copy)>,
+        items: &[T],
+        multiline: bool,
+        mut write: impl FnMut(&mut Self, &T),
     ) {
+        let multiline = multiline && self.indent.is_some() && !items.is_empty();
+
         self.output.push('[');
-        self.output.push_str(prefix);
-        let mut first = true;
-        for v in iter {
-            if !first {
+
+        if !prefix.is_empty() {
+            self.output.push_str(prefix);
+            self.output.push(';');
+
+            if self.indent.is_some() && !items.is_empty() {
+                self.output.push(' ');
+            }
+        }
+
+        if multiline {
+            self.depth += 1;
+        }
+
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
                 self.output.push(',');
+
+                if self.indent.is_some() && !multiline {
+                    self.output.push(' ');
+                }
             }
-            first = false;
-            self.write_element(&(*v).into());
+
+            if multiline {
+                self.new_line();
+            }
+
+            write(self, item);
+        }
+
+        if multiline {
+            self.depth -= 1;
+            self.new_line();
         }
+
         self.output.push(']');
     }
 
@@ -504,55 +629,62 @@ impl<'a> SnbtWriter<'a> {
     }
 
     fn write_list(&mut self, list: &List) {
-        macro_rules! variant_impl {
-            ($v:expr, $handle:expr) => {{
-                self.output.push('[');
-                let mut first = true;
-                for v in $v.iter() {
-                    if !first {
-                        self.output.push(',');
-                    }
-                    first = false;
-                    $handle(v);
-                }
-                self.output.push(']');
-            }};
-        }
         match list {
-            List::Byte(v) => variant_impl!(v, |v| self.write_primitive("b", v)),
-            List::Short(v) => variant_impl!(v, |v| self.write_primitive("s", v)),
-            List::Int(v) => variant_impl!(v, |v| self.write_primitive("", v)),
-            List::Long(v) => variant_impl!(v, |v| self.write_primitive("l", v)),
-            List::Float(v) => variant_impl!(v, |v| self.write_primitive("f", v)),
-            List::Double(v) => variant_impl!(v, |v| self.write_primitive("d", v)),
-            List::ByteArray(v) => {
-                variant_impl!(v, |v: &Vec<i8>| self.write_primitive_array("B", v.iter()))
-            }
-            List::IntArray(v) => {
-                variant_impl!(v, |v: &Vec<i32>| self.write_primitive_array("", v.iter()))
-            }
-            List::LongArray(v) => {
-                variant_impl!(v, |v: &Vec<i64>| self.write_primitive_array("L", v.iter()))
-            }
-            List::String(v) => variant_impl!(v, |v| self.write_string(v)),
-            List::List(v) => variant_impl!(v, |v| self.write_list(v)),
-            List::Compound(v) => variant_impl!(v, |v| self.write_compound(v)),
+            List::Byte(v) => self.write_seq("", v, false, |w, v| w.write_primitive("b", v)),
+            List::Short(v) => self.write_seq("", v, false, |w, v| w.write_primitive("s", v)),
+            List::Int(v) => self.write_seq("", v, false, |w, v| w.write_primitive("", v)),
+            List::Long(v) => self.write_seq("", v, false, |w, v| w.write_primitive("l", v)),
+            List::Float(v) => self.write_seq("", v, false, |w, v| w.write_primitive("f", v)),
+            List::Double(v) => self.write_seq("", v, false, |w, v| w.write_primitive("d", v)),
+            List::ByteArray(v) => self.write_seq("", v, false, |w, v| w.write_byte_array(v)),
+            List::IntArray(v) => self.write_seq("", v, false, |w, v| w.write_int_array(v)),
+            List::LongArray(v) => self.write_seq("", v, false, |w, v| w.write_long_array(v)),
+            List::String(v) => self.write_seq("", v, false, |w, v| w.write_string(v)),
+            List::List(v) => self.write_seq("", v, true, |w, v| w.write_list(v)),
+            List::Compound(v) => self.write_seq("", v, true, |w, v| w.write_compound(v)),
             List::End => self.output.push_str("[]"),
         }
     }
 
+    fn write_byte_array(&mut self, v: &[i8]) {
+        self.write_seq("B", v, false, |w, v| w.write_primitive("b", v))
+    }
+
+    fn write_int_array(&mut self, v: &[i32]) {
+        self.write_seq("I", v, false, |w, v| w.write_primitive("", v))
+    }
+
+    fn write_long_array(&mut self, v: &[i64]) {
+        self.write_seq("L", v, false, |w, v| w.write_primitive("l", v))
+    }
+
     fn write_compound(&mut self, compound: &Compound) {
         self.output.push('{');
+
+        if compound.is_empty() {
+            self.output.push('}');
+            return;
+        }
+
+        self.depth += 1;
+
         let mut first = true;
         for (k, v) in compound.iter() {
             if !first {
                 self.output.push(',');
             }
             first = false;
-            self.write_string(k);
+            self.new_line();
+            self.write_key(k);
             self.output.push(':');
+            if self.indent.is_some() {
+                self.output.push(' ');
+            }
             self.write_element(v);
         }
+
+        self.depth -= 1;
+        self.new_line();
         self.output.push('}');
     }
 
@@ -566,9 +698,9 @@ impl<'a> SnbtWriter<'a> {
             Long(v) => self.write_primitive("l", v),
             Float(v) => self.write_primitive("f", v),
             Double(v) => self.write_primitive("d", v),
-            ByteArray(v) => self.write_primitive_array("B;", v.iter()),
-            IntArray(v) => self.write_primitive_array("I;", v.iter()),
-            LongArray(v) => self.write_primitive_array("L;", v.iter()),
+            ByteArray(v) => self.write_byte_array(v),
+            IntArray(v) => self.write_int_array(v),
+            LongArray(v) => self.write_long_array(v),
             String(v) => self.write_string(v),
             List(v) => self.write_list(v),
             Compound(v) => self.write_compound(v),
@@ -584,6 +716,33 @@ pub fn to_snbt_string(value: &Value) -> String {
     output
 }
 
+/// Convert a value to a string in SNBT format with the entries of compounds
+/// on their own lines, indented with four spaces. This is easier to read and
+/// edit by hand, e.g. in config files.
+///
+/// # Example
+/// ```
+/// use valence_nbt::compound;
+/// use valence_nbt::snbt::to_snbt_string_pretty;
+///
+/// let c = compound! {
+///     "name" => "Steve",
+///     "pos" => valence_nbt::List::Double(vec![1.0, 2.5, 3.0]),
+/// };
+///
+/// # #[cfg(feature = "preserve_order")]
+/// assert_eq!(
+///     to_snbt_string_pretty(&c.into()),
+///     "{\n    name: Steve,\n    pos: [1d, 2.5d, 3d]\n}"
+/// );
+/// ```
+pub fn to_snbt_string_pretty(value: &Value) -> String {
+    let mut output = String::new();
+    let mut writer = SnbtWriter::new_pretty(&mut output, "    ");
+    writer.write_element(value);
+    output
+}
+
 impl Display for SnbtWriter<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.output)
@@ -594,6 +753,7 @@ impl Display for SnbtWriter<'_> {
 mod tests {
 
     use super::*;
+    use crate::compound;
 
     #[test]
     fn test_parse() {
@@ -702,4 +862,51 @@ mod tests {
              [L;1l,2l,3l]},empty:[Bibabo]}"
         );
     }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(from_snbt_str("1e5d").unwrap(), Value::Double(1e5));
+        assert_eq!(from_snbt_str("-.5").unwrap(), Value::Double(-0.5));
+        assert_eq!(from_snbt_str("3.F").unwrap(), Value::Float(3.0));
+        assert_eq!(from_snbt_str("-128B").unwrap(), Value::Byte(-128));
+        assert_eq!(from_snbt_str("+7s").unwrap(), Value::Short(7));
+
+        // These are strings in vanilla.
+        for s in [
+            "128b",
+            "2147483648",
+            "007",
+            "1e5",
+            "inf",
+            "NaN",
+            "1.0b",
+            ".",
+        ] {
+            assert_eq!(from_snbt_str(s).unwrap(), Value::String(s.into()), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let value: Value = compound! {
+            "int" => 5,
+            "number_string" => "123",
+            "bool_string" => "true",
+            "empty" => "",
+            "arrays" => List::ByteArray(vec![vec![1, 2], vec![]]),
+            "nested" => List::Compound(vec![compound! { "a" => 1_i8 }, Compound::new()]),
+            "long_array" => vec![1_i64, -2],
+        }
+        .into();
+
+        let compact = to_snbt_string(&value);
+        assert!(compact.contains("number_string:\"123\""));
+        assert!(compact.contains("[[B;1b,2b],[B;]]"));
+        assert_eq!(from_snbt_str(&compact).unwrap(), value);
+
+        let pretty = to_snbt_string_pretty(&value);
+        assert!(pretty.contains("\n    long_array: [L; 1l, -2l]"));
+        assert!(pretty.contains("\n        {\n            a: 1b\n        },\n        {}\n"));
+        assert_eq!(from_snbt_str(&pretty).unwrap(), value);
+    }
 }
@@ -0,0 +1,602 @@
+//! A pull-based NBT decoder.
+//!
+//! [`NbtReader`] decodes binary NBT one [`Event`] at a time, so large
+//! structures such as Anvil chunks can be inspected without building the
+//! whole [`Compound`] in memory. Entries that aren't needed can be skipped
+//! without allocating with [`NbtReader::skip_value`], and entries that are
+//! needed can be read in full with [`NbtReader::read_value`].
+//!
+//! # Examples
+//!
+//! ```
+//! use valence_nbt::stream::{Event, NbtReader};
+//! use valence_nbt::{compound, to_binary_writer, Value};
+//!
+//! let c = compound! {
+//!     "DataVersion" => 3218,
+//!     "sections" => vec![0_i64; 4096],
+//! };
+//!
+//! let mut buf = vec![];
+//! to_binary_writer(&mut buf, &c, "").unwrap();
+//!
+//! let mut reader = NbtReader::new(buf.as_slice());
+//! let mut data_version = None;
+//!
+//! while let Some(event) = reader.next_event().unwrap() {
+//!     if let Event::Name(name) = event {
+//!         // Step into the root compound instead of skipping it.
+//!         if reader.depth() == 0 {
+//!             continue;
+//!         }
+//!
+//!         if reader.depth() == 1 && name == "DataVersion" {
+//!             data_version = Some(reader.read_value().unwrap());
+//!         } else {
+//!             reader.skip_value().unwrap();
+//!         }
+//!     }
+//! }
+//!
+//! assert_eq!(data_version, Some(Value::Int(3218)));
+//! ```
+
+use std::io;
+use std::io::Read;
+
+use byteorder::{BigEndian, ReadBytesExt};
+use cesu8::Cesu8DecodingError;
+
+use crate::tag::Tag;
+use crate::{Compound, Error, List, Result, Value};
+
+/// Maximum nesting depth of compounds and lists.
+const MAX_DEPTH: usize = 512;
+
+/// Upper bound on the capacity reserved for lists and arrays ahead of reading
+/// their elements. The length prefix cannot be checked against the size of
+/// the input, so larger collections grow as their elements are read.
+const MAX_PREALLOC: usize = 4096;
+
+/// A single step of NBT data produced by [`NbtReader`].
+#[derive(Clone, PartialEq, Debug)]
+pub enum Event {
+    /// The name of the next entry in the current compound, or of the root
+    /// compound. The value of the entry follows.
+    Name(String),
+    /// The start of a compound. Its entries follow until
+    /// [`Event::CompoundEnd`].
+    CompoundStart,
+    /// The end of the compound most recently started.
+    CompoundEnd,
+    /// The start of a list of `len` elements with the tag `element_tag`. The
+    /// elements follow until [`Event::ListEnd`].
+    ListStart {
+        element_tag: Tag,
+        len: usize,
+    },
+    /// The end of the list most recently started.
+    ListEnd,
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+/// Decodes uncompressed binary NBT from a reader as a sequence of [`Event`]s.
+///
+/// A root compound named `name` with a single byte entry produces the events
+/// `Name(name)`, `CompoundStart`, `Name(key)`, `Byte(value)` and
+/// `CompoundEnd`, after which [`NbtReader::next_event`] returns `None`.
+#[derive(Debug)]
+pub struct NbtReader<R> {
+    reader: R,
+    /// The compounds and lists currently open.
+    stack: Vec<Frame>,
+    /// Tag of the value whose name was just returned.
+    pending: Option<Tag>,
+    started: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Frame {
+    Compound,
+    List { element_tag: Tag, remaining: usize },
+}
+
+impl<R: Read> NbtReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            stack: vec![],
+            pending: None,
+            started: false,
+        }
+    }
+
+    /// Returns the number of compounds and lists currently open. Entries of
+    /// the root compound are at depth `1`.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Decodes the next event. Returns `None` once the root compound has been
+    /// closed, or immediately if the input starts with `TAG_End`.
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        if let Some(tag) = self.pending.take() {
+            return self.start_value(tag).map(Some);
+        }
+
+        match self.stack.last_mut() {
+            None => {
+                if self.started {
+                    return Ok(None);
+                }
+
+                self.started = true;
+
+                match self.read_tag()? {
+                    // Empty root, as in `from_binary_slice`.
+                    Tag::End => Ok(None),
+                    Tag::Compound => {
+                        let name = self.read_string()?;
+                        self.pending = Some(Tag::Compound);
+                        Ok(Some(Event::Name(name)))
+                    }
+                    tag => Err(Error::new_owned(format!(
+                        "expected root tag for compound (got {tag})"
+                    ))),
+                }
+            }
+            Some(Frame::Compound) => match self.read_tag()? {
+                Tag::End => {
+                    self.stack.pop();
+                    Ok(Some(Event::CompoundEnd))
+                }
+                tag => {
+                    let name = self.read_string()?;
+                    self.pending = Some(tag);
+                    Ok(Some(Event::Name(name)))
+                }
+            },
+            Some(Frame::List {
+                element_tag,
+                remaining,
+            }) => {
+                if *remaining == 0 {
+                    self.stack.pop();
+                    return Ok(Some(Event::ListEnd));
+                }
+
+                *remaining -= 1;
+                let tag = *element_tag;
+                self.start_value(tag).map(Some)
+            }
+        }
+    }
+
+    /// Decodes the next value in full. This is the value of the entry whose
+    /// [`Event::Name`] was just returned, or the next element of the current
+    /// list.
+    pub fn read_value(&mut self) -> Result<Value> {
+        let tag = self.take_value_tag()?;
+        let event = self.start_value(tag)?;
+        self.build_value(event)
+    }
+
+    /// Skips over the next value without allocating. Which value is skipped
+    /// is the same as for [`NbtReader::read_value`].
+    pub fn skip_value(&mut self) -> Result<()> {
+        let tag = self.take_value_tag()?;
+        self.skip_tag(tag, self.stack.len())
+    }
+
+    fn take_value_tag(&mut self) -> Result<Tag> {
+        if let Some(tag) = self.pending.take() {
+            return Ok(tag);
+        }
+
+        match self.stack.last_mut() {
+            Some(Frame::List {
+                element_tag,
+                remaining,
+            }) if *remaining > 0 => {
+                *remaining -= 1;
+                Ok(*element_tag)
+            }
+            _ => Err(Error::new_static("no value to read at this position")),
+        }
+    }
+
+    fn start_value(&mut self, tag: Tag) -> Result<Event> {
+        Ok(match tag {
+            Tag::End => unreachable!("illegal TAG_End argument"),
+            Tag::Byte => Event::Byte(self.reader.read_i8()?),
+            Tag::Short => Event::Short(self.reader.read_i16::<BigEndian>()?),
+            Tag::Int => Event::Int(self.reader.read_i32::<BigEndian>()?),
+            Tag::Long => Event::Long(self.reader.read_i64::<BigEndian>()?),
+            Tag::Float => Event::Float(self.reader.read_f32::<BigEndian>()?),
+            Tag::Double => Event::Double(self.reader.read_f64::<BigEndian>()?),
+            Tag::ByteArray => {
+                let len = self.read_len(tag)?;
+                let mut bytes = Vec::with_capacity(len.min(MAX_PREALLOC));
+                (&mut self.reader)
+                    .take(len as u64)
+                    .read_to_end(&mut bytes)?;
+
+                if bytes.len() != len {
+                    return Err(unexpected_eof());
+                }
+
+                Event::ByteArray(bytes.into_iter().map(|b| b as i8).collect())
+            }
+            Tag::String => Event::String(self.read_string()?),
+            Tag::List => {
+                self.check_depth()?;
+
+                let element_tag = self.read_tag()?;
+                let len = self.read_len(tag)?;
+
+                if element_tag == Tag::End && len != 0 {
+                    return Err(Error::new_owned(format!(
+                        "TAG_End list with nonzero length of {len}"
+                    )));
+                }
+
+                self.stack.push(Frame::List {
+                    element_tag,
+                    remaining: len,
+                });
+
+                Event::ListStart { element_tag, len }
+            }
+            Tag::Compound => {
+                self.check_depth()?;
+                self.stack.push(Frame::Compound);
+                Event::CompoundStart
+            }
+            Tag::IntArray => {
+                let len = self.read_len(tag)?;
+                let mut array = Vec::with_capacity(len.min(MAX_PREALLOC));
+                for _ in 0..len {
+                    array.push(self.reader.read_i32::<BigEndian>()?);
+                }
+                Event::IntArray(array)
+            }
+            Tag::LongArray => {
+                let len = self.read_len(tag)?;
+                let mut array = Vec::with_capacity(len.min(MAX_PREALLOC));
+                for _ in 0..len {
+                    array.push(self.reader.read_i64::<BigEndian>()?);
+                }
+                Event::LongArray(array)
+            }
+        })
+    }
+
+    /// Builds a value from the event that starts it, reading the rest of the
+    /// value if it is a compound or list.
+    fn build_value(&mut self, event: Event) -> Result<Value> {
+        Ok(match event {
+            Event::Byte(v) => v.into(),
+            Event::Short(v) => v.into(),
+            Event::Int(v) => v.into(),
+            Event::Long(v) => v.into(),
+            Event::Float(v) => v.into(),
+            Event::Double(v) => v.into(),
+            Event::ByteArray(v) => v.into(),
+            Event::String(v) => v.into(),
+            Event::IntArray(v) => v.into(),
+            Event::LongArray(v) => v.into(),
+            Event::CompoundStart => {
+                let mut compound = Compound::new();
+
+                loop {
+                    match self.next_event()? {
+                        Some(Event::Name(name)) => {
+                            let value = self.read_value()?;
+                            compound.insert(name, value);
+                        }
+                        Some(Event::CompoundEnd) => break,
+                        _ => unreachable!("compound entries start with a name"),
+                    }
+                }
+
+                compound.into()
+            }
+            Event::ListStart { element_tag, len } => self.build_list(element_tag, len)?.into(),
+            Event::Name(_) | Event::CompoundEnd | Event::ListEnd => {
+                unreachable!("event does not start a value")
+            }
+        })
+    }
+
+    /// Reads the elements of the list that was just started, along with its
+    /// end.
+    fn build_list(&mut self, element_tag: Tag, len: usize) -> Result<List> {
+        macro_rules! elements {
+            ($($variant:ident),*) => {
+                match element_tag {
+                    Tag::End => List::End,
+                    $(
+                        Tag::$variant => {
+                            let mut elems = Vec::with_capacity(len.min(MAX_PREALLOC));
+                            for _ in 0..len {
+                                match self.read_value()? {
+                                    Value::$variant(v) => elems.push(v),
+                                    _ => unreachable!("list elements have the element tag"),
+                                }
+                            }
+                            List::$variant(elems)
+                        }
+                    )*
+                }
+            };
+        }
+
+        let list = elements!(
+            Byte, Short, Int, Long, Float, Double, ByteArray, String, List, Compound, IntArray,
+            LongArray
+        );
+
+        match self.next_event()? {
+            Some(Event::ListEnd) => Ok(list),
+            _ => unreachable!("list ends after its elements"),
+        }
+    }
+
+    /// Skips a value with the given tag. `depth` is the nesting depth of the
+    /// value, which is checked without touching the stack.
+    fn skip_tag(&mut self, tag: Tag, depth: usize) -> Result<()> {
+        match tag {
+            Tag::End => unreachable!("illegal TAG_End argument"),
+            Tag::Byte => self.skip_bytes(1),
+            Tag::Short => self.skip_bytes(2),
+            Tag::Int | Tag::Float => self.skip_bytes(4),
+            Tag::Long | Tag::Double => self.skip_bytes(8),
+            Tag::ByteArray => {
+                let len = self.read_len(tag)?;
+                self.skip_bytes(len as u64)
+            }
+            Tag::String => {
+                let len = self.reader.read_u16::<BigEndian>()?;
+                self.skip_bytes(len.into())
+            }
+            Tag::List => {
+                if depth >= MAX_DEPTH {
+                    return Err(Error::new_static("reached maximum recursion depth"));
+                }
+
+                let element_tag = self.read_tag()?;
+                let len = self.read_len(tag)?;
+
+                match element_tag {
+                    Tag::End if len != 0 => Err(Error::new_owned(format!(
+                        "TAG_End list with nonzero length of {len}"
+                    ))),
+                    Tag::End => Ok(()),
+                    Tag::Byte => self.skip_bytes(len as u64),
+                    Tag::Short => self.skip_bytes(len as u64 * 2),
+                    Tag::Int | Tag::Float => self.skip_bytes(len as u64 * 4),
+                    Tag::Long | Tag::Double => self.skip_bytes(len as u64 * 8),
+                    _ => {
+                        for _ in 0..len {
+                            self.skip_tag(element_tag, depth + 1)?;
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            Tag::Compound => {
+                if depth >= MAX_DEPTH {
+                    return Err(Error::new_static("reached maximum recursion depth"));
+                }
+
+                loop {
+                    let tag = self.read_tag()?;
+                    if tag == Tag::End {
+                        return Ok(());
+                    }
+
+                    self.skip_tag(Tag::String, depth)?;
+                    self.skip_tag(tag, depth + 1)?;
+                }
+            }
+            Tag::IntArray => {
+                let len = self.read_len(tag)?;
+                self.skip_bytes(len as u64 * 4)
+            }
+            Tag::LongArray => {
+                let len = self.read_len(tag)?;
+                self.skip_bytes(len as u64 * 8)
+            }
+        }
+    }
+
+    fn skip_bytes(&mut self, count: u64) -> Result<()> {
+        let skipped = io::copy(&mut (&mut self.reader).take(count), &mut io::sink())?;
+
+        if skipped != count {
+            return Err(unexpected_eof());
+        }
+
+        Ok(())
+    }
+
+    fn check_depth(&self) -> Result<()> {
+        if self.stack.len() >= MAX_DEPTH {
+            return Err(Error::new_static("reached maximum recursion depth"));
+        }
+
+        Ok(())
+    }
+
+    fn read_tag(&mut self) -> Result<Tag> {
+        match self.reader.read_u8()? {
+            0 => Ok(Tag::End),
+            1 => Ok(Tag::Byte),
+            2 => Ok(Tag::Short),
+            3 => Ok(Tag::Int),
+            4 => Ok(Tag::Long),
+            5 => Ok(Tag::Float),
+            6 => Ok(Tag::Double),
+            7 => Ok(Tag::ByteArray),
+            8 => Ok(Tag::String),
+            9 => Ok(Tag::List),
+            10 => Ok(Tag::Compound),
+            11 => Ok(Tag::IntArray),
+            12 => Ok(Tag::LongArray),
+            byte => Err(Error::new_owned(format!("invalid tag byte of {byte:#x}"))),
+        }
+    }
+
+    /// Reads the length prefix of a list or array.
+    fn read_len(&mut self, tag: Tag) -> Result<usize> {
+        let len = self.reader.read_i32::<BigEndian>()?;
+
+        if len.is_negative() {
+            return Err(Error::new_owned(format!("negative {tag} length of {len}")));
+        }
+
+        Ok(len as usize)
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let len = self.reader.read_u16::<BigEndian>()?;
+
+        let mut bytes = vec![0; len.into()];
+        self.reader.read_exact(&mut bytes)?;
+
+        match cesu8::from_java_cesu8(&bytes) {
+            Ok(cow) => Ok(cow.into()),
+            Err(Cesu8DecodingError) => {
+                Err(Error::new_static("could not convert CESU-8 data to UTF-8"))
+            }
+        }
+    }
+}
+
+fn unexpected_eof() -> Error {
+    io::Error::from(io::ErrorKind::UnexpectedEof).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compound, from_binary_slice, to_binary_writer};
+
+    fn example() -> Compound {
+        compound! {
+            "DataVersion" => 3218,
+            "Status" => "full",
+            "sections" => List::Compound(vec![
+                compound! {
+                    "Y" => -4_i8,
+                    "block_states" => compound! {
+                        "palette" => List::Compound(vec![compound! { "Name" => "minecraft:stone" }]),
+                        "data" => vec![0_i64; 256],
+                    },
+                },
+                compound! { "Y" => -3_i8 },
+            ]),
+            "Heightmaps" => compound! { "WORLD_SURFACE" => vec![7_i64; 37] },
+            "empty" => List::End,
+            "lists" => List::List(vec![List::Int(vec![1, 2]), List::Double(vec![0.5])]),
+            "bytes" => vec![1_i8, -2, 3],
+            "ints" => vec![i32::MIN, i32::MAX],
+        }
+    }
+
+    fn encode(c: &Compound) -> Vec<u8> {
+        let mut buf = vec![];
+        to_binary_writer(&mut buf, c, "root").unwrap();
+        buf
+    }
+
+    #[test]
+    fn read_matches_from_binary_slice() {
+        let buf = encode(&example());
+
+        let mut reader = NbtReader::new(buf.as_slice());
+        assert_eq!(
+            reader.next_event().unwrap(),
+            Some(Event::Name("root".into()))
+        );
+        let value = reader.read_value().unwrap();
+        assert_eq!(reader.next_event().unwrap(), None);
+
+        let (expected, _) = from_binary_slice(&mut buf.as_slice()).unwrap();
+        assert_eq!(value, Value::Compound(expected));
+    }
+
+    #[test]
+    fn skip_and_events() {
+        let buf = encode(&example());
+
+        let mut reader = NbtReader::new(buf.as_slice());
+        let mut names = vec![];
+        let mut ys = vec![];
+
+        while let Some(event) = reader.next_event().unwrap() {
+            match event {
+                Event::Name(name) if reader.depth() == 1 && name != "sections" => {
+                    reader.skip_value().unwrap();
+                    names.push(name);
+                }
+                Event::Name(name) if name == "Y" => {
+                    ys.push(reader.read_value().unwrap());
+                }
+                Event::Name(name) if name == "block_states" => {
+                    reader.skip_value().unwrap();
+                }
+                _ => {}
+            }
+        }
+
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "DataVersion",
+                "Heightmaps",
+                "Status",
+                "bytes",
+                "empty",
+                "ints",
+                "lists"
+            ]
+        );
+        assert_eq!(ys, [Value::Byte(-4), Value::Byte(-3)]);
+        assert!(reader.into_inner().is_empty());
+    }
+
+    #[test]
+    fn truncated_input() {
+        let buf = encode(&example());
+
+        for len in [1, buf.len() / 2, buf.len() - 1] {
+            let mut reader = NbtReader::new(&buf[..len]);
+            assert!(reader
+                .next_event()
+                .and_then(|_| reader.read_value())
+                .is_err());
+
+            let mut reader = NbtReader::new(&buf[..len]);
+            assert!(reader
+                .next_event()
+                .and_then(|_| reader.skip_value())
+                .is_err());
+        }
+    }
+}
@@ -18,6 +18,8 @@ struct TopLevel {
 struct Block {
     id: u16,
     item_id: u16,
+    hardness: f32,
+    blast_resistance: f32,
     translation_key: String,
     name: String,
     properties: Vec<Property>,
@@ -373,6 +375,30 @@ pub fn build() -> anyhow::Result<TokenStream> {
         })
         .collect::<TokenStream>();
 
+    let block_kind_to_hardness_arms = blocks
+        .iter()
+        .map(|block| {
+            let name = ident(block.name.to_pascal_case());
+            let hardness = block.hardness;
+
+            quote! {
+                Self::#name => #hardness,
+            }
+        })
+        .collect::<TokenStream>();
+
+    let block_kind_to_blast_resistance_arms = blocks
+        .iter()
+        .map(|block| {
+            let name = ident(block.name.to_pascal_case());
+            let blast_resistance = block.blast_resistance;
+
+            quote! {
+                Self::#name => #blast_resistance,
+            }
+        })
+        .collect::<TokenStream>();
+
     let block_kind_from_item_kind_arms = blocks
         .iter()
         .filter(|block| block.item_id != 0)
@@ -724,6 +750,23 @@ pub fn build() -> anyhow::Result<TokenStream> {
                 }
             }
 
+            /// Returns the hardness of this block kind, which determines how
+            /// long it takes to break. Blocks which cannot be broken outside
+            /// of creative mode have a hardness of `-1.0`.
+            pub const fn hardness(self) -> f32 {
+                match self {
+                    #block_kind_to_hardness_arms
+                }
+            }
+
+            /// Returns the blast resistance of this block kind, which
+            /// determines how well it withstands explosions.
+            pub const fn blast_resistance(self) -> f32 {
+                match self {
+                    #block_kind_to_blast_resistance_arms
+                }
+            }
+
             /// Converts a block kind to its corresponding item kind.
             ///
             /// [`ItemKind::Air`] is used to indicate the absence of an item.
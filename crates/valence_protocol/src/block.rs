@@ -109,6 +109,20 @@ pub enum BlockFace {
     East,
 }
 
+impl BlockFace {
+    /// Returns the face pointing in the opposite direction.
+    pub const fn opposite(self) -> Self {
+        match self {
+            Self::Bottom => Self::Top,
+            Self::Top => Self::Bottom,
+            Self::North => Self::South,
+            Self::South => Self::North,
+            Self::West => Self::East,
+            Self::East => Self::West,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,4 +138,19 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn opposite_faces() {
+        for face in [
+            BlockFace::Bottom,
+            BlockFace::Top,
+            BlockFace::North,
+            BlockFace::South,
+            BlockFace::West,
+            BlockFace::East,
+        ] {
+            assert_ne!(face.opposite(), face);
+            assert_eq!(face.opposite().opposite(), face);
+        }
+    }
 }
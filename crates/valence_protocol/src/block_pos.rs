@@ -5,7 +5,7 @@ use anyhow::bail;
 use crate::{BlockFace, Decode, Encode};
 
 /// Represents an absolute block position in world space.
-#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct BlockPos {
     pub x: i32,
     pub y: i32,
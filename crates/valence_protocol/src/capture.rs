@@ -0,0 +1,210 @@
+//! Recording packets of a connection to replay them later.
+//!
+//! A capture starts with a header containing the protocol version, followed
+//! by a [`CapturedPacket`] for every packet in the order they were sent in
+//! either direction. Packets are stored uncompressed and unencrypted.
+//!
+//! ```
+//! use valence_protocol::capture::{
+//!     read_capture, CaptureWriter, PacketDirection, PacketState,
+//! };
+//! use valence_protocol::packets::c2s::play::RenameItem;
+//!
+//! let mut capture = vec![];
+//! let mut writer = CaptureWriter::new(&mut capture).unwrap();
+//!
+//! let packet = RenameItem {
+//!     item_name: "Hello!",
+//! };
+//!
+//! writer
+//!     .write_packet(PacketDirection::Serverbound, PacketState::Play, &packet)
+//!     .unwrap();
+//!
+//! let packets = read_capture(&capture).unwrap();
+//!
+//! assert_eq!(packets.len(), 1);
+//! assert_eq!(packets[0].decode::<RenameItem>().unwrap().item_name, "Hello!");
+//! ```
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Context};
+
+use crate::{Decode, DecodePacket, Encode, EncodePacket, Result, VarInt, PROTOCOL_VERSION};
+
+/// The bytes at the start of every capture.
+pub const CAPTURE_MAGIC: [u8; 4] = *b"VCAP";
+
+/// The direction a captured packet was sent in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode)]
+pub enum PacketDirection {
+    /// Sent by the client to the server.
+    Serverbound,
+    /// Sent by the server to the client.
+    Clientbound,
+}
+
+/// The state of the connection when a captured packet was sent, which
+/// determines how the packet is decoded.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode)]
+pub enum PacketState {
+    Handshaking,
+    Status,
+    Login,
+    Play,
+}
+
+/// A packet read from a capture.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Decode)]
+pub struct CapturedPacket<'a> {
+    /// The number of microseconds between the start of the capture and the
+    /// packet.
+    pub timestamp_micros: u64,
+    pub direction: PacketDirection,
+    pub state: PacketState,
+    /// The packet ID followed by the packet's body.
+    pub data: &'a [u8],
+}
+
+impl<'a> CapturedPacket<'a> {
+    /// Returns the time between the start of the capture and the packet.
+    pub fn timestamp(&self) -> Duration {
+        Duration::from_micros(self.timestamp_micros)
+    }
+
+    /// Decodes the captured packet as `P`.
+    pub fn decode<P: DecodePacket<'a>>(&self) -> Result<P> {
+        let mut r = self.data;
+        let pkt = P::decode_packet(&mut r)?;
+
+        ensure!(
+            r.is_empty(),
+            "missed {} bytes while decoding captured {pkt:?}",
+            r.len()
+        );
+
+        Ok(pkt)
+    }
+}
+
+/// Writes packets to a capture, timestamped with the time since the writer
+/// was created.
+pub struct CaptureWriter<W> {
+    writer: W,
+    start: Instant,
+    scratch: Vec<u8>,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Starts a capture by writing its header to `writer`.
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(&CAPTURE_MAGIC)?;
+        VarInt(PROTOCOL_VERSION).encode(&mut writer)?;
+
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+            scratch: vec![],
+        })
+    }
+
+    /// Encodes and appends a packet to the capture.
+    pub fn write_packet<P>(
+        &mut self,
+        direction: PacketDirection,
+        state: PacketState,
+        pkt: &P,
+    ) -> Result<()>
+    where
+        P: EncodePacket,
+    {
+        self.scratch.clear();
+        pkt.encode_packet(&mut self.scratch)?;
+
+        CapturedPacket {
+            timestamp_micros: self.start.elapsed().as_micros() as u64,
+            direction,
+            state,
+            data: &self.scratch,
+        }
+        .encode(&mut self.writer)
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads all packets from the bytes of a capture.
+pub fn read_capture(mut r: &[u8]) -> Result<Vec<CapturedPacket>> {
+    ensure!(
+        r.starts_with(&CAPTURE_MAGIC),
+        "not a packet capture (missing magic bytes)"
+    );
+    r = &r[CAPTURE_MAGIC.len()..];
+
+    let version = VarInt::decode(&mut r)?.0;
+    ensure!(
+        version == PROTOCOL_VERSION,
+        "capture has protocol version {version}, but {PROTOCOL_VERSION} is supported"
+    );
+
+    let mut packets = vec![];
+
+    while !r.is_empty() {
+        let pkt = <CapturedPacket as Decode>::decode(&mut r)
+            .with_context(|| format!("failed to read captured packet #{}", packets.len()))?;
+
+        packets.push(pkt);
+    }
+
+    Ok(packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::c2s::play::{C2sPlayPacket, KeepAliveC2s};
+    use crate::packets::s2c::play::{KeepAliveS2c, S2cPlayPacket};
+
+    #[test]
+    fn capture_round_trip() {
+        let mut writer = CaptureWriter::new(vec![]).unwrap();
+
+        writer
+            .write_packet(
+                PacketDirection::Clientbound,
+                PacketState::Play,
+                &KeepAliveS2c { id: 123 },
+            )
+            .unwrap();
+
+        writer
+            .write_packet(
+                PacketDirection::Serverbound,
+                PacketState::Play,
+                &KeepAliveC2s { id: 123 },
+            )
+            .unwrap();
+
+        let capture = writer.into_inner();
+        let packets = read_capture(&capture).unwrap();
+
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].direction, PacketDirection::Clientbound);
+        assert!(packets[0].timestamp() <= packets[1].timestamp());
+        assert!(matches!(
+            packets[0].decode::<S2cPlayPacket>().unwrap(),
+            S2cPlayPacket::KeepAliveS2c(KeepAliveS2c { id: 123 })
+        ));
+        assert!(matches!(
+            packets[1].decode::<C2sPlayPacket>().unwrap(),
+            C2sPlayPacket::KeepAliveC2s(KeepAliveC2s { id: 123 })
+        ));
+
+        assert!(read_capture(b"nope").is_err());
+    }
+}
@@ -1,7 +1,7 @@
 #[cfg(feature = "encryption")]
 use aes::cipher::{AsyncStreamCipher, NewCipher};
 use anyhow::{bail, ensure};
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use tracing::debug;
 
 use crate::var_int::{VarInt, VarIntDecodeError};
@@ -12,17 +12,43 @@ use crate::{DecodePacket, Encode, EncodePacket, Result, MAX_PACKET_SIZE};
 #[cfg(feature = "encryption")]
 type Cipher = cfb8::Cfb8<aes::Aes128>;
 
-#[derive(Default)]
+/// The zlib compression level used for packets unless configured otherwise.
+#[cfg(feature = "compression")]
+pub const DEFAULT_COMPRESSION_LEVEL: u32 = 4;
+
 pub struct PacketEncoder {
     buf: BytesMut,
+    /// Packet data which comes before `buf`. Shared byte buffers appended with
+    /// [`Self::append_shared`] are kept here instead of being copied into
+    /// `buf`. Always empty when encryption is enabled.
+    segments: Vec<Bytes>,
     #[cfg(feature = "compression")]
     compress_buf: Vec<u8>,
     #[cfg(feature = "compression")]
     compression_threshold: Option<u32>,
+    #[cfg(feature = "compression")]
+    compression_level: u32,
     #[cfg(feature = "encryption")]
     cipher: Option<Cipher>,
 }
 
+impl Default for PacketEncoder {
+    fn default() -> Self {
+        Self {
+            buf: BytesMut::new(),
+            segments: vec![],
+            #[cfg(feature = "compression")]
+            compress_buf: vec![],
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
+            #[cfg(feature = "compression")]
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+        }
+    }
+}
+
 impl PacketEncoder {
     pub fn new() -> Self {
         Self::default()
@@ -33,6 +59,29 @@ impl PacketEncoder {
         self.buf.extend_from_slice(bytes)
     }
 
+    /// Appends already encoded packet data which is shared with other
+    /// encoders, such as cached chunk data. Unless encryption is enabled, the
+    /// bytes are not copied but referenced until they are taken with
+    /// [`Self::take_vectored`].
+    pub fn append_shared(&mut self, bytes: Bytes) {
+        if bytes.is_empty() {
+            return;
+        }
+
+        #[cfg(feature = "encryption")]
+        if self.cipher.is_some() {
+            // Encryption happens in place, so the bytes must be copied anyway.
+            self.buf.extend_from_slice(&bytes);
+            return;
+        }
+
+        if !self.buf.is_empty() {
+            self.segments.push(self.buf.split().freeze());
+        }
+
+        self.segments.push(bytes);
+    }
+
     pub fn prepend_packet<P>(&mut self, pkt: &P) -> Result<()>
     where
         P: EncodePacket + ?Sized,
@@ -40,6 +89,13 @@ impl PacketEncoder {
         let start_len = self.buf.len();
         self.append_packet(pkt)?;
 
+        if !self.segments.is_empty() {
+            // The packet must come before all segments.
+            let pkt_bytes = self.buf.split_off(start_len).freeze();
+            self.segments.insert(0, pkt_bytes);
+            return Ok(());
+        }
+
         let end_len = self.buf.len();
         let total_packet_len = end_len - start_len;
 
@@ -72,7 +128,10 @@ impl PacketEncoder {
             use flate2::Compression;
 
             if data_len > threshold as usize {
-                let mut z = ZlibEncoder::new(&self.buf[start_len..], Compression::new(4));
+                let mut z = ZlibEncoder::new(
+                    &self.buf[start_len..],
+                    Compression::new(self.compression_level),
+                );
 
                 self.compress_buf.clear();
 
@@ -142,7 +201,23 @@ impl PacketEncoder {
 
     /// Takes all the packets written so far and encrypts them if encryption is
     /// enabled.
+    ///
+    /// Shared bytes appended with [`Self::append_shared`] are copied into the
+    /// returned buffer. Use [`Self::take_vectored`] to avoid the copy.
     pub fn take(&mut self) -> BytesMut {
+        if !self.segments.is_empty() {
+            let mut bytes = BytesMut::with_capacity(self.len());
+
+            for segment in self.segments.drain(..) {
+                bytes.extend_from_slice(&segment);
+            }
+
+            bytes.extend_from_slice(&self.buf);
+            self.buf.clear();
+
+            return bytes;
+        }
+
         #[cfg(feature = "encryption")]
         if let Some(cipher) = &mut self.cipher {
             cipher.encrypt(&mut self.buf);
@@ -151,7 +226,54 @@ impl PacketEncoder {
         self.buf.split()
     }
 
+    /// Takes all the packets written so far as a sequence of byte buffers,
+    /// encrypting them if encryption is enabled. Concatenating the buffers
+    /// gives the same bytes as [`Self::take`], but shared bytes appended with
+    /// [`Self::append_shared`] are returned without being copied. This is
+    /// intended for vectored writes.
+    pub fn take_vectored(&mut self) -> Vec<Bytes> {
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = &mut self.cipher {
+            cipher.encrypt(&mut self.buf);
+        }
+
+        let mut segments = std::mem::take(&mut self.segments);
+
+        if !self.buf.is_empty() {
+            segments.push(self.buf.split().freeze());
+        }
+
+        segments
+    }
+
+    /// Returns the number of bytes written since the packets were last taken.
+    pub fn len(&self) -> usize {
+        self.segments.iter().map(|s| s.len()).sum::<usize>() + self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of packets written since the packets were last
+    /// taken. Only the length prefixes of the packets are read.
+    pub fn packet_count(&self) -> usize {
+        fn count(mut bytes: &[u8]) -> usize {
+            let mut count = 0;
+
+            while let Ok(packet_len) = VarInt::decode_partial(&mut bytes) {
+                bytes = bytes.get(packet_len as usize..).unwrap_or_default();
+                count += 1;
+            }
+
+            count
+        }
+
+        self.segments.iter().map(|s| count(s)).sum::<usize>() + count(&self.buf)
+    }
+
     pub fn clear(&mut self) {
+        self.segments.clear();
         self.buf.clear();
     }
 
@@ -160,6 +282,23 @@ impl PacketEncoder {
         self.compression_threshold = threshold;
     }
 
+    #[cfg(feature = "compression")]
+    pub fn compression_threshold(&self) -> Option<u32> {
+        self.compression_threshold
+    }
+
+    /// Sets the zlib compression level from 0 (fastest) to 9 (smallest) for
+    /// packets above the compression threshold. Values above 9 are clamped.
+    #[cfg(feature = "compression")]
+    pub fn set_compression_level(&mut self, level: u32) {
+        self.compression_level = level.min(9);
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn compression_level(&self) -> u32 {
+        self.compression_level
+    }
+
     /// Encrypts all future packets **and any packets that have
     /// not been [taken] yet.**
     ///
@@ -167,6 +306,12 @@ impl PacketEncoder {
     #[cfg(feature = "encryption")]
     pub fn enable_encryption(&mut self, key: &[u8; 16]) {
         assert!(self.cipher.is_none(), "encryption is already enabled");
+
+        if !self.segments.is_empty() {
+            // The pending shared bytes must be encrypted too.
+            self.buf = self.take();
+        }
+
         self.cipher = Some(NewCipher::new(key.into(), key.into()));
     }
 }
@@ -207,6 +352,22 @@ pub fn encode_packet_compressed<P>(
     threshold: u32,
     scratch: &mut Vec<u8>,
 ) -> Result<()>
+where
+    P: EncodePacket + ?Sized,
+{
+    encode_packet_compressed_with_level(buf, pkt, threshold, DEFAULT_COMPRESSION_LEVEL, scratch)
+}
+
+/// Like [`encode_packet_compressed`], but with the given zlib compression
+/// level from 0 to 9.
+#[cfg(feature = "compression")]
+pub fn encode_packet_compressed_with_level<P>(
+    buf: &mut Vec<u8>,
+    pkt: &P,
+    threshold: u32,
+    level: u32,
+    scratch: &mut Vec<u8>,
+) -> Result<()>
 where
     P: EncodePacket + ?Sized,
 {
@@ -222,7 +383,7 @@ where
     let data_len = buf.len() - start_len;
 
     if data_len > threshold as usize {
-        let mut z = ZlibEncoder::new(&buf[start_len..], Compression::new(4));
+        let mut z = ZlibEncoder::new(&buf[start_len..], Compression::new(level.min(9)));
 
         scratch.clear();
 
@@ -555,6 +716,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn encoder_packet_count() {
+        let mut enc = PacketEncoder::new();
+        assert_eq!(enc.packet_count(), 0);
+
+        enc.append_packet(&TestPacket::new("first")).unwrap();
+        #[cfg(feature = "compression")]
+        enc.set_compression(Some(0));
+        enc.append_packet(&TestPacket::new("second")).unwrap();
+
+        let mut shared = PacketEncoder::new();
+        shared.append_packet(&TestPacket::new("shared")).unwrap();
+        enc.append_shared(shared.take().freeze());
+
+        enc.prepend_packet(&TestPacket::new("third")).unwrap();
+
+        assert_eq!(enc.packet_count(), 4);
+
+        enc.take_vectored();
+        assert_eq!(enc.packet_count(), 0);
+    }
+
     #[test]
     fn packets_round_trip() {
         let mut buf = BytesMut::new();
@@ -598,6 +781,59 @@ mod tests {
             .check("third");
     }
 
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compression_levels() {
+        for level in [0, 9] {
+            let mut enc = PacketEncoder::new();
+            enc.set_compression(Some(0));
+            enc.set_compression_level(level);
+            enc.append_packet(&TestPacket::new("level")).unwrap();
+
+            let mut dec = PacketDecoder::new();
+            dec.set_compression(true);
+            dec.queue_bytes(enc.take());
+            dec.try_next_packet::<TestPacket>()
+                .unwrap()
+                .unwrap()
+                .check("level");
+        }
+    }
+
+    #[test]
+    fn shared_bytes_round_trip() {
+        let mut shared = PacketEncoder::new();
+        shared.append_packet(&TestPacket::new("shared")).unwrap();
+        let shared = shared.take().freeze();
+
+        let mut enc = PacketEncoder::new();
+
+        enc.append_packet(&TestPacket::new("first")).unwrap();
+        enc.append_shared(shared.clone());
+        enc.append_packet(&TestPacket::new("second")).unwrap();
+        enc.prepend_packet(&TestPacket::new("third")).unwrap();
+
+        let segments = enc.take_vectored();
+
+        assert_eq!(segments.len(), 4);
+        // The shared bytes were not copied.
+        assert_eq!(segments[2].as_ptr(), shared.as_ptr());
+        assert!(enc.is_empty());
+
+        let mut dec = PacketDecoder::new();
+
+        for segment in segments {
+            dec.queue_slice(&segment);
+        }
+
+        for n in ["third", "first", "shared", "second"] {
+            dec.try_next_packet::<TestPacket>()
+                .unwrap()
+                .unwrap()
+                .check(n);
+        }
+    }
+
     #[test]
     fn collect_packets_into_vec() {
         let packets = vec![
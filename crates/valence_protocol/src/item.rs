@@ -1,7 +1,8 @@
 use std::io::Write;
 
-use anyhow::{ensure, Context};
-use valence_nbt::Compound;
+use anyhow::{bail, ensure, Context};
+use valence_nbt::snbt::{to_snbt_string, SnbtReader};
+use valence_nbt::{Compound, Value};
 
 use crate::{BlockKind, Decode, Encode, Result, VarInt};
 
@@ -36,6 +37,142 @@ impl ItemStack {
     pub fn set_count(&mut self, count: u8) {
         self.count = count.clamp(STACK_MIN, STACK_MAX);
     }
+
+    /// Converts this stack to vanilla's NBT format for items, as found in
+    /// inventories and the `Item` tag of item entities.
+    ///
+    /// ```
+    /// # use valence_protocol::{ItemKind, ItemStack};
+    /// let stack = ItemStack::new(ItemKind::Stone, 64, None);
+    /// let nbt = stack.to_nbt();
+    ///
+    /// assert_eq!(nbt.get("id"), Some(&"minecraft:stone".to_owned().into()));
+    /// assert_eq!(nbt.get("Count"), Some(&64_i8.into()));
+    /// ```
+    pub fn to_nbt(&self) -> Compound {
+        let mut compound = Compound::new();
+
+        compound.insert("id", format!("minecraft:{}", self.item.to_str()));
+        compound.insert("Count", self.count as i8);
+
+        if let Some(nbt) = &self.nbt {
+            compound.insert("tag", nbt.clone());
+        }
+
+        compound
+    }
+
+    /// Reads a stack from vanilla's NBT format for items. The inverse of
+    /// [`Self::to_nbt`].
+    pub fn from_nbt(compound: &Compound) -> Result<Self> {
+        let Some(Value::String(id)) = compound.get("id") else {
+            bail!("missing item ID")
+        };
+
+        let item = parse_item_id(id)?;
+
+        let count = match compound.get("Count") {
+            Some(Value::Byte(n)) => *n as i64,
+            Some(Value::Short(n)) => *n as i64,
+            Some(Value::Int(n)) => *n as i64,
+            None => 1,
+            Some(_) => bail!("item count is not an integer"),
+        };
+
+        ensure!(
+            (STACK_MIN as i64..=STACK_MAX as i64).contains(&count),
+            "invalid item stack count (got {count}, expected {STACK_MIN}..={STACK_MAX})"
+        );
+
+        let nbt = match compound.get("tag") {
+            Some(Value::Compound(tag)) => Some(tag.clone()),
+            None => None,
+            Some(_) => bail!("item tag is not a compound"),
+        };
+
+        Ok(Self::new(item, count as u8, nbt))
+    }
+
+    /// Converts this stack to an SNBT string in vanilla's format for items.
+    /// See [`Self::to_nbt`].
+    pub fn to_snbt(&self) -> String {
+        to_snbt_string(&Value::Compound(self.to_nbt()))
+    }
+
+    /// Reads a stack from an SNBT string in vanilla's format for items, such
+    /// as `{id:"minecraft:stone",Count:64b}`. See [`Self::from_nbt`].
+    pub fn from_snbt(snbt: &str) -> Result<Self> {
+        match SnbtReader::new(snbt).read()? {
+            Value::Compound(compound) => Self::from_nbt(&compound),
+            _ => bail!("item SNBT is not a compound"),
+        }
+    }
+
+    /// Formats this stack like the item and count arguments of vanilla's
+    /// `/give` command.
+    ///
+    /// ```
+    /// # use valence_protocol::{ItemKind, ItemStack};
+    /// # use valence_nbt::compound;
+    /// let stack = ItemStack::new(ItemKind::DiamondSword, 1, Some(compound! { "Damage" => 5 }));
+    /// assert_eq!(stack.to_give_args(), "minecraft:diamond_sword{Damage:5} 1");
+    /// ```
+    pub fn to_give_args(&self) -> String {
+        let mut args = format!("minecraft:{}", self.item.to_str());
+
+        if let Some(nbt) = &self.nbt {
+            args += &to_snbt_string(&Value::Compound(nbt.clone()));
+        }
+
+        args + &format!(" {}", self.count)
+    }
+
+    /// Reads a stack from the item and optional count arguments of vanilla's
+    /// `/give` command, such as `minecraft:diamond_sword{Damage:5} 1`. The
+    /// inverse of [`Self::to_give_args`].
+    pub fn from_give_args(args: &str) -> Result<Self> {
+        let args = args.trim();
+
+        let id_end = args
+            .find(|c: char| c == '{' || c.is_whitespace())
+            .unwrap_or(args.len());
+
+        let item = parse_item_id(&args[..id_end])?;
+        let mut rest = &args[id_end..];
+
+        let nbt = if rest.starts_with('{') {
+            let mut reader = SnbtReader::new(rest);
+
+            let Value::Compound(nbt) = reader.parse_element()? else {
+                unreachable!("element starting with '{{' is not a compound")
+            };
+
+            rest = &rest[reader.bytes_read()..];
+            Some(nbt)
+        } else {
+            None
+        };
+
+        let count = match rest.trim() {
+            "" => 1,
+            count => count.parse().context("invalid item count")?,
+        };
+
+        ensure!(
+            (STACK_MIN..=STACK_MAX).contains(&count),
+            "invalid item stack count (got {count}, expected {STACK_MIN}..={STACK_MAX})"
+        );
+
+        Ok(Self::new(item, count, nbt))
+    }
+}
+
+/// Parses a namespaced item ID such as `minecraft:stone`. The namespace may be
+/// omitted.
+fn parse_item_id(id: &str) -> Result<ItemKind> {
+    let name = id.strip_prefix("minecraft:").unwrap_or(id);
+
+    ItemKind::from_str(name).with_context(|| format!("unknown item ID \"{id}\""))
 }
 
 impl Encode for Option<ItemStack> {
@@ -132,4 +269,53 @@ mod tests {
         stack.set_count(201);
         assert_eq!(stack.count, STACK_MAX);
     }
+
+    #[test]
+    fn item_stack_nbt_round_trip() {
+        let stack = ItemStack::new(
+            ItemKind::DiamondSword,
+            1,
+            Some(valence_nbt::compound! { "Damage" => 5 }),
+        );
+
+        let snbt = stack.to_snbt();
+        assert!(snbt.contains(r#"id:"minecraft:diamond_sword""#));
+        assert!(snbt.contains("Count:1b"));
+        assert!(snbt.contains("tag:{Damage:5}"));
+        assert_eq!(ItemStack::from_snbt(&snbt).unwrap(), stack);
+        assert_eq!(ItemStack::from_nbt(&stack.to_nbt()).unwrap(), stack);
+
+        assert_eq!(
+            ItemStack::from_snbt("{id:stone}").unwrap(),
+            ItemStack::new(ItemKind::Stone, 1, None)
+        );
+        assert!(ItemStack::from_snbt(r#"{id:"minecraft:not_an_item",Count:1b}"#).is_err());
+        assert!(ItemStack::from_snbt(r#"{id:"minecraft:stone",Count:0b}"#).is_err());
+    }
+
+    #[test]
+    fn item_stack_give_args() {
+        let stack = ItemStack::new(
+            ItemKind::DiamondSword,
+            3,
+            Some(valence_nbt::compound! { "Damage" => 5 }),
+        );
+
+        assert_eq!(stack.to_give_args(), "minecraft:diamond_sword{Damage:5} 3");
+        assert_eq!(
+            ItemStack::from_give_args(&stack.to_give_args()).unwrap(),
+            stack
+        );
+
+        assert_eq!(
+            ItemStack::from_give_args("stone").unwrap(),
+            ItemStack::new(ItemKind::Stone, 1, None)
+        );
+        assert_eq!(
+            ItemStack::from_give_args("minecraft:stone 64").unwrap(),
+            ItemStack::new(ItemKind::Stone, 64, None)
+        );
+        assert!(ItemStack::from_give_args("minecraft:stone 0").is_err());
+        assert!(ItemStack::from_give_args("minecraft:stone{").is_err());
+    }
 }
@@ -101,6 +101,7 @@ pub mod block;
 mod block_pos;
 mod bounded;
 mod byte_angle;
+pub mod capture;
 mod codec;
 pub mod enchant;
 pub mod entity_meta;
@@ -348,12 +348,15 @@ pub mod play {
         pub entity_status: u8,
     }
 
-    #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x1a]
-    pub struct PlaceRecipe<'a> {
-        pub window_id: u8,
-        pub recipe: Ident<&'a str>,
-        pub make_all: bool,
+    pub struct Explosion {
+        pub position: [f64; 3],
+        pub strength: f32,
+        /// Offsets of the destroyed blocks relative to `position`.
+        pub records: Vec<[i8; 3]>,
+        /// Added to the velocity of the receiving player.
+        pub player_motion: [f32; 3],
     }
 
     #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
@@ -556,6 +559,11 @@ pub mod play {
         pub recipe: Ident<&'a str>,
     }
 
+    /// The recipe response packet, which had the ID of [`Explosion`] before
+    /// it was corrected.
+    #[deprecated = "use `PlaceGhostRecipe` instead"]
+    pub type PlaceRecipe<'a> = PlaceGhostRecipe<'a>;
+
     #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x30]
     pub struct PlayerAbilitiesS2c {
@@ -1000,7 +1008,7 @@ pub mod play {
             DisconnectPlay<'a>,
             DisguisedChatMessage<'a>,
             EntityEvent,
-            PlaceRecipe<'a>,
+            Explosion,
             UnloadChunk,
             GameEvent,
             OpenHorseScreen,
@@ -285,6 +285,18 @@ impl Text {
         }))
     }
 
+    /// Creates text which moves the text following it horizontally by
+    /// `pixels`, which may be negative.
+    ///
+    /// This relies on a font with the advances given by [`space_advances`],
+    /// which must be set on the returned text with [`TextFormat::font`]. The
+    /// default font does not contain these characters. Negative space is
+    /// typically used to overlay custom glyphs on top of each other to build
+    /// image-based HUDs.
+    pub fn space(pixels: i32) -> Self {
+        Self::text(space_chars(pixels))
+    }
+
     /// Create translated text based on the given translation key, with extra
     /// text components to be inserted into the slots of the translation text.
     pub fn translate(key: impl Into<Cow<'static, str>>, with: impl Into<Vec<Text>>) -> Self {
@@ -960,6 +972,64 @@ fn color_from_str(s: &str) -> Option<Color> {
     }
 }
 
+/// The first character of the private use area used by [`space_chars`] for
+/// positive advances. Character `SPACE_POSITIVE_START + n` advances by `2^n`
+/// pixels.
+pub const SPACE_POSITIVE_START: char = '\u{f800}';
+
+/// The first character of the private use area used by [`space_chars`] for
+/// negative advances. Character `SPACE_NEGATIVE_START + n` advances by `-2^n`
+/// pixels.
+pub const SPACE_NEGATIVE_START: char = '\u{f810}';
+
+/// The number of distinct powers of two in each direction.
+const SPACE_POWERS: u32 = 11;
+
+/// Returns a string of characters advancing the cursor by exactly `pixels`
+/// when rendered with a font using [`space_advances`].
+pub fn space_chars(pixels: i32) -> String {
+    let start = if pixels < 0 {
+        SPACE_NEGATIVE_START
+    } else {
+        SPACE_POSITIVE_START
+    };
+
+    let largest = 1 << (SPACE_POWERS - 1);
+    let mut remaining = pixels.unsigned_abs();
+    let mut res = String::new();
+
+    while remaining >= largest * 2 {
+        res.push(space_char(start, SPACE_POWERS - 1));
+        remaining -= largest;
+    }
+
+    for power in (0..SPACE_POWERS).rev() {
+        if remaining & (1 << power) != 0 {
+            res.push(space_char(start, power));
+        }
+    }
+
+    res
+}
+
+/// Returns the characters used by [`space_chars`] along with the number of
+/// pixels they advance the cursor by.
+///
+/// These are meant to be added to a font as a `space` provider, which is done
+/// by `ResourcePackBuilder::add_space_font` in `valence`.
+pub fn space_advances() -> impl Iterator<Item = (char, i32)> {
+    (0..SPACE_POWERS).flat_map(|power| {
+        [
+            (space_char(SPACE_POSITIVE_START, power), 1 << power),
+            (space_char(SPACE_NEGATIVE_START, power), -(1 << power)),
+        ]
+    })
+}
+
+fn space_char(start: char, power: u32) -> char {
+    char::from_u32(start as u32 + power).expect("space character should be valid")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1088,4 +1158,14 @@ mod tests {
         assert_eq!(serialized, expected);
         assert_eq!(txt, deserialized);
     }
+
+    #[test]
+    fn space_chars_sum_to_advance() {
+        let advances: std::collections::HashMap<_, _> = space_advances().collect();
+
+        for pixels in [-5000, -1025, -1, 0, 1, 7, 64, 2047, 2048, 10_000] {
+            let sum: i32 = space_chars(pixels).chars().map(|c| advances[&c]).sum();
+            assert_eq!(sum, pixels);
+        }
+    }
 }
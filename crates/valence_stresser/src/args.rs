@@ -1,5 +1,7 @@
 use clap::{arg, command, Parser};
 
+use crate::movement::MovementPattern;
+
 #[derive(Parser)]
 #[command(author, version, about)]
 pub(crate) struct StresserArgs {
@@ -26,4 +28,20 @@ pub(crate) struct StresserArgs {
     #[arg(default_value = "4096")]
     #[arg(long = "read-buffer")]
     pub read_buffer_size: usize,
+
+    /// Movement pattern of sessions after they spawn.
+    #[arg(value_enum, default_value_t = MovementPattern::Idle)]
+    #[arg(short = 'm', long = "movement")]
+    pub movement: MovementPattern,
+
+    /// Movement speed of sessions in blocks per second.
+    #[arg(default_value = "4.317")]
+    #[arg(long = "speed")]
+    pub speed: f64,
+
+    /// Interval between chat messages of each session in seconds.
+    /// Sessions don't chat if the value is zero.
+    #[arg(default_value = "0")]
+    #[arg(long = "chat-interval")]
+    pub chat_interval: u64,
 }
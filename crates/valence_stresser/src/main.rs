@@ -8,6 +8,7 @@ use stresser::{make_session, SessionParams};
 use tokio::sync::Semaphore;
 
 mod args;
+pub mod movement;
 pub mod stresser;
 
 #[tokio::main]
@@ -28,6 +29,10 @@ async fn main() {
                 socket_addr: target_addr,
                 session_name: session_name.as_str(),
                 read_buffer_size: args.read_buffer_size,
+                movement: args.movement,
+                speed: args.speed,
+                chat_interval: (args.chat_interval > 0)
+                    .then(|| Duration::from_secs(args.chat_interval)),
             };
 
             if let Err(err) = make_session(&params).await {
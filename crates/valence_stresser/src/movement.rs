@@ -0,0 +1,113 @@
+use std::f64::consts::TAU;
+
+use clap::ValueEnum;
+use rand::Rng;
+
+/// The number of position updates sent per second while moving, matching the
+/// client's tick rate.
+pub const MOVES_PER_SECOND: u32 = 20;
+
+/// The distance from the spawn point at which the circle and line patterns
+/// turn around.
+const PATTERN_RADIUS: f64 = 8.0;
+
+/// The chance for a session with the random pattern to change its direction
+/// every move.
+const TURN_CHANCE: f64 = 0.05;
+
+/// How sessions move around after they spawn.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum MovementPattern {
+    /// Stays at the spawn point.
+    Idle,
+    /// Walks in a random direction, turning from time to time.
+    Random,
+    /// Walks in a circle around the spawn point.
+    Circle,
+    /// Walks back and forth along the X axis through the spawn point.
+    Line,
+}
+
+/// The position of a session following a [`MovementPattern`].
+pub struct Movement {
+    pattern: MovementPattern,
+    /// Distance moved per position update.
+    step: f64,
+    origin: [f64; 3],
+    position: [f64; 3],
+    /// The direction of the random pattern, or the angle or distance along
+    /// the circle and line patterns.
+    progress: f64,
+    spawned: bool,
+}
+
+impl Movement {
+    /// Creates a movement moving at `speed` blocks per second.
+    pub fn new(pattern: MovementPattern, speed: f64) -> Self {
+        Self {
+            pattern,
+            step: speed / MOVES_PER_SECOND as f64,
+            origin: [0.0; 3],
+            position: [0.0; 3],
+            progress: 0.0,
+            spawned: false,
+        }
+    }
+
+    /// Restarts the pattern from the position the server teleported the
+    /// session to.
+    pub fn teleport(&mut self, position: [f64; 3]) {
+        self.origin = position;
+        self.position = position;
+        self.progress = 0.0;
+        self.spawned = true;
+    }
+
+    /// Advances the pattern by one move. Returns the new position to send, or
+    /// `None` if the session didn't move.
+    pub fn step(&mut self) -> Option<[f64; 3]> {
+        if !self.spawned {
+            return None;
+        }
+
+        let [x, y, z] = self.origin;
+
+        self.position = match self.pattern {
+            MovementPattern::Idle => return None,
+            MovementPattern::Random => {
+                let mut rng = rand::thread_rng();
+
+                if rng.gen_bool(TURN_CHANCE) {
+                    self.progress = rng.gen_range(0.0..TAU);
+                }
+
+                let [px, py, pz] = self.position;
+                [
+                    px + self.progress.cos() * self.step,
+                    py,
+                    pz + self.progress.sin() * self.step,
+                ]
+            }
+            MovementPattern::Circle => {
+                self.progress = (self.progress + self.step / PATTERN_RADIUS) % TAU;
+
+                [
+                    x + self.progress.cos() * PATTERN_RADIUS,
+                    y,
+                    z + self.progress.sin() * PATTERN_RADIUS,
+                ]
+            }
+            MovementPattern::Line => {
+                self.progress = (self.progress + self.step) % (PATTERN_RADIUS * 4.0);
+
+                // A triangle wave between -PATTERN_RADIUS and PATTERN_RADIUS
+                // starting at the spawn point.
+                let phase = (self.progress + PATTERN_RADIUS) % (PATTERN_RADIUS * 4.0);
+                let offset = PATTERN_RADIUS - (phase - PATTERN_RADIUS * 2.0).abs();
+                [x + offset, y, z]
+            }
+        };
+
+        Some(self.position)
+    }
+}
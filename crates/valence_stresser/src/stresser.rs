@@ -1,20 +1,32 @@
+use std::future;
 use std::io::{self, ErrorKind};
 use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::bail;
 use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
+use tokio::time::{self, Interval};
 use valence_protocol::packets::c2s::handshake::Handshake;
 use valence_protocol::packets::c2s::login::LoginStart;
-use valence_protocol::packets::c2s::play::{ConfirmTeleport, KeepAliveC2s, SetPlayerPosition};
+use valence_protocol::packets::c2s::play::{
+    ChatMessage, ConfirmTeleport, KeepAliveC2s, SetPlayerPosition,
+};
 use valence_protocol::packets::{C2sHandshakePacket, S2cLoginPacket, S2cPlayPacket};
 use valence_protocol::types::HandshakeNextState;
 use valence_protocol::{PacketDecoder, PacketEncoder, Username, Uuid, VarInt, PROTOCOL_VERSION};
 
+use crate::movement::{Movement, MovementPattern, MOVES_PER_SECOND};
+
 pub struct SessionParams<'a> {
     pub socket_addr: SocketAddr,
     pub session_name: &'a str,
     pub read_buffer_size: usize,
+    pub movement: MovementPattern,
+    /// Movement speed in blocks per second.
+    pub speed: f64,
+    /// Interval between chat messages, or `None` to not chat.
+    pub chat_interval: Option<Duration>,
 }
 
 pub async fn make_session<'a>(params: &SessionParams<'a>) -> anyhow::Result<()> {
@@ -97,55 +109,103 @@ pub async fn make_session<'a>(params: &SessionParams<'a>) -> anyhow::Result<()>
 
     println!("{sess_name} logined");
 
+    let mut movement = Movement::new(params.movement, params.speed);
+    let mut move_interval = time::interval(Duration::from_secs(1) / MOVES_PER_SECOND);
+    let mut chat_interval = params.chat_interval.map(time::interval);
+    let mut chat_count: u64 = 0;
+
     loop {
-        while !dec.has_next_packet()? {
-            dec.reserve(rb_size);
+        tokio::select! {
+            readable = conn.readable() => {
+                readable?;
 
-            let mut read_buf = dec.take_capacity();
+                dec.reserve(rb_size);
 
-            conn.readable().await?;
+                let mut read_buf = dec.take_capacity();
 
-            match conn.try_read_buf(&mut read_buf) {
-                Ok(0) => return Err(io::Error::from(ErrorKind::UnexpectedEof).into()),
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
-                Err(e) => return Err(e.into()),
-                Ok(_) => (),
-            };
+                match conn.try_read_buf(&mut read_buf) {
+                    Ok(0) => return Err(io::Error::from(ErrorKind::UnexpectedEof).into()),
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                    Err(e) => return Err(e.into()),
+                    Ok(_) => (),
+                };
 
-            dec.queue_bytes(read_buf);
-        }
+                dec.queue_bytes(read_buf);
 
-        match dec.try_next_packet::<S2cPlayPacket>() {
-            Ok(None) => continue,
-            Ok(Some(pkt)) => match pkt {
-                S2cPlayPacket::KeepAliveS2c(p) => {
-                    enc.clear();
+                while let Some(pkt) = dec.try_next_packet::<S2cPlayPacket>()? {
+                    match pkt {
+                        S2cPlayPacket::KeepAliveS2c(p) => {
+                            enc.clear();
 
-                    _ = enc.append_packet(&KeepAliveC2s { id: p.id });
-                    conn.write_all(&enc.take()).await?;
+                            _ = enc.append_packet(&KeepAliveC2s { id: p.id });
+                            conn.write_all(&enc.take()).await?;
 
-                    println!("{sess_name} keep alive")
-                }
+                            println!("{sess_name} keep alive")
+                        }
 
-                S2cPlayPacket::SynchronizePlayerPosition(p) => {
-                    enc.clear();
+                        S2cPlayPacket::SynchronizePlayerPosition(p) => {
+                            enc.clear();
 
-                    _ = enc.append_packet(&ConfirmTeleport {
-                        teleport_id: p.teleport_id,
-                    });
+                            _ = enc.append_packet(&ConfirmTeleport {
+                                teleport_id: p.teleport_id,
+                            });
+
+                            _ = enc.append_packet(&SetPlayerPosition {
+                                position: p.position,
+                                on_ground: true,
+                            });
+
+                            conn.write_all(&enc.take()).await?;
+
+                            movement.teleport(p.position);
+
+                            println!("{sess_name} spawned")
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            _ = move_interval.tick() => {
+                if let Some(position) = movement.step() {
+                    enc.clear();
 
                     _ = enc.append_packet(&SetPlayerPosition {
-                        position: p.position,
+                        position,
                         on_ground: true,
                     });
 
                     conn.write_all(&enc.take()).await?;
-
-                    println!("{sess_name} spawned")
                 }
-                _ => (),
-            },
-            Err(err) => return Err(err),
+            }
+            _ = tick(&mut chat_interval) => {
+                chat_count += 1;
+
+                let message = format!("Hello from {sess_name} #{chat_count}");
+                let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+
+                enc.clear();
+
+                _ = enc.append_packet(&ChatMessage {
+                    message: &message,
+                    timestamp,
+                    salt: 0,
+                    signature: None,
+                    message_count: VarInt(0),
+                    acknowledgement: &[0; 3],
+                });
+
+                conn.write_all(&enc.take()).await?;
+            }
+        }
+    }
+}
+
+/// Waits for the next tick of the interval, or forever if there is none.
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
         }
+        None => future::pending().await,
     }
 }